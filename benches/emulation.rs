@@ -0,0 +1,111 @@
+//! Whole system throughput: instructions per second over a busy loop and frames per second over
+//! a screen full of background, window and sprites, in each accuracy profile. Instructions are
+//! also measured with the block cache.
+//!
+//! The ROMs are built here, so the benchmarks run without test ROMs.
+//! `cargo bench --bench emulation`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use gameboy_emulator::{
+    gameboy::{test_rom, AccuracyProfile},
+    GameBoy,
+};
+
+/// Instructions per iteration of the CPU benchmark
+const INSTRUCTIONS: u64 = 10_000;
+
+const PROFILES: [(&str, AccuracyProfile); 3] = [
+    ("fast", AccuracyProfile::Fast),
+    ("balanced", AccuracyProfile::Balanced),
+    ("cycle_accurate", AccuracyProfile::CycleAccurate),
+];
+
+/// Arithmetic, a WRAM write and a jump per loop, like a game's main loop
+fn busy_loop() -> GameBoy {
+    let mut gameboy = GameBoy::new();
+    gameboy
+        .load_rom(test_rom(&[
+            0x21, 0x00, 0xC0, // LD HL, 0xC000
+            0x3C, // loop: INC A
+            0x77, // LD (HL), A
+            0x2C, // INC L
+            0x80, // ADD A, B
+            0x05, // DEC B
+            0x18, 0xF9, // JR loop
+        ]))
+        .unwrap();
+    gameboy
+}
+
+/// Every tile, map entry and sprite in use, with the window over the bottom right quarter
+fn busy_screen() -> GameBoy {
+    let mut gameboy = GameBoy::new();
+    // JR -2
+    gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+    let memory = gameboy.memory_mut();
+    memory.write_byte(0xFF40, 0x00);
+    for address in 0x8000..0x9000u16 {
+        memory.write_byte(address, (address as u8).wrapping_mul(37));
+    }
+    for index in 0..0x800u16 {
+        memory.write_byte(0x9800 + index, index as u8);
+    }
+    for sprite in 0..40u16 {
+        let oam = 0xFE00 + sprite * 4;
+        memory.write_byte(oam, 16 + (sprite as u8 % 9) * 16);
+        memory.write_byte(oam + 1, 8 + (sprite as u8) * 4);
+        memory.write_byte(oam + 2, sprite as u8 * 2);
+        memory.write_byte(oam + 3, (sprite as u8 & 1) << 4);
+    }
+    memory.write_byte(0xFF47, 0xE4); // BGP
+    memory.write_byte(0xFF48, 0xE4); // OBP0
+    memory.write_byte(0xFF49, 0x1B); // OBP1
+    memory.write_byte(0xFF4A, 72); // WY
+    memory.write_byte(0xFF4B, 87); // WX
+
+    // LCD, window at 0x9C00, background, 8x16 sprites and tile data at 0x8000 all on
+    memory.write_byte(0xFF40, 0xF7);
+    gameboy
+}
+
+fn instructions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instructions");
+    group.throughput(Throughput::Elements(INSTRUCTIONS));
+    for (name, profile) in PROFILES {
+        let mut gameboy = busy_loop();
+        gameboy.set_accuracy(profile);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                for _ in 0..INSTRUCTIONS {
+                    gameboy.step();
+                }
+            })
+        });
+    }
+    let mut gameboy = busy_loop();
+    gameboy.set_block_cache(true);
+    group.bench_function(BenchmarkId::from_parameter("block_cache"), |b| {
+        b.iter(|| {
+            for _ in 0..INSTRUCTIONS {
+                gameboy.step();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frames");
+    group.throughput(Throughput::Elements(1));
+    for (name, profile) in PROFILES {
+        let mut gameboy = busy_screen();
+        gameboy.set_accuracy(profile);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| gameboy.run_until_vblank())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, instructions, frames);
+criterion_main!(benches);
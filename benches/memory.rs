@@ -0,0 +1,53 @@
+//! Memory access throughput, every instruction goes through these paths several times.
+//!
+//! `cargo bench --bench memory`
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use gameboy_emulator::gameboy::{Memory, MemoryBus, TimedBus};
+
+/// WRAM bank 0 and the switchable bank
+const WRAM: std::ops::Range<u16> = 0xC000..0xE000;
+
+fn wram(c: &mut Criterion) {
+    let mut memory = Memory::new();
+    let mut group = c.benchmark_group("wram");
+    group.throughput(Throughput::Elements(WRAM.len() as u64));
+    group.bench_function("write", |b| {
+        b.iter(|| {
+            for address in WRAM {
+                memory.write_byte(black_box(address), address as u8);
+            }
+        })
+    });
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let mut sum = 0u8;
+            for address in WRAM {
+                sum = sum.wrapping_add(memory.read_byte(black_box(address)));
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+/// Reads through the bus the CPU uses, which also advances the timer and DMA
+fn timed_bus(c: &mut Criterion) {
+    let mut memory = Memory::new();
+    let mut group = c.benchmark_group("timed_bus");
+    group.throughput(Throughput::Elements(WRAM.len() as u64));
+    group.bench_function("read", |b| {
+        b.iter(|| {
+            let mut bus = TimedBus::new(&mut memory);
+            let mut sum = 0u8;
+            for address in WRAM {
+                sum = sum.wrapping_add(bus.read_byte(black_box(address)));
+            }
+            sum
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, wram, timed_bus);
+criterion_main!(benches);
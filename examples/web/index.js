@@ -0,0 +1,56 @@
+// Build the package first, from the repository root:
+// wasm-pack build --target web --out-dir examples/web/pkg -- --features wasm
+import init, { Emulator } from "./pkg/gameboy_emulator.js";
+
+const KEYS = {
+    ArrowRight: "right",
+    ArrowLeft: "left",
+    ArrowUp: "up",
+    ArrowDown: "down",
+    x: "a",
+    z: "b",
+    Backspace: "select",
+    Enter: "start",
+};
+
+await init();
+
+const emulator = new Emulator();
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+let running = false;
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+    const rom = new Uint8Array(await event.target.files[0].arrayBuffer());
+    try {
+        emulator.load_rom(rom);
+    } catch (error) {
+        alert(error);
+        return;
+    }
+    if (!running) {
+        running = true;
+        requestAnimationFrame(frame);
+    }
+});
+
+function frame() {
+    emulator.run_frame();
+    const pixels = new Uint8ClampedArray(emulator.framebuffer());
+    context.putImageData(new ImageData(pixels, emulator.width, emulator.height), 0, 0);
+    // no audio output in this demo, drop the samples so they don't pile up
+    emulator.audio_samples();
+    requestAnimationFrame(frame);
+}
+
+document.addEventListener("keydown", (event) => {
+    if (event.key in KEYS && emulator.key_down(KEYS[event.key])) {
+        event.preventDefault();
+    }
+});
+
+document.addEventListener("keyup", (event) => {
+    if (event.key in KEYS) {
+        emulator.key_up(KEYS[event.key]);
+    }
+});
@@ -0,0 +1,112 @@
+//! Audio output through cpal, shared by the graphical frontends.
+//!
+//! The frontend queues the samples of every emulated frame, they are resampled to the device's
+//! rate (48 kHz where supported) and handed to the device's callback through an `AudioRing`
+//! holding at most `LATENCY` of audio.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    FromSample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig,
+};
+use gameboy_emulator::{
+    gameboy::SAMPLE_RATE,
+    utils::{AudioRing, Resampler},
+};
+
+/// The preferred device sample rate
+const DEVICE_RATE: u32 = 48000;
+
+/// The most audio queued for the device, more is dropped to keep the sound in sync
+const LATENCY: Duration = Duration::from_millis(100);
+
+pub struct AudioOutput {
+    _stream: Stream, // playback stops when the stream is dropped
+    ring: Arc<Mutex<AudioRing>>,
+    resampler: Resampler,
+    resampled: Vec<i16>,
+}
+
+impl AudioOutput {
+    /// Start playing on the default output device
+    pub fn open() -> Result<AudioOutput, String> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No audio output device")?;
+        let supported = device
+            .supported_output_configs()
+            .map_err(|error| error.to_string())?
+            .filter(|config| {
+                matches!(
+                    config.sample_format(),
+                    SampleFormat::F32 | SampleFormat::I16
+                ) && (config.min_sample_rate()..=config.max_sample_rate())
+                    .contains(&SampleRate(DEVICE_RATE))
+            })
+            .max_by_key(|config| config.sample_format() == SampleFormat::F32)
+            .map(|config| config.with_sample_rate(SampleRate(DEVICE_RATE)));
+        let supported = match supported {
+            Some(config) => config,
+            None => device
+                .default_output_config()
+                .map_err(|error| error.to_string())?,
+        };
+
+        let config = supported.config();
+        let rate = config.sample_rate.0;
+        let capacity = (rate as f32 * LATENCY.as_secs_f32()) as usize * 2;
+        let ring = Arc::new(Mutex::new(AudioRing::new(capacity)));
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, ring.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, ring.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, ring.clone()),
+            format => return Err(format!("Unsupported sample format {format}")),
+        }?;
+        stream.play().map_err(|error| error.to_string())?;
+
+        Ok(AudioOutput {
+            _stream: stream,
+            ring,
+            resampler: Resampler::new(SAMPLE_RATE, rate),
+            resampled: Vec::new(),
+        })
+    }
+
+    /// Queue interleaved stereo samples at `SAMPLE_RATE`, as drained from the Game Boy
+    pub fn queue(&mut self, samples: &[i16]) {
+        self.resampled.clear();
+        self.resampler.process(samples, &mut self.resampled);
+        self.ring.lock().unwrap().push(&self.resampled);
+    }
+}
+
+/// A stream playing the ring's stereo frames on every channel pair, mono devices get the left
+/// channel
+fn build_stream<T: SizedSample + FromSample<i16>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    ring: Arc<Mutex<AudioRing>>,
+) -> Result<Stream, String> {
+    let channels = usize::from(config.channels);
+    let mut frames = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                frames.resize(data.len() / channels * 2, 0);
+                ring.lock().unwrap().fill(&mut frames);
+                for (output, frame) in data.chunks_mut(channels).zip(frames.chunks(2)) {
+                    for (channel, sample) in output.iter_mut().enumerate() {
+                        *sample = T::from_sample(frame[channel % 2]);
+                    }
+                }
+            },
+            |error| tracing::warn!("Audio stream error: {error}"),
+            None,
+        )
+        .map_err(|error| error.to_string())
+}
@@ -0,0 +1,471 @@
+//! Command line debugger, reads commands from stdin until `quit` or end of input.
+//!
+//! Addresses are hexadecimal (`C000`, `$C000` or `0xC000`) or labels from the loaded symbols,
+//! counts are decimal. Conditions and watch expressions use the syntax of `Expression`, where
+//! hexadecimal needs its prefix.
+
+use std::io::{self, BufRead, Write};
+
+use gameboy_emulator::gameboy::{
+    Debugger, Expression, Heatmap, IllegalOpcodePolicy, MemoryBus, Profiler, Register16,
+    StopReason, Symbols, WatchKind, T_CYCLES_PER_FRAME,
+};
+use gameboy_emulator::GameBoy;
+
+use super::{print_profile, write_core_dump, write_heatmap, PROFILE_LENGTH};
+
+/// How long `continue` and friends run before giving control back, 10 seconds of emulated time
+const RUN_LIMIT: u64 = T_CYCLES_PER_FRAME as u64 * 600;
+
+const HELP: &str = "\
+step [n]            execute n instructions (s)
+next                step over CALL and RST (n)
+finish              run until the current function returns
+continue            run until a breakpoint or watchpoint (c)
+break <addr> [if <expr>]
+                    add a breakpoint, stopping only when expr is true (b)
+delete <addr>       remove a breakpoint or watchpoint (d)
+watch <addr> [r|w]  stop on reads, writes or both (w)
+display <expr>      show expr after every stop
+undisplay <n>       stop showing the nth display
+print <expr>        evaluate expr once (p)
+info                list breakpoints, watchpoints and displays
+regs                show the registers (r)
+mem <addr> [n]      dump n bytes of memory (x)
+dis [addr] [n]      disassemble n instructions (l)
+history [n]         the last n instructions run (hist)
+profile [on|off|reset|n]
+                    count cycles per function, or show the n hottest
+heatmap [on|off|reset|save]
+                    count accesses per address, show the busiest pages or save it
+dump                write a core dump to the working directory
+quit                exit (q)";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Step(usize),
+    Next,
+    Finish,
+    Continue,
+    Break(u16, Option<Expression>),
+    Delete(u16),
+    Watch(u16, WatchKind),
+    Display(Expression),
+    Undisplay(usize),
+    Print(Expression),
+    Info,
+    Registers,
+    Memory(u16, usize),
+    Disassemble(Option<u16>, usize),
+    History(usize),
+    Profile(ProfileCommand),
+    Heatmap(HeatmapCommand),
+    Dump,
+    Help,
+    Quit,
+}
+
+/// A label from `symbols`, or a hexadecimal address
+#[derive(Debug, PartialEq)]
+enum ProfileCommand {
+    On,
+    Off,
+    Reset,
+    Report(usize),
+}
+
+#[derive(Debug, PartialEq)]
+enum HeatmapCommand {
+    On,
+    Off,
+    Reset,
+    Save,
+    Show,
+}
+
+fn parse_address(text: &str, symbols: Option<&Symbols>) -> Result<u16, String> {
+    if let Some((_, address)) = symbols.and_then(|symbols| symbols.address(text)) {
+        return Ok(address);
+    }
+    let digits = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("invalid address or label: {text}"))
+}
+
+fn parse_count(text: Option<&str>, default: usize) -> Result<usize, String> {
+    match text {
+        Some(text) => text.parse().map_err(|_| format!("invalid count: {text}")),
+        None => Ok(default),
+    }
+}
+
+/// The rest of the line as an expression
+fn parse_expression<'a>(words: impl Iterator<Item = &'a str>) -> Result<Expression, String> {
+    let text: Vec<&str> = words.collect();
+    Expression::parse(&text.join(" ")).map_err(|error| error.to_string())
+}
+
+fn parse_command(line: &str, symbols: Option<&Symbols>) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or("step");
+    let parse_address = |text: &str| parse_address(text, symbols);
+    let address = |word: Option<&str>| {
+        word.ok_or_else(|| format!("{name} needs an address"))
+            .and_then(parse_address)
+    };
+
+    let command = match name {
+        "step" | "s" => Command::Step(parse_count(words.next(), 1)?),
+        "next" | "n" => Command::Next,
+        "finish" => Command::Finish,
+        "continue" | "c" => Command::Continue,
+        "break" | "b" => {
+            let address = address(words.next())?;
+            let condition = match words.next() {
+                Some("if") => Some(parse_expression(words)?),
+                Some(word) => return Err(format!("expected if, found {word}")),
+                None => None,
+            };
+            Command::Break(address, condition)
+        }
+        "delete" | "d" => Command::Delete(address(words.next())?),
+        "watch" | "w" => {
+            let address = address(words.next())?;
+            let kind = match words.next() {
+                Some("r") => WatchKind::Read,
+                Some("w") => WatchKind::Write,
+                Some("rw") | None => WatchKind::ReadWrite,
+                Some(kind) => return Err(format!("invalid watch kind: {kind}")),
+            };
+            Command::Watch(address, kind)
+        }
+        "display" => Command::Display(parse_expression(words)?),
+        "undisplay" => Command::Undisplay(parse_count(words.next(), 1)?),
+        "print" | "p" => Command::Print(parse_expression(words)?),
+        "info" => Command::Info,
+        "regs" | "r" => Command::Registers,
+        "mem" | "x" => {
+            let address = address(words.next())?;
+            Command::Memory(address, parse_count(words.next(), 16)?)
+        }
+        "dis" | "l" => {
+            let address = words.next().map(parse_address).transpose()?;
+            Command::Disassemble(address, parse_count(words.next(), 8)?)
+        }
+        "history" | "hist" => Command::History(parse_count(words.next(), 16)?),
+        "profile" => Command::Profile(match words.next() {
+            Some("on") => ProfileCommand::On,
+            Some("off") => ProfileCommand::Off,
+            Some("reset") => ProfileCommand::Reset,
+            count => ProfileCommand::Report(parse_count(count, PROFILE_LENGTH)?),
+        }),
+        "heatmap" => Command::Heatmap(match words.next() {
+            Some("on") => HeatmapCommand::On,
+            Some("off") => HeatmapCommand::Off,
+            Some("reset") => HeatmapCommand::Reset,
+            Some("save") => HeatmapCommand::Save,
+            None => HeatmapCommand::Show,
+            Some(word) => return Err(format!("invalid heatmap command: {word}")),
+        }),
+        "dump" => Command::Dump,
+        "help" | "h" | "?" => Command::Help,
+        "quit" | "q" => Command::Quit,
+        _ => return Err(format!("unknown command: {name}, try help")),
+    };
+    Ok(command)
+}
+
+fn print_registers(gameboy: &GameBoy) {
+    let registers = &gameboy.cpu().registers;
+    println!(
+        "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}",
+        registers.read_16(Register16::AF),
+        registers.read_16(Register16::BC),
+        registers.read_16(Register16::DE),
+        registers.read_16(Register16::HL),
+        registers.read_16(Register16::SP),
+        registers.pc,
+    );
+}
+
+fn print_memory(gameboy: &GameBoy, address: u16, count: usize) {
+    let memory = gameboy.memory();
+    let addresses: Vec<u16> = (0..count)
+        .map_while(|offset| address.checked_add(offset as u16))
+        .collect();
+    for line in addresses.chunks(16) {
+        let bytes: Vec<String> = line
+            .iter()
+            .map(|&address| format!("{:02X}", memory.peek_byte(address)))
+            .collect();
+        println!("{:04X}: {}", line[0], bytes.join(" "));
+    }
+}
+
+/// `0150`, or `0150 <Main+3>` with a label at or before it
+fn describe(gameboy: &GameBoy, address: u16) -> String {
+    let nearest = gameboy
+        .symbols()
+        .and_then(|symbols| symbols.nearest(gameboy.memory(), address));
+    match nearest {
+        Some((label, 0)) => format!("{address:04X} <{label}>"),
+        Some((label, offset)) => format!("{address:04X} <{label}+{offset}>"),
+        None => format!("{address:04X}"),
+    }
+}
+
+fn print_disassembly(gameboy: &GameBoy, address: u16, count: usize) {
+    let disassembler = gameboy.disassembler();
+    for instruction in disassembler.range(address, 0xFFFF).take(count) {
+        println!("{instruction}");
+    }
+}
+
+fn print_value(expression: &Expression, value: i64) {
+    println!("{expression} = {value} ({value:#X})");
+}
+
+fn report(debugger: &mut Debugger, reason: StopReason) {
+    match reason {
+        StopReason::Step | StopReason::Returned => {}
+        StopReason::Breakpoint(address) => {
+            println!("Breakpoint at {}", describe(debugger.gameboy(), address));
+        }
+        StopReason::Watchpoint(access) => println!("Watchpoint: {access:?}"),
+        StopReason::CycleLimit => println!("Still running, stopped after {RUN_LIMIT} cycles"),
+        StopReason::IllegalOpcode(_) => {
+            // cleared so the next command retries the opcode, or runs wherever PC was moved to
+            if let Some(error) = debugger.gameboy_mut().take_error() {
+                println!("{error}");
+            }
+        }
+    }
+    for (index, (expression, value)) in debugger.watch_expressions().enumerate() {
+        print!("{}: ", index + 1);
+        print_value(expression, value);
+    }
+    let gameboy = debugger.gameboy();
+    print_disassembly(gameboy, gameboy.cpu().registers.pc, 1);
+}
+
+/// Run the debugger REPL until the user quits
+pub fn run(gameboy: GameBoy) -> io::Result<()> {
+    let mut debugger = Debugger::new(gameboy);
+    debugger
+        .gameboy_mut()
+        .set_illegal_opcode_policy(IllegalOpcodePolicy::Trap);
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+
+    report(&mut debugger, StopReason::Step);
+    loop {
+        print!("(gb) ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+
+        let command = match parse_command(&line, debugger.gameboy().symbols()) {
+            Ok(command) => command,
+            Err(error) => {
+                println!("{error}");
+                continue;
+            }
+        };
+
+        match command {
+            Command::Step(count) => {
+                let mut reason = StopReason::Step;
+                for _ in 0..count {
+                    reason = debugger.step();
+                    if reason != StopReason::Step {
+                        break;
+                    }
+                }
+                report(&mut debugger, reason);
+            }
+            Command::Next => {
+                let reason = debugger.step_over(RUN_LIMIT);
+                report(&mut debugger, reason);
+            }
+            Command::Finish => {
+                let reason = debugger.run_to_return(RUN_LIMIT);
+                report(&mut debugger, reason);
+            }
+            Command::Continue => {
+                let reason = debugger.continue_execution(RUN_LIMIT);
+                report(&mut debugger, reason);
+            }
+            Command::Break(address, None) => {
+                debugger.add_breakpoint(address);
+            }
+            Command::Break(address, Some(condition)) => {
+                debugger.add_conditional_breakpoint(address, condition);
+            }
+            Command::Delete(address) => {
+                if !debugger.remove_breakpoint(address) && !debugger.remove_watchpoint(address) {
+                    println!("Nothing set at {address:04X}");
+                }
+            }
+            Command::Watch(address, kind) => debugger.add_watchpoint(address, kind),
+            Command::Display(expression) => {
+                print_value(&expression, expression.evaluate(debugger.gameboy()));
+                debugger.add_watch_expression(expression);
+            }
+            Command::Undisplay(number) => {
+                if number == 0 || debugger.remove_watch_expression(number - 1).is_none() {
+                    println!("No display {number}");
+                }
+            }
+            Command::Print(expression) => {
+                print_value(&expression, expression.evaluate(debugger.gameboy()));
+            }
+            Command::Info => {
+                let gameboy = debugger.gameboy();
+                for address in debugger.breakpoints() {
+                    let location = describe(gameboy, address);
+                    match debugger.breakpoint_condition(address) {
+                        Some(condition) => println!("break {location} if {condition}"),
+                        None => println!("break {location}"),
+                    }
+                }
+                for (address, kind) in debugger.watchpoints() {
+                    println!("watch {} {kind:?}", describe(gameboy, address));
+                }
+                for (index, (expression, _)) in debugger.watch_expressions().enumerate() {
+                    println!("display {}: {expression}", index + 1);
+                }
+            }
+            Command::Registers => print_registers(debugger.gameboy()),
+            Command::Memory(address, count) => print_memory(debugger.gameboy(), address, count),
+            Command::Disassemble(address, count) => {
+                let gameboy = debugger.gameboy();
+                let address = address.unwrap_or(gameboy.cpu().registers.pc);
+                print_disassembly(gameboy, address, count);
+            }
+            Command::History(count) => {
+                for instruction in debugger.history(count) {
+                    println!("{instruction}");
+                }
+            }
+            Command::Profile(ProfileCommand::On) => {
+                if debugger.gameboy().profiler().is_none() {
+                    debugger.gameboy_mut().set_profiler(Some(Profiler::new()));
+                }
+            }
+            Command::Profile(ProfileCommand::Off) => debugger.gameboy_mut().set_profiler(None),
+            Command::Profile(ProfileCommand::Reset) => {
+                if let Some(profiler) = debugger.gameboy_mut().profiler_mut() {
+                    profiler.clear();
+                }
+            }
+            Command::Profile(ProfileCommand::Report(count)) => {
+                print_profile(debugger.gameboy(), count);
+            }
+            Command::Heatmap(HeatmapCommand::On) => {
+                if debugger.gameboy().heatmap().is_none() {
+                    debugger.gameboy_mut().set_heatmap(Some(Heatmap::new()));
+                }
+            }
+            Command::Heatmap(HeatmapCommand::Off) => debugger.gameboy_mut().set_heatmap(None),
+            Command::Heatmap(HeatmapCommand::Reset) => {
+                if let Some(heatmap) = debugger.gameboy_mut().heatmap_mut() {
+                    heatmap.clear();
+                }
+            }
+            Command::Heatmap(HeatmapCommand::Save) => write_heatmap(debugger.gameboy()),
+            Command::Heatmap(HeatmapCommand::Show) => match debugger.gameboy().heatmap() {
+                Some(heatmap) => {
+                    for page in heatmap.busiest_pages(16) {
+                        println!("{page}");
+                    }
+                }
+                None => println!("The heatmap is off"),
+            },
+            Command::Dump => write_core_dump(debugger.gameboy(), "requested"),
+            Command::Help => println!("{HELP}"),
+            Command::Quit => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("C000", None), Ok(0xC000));
+        assert_eq!(parse_address("$ff44", None), Ok(0xFF44));
+        assert_eq!(parse_address("0x0100", None), Ok(0x0100));
+        assert!(parse_address("10000", None).is_err());
+
+        let symbols = Symbols::parse("00:0150 Main\n00:c000 Add").unwrap();
+        assert_eq!(parse_address("Main", Some(&symbols)), Ok(0x0150));
+        assert_eq!(parse_address("Add", Some(&symbols)), Ok(0xC000));
+        assert_eq!(parse_address("ADD", Some(&symbols)), Ok(0x0ADD));
+        assert_eq!(
+            parse_command("b Main", Some(&symbols)),
+            Ok(Command::Break(0x0150, None))
+        );
+        assert!(parse_address("Missing", Some(&symbols)).is_err());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("", None), Ok(Command::Step(1)));
+        assert_eq!(parse_command("s 10", None), Ok(Command::Step(10)));
+        assert_eq!(
+            parse_command("b $0150", None),
+            Ok(Command::Break(0x0150, None))
+        );
+        assert_eq!(
+            parse_command("break 4000 if A == 0x3E && [0xFF44] > 0x90", None),
+            Ok(Command::Break(
+                0x4000,
+                Some(Expression::parse("A == 0x3E && [0xFF44] > 0x90").unwrap())
+            ))
+        );
+        assert_eq!(
+            parse_command("display [$C000]", None),
+            Ok(Command::Display(Expression::parse("[$C000]").unwrap()))
+        );
+        assert_eq!(
+            parse_command("undisplay 2", None),
+            Ok(Command::Undisplay(2))
+        );
+        assert_eq!(
+            parse_command("watch FF40 w", None),
+            Ok(Command::Watch(0xFF40, WatchKind::Write))
+        );
+        assert_eq!(
+            parse_command("x C000", None),
+            Ok(Command::Memory(0xC000, 16))
+        );
+        assert_eq!(
+            parse_command("dis", None),
+            Ok(Command::Disassemble(None, 8))
+        );
+        assert_eq!(parse_command("hist 4", None), Ok(Command::History(4)));
+        assert_eq!(parse_command("dump", None), Ok(Command::Dump));
+        assert_eq!(
+            parse_command("profile", None),
+            Ok(Command::Profile(ProfileCommand::Report(PROFILE_LENGTH)))
+        );
+        assert_eq!(
+            parse_command("profile reset", None),
+            Ok(Command::Profile(ProfileCommand::Reset))
+        );
+        assert_eq!(
+            parse_command("heatmap save", None),
+            Ok(Command::Heatmap(HeatmapCommand::Save))
+        );
+        assert!(parse_command("heatmap png", None).is_err());
+        assert!(parse_command("break", None).is_err());
+        assert!(parse_command("break 4000 when A", None).is_err());
+        assert!(parse_command("print A +", None).is_err());
+        assert!(parse_command("jump", None).is_err());
+    }
+}
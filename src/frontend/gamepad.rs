@@ -0,0 +1,35 @@
+//! Gamepads through gilrs, for the SDL frontend.
+//!
+//! Buttons are reported by their gilrs name (`South`, `DPadUp`, `Start`...), which is how the
+//! `InputMap` and the game configs refer to them. Any connected gamepad drives the joypad.
+
+use gameboy_emulator::gameboy::Input;
+use gilrs::{EventType, Gilrs};
+
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    pub fn open() -> Result<Gamepads, String> {
+        let gilrs = Gilrs::new().map_err(|error| error.to_string())?;
+        Ok(Gamepads { gilrs })
+    }
+
+    /// The buttons that went down (true) or up (false) since the last call
+    pub fn poll(&mut self) -> Vec<(Input, bool)> {
+        let mut changes = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    changes.push((Input::Pad(format!("{button:?}")), true))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    changes.push((Input::Pad(format!("{button:?}")), false))
+                }
+                _ => {}
+            }
+        }
+        changes
+    }
+}
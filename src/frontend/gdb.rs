@@ -0,0 +1,418 @@
+//! A GDB remote serial protocol stub, so gdb or lldb can debug the running game over TCP.
+//!
+//! The SM83 has no target description of its own, registers are sent in the order of the
+//! Z80 target (AF, BC, DE, HL, SP, PC) as 16-bit little-endian values, so connecting with
+//! a Z80 capable gdb and `target remote localhost:<port>` works.
+//! Breakpoints use `Z0`/`Z1`, watchpoints `Z2` (write), `Z3` (read) and `Z4` (access).
+
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use gameboy_emulator::gameboy::{
+    Debugger, MemoryAccess, MemoryBus, Register16, StopReason, WatchKind, T_CYCLES_PER_FRAME,
+};
+use gameboy_emulator::GameBoy;
+
+pub const DEFAULT_PORT: u16 = 1234;
+
+/// Registers in the order gdb numbers them
+const REGISTERS: [Register16; 6] = [
+    Register16::AF,
+    Register16::BC,
+    Register16::DE,
+    Register16::HL,
+    Register16::SP,
+    Register16::PC,
+];
+
+/// SIGTRAP, reported for breakpoints and steps
+const SIGTRAP: u8 = 5;
+/// SIGINT, reported when gdb interrupts a running target
+const SIGINT: u8 = 2;
+/// Sent by gdb outside a packet to interrupt the target
+const INTERRUPT: u8 = 0x03;
+
+/// What the stub does after handling a packet
+#[derive(Debug, PartialEq)]
+enum Action {
+    Reply(String),
+    Continue,
+    Step,
+    Detach,
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, |sum, byte| sum.wrapping_add(byte))
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn decode_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_register(value: u16) -> String {
+    format!("{:02x}{:02x}", value & 0xFF, value >> 8)
+}
+
+fn decode_register(text: &str) -> Option<u16> {
+    match decode_hex_bytes(text)?.as_slice() {
+        [lo, hi] => Some(u16::from(*lo) | (u16::from(*hi) << 8)),
+        _ => None,
+    }
+}
+
+/// `addr,length`
+fn parse_range(text: &str) -> Option<(u16, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((parse_hex(address)? as u16, parse_hex(length)? as usize))
+}
+
+fn error() -> Action {
+    Action::Reply("E01".to_string())
+}
+
+fn ok() -> Action {
+    Action::Reply("OK".to_string())
+}
+
+/// Handle one packet, without its `$` and checksum
+fn handle_packet(debugger: &mut Debugger, packet: &str) -> Action {
+    let command = packet.get(..1).unwrap_or_default();
+    let arguments = packet.get(1..).unwrap_or_default();
+    match command {
+        "?" => Action::Reply(format!("S{SIGTRAP:02x}")),
+        "g" => {
+            let registers = &debugger.gameboy().cpu().registers;
+            let values = REGISTERS.map(|register| encode_register(registers.read_16(register)));
+            Action::Reply(values.concat())
+        }
+        "G" => {
+            let values: Option<Vec<u16>> = (0..REGISTERS.len())
+                .map(|index| decode_register(arguments.get(index * 4..index * 4 + 4)?))
+                .collect();
+            let Some(values) = values else {
+                return error();
+            };
+            let registers = &mut debugger.gameboy_mut().cpu_mut().registers;
+            for (register, value) in REGISTERS.into_iter().zip(values) {
+                registers.write_16(register, value);
+            }
+            ok()
+        }
+        "p" => match parse_hex(arguments).and_then(|index| REGISTERS.get(index as usize)) {
+            Some(&register) => {
+                let value = debugger.gameboy().cpu().registers.read_16(register);
+                Action::Reply(encode_register(value))
+            }
+            None => error(),
+        },
+        "P" => {
+            let register = arguments.split_once('=').and_then(|(index, value)| {
+                let register = REGISTERS.get(parse_hex(index)? as usize)?;
+                Some((*register, decode_register(value)?))
+            });
+            let Some((register, value)) = register else {
+                return error();
+            };
+            debugger
+                .gameboy_mut()
+                .cpu_mut()
+                .registers
+                .write_16(register, value);
+            ok()
+        }
+        "m" => {
+            let Some((address, length)) = parse_range(arguments) else {
+                return error();
+            };
+            let memory = debugger.gameboy().memory();
+            let bytes: String = (0..length)
+                .map(|offset| memory.peek_byte(address.wrapping_add(offset as u16)))
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            Action::Reply(bytes)
+        }
+        "M" => {
+            let write = arguments.split_once(':').and_then(|(range, data)| {
+                let (address, length) = parse_range(range)?;
+                let bytes = decode_hex_bytes(data)?;
+                (bytes.len() == length).then_some((address, bytes))
+            });
+            let Some((address, bytes)) = write else {
+                return error();
+            };
+            let memory = debugger.gameboy_mut().memory_mut();
+            for (offset, byte) in bytes.into_iter().enumerate() {
+                memory.poke_byte(address.wrapping_add(offset as u16), byte);
+            }
+            ok()
+        }
+        "Z" | "z" => {
+            let mut fields = arguments.split(',');
+            let (Some(kind), Some(address)) = (fields.next(), fields.next().and_then(parse_hex))
+            else {
+                return error();
+            };
+            let address = address as u16;
+            let insert = command == "Z";
+            let watch_kind = match kind {
+                "0" | "1" => {
+                    if insert {
+                        debugger.add_breakpoint(address);
+                    } else {
+                        debugger.remove_breakpoint(address);
+                    }
+                    return ok();
+                }
+                "2" => WatchKind::Write,
+                "3" => WatchKind::Read,
+                "4" => WatchKind::ReadWrite,
+                _ => return Action::Reply(String::new()),
+            };
+            if insert {
+                debugger.add_watchpoint(address, watch_kind);
+            } else {
+                debugger.remove_watchpoint(address);
+            }
+            ok()
+        }
+        "c" | "s" => {
+            if let Some(address) = parse_hex(arguments) {
+                debugger.gameboy_mut().cpu_mut().registers.pc = address as u16;
+            }
+            if command == "c" {
+                Action::Continue
+            } else {
+                Action::Step
+            }
+        }
+        "H" => ok(),
+        "D" | "k" => Action::Detach,
+        "q" => match arguments {
+            args if args.starts_with("Supported") => {
+                Action::Reply("PacketSize=4000;swbreak+;hwbreak+".to_string())
+            }
+            "Attached" => Action::Reply("1".to_string()),
+            "C" => Action::Reply("QC1".to_string()),
+            "fThreadInfo" => Action::Reply("m1".to_string()),
+            "sThreadInfo" => Action::Reply("l".to_string()),
+            _ => Action::Reply(String::new()),
+        },
+        // anything else is unsupported, which gdb expects as an empty reply
+        _ => Action::Reply(String::new()),
+    }
+}
+
+/// The stop reply for why the debugger stopped
+fn stop_reply(reason: StopReason) -> String {
+    match reason {
+        StopReason::Watchpoint(MemoryAccess::Write { address, .. }) => {
+            format!("T{SIGTRAP:02x}watch:{address:x};")
+        }
+        StopReason::Watchpoint(MemoryAccess::Read { address, .. }) => {
+            format!("T{SIGTRAP:02x}rwatch:{address:x};")
+        }
+        StopReason::Breakpoint(_) => format!("T{SIGTRAP:02x}swbreak:;"),
+        _ => format!("S{SIGTRAP:02x}"),
+    }
+}
+
+struct Connection {
+    reader: BufReader<TcpStream>,
+}
+
+impl Connection {
+    /// Read the next packet, acknowledging it. Returns `None` when gdb disconnects
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            // acknowledgements and stray interrupts between packets are ignored
+            if byte[0] != b'$' {
+                continue;
+            }
+
+            let mut data = Vec::new();
+            if self.reader.read_until(b'#', &mut data)? == 0 {
+                return Ok(None);
+            }
+            data.pop();
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum)?;
+
+            let data = String::from_utf8_lossy(&data).into_owned();
+            let valid = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok())
+                == Some(checksum(&data));
+            let stream = self.reader.get_mut();
+            if valid {
+                stream.write_all(b"+")?;
+                return Ok(Some(data));
+            }
+            stream.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data));
+        self.reader.get_mut().write_all(packet.as_bytes())
+    }
+
+    /// Whether gdb sent an interrupt while the target was running
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.reader.get_ref().set_nonblocking(true)?;
+        let result = match self.reader.fill_buf() {
+            Ok(buffer) => Ok(buffer.first() == Some(&INTERRUPT)),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error),
+        };
+        self.reader.get_ref().set_nonblocking(false)?;
+        if result.as_ref().is_ok_and(|&interrupted| interrupted) {
+            self.reader.consume(1);
+        }
+        result
+    }
+}
+
+/// Wait for gdb to connect on `port` and serve it until it detaches
+pub fn run(gameboy: GameBoy, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    println!("Waiting for gdb on port {port}");
+    let (stream, address) = listener.accept()?;
+    println!("gdb connected from {address}");
+
+    let mut connection = Connection {
+        reader: BufReader::new(stream),
+    };
+    let mut debugger = Debugger::new(gameboy);
+
+    while let Some(packet) = connection.read_packet()? {
+        match handle_packet(&mut debugger, &packet) {
+            Action::Reply(reply) => connection.send_packet(&reply)?,
+            Action::Step => {
+                let reason = debugger.step();
+                connection.send_packet(&stop_reply(reason))?;
+            }
+            Action::Continue => {
+                // run a frame at a time so an interrupt from gdb is noticed
+                let reply = loop {
+                    match debugger.continue_execution(u64::from(T_CYCLES_PER_FRAME)) {
+                        StopReason::CycleLimit if connection.interrupted()? => {
+                            break format!("S{SIGINT:02x}");
+                        }
+                        StopReason::CycleLimit => {}
+                        reason => break stop_reply(reason),
+                    }
+                };
+                connection.send_packet(&reply)?;
+            }
+            Action::Detach => {
+                connection.send_packet("OK")?;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gameboy_emulator::gameboy::test_rom;
+
+    fn make_debugger() -> Debugger {
+        let mut gameboy = GameBoy::new();
+        // NOP; JR -2
+        gameboy.load_rom(test_rom(&[0x00, 0x18, 0xFE])).unwrap();
+        Debugger::new(gameboy)
+    }
+
+    fn reply(debugger: &mut Debugger, packet: &str) -> String {
+        match handle_packet(debugger, packet) {
+            Action::Reply(reply) => reply,
+            action => panic!("expected a reply, got {action:?}"),
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum("OK"), 0x9A);
+        assert_eq!(checksum(""), 0);
+    }
+
+    #[test]
+    fn test_read_registers() {
+        let mut debugger = make_debugger();
+
+        assert_eq!(reply(&mut debugger, "g"), "b0011300d8004d01feff0001");
+        assert_eq!(reply(&mut debugger, "p5"), "0001");
+        assert_eq!(reply(&mut debugger, "p6"), "E01");
+    }
+
+    #[test]
+    fn test_write_registers() {
+        let mut debugger = make_debugger();
+
+        assert_eq!(reply(&mut debugger, "P5=5001"), "OK");
+        assert_eq!(debugger.gameboy().cpu().registers.pc, 0x0150);
+        assert_eq!(reply(&mut debugger, "G000000000000000000c00002"), "OK");
+        assert_eq!(debugger.gameboy().cpu().registers.pc, 0x0200);
+        assert_eq!(
+            debugger.gameboy().cpu().registers.read_16(Register16::SP),
+            0xC000
+        );
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut debugger = make_debugger();
+
+        assert_eq!(reply(&mut debugger, "m150,3"), "0018fe");
+        assert_eq!(reply(&mut debugger, "Mc000,2:1234"), "OK");
+        assert_eq!(reply(&mut debugger, "mc000,2"), "1234");
+        assert_eq!(reply(&mut debugger, "Mc000,2:12"), "E01");
+    }
+
+    #[test]
+    fn test_breakpoint_and_continue() {
+        let mut debugger = make_debugger();
+
+        assert_eq!(reply(&mut debugger, "Z0,101,1"), "OK");
+        assert_eq!(handle_packet(&mut debugger, "c"), Action::Continue);
+        let reason = debugger.continue_execution(1000);
+        assert_eq!(stop_reply(reason), "T05swbreak:;");
+        assert_eq!(reply(&mut debugger, "z0,101,1"), "OK");
+        assert_eq!(debugger.breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn test_watchpoint_reply() {
+        let reason = StopReason::Watchpoint(MemoryAccess::Write {
+            address: 0xC000,
+            value: 1,
+        });
+        assert_eq!(stop_reply(reason), "T05watch:c000;");
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut debugger = make_debugger();
+
+        assert_eq!(reply(&mut debugger, "vCont?"), "");
+        assert_eq!(reply(&mut debugger, "qAttached"), "1");
+    }
+}
@@ -0,0 +1,84 @@
+//! Desktop frontends, the graphical ones each behind their own cargo feature.
+//!
+//! With the `audio` feature they play sound through cpal, see `audio`, with the `gamepad`
+//! feature the SDL frontend reads gamepads through gilrs, see `gamepad`.
+
+#[cfg(feature = "audio")]
+pub mod audio;
+pub mod debugger;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod gdb;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+use std::{
+    fs,
+    io::BufWriter,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use gameboy_emulator::{gameboy::InputMap, Frame, GameBoy};
+
+/// The Game Boy refreshes at about 59.73 frames per second
+pub const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+
+/// Called after every frame with the frame about to be shown, which it may draw over
+pub type FrameHook = Box<dyn FnMut(&mut GameBoy, &mut Frame)>;
+
+/// Stores the controls after the player changed them
+pub type SaveControls = Box<dyn FnMut(&InputMap) -> Result<(), String>>;
+
+/// What to tell the player once the CPU hung on an illegal opcode, see `Cpu::is_locked`
+#[cfg(any(feature = "sdl", feature = "tui"))]
+pub const CRASHED: &str = "The game crashed, the CPU hung on an illegal opcode";
+
+/// Write a core dump of `gameboy` to the working directory and tell where, a failure is only
+/// reported
+pub fn write_core_dump(gameboy: &GameBoy, reason: &str) {
+    match gameboy.core_dump(reason).write(".") {
+        Ok(path) => eprintln!("Wrote a core dump to {}", path.display()),
+        Err(error) => eprintln!("Failed to write a core dump: {}", error),
+    }
+}
+
+/// How many functions a profile report lists
+pub const PROFILE_LENGTH: usize = 20;
+
+/// Print the `count` hottest functions of the running game, if it is being profiled
+pub fn print_profile(gameboy: &GameBoy, count: usize) {
+    match gameboy.profile_report(count) {
+        Some(report) => print!("{report}"),
+        None => println!("The profiler is off"),
+    }
+}
+
+/// Export the memory heatmap as `heatmap-<time>.csv` and `.png` in the working directory, if
+/// accesses are being counted
+pub fn write_heatmap(gameboy: &GameBoy) {
+    let Some(heatmap) = gameboy.heatmap() else {
+        println!("The heatmap is off");
+        return;
+    };
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let csv = format!("heatmap-{seconds}.csv");
+    let png = format!("heatmap-{seconds}.png");
+    let written = fs::write(&csv, heatmap.csv())
+        .map_err(|error| error.to_string())
+        .and_then(|_| fs::File::create(&png).map_err(|error| error.to_string()))
+        .and_then(|file| {
+            heatmap
+                .image()
+                .write_png(BufWriter::new(file))
+                .map_err(|error| error.to_string())
+        });
+    match written {
+        Ok(()) => println!("Saved {csv} and {png}"),
+        Err(error) => eprintln!("Failed to save the heatmap: {error}"),
+    }
+}
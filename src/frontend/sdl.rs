@@ -0,0 +1,537 @@
+//! SDL2 frontend, shows the framebuffer in a scalable window and forwards the keyboard to the joypad.
+//!
+//! The keys and gamepad buttons are mapped through an `InputMap`, by default arrow keys are the
+//! D-pad, X is A, Z is B, Enter is Start and Backspace is Select. With the `gamepad` feature
+//! gamepads work too, see `gamepad`.
+//! F12 saves a screenshot to the working directory, holding R rewinds and holding Tab fast-forwards.
+//! F11 writes a core dump to the working directory, as do illegal opcodes and panics in the core.
+//! An illegal opcode hangs the CPU like on hardware, the window title says the game crashed until
+//! it is rewound to before.
+//! F9 prints the hottest functions when running with `--profile`, F8 exports the memory heatmap
+//! when running with `--heatmap`.
+//! F4 switches between the built-in palettes and the one given with `--palette`, F5 turns the
+//! LCD grid on and off, F6 switches between frame blending, ghosting and neither, F7 integer scaling and F10 aspect correction, see
+//! `PostProcessor`.
+//! F2 asks for a new key or gamepad button for every button in turn and F3 for turbo A and B,
+//! Escape stops asking.
+//!
+//! A netplay session runs in a window of its own with `run_netplay`, showing this side's Game Boy.
+
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use gameboy_emulator::{
+    gameboy::{
+        DmgPalette, Frame, IllegalOpcodePolicy, Input, InputMap, Netplay, PostProcessor, Rewind,
+        SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+    utils::{DeltaTime, Speed},
+    Button, GameBoy,
+};
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    pixels::PixelFormatEnum,
+    render::{Canvas, Texture, TextureCreator},
+    video::{Window, WindowContext},
+    Sdl,
+};
+
+use super::{
+    print_profile, write_core_dump, write_heatmap, FrameHook, SaveControls, CRASHED,
+    FRAME_DURATION, PROFILE_LENGTH,
+};
+
+const SCALE: u32 = 4;
+
+/// Run the emulator in a window until it is closed
+///
+/// Keys are named in `input_map` by their SDL name, `save_controls` is called with the map after
+/// the controls were changed with F2 or F3. Frames go through `post` before they are shown
+pub fn run(
+    mut gameboy: GameBoy,
+    speed: Speed,
+    mut frame_hook: Option<FrameHook>,
+    mut input_map: InputMap,
+    mut save_controls: Option<SaveControls>,
+    mut post: PostProcessor,
+) -> Result<(), String> {
+    check_keys(&input_map)?;
+    let sdl = sdl2::init()?;
+    let mut canvas = open_window(&sdl)?;
+    canvas
+        .set_integer_scale(post.integer_scaling())
+        .map_err(|error| error.to_string())?;
+    // the built-in palettes and the one the game started with, if it is another
+    let mut palettes: Vec<DmgPalette> = DmgPalette::NAMED.map(|(_, palette)| palette).to_vec();
+    if !palettes.contains(gameboy.palette()) {
+        palettes.push(*gameboy.palette());
+    }
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGBA32,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        )
+        .map_err(|error| error.to_string())?;
+
+    let mut event_pump = sdl.event_pump()?;
+    let mut delta_time = DeltaTime::new();
+    let mut rewinding = false;
+    let mut fast_forward = false;
+    gameboy.set_rewind(Some(Rewind::default()));
+    // illegal opcodes are trapped for a core dump, then lock up the CPU like on hardware
+    gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Trap);
+    #[cfg(feature = "audio")]
+    let mut audio = super::audio::AudioOutput::open()
+        .inspect_err(|error| eprintln!("No sound: {}", error))
+        .ok();
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = super::gamepad::Gamepads::open()
+        .inspect_err(|error| eprintln!("No gamepads: {}", error))
+        .ok();
+    // the buttons (and whether turbo) still to ask a new input for
+    let mut rebinding: VecDeque<(Button, bool)> = VecDeque::new();
+    let mut crashed = false;
+
+    loop {
+        delta_time.update();
+
+        #[cfg(feature = "gamepad")]
+        for (input, down) in gamepads.iter_mut().flat_map(|gamepads| gamepads.poll()) {
+            if down && !rebinding.is_empty() {
+                rebind(&mut input_map, &mut rebinding, input, &mut save_controls);
+            } else if down {
+                input_map.input_down(input);
+            } else {
+                input_map.input_up(&input);
+            }
+        }
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } if !rebinding.is_empty() => {
+                    rebinding.clear();
+                    println!("Controls unchanged from here on");
+                }
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if !rebinding.is_empty() => {
+                    let input = Input::Key(keycode.name());
+                    rebind(&mut input_map, &mut rebinding, input, &mut save_controls);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => {
+                    rebinding.extend(Button::ALL.map(|button| (button, false)));
+                    ask_input(&rebinding);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => {
+                    rebinding.extend([(Button::A, true), (Button::B, true)]);
+                    ask_input(&rebinding);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => save_screenshot(&gameboy),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F11),
+                    repeat: false,
+                    ..
+                } => write_core_dump(&gameboy, "requested"),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => print_profile(&gameboy, PROFILE_LENGTH),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    repeat: false,
+                    ..
+                } => write_heatmap(&gameboy),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    repeat: false,
+                    ..
+                } => {
+                    let next = palettes
+                        .iter()
+                        .position(|palette| palette == gameboy.palette())
+                        .map_or(0, |index| (index + 1) % palettes.len());
+                    gameboy.set_palette(palettes[next]);
+                    println!("Palette: {}", palettes[next].name().unwrap_or("custom"));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    post.set_lcd_grid(!post.lcd_grid());
+                    println!("LCD grid {}", on_off(post.lcd_grid()));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    repeat: false,
+                    ..
+                } => {
+                    let (blending, ghosting) = match (post.frame_blending(), post.ghosting()) {
+                        (false, false) => (true, false),
+                        (true, _) => (false, true),
+                        (false, true) => (false, false),
+                    };
+                    post.set_frame_blending(blending);
+                    post.set_ghosting(ghosting);
+                    println!(
+                        "Frame blending {}, ghosting {}",
+                        on_off(blending),
+                        on_off(ghosting)
+                    );
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    repeat: false,
+                    ..
+                } => {
+                    post.set_integer_scaling(!post.integer_scaling());
+                    canvas
+                        .set_integer_scale(post.integer_scaling())
+                        .map_err(|error| error.to_string())?;
+                    println!("Integer scaling {}", on_off(post.integer_scaling()));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F10),
+                    repeat: false,
+                    ..
+                } => {
+                    post.set_aspect_correction(!post.aspect_correction());
+                    println!("Aspect correction {}", on_off(post.aspect_correction()));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::R),
+                    ..
+                } => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::R),
+                    ..
+                } => rewinding = false,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => {
+                    fast_forward = true;
+                    gameboy.set_speed(Speed::Unlimited);
+                }
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => {
+                    fast_forward = false;
+                    gameboy.set_speed(speed);
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => input_map.input_down(Input::Key(keycode.name())),
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => input_map.input_up(&Input::Key(keycode.name())),
+                _ => {}
+            }
+        }
+
+        input_map.apply(&mut gameboy);
+        let ran = panic::catch_unwind(AssertUnwindSafe(|| {
+            if rewinding {
+                gameboy.rewind();
+            } else {
+                gameboy.run_until_vblank();
+            }
+        }));
+        if let Err(payload) = ran {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            write_core_dump(&gameboy, &format!("panicked: {message}"));
+            return Err("The emulator crashed".to_string());
+        }
+        if let Some(error) = gameboy.cpu().error() {
+            write_core_dump(&gameboy, &error.to_string());
+            gameboy.set_illegal_opcode_policy(IllegalOpcodePolicy::Lockup);
+            gameboy.take_error();
+        }
+        if gameboy.cpu().is_locked() != crashed {
+            crashed = !crashed;
+            let title = if crashed {
+                eprintln!("{CRASHED}, hold R to rewind");
+                "Game Boy - crashed"
+            } else {
+                "Game Boy"
+            };
+            canvas
+                .window_mut()
+                .set_title(title)
+                .map_err(|error| error.to_string())?;
+        }
+        input_map.end_frame();
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &mut audio {
+            audio.queue(&gameboy.drain_audio_samples());
+        }
+
+        let mut frame = gameboy.frame();
+        if let Some(hook) = &mut frame_hook {
+            hook(&mut gameboy, &mut frame);
+        }
+        let frame = gameboy.with_sgb_border(frame);
+        let size = post.display_size(&frame);
+        let frame = post.process(frame);
+        present(&mut canvas, &texture_creator, &mut texture, &frame, size)?;
+
+        if !fast_forward {
+            delta_time.wait_frame(FRAME_DURATION, speed);
+        }
+    }
+}
+
+/// Run a netplay session in a window until it is closed or the session ends
+///
+/// The inputs in `input_map` press this side's buttons, sent to the session every frame. There
+/// is no rewinding, fast-forwarding or pausing the other player's game.
+pub fn run_netplay(mut netplay: Netplay, mut input_map: InputMap) -> Result<(), String> {
+    check_keys(&input_map)?;
+    let sdl = sdl2::init()?;
+    let mut canvas = open_window(&sdl)?;
+    canvas
+        .window_mut()
+        .set_title(&format!("Game Boy - player {}", netplay.player() + 1))
+        .map_err(|error| error.to_string())?;
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGBA32,
+            SCREEN_WIDTH as u32,
+            SCREEN_HEIGHT as u32,
+        )
+        .map_err(|error| error.to_string())?;
+
+    let mut event_pump = sdl.event_pump()?;
+    let mut delta_time = DeltaTime::new();
+    #[cfg(feature = "audio")]
+    let mut audio = super::audio::AudioOutput::open()
+        .inspect_err(|error| eprintln!("No sound: {}", error))
+        .ok();
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = super::gamepad::Gamepads::open()
+        .inspect_err(|error| eprintln!("No gamepads: {}", error))
+        .ok();
+
+    loop {
+        delta_time.update();
+
+        #[cfg(feature = "gamepad")]
+        for (input, down) in gamepads.iter_mut().flat_map(|gamepads| gamepads.poll()) {
+            if down {
+                input_map.input_down(input);
+            } else {
+                input_map.input_up(&input);
+            }
+        }
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => return Ok(()),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => save_screenshot(netplay.local()),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => input_map.input_down(Input::Key(keycode.name())),
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => input_map.input_up(&Input::Key(keycode.name())),
+                _ => {}
+            }
+        }
+
+        netplay
+            .run_frame(input_map.pressed_buttons())
+            .map_err(|error| error.to_string())?;
+        input_map.end_frame();
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &mut audio {
+            audio.queue(&netplay.drain_audio_samples());
+        }
+
+        let gameboy = netplay.local();
+        let frame = gameboy.with_sgb_border(gameboy.frame());
+        let size = (frame.width(), frame.height());
+        present(&mut canvas, &texture_creator, &mut texture, &frame, size)?;
+        delta_time.wait_frame(FRAME_DURATION, Speed::Normal);
+    }
+}
+
+/// Fail on keys SDL does not know, before opening the window
+fn check_keys(input_map: &InputMap) -> Result<(), String> {
+    for binding in input_map.bindings() {
+        if let Input::Key(name) = &binding.input {
+            if Keycode::from_name(name).is_none() {
+                return Err(format!(
+                    "Unknown key {:?} for {}",
+                    name,
+                    binding.button.name()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_window(sdl: &Sdl) -> Result<Canvas<Window>, String> {
+    let window = sdl
+        .video()?
+        .window(
+            "Game Boy",
+            SCREEN_WIDTH as u32 * SCALE,
+            SCREEN_HEIGHT as u32 * SCALE,
+        )
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|error| error.to_string())?;
+    let mut canvas = window
+        .into_canvas()
+        .build()
+        .map_err(|error| error.to_string())?;
+    canvas
+        .set_logical_size(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+        .map_err(|error| error.to_string())?;
+    Ok(canvas)
+}
+
+/// Draw `frame` in the window, stretched to `size` before it is scaled up
+fn present<'a>(
+    canvas: &mut Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    texture: &mut Texture<'a>,
+    frame: &Frame,
+    (width, height): (usize, usize),
+) -> Result<(), String> {
+    let query = texture.query();
+    if (query.width as usize, query.height as usize) != (frame.width(), frame.height()) {
+        *texture = texture_creator
+            .create_texture_streaming(
+                PixelFormatEnum::RGBA32,
+                frame.width() as u32,
+                frame.height() as u32,
+            )
+            .map_err(|error| error.to_string())?;
+    }
+    // the window grows once a Super Game Boy border arrives or with aspect correction
+    let (width, height) = (width as u32, height as u32);
+    if canvas.logical_size() != (width, height) {
+        canvas
+            .set_logical_size(width, height)
+            .map_err(|error| error.to_string())?;
+        canvas
+            .window_mut()
+            .set_size(width * SCALE, height * SCALE)
+            .map_err(|error| error.to_string())?;
+    }
+    texture
+        .update(None, frame.as_bytes(), frame.width() * 4)
+        .map_err(|error| error.to_string())?;
+    canvas.clear();
+    canvas.copy(texture, None, None)?;
+    canvas.present();
+    Ok(())
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// Save the screen as screenshot-SECONDS.png, a failure is only reported so the game keeps running
+fn save_screenshot(gameboy: &GameBoy) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let path = format!("screenshot-{}.png", seconds);
+    match gameboy.screenshot(&path) {
+        Ok(()) => println!("Saved {}", path),
+        Err(error) => eprintln!("Failed to save {}: {}", path, error),
+    }
+}
+
+/// Bind `input` to the button at the front of `rebinding`, saving the controls after the last one
+fn rebind(
+    input_map: &mut InputMap,
+    rebinding: &mut VecDeque<(Button, bool)>,
+    input: Input,
+    save_controls: &mut Option<SaveControls>,
+) {
+    let Some((button, turbo)) = rebinding.pop_front() else {
+        return;
+    };
+    input_map.rebind(input, button, turbo);
+    if !rebinding.is_empty() {
+        ask_input(rebinding);
+        return;
+    }
+    println!("Controls changed");
+    if let Some(save) = save_controls {
+        if let Err(error) = save(input_map) {
+            eprintln!("Failed to save the controls: {}", error);
+        }
+    }
+}
+
+fn ask_input(rebinding: &VecDeque<(Button, bool)>) {
+    if let Some((button, turbo)) = rebinding.front() {
+        let turbo = if *turbo { "turbo " } else { "" };
+        println!(
+            "Press the key or gamepad button for {}{}, Escape to stop",
+            turbo,
+            button.name()
+        );
+    }
+}
@@ -0,0 +1,203 @@
+//! Terminal frontend, draws the framebuffer with half-block characters, two pixels per cell.
+//!
+//! Needs a terminal with true color and at least 160x72 cells. Arrow keys are the D-pad, X is A,
+//! Z is B, Enter is Start and Backspace is Select, Escape or Q quits. Most terminals only report
+//! key presses, so a key counts as held until it hasn't repeated for a few frames. Once the game
+//! crashes on an illegal opcode the top line says so.
+
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    queue,
+    style::{Color, Print, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use gameboy_emulator::{
+    gameboy::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    utils::DeltaTime,
+    Button, Frame, GameBoy,
+};
+
+use super::{FrameHook, CRASHED, FRAME_DURATION};
+
+/// Frames a key stays pressed after its last press or repeat, when releases aren't reported
+const HOLD_FRAMES: u32 = 8;
+
+/// Run the emulator in the terminal until Escape or Q is pressed
+pub fn run(mut gameboy: GameBoy, frame_hook: Option<FrameHook>) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    queue!(stdout, EnterAlternateScreen, cursor::Hide)?;
+    if releases {
+        queue!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+    stdout.flush()?;
+
+    let result = run_loop(&mut gameboy, &mut stdout, releases, frame_hook);
+
+    if releases {
+        queue!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    queue!(
+        stdout,
+        SetBackgroundColor(Color::Reset),
+        cursor::Show,
+        LeaveAlternateScreen
+    )?;
+    stdout.flush()?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run_loop(
+    gameboy: &mut GameBoy,
+    stdout: &mut impl Write,
+    releases: bool,
+    mut frame_hook: Option<FrameHook>,
+) -> io::Result<()> {
+    let mut delta_time = DeltaTime::new();
+    // frames left until each button in `Button::ALL` is released
+    let mut held = [0; Button::ALL.len()];
+    #[cfg(feature = "audio")]
+    let mut audio = super::audio::AudioOutput::open()
+        .inspect_err(|error| tracing::warn!("No sound: {error}"))
+        .ok();
+
+    loop {
+        delta_time.update();
+
+        while event::poll(std::time::Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            let quit = matches!(key.code, KeyCode::Esc | KeyCode::Char('q'))
+                || (key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL));
+            if quit {
+                return Ok(());
+            }
+            let Some(index) = map_key(key.code)
+                .and_then(|button| Button::ALL.iter().position(|other| *other == button))
+            else {
+                continue;
+            };
+            held[index] = match key.kind {
+                KeyEventKind::Release => 0,
+                _ if releases => u32::MAX,
+                _ => HOLD_FRAMES,
+            };
+        }
+
+        for (frames, button) in held.iter_mut().zip(Button::ALL) {
+            if *frames > 0 {
+                gameboy.press_button(button);
+                if *frames != u32::MAX {
+                    *frames -= 1;
+                }
+            } else {
+                gameboy.release_button(button);
+            }
+        }
+
+        gameboy.run_until_vblank();
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &mut audio {
+            audio.queue(&gameboy.drain_audio_samples());
+        }
+
+        let mut frame = gameboy.frame();
+        if let Some(hook) = &mut frame_hook {
+            hook(gameboy, &mut frame);
+        }
+        stdout.write_all(&render(&frame)?)?;
+        if gameboy.cpu().is_locked() {
+            queue!(
+                stdout,
+                cursor::MoveTo(0, 0),
+                SetForegroundColor(Color::White),
+                SetBackgroundColor(Color::Black),
+                Print(CRASHED)
+            )?;
+        }
+        stdout.flush()?;
+
+        delta_time.wait(FRAME_DURATION);
+    }
+}
+
+/// The escape sequences drawing `frame`, each cell's foreground is the upper pixel and its
+/// background the lower one, colors are only sent when they change
+fn render(frame: &Frame) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut colors = None;
+    for row in 0..SCREEN_HEIGHT / 2 {
+        queue!(output, cursor::MoveTo(0, row as u16))?;
+        for x in 0..SCREEN_WIDTH {
+            let top = color(frame.pixel(x, row * 2));
+            let bottom = color(frame.pixel(x, row * 2 + 1));
+            if colors != Some((top, bottom)) {
+                queue!(output, SetForegroundColor(top), SetBackgroundColor(bottom))?;
+                colors = Some((top, bottom));
+            }
+            queue!(output, Print('▀'))?;
+        }
+    }
+    Ok(output)
+}
+
+fn color([r, g, b, _]: [u8; 4]) -> Color {
+    Color::Rgb { r, g, b }
+}
+
+fn map_key(code: KeyCode) -> Option<Button> {
+    match code {
+        KeyCode::Right => Some(Button::Right),
+        KeyCode::Left => Some(Button::Left),
+        KeyCode::Up => Some(Button::Up),
+        KeyCode::Down => Some(Button::Down),
+        KeyCode::Char('x') => Some(Button::A),
+        KeyCode::Char('z') => Some(Button::B),
+        KeyCode::Backspace => Some(Button::Select),
+        KeyCode::Enter => Some(Button::Start),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gameboy_emulator::DmgPalette;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let blank = Frame::from_shades(&[0; SCREEN_WIDTH * SCREEN_HEIGHT], &DmgPalette::GRAYSCALE);
+        let text = String::from_utf8(render(&blank).unwrap()).unwrap();
+
+        assert_eq!(text.matches('▀').count(), SCREEN_WIDTH * SCREEN_HEIGHT / 2);
+        // one color change for the whole frame
+        assert_eq!(text.matches("38;2;255;255;255").count(), 1);
+
+        let mut shades = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        shades[SCREEN_WIDTH] = 3;
+        let dot = Frame::from_shades(&shades, &DmgPalette::GRAYSCALE);
+        let text = String::from_utf8(render(&dot).unwrap()).unwrap();
+        assert!(text.contains("48;2;0;0;0m▀"));
+    }
+
+    #[test]
+    fn test_map_key() {
+        assert_eq!(map_key(KeyCode::Char('x')), Some(Button::A));
+        assert_eq!(map_key(KeyCode::Enter), Some(Button::Start));
+        assert_eq!(map_key(KeyCode::Char('p')), None);
+    }
+}
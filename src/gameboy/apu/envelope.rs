@@ -0,0 +1,119 @@
+//! The volume envelope, changes the volume by one every `period` 64 Hz frame sequencer clocks.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub struct Envelope {
+    register: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope {
+            register: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    /// The DAC is on as long as the initial volume or the direction bit is set
+    pub fn dac_enabled(&self) -> bool {
+        self.register & 0xF8 != 0
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    pub fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return;
+        }
+        self.timer = self.period();
+
+        let increase = self.register & 0x08 != 0;
+        if increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    fn period(&self) -> u8 {
+        self.register & 0x07
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        state.write_u8(self.volume);
+        state.write_u8(self.timer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register = state.read_u8()?;
+        self.volume = state.read_u8()?;
+        self.timer = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decrease() {
+        let mut envelope = Envelope::new();
+        envelope.write(0xA2);
+        envelope.trigger();
+        assert_eq!(envelope.volume(), 10);
+
+        envelope.clock();
+        assert_eq!(envelope.volume(), 10);
+        envelope.clock();
+        assert_eq!(envelope.volume(), 9);
+    }
+
+    #[test]
+    fn test_increase_saturates() {
+        let mut envelope = Envelope::new();
+        envelope.write(0xF9);
+        envelope.trigger();
+
+        envelope.clock();
+        assert_eq!(envelope.volume(), 15);
+    }
+
+    #[test]
+    fn test_dac_enabled() {
+        let mut envelope = Envelope::new();
+        envelope.write(0x07);
+        assert!(!envelope.dac_enabled());
+        envelope.write(0x08);
+        assert!(envelope.dac_enabled());
+    }
+}
@@ -0,0 +1,101 @@
+//! The length counter, turns a channel off after a number of 256 Hz frame sequencer clocks.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub struct LengthCounter {
+    max: u16,
+    value: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    /// A counter of `max` steps, 64 for most channels and 256 for the wave channel
+    pub fn new(max: u16) -> LengthCounter {
+        LengthCounter {
+            max,
+            value: 0,
+            enabled: false,
+        }
+    }
+
+    /// Load the length register, the counter runs for `max - length` clocks
+    pub fn load(&mut self, length: u16) {
+        self.value = self.max - length;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Reload an expired counter when the channel is triggered
+    pub fn trigger(&mut self) {
+        if self.value == 0 {
+            self.value = self.max;
+        }
+    }
+
+    /// Clock the counter, returns true if it expired and the channel should be disabled
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.value == 0 {
+            return false;
+        }
+        self.value -= 1;
+        self.value == 0
+    }
+}
+
+impl SaveState for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.value);
+        state.write_bool(self.enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.value = state.read_u16()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expire() {
+        let mut length = LengthCounter::new(64);
+        length.load(62);
+        length.set_enabled(true);
+
+        assert!(!length.clock());
+        assert!(length.clock());
+        assert!(!length.clock());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut length = LengthCounter::new(64);
+        length.load(63);
+
+        assert!(!length.clock());
+    }
+
+    #[test]
+    fn test_trigger_reload() {
+        let mut length = LengthCounter::new(256);
+        length.set_enabled(true);
+        length.trigger();
+
+        for _ in 0..255 {
+            assert!(!length.clock());
+        }
+        assert!(length.clock());
+    }
+}
@@ -0,0 +1,589 @@
+//! The audio processing unit.
+//!
+//! Channels 1 and 2 are square waves, channel 3 plays wave RAM and channel 4 is noise.
+//! The channels are clocked every T-cycle, the frame sequencer clocks their length counters (256 Hz),
+//! sweep (128 Hz) and envelopes (64 Hz). The mixed output is sampled at `SAMPLE_RATE` into a ring
+//! buffer of interleaved stereo samples the frontend drains.
+//!
+//! For debugging and ripping, channels can be muted in the mixed output and the output (or a
+//! single channel) recorded to a WAV file at `SAMPLE_RATE`, whatever speed the emulator runs at.
+//!
+//! PCM12 and PCM34 read the current output of channels 1 and 2, 3 and 4, one per nibble. They
+//! only exist in CGB mode, `Io` reads them as 0xFF otherwise.
+
+mod envelope;
+mod length;
+mod noise;
+mod square;
+mod sweep;
+#[cfg(feature = "std")]
+mod wav;
+mod wave;
+
+use alloc::{collections::VecDeque, vec::Vec};
+use core::ops::RangeInclusive;
+#[cfg(feature = "std")]
+use std::{io, path::Path};
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+use noise::Noise;
+use square::Square;
+#[cfg(feature = "std")]
+use wav::WavWriter;
+use wave::{Wave, WAVE_RAM_SIZE};
+
+#[cfg(feature = "std")]
+pub use wav::WavSource;
+
+pub const NR10: u16 = 0xFF10;
+pub const NR11: u16 = 0xFF11;
+pub const NR12: u16 = 0xFF12;
+pub const NR21: u16 = 0xFF16;
+pub const NR30: u16 = 0xFF1A;
+pub const NR41: u16 = 0xFF20;
+pub const NR50: u16 = 0xFF24;
+pub const NR51: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const WAVE_RAM: u16 = 0xFF30;
+pub const PCM12: u16 = 0xFF76;
+pub const PCM34: u16 = 0xFF77;
+
+pub const APU_START: u16 = NR10;
+pub const APU_END: u16 = WAVE_RAM + WAVE_RAM_SIZE as u16 - 1;
+
+pub const SAMPLE_RATE: u32 = 44100;
+const CLOCK_RATE: u32 = 4_194_304;
+const FRAME_SEQUENCER_PERIOD: u32 = 8192;
+const BUFFER_CAPACITY: usize = SAMPLE_RATE as usize; // half a second of stereo samples
+const VOLUME_SCALE: i16 = 64;
+
+const POWER: u8 = 1 << 7;
+
+/// The four sound channels, in NR51 bit order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
+    Square1,
+    Square2,
+    Wave,
+    Noise,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [
+        Channel::Square1,
+        Channel::Square2,
+        Channel::Wave,
+        Channel::Noise,
+    ];
+}
+
+pub struct Apu {
+    enabled: bool,
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+    nr50: u8,
+    nr51: u8,
+    frame_sequencer_cycles: u32,
+    frame_sequencer_step: u8,
+    sample_cycles: u32,
+    sample_rate: u32, // samples per emulated second, SAMPLE_RATE unless the emulator runs faster
+    samples: VecDeque<i16>,
+    muted: [bool; 4], // by Channel
+    #[cfg(feature = "std")]
+    wav: Option<(WavWriter, WavSource)>,
+    #[cfg(feature = "std")]
+    wav_cycles: u32,
+}
+
+impl Apu {
+    pub fn new() -> Apu {
+        Apu {
+            enabled: false,
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0,
+            nr51: 0,
+            frame_sequencer_cycles: 0,
+            frame_sequencer_step: 0,
+            sample_cycles: 0,
+            sample_rate: SAMPLE_RATE,
+            samples: VecDeque::with_capacity(BUFFER_CAPACITY),
+            muted: [false; 4],
+            #[cfg(feature = "std")]
+            wav: None,
+            #[cfg(feature = "std")]
+            wav_cycles: 0,
+        }
+    }
+
+    /// Advance the APU by a number of T-cycles
+    pub fn tick(&mut self, cycles: u32) {
+        for _ in 0..cycles {
+            if self.enabled {
+                self.square1.tick();
+                self.square2.tick();
+                self.wave.tick();
+                self.noise.tick();
+
+                self.frame_sequencer_cycles += 1;
+                if self.frame_sequencer_cycles == FRAME_SEQUENCER_PERIOD {
+                    self.frame_sequencer_cycles = 0;
+                    self.step_frame_sequencer();
+                }
+            }
+
+            self.sample_cycles += self.sample_rate;
+            if self.sample_cycles >= CLOCK_RATE {
+                self.sample_cycles -= CLOCK_RATE;
+                self.push_sample();
+            }
+
+            #[cfg(feature = "std")]
+            self.tick_wav();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn tick_wav(&mut self) {
+        if self.wav.is_some() {
+            self.wav_cycles += SAMPLE_RATE;
+            if self.wav_cycles >= CLOCK_RATE {
+                self.wav_cycles -= CLOCK_RATE;
+                self.record_wav();
+            }
+        }
+    }
+
+    /// Sample the output `rate` times per emulated second, 0 stops producing samples
+    ///
+    /// Running faster than real time at a lower rate keeps the real time output at `SAMPLE_RATE`
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.sample_rate = rate.min(CLOCK_RATE);
+    }
+
+    /// Mute or unmute a channel in the mixed output, the channel keeps running either way
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel as usize] = muted;
+    }
+
+    pub fn channel_muted(&self, channel: Channel) -> bool {
+        self.muted[channel as usize]
+    }
+
+    /// Mute every channel but `channel`, or unmute them all with `None`
+    pub fn solo_channel(&mut self, channel: Option<Channel>) {
+        for other in Channel::ALL {
+            self.muted[other as usize] = channel.is_some_and(|channel| channel != other);
+        }
+    }
+
+    /// Record `source` to a WAV file at `path` until `stop_wav_dump`, ending a running recording
+    #[cfg(feature = "std")]
+    pub fn start_wav_dump(&mut self, path: impl AsRef<Path>, source: WavSource) -> io::Result<()> {
+        self.stop_wav_dump()?;
+        let channels = match source {
+            WavSource::Mixed => 2,
+            WavSource::Channel(_) => 1,
+        };
+        self.wav = Some((WavWriter::create(path, SAMPLE_RATE, channels)?, source));
+        self.wav_cycles = 0;
+        Ok(())
+    }
+
+    /// Finish the running WAV recording, if any, reporting any write error since it started
+    #[cfg(feature = "std")]
+    pub fn stop_wav_dump(&mut self) -> io::Result<()> {
+        match self.wav.take() {
+            Some((writer, _)) => writer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Take all buffered samples, interleaved left and right
+    pub fn drain_samples(&mut self) -> Vec<i16> {
+        self.samples.drain(..).collect()
+    }
+
+    fn set_power(&mut self, enabled: bool) {
+        if self.enabled && !enabled {
+            self.square1 = Square::new(true);
+            self.square2 = Square::new(false);
+            self.noise = Noise::new();
+            let mut wave = Wave::new();
+            for index in 0..WAVE_RAM_SIZE {
+                wave.write_ram(index, self.wave.read_ram(index));
+            }
+            self.wave = wave;
+            self.nr50 = 0;
+            self.nr51 = 0;
+        }
+        if !self.enabled && enabled {
+            self.frame_sequencer_cycles = 0;
+            self.frame_sequencer_step = 0;
+        }
+        self.enabled = enabled;
+    }
+
+    fn step_frame_sequencer(&mut self) {
+        let step = self.frame_sequencer_step;
+        if step.is_multiple_of(2) {
+            self.square1.clock_length();
+            self.square2.clock_length();
+            self.wave.clock_length();
+            self.noise.clock_length();
+        }
+        if step == 2 || step == 6 {
+            self.square1.clock_sweep();
+        }
+        if step == 7 {
+            self.square1.clock_envelope();
+            self.square2.clock_envelope();
+            self.noise.clock_envelope();
+        }
+        self.frame_sequencer_step = (step + 1) % 8;
+    }
+
+    /// The level of each channel, in Channel order
+    fn channel_outputs(&self) -> [i16; 4] {
+        [
+            dac_output(self.square1.dac_enabled(), self.square1.output()),
+            dac_output(self.square2.dac_enabled(), self.square2.output()),
+            dac_output(self.wave.dac_enabled(), self.wave.output()),
+            dac_output(self.noise.dac_enabled(), self.noise.output()),
+        ]
+    }
+
+    /// Mix the unmuted channels into a stereo sample
+    fn mix(&self) -> [i16; 2] {
+        let mut left = 0;
+        let mut right = 0;
+        for (channel, output) in self.channel_outputs().iter().enumerate() {
+            if self.muted[channel] {
+                continue;
+            }
+            if self.nr51 & (1 << channel) != 0 {
+                right += output;
+            }
+            if self.nr51 & (1 << (channel + 4)) != 0 {
+                left += output;
+            }
+        }
+
+        let left_volume = i16::from((self.nr50 >> 4) & 0x07) + 1;
+        let right_volume = i16::from(self.nr50 & 0x07) + 1;
+        [
+            left * left_volume * VOLUME_SCALE,
+            right * right_volume * VOLUME_SCALE,
+        ]
+    }
+
+    fn push_sample(&mut self) {
+        if self.samples.len() + 2 > BUFFER_CAPACITY {
+            self.samples.pop_front();
+            self.samples.pop_front();
+        }
+        self.samples.extend(self.mix());
+    }
+
+    #[cfg(feature = "std")]
+    fn record_wav(&mut self) {
+        let mixed = self.mix();
+        let outputs = self.channel_outputs();
+        let Some((writer, source)) = &mut self.wav else {
+            return;
+        };
+        match *source {
+            WavSource::Mixed => writer.write(&mixed),
+            // a single channel at the loudest master volume
+            WavSource::Channel(channel) => {
+                writer.write(&[outputs[channel as usize] * 8 * VOLUME_SCALE])
+            }
+        }
+    }
+}
+
+impl IoDevice for Apu {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[APU_START..=APU_END, PCM12..=PCM34]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            0xFF10..=0xFF14 => self.square1.read(address - NR10),
+            0xFF15..=0xFF19 => self.square2.read(address - (NR21 - 1)),
+            0xFF1A..=0xFF1E => self.wave.read(address - NR30),
+            0xFF20..=0xFF23 => self.noise.read(address - NR41),
+            NR50 => self.nr50,
+            NR51 => self.nr51,
+            NR52 => {
+                let status = u8::from(self.square1.is_enabled())
+                    | (u8::from(self.square2.is_enabled()) << 1)
+                    | (u8::from(self.wave.is_enabled()) << 2)
+                    | (u8::from(self.noise.is_enabled()) << 3);
+                (u8::from(self.enabled) << 7) | 0x70 | status
+            }
+            WAVE_RAM..=APU_END => self.wave.read_ram(usize::from(address - WAVE_RAM)),
+            PCM12 => self.square1.output() | self.square2.output() << 4,
+            PCM34 => self.wave.output() | self.noise.output() << 4,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        // wave RAM is not affected by the power switch
+        if (WAVE_RAM..=APU_END).contains(&address) {
+            self.wave.write_ram(usize::from(address - WAVE_RAM), value);
+            return;
+        }
+        if address == NR52 {
+            self.set_power(value & POWER != 0);
+            return;
+        }
+        // while powered off the registers are read-only
+        if !self.enabled {
+            return;
+        }
+
+        match address {
+            0xFF10..=0xFF14 => self.square1.write(address - NR10, value),
+            0xFF15..=0xFF19 => self.square2.write(address - (NR21 - 1), value),
+            0xFF1A..=0xFF1E => self.wave.write(address - NR30, value),
+            0xFF20..=0xFF23 => self.noise.write(address - NR41, value),
+            NR50 => self.nr50 = value,
+            NR51 => self.nr51 = value,
+            _ => {}
+        }
+    }
+}
+
+/// Convert a 0-15 channel output to a signed level, a disabled DAC outputs silence
+fn dac_output(dac_enabled: bool, output: u8) -> i16 {
+    if !dac_enabled {
+        return 0;
+    }
+    i16::from(output) * 2 - 15
+}
+
+impl SaveState for Apu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        self.square1.save_state(state);
+        self.square2.save_state(state);
+        self.wave.save_state(state);
+        self.noise.save_state(state);
+        state.write_u8(self.nr50);
+        state.write_u8(self.nr51);
+        state.write_u32(self.frame_sequencer_cycles);
+        state.write_u8(self.frame_sequencer_step);
+        state.write_u32(self.sample_cycles);
+    }
+
+    /// Buffered samples are dropped, they belong to the timeline before the load
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.enabled = state.read_bool()?;
+        self.square1.load_state(state)?;
+        self.square2.load_state(state)?;
+        self.wave.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.nr50 = state.read_u8()?;
+        self.nr51 = state.read_u8()?;
+        self.frame_sequencer_cycles = state.read_u32()?;
+        self.frame_sequencer_step = state.read_u8()? % 8;
+        self.sample_cycles = state.read_u32()?;
+        self.samples.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered() -> Apu {
+        let mut apu = Apu::new();
+        apu.write(NR52, 0x80);
+        apu.write(NR50, 0x77);
+        apu.write(NR51, 0xFF);
+        apu
+    }
+
+    #[test]
+    fn test_mute_and_solo() {
+        let mut apu = powered();
+        // channel 2 at full volume, duty 50%
+        apu.write(0xFF16, 0x80);
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0x80);
+        apu.tick(4096);
+        let loud = apu.mix();
+        assert_ne!(loud, [0, 0]);
+
+        apu.set_channel_muted(Channel::Square2, true);
+        assert_eq!(apu.mix(), [0, 0]);
+        apu.solo_channel(Some(Channel::Square2));
+        assert!(apu.channel_muted(Channel::Square1));
+        assert_eq!(apu.mix(), loud);
+        apu.solo_channel(Some(Channel::Noise));
+        assert_eq!(apu.mix(), [0, 0]);
+        apu.solo_channel(None);
+        assert_eq!(apu.mix(), loud);
+    }
+
+    #[test]
+    fn test_wav_dump() {
+        let path =
+            std::env::temp_dir().join(format!("gameboy_emulator_{}_dump.wav", std::process::id()));
+        let mut apu = powered();
+        apu.set_sample_rate(0);
+        apu.start_wav_dump(&path, WavSource::Channel(Channel::Noise))
+            .unwrap();
+        apu.tick(CLOCK_RATE / 4);
+        apu.stop_wav_dump().unwrap();
+
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // a quarter second of mono samples, recorded while no samples were played
+        assert_eq!(wav.len(), 44 + SAMPLE_RATE as usize / 4 * 2);
+        assert_eq!(wav[22..24], 1u16.to_le_bytes());
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let mut apu = Apu::new();
+        apu.tick(CLOCK_RATE / 4);
+
+        assert_eq!(apu.drain_samples().len(), SAMPLE_RATE as usize / 4 * 2);
+        assert!(apu.drain_samples().is_empty());
+    }
+
+    #[test]
+    fn test_set_sample_rate() {
+        let mut apu = Apu::new();
+        apu.set_sample_rate(SAMPLE_RATE / 2);
+        apu.tick(CLOCK_RATE / 4);
+        assert_eq!(apu.drain_samples().len(), SAMPLE_RATE as usize / 8 * 2);
+
+        apu.set_sample_rate(0);
+        apu.tick(CLOCK_RATE / 4);
+        assert!(apu.drain_samples().is_empty());
+    }
+
+    #[test]
+    fn test_buffer_capacity() {
+        let mut apu = Apu::new();
+        apu.tick(CLOCK_RATE);
+
+        assert_eq!(apu.drain_samples().len(), BUFFER_CAPACITY);
+    }
+
+    #[test]
+    fn test_power_off() {
+        let mut apu = powered();
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x80);
+        assert_eq!(apu.read(NR52), 0xF1);
+
+        apu.write(NR52, 0x00);
+        assert_eq!(apu.read(NR52), 0x70);
+        assert_eq!(apu.read(NR50), 0x00);
+
+        apu.write(NR50, 0x77);
+        assert_eq!(apu.read(NR50), 0x00);
+    }
+
+    #[test]
+    fn test_square_output() {
+        let mut apu = powered();
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0x87);
+        apu.drain_samples();
+
+        apu.tick(CLOCK_RATE / 100);
+        let samples = apu.drain_samples();
+        assert!(samples.iter().any(|&sample| sample > 0));
+        assert!(samples.iter().any(|&sample| sample < 0));
+        assert_eq!(apu.read(NR52), 0xF2);
+    }
+
+    #[test]
+    fn test_pcm_registers() {
+        let mut apu = powered();
+        assert_eq!(apu.read(PCM12), 0x00);
+        // channel 2 at volume 12 and the highest frequency, one duty step every 4 T-cycles
+        apu.write(0xFF16, 0x80);
+        apu.write(0xFF17, 0xC0);
+        apu.write(0xFF18, 0xFF);
+        apu.write(0xFF19, 0x87);
+
+        let mut levels = Vec::new();
+        for _ in 0..64 {
+            apu.tick(4);
+            levels.push(apu.read(PCM12));
+        }
+        assert!(levels.contains(&0x00));
+        assert!(levels.contains(&0xC0));
+        assert!(levels.iter().all(|&level| level == 0x00 || level == 0xC0));
+        assert_eq!(apu.read(PCM34), 0x00);
+    }
+
+    #[test]
+    fn test_wave_ram() {
+        let mut apu = Apu::new();
+        apu.write(WAVE_RAM, 0x12);
+        apu.write(APU_END, 0x34);
+        assert_eq!(apu.read(WAVE_RAM), 0x12);
+        assert_eq!(apu.read(APU_END), 0x34);
+
+        apu.write(NR52, 0x80);
+        apu.write(NR52, 0x00);
+        assert_eq!(apu.read(WAVE_RAM), 0x12);
+    }
+
+    #[test]
+    fn test_channel_status() {
+        let mut apu = powered();
+        apu.write(NR30, 0x80);
+        apu.write(0xFF1E, 0x80);
+        apu.write(0xFF21, 0xF0);
+        apu.write(0xFF23, 0x80);
+
+        assert_eq!(apu.read(NR52), 0xFC);
+        assert_eq!(apu.read(0xFF1F), 0xFF);
+    }
+
+    #[test]
+    fn test_noise_output() {
+        let mut apu = powered();
+        apu.write(0xFF21, 0xF0);
+        apu.write(0xFF23, 0x80);
+        apu.drain_samples();
+
+        apu.tick(CLOCK_RATE / 100);
+        let samples = apu.drain_samples();
+        assert!(samples.iter().any(|&sample| sample > 0));
+        assert!(samples.iter().any(|&sample| sample < 0));
+    }
+
+    #[test]
+    fn test_length_frame_sequencer() {
+        let mut apu = powered();
+        apu.write(0xFF16, 0x3F);
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0xC7);
+        assert_eq!(apu.read(NR52) & 0x02, 0x02);
+
+        apu.tick(FRAME_SEQUENCER_PERIOD);
+        assert_eq!(apu.read(NR52) & 0x02, 0x00);
+    }
+}
@@ -0,0 +1,197 @@
+//! The noise channel, channel 4, outputs the low bit of a 15 bit linear feedback shift register.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{envelope::Envelope, length::LengthCounter};
+
+const DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+pub struct Noise {
+    length: LengthCounter,
+    envelope: Envelope,
+    enabled: bool,
+    polynomial: u8,
+    lfsr: u16,
+    timer: u32,
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            enabled: false,
+            polynomial: 0,
+            lfsr: 0x7FFF,
+            timer: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// Read register NR41-NR44, `register` is the offset from NR41
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            1 => self.envelope.read(),
+            2 => self.polynomial,
+            3 => (u8::from(self.length.is_enabled()) << 6) | 0xBF,
+            _ => 0xFF,
+        }
+    }
+
+    /// Write register NR41-NR44, `register` is the offset from NR41
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => self.length.load(u16::from(value & 0x3F)),
+            1 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            2 => self.polynomial = value,
+            3 => {
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Advance the frequency timer by one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            self.step_lfsr();
+        }
+    }
+
+    /// The current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled || self.lfsr & 1 != 0 {
+            return 0;
+        }
+        self.envelope.volume()
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+    }
+
+    fn step_lfsr(&mut self) {
+        let bit = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+        self.lfsr = (self.lfsr >> 1) | (bit << 14);
+        // 7 bit mode also feeds the result into bit 6
+        if self.polynomial & 0x08 != 0 {
+            self.lfsr = (self.lfsr & !(1 << 6)) | (bit << 6);
+        }
+    }
+
+    /// T-cycles per LFSR step
+    fn period(&self) -> u32 {
+        DIVISORS[usize::from(self.polynomial & 0x07)] << (self.polynomial >> 4)
+    }
+}
+
+impl SaveState for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.write_bool(self.enabled);
+        state.write_u8(self.polynomial);
+        state.write_u16(self.lfsr);
+        state.write_u32(self.timer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        self.polynomial = state.read_u8()?;
+        self.lfsr = state.read_u16()?;
+        self.timer = state.read_u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(polynomial: u8) -> Noise {
+        let mut noise = Noise::new();
+        noise.write(1, 0xF0);
+        noise.write(2, polynomial);
+        noise.write(3, 0x80);
+        noise
+    }
+
+    #[test]
+    fn test_lfsr() {
+        let mut noise = triggered(0x00);
+        assert_eq!(noise.output(), 0);
+
+        for _ in 0..8 {
+            noise.tick();
+        }
+        assert_eq!(noise.lfsr, 0x3FFF);
+
+        for _ in 0..8 * 14 {
+            noise.tick();
+        }
+        assert_eq!(noise.lfsr, 0x4000);
+        assert_eq!(noise.output(), 15);
+    }
+
+    #[test]
+    fn test_short_mode() {
+        let mut noise = triggered(0x08);
+        noise.lfsr = 0x0001;
+
+        noise.step_lfsr();
+        assert_eq!(noise.lfsr, 0x4040);
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(triggered(0x00).period(), 8);
+        assert_eq!(triggered(0x21).period(), 64);
+        assert_eq!(triggered(0xF7).period(), 112 << 15);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut noise = triggered(0x00);
+        noise.lfsr = 0x7FFE;
+
+        assert_eq!(noise.output(), 15);
+    }
+}
@@ -0,0 +1,251 @@
+//! The square wave channels, channel 1 (with frequency sweep) and channel 2.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{
+    envelope::Envelope,
+    length::LengthCounter,
+    sweep::{Sweep, SweepResult},
+};
+
+const DUTY_PATTERNS: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+pub struct Square {
+    sweep: Option<Sweep>,
+    length: LengthCounter,
+    envelope: Envelope,
+    enabled: bool,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    timer: u32,
+}
+
+impl Square {
+    /// Create a square channel, only channel 1 has a frequency sweep
+    pub fn new(has_sweep: bool) -> Square {
+        Square {
+            sweep: has_sweep.then(Sweep::new),
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            enabled: false,
+            duty: 0,
+            duty_step: 0,
+            frequency: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    /// Read register NRx0-NRx4, `register` is the offset from NRx0
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => self.sweep.as_ref().map_or(0xFF, Sweep::read),
+            1 => (self.duty << 6) | 0x3F,
+            2 => self.envelope.read(),
+            3 => 0xFF,
+            4 => (u8::from(self.length.is_enabled()) << 6) | 0xBF,
+            _ => 0xFF,
+        }
+    }
+
+    /// Write register NRx0-NRx4, `register` is the offset from NRx0
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                if let Some(sweep) = self.sweep.as_mut() {
+                    sweep.write(value);
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(u16::from(value & 0x3F));
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | u16::from(value),
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (u16::from(value & 0x07) << 8);
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Advance the frequency timer by one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+    }
+
+    /// The current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+        let high = DUTY_PATTERNS[usize::from(self.duty)] >> (7 - self.duty_step) & 1;
+        high * self.envelope.volume()
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let Some(sweep) = self.sweep.as_mut() else {
+            return;
+        };
+        match sweep.clock() {
+            SweepResult::Unchanged => {}
+            SweepResult::Frequency(frequency) => self.frequency = frequency,
+            SweepResult::Overflow => self.enabled = false,
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+
+        if let Some(sweep) = self.sweep.as_mut() {
+            if !sweep.trigger(self.frequency) {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// T-cycles per duty step
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 4
+    }
+}
+
+impl SaveState for Square {
+    fn save_state(&self, state: &mut StateWriter) {
+        if let Some(sweep) = self.sweep.as_ref() {
+            sweep.save_state(state);
+        }
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.write_bool(self.enabled);
+        state.write_u8(self.duty);
+        state.write_u8(self.duty_step);
+        state.write_u16(self.frequency);
+        state.write_u32(self.timer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        if let Some(sweep) = self.sweep.as_mut() {
+            sweep.load_state(state)?;
+        }
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        self.duty = state.read_u8()? & 0x03;
+        self.duty_step = state.read_u8()? & 0x07;
+        self.frequency = state.read_u16()? & 0x7FF;
+        self.timer = state.read_u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(duty: u8, frequency: u16) -> Square {
+        let mut square = Square::new(false);
+        square.write(1, duty << 6);
+        square.write(2, 0xF0);
+        square.write(3, frequency as u8);
+        square.write(4, 0x80 | (frequency >> 8) as u8);
+        square
+    }
+
+    #[test]
+    fn test_duty_waveform() {
+        let mut square = triggered(2, 0x7FF);
+        let mut waveform = Vec::new();
+        for _ in 0..8 {
+            waveform.push(square.output());
+            for _ in 0..4 {
+                square.tick();
+            }
+        }
+
+        assert_eq!(waveform, vec![15, 0, 0, 0, 0, 15, 15, 15]);
+    }
+
+    #[test]
+    fn test_dac_off_disables() {
+        let mut square = triggered(2, 0x700);
+        assert!(square.is_enabled());
+
+        square.write(2, 0x00);
+        assert!(!square.is_enabled());
+    }
+
+    #[test]
+    fn test_length_disables() {
+        let mut square = triggered(2, 0x700);
+        square.write(1, 0x3F);
+        square.write(4, 0x47);
+
+        square.clock_length();
+        assert!(!square.is_enabled());
+    }
+
+    #[test]
+    fn test_registers_read() {
+        let mut square = Square::new(true);
+        square.write(0, 0x7F);
+        square.write(1, 0x80);
+        square.write(4, 0x40);
+
+        assert_eq!(square.read(0), 0xFF);
+        assert_eq!(square.read(1), 0xBF);
+        assert_eq!(square.read(3), 0xFF);
+        assert_eq!(square.read(4), 0xFF);
+        assert_eq!(Square::new(false).read(0), 0xFF);
+    }
+
+    #[test]
+    fn test_sweep_overflow_disables() {
+        let mut square = Square::new(true);
+        square.write(0, 0x11);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0x87);
+
+        assert!(!square.is_enabled());
+    }
+}
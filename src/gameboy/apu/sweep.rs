@@ -0,0 +1,152 @@
+//! The frequency sweep of channel 1, clocked at 128 Hz by the frame sequencer.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub struct Sweep {
+    register: u8,
+    shadow: u16,
+    timer: u8,
+    enabled: bool,
+}
+
+/// The result of a sweep clock
+pub enum SweepResult {
+    Unchanged,
+    Frequency(u16),
+    Overflow,
+}
+
+impl Sweep {
+    pub fn new() -> Sweep {
+        Sweep {
+            register: 0,
+            shadow: 0,
+            timer: 0,
+            enabled: false,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register | 0x80
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value & 0x7F;
+    }
+
+    /// Restart the sweep from the channel frequency, returns false if the first calculation overflows
+    pub fn trigger(&mut self, frequency: u16) -> bool {
+        self.shadow = frequency;
+        self.timer = self.reload_value();
+        self.enabled = self.period() != 0 || self.shift() != 0;
+
+        self.shift() == 0 || self.calculate() <= 0x7FF
+    }
+
+    pub fn clock(&mut self) -> SweepResult {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return SweepResult::Unchanged;
+        }
+        self.timer = self.reload_value();
+
+        if !self.enabled || self.period() == 0 {
+            return SweepResult::Unchanged;
+        }
+
+        let frequency = self.calculate();
+        if frequency > 0x7FF {
+            return SweepResult::Overflow;
+        }
+        if self.shift() == 0 {
+            return SweepResult::Unchanged;
+        }
+
+        self.shadow = frequency;
+        // the new frequency is checked for overflow a second time
+        if self.calculate() > 0x7FF {
+            return SweepResult::Overflow;
+        }
+        SweepResult::Frequency(frequency)
+    }
+
+    fn calculate(&self) -> u16 {
+        let delta = self.shadow >> self.shift();
+        if self.register & 0x08 != 0 {
+            self.shadow.wrapping_sub(delta)
+        } else {
+            self.shadow + delta
+        }
+    }
+
+    fn period(&self) -> u8 {
+        (self.register >> 4) & 0x07
+    }
+
+    fn shift(&self) -> u8 {
+        self.register & 0x07
+    }
+
+    /// A period of 0 is treated as 8 by the timer
+    fn reload_value(&self) -> u8 {
+        match self.period() {
+            0 => 8,
+            period => period,
+        }
+    }
+}
+
+impl SaveState for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.register);
+        state.write_u16(self.shadow);
+        state.write_u8(self.timer);
+        state.write_bool(self.enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.register = state.read_u8()?;
+        self.shadow = state.read_u16()?;
+        self.timer = state.read_u8()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_increase() {
+        let mut sweep = Sweep::new();
+        sweep.write(0x11);
+        assert!(sweep.trigger(0x100));
+
+        assert!(matches!(sweep.clock(), SweepResult::Frequency(0x180)));
+        assert!(matches!(sweep.clock(), SweepResult::Frequency(0x240)));
+    }
+
+    #[test]
+    fn test_decrease() {
+        let mut sweep = Sweep::new();
+        sweep.write(0x19);
+        sweep.trigger(0x100);
+
+        assert!(matches!(sweep.clock(), SweepResult::Frequency(0x080)));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut sweep = Sweep::new();
+        sweep.write(0x11);
+        assert!(!sweep.trigger(0x700));
+
+        sweep.write(0x11);
+        sweep.trigger(0x500);
+        assert!(matches!(sweep.clock(), SweepResult::Overflow));
+    }
+}
@@ -0,0 +1,142 @@
+//! Recording the APU output to a 16-bit PCM WAV file.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const HEADER_SIZE: u32 = 44;
+
+/// Where a WAV file is written, seekable to fill in the sizes once recording stops
+pub trait WavSink: Write + Seek + Send {}
+
+impl<T: Write + Seek + Send> WavSink for T {}
+
+/// What a WAV dump records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WavSource {
+    /// The stereo output as it is played, after panning, master volume and muting
+    Mixed,
+    /// A single channel in mono, before panning and muting
+    Channel(super::Channel),
+}
+
+/// Writes interleaved 16-bit samples, the header sizes are filled in by `finish`
+pub struct WavWriter {
+    sink: Box<dyn WavSink>,
+    data_size: u32,
+    error: Option<io::Error>, // the first write error, reported by `finish`
+}
+
+impl WavWriter {
+    pub fn create(
+        path: impl AsRef<Path>,
+        sample_rate: u32,
+        channels: u16,
+    ) -> io::Result<WavWriter> {
+        let file = File::create(path)?;
+        WavWriter::new(Box::new(BufWriter::new(file)), sample_rate, channels)
+    }
+
+    pub fn new(
+        mut sink: Box<dyn WavSink>,
+        sample_rate: u32,
+        channels: u16,
+    ) -> io::Result<WavWriter> {
+        let block_align = channels * 2;
+        sink.write_all(b"RIFF")?;
+        sink.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        sink.write_all(b"WAVEfmt ")?;
+        sink.write_all(&16u32.to_le_bytes())?;
+        sink.write_all(&1u16.to_le_bytes())?; // PCM
+        sink.write_all(&channels.to_le_bytes())?;
+        sink.write_all(&sample_rate.to_le_bytes())?;
+        sink.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        sink.write_all(&block_align.to_le_bytes())?;
+        sink.write_all(&16u16.to_le_bytes())?; // bits per sample
+        sink.write_all(b"data")?;
+        sink.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter {
+            sink,
+            data_size: 0,
+            error: None,
+        })
+    }
+
+    /// Append samples, errors are kept for `finish` so the APU can keep running
+    pub fn write(&mut self, samples: &[i16]) {
+        if self.error.is_some() {
+            return;
+        }
+        for sample in samples {
+            if let Err(error) = self.sink.write_all(&sample.to_le_bytes()) {
+                self.error = Some(error);
+                return;
+            }
+            self.data_size += 2;
+        }
+    }
+
+    /// Fill in the header sizes and flush the file
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        self.sink.seek(SeekFrom::Start(4))?;
+        self.sink
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.sink
+            .seek(SeekFrom::Start(u64::from(HEADER_SIZE) - 4))?;
+        self.sink.write_all(&self.data_size.to_le_bytes())?;
+        self.sink.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// A cursor the test can still read after the writer took it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Cursor<Vec<u8>>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Shared {
+        fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+            self.0.lock().unwrap().seek(position)
+        }
+    }
+
+    #[test]
+    fn test_wav_header() {
+        let shared = Shared::default();
+        let mut writer = WavWriter::new(Box::new(shared.clone()), 44100, 2).unwrap();
+        writer.write(&[1, -1, 2, -2]);
+        writer.finish().unwrap();
+
+        let bytes = shared.0.lock().unwrap().get_ref().clone();
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(bytes[4..8], 44u32.to_le_bytes());
+        assert_eq!(bytes[22..24], 2u16.to_le_bytes());
+        assert_eq!(bytes[24..28], 44100u32.to_le_bytes());
+        assert_eq!(bytes[40..44], 8u32.to_le_bytes());
+        assert_eq!(bytes[44..46], 1i16.to_le_bytes());
+        assert_eq!(bytes[46..48], (-1i16).to_le_bytes());
+    }
+}
@@ -0,0 +1,210 @@
+//! The wave channel, channel 3, plays 32 4-bit samples from wave RAM at 0xFF30-0xFF3F.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::length::LengthCounter;
+
+pub const WAVE_RAM_SIZE: usize = 16;
+
+pub struct Wave {
+    length: LengthCounter,
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    position: u8,
+    ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Wave {
+    pub fn new() -> Wave {
+        Wave {
+            length: LengthCounter::new(256),
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    /// Read register NR30-NR34, `register` is the offset from NR30
+    pub fn read(&self, register: u16) -> u8 {
+        match register {
+            0 => (u8::from(self.dac_enabled) << 7) | 0x7F,
+            2 => (self.volume_code << 5) | 0x9F,
+            4 => (u8::from(self.length.is_enabled()) << 6) | 0xBF,
+            _ => 0xFF,
+        }
+    }
+
+    /// Write register NR30-NR34, `register` is the offset from NR30
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(u16::from(value)),
+            2 => self.volume_code = (value >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | u16::from(value),
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (u16::from(value & 0x07) << 8);
+                self.length.set_enabled(value & 0x40 != 0);
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn read_ram(&self, index: usize) -> u8 {
+        self.ram[index]
+    }
+
+    pub fn write_ram(&mut self, index: usize, value: u8) {
+        self.ram[index] = value;
+    }
+
+    /// Advance the frequency timer by one T-cycle
+    pub fn tick(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            self.position = (self.position + 1) % 32;
+        }
+    }
+
+    /// The current output level, 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        let byte = self.ram[usize::from(self.position / 2)];
+        let sample = if self.position.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        };
+
+        match self.volume_code {
+            0 => 0,
+            code => sample >> (code - 1),
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.timer = self.period();
+        self.position = 0;
+    }
+
+    /// T-cycles per sample
+    fn period(&self) -> u32 {
+        (2048 - u32::from(self.frequency)) * 2
+    }
+}
+
+impl SaveState for Wave {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.length.save_state(state);
+        state.write_bool(self.enabled);
+        state.write_bool(self.dac_enabled);
+        state.write_u8(self.volume_code);
+        state.write_u16(self.frequency);
+        state.write_u32(self.timer);
+        state.write_u8(self.position);
+        state.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.length.load_state(state)?;
+        self.enabled = state.read_bool()?;
+        self.dac_enabled = state.read_bool()?;
+        self.volume_code = state.read_u8()? & 0x03;
+        self.frequency = state.read_u16()? & 0x7FF;
+        self.timer = state.read_u32()?;
+        self.position = state.read_u8()? % 32;
+        state.read_into(&mut self.ram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triggered(volume_code: u8) -> Wave {
+        let mut wave = Wave::new();
+        for index in 0..WAVE_RAM_SIZE {
+            wave.write_ram(index, 0x8F);
+        }
+        wave.write(0, 0x80);
+        wave.write(2, volume_code << 5);
+        wave.write(3, 0xFF);
+        wave.write(4, 0x87);
+        wave
+    }
+
+    #[test]
+    fn test_samples() {
+        let mut wave = triggered(1);
+        assert_eq!(wave.output(), 0x8);
+
+        wave.tick();
+        wave.tick();
+        assert_eq!(wave.output(), 0xF);
+    }
+
+    #[test]
+    fn test_volume_shift() {
+        assert_eq!(triggered(0).output(), 0);
+        assert_eq!(triggered(2).output(), 4);
+        assert_eq!(triggered(3).output(), 2);
+    }
+
+    #[test]
+    fn test_dac_off() {
+        let mut wave = triggered(1);
+        wave.write(0, 0x00);
+
+        assert!(!wave.is_enabled());
+        assert_eq!(wave.read(0), 0x7F);
+    }
+
+    #[test]
+    fn test_length() {
+        let mut wave = triggered(1);
+        wave.write(1, 0xFF);
+        wave.write(4, 0x47);
+
+        wave.clock_length();
+        assert!(!wave.is_enabled());
+    }
+}
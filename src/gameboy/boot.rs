@@ -0,0 +1,30 @@
+//! The DMG boot ROM, mapped over 0x0000-0x00FF until a non-zero value is written to 0xFF50.
+//!
+//! Without a boot ROM the registers are set to the values it leaves behind.
+
+use super::{
+    apu::{NR11, NR12, NR50, NR51, NR52},
+    interrupts::IF,
+    joypad::P1,
+    ppu::{BGP, LCDC, STAT},
+};
+
+pub const BOOT: u16 = 0xFF50;
+pub const BOOT_ROM_SIZE: usize = 0x100;
+
+/// The internal timer counter when the boot ROM hands over, DIV reads 0xAB
+pub const POST_BOOT_TIMER_COUNTER: u16 = 0xABCC;
+
+/// IO register writes that reproduce the state the boot ROM leaves behind, in order
+pub const POST_BOOT_IO: [(u16, u8); 10] = [
+    (P1, 0x00),
+    (NR52, 0x80), // the APU has to be powered before the other sound registers can be written
+    (NR50, 0x77),
+    (NR51, 0xF3),
+    (NR11, 0x80),
+    (NR12, 0xF3),
+    (LCDC, 0x91),
+    (STAT, 0x85),
+    (BGP, 0xFC),
+    (IF, 0xE1),
+];
@@ -0,0 +1,454 @@
+//! Parsing and validation of the cartridge header located at 0x0100-0x014F of every ROM.
+//!
+//! The boot ROM refuses to start a cartridge whose Nintendo logo or header checksum is wrong,
+//! the global checksum over the whole ROM is never checked by the hardware.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::utils::CartridgeError;
+
+const ENTRY_POINT: usize = 0x0100;
+const LOGO_START: usize = 0x0104;
+const TITLE_START: usize = 0x0134;
+const TITLE_END: usize = 0x0143;
+const CGB_FLAG: usize = 0x0143; // the last title byte on cartridges made after the CGB
+const NEW_LICENSEE: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+const RAM_SIZE: usize = 0x0149;
+const OLD_LICENSEE: usize = 0x014B;
+const VERSION: usize = 0x014C;
+const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E;
+
+/// Old licensee code meaning the new licensee code is used instead
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+/// The logo the boot ROM scrolls down and compares, at 0x0104-0x0133
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+pub const HEADER_END: usize = 0x014F;
+
+/// The information in the cartridge header
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub title: String,
+    pub cgb_flag: u8,
+    pub sgb_flag: u8,
+    pub cartridge_type: u8,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    /// The old licensee code, or the two characters of the new one when the old one is 0x33
+    pub licensee: String,
+    /// Whether `licensee` is the new licensee code
+    pub new_licensee: bool,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl Header {
+    /// Parse the header of a ROM
+    pub fn parse(rom: &[u8]) -> Result<Header, CartridgeError> {
+        if rom.len() <= HEADER_END {
+            return Err(CartridgeError::RomTooSmall(rom.len()));
+        }
+
+        let cgb_flag = rom[CGB_FLAG];
+        let title_end = if cgb_flag & 0x80 != 0 {
+            CGB_FLAG - 1
+        } else {
+            TITLE_END
+        };
+        let title = rom[TITLE_START..=title_end]
+            .iter()
+            .take_while(|&&byte| byte != 0)
+            .map(|&byte| byte as char)
+            .collect();
+
+        let new_licensee = rom[OLD_LICENSEE] == USE_NEW_LICENSEE;
+        let licensee = if new_licensee {
+            rom[NEW_LICENSEE..NEW_LICENSEE + 2]
+                .iter()
+                .map(|&byte| byte as char)
+                .collect()
+        } else {
+            format!("{:02X}", rom[OLD_LICENSEE])
+        };
+
+        Ok(Header {
+            title,
+            cgb_flag,
+            sgb_flag: rom[SGB_FLAG],
+            cartridge_type: rom[CARTRIDGE_TYPE],
+            rom_size: 0x8000usize
+                .checked_shl(u32::from(rom[ROM_SIZE]))
+                .unwrap_or(0),
+            ram_size: ram_size(rom[RAM_SIZE]),
+            licensee,
+            new_licensee,
+            version: rom[VERSION],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM], rom[GLOBAL_CHECKSUM + 1]]),
+        })
+    }
+
+    /// Check what the boot ROM checks, the logo and the header checksum, and that the ROM is
+    /// as large as the header says
+    pub fn validate(rom: &[u8]) -> Result<(), CartridgeError> {
+        let header = Header::parse(rom)?;
+        if rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()] != NINTENDO_LOGO {
+            return Err(CartridgeError::InvalidLogo);
+        }
+        let expected = header_checksum(rom);
+        if header.header_checksum != expected {
+            return Err(CartridgeError::HeaderChecksumMismatch {
+                expected,
+                actual: header.header_checksum,
+            });
+        }
+        if rom.len() < header.rom_size {
+            return Err(CartridgeError::RomSizeMismatch {
+                expected: header.rom_size,
+                actual: rom.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the global checksum matches `rom`, which real hardware ignores
+    pub fn global_checksum_valid(&self, rom: &[u8]) -> bool {
+        self.global_checksum == global_checksum(rom)
+    }
+
+    /// Whether the game runs in CGB mode, 0x80 supports both models and 0xC0 is CGB only
+    pub fn supports_cgb(&self) -> bool {
+        matches!(self.cgb_flag, 0x80 | 0xC0)
+    }
+
+    /// Whether the game supports the Super Game Boy's features
+    pub fn supports_sgb(&self) -> bool {
+        self.sgb_flag == 0x03
+    }
+
+    /// The name of the cartridge type, "Unknown" for types that don't exist
+    pub fn cartridge_type_name(&self) -> &'static str {
+        match self.cartridge_type {
+            0x00 => "ROM ONLY",
+            0x01 => "MBC1",
+            0x02 => "MBC1+RAM",
+            0x03 => "MBC1+RAM+BATTERY",
+            0x05 => "MBC2",
+            0x06 => "MBC2+BATTERY",
+            0x08 => "ROM+RAM",
+            0x09 => "ROM+RAM+BATTERY",
+            0x0B => "MMM01",
+            0x0C => "MMM01+RAM",
+            0x0D => "MMM01+RAM+BATTERY",
+            0x0F => "MBC3+TIMER+BATTERY",
+            0x10 => "MBC3+TIMER+RAM+BATTERY",
+            0x11 => "MBC3",
+            0x12 => "MBC3+RAM",
+            0x13 => "MBC3+RAM+BATTERY",
+            0x19 => "MBC5",
+            0x1A => "MBC5+RAM",
+            0x1B => "MBC5+RAM+BATTERY",
+            0x1C => "MBC5+RUMBLE",
+            0x1D => "MBC5+RUMBLE+RAM",
+            0x1E => "MBC5+RUMBLE+RAM+BATTERY",
+            0x20 => "MBC6",
+            0x22 => "MBC7+SENSOR+RUMBLE+RAM+BATTERY",
+            0xFC => "POCKET CAMERA",
+            0xFD => "BANDAI TAMA5",
+            0xFE => "HuC3",
+            0xFF => "HuC1+RAM+BATTERY",
+            _ => "Unknown",
+        }
+    }
+
+    /// The publisher's name, for the most common licensee codes
+    ///
+    /// The old and new codes are separate tables, the same code can name different publishers
+    pub fn licensee_name(&self) -> Option<&'static str> {
+        let name = if self.new_licensee {
+            match self.licensee.as_str() {
+                "00" => "None",
+                "01" | "31" => "Nintendo",
+                "08" => "Capcom",
+                "13" | "69" => "Electronic Arts",
+                "18" | "38" => "Hudson Soft",
+                "19" => "B-AI",
+                "20" => "KSS",
+                "28" => "Kemco",
+                "32" => "Bandai",
+                "34" | "54" | "A4" => "Konami",
+                "41" => "Ubi Soft",
+                "42" => "Atlus",
+                "51" => "Acclaim",
+                "52" => "Activision",
+                "56" => "LJN",
+                "60" => "Titus",
+                "64" => "Lucasfilm Games",
+                "67" => "Ocean",
+                "70" => "Infogrames",
+                "78" => "THQ",
+                "79" => "Accolade",
+                "86" => "Tokuma Shoten",
+                "91" => "Chunsoft",
+                "93" => "Ocean/Acclaim",
+                "99" => "Pack-In-Video",
+                _ => return None,
+            }
+        } else {
+            match self.licensee.as_str() {
+                "00" => "None",
+                "01" | "31" => "Nintendo",
+                "08" | "38" => "Capcom",
+                "09" => "Hot-B",
+                "0A" => "Jaleco",
+                "13" | "69" => "Electronic Arts",
+                "18" => "Hudson Soft",
+                "19" => "ITC Entertainment",
+                "28" | "7F" => "Kemco",
+                "34" | "A4" => "Konami",
+                "41" => "Ubi Soft",
+                "42" | "EB" => "Atlus",
+                "4A" => "Virgin Games",
+                "51" | "B0" => "Acclaim",
+                "52" => "Activision",
+                "56" => "LJN",
+                "5B" => "Romstar",
+                "60" => "Titus",
+                "67" => "Ocean",
+                "70" => "Infogrames",
+                "78" => "THQ",
+                "79" => "Accolade",
+                "8F" => "I'Max",
+                "A7" => "Takara",
+                "AF" => "Namco",
+                "B1" => "ASCII or Nexsoft",
+                "B2" => "Bandai",
+                "B6" => "HAL Laboratory",
+                "BF" => "Sammy",
+                "C0" => "Taito",
+                "C3" => "Squaresoft",
+                "C5" => "Data East",
+                "C8" => "Koei",
+                "DA" => "Tomy",
+                "E7" => "Athena",
+                "E9" => "Natsume",
+                "EC" => "Epic/Sony Records",
+                _ => return None,
+            }
+        };
+        Some(name)
+    }
+
+    /// Whether the cartridge type declares a battery
+    pub fn has_battery(&self) -> bool {
+        matches!(
+            self.cartridge_type,
+            0x03 | 0x06 | 0x09 | 0x0D | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E | 0x22 | 0xFF
+        )
+    }
+
+    /// Whether the cartridge type declares a real-time clock
+    pub fn has_rtc(&self) -> bool {
+        matches!(self.cartridge_type, 0x0F | 0x10)
+    }
+
+    /// Whether the cartridge type declares a rumble motor
+    pub fn has_rumble(&self) -> bool {
+        matches!(self.cartridge_type, 0x1C..=0x1E)
+    }
+}
+
+/// The header checksum the boot ROM computes over 0x0134-0x014C
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[TITLE_START..HEADER_CHECKSUM]
+        .iter()
+        .fold(0u8, |checksum, &byte| {
+            checksum.wrapping_sub(byte).wrapping_sub(1)
+        })
+}
+
+/// Store the header checksum of `rom` after changing its header
+pub fn fix_header_checksum(rom: &mut [u8]) {
+    rom[HEADER_CHECKSUM] = header_checksum(rom);
+}
+
+/// A 32KB ROM only cartridge the boot ROM accepts, its entry point jumps to `program` right
+/// after the header at 0x0150. For tests and benchmarks, call `fix_header_checksum` after
+/// changing header fields
+#[doc(hidden)]
+pub fn test_rom(program: &[u8]) -> Vec<u8> {
+    let program_start = HEADER_END + 1;
+    let mut rom = vec![0; 0x8000];
+    // NOP; JP 0x0150
+    rom[ENTRY_POINT..LOGO_START].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]);
+    rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+    rom[program_start..program_start + program.len()].copy_from_slice(program);
+    fix_header_checksum(&mut rom);
+    rom
+}
+
+/// The sum of every ROM byte except the global checksum itself
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(index, _)| !matches!(index, &GLOBAL_CHECKSUM | 0x014F))
+        .fold(0u16, |checksum, (_, &byte)| {
+            checksum.wrapping_add(u16::from(byte))
+        })
+}
+
+/// Size in bytes of the external RAM for a header RAM size code
+fn ram_size(code: u8) -> usize {
+    match code {
+        0x01 => 0x800,
+        0x02 => 0x2000,
+        0x03 => 0x8000,
+        0x04 => 0x20000,
+        0x05 => 0x10000,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_rom(cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[TITLE_START..TITLE_START + 4].copy_from_slice(b"TEST");
+        rom[CARTRIDGE_TYPE] = cartridge_type;
+        rom[ROM_SIZE] = rom_size;
+        rom[RAM_SIZE] = ram_size;
+        rom
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = Header::parse(&make_rom(0x13, 0x06, 0x03)).unwrap();
+
+        assert_eq!(header.title, "TEST");
+        assert_eq!(header.cartridge_type, 0x13);
+        assert_eq!(header.rom_size, 0x200000);
+        assert_eq!(header.ram_size, 0x8000);
+        assert!(header.has_battery());
+        assert!(!header.has_rtc());
+    }
+
+    #[test]
+    fn test_cgb_flag() {
+        let mut rom = make_rom(0x00, 0, 0);
+        assert!(!Header::parse(&rom).unwrap().supports_cgb());
+
+        rom[CGB_FLAG] = 0xC0;
+        let header = Header::parse(&rom).unwrap();
+        assert!(header.supports_cgb());
+        assert_eq!(header.title, "TEST");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut rom = test_rom(&[]);
+        let [high, low] = global_checksum(&rom).to_be_bytes();
+        rom[GLOBAL_CHECKSUM] = high;
+        rom[GLOBAL_CHECKSUM + 1] = low;
+        assert!(Header::validate(&rom).is_ok());
+        assert!(Header::parse(&rom).unwrap().global_checksum_valid(&rom));
+
+        let mut corrupt = rom.clone();
+        corrupt[0x0200] = 0xFF;
+        assert!(Header::validate(&corrupt).is_ok());
+        assert!(!Header::parse(&corrupt)
+            .unwrap()
+            .global_checksum_valid(&corrupt));
+
+        let mut corrupt = rom.clone();
+        corrupt[TITLE_START] = b'X';
+        assert!(matches!(
+            Header::validate(&corrupt),
+            Err(CartridgeError::HeaderChecksumMismatch { .. })
+        ));
+
+        let mut corrupt = rom.clone();
+        corrupt[LOGO_START] = 0;
+        assert!(matches!(
+            Header::validate(&corrupt),
+            Err(CartridgeError::InvalidLogo)
+        ));
+
+        assert!(matches!(
+            Header::validate(&rom[..0x4000]),
+            Err(CartridgeError::RomSizeMismatch {
+                expected: 0x8000,
+                actual: 0x4000
+            })
+        ));
+    }
+
+    #[test]
+    fn test_licensee() {
+        let mut rom = make_rom(0x00, 0, 0);
+        rom[OLD_LICENSEE] = 0x01;
+        assert_eq!(
+            Header::parse(&rom).unwrap().licensee_name(),
+            Some("Nintendo")
+        );
+
+        rom[OLD_LICENSEE] = USE_NEW_LICENSEE;
+        rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(b"A4");
+        let header = Header::parse(&rom).unwrap();
+        assert_eq!(header.licensee, "A4");
+        assert!(header.new_licensee);
+        assert_eq!(header.licensee_name(), Some("Konami"));
+    }
+
+    #[test]
+    fn test_licensee_tables() {
+        let name = |old: u8, new: &[u8; 2]| {
+            let mut rom = make_rom(0x00, 0, 0);
+            rom[OLD_LICENSEE] = old;
+            rom[NEW_LICENSEE..NEW_LICENSEE + 2].copy_from_slice(new);
+            Header::parse(&rom).unwrap().licensee_name()
+        };
+
+        assert_eq!(name(0x19, b"00"), Some("ITC Entertainment"));
+        assert_eq!(name(USE_NEW_LICENSEE, b"19"), Some("B-AI"));
+        assert_eq!(name(0x38, b"00"), Some("Capcom"));
+        assert_eq!(name(USE_NEW_LICENSEE, b"38"), Some("Hudson Soft"));
+        assert_eq!(name(0x52, b"00"), Some("Activision"));
+        assert_eq!(name(USE_NEW_LICENSEE, b"52"), Some("Activision"));
+        assert_eq!(name(0x56, b"00"), Some("LJN"));
+        assert_eq!(name(USE_NEW_LICENSEE, b"56"), Some("LJN"));
+    }
+
+    #[test]
+    fn test_invalid_rom_size_code() {
+        assert_eq!(Header::parse(&make_rom(0x00, 0x52, 0)).unwrap().rom_size, 0);
+    }
+
+    #[test]
+    fn test_parse_too_small() {
+        assert!(Header::parse(&[0; 0x100]).is_err());
+    }
+
+    #[test]
+    fn test_has_rtc() {
+        assert!(Header::parse(&make_rom(0x10, 0, 0)).unwrap().has_rtc());
+        assert!(!Header::parse(&make_rom(0x00, 0, 0)).unwrap().has_rtc());
+    }
+
+    #[test]
+    fn test_has_rumble() {
+        assert!(Header::parse(&make_rom(0x1E, 0, 0)).unwrap().has_rumble());
+        assert!(!Header::parse(&make_rom(0x1B, 0, 0)).unwrap().has_rumble());
+    }
+}
@@ -0,0 +1,171 @@
+//! Hudson's HuC1, an MBC1 without the banking mode but with an infrared port.
+//!
+//! Writing 0x0E to 0x0000-0x1FFF maps the infrared port over 0xA000-0xBFFF instead of RAM, any
+//! other value maps the RAM back, there is no RAM enable. Nothing is ever on the other end of
+//! the port, it always reads as no light received.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{Mbc, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// Written to 0x0000-0x1FFF to map the infrared port
+const IR_MODE: u8 = 0x0E;
+/// The infrared port with no light received
+const IR_DARK: u8 = 0xC0;
+
+pub struct Huc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ir_mode: bool,
+    rom_bank: usize, // 6 bits
+    ram_bank: usize, // 2 bits
+}
+
+impl Huc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Huc1 {
+        Huc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ir_mode: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+        let index = self.ram_bank * RAM_BANK_SIZE + usize::from(address - 0xA000);
+        Some(index % self.ram.len())
+    }
+}
+
+impl Mbc for Huc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let index = match address {
+            0x0000..=0x3FFF => usize::from(address),
+            _ => self.rom_bank * ROM_BANK_SIZE + usize::from(address - 0x4000),
+        };
+        self.rom
+            .get(index % self.rom.len())
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = value & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => {
+                self.rom_bank = usize::from(value & 0x3F);
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank = usize::from(value & 0x03);
+                tracing::debug!(target: "mbc", bank = self.ram_bank, "RAM bank switched");
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if self.ir_mode {
+            return IR_DARK;
+        }
+        self.ram_index(address)
+            .map(|index| self.ram[index])
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if self.ir_mode {
+            tracing::trace!(target: "mbc", led = value & 0x01 != 0, "infrared LED");
+            return;
+        }
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_enabled(&self) -> bool {
+        !self.ir_mode
+    }
+}
+
+impl SaveState for Huc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bool(self.ir_mode);
+        state.write_u8(self.rom_bank as u8);
+        state.write_u8(self.ram_bank as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)?;
+        self.ir_mode = state.read_bool()?;
+        self.rom_bank = usize::from(state.read_u8()?);
+        self.ram_bank = usize::from(state.read_u8()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_mbc() -> Huc1 {
+        let mut rom = vec![0; ROM_BANK_SIZE * 64];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        Huc1::new(rom, RAM_BANK_SIZE * 4)
+    }
+
+    #[test]
+    fn test_rom_and_ram_banks() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x2000, 0x25);
+        assert_eq!(mbc.read_rom(0x4000), 0x25);
+
+        // RAM needs no enable
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(0xA000, 0x11);
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+    }
+
+    #[test]
+    fn test_ir_mode() {
+        let mut mbc = make_mbc();
+        mbc.write_ram(0xA000, 0x11);
+        mbc.write_rom(0x0000, 0x0E);
+        assert!(!mbc.ram_enabled());
+        assert_eq!(mbc.read_ram(0xA000), IR_DARK);
+
+        // the LED doesn't reach RAM
+        mbc.write_ram(0xA000, 0x01);
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+    }
+}
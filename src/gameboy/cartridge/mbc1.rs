@@ -0,0 +1,242 @@
+//! The MBC1 memory bank controller, with up to 2MB ROM and 32KB RAM.
+//!
+//! Multicart compilations (MBC1M) wire the upper bank bits one lower, as bits 4-5 of the ROM
+//! bank, so each game gets 16 banks. Their boards can't be told apart from the header, a 1MB ROM
+//! with a second Nintendo logo at bank 0x10 is taken to be one.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{header::NINTENDO_LOGO, Mbc, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// Size of the multicart ROMs, four games of 256KB
+const MULTICART_ROM_SIZE: usize = 0x100000;
+/// Where the logo of each game's header is within its first bank
+const LOGO_START: usize = 0x0104;
+
+/// Whether an MBC1 ROM is a multicart compilation, with the header of a second game at bank 0x10
+pub fn is_multicart(rom: &[u8]) -> bool {
+    let logo = 0x10 * ROM_BANK_SIZE + LOGO_START;
+    rom.len() == MULTICART_ROM_SIZE && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+}
+
+pub struct Mbc1 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: usize,        // 5 bits, 0 is read as 1
+    upper_bank: usize,      // 2 bits, the RAM bank or bits 5-6 of the ROM bank
+    advanced_banking: bool, // mode 1, the upper bits also bank 0x0000-0x3FFF and RAM
+    upper_shift: usize,     // the ROM bank bit the upper bits start at, 4 on multicarts
+}
+
+impl Mbc1 {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> Mbc1 {
+        Mbc1 {
+            rom,
+            ram: vec![0; ram_size],
+            ram_enabled: false,
+            rom_bank: 1,
+            upper_bank: 0,
+            advanced_banking: false,
+            upper_shift: 5,
+        }
+    }
+
+    /// An MBC1M, which leaves out bit 4 of the ROM bank register
+    pub fn new_multicart(rom: Vec<u8>, ram_size: usize) -> Mbc1 {
+        Mbc1 {
+            upper_shift: 4,
+            ..Mbc1::new(rom, ram_size)
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let bank = if self.advanced_banking {
+            self.upper_bank
+        } else {
+            0
+        };
+        let index = bank * RAM_BANK_SIZE + usize::from(address - 0xA000);
+        Some(index % self.ram.len())
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let index = self.rom_bank(address) * ROM_BANK_SIZE + usize::from(address & 0x3FFF);
+        self.rom
+            .get(index % self.rom.len())
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF if self.advanced_banking => self.upper_bank << self.upper_shift,
+            0x0000..=0x3FFF => 0,
+            _ => {
+                let lower_mask = (1 << self.upper_shift) - 1;
+                (self.upper_bank << self.upper_shift) | (self.rom_bank & lower_mask)
+            }
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = usize::from(value & 0x1F).max(1);
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x4000..=0x5FFF => {
+                self.upper_bank = usize::from(value & 0x03);
+                tracing::debug!(target: "mbc", bank = self.upper_bank, "upper bank switched");
+            }
+            _ => self.advanced_banking = value & 0x01 != 0,
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map(|index| self.ram[index])
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
+
+impl SaveState for Mbc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.rom_bank as u8);
+        state.write_u8(self.upper_bank as u8);
+        state.write_bool(self.advanced_banking);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = usize::from(state.read_u8()?);
+        self.upper_bank = usize::from(state.read_u8()?);
+        self.advanced_banking = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_mbc() -> Mbc1 {
+        let mut rom = vec![0; ROM_BANK_SIZE * 128];
+        for bank in 0..128 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        Mbc1::new(rom, RAM_BANK_SIZE * 4)
+    }
+
+    #[test]
+    fn test_rom_bank() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x2000, 0x05);
+
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0x05);
+    }
+
+    #[test]
+    fn test_rom_bank_zero_is_one() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 0x01);
+
+        // only the low 5 bits are checked, bank 0x20 selects 0x21
+        mbc.write_rom(0x2000, 0x20);
+        mbc.write_rom(0x4000, 0x01);
+        assert_eq!(mbc.read_rom(0x4000), 0x21);
+    }
+
+    #[test]
+    fn test_upper_rom_bits() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x2000, 0x03);
+        mbc.write_rom(0x4000, 0x02);
+
+        assert_eq!(mbc.read_rom(0x4000), 0x43);
+        assert_eq!(mbc.read_rom(0x0000), 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x40);
+    }
+
+    #[test]
+    fn test_ram_banking_mode() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(0xA000, 0x11);
+
+        // simple banking always uses RAM bank 0
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 0x00);
+        mbc.write_ram(0xA000, 0x22);
+        mbc.write_rom(0x6000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+    }
+
+    #[test]
+    fn test_multicart_banks() {
+        let mut rom = vec![0; MULTICART_ROM_SIZE];
+        for bank in 0..64 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        assert!(!is_multicart(&rom));
+        let logo = 0x10 * ROM_BANK_SIZE + LOGO_START;
+        rom[logo..logo + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        assert!(is_multicart(&rom));
+
+        let mut mbc = Mbc1::new_multicart(rom, 0);
+        mbc.write_rom(0x2000, 0x13);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(mbc.read_rom(0x4000), 0x23);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_rom(0x0000), 0x20);
+
+        // bank 0x10 is not 0, so it selects the first bank of the game
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 0x20);
+    }
+
+    #[test]
+    fn test_ram_disabled() {
+        let mut mbc = make_mbc();
+        mbc.write_ram(0xA000, 0x11);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,163 @@
+//! The MBC2 memory bank controller, with up to 256KB ROM and 512 half-bytes of built-in RAM.
+//!
+//! The registers at 0x0000-0x3FFF are told apart by address bit 8: clear for the RAM enable,
+//! set for the ROM bank. The RAM only holds the low nibble of each byte, the upper nibble reads
+//! as 1, and it repeats across 0xA000-0xBFFF.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{Mbc, ROM_BANK_SIZE};
+
+/// Half-bytes of built-in RAM, the header declares none
+pub const MBC2_RAM_SIZE: usize = 512;
+
+/// Address bit 8, which selects the ROM bank register
+const ROM_BANK_SELECT: u16 = 1 << 8;
+
+pub struct Mbc2 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    ram_enabled: bool,
+    rom_bank: usize, // 4 bits, 0 is read as 1
+}
+
+impl Mbc2 {
+    pub fn new(rom: Vec<u8>) -> Mbc2 {
+        Mbc2 {
+            rom,
+            ram: vec![0; MBC2_RAM_SIZE],
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        self.ram_enabled
+            .then(|| usize::from(address - 0xA000) % MBC2_RAM_SIZE)
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let index = match address {
+            0x0000..=0x3FFF => usize::from(address),
+            _ => self.rom_bank * ROM_BANK_SIZE + usize::from(address - 0x4000),
+        };
+        self.rom
+            .get(index % self.rom.len())
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF if address & ROM_BANK_SELECT != 0 => {
+                self.rom_bank = usize::from(value & 0x0F).max(1);
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x0000..=0x3FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map(|index| 0xF0 | self.ram[index])
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value & 0x0F;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+}
+
+impl SaveState for Mbc2 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bool(self.ram_enabled);
+        state.write_u8(self.rom_bank as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = usize::from(state.read_u8()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_mbc() -> Mbc2 {
+        let mut rom = vec![0; ROM_BANK_SIZE * 16];
+        for bank in 0..16 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        Mbc2::new(rom)
+    }
+
+    #[test]
+    fn test_rom_bank_needs_bit_8() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x2100, 0x05);
+        assert_eq!(mbc.read_rom(0x4000), 0x05);
+
+        // with bit 8 clear it is the RAM enable
+        mbc.write_rom(0x2000, 0x07);
+        assert_eq!(mbc.read_rom(0x4000), 0x05);
+        mbc.write_rom(0x0100, 0x10);
+        assert_eq!(mbc.read_rom(0x4000), 0x01);
+    }
+
+    #[test]
+    fn test_ram_enable_needs_bit_8_clear() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x0100, 0x0A);
+        mbc.write_ram(0xA000, 0x05);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        mbc.write_rom(0x3E00, 0x0A);
+        mbc.write_ram(0xA000, 0x05);
+        assert_eq!(mbc.read_ram(0xA000), 0xF5);
+    }
+
+    #[test]
+    fn test_ram_nibbles_and_mirroring() {
+        let mut mbc = make_mbc();
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA001, 0xAB);
+
+        assert_eq!(mbc.read_ram(0xA001), 0xFB);
+        assert_eq!(mbc.read_ram(0xA201), 0xFB);
+        assert_eq!(mbc.read_ram(0xBE01), 0xFB);
+        assert_eq!(mbc.ram()[1], 0x0B);
+    }
+}
@@ -0,0 +1,252 @@
+//! The MBC3 memory bank controller, with up to 2MB ROM, 32KB RAM and an optional real-time clock.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{
+    rtc::{Rtc, RTC_DAYS_HI, RTC_SECONDS},
+    Mbc, RAM_BANK_SIZE, ROM_BANK_SIZE,
+};
+
+pub struct Mbc3 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    rtc: Option<Rtc>,
+    ram_enabled: bool,
+    rom_bank: usize,
+    ram_bank: u8, // 0x00-0x03 selects a RAM bank, 0x08-0x0C an RTC register
+}
+
+impl Mbc3 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rtc: bool) -> Mbc3 {
+        Mbc3 {
+            rom,
+            ram: vec![0; ram_size],
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            return None;
+        }
+
+        let index = usize::from(self.ram_bank) * RAM_BANK_SIZE + usize::from(address - 0xA000);
+        Some(index % self.ram.len())
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let index = match address {
+            0x0000..=0x3FFF => usize::from(address),
+            _ => self.rom_bank * ROM_BANK_SIZE + usize::from(address - 0x4000),
+        };
+        self.rom
+            .get(index % self.rom.len())
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                let bank = usize::from(value & 0x7F);
+                self.rom_bank = if bank == 0 { 1 } else { bank };
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank = value;
+                tracing::debug!(target: "mbc", bank = self.ram_bank, "RAM bank switched");
+            }
+            _ => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(value);
+                }
+            }
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match self.ram_bank {
+            0x00..=0x03 => self
+                .ram_index(address)
+                .map(|index| self.ram[index])
+                .unwrap_or(0xFF),
+            RTC_SECONDS..=RTC_DAYS_HI => self
+                .rtc
+                .as_ref()
+                .map(|rtc| rtc.read(self.ram_bank))
+                .unwrap_or(0xFF),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        match self.ram_bank {
+            0x00..=0x03 => {
+                if let Some(index) = self.ram_index(address) {
+                    self.ram[index] = value;
+                }
+            }
+            RTC_SECONDS..=RTC_DAYS_HI => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write(self.ram_bank, value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+}
+
+impl SaveState for Mbc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bool(self.ram_enabled);
+        state.write_u16(self.rom_bank as u16);
+        state.write_u8(self.ram_bank);
+        if let Some(rtc) = self.rtc.as_ref() {
+            rtc.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = usize::from(state.read_u16()?);
+        self.ram_bank = state.read_u8()?;
+        if let Some(rtc) = self.rtc.as_mut() {
+            rtc.load_state(state)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::cartridge::rtc::{RTC_HOURS, RTC_MINUTES};
+
+    fn make_mbc(has_rtc: bool) -> Mbc3 {
+        let mut rom = vec![0; ROM_BANK_SIZE * 128];
+        for bank in 0..128 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+        Mbc3::new(rom, RAM_BANK_SIZE * 4, has_rtc)
+    }
+
+    #[test]
+    fn test_rom_banking() {
+        let mut mbc = make_mbc(false);
+        assert_eq!(mbc.read_rom(0x0000), 0);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(mbc.read_rom(0x4000), 0x7F);
+
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(mbc.read_rom(0x4000), 1);
+    }
+
+    #[test]
+    fn test_ram_enable() {
+        let mut mbc = make_mbc(false);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(0xA000, 0x12);
+        assert_eq!(mbc.read_ram(0xA000), 0x12);
+
+        mbc.write_rom(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_ram_banking() {
+        let mut mbc = make_mbc(false);
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, 0x00);
+        mbc.write_ram(0xA000, 0x11);
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(0xA000, 0x33);
+
+        assert_eq!(mbc.read_ram(0xA000), 0x33);
+        mbc.write_rom(0x4000, 0x00);
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+    }
+
+    #[test]
+    fn test_rtc_registers() {
+        let mut mbc = make_mbc(true);
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, RTC_HOURS);
+        mbc.write_ram(0xA000, 5);
+        mbc.write_rom(0x4000, RTC_MINUTES);
+        mbc.write_ram(0xA000, 30);
+
+        // not latched yet
+        assert_eq!(mbc.read_ram(0xA000), 0);
+
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(0xA000), 30);
+        mbc.write_rom(0x4000, RTC_HOURS);
+        assert_eq!(mbc.read_ram(0xA000), 5);
+    }
+
+    #[test]
+    fn test_rtc_absent() {
+        let mut mbc = make_mbc(false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, RTC_SECONDS);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+        assert!(mbc.rtc().is_none());
+    }
+}
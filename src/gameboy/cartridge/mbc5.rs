@@ -0,0 +1,198 @@
+//! The MBC5 memory bank controller, with up to 8MB ROM, 128KB RAM and an optional rumble motor.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::{Mbc, RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const RUMBLE_BIT: u8 = 0x08;
+
+pub struct Mbc5 {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    has_rumble: bool,
+    rumble_active: bool,
+    ram_enabled: bool,
+    rom_bank: usize, // 9 bits
+    ram_bank: usize, // 4 bits, 3 on rumble cartridges
+}
+
+impl Mbc5 {
+    pub fn new(rom: Vec<u8>, ram_size: usize, has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            rom,
+            ram: vec![0; ram_size],
+            has_rumble,
+            rumble_active: false,
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+        }
+    }
+
+    fn ram_index(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram.is_empty() {
+            return None;
+        }
+
+        let index = self.ram_bank * RAM_BANK_SIZE + usize::from(address - 0xA000);
+        Some(index % self.ram.len())
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_rom(&self, address: u16) -> u8 {
+        let index = match address {
+            0x0000..=0x3FFF => usize::from(address),
+            _ => self.rom_bank * ROM_BANK_SIZE + usize::from(address - 0x4000),
+        };
+        self.rom
+            .get(index % self.rom.len())
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn rom_bank(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => 0,
+            _ => self.rom_bank,
+        }
+    }
+
+    fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x2FFF => {
+                self.rom_bank = (self.rom_bank & 0x100) | usize::from(value);
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (usize::from(value & 0x01) << 8);
+                tracing::debug!(target: "mbc", bank = self.rom_bank, "ROM bank switched");
+            }
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    self.rumble_active = value & RUMBLE_BIT != 0;
+                    self.ram_bank = usize::from(value & 0x07);
+                } else {
+                    self.ram_bank = usize::from(value & 0x0F);
+                }
+                tracing::debug!(target: "mbc", bank = self.ram_bank, "RAM bank switched");
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram_index(address)
+            .map(|index| self.ram[index])
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(index) = self.ram_index(address) {
+            self.ram[index] = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.ram_enabled
+    }
+
+    fn rumble_active(&self) -> bool {
+        self.rumble_active
+    }
+}
+
+impl SaveState for Mbc5 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+        state.write_bool(self.rumble_active);
+        state.write_bool(self.ram_enabled);
+        state.write_u16(self.rom_bank as u16);
+        state.write_u8(self.ram_bank as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)?;
+        self.rumble_active = state.read_bool()?;
+        self.ram_enabled = state.read_bool()?;
+        self.rom_bank = usize::from(state.read_u16()?);
+        self.ram_bank = usize::from(state.read_u8()?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_mbc(has_rumble: bool) -> Mbc5 {
+        let mut rom = vec![0; ROM_BANK_SIZE * 512];
+        for bank in 0..512 {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+        Mbc5::new(rom, RAM_BANK_SIZE * 16, has_rumble)
+    }
+
+    #[test]
+    fn test_nine_bit_rom_bank() {
+        let mut mbc = make_mbc(false);
+        mbc.write_rom(0x2000, 0x23);
+        mbc.write_rom(0x3000, 0x01);
+
+        assert_eq!(mbc.read_rom(0x4000), 0x23);
+        assert_eq!(mbc.read_rom(0x4001), 0x01);
+    }
+
+    #[test]
+    fn test_rom_bank_zero() {
+        let mut mbc = make_mbc(false);
+        mbc.write_rom(0x2000, 0x00);
+        mbc.write_rom(0x0000, 0x00);
+
+        assert_eq!(mbc.read_rom(0x4000), 0x00);
+        assert_eq!(mbc.read_rom(0x4001), 0x00);
+    }
+
+    #[test]
+    fn test_ram_banking() {
+        let mut mbc = make_mbc(false);
+        mbc.write_rom(0x0000, 0x0A);
+
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(0xA000, 0xFF);
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_ram(0xA000, 0x11);
+
+        assert_eq!(mbc.read_ram(0xA000), 0x11);
+        mbc.write_rom(0x4000, 0x0F);
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn test_rumble() {
+        let mut mbc = make_mbc(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, RUMBLE_BIT | 0x02);
+        mbc.write_ram(0xA000, 0x22);
+
+        assert!(mbc.rumble_active());
+        mbc.write_rom(0x4000, 0x02);
+        assert!(!mbc.rumble_active());
+        assert_eq!(mbc.read_ram(0xA000), 0x22);
+    }
+}
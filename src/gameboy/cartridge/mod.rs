@@ -0,0 +1,650 @@
+//! Cartridges and their memory bank controllers (MBC).
+//!
+//! The cartridge is mapped at 0x0000-0x7FFF (ROM) and 0xA000-0xBFFF (external RAM),
+//! the MBC selected from the header decides how these regions are banked.
+
+mod header;
+mod huc1;
+mod mbc1;
+mod mbc2;
+mod mbc3;
+mod mbc5;
+mod rom_only;
+mod rtc;
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::{CartridgeError, SaveStateError},
+};
+
+pub use header::{fix_header_checksum, header_checksum, test_rom, Header, NINTENDO_LOGO};
+use huc1::Huc1;
+use mbc1::Mbc1;
+use mbc2::Mbc2;
+use mbc3::Mbc3;
+use mbc5::Mbc5;
+use rom_only::RomOnly;
+pub use rtc::Rtc;
+use rtc::RTC_STATE_SIZE;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// A memory bank controller, receives all cartridge reads and writes
+///
+/// Its save state covers the banking registers and RAM, not the ROM
+pub trait Mbc: SaveState + Send {
+    /// read from 0x0000-0x7FFF
+    fn read_rom(&self, address: u16) -> u8;
+
+    /// the ROM bank mapped at `address` in 0x0000-0x7FFF, before wrapping to the ROM size
+    fn rom_bank(&self, address: u16) -> usize {
+        usize::from(address >= 0x4000)
+    }
+
+    /// write to 0x0000-0x7FFF, used to control the MBC
+    fn write_rom(&mut self, address: u16, value: u8);
+
+    /// read from 0xA000-0xBFFF
+    fn read_ram(&self, address: u16) -> u8;
+
+    /// write to 0xA000-0xBFFF
+    fn write_ram(&mut self, address: u16, value: u8);
+
+    /// the whole external RAM, as stored in a battery save file
+    fn ram(&self) -> &[u8];
+
+    /// mutable access to the external RAM, for loading a battery save
+    fn ram_mut(&mut self) -> &mut [u8];
+
+    /// whether the external RAM is currently enabled
+    fn ram_enabled(&self) -> bool {
+        true
+    }
+
+    /// the real-time clock, if the cartridge has one
+    fn rtc(&self) -> Option<&Rtc> {
+        None
+    }
+
+    /// mutable access to the real-time clock, for restoring its saved state
+    fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        None
+    }
+
+    /// whether the rumble motor is currently turned on
+    fn rumble_active(&self) -> bool {
+        false
+    }
+}
+
+/// Replaces the ROM byte at `address`, as a Game Genie does
+///
+/// With `compare` set only when the byte read is `compare`, which tells apart the banks
+/// switched into 0x4000-0x7FFF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
+}
+
+/// A loaded cartridge, the MBC is chosen from the cartridge type in the header
+///
+/// Cartridges with a battery can be attached to a save file, external RAM (and the
+/// real-time clock) is written to it whenever the game disables RAM and when the
+/// cartridge is dropped
+pub struct Cartridge {
+    header: Header,
+    mbc: Box<dyn Mbc>,
+    #[cfg(feature = "std")]
+    save_path: Option<PathBuf>,
+    patches: Vec<RomPatch>,
+}
+
+impl Cartridge {
+    /// Create a cartridge from the contents of a ROM, without the checks of `Header::validate`
+    pub fn new(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        let header = Header::parse(&rom)?;
+
+        let mbc: Box<dyn Mbc> = match header.cartridge_type {
+            0x00 | 0x08 | 0x09 => Box::new(RomOnly::new(rom, header.ram_size)),
+            0x01..=0x03 if mbc1::is_multicart(&rom) => {
+                Box::new(Mbc1::new_multicart(rom, header.ram_size))
+            }
+            0x01..=0x03 => Box::new(Mbc1::new(rom, header.ram_size)),
+            0x05 | 0x06 => Box::new(Mbc2::new(rom)),
+            0x0F..=0x13 => Box::new(Mbc3::new(rom, header.ram_size, header.has_rtc())),
+            0x19..=0x1E => Box::new(Mbc5::new(rom, header.ram_size, header.has_rumble())),
+            0xFF => Box::new(Huc1::new(rom, header.ram_size)),
+            cartridge_type => return Err(CartridgeError::UnsupportedCartridgeType(cartridge_type)),
+        };
+
+        Ok(Cartridge {
+            header,
+            mbc,
+            #[cfg(feature = "std")]
+            save_path: None,
+            patches: Vec::new(),
+        })
+    }
+
+    /// Load a cartridge from a ROM file, failing on ROMs the boot ROM would reject (see `Header::validate`)
+    ///
+    /// `.zip` files are opened at their first `.gb` or `.gbc` file and `.gz` files are unpacked.
+    /// If the cartridge has a battery, the `.sav` file next to the ROM is loaded and kept up to date,
+    /// `game.gb.gz` and `game.zip` save to `game.sav`
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Cartridge, CartridgeError> {
+        let path = path.as_ref();
+        let unpacked = if has_extension(path, "gz") {
+            path.with_extension("")
+        } else {
+            path.to_path_buf()
+        };
+        Cartridge::load(read_rom_file(path)?, unpacked.with_extension("sav"))
+    }
+
+    /// Load a cartridge from the contents of a ROM like `from_file`, keeping battery-backed data
+    /// in `save_path`
+    #[cfg(feature = "std")]
+    pub fn load(rom: Vec<u8>, save_path: impl Into<PathBuf>) -> Result<Cartridge, CartridgeError> {
+        Header::validate(&rom)?;
+        let mut cartridge = Cartridge::new(rom)?;
+        if cartridge.header.has_battery() {
+            cartridge.attach_save_file(save_path)?;
+        }
+        Ok(cartridge)
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn read_rom(&self, address: u16) -> u8 {
+        let value = self.mbc.read_rom(address);
+        self.patches
+            .iter()
+            .find(|patch| {
+                patch.address == address && patch.compare.is_none_or(|compare| compare == value)
+            })
+            .map_or(value, |patch| patch.value)
+    }
+
+    /// The ROM bank mapped at `address` in 0x0000-0x7FFF
+    pub fn rom_bank(&self, address: u16) -> usize {
+        let banks = (self.header.rom_size / ROM_BANK_SIZE).max(1);
+        self.mbc.rom_bank(address) % banks
+    }
+
+    /// Patch the ROM as seen by reads, replacing the previous patches
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.patches = patches;
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        let was_enabled = self.mbc.ram_enabled();
+        self.mbc.write_rom(address, value);
+
+        // games disable RAM once they are done saving, a good moment to persist it
+        #[cfg(feature = "std")]
+        if was_enabled && !self.mbc.ram_enabled() {
+            if let Err(error) = self.flush_save() {
+                tracing::warn!("Failed to write save file: {error}");
+            }
+        }
+    }
+
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mbc.read_ram(address)
+    }
+
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        self.mbc.write_ram(address, value)
+    }
+
+    /// Whether the cartridge's rumble motor is currently turned on
+    pub fn rumble_active(&self) -> bool {
+        self.mbc.rumble_active()
+    }
+
+    /// The serialized real-time clock state, if the cartridge has a clock
+    pub fn rtc_state(&self) -> Option<Vec<u8>> {
+        self.mbc.rtc().map(Rtc::save)
+    }
+
+    /// Restore a real-time clock state created by `rtc_state`
+    ///
+    /// Does nothing if the cartridge has no clock
+    pub fn load_rtc_state(&mut self, state: &[u8]) -> Result<(), CartridgeError> {
+        match self.mbc.rtc_mut() {
+            Some(rtc) => rtc.load(state),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the real-time clock state to a file, if the cartridge has a clock
+    #[cfg(feature = "std")]
+    pub fn save_rtc(&self, path: impl AsRef<Path>) -> Result<(), CartridgeError> {
+        if let Some(state) = self.rtc_state() {
+            fs::write(path, state)?;
+        }
+        Ok(())
+    }
+
+    /// Read the real-time clock state from a file written by `save_rtc`
+    #[cfg(feature = "std")]
+    pub fn load_rtc(&mut self, path: impl AsRef<Path>) -> Result<(), CartridgeError> {
+        if self.mbc.rtc().is_none() {
+            return Ok(());
+        }
+        let state = fs::read(path)?;
+        self.load_rtc_state(&state)
+    }
+
+    /// The battery-backed data: external RAM followed by the real-time clock state, if any
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.mbc.ram().to_vec();
+        if let Some(rtc) = self.mbc.rtc() {
+            data.extend(rtc.save());
+        }
+        data
+    }
+
+    /// Restore battery-backed data created by `save_data`
+    ///
+    /// The clock state is optional, so save files without it are accepted
+    pub fn load_save_data(&mut self, data: &[u8]) -> Result<(), CartridgeError> {
+        let ram_size = self.mbc.ram().len();
+        let has_rtc = self.mbc.rtc().is_some();
+        let rtc_state = match data.len() {
+            len if len == ram_size => None,
+            len if has_rtc && len == ram_size + RTC_STATE_SIZE => Some(&data[ram_size..]),
+            actual => {
+                return Err(CartridgeError::InvalidSaveSize {
+                    expected: ram_size,
+                    actual,
+                })
+            }
+        };
+
+        self.mbc.ram_mut().copy_from_slice(&data[..ram_size]);
+        if let Some(state) = rtc_state {
+            self.load_rtc_state(state)?;
+        }
+        Ok(())
+    }
+
+    /// Persist battery-backed data to `path`, loading it first if the file exists
+    #[cfg(feature = "std")]
+    pub fn attach_save_file(&mut self, path: impl Into<PathBuf>) -> Result<(), CartridgeError> {
+        let path = path.into();
+        if path.exists() {
+            let data = fs::read(&path)?;
+            self.load_save_data(&data)?;
+        }
+        self.save_path = Some(path);
+        Ok(())
+    }
+
+    /// Write battery-backed data to the attached save file
+    ///
+    /// Does nothing if no save file is attached or the cartridge has no battery
+    #[cfg(feature = "std")]
+    pub fn flush_save(&self) -> Result<(), CartridgeError> {
+        match &self.save_path {
+            Some(path) if self.header.has_battery() => Ok(fs::write(path, self.save_data())?),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for Cartridge {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush_save() {
+            tracing::warn!("Failed to write save file: {error}");
+        }
+    }
+}
+
+impl SaveState for Cartridge {
+    /// The ROM is not saved, the title is checked so a state is only loaded into its own game
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(self.header.title.as_bytes());
+        self.mbc.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let title = String::from_utf8_lossy(state.read_bytes()?).into_owned();
+        if title != self.header.title {
+            return Err(SaveStateError::CartridgeMismatch {
+                expected: title,
+                actual: self.header.title.clone(),
+            });
+        }
+        self.mbc.load_state(state)
+    }
+}
+
+#[cfg(feature = "std")]
+fn has_extension(path: impl AsRef<Path>, extension: &str) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|actual| actual.eq_ignore_ascii_case(extension))
+}
+
+/// The ROM in a `.gb`, `.gbc`, `.zip` or `.gz` file
+#[cfg(feature = "std")]
+pub fn read_rom_file(path: &Path) -> Result<Vec<u8>, CartridgeError> {
+    let mut rom = Vec::new();
+    if has_extension(path, "zip") {
+        let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+            let name = Path::new(file.name());
+            if has_extension(name, "gb") || has_extension(name, "gbc") {
+                file.read_to_end(&mut rom)?;
+                return Ok(rom);
+            }
+        }
+        return Err(CartridgeError::NoRomInArchive);
+    }
+    if has_extension(path, "gz") {
+        flate2::read::GzDecoder::new(fs::File::open(path)?).read_to_end(&mut rom)?;
+        return Ok(rom);
+    }
+    Ok(fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_rom(cartridge_type: u8) -> Vec<u8> {
+        let mut rom = test_rom(&[]);
+        rom.resize(ROM_BANK_SIZE * 4, 0);
+        rom[0x0147] = cartridge_type;
+        rom[0x0148] = 0x01;
+        rom[0x0149] = 0x02;
+        rom[ROM_BANK_SIZE * 2] = 0x22;
+        fix_header_checksum(&mut rom);
+        rom
+    }
+
+    #[test]
+    fn test_rom_only() {
+        let cartridge = Cartridge::new(make_rom(0x00)).unwrap();
+
+        assert_eq!(cartridge.read_rom(0x0147), 0x00);
+        assert!(cartridge.rtc_state().is_none());
+    }
+
+    #[test]
+    fn test_mbc1() {
+        let mut cartridge = Cartridge::new(make_rom(0x03)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22);
+        assert!(cartridge.header().has_battery());
+    }
+
+    #[test]
+    fn test_huc1() {
+        let mut cartridge = Cartridge::new(make_rom(0xFF)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22);
+        assert_eq!(cartridge.save_data().len(), RAM_BANK_SIZE);
+    }
+
+    #[test]
+    fn test_mbc2() {
+        let mut rom = make_rom(0x06);
+        rom[0x0149] = 0x00;
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        cartridge.write_rom(0x2100, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22);
+        assert!(cartridge.header().has_battery());
+        assert_eq!(cartridge.save_data().len(), 512);
+    }
+
+    #[test]
+    fn test_mbc3() {
+        let mut cartridge = Cartridge::new(make_rom(0x10)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22);
+        assert!(cartridge.rtc_state().is_some());
+    }
+
+    #[test]
+    fn test_mbc5() {
+        let mut cartridge = Cartridge::new(make_rom(0x1B)).unwrap();
+
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x22);
+        assert!(!cartridge.rumble_active());
+    }
+
+    #[test]
+    fn test_mbc5_rumble() {
+        let mut cartridge = Cartridge::new(make_rom(0x1C)).unwrap();
+
+        cartridge.write_rom(0x4000, 0x08);
+        assert!(cartridge.rumble_active());
+    }
+
+    #[test]
+    fn test_rom_patches() {
+        let mut cartridge = Cartridge::new(make_rom(0x01)).unwrap();
+        cartridge.set_rom_patches(vec![
+            RomPatch {
+                address: 0x0150,
+                value: 0x12,
+                compare: None,
+            },
+            RomPatch {
+                address: 0x4000,
+                value: 0x34,
+                compare: Some(0x22),
+            },
+        ]);
+
+        assert_eq!(cartridge.read_rom(0x0150), 0x12);
+        assert_eq!(cartridge.read_rom(0x4000), 0x00);
+        cartridge.write_rom(0x2000, 0x02);
+        assert_eq!(cartridge.read_rom(0x4000), 0x34);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(matches!(
+            Cartridge::new(make_rom(0xFC)),
+            Err(CartridgeError::UnsupportedCartridgeType(0xFC))
+        ));
+    }
+
+    #[test]
+    fn test_rtc_state_round_trip() {
+        let mut cartridge = Cartridge::new(make_rom(0x10)).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_rom(0x4000, 0x0A);
+        cartridge.write_ram(0xA000, 0x0C);
+        let state = cartridge.rtc_state().unwrap();
+
+        let mut restored = Cartridge::new(make_rom(0x10)).unwrap();
+        restored.load_rtc_state(&state).unwrap();
+        restored.write_rom(0x0000, 0x0A);
+        restored.write_rom(0x4000, 0x0A);
+        restored.write_rom(0x6000, 0x00);
+        restored.write_rom(0x6000, 0x01);
+        assert_eq!(restored.read_ram(0xA000), 0x0C);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gameboy_emulator_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_save_data_round_trip() {
+        let mut cartridge = Cartridge::new(make_rom(0x1B)).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        let data = cartridge.save_data();
+        assert_eq!(data.len(), RAM_BANK_SIZE);
+
+        let mut restored = Cartridge::new(make_rom(0x1B)).unwrap();
+        restored.load_save_data(&data).unwrap();
+        restored.write_rom(0x0000, 0x0A);
+        assert_eq!(restored.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn test_save_data_with_rtc() {
+        let cartridge = Cartridge::new(make_rom(0x10)).unwrap();
+        let data = cartridge.save_data();
+        assert_eq!(data.len(), RAM_BANK_SIZE + RTC_STATE_SIZE);
+
+        let mut restored = Cartridge::new(make_rom(0x10)).unwrap();
+        restored.load_save_data(&data).unwrap();
+        restored.load_save_data(&data[..RAM_BANK_SIZE]).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_save_size() {
+        let mut cartridge = Cartridge::new(make_rom(0x1B)).unwrap();
+
+        assert!(matches!(
+            cartridge.load_save_data(&[0; 16]),
+            Err(CartridgeError::InvalidSaveSize {
+                expected: RAM_BANK_SIZE,
+                actual: 16
+            })
+        ));
+    }
+
+    #[test]
+    fn test_save_file_written_on_ram_disable() {
+        let path = temp_path("ram_disable.sav");
+        let mut cartridge = Cartridge::new(make_rom(0x1B)).unwrap();
+        cartridge.attach_save_file(&path).unwrap();
+
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA001, 0x99);
+        assert!(!path.exists());
+        cartridge.write_rom(0x0000, 0x00);
+
+        let data = fs::read(&path).unwrap();
+        drop(cartridge);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(data[1], 0x99);
+    }
+
+    #[test]
+    fn test_save_file_round_trip() {
+        let rom_path = temp_path("round_trip.gb");
+        let save_path = rom_path.with_extension("sav");
+        fs::write(&rom_path, make_rom(0x1B)).unwrap();
+
+        let mut cartridge = Cartridge::from_file(&rom_path).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x24);
+        drop(cartridge);
+
+        let mut cartridge = Cartridge::from_file(&rom_path).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        let value = cartridge.read_ram(0xA000);
+        drop(cartridge);
+        fs::remove_file(&rom_path).unwrap();
+        fs::remove_file(&save_path).unwrap();
+        assert_eq!(value, 0x24);
+    }
+
+    #[test]
+    fn test_from_file_corrupt() {
+        let rom_path = temp_path("corrupt.gb");
+        let mut rom = make_rom(0x1B);
+        rom[0x0104] ^= 0xFF;
+        fs::write(&rom_path, rom).unwrap();
+
+        let result = Cartridge::from_file(&rom_path);
+        fs::remove_file(&rom_path).unwrap();
+        assert!(matches!(result, Err(CartridgeError::InvalidLogo)));
+    }
+
+    #[test]
+    fn test_from_zip() {
+        use std::io::Write;
+
+        let zip_path = temp_path("archive.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        zip.start_file("readme.txt", options).unwrap();
+        zip.write_all(b"not a rom").unwrap();
+        zip.start_file("Game.GBC", options).unwrap();
+        zip.write_all(&make_rom(0x1A)).unwrap();
+        zip.finish().unwrap();
+
+        let cartridge = Cartridge::from_file(&zip_path);
+        fs::remove_file(&zip_path).unwrap();
+        assert_eq!(cartridge.unwrap().read_rom(0x0104), NINTENDO_LOGO[0]);
+    }
+
+    #[test]
+    fn test_zip_without_rom() {
+        let zip_path = temp_path("empty.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.start_file("readme.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+
+        let result = Cartridge::from_file(&zip_path);
+        fs::remove_file(&zip_path).unwrap();
+        assert!(matches!(result, Err(CartridgeError::NoRomInArchive)));
+    }
+
+    #[test]
+    fn test_from_gzip() {
+        use std::io::Write;
+
+        let gz_path = temp_path("gzip.gb.gz");
+        let save_path = temp_path("gzip.sav");
+        let mut encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&gz_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(&make_rom(0x1B)).unwrap();
+        encoder.finish().unwrap();
+
+        let mut cartridge = Cartridge::from_file(&gz_path).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x24);
+        drop(cartridge);
+        fs::remove_file(&gz_path).unwrap();
+        // saved next to the archive as `gzip.sav`, not `gzip.gb.sav`
+        let data = fs::read(&save_path).unwrap();
+        fs::remove_file(&save_path).unwrap();
+        assert_eq!(data[0], 0x24);
+    }
+
+    #[test]
+    fn test_no_battery_no_save_file() {
+        let path = temp_path("no_battery.sav");
+        let mut cartridge = Cartridge::new(make_rom(0x1A)).unwrap();
+        cartridge.attach_save_file(&path).unwrap();
+        drop(cartridge);
+
+        assert!(!path.exists());
+    }
+}
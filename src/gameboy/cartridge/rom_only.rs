@@ -0,0 +1,84 @@
+//! Cartridges without a memory bank controller, 32KB of ROM and optionally 8KB of RAM.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+use super::Mbc;
+
+pub struct RomOnly {
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+}
+
+impl RomOnly {
+    pub fn new(rom: Vec<u8>, ram_size: usize) -> RomOnly {
+        RomOnly {
+            rom,
+            ram: vec![0; ram_size],
+        }
+    }
+}
+
+impl Mbc for RomOnly {
+    fn read_rom(&self, address: u16) -> u8 {
+        self.rom.get(usize::from(address)).copied().unwrap_or(0xFF)
+    }
+
+    fn write_rom(&mut self, _address: u16, _value: u8) {}
+
+    fn read_ram(&self, address: u16) -> u8 {
+        self.ram
+            .get(usize::from(address - 0xA000))
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Some(byte) = self.ram.get_mut(usize::from(address - 0xA000)) {
+            *byte = value;
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
+impl SaveState for RomOnly {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.ram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rom_is_read_only() {
+        let mut mbc = RomOnly::new(vec![0x12; 0x8000], 0);
+        mbc.write_rom(0x2000, 0x34);
+
+        assert_eq!(mbc.read_rom(0x2000), 0x12);
+    }
+
+    #[test]
+    fn test_missing_ram() {
+        let mut mbc = RomOnly::new(vec![0; 0x8000], 0);
+        mbc.write_ram(0xA000, 0x12);
+
+        assert_eq!(mbc.read_ram(0xA000), 0xFF);
+    }
+}
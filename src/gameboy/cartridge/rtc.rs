@@ -0,0 +1,338 @@
+//! The real-time clock found on some MBC3 cartridges.
+//!
+//! The clock keeps running in wall-clock time, also while the emulator is not running,
+//! as long as the state is persisted with `save` and restored with `load`.
+//! Without the `std` feature there is no wall clock and the clock stands still.
+
+use alloc::vec::Vec;
+#[cfg(all(feature = "std", not(all(target_arch = "wasm32", feature = "wasm"))))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::{CartridgeError, SaveStateError},
+};
+
+pub const RTC_SECONDS: u8 = 0x08;
+pub const RTC_MINUTES: u8 = 0x09;
+pub const RTC_HOURS: u8 = 0x0A;
+pub const RTC_DAYS_LO: u8 = 0x0B;
+pub const RTC_DAYS_HI: u8 = 0x0C;
+
+/// Size of the persisted state: 5 live and 5 latched registers as u32 followed by a u64 timestamp
+pub const RTC_STATE_SIZE: usize = 48;
+
+const DAY_HI_BIT: u8 = 0x01;
+const HALT_BIT: u8 = 0x40;
+const DAY_CARRY_BIT: u8 = 0x80;
+
+const SECONDS_PER_DAY: u64 = 86400;
+const DAY_OVERFLOW: u64 = 512;
+
+/// Clock registers in the order they are mapped, from 0x08 to 0x0C
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ClockRegisters {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days_lo: u8,
+    days_hi: u8,
+}
+
+impl ClockRegisters {
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            RTC_SECONDS => self.seconds,
+            RTC_MINUTES => self.minutes,
+            RTC_HOURS => self.hours,
+            RTC_DAYS_LO => self.days_lo,
+            RTC_DAYS_HI => self.days_hi,
+            _ => 0xFF,
+        }
+    }
+
+    fn to_array(self) -> [u8; 5] {
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days_lo,
+            self.days_hi,
+        ]
+    }
+
+    fn from_array(values: [u8; 5]) -> ClockRegisters {
+        ClockRegisters {
+            seconds: values[0],
+            minutes: values[1],
+            hours: values[2],
+            days_lo: values[3],
+            days_hi: values[4],
+        }
+    }
+}
+
+/// MBC3 real-time clock with latch support
+pub struct Rtc {
+    clock: ClockRegisters,
+    latched: ClockRegisters,
+    latch_armed: bool,
+    last_update: u64,
+}
+
+impl Rtc {
+    pub fn new() -> Rtc {
+        Rtc {
+            clock: ClockRegisters::default(),
+            latched: ClockRegisters::default(),
+            latch_armed: false,
+            last_update: unix_time(),
+        }
+    }
+
+    /// Read a clock register, returns the latched value
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    /// Write a clock register, modifies the running clock
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.update();
+
+        match register {
+            RTC_SECONDS => self.clock.seconds = value & 0x3F,
+            RTC_MINUTES => self.clock.minutes = value & 0x3F,
+            RTC_HOURS => self.clock.hours = value & 0x1F,
+            RTC_DAYS_LO => self.clock.days_lo = value,
+            RTC_DAYS_HI => self.clock.days_hi = value & (DAY_HI_BIT | HALT_BIT | DAY_CARRY_BIT),
+            _ => {}
+        }
+    }
+
+    /// Handle a write to the latch register, writing 0x00 followed by 0x01 latches the clock
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_armed && value == 0x01 {
+            self.update();
+            self.latched = self.clock;
+        }
+
+        self.latch_armed = value == 0x00;
+    }
+
+    /// Bring the clock up to date with the wall clock
+    pub fn update(&mut self) {
+        let now = unix_time();
+        if now > self.last_update {
+            self.advance(now - self.last_update);
+        }
+        self.last_update = now;
+    }
+
+    /// Advance the running clock by a number of seconds, unless it is halted
+    fn advance(&mut self, seconds: u64) {
+        if self.clock.days_hi & HALT_BIT != 0 {
+            return;
+        }
+
+        let days =
+            u64::from(self.clock.days_lo) | (u64::from(self.clock.days_hi & DAY_HI_BIT) << 8);
+        let total = u64::from(self.clock.seconds)
+            + u64::from(self.clock.minutes) * 60
+            + u64::from(self.clock.hours) * 3600
+            + days * SECONDS_PER_DAY
+            + seconds;
+
+        let days = total / SECONDS_PER_DAY;
+        let mut days_hi = self.clock.days_hi & !DAY_HI_BIT;
+        if days >= DAY_OVERFLOW {
+            days_hi |= DAY_CARRY_BIT;
+        }
+        let days = days % DAY_OVERFLOW;
+
+        self.clock = ClockRegisters {
+            seconds: (total % 60) as u8,
+            minutes: ((total / 60) % 60) as u8,
+            hours: ((total / 3600) % 24) as u8,
+            days_lo: days as u8,
+            days_hi: days_hi | (days >> 8) as u8,
+        };
+    }
+
+    /// Serialize the clock state, including the time it was saved at
+    pub fn save(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(RTC_STATE_SIZE);
+        for value in self.clock.to_array().iter().chain(&self.latched.to_array()) {
+            state.extend_from_slice(&u32::from(*value).to_le_bytes());
+        }
+        state.extend_from_slice(&self.last_update.to_le_bytes());
+        state
+    }
+
+    /// Restore a clock state created by `save`, advancing it by the time passed since
+    pub fn load(&mut self, state: &[u8]) -> Result<(), CartridgeError> {
+        if state.len() != RTC_STATE_SIZE {
+            return Err(CartridgeError::InvalidRtcState {
+                expected: RTC_STATE_SIZE,
+                actual: state.len(),
+            });
+        }
+
+        let mut registers = [0; 10];
+        for (register, chunk) in registers.iter_mut().zip(state.chunks_exact(4)) {
+            *register = chunk[0];
+        }
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&state[40..48]);
+
+        self.clock = ClockRegisters::from_array([
+            registers[0],
+            registers[1],
+            registers[2],
+            registers[3],
+            registers[4],
+        ]);
+        self.latched = ClockRegisters::from_array([
+            registers[5],
+            registers[6],
+            registers[7],
+            registers[8],
+            registers[9],
+        ]);
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.update();
+
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "std", not(all(target_arch = "wasm32", feature = "wasm"))))]
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(not(feature = "std"))]
+fn unix_time() -> u64 {
+    0
+}
+
+/// `SystemTime` is not available in the browser, ask JavaScript instead
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+fn unix_time() -> u64 {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date)]
+        fn now() -> f64;
+    }
+
+    (now() / 1000.0) as u64
+}
+
+impl SaveState for Rtc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.save());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.load(state.read_bytes()?)
+            .map_err(|_| SaveStateError::InvalidData("RTC state"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn test_advance() {
+        let mut rtc = Rtc::new();
+        rtc.advance(SECONDS_PER_DAY + 3600 + 60 + 1);
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_SECONDS), 1);
+        assert_eq!(rtc.read(RTC_MINUTES), 1);
+        assert_eq!(rtc.read(RTC_HOURS), 1);
+        assert_eq!(rtc.read(RTC_DAYS_LO), 1);
+        assert_eq!(rtc.read(RTC_DAYS_HI), 0);
+    }
+
+    #[test]
+    fn test_day_overflow() {
+        let mut rtc = Rtc::new();
+        rtc.write(RTC_DAYS_LO, 0xFF);
+        rtc.write(RTC_DAYS_HI, DAY_HI_BIT);
+        rtc.advance(SECONDS_PER_DAY);
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_DAYS_LO), 0);
+        assert_eq!(rtc.read(RTC_DAYS_HI), DAY_CARRY_BIT);
+    }
+
+    #[test]
+    fn test_halt() {
+        let mut rtc = Rtc::new();
+        rtc.write(RTC_DAYS_HI, HALT_BIT);
+        rtc.advance(100);
+        latch(&mut rtc);
+
+        assert_eq!(rtc.read(RTC_SECONDS), 0);
+    }
+
+    #[test]
+    fn test_latch_requires_zero_then_one() {
+        let mut rtc = Rtc::new();
+        rtc.write(RTC_MINUTES, 5);
+
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(RTC_MINUTES), 0);
+
+        latch(&mut rtc);
+        assert_eq!(rtc.read(RTC_MINUTES), 5);
+
+        rtc.write(RTC_MINUTES, 7);
+        assert_eq!(rtc.read(RTC_MINUTES), 5);
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut rtc = Rtc::new();
+        rtc.write(RTC_HOURS, 12);
+        rtc.write(RTC_DAYS_HI, HALT_BIT);
+        latch(&mut rtc);
+
+        let state = rtc.save();
+        assert_eq!(state.len(), RTC_STATE_SIZE);
+
+        let mut restored = Rtc::new();
+        restored.load(&state).unwrap();
+        assert_eq!(restored.read(RTC_HOURS), 12);
+        assert_eq!(restored.clock, rtc.clock);
+    }
+
+    #[test]
+    fn test_load_advances_time() {
+        let mut rtc = Rtc::new();
+        rtc.last_update -= 120;
+        let state = rtc.save();
+
+        let mut restored = Rtc::new();
+        restored.load(&state).unwrap();
+        latch(&mut restored);
+        assert_eq!(restored.read(RTC_MINUTES), 2);
+    }
+
+    #[test]
+    fn test_load_invalid_size() {
+        let mut rtc = Rtc::new();
+        assert!(rtc.load(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+//! Game Genie and GameShark cheat codes.
+//!
+//! A Game Genie sits between the console and the cartridge and replaces ROM bytes, its codes are
+//! `ABC-DEF` or `ABC-DEF-GHI`: AB is the new value, FCDE the address with F inverted and GI the
+//! byte to replace, rotated and scrambled, checked so only the intended bank is patched.
+//! A GameShark writes RAM once per frame, its codes are 8 hex digits `TTVVLLHH`: a type,
+//! the value and the address, low byte first, in 0xA000-0xDFFF.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::utils::CheatError;
+
+use super::{cartridge::RomPatch, Memory};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheatCode {
+    GameGenie(RomPatch),
+    /// `kind` is the type byte of the code, the write goes to whichever bank is mapped, the
+    /// address is always in cartridge RAM or work RAM
+    GameShark {
+        kind: u8,
+        address: u16,
+        value: u8,
+    },
+}
+
+impl CheatCode {
+    /// Parse a Game Genie or GameShark code, dashes and spaces are ignored
+    pub fn parse(code: &str) -> Result<CheatCode, CheatError> {
+        let invalid = || CheatError::InvalidCode(code.to_string());
+        let digits = code
+            .chars()
+            .filter(|char| *char != '-' && !char.is_whitespace())
+            .map(|char| char.to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+
+        match *digits.as_slice() {
+            [v1, v0, a2, a1, a0, a3, ref compare @ ..] if matches!(compare.len(), 0 | 3) => {
+                let address = (u16::from(a3 ^ 0xF) << 12)
+                    | (u16::from(a2) << 8)
+                    | (u16::from(a1) << 4)
+                    | u16::from(a0);
+                if address >= 0x8000 {
+                    return Err(invalid());
+                }
+                let compare = match *compare {
+                    [c1, _, c0] => Some(((c1 << 4) | c0).rotate_right(2) ^ 0xBA),
+                    _ => None,
+                };
+                Ok(CheatCode::GameGenie(RomPatch {
+                    address,
+                    value: (v1 << 4) | v0,
+                    compare,
+                }))
+            }
+            [t1, t0, v1, v0, l1, l0, h1, h0] => {
+                let address = u16::from_le_bytes([(l1 << 4) | l0, (h1 << 4) | h0]);
+                if !(0xA000..=0xDFFF).contains(&address) {
+                    return Err(invalid());
+                }
+                Ok(CheatCode::GameShark {
+                    kind: (t1 << 4) | t0,
+                    address,
+                    value: (v1 << 4) | v0,
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    /// The code as entered
+    pub code: String,
+    pub cheat: CheatCode,
+    pub enabled: bool,
+}
+
+/// The cheats entered for the running game
+#[derive(Default)]
+pub struct CheatEngine {
+    cheats: Vec<Cheat>,
+}
+
+impl CheatEngine {
+    pub fn new() -> CheatEngine {
+        CheatEngine::default()
+    }
+
+    /// Add an enabled cheat, returns its index
+    pub fn add(&mut self, code: &str) -> Result<usize, CheatError> {
+        let cheat = CheatCode::parse(code)?;
+        self.cheats.push(Cheat {
+            code: code.to_string(),
+            cheat,
+            enabled: true,
+        });
+        Ok(self.cheats.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| self.cheats.remove(index))
+    }
+
+    /// Returns false if there is no cheat at `index`
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return false;
+        };
+        cheat.enabled = enabled;
+        true
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    /// The ROM patches of the enabled Game Genie codes
+    pub fn rom_patches(&self) -> Vec<RomPatch> {
+        self.enabled()
+            .filter_map(|cheat| match cheat {
+                CheatCode::GameGenie(patch) => Some(patch),
+                CheatCode::GameShark { .. } => None,
+            })
+            .collect()
+    }
+
+    /// Perform the writes of the enabled GameShark codes, done once per frame at VBlank
+    pub fn apply_ram_writes(&self, memory: &mut Memory) {
+        for cheat in self.enabled() {
+            if let CheatCode::GameShark { address, value, .. } = cheat {
+                memory.write_byte(address, value);
+            }
+        }
+    }
+
+    fn enabled(&self) -> impl Iterator<Item = CheatCode> + '_ {
+        self.cheats
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .map(|cheat| cheat.cheat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_game_genie() {
+        assert_eq!(
+            CheatCode::parse("00A-17B").unwrap(),
+            CheatCode::GameGenie(RomPatch {
+                address: 0x4A17,
+                value: 0x00,
+                compare: None,
+            })
+        );
+        assert_eq!(
+            CheatCode::parse("3EC-29F-E6E").unwrap(),
+            CheatCode::GameGenie(RomPatch {
+                address: 0x0C29,
+                value: 0x3E,
+                compare: Some(0x01),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_game_shark() {
+        assert_eq!(
+            CheatCode::parse("0163A0C0").unwrap(),
+            CheatCode::GameShark {
+                kind: 0x01,
+                address: 0xC0A0,
+                value: 0x63,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(CheatCode::parse("").is_err());
+        assert!(CheatCode::parse("00A-17").is_err());
+        assert!(CheatCode::parse("00A-17G").is_err());
+        // the inverted high nibble puts this at 0x8A17, outside ROM
+        assert!(CheatCode::parse("00A-177").is_err());
+        // GameShark codes may only write cartridge RAM or work RAM, not MBC or I/O registers
+        assert!(CheatCode::parse("01FF0020").is_err());
+        assert!(CheatCode::parse("0101FFFF").is_err());
+        assert!(CheatCode::parse("010150FF").is_err());
+    }
+
+    #[test]
+    fn test_engine() {
+        let mut memory = Memory::new();
+        let mut engine = CheatEngine::new();
+        let genie = engine.add("00A-17B").unwrap();
+        let shark = engine.add("0163A0C0").unwrap();
+
+        assert_eq!(engine.rom_patches().len(), 1);
+        engine.apply_ram_writes(&mut memory);
+        assert_eq!(memory.read_byte(0xC0A0), 0x63);
+
+        assert!(engine.set_enabled(shark, false));
+        memory.write_byte(0xC0A0, 0x00);
+        engine.apply_ram_writes(&mut memory);
+        assert_eq!(memory.read_byte(0xC0A0), 0x00);
+
+        assert!(engine.remove(genie).is_some());
+        assert!(engine.rom_patches().is_empty());
+        assert!(!engine.set_enabled(5, true));
+    }
+}
@@ -0,0 +1,178 @@
+//! Core dumps, a snapshot of the emulator for bug reports when a game crashes it.
+//!
+//! A dump is a directory with `report.txt`, the reason, the registers, the last instructions run
+//! and the IO registers, and `screen.png`, the last frame. The instructions come from the CPU's
+//! instruction history, disassembled from memory as it is when the dump is taken.
+
+use core::{fmt::Write as _, ops::Range};
+use std::{
+    fs,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::utils::CoreDumpError;
+
+use super::{DisassembledInstruction, Frame, GameBoy, MemoryBus, Register16};
+
+const TITLE: Range<u16> = 0x0134..0x0144;
+const CARTRIDGE_TYPE: u16 = 0x0147;
+const IO: Range<u16> = 0xFF00..0xFF80;
+const IE: u16 = 0xFFFF;
+
+pub struct CoreDump {
+    pub reason: String,
+    /// `AF=01B0 BC=0013 DE=00D8 HL=014D SP=FFFE PC=0100`
+    pub registers: String,
+    pub ime: bool,
+    pub cgb_mode: bool,
+    pub title: String,
+    pub cartridge_type: u8,
+    /// The last instructions run, oldest first, with the opcode each ran with
+    pub instructions: Vec<(DisassembledInstruction, u8)>,
+    /// 0xFF00-0xFF7F
+    pub io: Vec<u8>,
+    pub ie: u8,
+    pub frame: Frame,
+}
+
+impl CoreDump {
+    /// Snapshot `gameboy`, `reason` is what went wrong, or that the user asked for it
+    pub fn capture(gameboy: &GameBoy, reason: &str) -> CoreDump {
+        let cpu = gameboy.cpu();
+        let registers = &cpu.registers;
+        let memory = gameboy.memory();
+        let disassembler = gameboy.disassembler();
+        let instructions = cpu
+            .instruction_history()
+            .iter()
+            .map(|(address, opcode)| (disassembler.disassemble(address), opcode))
+            .collect();
+        let title = TITLE
+            .map(|address| memory.peek_byte(address))
+            .take_while(|byte| *byte != 0)
+            .map(|byte| {
+                if byte.is_ascii_graphic() {
+                    byte as char
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+
+        CoreDump {
+            reason: reason.to_string(),
+            registers: format!(
+                "AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X} PC={:04X}",
+                registers.read_16(Register16::AF),
+                registers.read_16(Register16::BC),
+                registers.read_16(Register16::DE),
+                registers.read_16(Register16::HL),
+                registers.read_16(Register16::SP),
+                registers.pc,
+            ),
+            ime: cpu.ime(),
+            cgb_mode: gameboy.cgb_mode(),
+            title,
+            cartridge_type: memory.peek_byte(CARTRIDGE_TYPE),
+            instructions,
+            io: IO.map(|address| memory.peek_byte(address)).collect(),
+            ie: memory.peek_byte(IE),
+            frame: gameboy.frame(),
+        }
+    }
+
+    /// The text of `report.txt`
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let _ = writeln!(report, "Game Boy core dump: {}", self.reason);
+        let _ = writeln!(
+            report,
+            "Game: {} (cartridge type {:#04X}, {} mode)",
+            self.title,
+            self.cartridge_type,
+            if self.cgb_mode { "CGB" } else { "DMG" }
+        );
+        let _ = writeln!(
+            report,
+            "\nRegisters:\n{} IME={}",
+            self.registers,
+            u8::from(self.ime)
+        );
+
+        let _ = writeln!(report, "\nLast instructions, oldest first:");
+        if self.instructions.is_empty() {
+            let _ = writeln!(report, "(the instruction history is off)");
+        }
+        for (instruction, opcode) in &self.instructions {
+            let _ = write!(report, "{instruction}");
+            // the code was overwritten since, by a bank switch or self-modifying code
+            if instruction.bytes.first() != Some(opcode) {
+                let _ = write!(report, " (ran opcode {opcode:02X})");
+            }
+            report.push('\n');
+        }
+
+        let _ = writeln!(report, "\nIO registers:");
+        for (line, bytes) in self.io.chunks(16).enumerate() {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            let _ = writeln!(
+                report,
+                "{:04X}: {}",
+                IO.start as usize + line * 16,
+                bytes.join(" ")
+            );
+        }
+        let _ = writeln!(report, "FFFF: {:02X}", self.ie);
+        report
+    }
+
+    /// Write the dump to a new `coredump-SECONDS` directory in `dir`, returning its path
+    pub fn write(&self, dir: impl AsRef<Path>) -> Result<PathBuf, CoreDumpError> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let path = dir.as_ref().join(format!("coredump-{seconds}"));
+        fs::create_dir_all(&path)?;
+        fs::write(path.join("report.txt"), self.report())?;
+        let screen = fs::File::create(path.join("screen.png"))?;
+        self.frame.write_png(BufWriter::new(screen))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{fix_header_checksum, test_rom};
+
+    #[test]
+    fn test_core_dump() {
+        let mut gameboy = GameBoy::new();
+        // LD A, 0x42; LD (0xC000), A; then NOPs
+        let mut rom = test_rom(&[0x3E, 0x42, 0xEA, 0x00, 0xC0]);
+        rom[0x0134..0x0138].copy_from_slice(b"TEST");
+        fix_header_checksum(&mut rom);
+        gameboy.load_rom(rom).unwrap();
+        gameboy.set_instruction_history(2);
+        for _ in 0..5 {
+            gameboy.step();
+        }
+
+        let dump = CoreDump::capture(&gameboy, "requested");
+        assert_eq!(dump.title, "TEST");
+        assert_eq!(dump.io.len(), 0x80);
+        let report = dump.report();
+        assert!(report.starts_with("Game Boy core dump: requested\nGame: TEST"));
+        assert!(report.contains("0152: EA 00 C0  LD ($C000), A\n0155: 00        NOP\n"));
+        assert!(report.contains("\nFF40: 91 "));
+
+        let dir = std::env::temp_dir().join(format!("gb-core-dump-{}", std::process::id()));
+        let path = dump.write(&dir).unwrap();
+        assert!(path.join("report.txt").exists());
+        assert!(path.join("screen.png").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+//! A cached interpreter, turned on with `Cpu::set_block_cache`: code is decoded once per basic
+//! block and the decoded instructions are replayed until the code changes.
+//!
+//! A block starts wherever execution enters and runs to the next jump, call, return, HALT, STOP,
+//! EI or DI. Only code in ROM, WRAM and HRAM is cached, anything else is interpreted as usual.
+//! Blocks in RAM are dropped when the CPU writes to them, blocks in ROM on any write to the MBC
+//! or the boot ROM register, and blocks in the switchable WRAM bank on writes to SVBK. Writes
+//! from outside the CPU (debuggers, cheats, save states) go through `Cpu::flush_block_cache` or
+//! `Cpu::invalidate_code`.
+//!
+//! Cached instructions are not fetched over the bus again, so the accesses within an instruction
+//! happen earlier than on hardware, like with `AccuracyProfile::Fast`. The cycles taken are the
+//! same.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{mem, ops::RangeInclusive};
+
+use crate::gameboy::{interrupts::Interrupt, MemoryBus};
+
+use super::{cpu_core::decode_instruction, instructions::Instruction};
+
+const MAX_INSTRUCTIONS: usize = 64;
+/// The most bytes a block covers
+const MAX_BLOCK_BYTES: u16 = MAX_INSTRUCTIONS as u16 * 3;
+
+const BOOT_ROM_DISABLE: u16 = 0xFF50;
+const SVBK: u16 = 0xFF70;
+const WRAM_BANK: RangeInclusive<u16> = 0xD000..=0xDFFF;
+
+/// The areas code is cached from, a block never spans two of them
+const REGIONS: [RangeInclusive<u16>; 5] = [
+    0x0000..=0x3FFF,
+    0x4000..=0x7FFF,
+    0xC000..=0xCFFF,
+    WRAM_BANK,
+    0xFF80..=0xFFFE,
+];
+
+fn region(address: u16) -> Option<usize> {
+    REGIONS.iter().position(|region| region.contains(&address))
+}
+
+/// Instructions that change where execution goes or when interrupts are taken
+fn ends_block(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JrImm8(_)
+            | Instruction::JrCondImm8(..)
+            | Instruction::JpImm16(_)
+            | Instruction::JpCondImm16(..)
+            | Instruction::JpHl
+            | Instruction::CallImm16(_)
+            | Instruction::CallCondImm16(..)
+            | Instruction::Ret
+            | Instruction::RetCond(_)
+            | Instruction::Reti
+            | Instruction::RstTgt3(_)
+            | Instruction::Halt
+            | Instruction::Stop
+            | Instruction::Ei
+            | Instruction::Di
+    )
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct CachedInstruction {
+    pub address: u16,
+    pub length: u8,
+    pub instruction: Instruction,
+}
+
+struct Block {
+    start: u16,
+    end: u16, // one past the last byte
+    instructions: Vec<CachedInstruction>,
+}
+
+impl Block {
+    /// Decode the block at `start`, `None` if nothing there can be cached
+    fn decode(start: u16, memory: &impl MemoryBus) -> Option<Block> {
+        let start_region = region(start)?;
+        let mut instructions = Vec::new();
+        let mut address = start;
+        while instructions.len() < MAX_INSTRUCTIONS {
+            let mut next = address;
+            let mut fits = true;
+            let decoded = decode_instruction(address, || {
+                fits &= region(next) == Some(start_region);
+                let byte = memory.peek_byte(next);
+                next = next.wrapping_add(1);
+                byte
+            });
+            // illegal opcodes are left to the interpreter
+            let (Ok((instruction, length)), true) = (decoded, fits) else {
+                break;
+            };
+            instructions.push(CachedInstruction {
+                address,
+                length,
+                instruction,
+            });
+            address = next;
+            if ends_block(instruction) {
+                break;
+            }
+        }
+        (!instructions.is_empty()).then_some(Block {
+            start,
+            end: address,
+            instructions,
+        })
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = u16> {
+        self.start..self.end
+    }
+}
+
+pub(super) struct BlockCache {
+    blocks: Vec<Option<Box<Block>>>, // by start address
+    coverage: Vec<u16>,              // the number of blocks covering each address
+    rom_blocks: Vec<u16>,            // start addresses of the blocks in ROM
+    current: Option<(u16, usize)>,   // the block running and the index of its next instruction
+}
+
+impl BlockCache {
+    pub fn new() -> BlockCache {
+        BlockCache {
+            blocks: (0..0x10000).map(|_| None).collect(),
+            coverage: vec![0; 0x10000],
+            rom_blocks: Vec::new(),
+            current: None,
+        }
+    }
+
+    /// The instruction at `pc`, decoding the block starting there if needed
+    ///
+    /// `None` if the code at `pc` is not cached
+    pub fn next(&mut self, pc: u16, memory: &impl MemoryBus) -> Option<CachedInstruction> {
+        if let Some((start, index)) = self.current.take() {
+            let next = self.blocks[usize::from(start)]
+                .as_ref()
+                .and_then(|block| block.instructions.get(index))
+                .filter(|instruction| instruction.address == pc);
+            if let Some(instruction) = next {
+                self.current = Some((start, index + 1));
+                return Some(*instruction);
+            }
+        }
+
+        if self.blocks[usize::from(pc)].is_none() {
+            self.insert(Block::decode(pc, memory)?);
+        }
+        let block = self.blocks[usize::from(pc)].as_ref()?;
+        self.current = Some((pc, 1));
+        Some(block.instructions[0])
+    }
+
+    fn insert(&mut self, block: Block) {
+        for address in block.addresses() {
+            self.coverage[usize::from(address)] += 1;
+        }
+        if block.start < 0x8000 {
+            self.rom_blocks.push(block.start);
+        }
+        let start = usize::from(block.start);
+        self.blocks[start] = Some(Box::new(block));
+    }
+
+    fn remove(&mut self, start: u16) {
+        let Some(block) = self.blocks[usize::from(start)].take() else {
+            return;
+        };
+        for address in block.addresses() {
+            self.coverage[usize::from(address)] -= 1;
+        }
+        if self.current.is_some_and(|(current, _)| current == start) {
+            self.current = None;
+        }
+    }
+
+    /// Drop the blocks made stale by a CPU write to `address`
+    pub fn on_write(&mut self, address: u16) {
+        match address {
+            0x0000..=0x7FFF | BOOT_ROM_DISABLE => {
+                for start in mem::take(&mut self.rom_blocks) {
+                    self.remove(start);
+                }
+            }
+            SVBK => {
+                for start in WRAM_BANK {
+                    self.remove(start);
+                }
+            }
+            _ => self.invalidate(address),
+        }
+    }
+
+    /// Drop the blocks covering `address`
+    pub fn invalidate(&mut self, address: u16) {
+        if self.coverage[usize::from(address)] == 0 {
+            return;
+        }
+        for start in address.saturating_sub(MAX_BLOCK_BYTES)..=address {
+            let covers = self.blocks[usize::from(start)]
+                .as_ref()
+                .is_some_and(|block| block.end > address);
+            if covers {
+                self.remove(start);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.fill_with(|| None);
+        self.coverage.fill(0);
+        self.rom_blocks.clear();
+        self.current = None;
+    }
+}
+
+/// Forwards to the CPU's bus and notes the addresses written, for `BlockCache::on_write`
+pub(super) struct WriteLog<'a, M> {
+    bus: &'a mut M,
+    writes: [u16; 4],
+    len: usize,
+    overflowed: bool,
+}
+
+impl<'a, M: MemoryBus> WriteLog<'a, M> {
+    pub fn new(bus: &'a mut M) -> WriteLog<'a, M> {
+        WriteLog {
+            bus,
+            writes: [0; 4],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    fn log(&mut self, address: u16) {
+        match self.writes.get_mut(self.len) {
+            Some(write) => {
+                *write = address;
+                self.len += 1;
+            }
+            None => self.overflowed = true,
+        }
+    }
+
+    /// Drop the blocks the writes made stale, everything if there were too many to note
+    pub fn invalidate(&self, cache: &mut BlockCache) {
+        if self.overflowed {
+            cache.clear();
+            return;
+        }
+        for address in &self.writes[..self.len] {
+            cache.on_write(*address);
+        }
+    }
+}
+
+impl<M: MemoryBus> MemoryBus for WriteLog<'_, M> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.bus.read_byte(address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.bus.write_byte(address, value);
+        self.log(address);
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.bus.peek_byte(address)
+    }
+
+    fn bank(&self, address: u16) -> usize {
+        self.bus.bank(address)
+    }
+
+    fn poke_byte(&mut self, address: u16, value: u8) {
+        self.bus.poke_byte(address, value);
+        self.log(address);
+    }
+
+    fn on_instruction(&mut self, address: u16) {
+        self.bus.on_instruction(address);
+    }
+
+    fn on_interrupt(&mut self, interrupt: Interrupt) {
+        self.bus.on_interrupt(interrupt);
+    }
+
+    fn on_increment(&mut self, address: u16) {
+        self.bus.on_increment(address);
+    }
+
+    fn switch_speed(&mut self) -> bool {
+        self.bus.switch_speed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Memory;
+
+    /// Decode a block from `program` in WRAM
+    fn setup(program: &[u8]) -> (BlockCache, Memory) {
+        let mut memory = Memory::new();
+        for (offset, byte) in program.iter().enumerate() {
+            memory.write_byte(0xC000 + offset as u16, *byte);
+        }
+        (BlockCache::new(), memory)
+    }
+
+    #[test]
+    fn test_block_ends_at_jump() {
+        // INC A, LD B, 0x12, JR -5, NOP
+        let (mut cache, memory) = setup(&[0x3C, 0x06, 0x12, 0x18, 0xFB, 0x00]);
+        let first = cache.next(0xC000, &memory).unwrap();
+        assert_eq!((first.address, first.length), (0xC000, 1));
+        let second = cache.next(0xC001, &memory).unwrap();
+        assert_eq!((second.address, second.length), (0xC001, 2));
+        assert!(matches!(
+            cache.next(0xC003, &memory).unwrap().instruction,
+            Instruction::JrImm8(0xFB)
+        ));
+        assert_eq!(cache.blocks[0xC000].as_ref().unwrap().end, 0xC005);
+        assert_eq!(cache.coverage[0xC005], 0);
+    }
+
+    #[test]
+    fn test_write_drops_block() {
+        let (mut cache, mut memory) = setup(&[0x3C, 0x3C, 0xC9]);
+        cache.next(0xC000, &memory);
+        assert_eq!(cache.coverage[0xC002], 1);
+
+        // INC A becomes DEC A
+        memory.write_byte(0xC001, 0x3D);
+        cache.on_write(0xC001);
+        assert!(cache.blocks[0xC000].is_none());
+        assert_eq!(cache.coverage[0xC002], 0);
+        cache.next(0xC000, &memory);
+        assert!(matches!(
+            cache.next(0xC001, &memory).unwrap().instruction,
+            Instruction::DecR8(_)
+        ));
+    }
+
+    #[test]
+    fn test_bank_switch_drops_rom_blocks() {
+        let (mut cache, memory) = setup(&[0xC9]);
+        cache.next(0x4000, &memory);
+        cache.next(0xC000, &memory);
+        cache.on_write(0x2000);
+        assert!(cache.blocks[0x4000].is_none());
+        assert!(cache.blocks[0xC000].is_some());
+    }
+
+    #[test]
+    fn test_uncached_areas() {
+        let (mut cache, memory) = setup(&[]);
+        // VRAM and illegal opcodes are left to the interpreter
+        assert!(cache.next(0x8000, &memory).is_none());
+        let (mut cache, memory) = setup(&[0xD3]);
+        assert!(cache.next(0xC000, &memory).is_none());
+    }
+}
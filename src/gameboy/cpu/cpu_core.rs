@@ -1,170 +1,591 @@
-use crate::gameboy::Memory;
+use alloc::boxed::Box;
+use core::mem;
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use crate::{
+    gameboy::{
+        interrupts::{clear_interrupt, pending_interrupt, Interrupt, IF},
+        save_state::{SaveState, StateReader, StateWriter},
+        MemoryBus, Symbols,
+    },
+    utils::{combine, EmuError, SaveStateError},
+};
 
+#[cfg(feature = "jump-table")]
+use super::dispatch;
+#[cfg(feature = "std")]
+use super::trace::doctor_line;
 use super::{
+    block_cache::{BlockCache, WriteLog},
     instruction_variables::{Cond, B3, R16, R16MEM, R16STK, R8, TGT3},
-    instructions::Instruction,
-    registers::Registers,
+    instructions::{stack_push_16, Instruction},
+    registers::{Register16, Registers},
+    trace::{InstructionHistory, HISTORY_LENGTH},
 };
 
-const STARTUP_AF: u16 = 0x0;
-const STARTUP_BC: u16 = 0x0;
-const STARTUP_DE: u16 = 0x0;
-const STARTUP_HL: u16 = 0x0;
-const STARTUP_SP: u16 = 0x0;
-const STARTUP_PC: u16 = 0x0;
+// register values left behind by the DMG boot ROM
+const STARTUP_AF: u16 = 0x01B0;
+const STARTUP_BC: u16 = 0x0013;
+const STARTUP_DE: u16 = 0x00D8;
+const STARTUP_HL: u16 = 0x014D;
+const STARTUP_SP: u16 = 0xFFFE;
+const STARTUP_PC: u16 = 0x0100;
+
+// the CGB boot ROM hands over with A = 0x11, which games check to enable their color code
+const CGB_STARTUP_AF: u16 = 0x1180;
+const CGB_STARTUP_BC: u16 = 0x0000;
+const CGB_STARTUP_DE: u16 = 0xFF56;
+const CGB_STARTUP_HL: u16 = 0x000D;
+
+const INTERRUPT_DISPATCH_CYCLES: u8 = 5;
+
+const SAVED_REGISTERS: [Register16; 6] = [
+    Register16::AF,
+    Register16::BC,
+    Register16::DE,
+    Register16::HL,
+    Register16::SP,
+    Register16::PC,
+];
+
+/// What the CPU does when it fetches one of the 11 opcodes that have no instruction
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IllegalOpcodePolicy {
+    /// Hang like the hardware does, nothing more is fetched and interrupts are ignored until reset
+    #[default]
+    Lockup,
+    /// Skip the opcode as if it were a NOP
+    Nop,
+    /// Stop on the opcode with PC left pointing at it and report it through `Cpu::error`,
+    /// emulation resumes once the error is taken
+    Trap,
+}
+
+/// What the CPU did in one `Cpu::step_instruction`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    /// Where the instruction started, or the return address pushed for an interrupt
+    pub address: u16,
+    /// The instruction run, `None` for an interrupt dispatch, an illegal opcode or a CPU idling
+    /// in HALT or STOP
+    pub instruction: Option<Instruction>,
+    /// Machine cycles taken
+    pub cycles: u8,
+    /// The interrupt serviced instead of running an instruction
+    pub interrupt: Option<Interrupt>,
+}
+
+impl StepResult {
+    /// One cycle without running anything
+    fn idle(address: u16) -> StepResult {
+        StepResult {
+            address,
+            instruction: None,
+            cycles: 1,
+            interrupt: None,
+        }
+    }
+}
 
 pub struct Cpu {
     pub registers: Registers,
+    pub(super) ime: bool,         // interrupt master enable
+    pub(super) ime_pending: bool, // set by EI, IME follows after the next instruction
+    pub(super) halted: bool,
+    pub(super) stopped: bool, // low power mode entered by STOP, left on joypad input
+    locked: bool,             // hung on an illegal opcode
+    illegal_opcode_policy: IllegalOpcodePolicy,
+    error: Option<EmuError>, // a trapped illegal opcode, waiting to be taken
+    block_cache: Option<Box<BlockCache>>,
+    history: InstructionHistory, // the last instructions started
+    symbols: Option<Symbols>,    // labels for the trace, the disassembler and the debugger
+    #[cfg(feature = "std")]
+    trace: Option<Box<dyn Write + Send>>, // receives a Gameboy Doctor line before each instruction
 }
 
 impl Cpu {
+    /// A CPU in the state the boot ROM leaves it in, ready to run the cartridge at 0x0100
     pub fn new() -> Cpu {
         Cpu {
             registers: Registers::new(
                 STARTUP_AF, STARTUP_BC, STARTUP_DE, STARTUP_HL, STARTUP_SP, STARTUP_PC,
             ),
+            ime: false,
+            ime_pending: false,
+            halted: false,
+            stopped: false,
+            locked: false,
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            error: None,
+            block_cache: None,
+            history: InstructionHistory::new(HISTORY_LENGTH),
+            symbols: None,
+            #[cfg(feature = "std")]
+            trace: None,
         }
     }
 
-    fn fetch_byte(&mut self, memory: &Memory) -> u8 {
+    /// A CPU at power on, starting at 0x0000 to run a boot ROM
+    pub fn power_on() -> Cpu {
+        Cpu {
+            registers: Registers::new(0, 0, 0, 0, 0, 0),
+            ime: false,
+            ime_pending: false,
+            halted: false,
+            stopped: false,
+            locked: false,
+            illegal_opcode_policy: IllegalOpcodePolicy::default(),
+            error: None,
+            block_cache: None,
+            history: InstructionHistory::new(HISTORY_LENGTH),
+            symbols: None,
+            #[cfg(feature = "std")]
+            trace: None,
+        }
+    }
+
+    /// Set the registers to the values the CGB boot ROM leaves behind
+    pub fn skip_cgb_boot(&mut self) {
+        self.registers = Registers::new(
+            CGB_STARTUP_AF,
+            CGB_STARTUP_BC,
+            CGB_STARTUP_DE,
+            CGB_STARTUP_HL,
+            STARTUP_SP,
+            STARTUP_PC,
+        );
+    }
+
+    /// Whether interrupts are enabled (IME)
+    pub fn ime(&self) -> bool {
+        self.ime
+    }
+
+    pub fn set_ime(&mut self, ime: bool) {
+        self.ime = ime;
+        self.ime_pending = false;
+    }
+
+    /// Whether HALT stopped the CPU until an interrupt is pending
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Whether the CPU hung on an illegal opcode, with `IllegalOpcodePolicy::Lockup`
+    ///
+    /// Nothing runs until the CPU is reset or a state from before the lockup is loaded, a
+    /// frontend can tell the player the game crashed.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether EI ran and IME is set once the next instruction starts
+    pub fn ime_pending(&self) -> bool {
+        self.ime_pending
+    }
+
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.illegal_opcode_policy = policy;
+    }
+
+    pub fn illegal_opcode_policy(&self) -> IllegalOpcodePolicy {
+        self.illegal_opcode_policy
+    }
+
+    /// The illegal opcode the CPU is trapped on, with `IllegalOpcodePolicy::Trap`
+    pub fn error(&self) -> Option<&EmuError> {
+        self.error.as_ref()
+    }
+
+    /// Clear the trapped error so execution resumes at PC
+    pub fn take_error(&mut self) -> Option<EmuError> {
+        self.error.take()
+    }
+
+    /// Run code from a cache of decoded basic blocks instead of decoding every instruction
+    ///
+    /// Experimental, see `block_cache` for what it changes. Writes to memory from outside the
+    /// CPU have to be reported with `invalidate_code` or `flush_block_cache`
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        self.block_cache = enabled.then(|| Box::new(BlockCache::new()));
+    }
+
+    pub fn block_cache_enabled(&self) -> bool {
+        self.block_cache.is_some()
+    }
+
+    /// Drop every cached block, after memory changed from outside the CPU
+    pub fn flush_block_cache(&mut self) {
+        if let Some(cache) = &mut self.block_cache {
+            cache.clear();
+        }
+    }
+
+    /// Drop the cached blocks covering `address`, after it was written from outside the CPU
+    pub fn invalidate_code(&mut self, address: u16) {
+        if let Some(cache) = &mut self.block_cache {
+            cache.invalidate(address);
+        }
+    }
+
+    /// Write a Gameboy Doctor trace line to `trace` before every instruction, `None` stops tracing
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: Option<Box<dyn Write + Send>>) {
+        self.trace = trace;
+    }
+
+    /// Name addresses by `symbols` in the trace, labels get a `Main:` line before the instruction
+    pub fn set_symbols(&mut self, symbols: Option<Symbols>) {
+        self.symbols = symbols;
+    }
+
+    pub fn symbols(&self) -> Option<&Symbols> {
+        self.symbols.as_ref()
+    }
+
+    /// Keep the last `length` instructions started instead of `HISTORY_LENGTH`, 0 keeps none
+    pub fn set_instruction_history(&mut self, length: usize) {
+        self.history = InstructionHistory::new(length);
+    }
+
+    /// The last instructions started, oldest first
+    pub fn instruction_history(&self) -> &InstructionHistory {
+        &self.history
+    }
+
+    #[cfg(feature = "std")]
+    fn write_trace(&mut self, memory: &impl MemoryBus) {
+        let Some(trace) = &mut self.trace else {
+            return;
+        };
+        let label = self
+            .symbols
+            .as_ref()
+            .and_then(|symbols| symbols.lookup(memory, self.registers.pc));
+        let written = match label {
+            Some(label) => writeln!(trace, "{label}:"),
+            None => Ok(()),
+        }
+        .and_then(|_| writeln!(trace, "{}", doctor_line(&self.registers, memory)));
+        if let Err(error) = written {
+            tracing::warn!("Stopped tracing: {error}");
+            self.trace = None;
+        }
+    }
+
+    pub(super) fn fetch_byte(&mut self, memory: &mut impl MemoryBus) -> u8 {
         let byte = memory.read_byte(self.registers.pc);
-        self.registers.pc += 1;
+        self.registers.pc = self.registers.pc.wrapping_add(1);
         byte
     }
 
-    fn fetch_word(&mut self, memory: &Memory) -> u16 {
-        let word = memory.read_word(self.registers.pc);
-        self.registers.pc += 2;
-        word
-    }
-
-    fn fetch_instruction(&mut self, memory: &Memory) -> Instruction {
-        // opcode == xxyyzzzz == xxaaabbb == iiijjbbb
-        let opcode = self.fetch_byte(memory);
-        let xx = opcode >> 6;
-        let yy = (opcode >> 4) & 0x3;
-        let zzzz = opcode & 0xF;
-        let aaa = (opcode >> 3) & 0x7;
-        let bbb = opcode & 0x7;
-        let iii = opcode >> 5;
-        let jj = (opcode >> 3) & 0x3;
-
-        // matching any one of the three tuples is enough to match the instruction, avoid mixing usage
-        match ((xx, yy, zzzz), (xx, aaa, bbb), (iii, jj, bbb)) {
-            // Block 0
-            ((0x0, 0x0, 0x0), _, _) => Instruction::Nop, // NOP
-
-            ((0x0, _, 0x1), _, _) => {
-                Instruction::LdR16Imm16(R16::from(yy), self.fetch_word(memory))
-            } // LD R16, imm16
-            ((0x0, _, 0x2), _, _) => Instruction::LdR16MemA(R16MEM::from(yy)), // LD (R16), A
-            ((0x0, _, 0xA), _, _) => Instruction::LdAR16Mem(R16MEM::from(yy)), // LD A, (R16)
-            ((0x0, 0x0, 0x8), _, _) => Instruction::LdMemImm16SP(self.fetch_word(memory)), // LD (imm16), SP
-
-            ((0x0, _, 0x3), _, _) => Instruction::IncR16(R16::from(yy)), // INC R16
-            ((0x0, _, 0xB), _, _) => Instruction::DecR16(R16::from(yy)), // DEC R16
-            ((0x0, _, 0x9), _, _) => Instruction::AddHlR16(R16::from(yy)), // ADD HL, R16
-
-            (_, (0x0, 0x6, 0x4), _) => Instruction::IncMemHl,
-            (_, (0x0, _, 0x4), _) => Instruction::IncR8(R8::from(aaa)), // INC R8
-            (_, (0x0, 0x6, 0x5), _) => Instruction::DecMemHl,
-            (_, (0x0, _, 0x5), _) => Instruction::DecR8(R8::from(aaa)), // DEC R8
-
-            (_, (0x0, 0x6, 0x6), _) => Instruction::LdMemHlImm8(self.fetch_byte(memory)),
-            (_, (0x0, _, 0x6), _) => Instruction::LdR8Imm8(R8::from(aaa), self.fetch_byte(memory)), // LD R8, Imm8
-
-            ((0x0, 0x0, 0x7), _, _) => Instruction::Rlca, // RLCA
-            ((0x0, 0x0, 0xF), _, _) => Instruction::Rrca, // RRCA
-            ((0x0, 0x1, 0x7), _, _) => Instruction::Rla,  // RLA
-            ((0x0, 0x1, 0xF), _, _) => Instruction::Rra,  // RRA
-            ((0x0, 0x2, 0x7), _, _) => Instruction::Daa,  // DAA
-            ((0x0, 0x2, 0xF), _, _) => Instruction::Cpl,  // CPL
-            ((0x0, 0x3, 0x7), _, _) => Instruction::Scf,  // SCF
-            ((0x0, 0x3, 0xF), _, _) => Instruction::Ccf,  // CCF
-
-            // Note: offset is signed
-            (_, _, (0x0, 0x3, 0x0)) => Instruction::JrImm8(self.fetch_byte(memory)), // JR imm8
-            (_, _, (0x1, _, 0x0)) => {
-                Instruction::JrCondImm8(Cond::from(jj), self.fetch_byte(memory))
-            } // JR cond, imm8
-
-            ((0x0, 0x1, 0x0), _, _) => Instruction::Stop, // STOP
-
-            // Block 1
-            (_, (0x1, 0x6, 0x6), _) => Instruction::Halt, // HALT
-            (_, (0x1, 0x6, _), _) => Instruction::LdMemHlR8(R8::from(bbb)), // LD (HL), R8
-            (_, (0x1, _, 0x6), _) => Instruction::LdR8MemHl(R8::from(aaa)), // LD R8, (HL)
-            (_, (0x1, _, _), _) => Instruction::LdR8R8(R8::from(aaa), R8::from(bbb)), // LD R8, R8
-
-            // Block 2
-            (_, (0x2, 0x0, 0x6), _) => Instruction::AddAMemHl,
-            (_, (0x2, 0x0, _), _) => Instruction::AddAR8(R8::from(bbb)), // ADD A, R8
-            (_, (0x2, 0x1, 0x6), _) => Instruction::AdcAMemHl,
-            (_, (0x2, 0x1, _), _) => Instruction::AdcAR8(R8::from(bbb)), // ADC A, R8
-            (_, (0x2, 0x2, 0x6), _) => Instruction::SubAMemHl,
-            (_, (0x2, 0x2, _), _) => Instruction::SubAR8(R8::from(bbb)), // SUB A, R8
-            (_, (0x2, 0x3, 0x6), _) => Instruction::SbcAMemHl,
-            (_, (0x2, 0x3, _), _) => Instruction::SbcAR8(R8::from(bbb)), // SBC A, R8
-            (_, (0x2, 0x4, 0x6), _) => Instruction::AndAMemHl,
-            (_, (0x2, 0x4, _), _) => Instruction::AndAR8(R8::from(bbb)), // AND A, R8
-            (_, (0x2, 0x5, 0x6), _) => Instruction::XorAMemHl,
-            (_, (0x2, 0x5, _), _) => Instruction::XorAR8(R8::from(bbb)), // XOR A, R8
-            (_, (0x2, 0x6, 0x6), _) => Instruction::OrAMemHl,
-            (_, (0x2, 0x6, _), _) => Instruction::OrAR8(R8::from(bbb)), // OR A, R8
-            (_, (0x2, 0x7, 0x6), _) => Instruction::CpAMemHl,
-            (_, (0x2, 0x7, _), _) => Instruction::CpAR8(R8::from(bbb)), // CP A, R8
-
-            // Block 3
-            ((0x3, 0x0, 0x6), _, _) => Instruction::AddAImm8(self.fetch_byte(memory)), // ADD A, imm8
-            ((0x3, 0x0, 0xE), _, _) => Instruction::AdcAImm8(self.fetch_byte(memory)), // ADC A, imm8
-            ((0x3, 0x1, 0x6), _, _) => Instruction::SubAImm8(self.fetch_byte(memory)), // SUB A, imm8
-            ((0x3, 0x1, 0xE), _, _) => Instruction::SbcAImm8(self.fetch_byte(memory)), // SBC A, imm8
-            ((0x3, 0x2, 0x6), _, _) => Instruction::AndAImm8(self.fetch_byte(memory)), // AND A, imm8
-            ((0x3, 0x2, 0xE), _, _) => Instruction::XorAImm8(self.fetch_byte(memory)), // XOR A, imm8
-            ((0x3, 0x3, 0x6), _, _) => Instruction::OrAImm8(self.fetch_byte(memory)),  // OR A, imm8
-            ((0x3, 0x3, 0xE), _, _) => Instruction::CpAImm8(self.fetch_byte(memory)),  // CP A, imm8
-
-            (_, _, (0x6, _, 0x0)) => Instruction::RetCond(Cond::from(jj)), // RET cond
-            (_, _, (0x6, 0x1, 0x1)) => Instruction::Ret,                   // RET
-            (_, _, (0x6, 0x3, 0x1)) => Instruction::Reti,                  // RETI
-            (_, _, (0x6, _, 0x2)) => {
-                Instruction::JpCondImm16(Cond::from(jj), self.fetch_word(memory))
-            } // JP cond, imm16
-            (_, _, (0x6, 0x0, 0x3)) => Instruction::JpImm16(self.fetch_word(memory)), // JP imm16
-            (_, _, (0x7, 0x1, 0x1)) => Instruction::JpHl,                  // JP HL
-            (_, _, (0x6, _, 0x4)) => {
-                Instruction::CallCondImm16(Cond::from(jj), self.fetch_word(memory))
-            } // CALL cond, imm16
-            (_, _, (0x6, 0x1, 0x5)) => Instruction::CallImm16(self.fetch_word(memory)), // CALL imm16
-            (_, (0x3, _, 0x7), _) => Instruction::RstTgt3(TGT3::from(aaa)),             // RST tgt3
-
-            ((0x3, _, 0x1), _, _) => Instruction::PopR16Stk(R16STK::from(yy)), // POP R16
-            ((0x3, _, 0x5), _, _) => Instruction::PushR16Stk(R16STK::from(yy)), // PUSH R16
-
-            ((0x3, 0x0, 0xB), _, _) => map_prefixed_instruction(self.fetch_byte(memory)), // CB
-
-            ((0x3, 0x2, 0x2), _, _) => Instruction::LdhMemCA, // LD (C), A
-            ((0x3, 0x2, 0x0), _, _) => Instruction::LdhMemImm8A(self.fetch_byte(memory)), // LDH (imm8), A
-            ((0x3, 0x2, 0xA), _, _) => Instruction::LdMemImm16A(self.fetch_word(memory)), // LD (imm16), A
-            ((0x3, 0x3, 0x2), _, _) => Instruction::LdAMemC, // LD A, (C)
-            ((0x3, 0x3, 0x0), _, _) => Instruction::LdhAMemImm8(self.fetch_byte(memory)), // LDH A, (imm8)
-            ((0x3, 0x3, 0xA), _, _) => Instruction::LdAMemImm16(self.fetch_word(memory)), // LD A, (imm16)
-
-            ((0x3, 0x2, 0x8), _, _) => Instruction::AddSpImm8(self.fetch_byte(memory)),
-            ((0x3, 0x3, 0x8), _, _) => Instruction::LdHlSpImm8(self.fetch_byte(memory)),
-            ((0x3, 0x3, 0x9), _, _) => Instruction::LdSpHl,
-
-            ((0x3, 0x3, 0x3), _, _) => Instruction::Di,
-            ((0x3, 0x3, 0xB), _, _) => Instruction::Ei,
-
-            _ => panic!("Unknown instruction: {:#04X}", opcode),
+    /// Fetch and decode the instruction at PC, with its length in bytes
+    fn fetch_instruction(
+        &mut self,
+        memory: &mut impl MemoryBus,
+    ) -> Result<(Instruction, u8), EmuError> {
+        let address = self.registers.pc;
+        decode_instruction(address, || self.fetch_byte(memory))
+    }
+
+    /// Run one instruction or interrupt dispatch, or idle for a cycle in HALT or STOP
+    pub fn step_instruction(&mut self, memory: &mut impl MemoryBus) -> StepResult {
+        let Some(mut cache) = self.block_cache.take() else {
+            return self.run(memory, None);
+        };
+        let mut log = WriteLog::new(memory);
+        let result = self.run(&mut log, Some(&mut cache));
+        log.invalidate(&mut cache);
+        self.block_cache = Some(cache);
+        result
+    }
+
+    /// Run one instruction or interrupt dispatch, taking the instruction from `cache` if given
+    fn run(&mut self, memory: &mut impl MemoryBus, cache: Option<&mut BlockCache>) -> StepResult {
+        if self.locked || self.error.is_some() {
+            return StepResult::idle(self.registers.pc);
+        }
+
+        if self.stopped {
+            if memory.peek_byte(IF) & Interrupt::Joypad.bit() == 0 {
+                return StepResult::idle(self.registers.pc);
+            }
+            self.stopped = false;
+        }
+
+        if let Some(result) = self.handle_interrupts(memory) {
+            return result;
+        }
+
+        if self.halted {
+            return StepResult::idle(self.registers.pc);
+        }
+
+        // EI takes effect here, so no interrupt comes between it and the next instruction and a
+        // DI right after it cancels it
+        if mem::take(&mut self.ime_pending) {
+            self.ime = true;
+        }
+
+        #[cfg(feature = "std")]
+        self.write_trace(memory);
+        memory.on_instruction(self.registers.pc);
+        let pc = self.registers.pc;
+        self.history.record(pc, memory.peek_byte(pc));
+        tracing::trace!(
+            target: "cpu",
+            pc = %format_args!("{pc:#06X}"),
+            opcode = %format_args!("{:#04X}", memory.peek_byte(pc)),
+            "executing"
+        );
+        let (instruction, cycles) = match cache.and_then(|cache| cache.next(pc, memory)) {
+            Some(cached) => {
+                let cycles = self.execute(cached.instruction, pc, cached.length, memory);
+                (Some(cached.instruction), cycles)
+            }
+            None => self.execute_next(memory),
+        };
+        StepResult {
+            address: pc,
+            instruction,
+            cycles,
+            interrupt: None,
         }
     }
 
-    pub fn tick(&mut self, memory: &mut Memory) -> u8 {
-        let instruction = self.fetch_instruction(memory);
+    /// Run `instruction`, decoded from `length` bytes at `address`
+    ///
+    /// PC is set to the next instruction first, which is what CALL and RST push and where
+    /// relative jumps count from, however the bytes were fetched
+    fn execute(
+        &mut self,
+        instruction: Instruction,
+        address: u16,
+        length: u8,
+        memory: &mut impl MemoryBus,
+    ) -> u8 {
+        self.registers.pc = address.wrapping_add(u16::from(length));
         instruction.execute(self, memory)
     }
+
+    /// Fetch the instruction at PC, decode it into an `Instruction` and run it
+    #[cfg(not(feature = "jump-table"))]
+    fn execute_next(&mut self, memory: &mut impl MemoryBus) -> (Option<Instruction>, u8) {
+        let address = self.registers.pc;
+        match self.fetch_instruction(memory) {
+            Ok((instruction, length)) => {
+                let cycles = self.execute(instruction, address, length, memory);
+                (Some(instruction), cycles)
+            }
+            Err(error) => (None, self.illegal_opcode(error)),
+        }
+    }
+
+    /// Fetch the opcode at PC and run its handler from the opcode table
+    ///
+    /// The handlers never build an `Instruction`, it is decoded on the side for `StepResult`
+    #[cfg(feature = "jump-table")]
+    fn execute_next(&mut self, memory: &mut impl MemoryBus) -> (Option<Instruction>, u8) {
+        let mut pc = self.registers.pc;
+        let instruction = decode_instruction(pc, || {
+            let byte = memory.peek_byte(pc);
+            pc = pc.wrapping_add(1);
+            byte
+        });
+        let instruction = instruction.ok().map(|(instruction, _)| instruction);
+        (instruction, dispatch::execute_next(self, memory))
+    }
+
+    /// Apply the illegal opcode policy, the opcode has been fetched
+    pub(super) fn illegal_opcode(&mut self, error: EmuError) -> u8 {
+        match self.illegal_opcode_policy {
+            IllegalOpcodePolicy::Lockup => {
+                tracing::warn!(target: "cpu", "{error}, the CPU locked up");
+                self.locked = true;
+            }
+            IllegalOpcodePolicy::Nop => {}
+            IllegalOpcodePolicy::Trap => {
+                let EmuError::IllegalOpcode { address, .. } = error;
+                self.registers.pc = address;
+                self.error = Some(error);
+            }
+        }
+        1
+    }
+
+    /// Wake from HALT on any pending interrupt and service it if IME is set
+    ///
+    /// Returns what was done if an interrupt was serviced
+    fn handle_interrupts(&mut self, memory: &mut impl MemoryBus) -> Option<StepResult> {
+        let interrupt = pending_interrupt(memory)?;
+        self.halted = false;
+
+        if !self.ime {
+            return None;
+        }
+
+        let return_address = self.registers.pc;
+        tracing::debug!(
+            target: "interrupts",
+            ?interrupt,
+            return_address = %format_args!("{return_address:#06X}"),
+            "dispatched"
+        );
+        self.ime = false;
+        clear_interrupt(memory, interrupt);
+        stack_push_16(self, memory, return_address);
+        self.registers.pc = interrupt.vector();
+        memory.on_interrupt(interrupt);
+
+        Some(StepResult {
+            address: return_address,
+            instruction: None,
+            cycles: INTERRUPT_DISPATCH_CYCLES,
+            interrupt: Some(interrupt),
+        })
+    }
+}
+
+/// The opcodes that decode to no instruction and hang the CPU
+pub(super) const ILLEGAL_OPCODES: [u8; 11] = [
+    0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+];
+
+/// Decode one instruction and its length in bytes, `fetch` is called for each of its bytes in
+/// order
+///
+/// Shared by the CPU and the disassembler, `address` is where the instruction starts and is
+/// only used to report illegal opcodes
+pub(super) fn decode_instruction(
+    address: u16,
+    mut fetch_byte: impl FnMut() -> u8,
+) -> Result<(Instruction, u8), EmuError> {
+    let mut length = 0;
+    let mut fetch = || {
+        length += 1;
+        fetch_byte()
+    };
+    // opcode == xxyyzzzz == xxaaabbb == iiijjbbb
+    let opcode = fetch();
+    let xx = opcode >> 6;
+    let yy = (opcode >> 4) & 0x3;
+    let zzzz = opcode & 0xF;
+    let aaa = (opcode >> 3) & 0x7;
+    let bbb = opcode & 0x7;
+    let iii = opcode >> 5;
+    let jj = (opcode >> 3) & 0x3;
+
+    // matching any one of the three tuples is enough to match the instruction, avoid mixing usage
+    let instruction = match ((xx, yy, zzzz), (xx, aaa, bbb), (iii, jj, bbb)) {
+        // Block 0
+        ((0x0, 0x0, 0x0), _, _) => Instruction::Nop, // NOP
+
+        ((0x0, _, 0x1), _, _) => Instruction::LdR16Imm16(R16::from(yy), fetch_word(&mut fetch)), // LD R16, imm16
+        ((0x0, _, 0x2), _, _) => Instruction::LdR16MemA(R16MEM::from(yy)), // LD (R16), A
+        ((0x0, _, 0xA), _, _) => Instruction::LdAR16Mem(R16MEM::from(yy)), // LD A, (R16)
+        ((0x0, 0x0, 0x8), _, _) => Instruction::LdMemImm16SP(fetch_word(&mut fetch)), // LD (imm16), SP
+
+        ((0x0, _, 0x3), _, _) => Instruction::IncR16(R16::from(yy)), // INC R16
+        ((0x0, _, 0xB), _, _) => Instruction::DecR16(R16::from(yy)), // DEC R16
+        ((0x0, _, 0x9), _, _) => Instruction::AddHlR16(R16::from(yy)), // ADD HL, R16
+
+        (_, (0x0, 0x6, 0x4), _) => Instruction::IncMemHl,
+        (_, (0x0, _, 0x4), _) => Instruction::IncR8(R8::from(aaa)), // INC R8
+        (_, (0x0, 0x6, 0x5), _) => Instruction::DecMemHl,
+        (_, (0x0, _, 0x5), _) => Instruction::DecR8(R8::from(aaa)), // DEC R8
+
+        (_, (0x0, 0x6, 0x6), _) => Instruction::LdMemHlImm8(fetch()),
+        (_, (0x0, _, 0x6), _) => Instruction::LdR8Imm8(R8::from(aaa), fetch()), // LD R8, Imm8
+
+        ((0x0, 0x0, 0x7), _, _) => Instruction::Rlca, // RLCA
+        ((0x0, 0x0, 0xF), _, _) => Instruction::Rrca, // RRCA
+        ((0x0, 0x1, 0x7), _, _) => Instruction::Rla,  // RLA
+        ((0x0, 0x1, 0xF), _, _) => Instruction::Rra,  // RRA
+        ((0x0, 0x2, 0x7), _, _) => Instruction::Daa,  // DAA
+        ((0x0, 0x2, 0xF), _, _) => Instruction::Cpl,  // CPL
+        ((0x0, 0x3, 0x7), _, _) => Instruction::Scf,  // SCF
+        ((0x0, 0x3, 0xF), _, _) => Instruction::Ccf,  // CCF
+
+        // Note: offset is signed
+        (_, _, (0x0, 0x3, 0x0)) => Instruction::JrImm8(fetch()), // JR imm8
+        (_, _, (0x1, _, 0x0)) => Instruction::JrCondImm8(Cond::from(jj), fetch()), // JR cond, imm8
+
+        ((0x0, 0x1, 0x0), _, _) => Instruction::Stop, // STOP
+
+        // Block 1
+        (_, (0x1, 0x6, 0x6), _) => Instruction::Halt, // HALT
+        (_, (0x1, 0x6, _), _) => Instruction::LdMemHlR8(R8::from(bbb)), // LD (HL), R8
+        (_, (0x1, _, 0x6), _) => Instruction::LdR8MemHl(R8::from(aaa)), // LD R8, (HL)
+        (_, (0x1, _, _), _) => Instruction::LdR8R8(R8::from(aaa), R8::from(bbb)), // LD R8, R8
+
+        // Block 2
+        (_, (0x2, 0x0, 0x6), _) => Instruction::AddAMemHl,
+        (_, (0x2, 0x0, _), _) => Instruction::AddAR8(R8::from(bbb)), // ADD A, R8
+        (_, (0x2, 0x1, 0x6), _) => Instruction::AdcAMemHl,
+        (_, (0x2, 0x1, _), _) => Instruction::AdcAR8(R8::from(bbb)), // ADC A, R8
+        (_, (0x2, 0x2, 0x6), _) => Instruction::SubAMemHl,
+        (_, (0x2, 0x2, _), _) => Instruction::SubAR8(R8::from(bbb)), // SUB A, R8
+        (_, (0x2, 0x3, 0x6), _) => Instruction::SbcAMemHl,
+        (_, (0x2, 0x3, _), _) => Instruction::SbcAR8(R8::from(bbb)), // SBC A, R8
+        (_, (0x2, 0x4, 0x6), _) => Instruction::AndAMemHl,
+        (_, (0x2, 0x4, _), _) => Instruction::AndAR8(R8::from(bbb)), // AND A, R8
+        (_, (0x2, 0x5, 0x6), _) => Instruction::XorAMemHl,
+        (_, (0x2, 0x5, _), _) => Instruction::XorAR8(R8::from(bbb)), // XOR A, R8
+        (_, (0x2, 0x6, 0x6), _) => Instruction::OrAMemHl,
+        (_, (0x2, 0x6, _), _) => Instruction::OrAR8(R8::from(bbb)), // OR A, R8
+        (_, (0x2, 0x7, 0x6), _) => Instruction::CpAMemHl,
+        (_, (0x2, 0x7, _), _) => Instruction::CpAR8(R8::from(bbb)), // CP A, R8
+
+        // Block 3
+        ((0x3, 0x0, 0x6), _, _) => Instruction::AddAImm8(fetch()), // ADD A, imm8
+        ((0x3, 0x0, 0xE), _, _) => Instruction::AdcAImm8(fetch()), // ADC A, imm8
+        ((0x3, 0x1, 0x6), _, _) => Instruction::SubAImm8(fetch()), // SUB A, imm8
+        ((0x3, 0x1, 0xE), _, _) => Instruction::SbcAImm8(fetch()), // SBC A, imm8
+        ((0x3, 0x2, 0x6), _, _) => Instruction::AndAImm8(fetch()), // AND A, imm8
+        ((0x3, 0x2, 0xE), _, _) => Instruction::XorAImm8(fetch()), // XOR A, imm8
+        ((0x3, 0x3, 0x6), _, _) => Instruction::OrAImm8(fetch()),  // OR A, imm8
+        ((0x3, 0x3, 0xE), _, _) => Instruction::CpAImm8(fetch()),  // CP A, imm8
+
+        (_, _, (0x6, _, 0x0)) => Instruction::RetCond(Cond::from(jj)), // RET cond
+        (_, _, (0x6, 0x1, 0x1)) => Instruction::Ret,                   // RET
+        (_, _, (0x6, 0x3, 0x1)) => Instruction::Reti,                  // RETI
+        (_, _, (0x6, _, 0x2)) => Instruction::JpCondImm16(Cond::from(jj), fetch_word(&mut fetch)), // JP cond, imm16
+        (_, _, (0x6, 0x0, 0x3)) => Instruction::JpImm16(fetch_word(&mut fetch)), // JP imm16
+        (_, _, (0x7, 0x1, 0x1)) => Instruction::JpHl,                            // JP HL
+        (_, _, (0x6, _, 0x4)) => Instruction::CallCondImm16(Cond::from(jj), fetch_word(&mut fetch)), // CALL cond, imm16
+        (_, _, (0x6, 0x1, 0x5)) => Instruction::CallImm16(fetch_word(&mut fetch)), // CALL imm16
+        (_, (0x3, _, 0x7), _) => Instruction::RstTgt3(TGT3::from(aaa)),            // RST tgt3
+
+        ((0x3, _, 0x1), _, _) => Instruction::PopR16Stk(R16STK::from(yy)), // POP R16
+        ((0x3, _, 0x5), _, _) => Instruction::PushR16Stk(R16STK::from(yy)), // PUSH R16
+
+        ((0x3, 0x0, 0xB), _, _) => map_prefixed_instruction(fetch()), // CB
+
+        ((0x3, 0x2, 0x2), _, _) => Instruction::LdhMemCA, // LD (C), A
+        ((0x3, 0x2, 0x0), _, _) => Instruction::LdhMemImm8A(fetch()), // LDH (imm8), A
+        ((0x3, 0x2, 0xA), _, _) => Instruction::LdMemImm16A(fetch_word(&mut fetch)), // LD (imm16), A
+        ((0x3, 0x3, 0x2), _, _) => Instruction::LdAMemC,                             // LD A, (C)
+        ((0x3, 0x3, 0x0), _, _) => Instruction::LdhAMemImm8(fetch()), // LDH A, (imm8)
+        ((0x3, 0x3, 0xA), _, _) => Instruction::LdAMemImm16(fetch_word(&mut fetch)), // LD A, (imm16)
+
+        ((0x3, 0x2, 0x8), _, _) => Instruction::AddSpImm8(fetch()),
+        ((0x3, 0x3, 0x8), _, _) => Instruction::LdHlSpImm8(fetch()),
+        ((0x3, 0x3, 0x9), _, _) => Instruction::LdSpHl,
+
+        ((0x3, 0x3, 0x3), _, _) => Instruction::Di,
+        ((0x3, 0x3, 0xB), _, _) => Instruction::Ei,
+
+        // ILLEGAL_OPCODES
+        _ => return Err(EmuError::IllegalOpcode { opcode, address }),
+    };
+    Ok((instruction, length))
+}
+
+fn fetch_word(fetch: &mut impl FnMut() -> u8) -> u16 {
+    let lo = fetch();
+    let hi = fetch();
+    combine(hi, lo)
 }
 
 fn map_prefixed_instruction(byte: u8) -> Instruction {
@@ -193,41 +614,392 @@ fn map_prefixed_instruction(byte: u8) -> Instruction {
         (0x1, _, _) => Instruction::BitB3R8(B3::from(aaa), R8::from(bbb)),
         (0x2, _, 0x6) => Instruction::ResB3MemHl(B3::from(aaa)),
         (0x2, _, _) => Instruction::ResB3R8(B3::from(aaa), R8::from(bbb)),
-        (0x3, _, 0x6) => Instruction::SetB3MemHl(B3::from(aaa)),
-        (0x3, _, _) => Instruction::SetB3R8(B3::from(aaa), R8::from(bbb)),
-        _ => panic!("Unknown prefixed instruction: {:#04X}", byte),
+        // every prefixed opcode is an instruction, xx is 0x3 from here on
+        (_, _, 0x6) => Instruction::SetB3MemHl(B3::from(aaa)),
+        _ => Instruction::SetB3R8(B3::from(aaa), R8::from(bbb)),
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for Cpu {
+    fn save_state(&self, state: &mut StateWriter) {
+        for register in SAVED_REGISTERS {
+            state.write_u16(self.registers.read_16(register));
+        }
+        state.write_bool(self.ime);
+        state.write_bool(self.ime_pending);
+        state.write_bool(self.halted);
+        state.write_bool(self.stopped);
+        state.write_bool(self.locked);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        for register in SAVED_REGISTERS {
+            let value = state.read_u16()?;
+            self.registers.write_16(register, value);
+        }
+        self.ime = state.read_bool()?;
+        self.ime_pending = state.read_bool()?;
+        self.halted = state.read_bool()?;
+        self.stopped = state.read_bool()?;
+        self.locked = state.read_bool()?;
+        self.flush_block_cache();
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gameboy::{
+        cpu::registers::Register16,
+        interrupts::{request_interrupt, Interrupt, IE, IF},
+        Memory, TimedBus,
+    };
 
     #[test]
-    fn test_fetch_instruction() {
-        let memory = Memory::new();
+    fn test_post_boot_registers() {
+        let cpu = Cpu::new();
+
+        assert_eq!(cpu.registers.read_16(Register16::AF), 0x01B0);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
+        assert_eq!(cpu.registers.pc, 0x0100);
+        assert_eq!(Cpu::power_on().registers.pc, 0x0000);
+    }
+
+    #[test]
+    fn test_illegal_opcode_lockup() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        memory.write_byte(0x0000, 0xD3);
+        memory.write_byte(IE, Interrupt::VBlank.bit());
+        cpu.ime = true;
+
+        assert!(!cpu.is_locked());
+        assert_eq!(cpu.step_instruction(&mut memory).cycles, 1);
+        assert!(cpu.is_locked());
+        request_interrupt(&mut memory, Interrupt::VBlank);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 1);
+        assert!(cpu.error().is_none());
+    }
+
+    #[test]
+    fn test_only_illegal_opcodes_lock() {
+        let unprefixed = (0..=0xFF).map(|opcode| [opcode, 0x00]);
+        let prefixed = (0..=0xFF).map(|opcode| [0xCB, opcode]);
+        for [opcode, operand] in unprefixed.chain(prefixed) {
+            let mut memory = Memory::new();
+            memory.write_byte(0xC000, opcode);
+            memory.write_byte(0xC001, operand);
+            let mut cpu = Cpu::power_on();
+            cpu.registers.pc = 0xC000;
+            cpu.registers.write_16(Register16::SP, 0xD000);
+            cpu.registers.write_16(Register16::HL, 0xC100);
+
+            cpu.step_instruction(&mut memory);
+            assert_eq!(
+                cpu.is_locked(),
+                ILLEGAL_OPCODES.contains(&opcode),
+                "opcode {opcode:#04X} {operand:#04X}"
+            );
+            assert!(cpu.error().is_none());
+        }
+    }
+
+    #[test]
+    fn test_illegal_opcode_nop() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Nop);
+        memory.write_byte(0x0000, 0xFD);
+
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 2);
+    }
+
+    #[test]
+    fn test_illegal_opcode_trap() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        cpu.set_illegal_opcode_policy(IllegalOpcodePolicy::Trap);
+        memory.write_byte(0x0001, 0xE4);
+
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 1);
+        assert_eq!(
+            cpu.take_error(),
+            Some(EmuError::IllegalOpcode {
+                opcode: 0xE4,
+                address: 0x0001
+            })
+        );
+
+        // trapped again until PC moves on
+        cpu.step_instruction(&mut memory);
+        assert!(cpu.error().is_some());
+        cpu.take_error();
+        cpu.registers.pc = 2;
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 3);
+    }
+
+    #[test]
+    fn test_pc_wraps() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        cpu.registers.pc = 0xFFFF;
+        memory.write_byte(0xFFFF, 0x00); // NOP, IE doubles as the last byte of memory
+
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 0x0000);
+    }
+
+    #[test]
+    fn test_interrupt_dispatch() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::new();
+        cpu.registers.pc = 0x1234;
+        cpu.registers.write_16(Register16::SP, 0xFFFE);
+        cpu.ime = true;
+        memory.write_byte(IE, Interrupt::Timer.bit());
+        request_interrupt(&mut memory, Interrupt::Timer);
+
+        assert_eq!(
+            cpu.step_instruction(&mut memory).cycles,
+            INTERRUPT_DISPATCH_CYCLES
+        );
+        assert_eq!(cpu.registers.pc, 0x50);
+        assert_eq!(memory.read_word(0xFFFC), 0x1234);
+        assert_eq!(memory.read_byte(IF), 0xE0); // the unused bits read as 1
+        assert!(!cpu.ime);
+    }
+
+    #[test]
+    fn test_interrupt_ignored_without_ime() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        memory.write_byte(IE, Interrupt::VBlank.bit());
+        request_interrupt(&mut memory, Interrupt::VBlank);
+
+        assert_eq!(cpu.step_instruction(&mut memory).cycles, 1); // NOP
+        assert_eq!(cpu.registers.pc, 1);
+        assert_eq!(memory.read_byte(IF), 0xE0 | Interrupt::VBlank.bit());
+    }
+
+    /// A CPU at 0x0000 running `program`, with a timer interrupt waiting
+    fn setup_pending_interrupt(program: &[u8]) -> (Cpu, Memory) {
+        let mut memory = Memory::new();
+        for (address, byte) in program.iter().enumerate() {
+            memory.write_byte(address as u16, *byte);
+        }
+        memory.write_byte(IE, Interrupt::Timer.bit());
+        request_interrupt(&mut memory, Interrupt::Timer);
+        let mut cpu = Cpu::power_on();
+        cpu.registers.write_16(Register16::SP, 0xFFFE);
+        (cpu, memory)
+    }
+
+    #[test]
+    fn test_step_result() {
+        // LD A, 0x12, EI, NOP
+        let (mut cpu, mut memory) = setup_pending_interrupt(&[0x3E, 0x12, 0xFB, 0x00]);
+
+        assert_eq!(
+            cpu.step_instruction(&mut memory),
+            StepResult {
+                address: 0x0000,
+                instruction: Some(Instruction::LdR8Imm8(R8::A, 0x12)),
+                cycles: 2,
+                interrupt: None,
+            }
+        );
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(
+            cpu.step_instruction(&mut memory),
+            StepResult {
+                address: 0x0004,
+                instruction: None,
+                cycles: INTERRUPT_DISPATCH_CYCLES,
+                interrupt: Some(Interrupt::Timer),
+            }
+        );
+
+        cpu.halted = true;
+        let idle = cpu.step_instruction(&mut memory);
+        assert_eq!((idle.instruction, idle.cycles), (None, 1));
+    }
+
+    #[test]
+    fn test_call_at_end_of_memory() {
+        let mut memory = Memory::new();
         let mut cpu = Cpu::new();
+        cpu.registers.write_16(Register16::SP, 0xD000);
+        // CALL 0x1234, with the high byte of the target in IE
+        cpu.registers.pc = 0xFFFD;
+        memory.write_byte(0xFFFD, 0xCD);
+        memory.write_byte(0xFFFE, 0x34);
+        memory.write_byte(0xFFFF, 0x12);
+
+        let result = cpu.step_instruction(&mut memory);
+        assert_eq!(result.instruction, Some(Instruction::CallImm16(0x1234)));
+        assert_eq!(cpu.registers.pc, 0x1234);
+        assert_eq!(memory.read_word(0xCFFE), 0x0000);
+
+        // RST 0x38 from IE
+        memory.write_byte(0xFFFF, 0xFF);
+        cpu.registers.pc = 0xFFFF;
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 0x0038);
+        assert_eq!(memory.read_word(0xCFFC), 0x0000);
+    }
+
+    #[test]
+    fn test_cached_call_pushes_next_address() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::new();
+        cpu.set_block_cache(true);
+        cpu.registers.write_16(Register16::SP, 0xD000);
+        // NOP, CALL 0xC100
+        cpu.registers.pc = 0xC000;
+        for (offset, byte) in [0x00, 0xCD, 0x00, 0xC1].into_iter().enumerate() {
+            memory.write_byte(0xC000 + offset as u16, byte);
+        }
+
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 0xC100);
+        assert_eq!(memory.read_word(0xCFFE), 0xC004);
+    }
+
+    #[test]
+    fn test_ei_delay() {
+        // EI, NOP, NOP
+        let (mut cpu, mut memory) = setup_pending_interrupt(&[0xFB, 0x00, 0x00]);
+
+        cpu.step_instruction(&mut memory);
+        assert!(!cpu.ime());
+        assert!(cpu.ime_pending());
+        // the instruction after EI still runs before the interrupt
+        assert_eq!(cpu.step_instruction(&mut memory).cycles, 1);
+        assert_eq!(cpu.registers.pc, 2);
+        assert!(cpu.ime());
+
+        assert_eq!(
+            cpu.step_instruction(&mut memory).cycles,
+            INTERRUPT_DISPATCH_CYCLES
+        );
+        assert_eq!(cpu.registers.pc, Interrupt::Timer.vector());
+        assert_eq!(memory.read_word(0xFFFC), 2);
+    }
+
+    #[test]
+    fn test_di_cancels_ei() {
+        // EI, DI, NOP, EI, EI, NOP
+        let (mut cpu, mut memory) = setup_pending_interrupt(&[0xFB, 0xF3, 0x00, 0xFB, 0xFB, 0x00]);
+
+        for _ in 0..3 {
+            cpu.step_instruction(&mut memory);
+        }
+        assert_eq!(cpu.registers.pc, 3);
+        assert!(!cpu.ime());
+        assert_ne!(memory.read_byte(IF) & Interrupt::Timer.bit(), 0);
+
+        // EI twice in a row dispatches after the second one
+        cpu.step_instruction(&mut memory);
+        cpu.step_instruction(&mut memory);
+        assert_eq!(
+            cpu.step_instruction(&mut memory).cycles,
+            INTERRUPT_DISPATCH_CYCLES
+        );
+        assert_eq!(memory.read_word(0xFFFC), 5);
+    }
+
+    #[test]
+    fn test_reti_enables_at_once() {
+        // RETI
+        let (mut cpu, mut memory) = setup_pending_interrupt(&[0xD9]);
+        cpu.registers.write_16(Register16::SP, 0xFFFC);
+        memory.write_word(0xFFFC, 0x0100);
+
+        cpu.step_instruction(&mut memory);
+        assert_eq!(cpu.registers.pc, 0x0100);
+        assert_eq!(
+            cpu.step_instruction(&mut memory).cycles,
+            INTERRUPT_DISPATCH_CYCLES
+        );
+        assert_eq!(memory.read_word(0xFFFC), 0x0100);
+    }
+
+    #[test]
+    fn test_halt_wakes_on_interrupt() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        cpu.halted = true;
+
+        assert_eq!(cpu.step_instruction(&mut memory).cycles, 1);
+        assert_eq!(cpu.registers.pc, 0);
+
+        memory.write_byte(IE, Interrupt::Joypad.bit());
+        request_interrupt(&mut memory, Interrupt::Joypad);
+        cpu.step_instruction(&mut memory);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.pc, 1);
+    }
+
+    #[test]
+    fn test_stop_wakes_on_joypad() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
+        cpu.stopped = true;
+        request_interrupt(&mut memory, Interrupt::Timer);
+
+        assert_eq!(cpu.step_instruction(&mut memory).cycles, 1);
+        assert_eq!(cpu.registers.pc, 0);
+
+        request_interrupt(&mut memory, Interrupt::Joypad);
+        cpu.step_instruction(&mut memory);
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.registers.pc, 1);
+    }
+
+    #[test]
+    fn test_fetch_instruction() {
+        let mut memory = Memory::new();
+        let mut cpu = Cpu::power_on();
 
         memory.write_byte(0, 0x00);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Nop);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Nop
+        );
 
         memory.write_byte(1, 0x01);
         memory.write_byte(2, 0x34);
         memory.write_byte(3, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdR16Imm16(R16::BC, 0x1234)
         );
 
         memory.write_byte(4, 0x02);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdR16MemA(R16MEM::BC)
         );
 
         memory.write_byte(5, 0x0A);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdAR16Mem(R16MEM::BC)
         );
 
@@ -235,38 +1007,56 @@ mod tests {
         memory.write_byte(7, 0x34);
         memory.write_byte(8, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdMemImm16SP(0x1234)
         );
 
         memory.write_byte(9, 0x03);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::IncR16(R16::BC));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::IncR16(R16::BC)
+        );
 
         memory.write_byte(10, 0x0B);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::DecR16(R16::BC));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::DecR16(R16::BC)
+        );
 
         memory.write_byte(11, 0x09);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::AddHlR16(R16::BC)
         );
 
         memory.write_byte(12, 0x04);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::IncR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::IncR8(R8::B)
+        );
 
         memory.write_byte(13, 0x34);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::IncMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::IncMemHl
+        );
 
         memory.write_byte(14, 0x05);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::DecR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::DecR8(R8::B)
+        );
 
         memory.write_byte(15, 0x35);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::DecMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::DecMemHl
+        );
 
         memory.write_byte(16, 0x06);
         memory.write_byte(17, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdR8Imm8(R8::B, 0x12)
         );
         println!("reached");
@@ -274,183 +1064,300 @@ mod tests {
         memory.write_byte(18, 0x36);
         memory.write_byte(19, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdMemHlImm8(0x12)
         );
         println!("reached");
 
         memory.write_byte(20, 0x07);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Rlca);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Rlca
+        );
 
         memory.write_byte(21, 0x0F);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Rrca);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Rrca
+        );
 
         memory.write_byte(22, 0x17);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Rla);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Rla
+        );
 
         memory.write_byte(23, 0x1F);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Rra);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Rra
+        );
 
         memory.write_byte(24, 0x27);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Daa);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Daa
+        );
 
         memory.write_byte(25, 0x2F);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Cpl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Cpl
+        );
 
         memory.write_byte(26, 0x37);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Scf);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Scf
+        );
 
         memory.write_byte(27, 0x3F);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Ccf);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Ccf
+        );
 
         memory.write_byte(28, 0x18);
         memory.write_byte(29, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::JrImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::JrImm8(0x12)
+        );
 
         memory.write_byte(30, 0x20);
         memory.write_byte(31, 0x10);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::JrCondImm8(Cond::NotZero, 0x10)
         );
 
         memory.write_byte(32, 0x10);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Stop);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Stop
+        );
 
         memory.write_byte(33, 0x76);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Halt);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Halt
+        );
 
         memory.write_byte(34, 0x40);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdR8R8(R8::B, R8::B)
         );
 
         memory.write_byte(35, 0x46);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdR8MemHl(R8::B)
         );
 
         memory.write_byte(36, 0x70);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdMemHlR8(R8::B)
         );
 
         memory.write_byte(37, 0x80);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AddAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AddAR8(R8::B)
+        );
 
         memory.write_byte(38, 0x86);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AddAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AddAMemHl
+        );
 
         memory.write_byte(39, 0x88);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AdcAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AdcAR8(R8::B)
+        );
 
         memory.write_byte(40, 0x8E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AdcAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AdcAMemHl
+        );
 
         memory.write_byte(41, 0x90);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SubAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SubAR8(R8::B)
+        );
 
         memory.write_byte(42, 0x96);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SubAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SubAMemHl
+        );
 
         memory.write_byte(43, 0x98);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SbcAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SbcAR8(R8::B)
+        );
 
         memory.write_byte(44, 0x9E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SbcAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SbcAMemHl
+        );
 
         memory.write_byte(45, 0xA0);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AndAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AndAR8(R8::B)
+        );
 
         memory.write_byte(46, 0xA6);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AndAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AndAMemHl
+        );
 
         memory.write_byte(47, 0xA8);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::XorAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::XorAR8(R8::B)
+        );
 
         memory.write_byte(48, 0xAE);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::XorAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::XorAMemHl
+        );
 
         memory.write_byte(49, 0xB0);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::OrAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::OrAR8(R8::B)
+        );
 
         memory.write_byte(50, 0xB6);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::OrAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::OrAMemHl
+        );
 
         memory.write_byte(51, 0xB8);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::CpAR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::CpAR8(R8::B)
+        );
 
         memory.write_byte(52, 0xBE);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::CpAMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::CpAMemHl
+        );
 
         memory.write_byte(53, 0xC6);
         memory.write_byte(54, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AddAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AddAImm8(0x12)
+        );
 
         memory.write_byte(55, 0xCE);
         memory.write_byte(56, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AdcAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AdcAImm8(0x12)
+        );
 
         memory.write_byte(57, 0xD6);
         memory.write_byte(58, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SubAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SubAImm8(0x12)
+        );
 
         memory.write_byte(59, 0xDE);
         memory.write_byte(60, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SbcAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SbcAImm8(0x12)
+        );
 
         memory.write_byte(61, 0xE6);
         memory.write_byte(62, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AndAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AndAImm8(0x12)
+        );
 
         memory.write_byte(63, 0xEE);
         memory.write_byte(64, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::XorAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::XorAImm8(0x12)
+        );
 
         memory.write_byte(65, 0xF6);
         memory.write_byte(66, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::OrAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::OrAImm8(0x12)
+        );
 
         memory.write_byte(67, 0xFE);
         memory.write_byte(68, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::CpAImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::CpAImm8(0x12)
+        );
 
         memory.write_byte(69, 0xC0);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::RetCond(Cond::NotZero)
         );
 
         memory.write_byte(70, 0xC9);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Ret);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Ret
+        );
 
         memory.write_byte(71, 0xD9);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Reti);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Reti
+        );
 
         memory.write_byte(72, 0xC2);
         memory.write_byte(73, 0x12);
         memory.write_byte(74, 0x34);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::JpCondImm16(Cond::NotZero, 0x3412)
         );
 
         memory.write_byte(75, 0xC3);
         memory.write_byte(76, 0x12);
         memory.write_byte(77, 0x34);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::JpImm16(0x3412));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::JpImm16(0x3412)
+        );
 
         memory.write_byte(78, 0xE9);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::JpHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::JpHl
+        );
 
         memory.write_byte(79, 0xC4);
         memory.write_byte(80, 0x12);
         memory.write_byte(81, 0x34);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::CallCondImm16(Cond::NotZero, 0x3412)
         );
 
@@ -458,141 +1365,192 @@ mod tests {
         memory.write_byte(83, 0x12);
         memory.write_byte(84, 0x34);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::CallImm16(0x3412)
         );
 
         memory.write_byte(85, 0xC7);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::RstTgt3(TGT3::Zero)
         );
 
         memory.write_byte(86, 0xC1);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::PopR16Stk(R16STK::BC)
         );
 
         memory.write_byte(87, 0xC5);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::PushR16Stk(R16STK::BC)
         );
 
         memory.write_byte(88, 0xCB);
         memory.write_byte(89, 0x00);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RlcR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RlcR8(R8::B)
+        );
 
         memory.write_byte(90, 0xCB);
         memory.write_byte(91, 0x06);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RlcMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RlcMemHl
+        );
 
         memory.write_byte(92, 0xCB);
         memory.write_byte(93, 0x08);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RrcR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RrcR8(R8::B)
+        );
 
         memory.write_byte(94, 0xCB);
         memory.write_byte(95, 0x0E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RrcMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RrcMemHl
+        );
 
         memory.write_byte(96, 0xCB);
         memory.write_byte(97, 0x10);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RlR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RlR8(R8::B)
+        );
 
         memory.write_byte(98, 0xCB);
         memory.write_byte(99, 0x16);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RlMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RlMemHl
+        );
 
         memory.write_byte(100, 0xCB);
         memory.write_byte(101, 0x18);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RrR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RrR8(R8::B)
+        );
 
         memory.write_byte(102, 0xCB);
         memory.write_byte(103, 0x1E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::RrMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::RrMemHl
+        );
 
         memory.write_byte(104, 0xCB);
         memory.write_byte(105, 0x20);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SlaR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SlaR8(R8::B)
+        );
 
         memory.write_byte(106, 0xCB);
         memory.write_byte(107, 0x26);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SlaMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SlaMemHl
+        );
 
         memory.write_byte(108, 0xCB);
         memory.write_byte(109, 0x28);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SraR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SraR8(R8::B)
+        );
 
         memory.write_byte(110, 0xCB);
         memory.write_byte(111, 0x2E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SraMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SraMemHl
+        );
 
         memory.write_byte(112, 0xCB);
         memory.write_byte(113, 0x30);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SwapR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SwapR8(R8::B)
+        );
 
         memory.write_byte(114, 0xCB);
         memory.write_byte(115, 0x36);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SwapMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SwapMemHl
+        );
 
         memory.write_byte(116, 0xCB);
         memory.write_byte(117, 0x38);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SrlR8(R8::B));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SrlR8(R8::B)
+        );
 
         memory.write_byte(118, 0xCB);
         memory.write_byte(119, 0x3E);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::SrlMemHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::SrlMemHl
+        );
 
         memory.write_byte(120, 0xCB);
         memory.write_byte(121, 0x40);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::BitB3R8(B3::Zero, R8::B)
         );
 
         memory.write_byte(122, 0xCB);
         memory.write_byte(123, 0x46);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::BitB3MemHl(B3::Zero)
         );
 
         memory.write_byte(124, 0xCB);
         memory.write_byte(125, 0x80);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::ResB3R8(B3::Zero, R8::B)
         );
 
         memory.write_byte(126, 0xCB);
         memory.write_byte(127, 0x86);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::ResB3MemHl(B3::Zero)
         );
 
         memory.write_byte(128, 0xCB);
         memory.write_byte(129, 0xC0);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::SetB3R8(B3::Zero, R8::B)
         );
 
         memory.write_byte(130, 0xCB);
         memory.write_byte(131, 0xC6);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::SetB3MemHl(B3::Zero)
         );
 
         memory.write_byte(132, 0xE2);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::LdhMemCA);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::LdhMemCA
+        );
 
         memory.write_byte(133, 0xE0);
         memory.write_byte(134, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdhMemImm8A(0x12)
         );
 
@@ -600,17 +1558,20 @@ mod tests {
         memory.write_byte(136, 0x34);
         memory.write_byte(137, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdMemImm16A(0x1234)
         );
 
         memory.write_byte(138, 0xF2);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::LdAMemC);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::LdAMemC
+        );
 
         memory.write_byte(139, 0xF0);
         memory.write_byte(140, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdhAMemImm8(0x12)
         );
 
@@ -618,29 +1579,41 @@ mod tests {
         memory.write_byte(142, 0x34);
         memory.write_byte(143, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdAMemImm16(0x1234)
         );
 
         memory.write_byte(144, 0xE8);
         memory.write_byte(145, 0x12);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::AddSpImm8(0x12));
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::AddSpImm8(0x12)
+        );
 
         memory.write_byte(146, 0xF8);
         memory.write_byte(147, 0x12);
         assert_eq!(
-            cpu.fetch_instruction(&memory),
+            cpu.fetch_instruction(&mut memory).unwrap().0,
             Instruction::LdHlSpImm8(0x12)
         );
 
         memory.write_byte(148, 0xF9);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::LdSpHl);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::LdSpHl
+        );
 
         memory.write_byte(149, 0xF3);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Di);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Di
+        );
 
         memory.write_byte(150, 0xFB);
-        assert_eq!(cpu.fetch_instruction(&memory), Instruction::Ei);
+        assert_eq!(
+            cpu.fetch_instruction(&mut memory).unwrap().0,
+            Instruction::Ei
+        );
     }
 
     #[test]
@@ -686,4 +1659,29 @@ mod tests {
             Instruction::SetB3MemHl(B3::Zero)
         );
     }
+
+    #[test]
+    fn test_accesses_fit_in_instruction_cycles() {
+        let unprefixed = (0..=0xFF)
+            .filter(|opcode| *opcode != 0xCB && !ILLEGAL_OPCODES.contains(opcode))
+            .map(|opcode| [opcode, 0x00]);
+        let prefixed = (0..=0xFF).map(|opcode| [0xCB, opcode]);
+        for [opcode, operand] in unprefixed.chain(prefixed) {
+            let mut memory = Memory::new();
+            memory.write_byte(0xC000, opcode);
+            memory.write_byte(0xC001, operand);
+            let mut cpu = Cpu::power_on();
+            cpu.registers.pc = 0xC000;
+            cpu.registers.write_16(Register16::SP, 0xD000);
+            cpu.registers.write_16(Register16::HL, 0xC100);
+
+            let mut bus = TimedBus::new(&mut memory);
+            let cycles = cpu.step_instruction(&mut bus).cycles;
+            assert!(
+                bus.cycles() <= cycles,
+                "opcode {opcode:#04X} {operand:#04X} made {} accesses in {cycles} cycles",
+                bus.cycles()
+            );
+        }
+    }
 }
@@ -0,0 +1,467 @@
+//! Turns instructions in memory back into assembly text, for tracing and debugging.
+//!
+//! Decoding goes through the same tables as the CPU, memory is read with `peek_byte`
+//! so disassembling never advances the emulated clock. With `Symbols` jump targets and
+//! addresses read or written are shown by their labels.
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    gameboy::{MemoryBus, Symbols},
+    utils::EmuError,
+};
+
+use super::{
+    cpu_core::decode_instruction,
+    instruction_variables::{Cond, B3, R16, R16MEM, R16STK, R8, TGT3},
+    instructions::Instruction,
+};
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembledInstruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+    /// The label at `address`, if the disassembler has symbols
+    pub label: Option<String>,
+}
+
+impl fmt::Display for DisassembledInstruction {
+    /// `0150: 3E 01     LD A, $01`, after a `Main:` line when labelled
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{label}:")?;
+        }
+        let bytes: Vec<String> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        write!(
+            f,
+            "{:04X}: {:<9} {}",
+            self.address,
+            bytes.join(" "),
+            self.text
+        )
+    }
+}
+
+/// Decodes instructions from any memory bus
+pub struct Disassembler<'a, B: MemoryBus> {
+    memory: &'a B,
+    symbols: Option<&'a Symbols>,
+}
+
+impl<'a, B: MemoryBus> Disassembler<'a, B> {
+    pub fn new(memory: &'a B) -> Disassembler<'a, B> {
+        Disassembler {
+            memory,
+            symbols: None,
+        }
+    }
+
+    /// Name addresses by their labels in `symbols`, if any
+    pub fn with_symbols(mut self, symbols: Option<&'a Symbols>) -> Disassembler<'a, B> {
+        self.symbols = symbols;
+        self
+    }
+
+    /// The label at `address` in the bank mapped there
+    pub fn label(&self, address: u16) -> Option<&'a str> {
+        self.symbols?.lookup(self.memory, address)
+    }
+
+    /// An address operand, `$C000` or its label
+    fn operand(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => label.to_string(),
+            None => format!("${address:04X}"),
+        }
+    }
+
+    /// Decode the instruction starting at `address`
+    pub fn disassemble(&self, address: u16) -> DisassembledInstruction {
+        let mut bytes = Vec::with_capacity(3);
+        let mut pc = address;
+        let decoded = decode_instruction(address, || {
+            let byte = self.memory.peek_byte(pc);
+            pc = pc.wrapping_add(1);
+            bytes.push(byte);
+            byte
+        });
+
+        let text = match decoded {
+            Ok((instruction, _)) => mnemonic(&instruction, pc, |address| self.operand(address)),
+            Err(EmuError::IllegalOpcode { opcode, .. }) => format!("DB ${opcode:02X}"),
+        };
+        DisassembledInstruction {
+            address,
+            text,
+            bytes,
+            label: self.label(address).map(str::to_string),
+        }
+    }
+
+    /// Decode consecutive instructions from `start` until one begins past `end`
+    pub fn range(&self, start: u16, end: u16) -> Instructions<'_, 'a, B> {
+        Instructions {
+            disassembler: self,
+            next: Some(start).filter(|&start| start <= end),
+            end,
+        }
+    }
+}
+
+/// Iterator over consecutive instructions, created by `Disassembler::range`
+pub struct Instructions<'d, 'a, B: MemoryBus> {
+    disassembler: &'d Disassembler<'a, B>,
+    next: Option<u16>,
+    end: u16,
+}
+
+impl<B: MemoryBus> Iterator for Instructions<'_, '_, B> {
+    type Item = DisassembledInstruction;
+
+    fn next(&mut self) -> Option<DisassembledInstruction> {
+        let address = self.next?;
+        let instruction = self.disassembler.disassemble(address);
+
+        // stop at the end of the range, and when running off the end of the address space
+        let next = address.checked_add(instruction.bytes.len() as u16);
+        self.next = next.filter(|&next| next <= self.end);
+        Some(instruction)
+    }
+}
+
+/// The assembly text of an instruction, `next_pc` is the address after it for relative jumps
+/// and `operand` formats the addresses jumped to, read or written
+fn mnemonic(instruction: &Instruction, next_pc: u16, operand: impl Fn(u16) -> String) -> String {
+    match instruction {
+        // Block 0
+        Instruction::Nop => "NOP".to_string(),
+        Instruction::LdR16Imm16(register, value) => format!("LD {}, ${value:04X}", r16(register)),
+        Instruction::LdR16MemA(register) => format!("LD ({}), A", r16_mem(register)),
+        Instruction::LdAR16Mem(register) => format!("LD A, ({})", r16_mem(register)),
+        Instruction::LdMemImm16SP(address) => format!("LD ({}), SP", operand(*address)),
+
+        Instruction::IncR16(register) => format!("INC {}", r16(register)),
+        Instruction::DecR16(register) => format!("DEC {}", r16(register)),
+        Instruction::AddHlR16(register) => format!("ADD HL, {}", r16(register)),
+
+        Instruction::IncR8(register) => format!("INC {}", r8(register)),
+        Instruction::IncMemHl => "INC (HL)".to_string(),
+        Instruction::DecR8(register) => format!("DEC {}", r8(register)),
+        Instruction::DecMemHl => "DEC (HL)".to_string(),
+        Instruction::LdR8Imm8(register, value) => format!("LD {}, ${value:02X}", r8(register)),
+        Instruction::LdMemHlImm8(value) => format!("LD (HL), ${value:02X}"),
+
+        Instruction::Rlca => "RLCA".to_string(),
+        Instruction::Rrca => "RRCA".to_string(),
+        Instruction::Rla => "RLA".to_string(),
+        Instruction::Rra => "RRA".to_string(),
+        Instruction::Daa => "DAA".to_string(),
+        Instruction::Cpl => "CPL".to_string(),
+        Instruction::Scf => "SCF".to_string(),
+        Instruction::Ccf => "CCF".to_string(),
+
+        Instruction::JrImm8(offset) => format!("JR {}", operand(relative(next_pc, *offset))),
+        Instruction::JrCondImm8(condition, offset) => {
+            format!(
+                "JR {}, {}",
+                cond(condition),
+                operand(relative(next_pc, *offset))
+            )
+        }
+
+        Instruction::Stop => "STOP".to_string(),
+
+        // Block 1
+        Instruction::LdR8R8(destination, source) => {
+            format!("LD {}, {}", r8(destination), r8(source))
+        }
+        Instruction::LdR8MemHl(register) => format!("LD {}, (HL)", r8(register)),
+        Instruction::LdMemHlR8(register) => format!("LD (HL), {}", r8(register)),
+        Instruction::Halt => "HALT".to_string(),
+
+        // Block 2
+        Instruction::AddAR8(register) => format!("ADD A, {}", r8(register)),
+        Instruction::AddAMemHl => "ADD A, (HL)".to_string(),
+        Instruction::AdcAR8(register) => format!("ADC A, {}", r8(register)),
+        Instruction::AdcAMemHl => "ADC A, (HL)".to_string(),
+        Instruction::SubAR8(register) => format!("SUB A, {}", r8(register)),
+        Instruction::SubAMemHl => "SUB A, (HL)".to_string(),
+        Instruction::SbcAR8(register) => format!("SBC A, {}", r8(register)),
+        Instruction::SbcAMemHl => "SBC A, (HL)".to_string(),
+        Instruction::AndAR8(register) => format!("AND A, {}", r8(register)),
+        Instruction::AndAMemHl => "AND A, (HL)".to_string(),
+        Instruction::XorAR8(register) => format!("XOR A, {}", r8(register)),
+        Instruction::XorAMemHl => "XOR A, (HL)".to_string(),
+        Instruction::OrAR8(register) => format!("OR A, {}", r8(register)),
+        Instruction::OrAMemHl => "OR A, (HL)".to_string(),
+        Instruction::CpAR8(register) => format!("CP A, {}", r8(register)),
+        Instruction::CpAMemHl => "CP A, (HL)".to_string(),
+
+        // Block 3
+        Instruction::AddAImm8(value) => format!("ADD A, ${value:02X}"),
+        Instruction::AdcAImm8(value) => format!("ADC A, ${value:02X}"),
+        Instruction::SubAImm8(value) => format!("SUB A, ${value:02X}"),
+        Instruction::SbcAImm8(value) => format!("SBC A, ${value:02X}"),
+        Instruction::AndAImm8(value) => format!("AND A, ${value:02X}"),
+        Instruction::XorAImm8(value) => format!("XOR A, ${value:02X}"),
+        Instruction::OrAImm8(value) => format!("OR A, ${value:02X}"),
+        Instruction::CpAImm8(value) => format!("CP A, ${value:02X}"),
+
+        Instruction::RetCond(condition) => format!("RET {}", cond(condition)),
+        Instruction::Ret => "RET".to_string(),
+        Instruction::Reti => "RETI".to_string(),
+        Instruction::JpCondImm16(condition, address) => {
+            format!("JP {}, {}", cond(condition), operand(*address))
+        }
+        Instruction::JpImm16(address) => format!("JP {}", operand(*address)),
+        Instruction::JpHl => "JP HL".to_string(),
+        Instruction::CallCondImm16(condition, address) => {
+            format!("CALL {}, {}", cond(condition), operand(*address))
+        }
+        Instruction::CallImm16(address) => format!("CALL {}", operand(*address)),
+        Instruction::RstTgt3(target) => format!("RST ${:02X}", tgt3(target)),
+
+        Instruction::PopR16Stk(register) => format!("POP {}", r16_stk(register)),
+        Instruction::PushR16Stk(register) => format!("PUSH {}", r16_stk(register)),
+
+        Instruction::LdhMemCA => "LDH (C), A".to_string(),
+        Instruction::LdhMemImm8A(offset) => format!("LDH ({}), A", operand(high(*offset))),
+        Instruction::LdMemImm16A(address) => format!("LD ({}), A", operand(*address)),
+        Instruction::LdAMemC => "LDH A, (C)".to_string(),
+        Instruction::LdhAMemImm8(offset) => format!("LDH A, ({})", operand(high(*offset))),
+        Instruction::LdAMemImm16(address) => format!("LD A, ({})", operand(*address)),
+
+        Instruction::AddSpImm8(offset) => format!("ADD SP, {}", *offset as i8),
+        Instruction::LdHlSpImm8(offset) => format!("LD HL, SP{:+}", *offset as i8),
+        Instruction::LdSpHl => "LD SP, HL".to_string(),
+
+        Instruction::Di => "DI".to_string(),
+        Instruction::Ei => "EI".to_string(),
+
+        // Prefix CB
+        Instruction::RlcMemHl => "RLC (HL)".to_string(),
+        Instruction::RlcR8(register) => format!("RLC {}", r8(register)),
+        Instruction::RrcMemHl => "RRC (HL)".to_string(),
+        Instruction::RrcR8(register) => format!("RRC {}", r8(register)),
+        Instruction::RlMemHl => "RL (HL)".to_string(),
+        Instruction::RlR8(register) => format!("RL {}", r8(register)),
+        Instruction::RrMemHl => "RR (HL)".to_string(),
+        Instruction::RrR8(register) => format!("RR {}", r8(register)),
+        Instruction::SlaMemHl => "SLA (HL)".to_string(),
+        Instruction::SlaR8(register) => format!("SLA {}", r8(register)),
+        Instruction::SraMemHl => "SRA (HL)".to_string(),
+        Instruction::SraR8(register) => format!("SRA {}", r8(register)),
+        Instruction::SwapMemHl => "SWAP (HL)".to_string(),
+        Instruction::SwapR8(register) => format!("SWAP {}", r8(register)),
+        Instruction::SrlMemHl => "SRL (HL)".to_string(),
+        Instruction::SrlR8(register) => format!("SRL {}", r8(register)),
+
+        Instruction::BitB3MemHl(bit) => format!("BIT {}, (HL)", b3(bit)),
+        Instruction::BitB3R8(bit, register) => format!("BIT {}, {}", b3(bit), r8(register)),
+        Instruction::ResB3MemHl(bit) => format!("RES {}, (HL)", b3(bit)),
+        Instruction::ResB3R8(bit, register) => format!("RES {}, {}", b3(bit), r8(register)),
+        Instruction::SetB3MemHl(bit) => format!("SET {}, (HL)", b3(bit)),
+        Instruction::SetB3R8(bit, register) => format!("SET {}, {}", b3(bit), r8(register)),
+    }
+}
+
+/// target of a relative jump, the offset is signed
+fn relative(next_pc: u16, offset: u8) -> u16 {
+    next_pc.wrapping_add_signed(i16::from(offset as i8))
+}
+
+/// the address an LDH offset points at in 0xFF00-0xFFFF
+fn high(offset: u8) -> u16 {
+    0xFF00 | u16::from(offset)
+}
+
+fn r8(register: &R8) -> &'static str {
+    match register {
+        R8::B => "B",
+        R8::C => "C",
+        R8::D => "D",
+        R8::E => "E",
+        R8::H => "H",
+        R8::L => "L",
+        R8::A => "A",
+    }
+}
+
+fn r16(register: &R16) -> &'static str {
+    match register {
+        R16::BC => "BC",
+        R16::DE => "DE",
+        R16::HL => "HL",
+        R16::SP => "SP",
+    }
+}
+
+fn r16_stk(register: &R16STK) -> &'static str {
+    match register {
+        R16STK::BC => "BC",
+        R16STK::DE => "DE",
+        R16STK::HL => "HL",
+        R16STK::AF => "AF",
+    }
+}
+
+fn r16_mem(register: &R16MEM) -> &'static str {
+    match register {
+        R16MEM::BC => "BC",
+        R16MEM::DE => "DE",
+        R16MEM::Hli => "HL+",
+        R16MEM::Hld => "HL-",
+    }
+}
+
+fn cond(condition: &Cond) -> &'static str {
+    match condition {
+        Cond::Zero => "Z",
+        Cond::NotZero => "NZ",
+        Cond::Carry => "C",
+        Cond::NotCarry => "NC",
+    }
+}
+
+fn b3(bit: &B3) -> u8 {
+    match bit {
+        B3::Zero => 0,
+        B3::One => 1,
+        B3::Two => 2,
+        B3::Three => 3,
+        B3::Four => 4,
+        B3::Five => 5,
+        B3::Six => 6,
+        B3::Seven => 7,
+    }
+}
+
+fn tgt3(target: &TGT3) -> u8 {
+    match target {
+        TGT3::Zero => 0x00,
+        TGT3::One => 0x08,
+        TGT3::Two => 0x10,
+        TGT3::Three => 0x18,
+        TGT3::Four => 0x20,
+        TGT3::Five => 0x28,
+        TGT3::Six => 0x30,
+        TGT3::Seven => 0x38,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Memory;
+
+    fn setup(program: &[u8]) -> Memory {
+        let mut memory = Memory::new();
+        for (offset, byte) in program.iter().enumerate() {
+            memory.write_byte(0xC000 + offset as u16, *byte);
+        }
+        memory
+    }
+
+    #[test]
+    fn test_disassemble() {
+        // LD BC, $1234
+        let memory = setup(&[0x01, 0x34, 0x12]);
+        let instruction = Disassembler::new(&memory).disassemble(0xC000);
+
+        assert_eq!(instruction.bytes, vec![0x01, 0x34, 0x12]);
+        assert_eq!(instruction.text, "LD BC, $1234");
+        assert_eq!(instruction.to_string(), "C000: 01 34 12  LD BC, $1234");
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let cases: [(&[u8], &str); 10] = [
+            (&[0x22], "LD (HL+), A"),
+            (&[0x18, 0xFE], "JR $C000"),
+            (&[0x20, 0x02], "JR NZ, $C004"),
+            (&[0xE0, 0x44], "LDH ($FF44), A"),
+            (&[0xF8, 0xFE], "LD HL, SP-2"),
+            (&[0xE8, 0x05], "ADD SP, 5"),
+            (&[0xFF], "RST $38"),
+            (&[0xF5], "PUSH AF"),
+            (&[0xCB, 0x7C], "BIT 7, H"),
+            (&[0xCB, 0x36], "SWAP (HL)"),
+        ];
+        for (program, text) in cases {
+            let memory = setup(program);
+            let instruction = Disassembler::new(&memory).disassemble(0xC000);
+            assert_eq!(instruction.text, text);
+            assert_eq!(instruction.bytes, program);
+        }
+    }
+
+    #[test]
+    fn test_illegal_opcode() {
+        let memory = setup(&[0xDD]);
+        let instruction = Disassembler::new(&memory).disassemble(0xC000);
+
+        assert_eq!(instruction.text, "DB $DD");
+        assert_eq!(instruction.bytes, vec![0xDD]);
+    }
+
+    #[test]
+    fn test_range() {
+        // NOP; LD A, $01; JP $0150
+        let memory = setup(&[0x00, 0x3E, 0x01, 0xC3, 0x50, 0x01]);
+        let disassembler = Disassembler::new(&memory);
+        let lines: Vec<_> = disassembler
+            .range(0xC000, 0xC003)
+            .map(|instruction| (instruction.address, instruction.text))
+            .collect();
+
+        assert_eq!(
+            lines,
+            vec![
+                (0xC000, "NOP".to_string()),
+                (0xC001, "LD A, $01".to_string()),
+                (0xC003, "JP $0150".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_symbols() {
+        // CALL $C005; LDH ($FF80), A; JR -2
+        let memory = setup(&[0xCD, 0x05, 0xC0, 0xE0, 0x80, 0x18, 0xFE]);
+        let symbols = Symbols::parse("00:c000 Start\n00:c005 Loop\n00:ff80 hFlag").unwrap();
+        let disassembler = Disassembler::new(&memory).with_symbols(Some(&symbols));
+        let lines: Vec<_> = disassembler
+            .range(0xC000, 0xC005)
+            .map(|instruction| instruction.to_string())
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "Start:\nC000: CD 05 C0  CALL Loop",
+                "C003: E0 80     LDH (hFlag), A",
+                "Loop:\nC005: 18 FE     JR Loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_end_of_memory() {
+        let memory = Memory::new();
+        let disassembler = Disassembler::new(&memory);
+
+        assert_eq!(disassembler.range(0xFFFE, 0xFFFF).count(), 2);
+        assert_eq!(disassembler.range(0x0010, 0x0000).count(), 0);
+    }
+}
@@ -0,0 +1,339 @@
+//! Opcode dispatch through a table of handlers, used instead of `Instruction` with the
+//! `jump-table` feature.
+//!
+//! The enum path decodes every instruction into an `Instruction` and matches it again to run it.
+//! Here each of the 256 opcodes (and 256 CB prefixed opcodes) has its own handler, `execute`
+//! specialized for that opcode at compile time, which fetches the operands and calls the
+//! instruction directly. Both paths share the instructions in `instructions`.
+
+use core::marker::PhantomData;
+
+use crate::{
+    gameboy::MemoryBus,
+    utils::{combine, EmuError},
+};
+
+use super::{
+    instruction_variables::{Cond, B3, R16, R16MEM, R16STK, R8, TGT3},
+    instructions::*,
+    Cpu,
+};
+
+type Handler<M> = fn(&mut Cpu, &mut M) -> u8;
+
+/// Fetch the opcode at PC and run its handler
+pub(super) fn execute_next<M: MemoryBus>(cpu: &mut Cpu, memory: &mut M) -> u8 {
+    let opcode = cpu.fetch_byte(memory);
+    Table::<M>::OPCODES[usize::from(opcode >> 4)][usize::from(opcode & 0xF)](cpu, memory)
+}
+
+/// The handlers for a bus type, indexed by the high and low nibble of the opcode
+struct Table<M>(PhantomData<M>);
+
+/// A 16x16 table of `$handler::<M, OPCODE>` for every opcode
+macro_rules! table {
+    ($handler:ident) => {
+        table!(@rows $handler [0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15])
+    };
+    (@rows $handler:ident [$($row:literal)*]) => {
+        [$(table!(@row $handler $row [0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15])),*]
+    };
+    (@row $handler:ident $row:literal [$($column:literal)*]) => {
+        [$($handler::<M, { $row * 16 + $column }> as Handler<M>),*]
+    };
+}
+
+impl<M: MemoryBus> Table<M> {
+    const OPCODES: [[Handler<M>; 16]; 16] = table!(execute);
+    const PREFIXED: [[Handler<M>; 16]; 16] = table!(execute_prefixed);
+}
+
+fn imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    cpu.fetch_byte(memory)
+}
+
+fn imm16(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u16 {
+    let lo = cpu.fetch_byte(memory);
+    let hi = cpu.fetch_byte(memory);
+    combine(hi, lo)
+}
+
+/// Run `OPCODE`, which has been fetched
+///
+/// The fields are named like in `decode_instruction`, which this mirrors
+fn execute<M: MemoryBus, const OPCODE: u8>(cpu: &mut Cpu, memory: &mut M) -> u8 {
+    let yy = (OPCODE >> 4) & 0x3;
+    let aaa = (OPCODE >> 3) & 0x7;
+    let bbb = OPCODE & 0x7;
+    let jj = (OPCODE >> 3) & 0x3;
+
+    match OPCODE {
+        // Block 0
+        0x00 => nop(cpu, memory),
+        0x01 | 0x11 | 0x21 | 0x31 => {
+            let value = imm16(cpu, memory);
+            ld_r16_imm16(cpu, memory, R16::from(yy), value)
+        }
+        0x02 | 0x12 | 0x22 | 0x32 => ld_r16_mem_a(cpu, memory, R16MEM::from(yy)),
+        0x0A | 0x1A | 0x2A | 0x3A => ld_a_r16_mem(cpu, memory, R16MEM::from(yy)),
+        0x08 => {
+            let address = imm16(cpu, memory);
+            ld_mem_imm16_sp(cpu, memory, address)
+        }
+
+        0x03 | 0x13 | 0x23 | 0x33 => inc_r16(cpu, memory, R16::from(yy)),
+        0x0B | 0x1B | 0x2B | 0x3B => dec_r16(cpu, memory, R16::from(yy)),
+        0x09 | 0x19 | 0x29 | 0x39 => add_hl_r16(cpu, memory, R16::from(yy)),
+
+        0x34 => inc_mem_hl(cpu, memory),
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x3C => inc_r8(cpu, memory, R8::from(aaa)),
+        0x35 => dec_mem_hl(cpu, memory),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x3D => dec_r8(cpu, memory, R8::from(aaa)),
+
+        0x36 => {
+            let value = imm8(cpu, memory);
+            ld_mem_hl_imm8(cpu, memory, value)
+        }
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x3E => {
+            let value = imm8(cpu, memory);
+            ld_r8_imm8(cpu, memory, R8::from(aaa), value)
+        }
+
+        0x07 => rlca(cpu, memory),
+        0x0F => rrca(cpu, memory),
+        0x17 => rla(cpu, memory),
+        0x1F => rra(cpu, memory),
+        0x27 => daa(cpu, memory),
+        0x2F => cpl(cpu, memory),
+        0x37 => scf(cpu, memory),
+        0x3F => ccf(cpu, memory),
+
+        0x18 => {
+            let offset = imm8(cpu, memory);
+            jr_imm8(cpu, memory, offset)
+        }
+        0x20 | 0x28 | 0x30 | 0x38 => {
+            let offset = imm8(cpu, memory);
+            jr_cond_imm8(cpu, memory, Cond::from(jj), offset)
+        }
+
+        0x10 => stop(cpu, memory),
+
+        // Block 1
+        0x76 => halt(cpu, memory),
+        0x70..=0x77 => ld_mem_hl_r8(cpu, memory, R8::from(bbb)),
+        0x40..=0x7F if bbb == 0x6 => ld_r8_mem_hl(cpu, memory, R8::from(aaa)),
+        0x40..=0x7F => ld_r8_r8(cpu, memory, R8::from(aaa), R8::from(bbb)),
+
+        // Block 2
+        0x86 => add_a_mem_hl(cpu, memory),
+        0x80..=0x87 => add_a_r8(cpu, memory, R8::from(bbb)),
+        0x8E => adc_a_mem_hl(cpu, memory),
+        0x88..=0x8F => adc_a_r8(cpu, memory, R8::from(bbb)),
+        0x96 => sub_a_mem_hl(cpu, memory),
+        0x90..=0x97 => sub_a_r8(cpu, memory, R8::from(bbb)),
+        0x9E => sbc_a_mem_hl(cpu, memory),
+        0x98..=0x9F => sbc_a_r8(cpu, memory, R8::from(bbb)),
+        0xA6 => and_a_mem_hl(cpu, memory),
+        0xA0..=0xA7 => and_a_r8(cpu, memory, R8::from(bbb)),
+        0xAE => xor_a_mem_hl(cpu, memory),
+        0xA8..=0xAF => xor_a_r8(cpu, memory, R8::from(bbb)),
+        0xB6 => or_a_mem_hl(cpu, memory),
+        0xB0..=0xB7 => or_a_r8(cpu, memory, R8::from(bbb)),
+        0xBE => cp_a_mem_hl(cpu, memory),
+        0xB8..=0xBF => cp_a_r8(cpu, memory, R8::from(bbb)),
+
+        // Block 3
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => {
+            let value = imm8(cpu, memory);
+            match aaa {
+                0x0 => add_a_imm8(cpu, memory, value),
+                0x1 => adc_a_imm8(cpu, memory, value),
+                0x2 => sub_a_imm8(cpu, memory, value),
+                0x3 => sbc_a_imm8(cpu, memory, value),
+                0x4 => and_a_imm8(cpu, memory, value),
+                0x5 => xor_a_imm8(cpu, memory, value),
+                0x6 => or_a_imm8(cpu, memory, value),
+                _ => cp_a_imm8(cpu, memory, value),
+            }
+        }
+
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => ret_cond(cpu, memory, Cond::from(jj)),
+        0xC9 => ret(cpu, memory),
+        0xD9 => reti(cpu, memory),
+        0xC2 | 0xCA | 0xD2 | 0xDA => {
+            let location = imm16(cpu, memory);
+            jp_cond_imm16(cpu, memory, Cond::from(jj), location)
+        }
+        0xC3 => {
+            let location = imm16(cpu, memory);
+            jp_imm16(cpu, memory, location)
+        }
+        0xE9 => jp_hl(cpu, memory),
+        0xC4 | 0xCC | 0xD4 | 0xDC => {
+            let location = imm16(cpu, memory);
+            call_cond_imm16(cpu, memory, Cond::from(jj), location)
+        }
+        0xCD => {
+            let location = imm16(cpu, memory);
+            call_imm16(cpu, memory, location)
+        }
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
+            rst_tgt3(cpu, memory, TGT3::from(aaa))
+        }
+
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => pop_r16_stk(cpu, memory, R16STK::from(yy)),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => push_r16_stk(cpu, memory, R16STK::from(yy)),
+
+        0xCB => {
+            let opcode = cpu.fetch_byte(memory);
+            Table::<M>::PREFIXED[usize::from(opcode >> 4)][usize::from(opcode & 0xF)](cpu, memory)
+        }
+
+        0xE2 => ldh_mem_c_a(cpu, memory),
+        0xE0 => {
+            let offset = imm8(cpu, memory);
+            ldh_mem_imm8_a(cpu, memory, offset)
+        }
+        0xEA => {
+            let address = imm16(cpu, memory);
+            ld_mem_imm16_a(cpu, memory, address)
+        }
+        0xF2 => ld_a_mem_c(cpu, memory),
+        0xF0 => {
+            let offset = imm8(cpu, memory);
+            ldh_a_mem_imm8(cpu, memory, offset)
+        }
+        0xFA => {
+            let address = imm16(cpu, memory);
+            ld_a_mem_imm16(cpu, memory, address)
+        }
+
+        0xE8 => {
+            let offset = imm8(cpu, memory);
+            add_sp_imm8(cpu, memory, offset)
+        }
+        0xF8 => {
+            let offset = imm8(cpu, memory);
+            ld_hl_sp_imm8(cpu, memory, offset)
+        }
+        0xF9 => ld_sp_hl(cpu, memory),
+
+        0xF3 => di(cpu, memory),
+        0xFB => ei(cpu, memory),
+
+        // ILLEGAL_OPCODES in cpu_core
+        _ => cpu.illegal_opcode(EmuError::IllegalOpcode {
+            opcode: OPCODE,
+            address: cpu.registers.pc.wrapping_sub(1),
+        }),
+    }
+}
+
+/// Run the CB prefixed `OPCODE`, the prefix and opcode have been fetched
+fn execute_prefixed<M: MemoryBus, const OPCODE: u8>(cpu: &mut Cpu, memory: &mut M) -> u8 {
+    let xx = OPCODE >> 6;
+    let aaa = (OPCODE >> 3) & 0x7;
+    let bbb = OPCODE & 0x7;
+
+    match (xx, aaa, bbb) {
+        (0x0, 0x0, 0x6) => rlc_mem_hl(cpu, memory),
+        (0x0, 0x0, _) => rlc_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x1, 0x6) => rrc_mem_hl(cpu, memory),
+        (0x0, 0x1, _) => rrc_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x2, 0x6) => rl_mem_hl(cpu, memory),
+        (0x0, 0x2, _) => rl_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x3, 0x6) => rr_mem_hl(cpu, memory),
+        (0x0, 0x3, _) => rr_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x4, 0x6) => sla_mem_hl(cpu, memory),
+        (0x0, 0x4, _) => sla_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x5, 0x6) => sra_mem_hl(cpu, memory),
+        (0x0, 0x5, _) => sra_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x6, 0x6) => swap_mem_hl(cpu, memory),
+        (0x0, 0x6, _) => swap_r8(cpu, memory, R8::from(bbb)),
+        (0x0, 0x7, 0x6) => srl_mem_hl(cpu, memory),
+        (0x0, 0x7, _) => srl_r8(cpu, memory, R8::from(bbb)),
+
+        (0x1, _, 0x6) => bit_b3_mem_hl(cpu, memory, B3::from(aaa)),
+        (0x1, _, _) => bit_b3_r8(cpu, memory, B3::from(aaa), R8::from(bbb)),
+        (0x2, _, 0x6) => res_b3_mem_hl(cpu, memory, B3::from(aaa)),
+        (0x2, _, _) => res_b3_r8(cpu, memory, B3::from(aaa), R8::from(bbb)),
+        (_, _, 0x6) => set_b3_mem_hl(cpu, memory, B3::from(aaa)),
+        _ => set_b3_r8(cpu, memory, B3::from(aaa), R8::from(bbb)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{cpu::cpu_core::decode_instruction, Memory, Register16};
+
+    const REGISTERS: [Register16; 6] = [
+        Register16::AF,
+        Register16::BC,
+        Register16::DE,
+        Register16::HL,
+        Register16::SP,
+        Register16::PC,
+    ];
+
+    /// A CPU about to run `bytes` from WRAM, with HL, SP and C pointing into RAM
+    fn setup(bytes: &[u8]) -> (Cpu, Memory) {
+        let mut cpu = Cpu::new();
+        cpu.registers.write_16(Register16::AF, 0x1250);
+        cpu.registers.write_16(Register16::BC, 0x3480);
+        cpu.registers.write_16(Register16::DE, 0x5678);
+        cpu.registers.write_16(Register16::HL, 0xC180);
+        cpu.registers.write_16(Register16::SP, 0xD000);
+        cpu.registers.pc = 0xC000;
+        let mut memory = Memory::new();
+        for (offset, byte) in bytes.iter().enumerate() {
+            memory.write_byte(0xC000 + offset as u16, *byte);
+        }
+        memory.write_byte(0xC180, 0x9A);
+        (cpu, memory)
+    }
+
+    fn assert_same(bytes: &[u8]) {
+        let (mut enum_cpu, mut enum_memory) = setup(bytes);
+        let address = enum_cpu.registers.pc;
+        let enum_cycles =
+            match decode_instruction(address, || enum_cpu.fetch_byte(&mut enum_memory)) {
+                Ok((instruction, _)) => instruction.execute(&mut enum_cpu, &mut enum_memory),
+                Err(error) => enum_cpu.illegal_opcode(error),
+            };
+
+        let (mut table_cpu, mut table_memory) = setup(bytes);
+        let table_cycles = execute_next(&mut table_cpu, &mut table_memory);
+
+        assert_eq!(table_cycles, enum_cycles, "cycles of {bytes:02X?}");
+        for register in REGISTERS {
+            assert_eq!(
+                table_cpu.registers.read_16(register),
+                enum_cpu.registers.read_16(register),
+                "{register:?} after {bytes:02X?}"
+            );
+        }
+        assert_eq!(table_cpu.ime, enum_cpu.ime, "IME after {bytes:02X?}");
+        assert_eq!(table_cpu.halted, enum_cpu.halted);
+        for address in (0xC000..0xE000).chain(0xFF80..=0xFFFF) {
+            assert_eq!(
+                table_memory.peek_byte(address),
+                enum_memory.peek_byte(address),
+                "[{address:04X}] after {bytes:02X?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_table_matches_enum_dispatch() {
+        for opcode in 0..=0xFF {
+            if opcode != 0xCB {
+                assert_same(&[opcode, 0x34, 0xC2]);
+            }
+        }
+        for opcode in 0x00..=0xFF {
+            assert_same(&[0xCB, opcode]);
+        }
+    }
+}
@@ -5,7 +5,7 @@
 //! the different variables in the instructions, not the registers themselves
 
 /// The R8 enum is used to represent the 8-bit registers in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R8 {
     B,
     C,
@@ -32,7 +32,7 @@ impl From<u8> for R8 {
 }
 
 /// The R16 Enum is used to represent the 16-bit registers in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R16 {
     BC,
     DE,
@@ -53,7 +53,7 @@ impl From<u8> for R16 {
 }
 
 /// The R16STK Enum is used to represent the 16-bit reigsters for stack operations in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R16STK {
     BC,
     DE,
@@ -74,7 +74,7 @@ impl From<u8> for R16STK {
 }
 
 /// R16MEM is used to represent the 16-bit registers that point to memory in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum R16MEM {
     BC,
     DE,
@@ -95,7 +95,7 @@ impl From<u8> for R16MEM {
 }
 
 /// B3 is used to represent the 3-bit values in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum B3 {
     Zero,
     One,
@@ -124,7 +124,7 @@ impl From<u8> for B3 {
 }
 
 /// COND is used to represent the condition values in the instructions.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cond {
     Zero,
     NotZero,
@@ -145,7 +145,7 @@ impl From<u8> for Cond {
 }
 
 /// TGT3 is used to represent the 3-bit target values in the instructions, used for IO.
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TGT3 {
     Zero = 0x0,
     One = 0x8,
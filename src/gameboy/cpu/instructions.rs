@@ -1,13 +1,17 @@
-use crate::gameboy::Memory;
+use crate::{
+    gameboy::{timer::DIV, MemoryBus},
+    utils::EmuError,
+};
 
 use super::{
+    cpu_core::decode_instruction,
     instruction_variables::{Cond, B3, R16, R16MEM, R16STK, R8, TGT3},
     registers::{Flag, Register16, Register8},
     Cpu,
 };
 
 /// Instructions for the Gameboy CPU
-#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     // Block 0
     Nop,
@@ -129,1687 +133,2582 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// Decode the instruction at `address`, reading memory with `peek_byte`
+    pub fn decode(memory: &impl MemoryBus, address: u16) -> Result<Instruction, EmuError> {
+        let mut next = address;
+        let (instruction, _) = decode_instruction(address, || {
+            let byte = memory.peek_byte(next);
+            next = next.wrapping_add(1);
+            byte
+        })?;
+        Ok(instruction)
+    }
+
+    /// The number of bytes the instruction is encoded in, 1 to 3, 2 for CB prefixed ones
+    ///
+    /// STOP counts as 1 like in the decoder, running it skips the padding byte after it
+    #[allow(clippy::len_without_is_empty)] // no instruction is empty
+    pub fn len(&self) -> u8 {
+        match self {
+            Instruction::LdR16Imm16(..)
+            | Instruction::LdMemImm16SP(_)
+            | Instruction::JpCondImm16(..)
+            | Instruction::JpImm16(_)
+            | Instruction::CallCondImm16(..)
+            | Instruction::CallImm16(_)
+            | Instruction::LdMemImm16A(_)
+            | Instruction::LdAMemImm16(_) => 3,
+
+            Instruction::LdR8Imm8(..)
+            | Instruction::LdMemHlImm8(_)
+            | Instruction::JrImm8(_)
+            | Instruction::JrCondImm8(..)
+            | Instruction::AddAImm8(_)
+            | Instruction::AdcAImm8(_)
+            | Instruction::SubAImm8(_)
+            | Instruction::SbcAImm8(_)
+            | Instruction::AndAImm8(_)
+            | Instruction::XorAImm8(_)
+            | Instruction::OrAImm8(_)
+            | Instruction::CpAImm8(_)
+            | Instruction::LdhMemImm8A(_)
+            | Instruction::LdhAMemImm8(_)
+            | Instruction::AddSpImm8(_)
+            | Instruction::LdHlSpImm8(_) => 2,
+
+            // CB prefix
+            Instruction::RlcMemHl
+            | Instruction::RlcR8(_)
+            | Instruction::RrcMemHl
+            | Instruction::RrcR8(_)
+            | Instruction::RlMemHl
+            | Instruction::RlR8(_)
+            | Instruction::RrMemHl
+            | Instruction::RrR8(_)
+            | Instruction::SlaMemHl
+            | Instruction::SlaR8(_)
+            | Instruction::SraMemHl
+            | Instruction::SraR8(_)
+            | Instruction::SwapMemHl
+            | Instruction::SwapR8(_)
+            | Instruction::SrlMemHl
+            | Instruction::SrlR8(_)
+            | Instruction::BitB3MemHl(_)
+            | Instruction::BitB3R8(..)
+            | Instruction::ResB3MemHl(_)
+            | Instruction::ResB3R8(..)
+            | Instruction::SetB3MemHl(_)
+            | Instruction::SetB3R8(..) => 2,
+
+            _ => 1,
+        }
+    }
+
     /// Execute the instruction
     ///
-    /// Consumes the instruction and modifies the CPU and memory
+    /// Consumes the instruction and modifies the CPU and memory. PC has to point past the
+    /// instruction already, CALL and RST push it as the return address and relative jumps count
+    /// from it, see `Cpu::execute`
     ///
     /// Returns the number of cycles the instruction took
-    pub fn execute(self, cpu: &mut Cpu, memory: &mut Memory) -> u8 {
+    pub(super) fn execute(self, cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
         match self {
-            Instruction::Nop => 1,
-            Instruction::LdR16Imm16(register, value) => {
-                cpu.registers.write_16(Register16::from(register), value);
-
-                3
+            Instruction::Nop => nop(cpu, memory),
+            Instruction::LdR16Imm16(register, value) => ld_r16_imm16(cpu, memory, register, value),
+            Instruction::LdR16MemA(register) => ld_r16_mem_a(cpu, memory, register),
+            Instruction::LdAR16Mem(register) => ld_a_r16_mem(cpu, memory, register),
+            Instruction::LdMemImm16SP(adress) => ld_mem_imm16_sp(cpu, memory, adress),
+            Instruction::IncR16(register) => inc_r16(cpu, memory, register),
+            Instruction::DecR16(register) => dec_r16(cpu, memory, register),
+            Instruction::AddHlR16(register) => add_hl_r16(cpu, memory, register),
+            Instruction::IncMemHl => inc_mem_hl(cpu, memory),
+            Instruction::IncR8(register) => inc_r8(cpu, memory, register),
+            Instruction::DecMemHl => dec_mem_hl(cpu, memory),
+            Instruction::DecR8(register) => dec_r8(cpu, memory, register),
+            Instruction::LdMemHlImm8(value) => ld_mem_hl_imm8(cpu, memory, value),
+            Instruction::LdR8Imm8(register, value) => ld_r8_imm8(cpu, memory, register, value),
+            Instruction::Rlca => rlca(cpu, memory),
+            Instruction::Rrca => rrca(cpu, memory),
+            Instruction::Rla => rla(cpu, memory),
+            Instruction::Rra => rra(cpu, memory),
+            Instruction::Daa => daa(cpu, memory),
+            Instruction::Cpl => cpl(cpu, memory),
+            Instruction::Scf => scf(cpu, memory),
+            Instruction::Ccf => ccf(cpu, memory),
+            Instruction::JrImm8(byte) => jr_imm8(cpu, memory, byte),
+            Instruction::JrCondImm8(condition, byte) => jr_cond_imm8(cpu, memory, condition, byte),
+            Instruction::Stop => stop(cpu, memory),
+            Instruction::LdMemHlR8(register) => ld_mem_hl_r8(cpu, memory, register),
+            Instruction::LdR8MemHl(register) => ld_r8_mem_hl(cpu, memory, register),
+            Instruction::LdR8R8(target_register, source_register) => {
+                ld_r8_r8(cpu, memory, target_register, source_register)
             }
-            Instruction::LdR16MemA(register) => {
-                let value = cpu.registers.read_8(Register8::A);
-                let address = cpu.registers.read_16(Register16::from(register));
-                memory.write_byte(address, value);
-
-                2
+            Instruction::Halt => halt(cpu, memory),
+            Instruction::AddAMemHl => add_a_mem_hl(cpu, memory),
+            Instruction::AddAR8(register) => add_a_r8(cpu, memory, register),
+            Instruction::AdcAMemHl => adc_a_mem_hl(cpu, memory),
+            Instruction::AdcAR8(register) => adc_a_r8(cpu, memory, register),
+            Instruction::SubAMemHl => sub_a_mem_hl(cpu, memory),
+            Instruction::SubAR8(register) => sub_a_r8(cpu, memory, register),
+            Instruction::SbcAMemHl => sbc_a_mem_hl(cpu, memory),
+            Instruction::SbcAR8(register) => sbc_a_r8(cpu, memory, register),
+            Instruction::AndAMemHl => and_a_mem_hl(cpu, memory),
+            Instruction::AndAR8(register) => and_a_r8(cpu, memory, register),
+            Instruction::XorAMemHl => xor_a_mem_hl(cpu, memory),
+            Instruction::XorAR8(register) => xor_a_r8(cpu, memory, register),
+            Instruction::OrAMemHl => or_a_mem_hl(cpu, memory),
+            Instruction::OrAR8(register) => or_a_r8(cpu, memory, register),
+            Instruction::CpAMemHl => cp_a_mem_hl(cpu, memory),
+            Instruction::CpAR8(register) => cp_a_r8(cpu, memory, register),
+            Instruction::AddAImm8(value) => add_a_imm8(cpu, memory, value),
+            Instruction::AdcAImm8(value) => adc_a_imm8(cpu, memory, value),
+            Instruction::SubAImm8(value) => sub_a_imm8(cpu, memory, value),
+            Instruction::SbcAImm8(value) => sbc_a_imm8(cpu, memory, value),
+            Instruction::AndAImm8(value) => and_a_imm8(cpu, memory, value),
+            Instruction::XorAImm8(value) => xor_a_imm8(cpu, memory, value),
+            Instruction::OrAImm8(value) => or_a_imm8(cpu, memory, value),
+            Instruction::CpAImm8(value) => cp_a_imm8(cpu, memory, value),
+            Instruction::RetCond(condition) => ret_cond(cpu, memory, condition),
+            Instruction::Ret => ret(cpu, memory),
+            Instruction::Reti => reti(cpu, memory),
+            Instruction::JpCondImm16(condition, location) => {
+                jp_cond_imm16(cpu, memory, condition, location)
             }
-            Instruction::LdAR16Mem(register) => {
-                let address = cpu.registers.read_16(Register16::from(register));
-                let value = memory.read_byte(address);
-                cpu.registers.write_8(Register8::A, value);
-
-                2
+            Instruction::JpImm16(location) => jp_imm16(cpu, memory, location),
+            Instruction::JpHl => jp_hl(cpu, memory),
+            Instruction::CallCondImm16(condition, location) => {
+                call_cond_imm16(cpu, memory, condition, location)
             }
-            Instruction::LdMemImm16SP(adress) => {
-                let value = cpu.registers.read_16(Register16::SP);
-                memory.write_word(adress, value);
+            Instruction::CallImm16(location) => call_imm16(cpu, memory, location),
+            Instruction::RstTgt3(tgt) => rst_tgt3(cpu, memory, tgt),
+            Instruction::PopR16Stk(register) => pop_r16_stk(cpu, memory, register),
+            Instruction::PushR16Stk(register) => push_r16_stk(cpu, memory, register),
+            Instruction::LdhMemCA => ldh_mem_c_a(cpu, memory),
+            Instruction::LdhMemImm8A(offset) => ldh_mem_imm8_a(cpu, memory, offset),
+            Instruction::LdMemImm16A(adress) => ld_mem_imm16_a(cpu, memory, adress),
+            Instruction::LdAMemC => ld_a_mem_c(cpu, memory),
+            Instruction::LdhAMemImm8(offset) => ldh_a_mem_imm8(cpu, memory, offset),
+            Instruction::LdAMemImm16(adress) => ld_a_mem_imm16(cpu, memory, adress),
+            Instruction::AddSpImm8(byte) => add_sp_imm8(cpu, memory, byte),
+            Instruction::LdHlSpImm8(byte) => ld_hl_sp_imm8(cpu, memory, byte),
+            Instruction::LdSpHl => ld_sp_hl(cpu, memory),
+            Instruction::Di => di(cpu, memory),
+            Instruction::Ei => ei(cpu, memory),
+            Instruction::RlcMemHl => rlc_mem_hl(cpu, memory),
+            Instruction::RlcR8(register) => rlc_r8(cpu, memory, register),
+            Instruction::RrcMemHl => rrc_mem_hl(cpu, memory),
+            Instruction::RrcR8(register) => rrc_r8(cpu, memory, register),
+            Instruction::RlMemHl => rl_mem_hl(cpu, memory),
+            Instruction::RlR8(register8) => rl_r8(cpu, memory, register8),
+            Instruction::RrMemHl => rr_mem_hl(cpu, memory),
+            Instruction::RrR8(register8) => rr_r8(cpu, memory, register8),
+            Instruction::SlaMemHl => sla_mem_hl(cpu, memory),
+            Instruction::SlaR8(register8) => sla_r8(cpu, memory, register8),
+            Instruction::SraMemHl => sra_mem_hl(cpu, memory),
+            Instruction::SraR8(register8) => sra_r8(cpu, memory, register8),
+            Instruction::SwapMemHl => swap_mem_hl(cpu, memory),
+            Instruction::SwapR8(register8) => swap_r8(cpu, memory, register8),
+            Instruction::SrlMemHl => srl_mem_hl(cpu, memory),
+            Instruction::SrlR8(register8) => srl_r8(cpu, memory, register8),
+            Instruction::BitB3MemHl(b3) => bit_b3_mem_hl(cpu, memory, b3),
+            Instruction::BitB3R8(b3, register8) => bit_b3_r8(cpu, memory, b3, register8),
+            Instruction::ResB3MemHl(b3) => res_b3_mem_hl(cpu, memory, b3),
+            Instruction::ResB3R8(b3, register8) => res_b3_r8(cpu, memory, b3, register8),
+            Instruction::SetB3MemHl(b3) => set_b3_mem_hl(cpu, memory, b3),
+            Instruction::SetB3R8(b3, register8) => set_b3_r8(cpu, memory, b3, register8),
+        }
+    }
+}
 
-                5
-            }
-            Instruction::IncR16(register) => {
-                let reg = Register16::from(register);
-                let value = cpu.registers.read_16(reg);
-                cpu.registers.write_16(reg, value.wrapping_add(1));
+// the instructions, shared by `Instruction::execute` and the opcode table in `dispatch`
+pub(super) fn nop(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    1
+}
 
-                2
-            }
-            Instruction::DecR16(register) => {
-                let reg = Register16::from(register);
-                let value = cpu.registers.read_16(reg);
-                cpu.registers.write_16(reg, value.wrapping_sub(1));
+pub(super) fn ld_r16_imm16(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    register: R16,
+    value: u16,
+) -> u8 {
+    cpu.registers.write_16(Register16::from(register), value);
 
-                2
-            }
-            Instruction::AddHlR16(register) => {
-                let value = cpu.registers.read_16(Register16::from(register));
-                let hl = cpu.registers.read_16(Register16::HL);
-                let (result, overflow) = hl.overflowing_add(value);
-                cpu.registers.write_16(Register16::HL, result);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u16_bit11(hl, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::IncMemHl => {
-                let address = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(address);
-                let result = value.wrapping_add(1);
-
-                memory.write_byte(address, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u8(value, 1) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-
-                3
-            }
-            Instruction::IncR8(register) => {
-                let reg = Register8::from(register);
-                let value = cpu.registers.read_8(reg);
-                let result = value.wrapping_add(1);
-
-                cpu.registers.write_8(reg, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u8(value, 1) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-
-                1
-            }
-            Instruction::DecMemHl => {
-                let address = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(address);
-                let result = value.wrapping_sub(1);
-
-                memory.write_byte(address, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(value, 1) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-
-                3
-            }
-            Instruction::DecR8(register) => {
-                let reg = Register8::from(register);
-                let value = cpu.registers.read_8(reg);
-                let result = value.wrapping_sub(1);
-
-                cpu.registers.write_8(reg, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(value, 1) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-
-                1
-            }
-            Instruction::LdMemHlImm8(value) => {
-                let address = cpu.registers.read_16(Register16::HL);
-                memory.write_byte(address, value);
+    3
+}
 
-                3
-            }
-            Instruction::LdR8Imm8(register, value) => {
-                let reg = Register8::from(register);
-                cpu.registers.write_8(reg, value);
+pub(super) fn ld_r16_mem_a(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16MEM) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
+    let address = r16mem_address(cpu, register);
+    memory.write_byte(address, value);
 
-                2
-            }
-            Instruction::Rlca => {
-                let value = cpu.registers.read_8(Register8::A);
+    2
+}
 
-                let result = rotate_left_carry(cpu, value);
+pub(super) fn ld_a_r16_mem(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16MEM) -> u8 {
+    let address = r16mem_address(cpu, register);
+    let value = memory.read_byte(address);
+    cpu.registers.write_8(Register8::A, value);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    2
+}
 
-                1
-            }
-            Instruction::Rrca => {
-                let value = cpu.registers.read_8(Register8::A);
+pub(super) fn ld_mem_imm16_sp(cpu: &mut Cpu, memory: &mut impl MemoryBus, adress: u16) -> u8 {
+    let value = cpu.registers.read_16(Register16::SP);
+    memory.write_word(adress, value);
 
-                let result = rotate_right_carry(cpu, value);
+    5
+}
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+pub(super) fn inc_r16(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16) -> u8 {
+    let reg = Register16::from(register);
+    let value = cpu.registers.read_16(reg);
+    memory.on_increment(value);
+    cpu.registers.write_16(reg, value.wrapping_add(1));
 
-                1
-            }
-            Instruction::Rla => {
-                let value = cpu.registers.read_8(Register8::A);
+    2
+}
 
-                let result = rotate_left(cpu, value);
+pub(super) fn dec_r16(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16) -> u8 {
+    let reg = Register16::from(register);
+    let value = cpu.registers.read_16(reg);
+    memory.on_increment(value);
+    cpu.registers.write_16(reg, value.wrapping_sub(1));
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    2
+}
 
-                1
-            }
-            Instruction::Rra => {
-                let value = cpu.registers.read_8(Register8::A);
+pub(super) fn add_hl_r16(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16) -> u8 {
+    let value = cpu.registers.read_16(Register16::from(register));
+    let hl = cpu.registers.read_16(Register16::HL);
+    let (result, overflow) = hl.overflowing_add(value);
+    let z = cpu.registers.read_flag(Flag::Z) == 0x1;
+    cpu.registers.write_16(Register16::HL, result);
+    cpu.registers.set_flags(
+        z,
+        false,
+        check_half_carry_add_u16_bit11(hl, value),
+        overflow,
+    );
+
+    2
+}
 
-                let result = rotate_right(cpu, value);
+pub(super) fn inc_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let address = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(address);
+    let result = value.wrapping_add(1);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    memory.write_byte(address, result);
+    cpu.registers
+        .set_flags(result == 0, false, check_half_carry_add_u8(value, 1), c);
 
-                1
-            }
-            Instruction::Daa => {
-                let a = cpu.registers.read_8(Register8::A);
-                let n = cpu.registers.read_flag(Flag::N);
-                let h = cpu.registers.read_flag(Flag::H);
-                let c = cpu.registers.read_flag(Flag::C);
-                let mut adjustment = 0;
-
-                if h == 0x1 || (a & 0xF) > 9 {
-                    adjustment |= 0x6;
-                }
+    3
+}
 
-                if c == 0x1 || a > 0x99 {
-                    adjustment |= 0x60;
-                    cpu.registers.write_flag(Flag::C, 0x1);
-                }
+pub(super) fn inc_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let reg = Register8::from(register);
+    let value = cpu.registers.read_8(reg);
+    let result = value.wrapping_add(1);
 
-                let result = if n == 0x0 {
-                    a.wrapping_add(adjustment)
-                } else {
-                    a.wrapping_sub(adjustment)
-                };
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 0x1 } else { 0x0 });
-                cpu.registers.write_flag(Flag::H, 0x0);
-
-                1
-            }
-            Instruction::Cpl => {
-                let a = cpu.registers.read_8(Register8::A);
-                cpu.registers.write_8(Register8::A, !a);
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    cpu.registers.write_8(reg, result);
+    cpu.registers
+        .set_flags(result == 0, false, check_half_carry_add_u8(value, 1), c);
 
-                cpu.registers.write_flag(Flag::N, 0x1);
-                cpu.registers.write_flag(Flag::H, 0x1);
+    1
+}
 
-                1
-            }
-            Instruction::Scf => {
-                cpu.registers.write_flag(Flag::N, 0x0);
-                cpu.registers.write_flag(Flag::H, 0x0);
-                cpu.registers.write_flag(Flag::C, 0x1);
+pub(super) fn dec_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let address = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(address);
+    let result = value.wrapping_sub(1);
 
-                1
-            }
-            Instruction::Ccf => {
-                let c = cpu.registers.read_flag(Flag::C);
-                cpu.registers.write_flag(Flag::N, 0x0);
-                cpu.registers.write_flag(Flag::H, 0x0);
-                cpu.registers
-                    .write_flag(Flag::C, if c == 0x1 { 0x0 } else { 0x1 });
-
-                1
-            }
-            Instruction::JrImm8(byte) => {
-                let pc = cpu.registers.read_16(Register16::PC);
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    memory.write_byte(address, result);
+    cpu.registers
+        .set_flags(result == 0, true, check_half_borrow_sub_u8(value, 1), c);
 
-                let pc_new = pc.wrapping_add_signed(byte as i8 as i16); // two step casting to get the sign extension
-                cpu.registers.write_16(Register16::PC, pc_new);
+    3
+}
 
-                3
-            }
-            Instruction::JrCondImm8(condition, byte) => {
-                let jump = match condition {
-                    Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0,
-                    Cond::Zero => cpu.registers.read_flag(Flag::Z) == 1,
-                    Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0,
-                    Cond::Carry => cpu.registers.read_flag(Flag::C) == 1,
-                };
-
-                if jump {
-                    let pc = cpu.registers.read_16(Register16::PC);
-                    let pc_new = pc.wrapping_add_signed(byte as i8 as i16); // two step casting to get the sign extension
-                    cpu.registers.write_16(Register16::PC, pc_new);
-
-                    3
-                } else {
-                    2
-                }
-            }
-            Instruction::Stop => todo!(),
-            Instruction::LdMemHlR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let adress = cpu.registers.read_16(Register16::HL);
+pub(super) fn dec_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let reg = Register8::from(register);
+    let value = cpu.registers.read_8(reg);
+    let result = value.wrapping_sub(1);
 
-                memory.write_byte(adress, value);
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    cpu.registers.write_8(reg, result);
+    cpu.registers
+        .set_flags(result == 0, true, check_half_borrow_sub_u8(value, 1), c);
 
-                2
-            }
-            Instruction::LdR8MemHl(register) => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
+    1
+}
 
-                cpu.registers.write_8(Register8::from(register), value);
+pub(super) fn ld_mem_hl_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let address = cpu.registers.read_16(Register16::HL);
+    memory.write_byte(address, value);
 
-                2
-            }
-            Instruction::LdR8R8(target_register, source_register) => {
-                let value = cpu.registers.read_8(Register8::from(source_register));
+    3
+}
 
-                cpu.registers
-                    .write_8(Register8::from(target_register), value);
+pub(super) fn ld_r8_imm8(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    register: R8,
+    value: u8,
+) -> u8 {
+    let reg = Register8::from(register);
+    cpu.registers.write_8(reg, value);
 
-                1
-            }
-            Instruction::Halt => todo!(),
-            Instruction::AddAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, overflow) = a.overflowing_add(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::AddAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, overflow) = a.overflowing_add(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                1
-            }
-            Instruction::AdcAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
-                let carry = cpu.registers.read_flag(Flag::C);
-
-                let (partial_result, overflow_add_a_carry) = a.overflowing_add(carry);
-                let (result, overflow_add_sub_result_value) = partial_result.overflowing_add(value);
-
-                let half_overflow = check_half_carry_add_u8(a, carry)
-                    || check_half_carry_add_u8(partial_result, value);
-                let overflow = overflow_add_a_carry || overflow_add_sub_result_value;
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers
-                    .write_flag(Flag::H, if half_overflow { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::AdcAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
+    2
+}
 
-                let a = cpu.registers.read_8(Register8::A);
-                let carry = cpu.registers.read_flag(Flag::C);
+pub(super) fn rlca(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
 
-                let (partial_result, overflow_add_a_carry) = a.overflowing_add(carry);
-                let (result, overflow_add_sub_result_value) = partial_result.overflowing_add(value);
+    let (result, carry) = rotate_left_carry(value);
 
-                let half_overflow = check_half_carry_add_u8(a, carry)
-                    || check_half_carry_add_u8(partial_result, value);
-                let overflow = overflow_add_a_carry || overflow_add_sub_result_value;
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(false, false, false, carry);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers
-                    .write_flag(Flag::H, if half_overflow { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
+    1
+}
 
-                1
-            }
-            Instruction::SubAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::SubAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                1
-            }
-            Instruction::SbcAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
-                let carry = cpu.registers.read_flag(Flag::C);
-
-                let (sub_result, borrow_sub_a_borrow) = a.overflowing_sub(carry);
-                let (result, borrow_sub_result_value) = sub_result.overflowing_sub(value);
-
-                let half_borrow = check_half_borrow_sub_u8(a, carry)
-                    || check_half_borrow_sub_u8(sub_result, value);
-                let overflow = borrow_sub_a_borrow || borrow_sub_result_value;
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers
-                    .write_flag(Flag::H, if half_borrow { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::SbcAR8(register) => {
-                let a = cpu.registers.read_8(Register8::A);
-                let value = cpu.registers.read_8(Register8::from(register));
-                let carry = cpu.registers.read_flag(Flag::C);
-
-                let (sub_result, borrow_sub_a_borrow) = a.overflowing_sub(carry);
-                let (result, borrow_sub_result_value) = sub_result.overflowing_sub(value);
-
-                let half_borrow = check_half_borrow_sub_u8(a, carry)
-                    || check_half_borrow_sub_u8(sub_result, value);
-                let overflow = borrow_sub_a_borrow || borrow_sub_result_value;
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers
-                    .write_flag(Flag::H, if half_borrow { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                1
-            }
-            Instruction::AndAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
+pub(super) fn rrca(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
 
-                let result = a & value;
+    let (result, carry) = rotate_right_carry(value);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 1);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(false, false, false, carry);
 
-                2
-            }
-            Instruction::AndAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
+    1
+}
 
-                let result = a & value;
+pub(super) fn rla(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 1);
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_left(value, carry);
 
-                1
-            }
-            Instruction::XorAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(false, false, false, carry);
 
-                let result = a ^ value;
+    1
+}
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+pub(super) fn rra(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
 
-                2
-            }
-            Instruction::XorAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_right(value, carry);
 
-                let result = a ^ value;
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(false, false, false, carry);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    1
+}
 
-                1
-            }
-            Instruction::OrAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
+/// Adjust A to BCD after an addition or, with N set, a subtraction. After a subtraction only H
+/// and C say which digits borrowed, A itself can't be compared against 9
+pub(super) fn daa(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+    let n = cpu.registers.read_flag(Flag::N) == 0x1;
+    let h = cpu.registers.read_flag(Flag::H) == 0x1;
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    let mut adjustment = 0;
+    let mut carry = c;
 
-                let result = a | value;
+    if h || (!n && (a & 0xF) > 9) {
+        adjustment |= 0x6;
+    }
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
-                cpu.registers.write_flag(Flag::C, 0);
+    if c || (!n && a > 0x99) {
+        adjustment |= 0x60;
+        carry = true;
+    }
 
-                2
-            }
-            Instruction::OrAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
+    let result = if n {
+        a.wrapping_sub(adjustment)
+    } else {
+        a.wrapping_add(adjustment)
+    };
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, n, false, carry);
 
-                let result = a | value;
+    1
+}
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
-                cpu.registers.write_flag(Flag::C, 0);
+pub(super) fn cpl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+    let z = cpu.registers.read_flag(Flag::Z) == 0x1;
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    cpu.registers.write_8(Register8::A, !a);
 
-                1
-            }
-            Instruction::CpAMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::CpAR8(register) => {
-                let value = cpu.registers.read_8(Register8::from(register));
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                1
-            }
-            Instruction::AddAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, overflow) = a.overflowing_add(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_carry_add_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-                2
-            }
-            Instruction::AdcAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
-                let c = cpu.registers.read_flag(Flag::C);
-
-                let (sub_result, sub_result_carry) = a.overflowing_add(c);
-                let (result, result_carry) = sub_result.overflowing_add(value);
-
-                let half_carry =
-                    check_half_carry_add_u8(a, c) || check_half_carry_add_u8(sub_result, value);
-                let carry = sub_result_carry || result_carry;
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers
-                    .write_flag(Flag::H, if half_carry { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::C, if carry { 1 } else { 0 });
-
-                2
-            }
-            Instruction::SubAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-
-                2
-            }
-            Instruction::SbcAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
-                let c = cpu.registers.read_flag(Flag::C);
-
-                let (sub_result, sub_result_borrow) = a.overflowing_sub(c);
-                let (result, result_borrow) = sub_result.overflowing_sub(value);
-
-                let half_borrow =
-                    check_half_borrow_sub_u8(a, c) || check_half_borrow_sub_u8(sub_result, value);
-                let borrow = sub_result_borrow || result_borrow;
-
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers
-                    .write_flag(Flag::H, if half_borrow { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::AndAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
+    cpu.registers.set_flags(z, true, true, c);
 
-                let result = a & value;
+    1
+}
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 1);
-                cpu.registers.write_flag(Flag::C, 0);
+pub(super) fn scf(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let z = cpu.registers.read_flag(Flag::Z) == 0x1;
+    cpu.registers.set_flags(z, false, false, true);
 
-                2
-            }
-            Instruction::XorAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
+    1
+}
 
-                let result = a ^ value;
+pub(super) fn ccf(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let z = cpu.registers.read_flag(Flag::Z) == 0x1;
+    let c = cpu.registers.read_flag(Flag::C) == 0x1;
+    cpu.registers.set_flags(z, false, false, !c);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
-                cpu.registers.write_flag(Flag::C, 0);
+    1
+}
 
-                2
-            }
-            Instruction::OrAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
+pub(super) fn jr_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, byte: u8) -> u8 {
+    let pc = cpu.registers.read_16(Register16::PC);
 
-                let result = a | value;
+    let pc_new = pc.wrapping_add_signed(byte as i8 as i16); // two step casting to get the sign extension
+    cpu.registers.write_16(Register16::PC, pc_new);
 
-                cpu.registers.write_8(Register8::A, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
-                cpu.registers.write_flag(Flag::C, 0);
+    3
+}
 
-                2
-            }
-            Instruction::CpAImm8(value) => {
-                let a = cpu.registers.read_8(Register8::A);
-
-                let (result, borrow) = a.overflowing_sub(value);
-
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 1);
-                cpu.registers.write_flag(
-                    Flag::H,
-                    if check_half_borrow_sub_u8(a, value) {
-                        1
-                    } else {
-                        0
-                    },
-                );
-                cpu.registers
-                    .write_flag(Flag::C, if borrow { 1 } else { 0 });
-
-                2
-            }
-            Instruction::RetCond(condition) => {
-                let cond = match condition {
-                    Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
-                    Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
-                    Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
-                    Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
-                };
-
-                if !cond {
-                    return 2;
-                }
+pub(super) fn jr_cond_imm8(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    condition: Cond,
+    byte: u8,
+) -> u8 {
+    let jump = match condition {
+        Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0,
+        Cond::Zero => cpu.registers.read_flag(Flag::Z) == 1,
+        Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0,
+        Cond::Carry => cpu.registers.read_flag(Flag::C) == 1,
+    };
+
+    if jump {
+        let pc = cpu.registers.read_16(Register16::PC);
+        let pc_new = pc.wrapping_add_signed(byte as i8 as i16); // two step casting to get the sign extension
+        cpu.registers.write_16(Register16::PC, pc_new);
+
+        3
+    } else {
+        2
+    }
+}
 
-                let word = stack_pop_16(cpu, memory);
+pub(super) fn stop(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    // STOP is followed by a padding byte that is skipped
+    cpu.registers.pc = cpu.registers.pc.wrapping_add(1);
+    memory.poke_byte(DIV, 0);
+    // with a CGB speed switch armed STOP only switches speed
+    if memory.switch_speed() {
+        tracing::debug!(target: "cpu", "speed switched");
+    } else {
+        tracing::debug!(target: "cpu", "stopped");
+        cpu.stopped = true;
+    }
 
-                cpu.registers.write_16(Register16::PC, word);
+    1
+}
 
-                5
-            }
-            Instruction::Ret => {
-                let word = stack_pop_16(cpu, memory);
-                cpu.registers.write_16(Register16::PC, word);
+pub(super) fn ld_mem_hl_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let adress = cpu.registers.read_16(Register16::HL);
 
-                4
-            }
-            Instruction::Reti => todo!(),
-            Instruction::JpCondImm16(condition, location) => {
-                let jump = match condition {
-                    Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
-                    Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
-                    Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
-                    Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
-                };
-
-                if !jump {
-                    return 3;
-                }
+    memory.write_byte(adress, value);
 
-                cpu.registers.pc = location;
+    2
+}
 
-                4
-            }
-            Instruction::JpImm16(location) => {
-                cpu.registers.pc = location;
+pub(super) fn ld_r8_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
 
-                4
-            }
-            Instruction::JpHl => {
-                let hl = cpu.registers.read_16(Register16::HL);
+    cpu.registers.write_8(Register8::from(register), value);
 
-                cpu.registers.write_16(Register16::PC, hl);
+    2
+}
 
-                1
-            }
-            Instruction::CallCondImm16(condition, location) => {
-                let call = match condition {
-                    Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
-                    Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
-                    Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
-                    Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
-                };
-
-                if !call {
-                    return 3;
-                }
+pub(super) fn ld_r8_r8(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    target_register: R8,
+    source_register: R8,
+) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(source_register));
 
-                let current_adress = cpu.registers.read_16(Register16::PC);
-                stack_push_16(cpu, memory, current_adress);
+    cpu.registers
+        .write_8(Register8::from(target_register), value);
 
-                cpu.registers.pc = location;
+    1
+}
 
-                6
-            }
-            Instruction::CallImm16(location) => {
-                let current_adress = cpu.registers.read_16(Register16::PC);
-                stack_push_16(cpu, memory, current_adress);
-                cpu.registers.pc = location;
+pub(super) fn halt(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    cpu.halted = true;
+    tracing::trace!(target: "cpu", "halted");
 
-                6
-            }
-            Instruction::RstTgt3(tgt) => {
-                let adress = tgt as u16;
-                stack_push_16(cpu, memory, cpu.registers.pc);
-                cpu.registers.pc = adress;
+    1
+}
 
-                4
-            }
-            Instruction::PopR16Stk(register) => {
-                let value = stack_pop_16(cpu, memory);
-                cpu.registers.write_16(Register16::from(register), value);
+pub(super) fn add_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
 
-                3
-            }
-            Instruction::PushR16Stk(register) => {
-                let value = cpu.registers.read_16(Register16::from(register));
-                stack_push_16(cpu, memory, value);
+    let (result, overflow) = a.overflowing_add(value);
 
-                4
-            }
-            Instruction::LdhMemCA => {
-                let adress = 0xFF00 + u16::from(cpu.registers.read_8(Register8::C));
-                let value = cpu.registers.read_8(Register8::A);
-                memory.write_byte(adress, value);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        false,
+        check_half_carry_add_u8(a, value),
+        overflow,
+    );
 
-                2
-            }
-            Instruction::LdhMemImm8A(offset) => {
-                let adress = 0xFF00 + u16::from(offset);
-                let value = cpu.registers.read_8(Register8::A);
-                memory.write_byte(adress, value);
+    2
+}
 
-                3
-            }
-            Instruction::LdMemImm16A(adress) => {
-                let value = cpu.registers.read_8(Register8::A);
-                memory.write_byte(adress, value);
+pub(super) fn add_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
 
-                4
-            }
-            Instruction::LdAMemC => {
-                let address = 0xFF00 + u16::from(cpu.registers.read_8(Register8::C));
-                let value = memory.read_byte(address);
+    let (result, overflow) = a.overflowing_add(value);
 
-                cpu.registers.write_8(Register8::A, value);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        false,
+        check_half_carry_add_u8(a, value),
+        overflow,
+    );
 
-                2
-            }
-            Instruction::LdhAMemImm8(offset) => {
-                let adress = 0xFF00 + u16::from(offset);
-                let value = memory.read_byte(adress);
+    1
+}
 
-                cpu.registers.write_8(Register8::A, value);
+pub(super) fn adc_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
+    let carry = cpu.registers.read_flag(Flag::C);
 
-                3
-            }
-            Instruction::LdAMemImm16(adress) => {
-                let value = memory.read_byte(adress);
+    let (partial_result, overflow_add_a_carry) = a.overflowing_add(carry);
+    let (result, overflow_add_sub_result_value) = partial_result.overflowing_add(value);
 
-                cpu.registers.write_8(Register8::A, value);
+    let half_overflow =
+        check_half_carry_add_u8(a, carry) || check_half_carry_add_u8(partial_result, value);
+    let overflow = overflow_add_a_carry || overflow_add_sub_result_value;
 
-                4
-            }
-            Instruction::AddSpImm8(byte) => {
-                // TODO: Double check this implementation
-                let sp = cpu.registers.read_16(Register16::SP);
-                let operand = byte as i8 as i16;
-                let (result, overflow) = sp.overflowing_add_signed(operand);
-
-                cpu.registers.write_16(Register16::SP, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                if operand > 0 {
-                    println!(
-                        "half carry: {}, {:X}  +  {:X} = {:X}",
-                        check_half_carry_add_u16_bit11(sp, operand as u16),
-                        sp,
-                        operand,
-                        result
-                    );
-                    cpu.registers.write_flag(
-                        Flag::H,
-                        if check_half_carry_add_u16_bit7(sp, operand as u16) {
-                            1
-                        } else {
-                            0
-                        },
-                    );
-                    cpu.registers
-                        .write_flag(Flag::C, if overflow { 1 } else { 0 });
-                } else {
-                    cpu.registers.write_flag(Flag::H, 0);
-                    cpu.registers.write_flag(Flag::C, 0);
-                }
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, false, half_overflow, overflow);
 
-                4
-            }
-            Instruction::LdHlSpImm8(byte) => {
-                let sp = cpu.registers.read_16(Register16::SP);
-                let operand = byte as i8 as i16;
-                let (result, overflow) = sp.overflowing_add_signed(operand);
-                let half_carry = operand > 0 && check_half_carry_add_u16_bit7(sp, operand as u16);
-
-                cpu.registers.write_16(Register16::HL, result);
-                cpu.registers.write_flag(Flag::Z, 0);
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers
-                    .write_flag(Flag::H, if half_carry { 1 } else { 0 });
-                cpu.registers
-                    .write_flag(Flag::C, if overflow { 1 } else { 0 });
-
-                3
-            }
-            Instruction::LdSpHl => {
-                let value = cpu.registers.read_16(Register16::HL);
-                cpu.registers.write_16(Register16::SP, value);
+    2
+}
 
-                2
-            }
-            Instruction::Di => todo!(),
-            Instruction::Ei => todo!(),
-            Instruction::RlcMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
+pub(super) fn adc_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
 
-                let result = rotate_left_carry(cpu, value);
+    let a = cpu.registers.read_8(Register8::A);
+    let carry = cpu.registers.read_flag(Flag::C);
 
-                memory.write_byte(adress, result);
+    let (partial_result, overflow_add_a_carry) = a.overflowing_add(carry);
+    let (result, overflow_add_sub_result_value) = partial_result.overflowing_add(value);
 
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    let half_overflow =
+        check_half_carry_add_u8(a, carry) || check_half_carry_add_u8(partial_result, value);
+    let overflow = overflow_add_a_carry || overflow_add_sub_result_value;
 
-                4
-            }
-            Instruction::RlcR8(register) => {
-                let reg = Register8::from(register);
-                let value = cpu.registers.read_8(reg);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, false, half_overflow, overflow);
 
-                let result = rotate_left_carry(cpu, value);
+    1
+}
 
-                cpu.registers.write_8(reg, result);
+pub(super) fn sub_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
+    let (result, borrow) = a.overflowing_sub(value);
+
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
+
+    2
+}
 
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+pub(super) fn sub_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
+    let (result, borrow) = a.overflowing_sub(value);
 
-                2
-            }
-            Instruction::RrcMemHl => {
-                let adress = cpu.registers.read_16(Register16::HL);
-                let value = memory.read_byte(adress);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
 
-                let result = rotate_right_carry(cpu, value);
+    1
+}
 
-                memory.write_byte(adress, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+pub(super) fn sbc_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
+    let carry = cpu.registers.read_flag(Flag::C);
 
-                4
-            }
-            Instruction::RrcR8(register) => {
-                let reg = Register8::from(register);
-                let value = cpu.registers.read_8(reg);
+    let (sub_result, borrow_sub_a_borrow) = a.overflowing_sub(carry);
+    let (result, borrow_sub_result_value) = sub_result.overflowing_sub(value);
 
-                let result = rotate_right_carry(cpu, value);
+    let half_borrow =
+        check_half_borrow_sub_u8(a, carry) || check_half_borrow_sub_u8(sub_result, value);
+    let overflow = borrow_sub_a_borrow || borrow_sub_result_value;
 
-                cpu.registers.write_8(reg, result);
-                cpu.registers
-                    .write_flag(Flag::Z, if result == 0 { 1 } else { 0 });
-                cpu.registers.write_flag(Flag::N, 0);
-                cpu.registers.write_flag(Flag::H, 0);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, true, half_borrow, overflow);
 
-                2
-            }
-            Instruction::RlMemHl => todo!(),
-            Instruction::RlR8(register8) => todo!(),
-            Instruction::RrMemHl => todo!(),
-            Instruction::RrR8(register8) => todo!(),
-            Instruction::SlaMemHl => todo!(),
-            Instruction::SlaR8(register8) => todo!(),
-            Instruction::SraMemHl => todo!(),
-            Instruction::SraR8(register8) => todo!(),
-            Instruction::SwapMemHl => todo!(),
-            Instruction::SwapR8(register8) => todo!(),
-            Instruction::SrlMemHl => todo!(),
-            Instruction::SrlR8(register8) => todo!(),
-            Instruction::BitB3MemHl(b3) => todo!(),
-            Instruction::BitB3R8(b3, register8) => todo!(),
-            Instruction::ResB3MemHl(b3) => todo!(),
-            Instruction::ResB3R8(b3, register8) => todo!(),
-            Instruction::SetB3MemHl(b3) => todo!(),
-            Instruction::SetB3R8(b3, register8) => todo!(),
-        }
-    }
+    2
 }
 
-// helpers
-fn stack_push_16(cpu: &mut Cpu, memory: &mut Memory, value: u16) {
-    let sp = cpu.registers.read_16(Register16::SP);
+pub(super) fn sbc_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+    let value = cpu.registers.read_8(Register8::from(register));
+    let carry = cpu.registers.read_flag(Flag::C);
+
+    let (sub_result, borrow_sub_a_borrow) = a.overflowing_sub(carry);
+    let (result, borrow_sub_result_value) = sub_result.overflowing_sub(value);
+
+    let half_borrow =
+        check_half_borrow_sub_u8(a, carry) || check_half_borrow_sub_u8(sub_result, value);
+    let overflow = borrow_sub_a_borrow || borrow_sub_result_value;
 
-    memory.write_word(sp - 2, value);
-    cpu.registers.write_16(Register16::SP, sp - 2);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, true, half_borrow, overflow);
+
+    1
 }
 
-fn stack_pop_16(cpu: &mut Cpu, memory: &Memory) -> u16 {
-    let sp = cpu.registers.read_16(Register16::SP);
+pub(super) fn and_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
 
-    let value = memory.read_word(sp);
+    let result = a & value;
 
-    cpu.registers.write_16(Register16::SP, sp + 2);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, true, false);
 
-    value
+    2
 }
 
-fn stack_push_8(cpu: &mut Cpu, memory: &mut Memory, value: u8) {
-    let sp = cpu.registers.read_16(Register16::SP);
+pub(super) fn and_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
+
+    let result = a & value;
 
-    memory.write_byte(sp - 1, value);
-    cpu.registers.write_16(Register16::SP, sp - 1);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, true, false);
+
+    1
 }
 
-fn stack_pop_8(cpu: &mut Cpu, memory: &Memory) -> u8 {
-    let sp = cpu.registers.read_16(Register16::SP);
+pub(super) fn xor_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
 
-    let value = memory.read_byte(sp);
+    let result = a ^ value;
 
-    cpu.registers.write_16(Register16::SP, sp + 1);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
 
-    value
+    2
 }
 
-/// Rotate left through carry, return rotated value. set carry flag to wrapped bit
-fn rotate_left(cpu: &mut Cpu, value: u8) -> u8 {
-    let carry = cpu.registers.read_flag(Flag::C);
-    cpu.registers.write_flag(Flag::C, (value >> 7) & 0x1);
-    (value << 1) | carry
-}
+pub(super) fn xor_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
 
-/// Rotate right through carry, return rotated value. set carry to rotated bit
-fn rotate_right(cpu: &mut Cpu, value: u8) -> u8 {
-    let carry = cpu.registers.read_flag(Flag::C);
-    cpu.registers.write_flag(Flag::C, value & 0x1);
-    (value >> 1) | (carry << 0x7)
+    let result = a ^ value;
+
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
+
+    1
 }
 
-/// Rotate left wrapping, set carry flag to wrapped bit
-fn rotate_left_carry(cpu: &mut Cpu, value: u8) -> u8 {
-    cpu.registers.write_flag(Flag::C, (value >> 7) & 0x1);
+pub(super) fn or_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
+
+    let result = a | value;
 
-    value.rotate_left(1)
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
+
+    2
 }
 
-/// Rotate right wrapping, set carry flag to wrapped bit
-fn rotate_right_carry(cpu: &mut Cpu, value: u8) -> u8 {
-    cpu.registers.write_flag(Flag::C, value & 0x1);
+pub(super) fn or_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
+
+    let result = a | value;
 
-    value.rotate_right(1)
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
+
+    1
 }
 
-// utils
+pub(super) fn cp_a_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let a = cpu.registers.read_8(Register8::A);
 
-fn check_half_carry_add_u8(left: u8, right: u8) -> bool {
-    (((left & 0xF) + (right & 0xF)) & 0x10) != 0x0
+    let (result, borrow) = a.overflowing_sub(value);
+
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
+
+    2
 }
 
-fn check_half_carry_add_u16_bit11(left: u16, right: u16) -> bool {
-    (((left & 0xFFF) + (right & 0xFFF)) & 0x1000) != 0x0
+pub(super) fn cp_a_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register));
+    let a = cpu.registers.read_8(Register8::A);
+
+    let (result, borrow) = a.overflowing_sub(value);
+
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
+
+    1
 }
 
-fn check_half_carry_add_u16_bit7(left: u16, right: u16) -> bool {
-    (((left & 0xFF) + (right & 0xFF)) & 0x100) != 0x0
+pub(super) fn add_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+
+    let (result, overflow) = a.overflowing_add(value);
+
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        false,
+        check_half_carry_add_u8(a, value),
+        overflow,
+    );
+    2
 }
 
-fn check_half_borrow_sub_u8(left: u8, right: u8) -> bool {
-    (left & 0xF) < (right & 0xF)
+pub(super) fn adc_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+    let c = cpu.registers.read_flag(Flag::C);
+
+    let (sub_result, sub_result_carry) = a.overflowing_add(c);
+    let (result, result_carry) = sub_result.overflowing_add(value);
+
+    let half_carry = check_half_carry_add_u8(a, c) || check_half_carry_add_u8(sub_result, value);
+    let carry = sub_result_carry || result_carry;
+
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, false, half_carry, carry);
+
+    2
 }
 
-fn check_half_borrow_sub_u16(left: u16, right: u16) -> bool {
-    (left & 0xFFF) < (right & 0xFFF)
+pub(super) fn sub_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+
+    let (result, borrow) = a.overflowing_sub(value);
+
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
+
+    2
 }
 
-#[cfg(test)]
-mod tests {
+pub(super) fn sbc_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
+    let c = cpu.registers.read_flag(Flag::C);
 
-    use super::*;
+    let (sub_result, sub_result_borrow) = a.overflowing_sub(c);
+    let (result, result_borrow) = sub_result.overflowing_sub(value);
 
-    #[test]
-    fn test_rotate_left() {
-        let mut cpu = Cpu::new();
-        cpu.registers.write_flag(Flag::C, 0x1);
+    let half_borrow = check_half_borrow_sub_u8(a, c) || check_half_borrow_sub_u8(sub_result, value);
+    let borrow = sub_result_borrow || result_borrow;
 
-        let result = rotate_left(&mut cpu, 0b10101010);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers
+        .set_flags(result == 0, true, half_borrow, borrow);
 
-        assert_eq!(result, 0b01010101);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
-    }
+    2
+}
 
-    #[test]
-    fn test_rotate_right() {
-        let mut cpu = Cpu::new();
-        cpu.registers.write_flag(Flag::C, 0x1);
+pub(super) fn and_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
 
-        let result = rotate_right(&mut cpu, 0b10101010);
+    let result = a & value;
 
-        assert_eq!(result, 0b11010101);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-    }
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, true, false);
 
-    #[test]
-    fn test_rotate_left_carry() {
-        let mut cpu = Cpu::new();
-        cpu.registers.write_flag(Flag::C, 0x1);
+    2
+}
 
-        let result = rotate_left_carry(&mut cpu, 0b10101010);
+pub(super) fn xor_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
 
-        assert_eq!(result, 0b01010101);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
-    }
+    let result = a ^ value;
 
-    #[test]
-    fn test_rotate_right_carry() {
-        let mut cpu = Cpu::new();
-        cpu.registers.write_flag(Flag::C, 0x1);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
 
-        let result = rotate_right_carry(&mut cpu, 0b10101010);
+    2
+}
 
-        assert_eq!(result, 0b01010101);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-    }
+pub(super) fn or_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
 
-    #[test]
-    fn test_stack_push16() {
-        let mut cpu = Cpu::new();
-        let mut memory = Memory::new();
+    let result = a | value;
 
-        cpu.registers.write_16(Register16::SP, 0xFFFE);
-        stack_push_16(&mut cpu, &mut memory, 0xABCD);
+    cpu.registers.write_8(Register8::A, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
 
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFC);
-        assert_eq!(memory.read_word(0xFFFC), 0xABCD);
-    }
+    2
+}
 
-    #[test]
-    fn test_stack_pop16() {
-        let mut cpu = Cpu::new();
-        let memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0xFFFC);
-        memory.write_word(0xFFFC, 0xABCD);
+pub(super) fn cp_a_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) -> u8 {
+    let a = cpu.registers.read_8(Register8::A);
 
-        let result = stack_pop_16(&mut cpu, &memory);
+    let (result, borrow) = a.overflowing_sub(value);
 
-        assert_eq!(result, 0xABCD);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
-    }
+    cpu.registers.set_flags(
+        result == 0,
+        true,
+        check_half_borrow_sub_u8(a, value),
+        borrow,
+    );
 
-    #[test]
-    fn test_stack_push8() {
-        let mut cpu = Cpu::new();
-        let mut memory = Memory::new();
+    2
+}
 
-        cpu.registers.write_16(Register16::SP, 0xFFFE);
-        stack_push_8(&mut cpu, &mut memory, 0xAB);
+pub(super) fn ret_cond(cpu: &mut Cpu, memory: &mut impl MemoryBus, condition: Cond) -> u8 {
+    let cond = match condition {
+        Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
+        Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
+        Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
+        Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
+    };
 
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFD);
-        assert_eq!(memory.read_byte(0xFFFD), 0xAB);
+    if !cond {
+        return 2;
     }
 
-    #[test]
-    fn test_stack_pop8() {
-        let mut cpu = Cpu::new();
-        let memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0xFFFD);
-        memory.write_byte(0xFFFD, 0xAB);
+    let word = stack_pop_16(cpu, memory);
 
-        let result = stack_pop_8(&mut cpu, &memory);
+    cpu.registers.write_16(Register16::PC, word);
 
-        assert_eq!(result, 0xAB);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
-    }
+    5
+}
+
+pub(super) fn ret(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let word = stack_pop_16(cpu, memory);
+    cpu.registers.write_16(Register16::PC, word);
+
+    4
+}
+
+pub(super) fn reti(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let word = stack_pop_16(cpu, memory);
+    cpu.registers.write_16(Register16::PC, word);
+    cpu.ime = true;
+
+    4
+}
+
+pub(super) fn jp_cond_imm16(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    condition: Cond,
+    location: u16,
+) -> u8 {
+    let jump = match condition {
+        Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
+        Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
+        Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
+        Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
+    };
+
+    if !jump {
+        return 3;
+    }
+
+    cpu.registers.pc = location;
+
+    4
+}
+
+pub(super) fn jp_imm16(cpu: &mut Cpu, memory: &mut impl MemoryBus, location: u16) -> u8 {
+    cpu.registers.pc = location;
+
+    4
+}
+
+pub(super) fn jp_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let hl = cpu.registers.read_16(Register16::HL);
+
+    cpu.registers.write_16(Register16::PC, hl);
+
+    1
+}
+
+pub(super) fn call_cond_imm16(
+    cpu: &mut Cpu,
+    memory: &mut impl MemoryBus,
+    condition: Cond,
+    location: u16,
+) -> u8 {
+    let call = match condition {
+        Cond::Zero => cpu.registers.read_flag(Flag::Z) == 0x1,
+        Cond::NotZero => cpu.registers.read_flag(Flag::Z) == 0x0,
+        Cond::Carry => cpu.registers.read_flag(Flag::C) == 0x1,
+        Cond::NotCarry => cpu.registers.read_flag(Flag::C) == 0x0,
+    };
+
+    if !call {
+        return 3;
+    }
+
+    let return_address = cpu.registers.read_16(Register16::PC);
+    stack_push_16(cpu, memory, return_address);
+
+    cpu.registers.pc = location;
+
+    6
+}
+
+pub(super) fn call_imm16(cpu: &mut Cpu, memory: &mut impl MemoryBus, location: u16) -> u8 {
+    let return_address = cpu.registers.read_16(Register16::PC);
+    stack_push_16(cpu, memory, return_address);
+    cpu.registers.pc = location;
+
+    6
+}
+
+pub(super) fn rst_tgt3(cpu: &mut Cpu, memory: &mut impl MemoryBus, tgt: TGT3) -> u8 {
+    let adress = tgt as u16;
+    stack_push_16(cpu, memory, cpu.registers.pc);
+    cpu.registers.pc = adress;
+
+    4
+}
+
+pub(super) fn pop_r16_stk(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16STK) -> u8 {
+    let value = stack_pop_16(cpu, memory);
+    cpu.registers.write_16(Register16::from(register), value);
+
+    3
+}
+
+pub(super) fn push_r16_stk(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R16STK) -> u8 {
+    let value = cpu.registers.read_16(Register16::from(register));
+    stack_push_16(cpu, memory, value);
+
+    4
+}
+
+pub(super) fn ldh_mem_c_a(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = 0xFF00 + u16::from(cpu.registers.read_8(Register8::C));
+    let value = cpu.registers.read_8(Register8::A);
+    memory.write_byte(adress, value);
+
+    2
+}
+
+pub(super) fn ldh_mem_imm8_a(cpu: &mut Cpu, memory: &mut impl MemoryBus, offset: u8) -> u8 {
+    let adress = 0xFF00 + u16::from(offset);
+    let value = cpu.registers.read_8(Register8::A);
+    memory.write_byte(adress, value);
+
+    3
+}
+
+pub(super) fn ld_mem_imm16_a(cpu: &mut Cpu, memory: &mut impl MemoryBus, adress: u16) -> u8 {
+    let value = cpu.registers.read_8(Register8::A);
+    memory.write_byte(adress, value);
+
+    4
+}
+
+pub(super) fn ld_a_mem_c(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let address = 0xFF00 + u16::from(cpu.registers.read_8(Register8::C));
+    let value = memory.read_byte(address);
+
+    cpu.registers.write_8(Register8::A, value);
+
+    2
+}
+
+pub(super) fn ldh_a_mem_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, offset: u8) -> u8 {
+    let adress = 0xFF00 + u16::from(offset);
+    let value = memory.read_byte(adress);
+
+    cpu.registers.write_8(Register8::A, value);
+
+    3
+}
+
+pub(super) fn ld_a_mem_imm16(cpu: &mut Cpu, memory: &mut impl MemoryBus, adress: u16) -> u8 {
+    let value = memory.read_byte(adress);
+
+    cpu.registers.write_8(Register8::A, value);
+
+    4
+}
+
+pub(super) fn add_sp_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, byte: u8) -> u8 {
+    let result = add_sp_offset(cpu, byte);
+    cpu.registers.write_16(Register16::SP, result);
+
+    4
+}
+
+pub(super) fn ld_hl_sp_imm8(cpu: &mut Cpu, memory: &mut impl MemoryBus, byte: u8) -> u8 {
+    let result = add_sp_offset(cpu, byte);
+    cpu.registers.write_16(Register16::HL, result);
+
+    3
+}
+
+pub(super) fn ld_sp_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let value = cpu.registers.read_16(Register16::HL);
+    cpu.registers.write_16(Register16::SP, value);
+
+    2
+}
+
+pub(super) fn di(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    cpu.ime = false;
+    cpu.ime_pending = false;
+
+    1
+}
+
+pub(super) fn ei(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    cpu.ime_pending = true;
+
+    1
+}
+
+pub(super) fn rlc_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let (result, carry) = rotate_left_carry(value);
+
+    memory.write_byte(adress, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    4
+}
+
+pub(super) fn rlc_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let reg = Register8::from(register);
+    let value = cpu.registers.read_8(reg);
+
+    let (result, carry) = rotate_left_carry(value);
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    2
+}
+
+pub(super) fn rrc_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let (result, carry) = rotate_right_carry(value);
+
+    memory.write_byte(adress, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    4
+}
+
+pub(super) fn rrc_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register: R8) -> u8 {
+    let reg = Register8::from(register);
+    let value = cpu.registers.read_8(reg);
+
+    let (result, carry) = rotate_right_carry(value);
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    2
+}
+
+pub(super) fn rl_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_left(value, carry);
+
+    memory.write_byte(adress, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    4
+}
+
+pub(super) fn rl_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_left(value, carry);
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    2
+}
+
+pub(super) fn rr_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_right(value, carry);
+
+    memory.write_byte(adress, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    4
+}
+
+pub(super) fn rr_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+    let (result, carry) = rotate_right(value, carry);
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers.set_flags(result == 0, false, false, carry);
+
+    2
+}
+
+pub(super) fn sla_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let result = value << 1;
+
+    memory.write_byte(adress, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x80 != 0);
+
+    4
+}
+
+pub(super) fn sla_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let result = value << 1;
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x80 != 0);
+
+    2
+}
+
+/// Shift right, bit 7 keeps its value
+pub(super) fn sra_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let result = value >> 1 | value & 0x80;
+
+    memory.write_byte(adress, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x1 != 0);
+
+    4
+}
+
+/// Shift right, bit 7 keeps its value
+pub(super) fn sra_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let result = value >> 1 | value & 0x80;
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x1 != 0);
+
+    2
+}
+
+pub(super) fn swap_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let result = value.rotate_left(4);
+
+    memory.write_byte(adress, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
+
+    4
+}
+
+pub(super) fn swap_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let result = value.rotate_left(4);
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers.set_flags(result == 0, false, false, false);
+
+    2
+}
+
+pub(super) fn srl_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    let result = value >> 1;
+
+    memory.write_byte(adress, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x1 != 0);
+
+    4
+}
+
+pub(super) fn srl_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    let result = value >> 1;
+
+    cpu.registers.write_8(reg, result);
+    cpu.registers
+        .set_flags(result == 0, false, false, value & 0x1 != 0);
+
+    2
+}
+
+/// Z set if the bit is 0, C is left alone. Only reads (HL), so one cycle shorter than the other
+/// (HL) CB instructions
+pub(super) fn bit_b3_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+
+    cpu.registers
+        .set_flags(value & bit_mask(b3) == 0, false, true, carry);
+
+    3
+}
+
+/// Z set if the bit is 0, C is left alone
+pub(super) fn bit_b3_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3, register8: R8) -> u8 {
+    let value = cpu.registers.read_8(Register8::from(register8));
+    let carry = cpu.registers.read_flag(Flag::C) == 0x1;
+
+    cpu.registers
+        .set_flags(value & bit_mask(b3) == 0, false, true, carry);
+
+    2
+}
+
+pub(super) fn res_b3_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    memory.write_byte(adress, value & !bit_mask(b3));
+
+    4
+}
+
+pub(super) fn res_b3_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    cpu.registers.write_8(reg, value & !bit_mask(b3));
+
+    2
+}
+
+pub(super) fn set_b3_mem_hl(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3) -> u8 {
+    let adress = cpu.registers.read_16(Register16::HL);
+    let value = memory.read_byte(adress);
+
+    memory.write_byte(adress, value | bit_mask(b3));
+
+    4
+}
+
+pub(super) fn set_b3_r8(cpu: &mut Cpu, memory: &mut impl MemoryBus, b3: B3, register8: R8) -> u8 {
+    let reg = Register8::from(register8);
+    let value = cpu.registers.read_8(reg);
+
+    cpu.registers.write_8(reg, value | bit_mask(b3));
+
+    2
+}
+
+// helpers
+pub(super) fn stack_push_16(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u16) {
+    let sp = cpu.registers.read_16(Register16::SP);
+    memory.on_increment(sp);
+
+    let sp = sp.wrapping_sub(2);
+    memory.write_word(sp, value);
+    cpu.registers.write_16(Register16::SP, sp);
+}
+
+fn stack_pop_16(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u16 {
+    let sp = cpu.registers.read_16(Register16::SP);
+
+    let value = memory.read_word(sp);
+
+    cpu.registers.write_16(Register16::SP, sp.wrapping_add(2));
+
+    value
+}
+
+fn stack_push_8(cpu: &mut Cpu, memory: &mut impl MemoryBus, value: u8) {
+    let sp = cpu.registers.read_16(Register16::SP);
+
+    let sp = sp.wrapping_sub(1);
+    memory.write_byte(sp, value);
+    cpu.registers.write_16(Register16::SP, sp);
+}
+
+fn stack_pop_8(cpu: &mut Cpu, memory: &mut impl MemoryBus) -> u8 {
+    let sp = cpu.registers.read_16(Register16::SP);
+
+    let value = memory.read_byte(sp);
+
+    cpu.registers.write_16(Register16::SP, sp.wrapping_add(1));
+
+    value
+}
+
+/// SP plus the signed offset `byte`, H and C come from the unsigned addition of the low byte of
+/// SP and `byte` (bits 3 and 7), whatever the sign, Z and N are cleared
+fn add_sp_offset(cpu: &mut Cpu, byte: u8) -> u16 {
+    let sp = cpu.registers.read_16(Register16::SP);
+    let half_carry = (sp & 0xF) + u16::from(byte & 0xF) > 0xF;
+    let carry = (sp & 0xFF) + u16::from(byte) > 0xFF;
+    cpu.registers.set_flags(false, false, half_carry, carry);
+
+    sp.wrapping_add_signed(i16::from(byte as i8))
+}
+
+/// The address held in an R16MEM register, (HL+) and (HL-) step HL after reading it
+fn r16mem_address(cpu: &mut Cpu, register: R16MEM) -> u16 {
+    let step: i16 = match register {
+        R16MEM::Hli => 1,
+        R16MEM::Hld => -1,
+        R16MEM::BC | R16MEM::DE => 0,
+    };
+    let address = cpu.registers.read_16(Register16::from(register));
+    if step != 0 {
+        cpu.registers
+            .write_16(Register16::HL, address.wrapping_add_signed(step));
+    }
+    address
+}
+
+/// Rotate left through carry, return rotated value and the new carry, the bit shifted out
+fn rotate_left(value: u8, carry: bool) -> (u8, bool) {
+    ((value << 1) | u8::from(carry), value & 0x80 != 0)
+}
+
+/// Rotate right through carry, return rotated value and the new carry, the bit shifted out
+fn rotate_right(value: u8, carry: bool) -> (u8, bool) {
+    ((value >> 1) | (u8::from(carry) << 7), value & 0x1 != 0)
+}
+
+/// Rotate left wrapping, return rotated value and the new carry, the wrapped bit
+fn rotate_left_carry(value: u8) -> (u8, bool) {
+    (value.rotate_left(1), value & 0x80 != 0)
+}
+
+/// Rotate right wrapping, return rotated value and the new carry, the wrapped bit
+fn rotate_right_carry(value: u8) -> (u8, bool) {
+    (value.rotate_right(1), value & 0x1 != 0)
+}
+
+/// The byte with only the bit `b3` selects set
+fn bit_mask(b3: B3) -> u8 {
+    1 << b3 as u8
+}
+
+// utils
+
+fn check_half_carry_add_u8(left: u8, right: u8) -> bool {
+    (((left & 0xF) + (right & 0xF)) & 0x10) != 0x0
+}
+
+fn check_half_carry_add_u16_bit11(left: u16, right: u16) -> bool {
+    (((left & 0xFFF) + (right & 0xFFF)) & 0x1000) != 0x0
+}
+
+fn check_half_borrow_sub_u8(left: u8, right: u8) -> bool {
+    (left & 0xF) < (right & 0xF)
+}
+
+fn check_half_borrow_sub_u16(left: u16, right: u16) -> bool {
+    (left & 0xFFF) < (right & 0xFFF)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::gameboy::Memory;
+
+    #[test]
+    fn test_rotate_left() {
+        let (result, carry) = rotate_left(0b10101010, true);
+
+        assert_eq!(result, 0b01010101);
+        assert!(carry);
+    }
+
+    #[test]
+    fn test_rotate_right() {
+        let (result, carry) = rotate_right(0b10101010, true);
+
+        assert_eq!(result, 0b11010101);
+        assert!(!carry);
+    }
+
+    #[test]
+    fn test_rotate_left_carry() {
+        let (result, carry) = rotate_left_carry(0b10101010);
+
+        assert_eq!(result, 0b01010101);
+        assert!(carry);
+    }
+
+    #[test]
+    fn test_rotate_right_carry() {
+        let (result, carry) = rotate_right_carry(0b10101010);
+
+        assert_eq!(result, 0b01010101);
+        assert!(!carry);
+    }
+
+    #[test]
+    fn test_stack_push16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+
+        cpu.registers.write_16(Register16::SP, 0xFFFE);
+        stack_push_16(&mut cpu, &mut memory, 0xABCD);
+
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFC);
+        assert_eq!(memory.read_word(0xFFFC), 0xABCD);
+    }
+
+    #[test]
+    fn test_stack_pop16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::SP, 0xFFFC);
+        memory.write_word(0xFFFC, 0xABCD);
+
+        let result = stack_pop_16(&mut cpu, &mut memory);
+
+        assert_eq!(result, 0xABCD);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
+    }
+
+    #[test]
+    fn test_stack_push8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+
+        cpu.registers.write_16(Register16::SP, 0xFFFE);
+        stack_push_8(&mut cpu, &mut memory, 0xAB);
+
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFD);
+        assert_eq!(memory.read_byte(0xFFFD), 0xAB);
+    }
+
+    #[test]
+    fn test_stack_pop8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::SP, 0xFFFD);
+        memory.write_byte(0xFFFD, 0xAB);
+
+        let result = stack_pop_8(&mut cpu, &mut memory);
+
+        assert_eq!(result, 0xAB);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
+    }
+
+    #[test]
+    fn test_check_half_carry_add_u8() {
+        assert!(check_half_carry_add_u8(0x0F, 0x01));
+        assert!(check_half_carry_add_u8(0x0F, 0x0F));
+        assert!(!check_half_carry_add_u8(0x0F, 0x00));
+        assert!(!check_half_carry_add_u8(0x00, 0x00));
+    }
+
+    #[test]
+    fn test_check_half_carry_add_u16_bit11() {
+        assert!(check_half_carry_add_u16_bit11(0x0FFF, 0x0001));
+        assert!(check_half_carry_add_u16_bit11(0x0FFF, 0x0FFF));
+        assert!(!check_half_carry_add_u16_bit11(0x0FFF, 0x0000));
+        assert!(!check_half_carry_add_u16_bit11(0x0000, 0x0000));
+    }
+
+    #[test]
+    fn test_check_half_borrow_sub_u8() {
+        assert!(!check_half_borrow_sub_u8(0x01, 0x01));
+        assert!(check_half_borrow_sub_u8(0x01, 0x02));
+        assert!(check_half_borrow_sub_u8(0x00, 0x01));
+        assert!(!check_half_borrow_sub_u8(0x0F, 0x01));
+    }
+
+    #[test]
+    fn test_check_half_borrow_sub_u16() {
+        assert!(!check_half_borrow_sub_u16(0x0001, 0x0001));
+        assert!(check_half_borrow_sub_u16(0x0001, 0x0002));
+        assert!(check_half_borrow_sub_u16(0x0000, 0x0001));
+        assert!(!check_half_borrow_sub_u16(0x0FFF, 0x0001));
+    }
+
+    #[test]
+    fn test_nop() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Nop;
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+    }
+
+    #[test]
+    fn test_ld_r16_imm16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdR16Imm16(R16::BC, 0xABCD);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::BC), 0xABCD);
+    }
+
+    #[test]
+    fn test_ld_r16mem_a() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdR16MemA(R16MEM::BC);
+
+        cpu.registers.write_16(Register16::BC, 0x1234);
+        cpu.registers.write_8(Register8::A, 0xAB);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(memory.read_byte(0x1234), 0xAB);
+    }
+
+    #[test]
+    fn test_ld_a_r16mem() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdAR16Mem(R16MEM::BC);
+
+        cpu.registers.write_16(Register16::BC, 0x1234);
+        memory.write_byte(0x1234, 0xAB);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xAB);
+    }
+
+    #[test]
+    fn test_ld_hli_hld() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        cpu.registers.write_8(Register8::A, 0x11);
+
+        Instruction::LdR16MemA(R16MEM::Hli).execute(&mut cpu, &mut memory);
+        assert_eq!(memory.read_byte(0xC000), 0x11);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xC001);
+
+        Instruction::LdR16MemA(R16MEM::Hld).execute(&mut cpu, &mut memory);
+        assert_eq!(memory.read_byte(0xC001), 0x11);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xC000);
+
+        memory.write_byte(0xC000, 0x22);
+        memory.write_byte(0xBFFF, 0x33);
+        Instruction::LdAR16Mem(R16MEM::Hld).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xBFFF);
+
+        Instruction::LdAR16Mem(R16MEM::Hli).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x33);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xC000);
+    }
+
+    #[test]
+    fn test_ld_hli_wraps() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::HL, 0xFFFF);
+
+        Instruction::LdAR16Mem(R16MEM::Hli).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x0000);
+        Instruction::LdAR16Mem(R16MEM::Hld).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xFFFF);
+    }
+
+    #[test]
+    fn test_ld_memimm16_sp() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdMemImm16SP(0x1234);
+        cpu.registers.write_16(Register16::SP, 0xABCD);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 5);
+        assert_eq!(memory.read_word(0x1234), 0xABCD);
+    }
+
+    #[test]
+    fn test_inc_r16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncR16(R16::BC);
+        cpu.registers.write_16(Register16::BC, 0x1234);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_16(Register16::BC), 0x1235);
+    }
+
+    #[test]
+    fn test_dec_r16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecR16(R16::BC);
+        cpu.registers.write_16(Register16::BC, 0x1234);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_16(Register16::BC), 0x1233);
+    }
+
+    #[test]
+    fn test_add_hl_r16() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::AddHlR16(R16::BC);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        cpu.registers.write_16(Register16::BC, 0x5678);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x68AC);
+    }
+
+    #[test]
+    fn test_add_hl_r16_flags() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::AddHlR16(R16::BC);
+        cpu.registers.write_16(Register16::HL, 0xFFFF);
+        cpu.registers.write_16(Register16::BC, 0xFFFF);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xFFFE);
+    }
+
+    #[test]
+    fn test_inc_r8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0x0);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x1);
+    }
+
+    #[test]
+    fn test_inc_r8_half_overflow() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0xF);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x10);
+    }
+
+    #[test]
+    fn test_inc_r8_zero() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0xFF);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x00);
+    }
 
     #[test]
-    fn test_check_half_carry_add_u8() {
-        assert!(check_half_carry_add_u8(0x0F, 0x01));
-        assert!(check_half_carry_add_u8(0x0F, 0x0F));
-        assert!(!check_half_carry_add_u8(0x0F, 0x00));
-        assert!(!check_half_carry_add_u8(0x00, 0x00));
+    fn test_inc_memhl() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x0);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(memory.read_byte(0x1234), 0x1);
     }
 
     #[test]
-    fn test_check_half_carry_add_u16_bit11() {
-        assert!(check_half_carry_add_u16_bit11(0x0FFF, 0x0001));
-        assert!(check_half_carry_add_u16_bit11(0x0FFF, 0x0FFF));
-        assert!(!check_half_carry_add_u16_bit11(0x0FFF, 0x0000));
-        assert!(!check_half_carry_add_u16_bit11(0x0000, 0x0000));
+    fn test_inc_memhl_half_overflow() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0xF);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(memory.read_byte(0x1234), 0x10);
     }
 
     #[test]
-    fn test_check_half_carry_add_u16_bit7() {
-        assert!(check_half_carry_add_u16_bit7(0x00FF, 0x0001));
-        assert!(check_half_carry_add_u16_bit7(0x00FF, 0x00FF));
-        assert!(!check_half_carry_add_u16_bit7(0x00FF, 0x0000));
-        assert!(!check_half_carry_add_u16_bit7(0x0000, 0x0000));
+    fn test_inc_memhl_zero() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::IncMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0xFF);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(memory.read_byte(0x1234), 0x00);
     }
 
     #[test]
-    fn test_check_half_borrow_sub_u8() {
-        assert!(!check_half_borrow_sub_u8(0x01, 0x01));
-        assert!(check_half_borrow_sub_u8(0x01, 0x02));
-        assert!(check_half_borrow_sub_u8(0x00, 0x01));
-        assert!(!check_half_borrow_sub_u8(0x0F, 0x01));
+    fn test_dec_r8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0x2);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x1);
+    }
+
+    #[test]
+    fn test_dec_r8_half_borrow() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0x10);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0xF);
+    }
+
+    #[test]
+    fn test_dec_r8_zero() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0x1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x0);
+    }
+
+    #[test]
+    fn test_dec_memhl() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x2);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(memory.read_byte(0x1234), 0x1);
+    }
+
+    #[test]
+    fn test_dec_memhl_half_borrow() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x10);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(memory.read_byte(0x1234), 0xF);
+    }
+
+    #[test]
+    fn test_dec_memhl_zero() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::DecMemHl;
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(memory.read_byte(0x1234), 0x0);
+    }
+
+    #[test]
+    fn test_ld_r8_imm8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdR8Imm8(R8::B, 0xAB);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0xAB);
+    }
+
+    #[test]
+    fn test_ld_memhl_imm8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::LdMemHlImm8(0xAB);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(memory.read_byte(0x1234), 0xAB);
+    }
+
+    #[test]
+    fn test_rlca() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Rlca;
+
+        cpu.registers.write_8(Register8::A, 0b1001_1010);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b0011_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+    }
+
+    #[test]
+    fn test_rrca() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Rrca;
+        cpu.registers.write_8(Register8::A, 0b1001_1011);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1100_1101);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+    }
+
+    #[test]
+    fn test_rla() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Rla;
+        cpu.registers.write_8(Register8::A, 0b0001_1010);
+        cpu.registers.write_flag(Flag::C, 1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b0011_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+    }
+
+    #[test]
+    fn test_rra() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Rra;
+        cpu.registers.write_8(Register8::A, 0b0001_1010);
+        cpu.registers.write_flag(Flag::C, 1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1101);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
-    #[test]
-    fn test_check_half_borrow_sub_u16() {
-        assert!(!check_half_borrow_sub_u16(0x0001, 0x0001));
-        assert!(check_half_borrow_sub_u16(0x0001, 0x0002));
-        assert!(check_half_borrow_sub_u16(0x0000, 0x0001));
-        assert!(!check_half_borrow_sub_u16(0x0FFF, 0x0001));
+    #[test]
+    fn test_daa_no_change() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::Daa;
+        cpu.registers.write_8(Register8::A, 0x45);
+        cpu.registers.write_flag(Flag::N, 0);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 0);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x45);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_nop() {
+    fn test_daa_n_true_half() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Nop;
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0x45);
+
+        cpu.registers.write_flag(Flag::N, 1);
+        cpu.registers.write_flag(Flag::H, 1); // adjustment of 6
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x3F);
         assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_r16_imm16() {
+    fn test_daa_n_true_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR16Imm16(R16::BC, 0xABCD);
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0x61);
+
+        cpu.registers.write_flag(Flag::N, 1);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 1); // adjustment of 60
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::BC), 0xABCD);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x01);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ld_r16mem_a() {
+    fn test_daa_n_true_no_adjustment() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR16MemA(R16MEM::BC);
+        let instruction = Instruction::Daa;
 
-        cpu.registers.write_16(Register16::BC, 0x1234);
-        cpu.registers.write_8(Register8::A, 0xAB);
+        // after a subtraction without borrows A is left alone, even when it isn't BCD
+        cpu.registers.write_8(Register8::A, 0x9A);
+
+        cpu.registers.write_flag(Flag::N, 1);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(memory.read_byte(0x1234), 0xAB);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x9A);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_a_r16mem() {
+    fn test_daa_n_true_half_and_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdAR16Mem(R16MEM::BC);
+        let instruction = Instruction::Daa;
 
-        cpu.registers.write_16(Register16::BC, 0x1234);
-        memory.write_byte(0x1234, 0xAB);
+        // 0x00 - 0x01 = 0xFF, borrowing from both digits gives 99
+        cpu.registers.write_8(Register8::A, 0xFF);
+
+        cpu.registers.write_flag(Flag::N, 1);
+        cpu.registers.write_flag(Flag::H, 1);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xAB);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x99);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ld_memimm16_sp() {
+    fn test_daa_n_false_half() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdMemImm16SP(0x1234);
-        cpu.registers.write_16(Register16::SP, 0xABCD);
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0x45);
+
+        cpu.registers.write_flag(Flag::N, 0);
+        cpu.registers.write_flag(Flag::H, 1); // adjustment of 6
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 5);
-        assert_eq!(memory.read_word(0x1234), 0xABCD);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x4B);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_inc_r16() {
+    fn test_daa_n_false_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncR16(R16::BC);
-        cpu.registers.write_16(Register16::BC, 0x1234);
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0x60);
+
+        cpu.registers.write_flag(Flag::N, 0);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 1); // adjustment of 60
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_16(Register16::BC), 0x1235);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xC0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
+    /// Tests for alternative DAA trigger to apply offset: target is larger than 0x90
     #[test]
-    fn test_dec_r16() {
+    fn test_daa_n_false_half_alternative_large() {
+        // if A 0xF > 0x9
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecR16(R16::BC);
-        cpu.registers.write_16(Register16::BC, 0x1234);
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0xA0);
+
+        cpu.registers.write_flag(Flag::N, 0);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_16(Register16::BC), 0x1233);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_add_hl_r16() {
+    fn test_daa_n_false_half_alternative_small() {
+        // if A 0x6 < 0x9
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddHlR16(R16::BC);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        cpu.registers.write_16(Register16::BC, 0x5678);
+        let instruction = Instruction::Daa;
+
+        cpu.registers.write_8(Register8::A, 0x4A);
+
+        cpu.registers.write_flag(Flag::N, 0);
+        cpu.registers.write_flag(Flag::H, 0);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x50);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0x68AC);
     }
 
     #[test]
-    fn test_add_hl_r16_flags() {
+    fn test_cpl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddHlR16(R16::BC);
-        cpu.registers.write_16(Register16::HL, 0xFFFF);
-        cpu.registers.write_16(Register16::BC, 0xFFFF);
+        let instruction = Instruction::Cpl;
+        cpu.registers.write_8(Register8::A, 0x45);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xBA);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0xFFFE);
     }
 
     #[test]
-    fn test_inc_r8() {
+    fn test_scf() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0x0);
+        let instruction = Instruction::Scf;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_inc_r8_half_overflow() {
+    fn test_ccf() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0xF);
+        let instruction = Instruction::Ccf;
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0x10);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_inc_r8_zero() {
+    fn test_jr_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0xFF);
+        let instruction = Instruction::JrImm8(10i8 as u8);
+        let old_pc = cpu.registers.read_16(Register16::PC);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0x00);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc + 10);
     }
 
     #[test]
-    fn test_inc_memhl() {
+    fn test_jr_imm8_negative() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncMemHl;
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x0);
+        let instruction = Instruction::JrImm8(-2i8 as u8);
+        cpu.registers.write_16(Register16::PC, 0x1000);
+        let old_pc = cpu.registers.read_16(Register16::PC);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc - 2);
+    }
+
+    #[test]
+    fn test_jr_cond_imm8() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::JrCondImm8(Cond::Zero, 10i8 as u8);
+        cpu.registers.write_flag(Flag::Z, 1);
+        let old_pc = cpu.registers.read_16(Register16::PC);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc + 10);
+    }
+
+    #[test]
+    fn test_jr_cond_imm8_false() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.pc = 0x1000;
+        let instruction = Instruction::JrCondImm8(Cond::Zero, -10i8 as u8);
+        let old_pc = cpu.registers.read_16(Register16::PC);
+        cpu.registers.write_flag(Flag::Z, 1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc - 10);
+    }
+
+    #[test]
+    fn test_jr_cond_imm8_untaken() {
+        let mut cpu: Cpu = Cpu::new();
+        let mut memory: Memory = Memory::new();
+        let instruction = Instruction::JrCondImm8(Cond::Zero, 10i8 as u8);
+        let old_pc = cpu.registers.read_16(Register16::PC);
+        cpu.registers.write_flag(Flag::Z, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(memory.read_byte(0x1234), 0x1);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc);
     }
 
     #[test]
-    fn test_inc_memhl_half_overflow() {
+    fn test_ld_r8_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncMemHl;
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0xF);
+        let instruction = Instruction::LdR8R8(R8::A, R8::B);
+        cpu.registers.write_8(Register8::A, 0x12);
+        cpu.registers.write_8(Register8::B, 0x34);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(memory.read_byte(0x1234), 0x10);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x34);
     }
 
     #[test]
-    fn test_inc_memhl_zero() {
+    fn test_ld_r8_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::IncMemHl;
+        let instruction = Instruction::LdR8MemHl(R8::A);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0xFF);
+        memory.write_byte(0x1234, 0x56);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(memory.read_byte(0x1234), 0x00);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x56);
     }
 
     #[test]
-    fn test_dec_r8() {
+    fn test_ld_memhl_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0x2);
+        let instruction = Instruction::LdMemHlR8(R8::A);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        cpu.registers.write_8(Register8::A, 0x56);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0x1);
+        assert_eq!(cycles, 2);
+        assert_eq!(memory.read_byte(0x1234), 0x56);
     }
 
     #[test]
-    fn test_dec_r8_half_borrow() {
+    fn test_ld_r8_r8_no_op() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0x10);
+        let instruction = Instruction::LdR8R8(R8::A, R8::A);
+        cpu.registers.write_8(Register8::A, 0x12);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0xF);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x12);
     }
 
     #[test]
-    fn test_dec_r8_zero() {
+    fn test_add_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecR8(R8::B);
-        cpu.registers.write_8(Register8::B, 0x1);
+        let instruction = Instruction::AddAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x12);
+        cpu.registers.write_8(Register8::B, 0x34);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x46);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_dec_memhl() {
+    fn test_add_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecMemHl;
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x2);
+        let instruction = Instruction::AddAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x00);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(memory.read_byte(0x1234), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_dec_memhl_half_borrow() {
+    fn test_add_a_r8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecMemHl;
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x10);
+        let instruction = Instruction::AddAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x0F);
+        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(memory.read_byte(0x1234), 0xF);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_dec_memhl_zero() {
+    fn test_add_a_r8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::DecMemHl;
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x1);
+        let instruction = Instruction::AddAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0xFF);
+        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(memory.read_byte(0x1234), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ld_r8_imm8() {
+    fn test_add_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR8Imm8(R8::B, 0xAB);
+        let instruction = Instruction::AddAMemHl;
+        cpu.registers.write_8(Register8::A, 0x12);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x34);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0xAB);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x46);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_memhl_imm8() {
+    fn test_add_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdMemHlImm8(0xAB);
+        let instruction = Instruction::AddAMemHl;
+        cpu.registers.write_8(Register8::A, 0x00);
         cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x00);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(memory.read_byte(0x1234), 0xAB);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_rlca() {
+    fn test_add_a_memhl_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Rlca;
-
-        cpu.registers.write_8(Register8::A, 0b1001_1010);
+        let instruction = Instruction::AddAMemHl;
+        cpu.registers.write_8(Register8::A, 0x0F);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b0011_0101);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_rrca() {
+    fn test_add_a_memhl_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Rrca;
-        cpu.registers.write_8(Register8::A, 0b1001_1011);
+        let instruction = Instruction::AddAMemHl;
+        cpu.registers.write_8(Register8::A, 0xFF);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1100_1101);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_rla() {
+    fn test_adc_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Rla;
-        cpu.registers.write_8(Register8::A, 0b0001_1010);
+        let instruction = Instruction::AdcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x12);
+        cpu.registers.write_8(Register8::B, 0x34);
         cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b0011_0101);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x47);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -1817,508 +2716,528 @@ mod tests {
     }
 
     #[test]
-    fn test_rra() {
+    fn test_adc_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Rra;
-        cpu.registers.write_8(Register8::A, 0b0001_1010);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::AdcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x00);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1101);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_daa_no_change() {
+    fn test_adc_a_r8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-        cpu.registers.write_8(Register8::A, 0x45);
-        cpu.registers.write_flag(Flag::N, 0);
-        cpu.registers.write_flag(Flag::H, 0);
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::AdcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x0E);
+        cpu.registers.write_8(Register8::B, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x45);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_daa_n_true_half() {
+    fn test_adc_a_r8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0x45);
-
-        cpu.registers.write_flag(Flag::N, 1);
-        cpu.registers.write_flag(Flag::H, 1); // adjustment of 6
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::AdcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0xFE);
+        cpu.registers.write_8(Register8::B, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x3F);
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_daa_n_true_carry() {
+    fn test_adc_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0x61);
-
-        cpu.registers.write_flag(Flag::N, 1);
-        cpu.registers.write_flag(Flag::H, 0);
-        cpu.registers.write_flag(Flag::C, 1); // adjustment of 60
+        let instruction = Instruction::AdcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x12);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        cpu.registers.write_flag(Flag::C, 1);
+        memory.write_byte(0x1234, 0x34);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x01);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x47);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_daa_n_false_half() {
+    fn test_adc_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0x45);
-
-        cpu.registers.write_flag(Flag::N, 0);
-        cpu.registers.write_flag(Flag::H, 1); // adjustment of 6
+        let instruction = Instruction::AdcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_16(Register16::HL, 0x1234);
         cpu.registers.write_flag(Flag::C, 0);
+        memory.write_byte(0x1234, 0x00);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x4B);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_daa_n_false_carry() {
+    fn test_adc_a_memhl_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0x60);
-
-        cpu.registers.write_flag(Flag::N, 0);
-        cpu.registers.write_flag(Flag::H, 0);
-        cpu.registers.write_flag(Flag::C, 1); // adjustment of 60
+        let instruction = Instruction::AdcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x0E);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xC0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
-    /// Tests for alternative DAA trigger to apply offset: target is larger than 0x90
     #[test]
-    fn test_daa_n_false_half_alternative_large() {
-        // if A 0xF > 0x9
+    fn test_adc_a_memhl_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0xA0);
-
-        cpu.registers.write_flag(Flag::N, 0);
-        cpu.registers.write_flag(Flag::H, 0);
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::AdcAMemHl;
+        cpu.registers.write_8(Register8::A, 0xFE);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_daa_n_false_half_alternative_small() {
-        // if A 0x6 < 0x9
+    fn test_sub_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Daa;
-
-        cpu.registers.write_8(Register8::A, 0x4A);
-
-        cpu.registers.write_flag(Flag::N, 0);
-        cpu.registers.write_flag(Flag::H, 0);
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::SubAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_8(Register8::B, 0x12);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x50);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_cpl() {
+    fn test_sub_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Cpl;
-        cpu.registers.write_8(Register8::A, 0x45);
+        let instruction = Instruction::SubAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x00);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xBA);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_scf() {
+    fn test_sub_a_r8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Scf;
+        let instruction = Instruction::SubAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ccf() {
+    fn test_sub_a_r8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::Ccf;
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::SubAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_jr_imm8() {
+    fn test_sub_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::JrImm8(10i8 as u8);
-        let old_pc = cpu.registers.read_16(Register16::PC);
+        let instruction = Instruction::SubAMemHl;
+        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x12);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc + 10);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_jr_imm8_negative() {
+    fn test_sub_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::JrImm8(-2i8 as u8);
-        cpu.registers.write_16(Register16::PC, 0x1000);
-        let old_pc = cpu.registers.read_16(Register16::PC);
+        let instruction = Instruction::SubAMemHl;
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x00);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc - 2);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_jr_cond_imm8() {
+    fn test_sub_a_memhl_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::JrCondImm8(Cond::Zero, 10i8 as u8);
-        cpu.registers.write_flag(Flag::Z, 1);
-        let old_pc = cpu.registers.read_16(Register16::PC);
+        let instruction = Instruction::SubAMemHl;
+        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc + 10);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_jr_cond_imm8_false() {
+    fn test_sub_a_memhl_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.pc = 0x1000;
-        let instruction = Instruction::JrCondImm8(Cond::Zero, -10i8 as u8);
-        let old_pc = cpu.registers.read_16(Register16::PC);
-        cpu.registers.write_flag(Flag::Z, 1);
-
-        let cycles = instruction.execute(&mut cpu, &mut memory);
-
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc - 10);
-    }
-
-    #[test]
-    fn test_jr_cond_imm8_untaken() {
-        let mut cpu: Cpu = Cpu::new();
-        let mut memory: Memory = Memory::new();
-        let instruction = Instruction::JrCondImm8(Cond::Zero, 10i8 as u8);
-        let old_pc = cpu.registers.read_16(Register16::PC);
-        cpu.registers.write_flag(Flag::Z, 0);
+        let instruction = Instruction::SubAMemHl;
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_16(Register16::PC), old_pc);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ld_r8_r8() {
+    fn test_sbc_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR8R8(R8::A, R8::B);
-        cpu.registers.write_8(Register8::A, 0x12);
-        cpu.registers.write_8(Register8::B, 0x34);
+        let instruction = Instruction::SbcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_8(Register8::B, 0x32);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x34);
+        assert_eq!(cpu.registers.read_8(Register8::A), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_r8_memhl() {
+    fn test_sbc_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR8MemHl(R8::A);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x56);
+        let instruction = Instruction::SbcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x00);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x56);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_memhl_r8() {
+    fn test_sbc_a_r8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdMemHlR8(R8::A);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        cpu.registers.write_8(Register8::A, 0x56);
+        let instruction = Instruction::SbcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_8(Register8::B, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(memory.read_byte(0x1234), 0x56);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_r8_r8_no_op() {
+    fn test_sbc_a_r8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::LdR8R8(R8::A, R8::A);
-        cpu.registers.write_8(Register8::A, 0x12);
+        let instruction = Instruction::SbcAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_8(Register8::B, 0x0);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x12);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
     }
 
     #[test]
-    fn test_add_a_r8() {
+    fn test_sbc_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x12);
-        cpu.registers.write_8(Register8::B, 0x34);
+        let instruction = Instruction::SbcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x32);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x46);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 1);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_a_r8_zero() {
+    fn test_sbc_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAR8(R8::B);
+        let instruction = Instruction::SbcAMemHl;
         cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x00);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x00);
+        cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_a_r8_half_carry() {
+    fn test_sbc_a_memhl_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x0F);
-        cpu.registers.write_8(Register8::B, 0x01);
+        let instruction = Instruction::SbcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_a_r8_carry() {
+    fn test_sbc_a_memhl_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0xFF);
-        cpu.registers.write_8(Register8::B, 0x01);
+        let instruction = Instruction::SbcAMemHl;
+        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0x01);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFE);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_add_a_memhl() {
+    fn test_and_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAMemHl;
-        cpu.registers.write_8(Register8::A, 0x12);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x34);
+        let instruction = Instruction::AndAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_8(Register8::B, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x46);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_a_memhl_zero() {
+    fn test_and_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x00);
+        let instruction = Instruction::AndAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_8(Register8::B, 0b0101_0101);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
     }
 
     #[test]
-    fn test_add_a_memhl_half_carry() {
+    fn test_and_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAMemHl;
-        cpu.registers.write_8(Register8::A, 0x0F);
+        let instruction = Instruction::AndAMemHl;
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
+        memory.write_byte(0x1234, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_a_memhl_carry() {
+    fn test_and_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAMemHl;
-        cpu.registers.write_8(Register8::A, 0xFF);
+        let instruction = Instruction::AndAMemHl;
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
+        memory.write_byte(0x1234, 0b0101_0101);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_adc_a_r8() {
+    fn test_xor_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x12);
-        cpu.registers.write_8(Register8::B, 0x34);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::XorAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_8(Register8::B, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x47);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2326,76 +3245,71 @@ mod tests {
     }
 
     #[test]
-    fn test_adc_a_r8_zero() {
+    fn test_xor_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x00);
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::XorAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_8(Register8::B, 0b1010_1010);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_adc_a_r8_half_carry() {
+    fn test_xor_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x0E);
-        cpu.registers.write_8(Register8::B, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::XorAMemHl;
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_adc_a_r8_carry() {
+    fn test_xor_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0xFE);
-        cpu.registers.write_8(Register8::B, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::XorAMemHl;
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0b1010_1010);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_adc_a_memhl() {
+    fn test_or_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x12);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        cpu.registers.write_flag(Flag::C, 1);
-        memory.write_byte(0x1234, 0x34);
+        let instruction = Instruction::OrAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_8(Register8::B, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x47);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1110_1110);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2403,77 +3317,70 @@ mod tests {
     }
 
     #[test]
-    fn test_adc_a_memhl_zero() {
+    fn test_or_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        cpu.registers.write_flag(Flag::C, 0);
-        memory.write_byte(0x1234, 0x00);
+        let instruction = Instruction::OrAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_8(Register8::B, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_adc_a_memhl_half_carry() {
+    fn test_or_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x0E);
+        let instruction = Instruction::OrAMemHl;
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        memory.write_byte(0x1234, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1110_1110);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_adc_a_memhl_carry() {
+    fn test_or_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAMemHl;
-        cpu.registers.write_8(Register8::A, 0xFE);
+        let instruction = Instruction::OrAMemHl;
+        cpu.registers.write_8(Register8::A, 0x0);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        memory.write_byte(0x1234, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_sub_a_r8() {
+    fn test_cp_a_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAR8(R8::B);
+        let instruction = Instruction::CpAR8(R8::B);
         cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_8(Register8::B, 0x12);
+        cpu.registers.write_8(Register8::B, 0x32);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2481,17 +3388,16 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_r8_zero() {
+    fn test_cp_a_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x00);
+        let instruction = Instruction::CpAR8(R8::B);
+        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_8(Register8::B, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2499,17 +3405,16 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_r8_half_carry() {
+    fn test_cp_a_r8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAR8(R8::B);
+        let instruction = Instruction::CpAR8(R8::B);
         cpu.registers.write_8(Register8::A, 0x10);
         cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -2517,17 +3422,16 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_r8_carry() {
+    fn test_cp_a_r8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAR8(R8::B);
+        let instruction = Instruction::CpAR8(R8::B);
         cpu.registers.write_8(Register8::A, 0x00);
         cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -2535,18 +3439,17 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_memhl() {
+    fn test_cp_a_memhl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAMemHl;
+        let instruction = Instruction::CpAMemHl;
         cpu.registers.write_8(Register8::A, 0x34);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x12);
+        memory.write_byte(0x1234, 0x32);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2554,18 +3457,17 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_memhl_zero() {
+    fn test_cp_a_memhl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
+        let instruction = Instruction::CpAMemHl;
+        cpu.registers.write_8(Register8::A, 0x0);
         cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x00);
+        memory.write_byte(0x1234, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2573,10 +3475,10 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_memhl_half_carry() {
+    fn test_cp_a_memhl_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAMemHl;
+        let instruction = Instruction::CpAMemHl;
         cpu.registers.write_8(Register8::A, 0x10);
         cpu.registers.write_16(Register16::HL, 0x1234);
         memory.write_byte(0x1234, 0x01);
@@ -2584,7 +3486,6 @@ mod tests {
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -2592,10 +3493,10 @@ mod tests {
     }
 
     #[test]
-    fn test_sub_a_memhl_carry() {
+    fn test_cp_a_memhl_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAMemHl;
+        let instruction = Instruction::CpAMemHl;
         cpu.registers.write_8(Register8::A, 0x00);
         cpu.registers.write_16(Register16::HL, 0x1234);
         memory.write_byte(0x1234, 0x01);
@@ -2603,7 +3504,6 @@ mod tests {
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -2611,293 +3511,308 @@ mod tests {
     }
 
     #[test]
-    fn test_sbc_a_r8() {
+    fn test_add_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_8(Register8::B, 0x32);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::AddAImm8(0x12);
+        cpu.registers.write_8(Register8::A, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 1);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x12);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_r8_zero() {
+    fn test_add_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x00);
-        cpu.registers.write_flag(Flag::C, 0);
+        let instruction = Instruction::AddAImm8(0x0);
+        cpu.registers.write_8(Register8::A, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_r8_half_carry() {
+    fn test_add_a_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x10);
-        cpu.registers.write_8(Register8::B, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::AddAImm8(0x0F);
+        cpu.registers.write_8(Register8::A, 0x0F);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x1E);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_r8_carry() {
+    fn test_add_a_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x0);
-        cpu.registers.write_flag(Flag::C, 1);
+        let instruction = Instruction::AddAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0xFF);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_sbc_a_memhl() {
+    fn test_adc_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x32);
+        let instruction = Instruction::AdcAImm8(0x12);
+        cpu.registers.write_8(Register8::A, 0x1);
         cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x14);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_memhl_zero() {
+    fn test_adc_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x00);
+        let instruction = Instruction::AdcAImm8(0x0);
+        cpu.registers.write_8(Register8::A, 0x0);
         cpu.registers.write_flag(Flag::C, 0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x00);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_memhl_half_carry() {
+    fn test_adc_a_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x10);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
+        let instruction = Instruction::AdcAImm8(0x0E);
+        cpu.registers.write_8(Register8::A, 0x01);
         cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_sbc_a_memhl_carry() {
+    fn test_adc_a_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
+        let instruction = Instruction::AdcAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0xFF);
         cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFE);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x01);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_and_a_r8() {
+    fn test_sub_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_8(Register8::B, 0b1100_1100);
+        let instruction = Instruction::SubAImm8(0x12);
+        cpu.registers.write_8(Register8::A, 0x34);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_and_a_r8_zero() {
+    fn test_sub_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_8(Register8::B, 0b0101_0101);
+        let instruction = Instruction::SubAImm8(0x0);
+        cpu.registers.write_8(Register8::A, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_and_a_memhl() {
+    fn test_sub_a_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAMemHl;
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1100_1100);
+        let instruction = Instruction::SubAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0x10);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_and_a_memhl_zero() {
+    fn test_sub_a_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAMemHl;
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b0101_0101);
+        let instruction = Instruction::SubAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_xor_a_r8() {
+    fn test_sbc_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_8(Register8::B, 0b1100_1100);
+        let instruction = Instruction::SbcAImm8(0x12);
+        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x21);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_xor_a_r8_zero() {
+    fn test_sbc_a_imm8_zero() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::SbcAImm8(0x0);
+        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_flag(Flag::C, 0);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+    }
+
+    #[test]
+    fn test_sbc_a_imm8_half_carry() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let instruction = Instruction::SbcAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_flag(Flag::C, 1);
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+    }
+
+    #[test]
+    fn test_sbc_a_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_8(Register8::B, 0b1010_1010);
+        let instruction = Instruction::SbcAImm8(0x01);
+        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_flag(Flag::C, 1);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0xFE);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_xor_a_memhl() {
+    fn test_and_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAMemHl;
+        let instruction = Instruction::AndAImm8(0b1100_1100);
         cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_xor_a_memhl_zero() {
+    fn test_and_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAMemHl;
+        let instruction = Instruction::AndAImm8(0b0101_0101);
         cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1010_1010);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
@@ -2905,21 +3820,21 @@ mod tests {
         assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0)
     }
 
     #[test]
-    fn test_or_a_r8() {
+    fn test_xor_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAR8(R8::B);
+        let instruction = Instruction::XorAImm8(0b1100_1100);
         cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_8(Register8::B, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1110_1110);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2927,16 +3842,15 @@ mod tests {
     }
 
     #[test]
-    fn test_or_a_r8_zero() {
+    fn test_xor_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAR8(R8::B);
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_8(Register8::B, 0x0);
+        let instruction = Instruction::XorAImm8(0b1010_1010);
+        cpu.registers.write_8(Register8::A, 0b1010_1010);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_8(Register8::A), 0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
@@ -2944,13 +3858,11 @@ mod tests {
     }
 
     #[test]
-    fn test_or_a_memhl() {
+    fn test_or_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAMemHl;
+        let instruction = Instruction::OrAImm8(0b1100_1100);
         cpu.registers.write_8(Register8::A, 0b1010_1010);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1100_1100);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
@@ -2963,13 +3875,11 @@ mod tests {
     }
 
     #[test]
-    fn test_or_a_memhl_zero() {
+    fn test_or_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAMemHl;
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x0);
+        let instruction = Instruction::OrAImm8(0b0);
+        cpu.registers.write_8(Register8::A, 0b0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
@@ -2981,16 +3891,15 @@ mod tests {
     }
 
     #[test]
-    fn test_cp_a_r8() {
+    fn test_cp_a_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAR8(R8::B);
+        let instruction = Instruction::CpAImm8(0x32);
         cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_8(Register8::B, 0x32);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -2998,16 +3907,15 @@ mod tests {
     }
 
     #[test]
-    fn test_cp_a_r8_zero() {
+    fn test_cp_a_imm8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAR8(R8::B);
+        let instruction = Instruction::CpAImm8(0x0);
         cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_8(Register8::B, 0x0);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -3015,16 +3923,15 @@ mod tests {
     }
 
     #[test]
-    fn test_cp_a_r8_half_carry() {
+    fn test_cp_a_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAR8(R8::B);
+        let instruction = Instruction::CpAImm8(0x01);
         cpu.registers.write_8(Register8::A, 0x10);
-        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -3032,16 +3939,15 @@ mod tests {
     }
 
     #[test]
-    fn test_cp_a_r8_carry() {
+    fn test_cp_a_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAR8(R8::B);
+        let instruction = Instruction::CpAImm8(0x01);
         cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_8(Register8::B, 0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
+        assert_eq!(cycles, 2);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 1);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
@@ -3049,402 +3955,419 @@ mod tests {
     }
 
     #[test]
-    fn test_cp_a_memhl() {
+    fn test_ret() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAMemHl;
-        cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x32);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        memory.write_byte(0x1234, 0x78);
+        memory.write_byte(0x1235, 0x56);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::Ret.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
     }
 
     #[test]
-    fn test_cp_a_memhl_zero() {
+    fn test_len_matches_decoder() {
+        for opcode in 0..=0xFFu8 {
+            for prefixed in 0..=0xFFu8 {
+                let bytes = [opcode, prefixed, 0x12];
+                let mut index = 0;
+                let decoded = decode_instruction(0, || {
+                    index += 1;
+                    bytes[index - 1]
+                });
+                if let Ok((instruction, length)) = decoded {
+                    assert_eq!(instruction.len(), length, "{opcode:02X} {prefixed:02X}");
+                }
+            }
+        }
+        assert_eq!(Instruction::CallImm16(0x1234).len(), 3);
+        assert_eq!(Instruction::SwapR8(R8::A).len(), 2);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC000, 0xCB);
+        memory.write_byte(0xC001, 0x37);
+        assert_eq!(
+            Instruction::decode(&memory, 0xC000),
+            Ok(Instruction::SwapR8(R8::A))
+        );
+        memory.write_byte(0xC000, 0xD3);
+        assert!(Instruction::decode(&memory, 0xC000).is_err());
+    }
+
+    #[test]
+    fn test_reti() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAMemHl;
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x0);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        memory.write_word(0x1234, 0x5678);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::Reti.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+        assert!(cpu.ime);
     }
 
     #[test]
-    fn test_cp_a_memhl_half_carry() {
+    fn test_di_ei() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAMemHl;
-        cpu.registers.write_8(Register8::A, 0x10);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        assert_eq!(Instruction::Ei.execute(&mut cpu, &mut memory), 1);
+        assert!(!cpu.ime);
+        assert!(cpu.ime_pending);
+        assert_eq!(Instruction::Di.execute(&mut cpu, &mut memory), 1);
+        assert!(!cpu.ime);
+        assert!(!cpu.ime_pending);
+    }
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+    #[test]
+    fn test_halt() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+
+        let cycles = Instruction::Halt.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert!(cpu.halted);
     }
 
     #[test]
-    fn test_cp_a_memhl_carry() {
+    fn test_stop() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAMemHl;
-        cpu.registers.write_8(Register8::A, 0x00);
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0x01);
+        cpu.registers.pc = 0xC001;
+        memory.tick(255);
+        assert_ne!(memory.read_byte(DIV), 0);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::Stop.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cycles, 1);
+        assert!(cpu.stopped);
+        assert_eq!(cpu.registers.pc, 0xC002);
+        assert_eq!(memory.read_byte(DIV), 0);
     }
 
     #[test]
-    fn test_add_a_imm8() {
+    fn test_stop_speed_switch() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAImm8(0x12);
-        cpu.registers.write_8(Register8::A, 0x0);
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        memory.load_cartridge(crate::gameboy::Cartridge::new(rom).unwrap());
+        memory.write_byte(0xFF4D, 0x01);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        Instruction::Stop.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x12);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert!(!cpu.stopped);
+        assert!(memory.double_speed());
+        assert_eq!(memory.read_byte(0xFF4D), 0xFE);
     }
 
     #[test]
-    fn test_add_a_imm8_zero() {
+    fn test_ret_cond_taken() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAImm8(0x0);
-        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        memory.write_byte(0x1234, 0x78);
+        memory.write_byte(0x1235, 0x56);
+        cpu.registers.write_flag(Flag::Z, 1);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::RetCond(Cond::Zero).execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 5);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+    }
+
+    #[test]
+    fn test_ret_cond_untaken() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.pc = 0x4444;
+        memory.write_byte(0x1234, 0x78);
+        memory.write_byte(0x1235, 0x56);
+        cpu.registers.write_flag(Flag::Z, 0);
+
+        let cycles = Instruction::RetCond(Cond::Zero).execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4444);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1234);
     }
 
     #[test]
-    fn test_add_a_imm8_half_carry() {
+    fn test_jp_cond_imm16_taken() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAImm8(0x0F);
-        cpu.registers.write_8(Register8::A, 0x0F);
+        cpu.registers.write_flag(Flag::Z, 1);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::JpCondImm16(Cond::Zero, 0x1234);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x1E);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x1234);
     }
 
     #[test]
-    fn test_add_a_imm8_carry() {
+    fn test_jp_cond_imm16_untaken() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AddAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0xFF);
+        cpu.registers.write_flag(Flag::Z, 0);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::JpCondImm16(Cond::Zero, 0x1234);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4321);
+    }
+
+    #[test]
+    fn test_jp_hl() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::JpHl;
+
+        let cycles = instruction.execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 1);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x1234);
     }
 
     #[test]
-    fn test_adc_a_imm8() {
+    fn test_call_imm16() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAImm8(0x12);
-        cpu.registers.write_8(Register8::A, 0x1);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::CallImm16(0x5678);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x14);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
+        assert_eq!(memory.read_byte(0x1232), 0x21);
+        assert_eq!(memory.read_byte(0x1233), 0x43);
     }
 
     #[test]
-    fn test_adc_a_imm8_zero() {
+    fn test_call_cond_imm16_taken() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAImm8(0x0);
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_flag(Flag::C, 0);
+        cpu.registers.write_flag(Flag::Z, 1);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::CallCondImm16(Cond::Zero, 0x5678);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 6);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
+        assert_eq!(memory.read_byte(0x1232), 0x21);
+        assert_eq!(memory.read_byte(0x1233), 0x43);
     }
 
     #[test]
-    fn test_adc_a_imm8_half_carry() {
+    fn test_call_cond_imm16_untaken() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAImm8(0x0E);
-        cpu.registers.write_8(Register8::A, 0x01);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_flag(Flag::Z, 0);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::CallCondImm16(Cond::Zero, 0x5678);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x10);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1234);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4321);
     }
 
     #[test]
-    fn test_adc_a_imm8_carry() {
+    fn test_rst_tgt3() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AdcAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0xFF);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.pc = 0x4321;
+        let instruction = Instruction::RstTgt3(TGT3::Zero);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x01);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
+        assert_eq!(cpu.registers.read_16(Register16::PC), 0x0);
     }
 
     #[test]
-    fn test_sub_a_imm8() {
+    fn test_pop_r16stk() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAImm8(0x12);
-        cpu.registers.write_8(Register8::A, 0x34);
-
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        memory.write_word(0x1234, 0x5678);
+        let instruction = Instruction::PopR16Stk(R16STK::BC);
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x22);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::BC), 0x5678);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
     }
 
     #[test]
-    fn test_sub_a_imm8_zero() {
+    fn test_pop_af_clears_low_nibble() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAImm8(0x0);
-        cpu.registers.write_8(Register8::A, 0x0);
-
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        cpu.registers.write_16(Register16::SP, 0xC000);
+        memory.write_word(0xC000, 0x12FF);
+        Instruction::PopR16Stk(R16STK::AF).execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
+        assert_eq!(cpu.registers.read_16(Register16::AF), 0x12F0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_sub_a_imm8_half_carry() {
+    fn test_stack_wraps() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_16(Register16::SP, 0x0000);
+        cpu.registers.write_16(Register16::BC, 0x5678);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        Instruction::PushR16Stk(R16STK::BC).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFE);
+        assert_eq!(memory.read_word(0xFFFE), 0x5678);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0F);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        Instruction::PopR16Stk(R16STK::DE).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x0000);
+        assert_eq!(cpu.registers.read_16(Register16::DE), 0x5678);
+
+        cpu.registers.write_16(Register16::SP, 0x0001);
+        Instruction::PushR16Stk(R16STK::BC).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFF);
+        Instruction::PopR16Stk(R16STK::BC).execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x0001);
     }
 
     #[test]
-    fn test_sub_a_imm8_carry() {
+    fn test_push_r16stk() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SubAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x0);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        cpu.registers.write_16(Register16::BC, 0x5678);
+        let instruction = Instruction::PushR16Stk(R16STK::BC);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFF);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
+        assert_eq!(memory.read_word(0x1232), 0x5678);
     }
 
     #[test]
-    fn test_sbc_a_imm8() {
+    fn test_ldh_memc_a() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAImm8(0x12);
-        cpu.registers.write_8(Register8::A, 0x34);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_8(Register8::A, 0x42);
+        cpu.registers.write_8(Register8::C, 0x01);
+        let instruction = Instruction::LdhMemCA;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x21);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(memory.read_byte(0xFF01), 0x42);
     }
 
     #[test]
-    fn test_sbc_a_imm8_zero() {
+    fn test_ldh_mem_imm8_a() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAImm8(0x0);
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_flag(Flag::C, 0);
+        cpu.registers.write_8(Register8::A, 0x42);
+        let instruction = Instruction::LdhMemImm8A(0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 3);
+        assert_eq!(memory.read_byte(0xFF01), 0x42);
     }
 
     #[test]
-    fn test_sbc_a_imm8_half_carry() {
+    fn test_ld_mem_imm16_a() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x10);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_8(Register8::A, 0x42);
+        let instruction = Instruction::LdMemImm16A(0x1234);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x0E);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0x1234), 0x42);
     }
 
     #[test]
-    fn test_sbc_a_imm8_carry() {
+    fn test_ld_a_mem_c() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::SbcAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x0);
-        cpu.registers.write_flag(Flag::C, 1);
+        cpu.registers.write_8(Register8::C, 0x01);
+        memory.write_byte(0xFF01, 0x42);
+        let instruction = Instruction::LdAMemC;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0xFE);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
     }
 
     #[test]
-    fn test_and_a_imm8() {
+    fn test_ldh_a_mem_imm8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAImm8(0b1100_1100);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        memory.write_byte(0xFF01, 0x42);
+        let instruction = Instruction::LdhAMemImm8(0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1000_1000);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
     }
 
     #[test]
-    fn test_and_a_imm8_zero() {
+    fn test_ld_a_mem_imm16() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::AndAImm8(0b0101_0101);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        memory.write_byte(0x1234, 0x42);
+        let instruction = Instruction::LdAMemImm16(0x1234);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0)
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
     }
 
     #[test]
-    fn test_xor_a_imm8() {
+    fn test_add_sp_imm8_positive() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAImm8(0b1100_1100);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        let instruction = Instruction::AddSpImm8(0x02);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b0110_0110);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
@@ -3452,544 +4375,597 @@ mod tests {
     }
 
     #[test]
-    fn test_xor_a_imm8_zero() {
+    fn test_add_sp_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::XorAImm8(0b1010_1010);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_16(Register16::SP, 0x12FF);
+        let instruction = Instruction::AddSpImm8(0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1300);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_or_a_imm8() {
+    fn test_add_sp_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAImm8(0b1100_1100);
-        cpu.registers.write_8(Register8::A, 0b1010_1010);
+        cpu.registers.write_16(Register16::SP, 0xFFFF);
+        let instruction = Instruction::AddSpImm8(0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0b1110_1110);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x0000);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_or_a_imm8_zero() {
+    fn test_add_sp_imm8_negative() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::OrAImm8(0b0);
-        cpu.registers.write_8(Register8::A, 0b0);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        let instruction = Instruction::AddSpImm8(-1i8 as u8);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cycles, 4);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1233);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_cp_a_imm8() {
+    fn test_add_sp_imm8_negative_no_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAImm8(0x32);
-        cpu.registers.write_8(Register8::A, 0x34);
+        cpu.registers.write_16(Register16::SP, 0x0005);
+        Instruction::AddSpImm8(-8i8 as u8).execute(&mut cpu, &mut memory);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFD);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        cpu.registers.write_16(Register16::SP, 0x0000);
+        Instruction::AddSpImm8(-1i8 as u8).execute(&mut cpu, &mut memory);
+
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFFF);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_cp_a_imm8_zero() {
+    fn test_ld_hl_sp_imm8_negative_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAImm8(0x0);
-        cpu.registers.write_8(Register8::A, 0x0);
-
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        cpu.registers.write_16(Register16::SP, 0xFFF8);
+        Instruction::LdHlSpImm8(-8i8 as u8).execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0xFFF0);
+        assert_eq!(cpu.registers.read_16(Register16::SP), 0xFFF8);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_cp_a_imm8_half_carry() {
+    fn test_ld_hl_sp_imm8_positive() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x10);
+        cpu.registers.write_16(Register16::SP, 0x1234);
+        let instruction = Instruction::LdHlSpImm8(0x02);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1236);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_cp_a_imm8_carry() {
+    fn test_ld_hl_sp_imm8_half_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        let instruction = Instruction::CpAImm8(0x01);
-        cpu.registers.write_8(Register8::A, 0x00);
+        cpu.registers.write_16(Register16::SP, 0x12FF);
+        let instruction = Instruction::LdHlSpImm8(0x01);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1300);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 1);
         assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ret() {
+    fn test_ld_hl_sp_imm8_carry() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        memory.write_byte(0x1234, 0x78);
-        memory.write_byte(0x1235, 0x56);
+        cpu.registers.write_16(Register16::SP, 0xFFFF);
+        let instruction = Instruction::LdHlSpImm8(0x01);
 
-        let cycles = Instruction::Ret.execute(&mut cpu, &mut memory);
+        let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x0000);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ret_cond_taken() {
+    fn test_ld_hl_sp_imm8_negative() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
         cpu.registers.write_16(Register16::SP, 0x1234);
-        memory.write_byte(0x1234, 0x78);
-        memory.write_byte(0x1235, 0x56);
-        cpu.registers.write_flag(Flag::Z, 1);
+        let instruction = Instruction::LdHlSpImm8(-1i8 as u8);
 
-        let cycles = Instruction::RetCond(Cond::Zero).execute(&mut cpu, &mut memory);
+        let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 5);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+        assert_eq!(cycles, 3);
+        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1233);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_ret_cond_untaken() {
+    fn test_ld_sp_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.pc = 0x4444;
-        memory.write_byte(0x1234, 0x78);
-        memory.write_byte(0x1235, 0x56);
-        cpu.registers.write_flag(Flag::Z, 0);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        let instruction = Instruction::LdSpHl;
 
-        let cycles = Instruction::RetCond(Cond::Zero).execute(&mut cpu, &mut memory);
+        let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4444);
         assert_eq!(cpu.registers.read_16(Register16::SP), 0x1234);
     }
 
     #[test]
-    fn test_jp_cond_imm16_taken() {
+    fn test_rlc_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_flag(Flag::Z, 1);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::JpCondImm16(Cond::Zero, 0x1234);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0b1010_1010);
+        let instruction = Instruction::RlcMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x1234);
-    }
-
-    #[test]
-    fn test_jp_cond_imm16_untaken() {
-        let mut cpu = Cpu::new();
-        let mut memory = Memory::new();
-        cpu.registers.write_flag(Flag::Z, 0);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::JpCondImm16(Cond::Zero, 0x1234);
-
-        let cycles = instruction.execute(&mut cpu, &mut memory);
-
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4321);
+        assert_eq!(memory.read_byte(0x1234), 0b0101_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_jp_hl() {
+    fn test_rlc_mem_hl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
         cpu.registers.write_16(Register16::HL, 0x1234);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::JpHl;
+        memory.write_byte(0x1234, 0b0000_0000);
+        let instruction = Instruction::RlcMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 1);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x1234);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0x1234), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_call_imm16() {
+    fn test_rlc_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::CallImm16(0x5678);
+        cpu.registers.write_8(Register8::B, 0b1010_1010);
+        let instruction = Instruction::RlcR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 6);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
-        assert_eq!(memory.read_byte(0x1232), 0x21);
-        assert_eq!(memory.read_byte(0x1233), 0x43);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0101_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_call_cond_imm16_taken() {
+    fn test_rlc_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_flag(Flag::Z, 1);
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::CallCondImm16(Cond::Zero, 0x5678);
+        cpu.registers.write_8(Register8::B, 0b0000_0000);
+        let instruction = Instruction::RlcR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 6);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x5678);
-        assert_eq!(memory.read_byte(0x1232), 0x21);
-        assert_eq!(memory.read_byte(0x1233), 0x43);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_call_cond_imm16_untaken() {
+    fn test_rrc_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_flag(Flag::Z, 0);
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::CallCondImm16(Cond::Zero, 0x5678);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0b1010_1010);
+        let instruction = Instruction::RrcMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1234);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x4321);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0x1234), 0b0101_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_rst_tgt3() {
+    fn test_rrc_mem_hl_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.pc = 0x4321;
-        let instruction = Instruction::RstTgt3(TGT3::Zero);
+        cpu.registers.write_16(Register16::HL, 0x1234);
+        memory.write_byte(0x1234, 0b0000_0000);
+        let instruction = Instruction::RrcMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
-        assert_eq!(cpu.registers.read_16(Register16::PC), 0x0);
+        assert_eq!(memory.read_byte(0x1234), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_pop_r16stk() {
+    fn test_rrc_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        memory.write_word(0x1234, 0x5678);
-        let instruction = Instruction::PopR16Stk(R16STK::BC);
+        cpu.registers.write_8(Register8::B, 0b0101_0101);
+        let instruction = Instruction::RrcR8(R8::B);
+
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::BC), 0x5678);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b1010_1010);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_push_r16stk() {
+    fn test_rrc_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        cpu.registers.write_16(Register16::BC, 0x5678);
-        let instruction = Instruction::PushR16Stk(R16STK::BC);
+        cpu.registers.write_8(Register8::B, 0b0000_0000);
+        let instruction = Instruction::RrcR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1232);
-        assert_eq!(memory.read_word(0x1232), 0x5678);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ldh_memc_a() {
+    fn test_rl_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::A, 0x42);
-        cpu.registers.write_8(Register8::C, 0x01);
-        let instruction = Instruction::LdhMemCA;
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b1010_1010);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::RlMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(memory.read_byte(0xFF01), 0x42);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0xC000), 0b0101_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ldh_mem_imm8_a() {
+    fn test_rl_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::A, 0x42);
-        let instruction = Instruction::LdhMemImm8A(0x01);
+        cpu.registers.write_8(Register8::B, 0b0101_0101);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::RlR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(memory.read_byte(0xFF01), 0x42);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b1010_1010);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ld_mem_imm16_a() {
+    fn test_rl_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::A, 0x42);
-        let instruction = Instruction::LdMemImm16A(0x1234);
+        cpu.registers.write_8(Register8::B, 0b1000_0000);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::RlR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(memory.read_byte(0x1234), 0x42);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ld_a_mem_c() {
+    fn test_rr_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::C, 0x01);
-        memory.write_byte(0xFF01, 0x42);
-        let instruction = Instruction::LdAMemC;
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b0101_0101);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::RrMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0xC000), 0b0010_1010);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ldh_a_mem_imm8() {
+    fn test_rr_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        memory.write_byte(0xFF01, 0x42);
-        let instruction = Instruction::LdhAMemImm8(0x01);
+        cpu.registers.write_8(Register8::B, 0b1010_1010);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::RrR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b1101_0101);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ld_a_mem_imm16() {
+    fn test_rr_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        memory.write_byte(0x1234, 0x42);
-        let instruction = Instruction::LdAMemImm16(0x1234);
+        cpu.registers.write_8(Register8::B, 0b0000_0001);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::RrR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_8(Register8::A), 0x42);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_add_sp_imm8_positive() {
+    fn test_sla_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        let instruction = Instruction::AddSpImm8(0x02);
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b1100_0001);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SlaMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1236);
+        assert_eq!(memory.read_byte(0xC000), 0b1000_0010);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_add_sp_imm8_half_carry() {
+    fn test_sla_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x12FF);
-        let instruction = Instruction::AddSpImm8(0x01);
+        cpu.registers.write_8(Register8::B, 0b0100_0001);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SlaR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1300);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b1000_0010);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_add_sp_imm8_carry() {
+    fn test_sla_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0xFFFF);
-        let instruction = Instruction::AddSpImm8(0x01);
+        cpu.registers.write_8(Register8::B, 0b1000_0000);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SlaR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x0000);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_add_sp_imm8_negative() {
+    fn test_sra_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        let instruction = Instruction::AddSpImm8(-1i8 as u8);
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b1000_0011);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SraMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1233);
+        assert_eq!(memory.read_byte(0xC000), 0b1100_0001);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_hl_sp_imm8_positive() {
+    fn test_sra_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        let instruction = Instruction::LdHlSpImm8(0x02);
+        cpu.registers.write_8(Register8::B, 0b0100_0010);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SraR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1236);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0010_0001);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_hl_sp_imm8_half_carry() {
+    fn test_sra_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x12FF);
-        let instruction = Instruction::LdHlSpImm8(0x01);
+        cpu.registers.write_8(Register8::B, 0b0000_0001);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SraR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1300);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ld_hl_sp_imm8_carry() {
+    fn test_swap_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0xFFFF);
-        let instruction = Instruction::LdHlSpImm8(0x01);
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0xA5);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SwapMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0x0000);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0xC000), 0x5A);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_ld_hl_sp_imm8_negative() {
+    fn test_swap_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::SP, 0x1234);
-        let instruction = Instruction::LdHlSpImm8(-1i8 as u8);
+        cpu.registers.write_8(Register8::B, 0xF0);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SwapR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 3);
-        assert_eq!(cpu.registers.read_16(Register16::HL), 0x1233);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x0F);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
     }
 
     #[test]
-    fn test_ld_sp_hl() {
+    fn test_swap_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        let instruction = Instruction::LdSpHl;
+        cpu.registers.write_8(Register8::B, 0x00);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SwapR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_16(Register16::SP), 0x1234);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0x00);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_rlc_mem_hl() {
+    fn test_srl_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1010_1010);
-        let instruction = Instruction::RlcMemHl;
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b1000_0011);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SrlMemHl;
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(memory.read_byte(0x1234), 0b0101_0101);
+        assert_eq!(memory.read_byte(0xC000), 0b0100_0001);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
         assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
@@ -3997,124 +4973,137 @@ mod tests {
     }
 
     #[test]
-    fn test_rlc_mem_hl_zero() {
+    fn test_srl_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b0000_0000);
-        let instruction = Instruction::RlcMemHl;
+        cpu.registers.write_8(Register8::B, 0b1000_0010);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        let instruction = Instruction::SrlR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(memory.read_byte(0x1234), 0b0000_0000);
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0100_0001);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_rlc_r8() {
+    fn test_srl_r8_zero() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::B, 0b1010_1010);
-        let instruction = Instruction::RlcR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0b0000_0001);
+        cpu.registers.write_flag(Flag::C, 0x0);
+        let instruction = Instruction::SrlR8(R8::B);
 
         let cycles = instruction.execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0b0101_0101);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
         assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
         assert_eq!(cpu.registers.read_flag(Flag::H), 0);
     }
 
     #[test]
-    fn test_rlc_r8_zero() {
+    fn test_bit_b3_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::B, 0b0000_0000);
-        let instruction = Instruction::RlcR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0b0000_1000);
+        cpu.registers.write_flag(Flag::C, 0x1);
+        cpu.registers.write_flag(Flag::N, 0x1);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::BitB3R8(B3::Three, R8::B).execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_1000);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
+
+        Instruction::BitB3R8(B3::Seven, R8::B).execute(&mut cpu, &mut memory);
+
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 1);
     }
 
     #[test]
-    fn test_rrc_mem_hl() {
+    fn test_bit_b3_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b1010_1010);
-        let instruction = Instruction::RrcMemHl;
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0b1000_0000);
+        cpu.registers.write_flag(Flag::C, 0x0);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::BitB3MemHl(B3::Zero).execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 4);
-        assert_eq!(memory.read_byte(0x1234), 0b0101_0101);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
+        assert_eq!(cycles, 3);
+        assert_eq!(memory.read_byte(0xC000), 0b1000_0000);
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
         assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_flag(Flag::H), 1);
+        assert_eq!(cpu.registers.read_flag(Flag::C), 0);
+
+        Instruction::BitB3MemHl(B3::Seven).execute(&mut cpu, &mut memory);
+
+        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
     }
 
     #[test]
-    fn test_rrc_mem_hl_zero() {
+    fn test_res_b3_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_16(Register16::HL, 0x1234);
-        memory.write_byte(0x1234, 0b0000_0000);
-        let instruction = Instruction::RrcMemHl;
+        cpu.registers.write_8(Register8::B, 0xFF);
+        let flags = cpu.registers.read_8(Register8::F);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::ResB3R8(B3::Four, R8::B).execute(&mut cpu, &mut memory);
+
+        assert_eq!(cycles, 2);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b1110_1111);
+        assert_eq!(cpu.registers.read_8(Register8::F), flags);
+    }
+
+    #[test]
+    fn test_res_b3_mem_hl() {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0xFF);
+
+        let cycles = Instruction::ResB3MemHl(B3::Zero).execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 4);
-        assert_eq!(memory.read_byte(0x1234), 0b0000_0000);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(memory.read_byte(0xC000), 0b1111_1110);
     }
 
     #[test]
-    fn test_rrc_r8() {
+    fn test_set_b3_r8() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::B, 0b0101_0101);
-        let instruction = Instruction::RrcR8(R8::B);
+        cpu.registers.write_8(Register8::B, 0x00);
+        let flags = cpu.registers.read_8(Register8::F);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::SetB3R8(B3::Six, R8::B).execute(&mut cpu, &mut memory);
 
         assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0b1010_1010);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x1);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cpu.registers.read_8(Register8::B), 0b0100_0000);
+        assert_eq!(cpu.registers.read_8(Register8::F), flags);
     }
 
     #[test]
-    fn test_rrc_r8_zero() {
+    fn test_set_b3_mem_hl() {
         let mut cpu = Cpu::new();
         let mut memory = Memory::new();
-        cpu.registers.write_8(Register8::B, 0b0000_0000);
-        let instruction = Instruction::RrcR8(R8::B);
+        cpu.registers.write_16(Register16::HL, 0xC000);
+        memory.write_byte(0xC000, 0x00);
 
-        let cycles = instruction.execute(&mut cpu, &mut memory);
+        let cycles = Instruction::SetB3MemHl(B3::Seven).execute(&mut cpu, &mut memory);
 
-        assert_eq!(cycles, 2);
-        assert_eq!(cpu.registers.read_8(Register8::B), 0b0000_0000);
-        assert_eq!(cpu.registers.read_flag(Flag::C), 0x0);
-        assert_eq!(cpu.registers.read_flag(Flag::Z), 1);
-        assert_eq!(cpu.registers.read_flag(Flag::N), 0);
-        assert_eq!(cpu.registers.read_flag(Flag::H), 0);
+        assert_eq!(cycles, 4);
+        assert_eq!(memory.read_byte(0xC000), 0b1000_0000);
     }
 }
@@ -1,6 +1,15 @@
+mod block_cache;
 mod cpu_core;
+mod disassembler;
+mod dispatch;
 mod instruction_variables;
 mod instructions;
 mod registers;
+mod trace;
 
-pub use cpu_core::Cpu;
+pub use cpu_core::{Cpu, IllegalOpcodePolicy, StepResult};
+pub use disassembler::{DisassembledInstruction, Disassembler, Instructions};
+pub use instruction_variables::{Cond, B3, R16, R16MEM, R16STK, R8, TGT3};
+pub use instructions::Instruction;
+pub use registers::Register16;
+pub use trace::{InstructionHistory, HISTORY_LENGTH};
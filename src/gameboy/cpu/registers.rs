@@ -2,13 +2,16 @@
 //! It also contains the Register16, Register8 and Flag enums which are used to represent the different registers and flags.
 //! The Registers struct has methods to read and write the values of the registers and flags.
 //! The Register16 and Register8 enums have methods to convert the instruction variables to the corresponding register.
+//! The flags live in the upper nibble of F (Z = bit 7, N = 6, H = 5, C = 4), the lower nibble always reads 0.
 
-use crate::utils::{get_bit_u16, get_hi, get_lo, set_bit_u16, set_hi, set_lo};
+use core::ops::{BitOr, BitOrAssign};
+
+use crate::utils::{get_hi, get_lo, set_hi, set_lo};
 
 use super::instruction_variables::{R16, R16MEM, R16STK, R8};
 
 /// 16 bit register for reading and writing
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Register16 {
     AF,
     BC,
@@ -79,6 +82,7 @@ impl From<R8> for Register8 {
 }
 
 /// Flag register for reading and writing
+#[derive(Debug, Copy, Clone)]
 pub enum Flag {
     Z,
     N,
@@ -86,6 +90,68 @@ pub enum Flag {
     C,
 }
 
+/// A set of flags, laid out as in F
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Flags(u8);
+
+impl Flags {
+    pub const Z: Flags = Flags(0x80);
+    pub const N: Flags = Flags(0x40);
+    pub const H: Flags = Flags(0x20);
+    pub const C: Flags = Flags(0x10);
+
+    pub const fn empty() -> Flags {
+        Flags(0)
+    }
+
+    /// The flags in the upper nibble of `bits`, the lower nibble is dropped
+    pub const fn from_bits_truncate(bits: u8) -> Flags {
+        Flags(bits & 0xF0)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Insert `other` if `value` is true, remove it otherwise
+    pub fn set(&mut self, other: Flags, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl From<Flag> for Flags {
+    fn from(flag: Flag) -> Flags {
+        match flag {
+            Flag::Z => Flags::Z,
+            Flag::N => Flags::N,
+            Flag::H => Flags::H,
+            Flag::C => Flags::C,
+        }
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+}
+
 /// Contains the values of the CPU registers
 ///
 /// PC is public for easy access
@@ -99,10 +165,10 @@ pub struct Registers {
 }
 
 impl Registers {
-    /// Create a new Registers object
+    /// Create a new Registers object, the lower nibble of F is cleared
     pub fn new(af: u16, bc: u16, de: u16, hl: u16, sp: u16, pc: u16) -> Registers {
         Registers {
-            af,
+            af: af & 0xFFF0,
             bc,
             de,
             hl,
@@ -123,10 +189,10 @@ impl Registers {
         }
     }
 
-    /// write a value to a 16 bit register
+    /// write a value to a 16 bit register, the lower nibble of F can't be set
     pub fn write_16(&mut self, register: Register16, value: u16) {
         match register {
-            Register16::AF => self.af = value,
+            Register16::AF => self.af = value & 0xFFF0,
             Register16::BC => self.bc = value,
             Register16::DE => self.de = value,
             Register16::HL => self.hl = value,
@@ -149,11 +215,11 @@ impl Registers {
         }
     }
 
-    /// write a value to the 8 bit part of a register
+    /// write a value to the 8 bit part of a register, the lower nibble of F can't be set
     pub fn write_8(&mut self, register: Register8, value: u8) {
         match register {
             Register8::A => set_hi(&mut self.af, value),
-            Register8::F => set_lo(&mut self.af, value),
+            Register8::F => set_lo(&mut self.af, value & 0xF0),
             Register8::B => set_hi(&mut self.bc, value),
             Register8::C => set_lo(&mut self.bc, value),
             Register8::D => set_hi(&mut self.de, value),
@@ -165,22 +231,32 @@ impl Registers {
 
     /// read the value of a flag
     pub fn read_flag(&self, flag: Flag) -> u8 {
-        match flag {
-            Flag::Z => get_bit_u16(self.af, 0),
-            Flag::N => get_bit_u16(self.af, 1),
-            Flag::H => get_bit_u16(self.af, 2),
-            Flag::C => get_bit_u16(self.af, 3),
-        }
+        u8::from(self.flags().contains(Flags::from(flag)))
     }
 
-    /// write a value to a flag
+    /// write a value to a flag, any non zero value sets it
     pub fn write_flag(&mut self, flag: Flag, value: u8) {
-        match flag {
-            Flag::Z => set_bit_u16(&mut self.af, 0, value),
-            Flag::N => set_bit_u16(&mut self.af, 1, value),
-            Flag::H => set_bit_u16(&mut self.af, 2, value),
-            Flag::C => set_bit_u16(&mut self.af, 3, value),
-        }
+        let mut flags = self.flags();
+        flags.set(Flags::from(flag), value != 0);
+        self.write_flags(flags);
+    }
+
+    pub fn flags(&self) -> Flags {
+        Flags::from_bits_truncate(get_lo(self.af))
+    }
+
+    pub fn write_flags(&mut self, flags: Flags) {
+        set_lo(&mut self.af, flags.bits());
+    }
+
+    /// write all four flags at once
+    pub fn set_flags(&mut self, z: bool, n: bool, h: bool, c: bool) {
+        let mut flags = Flags::empty();
+        flags.set(Flags::Z, z);
+        flags.set(Flags::N, n);
+        flags.set(Flags::H, h);
+        flags.set(Flags::C, c);
+        self.write_flags(flags);
     }
 }
 
@@ -191,7 +267,7 @@ mod tests {
     #[test]
     fn test_read_16() {
         let registers = Registers::new(0x1234, 0x5678, 0x9ABC, 0xDEF0, 0x1357, 0x2468);
-        assert_eq!(registers.read_16(Register16::AF), 0x1234);
+        assert_eq!(registers.read_16(Register16::AF), 0x1230);
         assert_eq!(registers.read_16(Register16::BC), 0x5678);
         assert_eq!(registers.read_16(Register16::DE), 0x9ABC);
         assert_eq!(registers.read_16(Register16::HL), 0xDEF0);
@@ -208,7 +284,7 @@ mod tests {
         registers.write_16(Register16::HL, 0xDEF0);
         registers.write_16(Register16::SP, 0x1357);
         registers.write_16(Register16::PC, 0x2468);
-        assert_eq!(registers.af, 0x1234);
+        assert_eq!(registers.af, 0x1230);
         assert_eq!(registers.bc, 0x5678);
         assert_eq!(registers.de, 0x9ABC);
         assert_eq!(registers.hl, 0xDEF0);
@@ -220,7 +296,7 @@ mod tests {
     fn test_read_8() {
         let registers = Registers::new(0x1234, 0x5678, 0x9ABC, 0xDEF0, 0x1357, 0x2468);
         assert_eq!(registers.read_8(Register8::A), 0x12);
-        assert_eq!(registers.read_8(Register8::F), 0x34);
+        assert_eq!(registers.read_8(Register8::F), 0x30);
         assert_eq!(registers.read_8(Register8::B), 0x56);
         assert_eq!(registers.read_8(Register8::C), 0x78);
         assert_eq!(registers.read_8(Register8::D), 0x9A);
@@ -240,7 +316,7 @@ mod tests {
         registers.write_8(Register8::E, 0xBC);
         registers.write_8(Register8::H, 0xDE);
         registers.write_8(Register8::L, 0xF0);
-        assert_eq!(registers.af, 0x1234);
+        assert_eq!(registers.af, 0x1230);
         assert_eq!(registers.bc, 0x5678);
         assert_eq!(registers.de, 0x9ABC);
         assert_eq!(registers.hl, 0xDEF0);
@@ -249,10 +325,10 @@ mod tests {
     #[test]
     fn test_read_flag() {
         let registers = Registers::new(0b1010_1010_1010_1010, 0, 0, 0, 0, 0);
-        assert_eq!(registers.read_flag(Flag::Z), 0);
-        assert_eq!(registers.read_flag(Flag::N), 1);
-        assert_eq!(registers.read_flag(Flag::H), 0);
-        assert_eq!(registers.read_flag(Flag::C), 1);
+        assert_eq!(registers.read_flag(Flag::Z), 1);
+        assert_eq!(registers.read_flag(Flag::N), 0);
+        assert_eq!(registers.read_flag(Flag::H), 1);
+        assert_eq!(registers.read_flag(Flag::C), 0);
     }
 
     #[test]
@@ -263,6 +339,26 @@ mod tests {
         registers.write_flag(Flag::H, 1);
         registers.write_flag(Flag::C, 0);
 
-        assert_eq!(registers.read_8(Register8::F), 0x5);
+        assert_eq!(registers.read_8(Register8::F), 0xA0);
+    }
+
+    #[test]
+    fn test_set_flags() {
+        let mut registers = Registers::new(0, 0, 0, 0, 0, 0);
+        registers.set_flags(true, false, false, true);
+
+        assert_eq!(registers.read_8(Register8::F), 0x90);
+        assert_eq!(registers.flags(), Flags::Z | Flags::C);
+        assert!(!registers.flags().contains(Flags::N));
+    }
+
+    #[test]
+    fn test_f_low_nibble_reads_zero() {
+        let mut registers = Registers::new(0, 0, 0, 0, 0, 0);
+        registers.write_16(Register16::AF, 0x12FF);
+        assert_eq!(registers.read_16(Register16::AF), 0x12F0);
+
+        registers.write_8(Register8::F, 0x0F);
+        assert_eq!(registers.read_8(Register8::F), 0x00);
     }
 }
@@ -0,0 +1,115 @@
+//! Per-instruction trace lines in the Gameboy Doctor format, for diffing against reference logs.
+//!
+//! `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+//!
+//! The CPU also always keeps the last `HISTORY_LENGTH` instructions it started in an
+//! `InstructionHistory`, cheap enough to leave on, for the debugger and core dumps to show how a
+//! game got where it crashed.
+
+use alloc::{format, string::String, vec::Vec};
+
+use crate::gameboy::MemoryBus;
+
+use super::registers::{Register16, Registers};
+
+/// The state before executing the instruction at PC, as one trace line
+pub fn doctor_line(registers: &Registers, memory: &impl MemoryBus) -> String {
+    let [a, f] = registers.read_16(Register16::AF).to_be_bytes();
+    let [b, c] = registers.read_16(Register16::BC).to_be_bytes();
+    let [d, e] = registers.read_16(Register16::DE).to_be_bytes();
+    let [h, l] = registers.read_16(Register16::HL).to_be_bytes();
+    let sp = registers.read_16(Register16::SP);
+    let pc = registers.pc;
+    let pcmem = [0, 1, 2, 3].map(|offset| memory.peek_byte(pc.wrapping_add(offset)));
+
+    format!(
+        "A:{a:02X} F:{f:02X} B:{b:02X} C:{c:02X} D:{d:02X} E:{e:02X} H:{h:02X} L:{l:02X} \
+         SP:{sp:04X} PC:{pc:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        pcmem[0], pcmem[1], pcmem[2], pcmem[3]
+    )
+}
+
+/// How many instructions the CPU keeps in its history by default
+pub const HISTORY_LENGTH: usize = 64;
+
+/// The addresses and opcodes of the last instructions the CPU started, in a ring buffer
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionHistory {
+    entries: Vec<(u16, u8)>,
+    capacity: usize,
+    next: usize, // where the next entry goes once full
+}
+
+impl InstructionHistory {
+    pub fn new(capacity: usize) -> InstructionHistory {
+        InstructionHistory {
+            entries: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    pub fn record(&mut self, address: u16, opcode: u8) {
+        if self.entries.len() < self.capacity {
+            self.entries.push((address, opcode));
+        } else if self.capacity > 0 {
+            self.entries[self.next] = (address, opcode);
+            self.next += 1;
+            if self.next == self.capacity {
+                self.next = 0;
+            }
+        }
+    }
+
+    /// The instructions as `(address, opcode)`, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        let (newer, older) = self.entries.split_at(self.next);
+        older.iter().chain(newer).copied()
+    }
+
+    /// The most instructions kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Memory;
+
+    #[test]
+    fn test_doctor_line() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC000, 0xC3);
+        memory.write_byte(0xC001, 0x13);
+        memory.write_byte(0xC002, 0x02);
+        let registers = Registers::new(0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE, 0xC000);
+
+        assert_eq!(
+            doctor_line(&registers, &memory),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:C3,13,02,00"
+        );
+    }
+
+    #[test]
+    fn test_instruction_history() {
+        let mut history = InstructionHistory::new(3);
+        for address in 0..5 {
+            history.record(address, address as u8 + 0x10);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            [(2, 0x12), (3, 0x13), (4, 0x14)]
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! A debugger wrapping a `GameBoy`, with PC breakpoints, memory watchpoints and stepping.
+//!
+//! Breakpoints are checked before every instruction, watchpoints observe the memory
+//! accesses the CPU makes while executing one. A breakpoint may carry a condition, an
+//! `Expression` it only stops on when true, and watch expressions are evaluated for whoever
+//! shows them after each stop. The CPU's instruction history shows the way to wherever
+//! execution stopped.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::cell::Cell;
+
+use super::{
+    DisassembledInstruction, Expression, GameBoy, Instruction, MemoryAccess, MemoryBus, Register16,
+};
+
+/// Opcodes of RET, RET cond and RETI
+const RETURN_OPCODES: [u8; 6] = [0xC9, 0xC0, 0xC8, 0xD0, 0xD8, 0xD9];
+
+/// Which accesses trigger a watchpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, access: MemoryAccess) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _)
+                | (WatchKind::Read, MemoryAccess::Read { .. })
+                | (WatchKind::Write, MemoryAccess::Write { .. })
+        )
+    }
+}
+
+/// Why execution returned to the debugger
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    /// the requested step completed
+    Step,
+    /// PC reached a breakpoint, the instruction there has not run yet
+    Breakpoint(u16),
+    /// an instruction accessed a watched address
+    Watchpoint(MemoryAccess),
+    /// the current function returned to its caller
+    Returned,
+    /// the cycle budget ran out first
+    CycleLimit,
+    /// the CPU trapped on the illegal opcode at this address, see `IllegalOpcodePolicy::Trap`
+    IllegalOpcode(u16),
+}
+
+pub struct Debugger {
+    gameboy: GameBoy,
+    /// with the condition, if any
+    breakpoints: BTreeMap<u16, Option<Expression>>,
+    watchpoints: BTreeMap<u16, WatchKind>,
+    watch_expressions: Vec<Expression>,
+}
+
+impl Debugger {
+    pub fn new(gameboy: GameBoy) -> Debugger {
+        Debugger {
+            gameboy,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            watch_expressions: Vec::new(),
+        }
+    }
+
+    pub fn gameboy(&self) -> &GameBoy {
+        &self.gameboy
+    }
+
+    pub fn gameboy_mut(&mut self) -> &mut GameBoy {
+        &mut self.gameboy
+    }
+
+    pub fn into_inner(self) -> GameBoy {
+        self.gameboy
+    }
+
+    /// Returns false if there already was a breakpoint at `address`, its condition is dropped
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address, None).is_none()
+    }
+
+    /// Break at `address` only when `condition` is true as PC reaches it, replacing any
+    /// existing breakpoint there
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Expression) {
+        self.breakpoints.insert(address, Some(condition));
+    }
+
+    /// Returns false if there was no breakpoint at `address`
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    /// The condition of the breakpoint at `address`, None for an unconditional one
+    pub fn breakpoint_condition(&self, address: u16) -> Option<&Expression> {
+        self.breakpoints.get(&address)?.as_ref()
+    }
+
+    /// Watch `address`, replacing any existing watchpoint on it
+    pub fn add_watchpoint(&mut self, address: u16, kind: WatchKind) {
+        self.watchpoints.insert(address, kind);
+    }
+
+    /// Returns false if there was no watchpoint on `address`
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (u16, WatchKind)> + '_ {
+        self.watchpoints
+            .iter()
+            .map(|(&address, &kind)| (address, kind))
+    }
+
+    pub fn add_watch_expression(&mut self, expression: Expression) {
+        self.watch_expressions.push(expression);
+    }
+
+    /// Remove the watch expression at `index` in `watch_expressions`
+    pub fn remove_watch_expression(&mut self, index: usize) -> Option<Expression> {
+        (index < self.watch_expressions.len()).then(|| self.watch_expressions.remove(index))
+    }
+
+    /// The watch expressions with their current values, in the order they were added
+    pub fn watch_expressions(&self) -> impl Iterator<Item = (&Expression, i64)> + '_ {
+        self.watch_expressions
+            .iter()
+            .map(|expression| (expression, expression.evaluate(&self.gameboy)))
+    }
+
+    /// The last `count` instructions run before PC, oldest first, disassembled from memory as it
+    /// is now
+    pub fn history(&self, count: usize) -> Vec<DisassembledInstruction> {
+        let history = self.gameboy.cpu().instruction_history();
+        let disassembler = self.gameboy.disassembler();
+        history
+            .iter()
+            .skip(history.len().saturating_sub(count))
+            .map(|(address, _)| disassembler.disassemble(address))
+            .collect()
+    }
+
+    fn pc(&self) -> u16 {
+        self.gameboy.cpu().registers.pc
+    }
+
+    fn sp(&self) -> u16 {
+        self.gameboy.cpu().registers.read_16(Register16::SP)
+    }
+
+    /// Whether there is a breakpoint at PC with no condition or a true one
+    fn breakpoint_hit(&self) -> bool {
+        match self.breakpoints.get(&self.pc()) {
+            Some(Some(condition)) => condition.is_true(&self.gameboy),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    /// Execute a single instruction, breakpoints are ignored
+    ///
+    /// Returns the T-cycles taken and the first watched access made
+    fn step_watched(&mut self) -> (u32, Option<MemoryAccess>) {
+        let hit = Cell::new(None);
+        let watchpoints = &self.watchpoints;
+        let observer = |access: MemoryAccess| {
+            let watched = watchpoints
+                .get(&access.address())
+                .is_some_and(|kind| kind.matches(access));
+            if watched && hit.get().is_none() {
+                hit.set(Some(access));
+            }
+        };
+
+        let cycles = self.gameboy.step_observed(&observer);
+        (cycles, hit.get())
+    }
+
+    /// Execute a single instruction
+    pub fn step(&mut self) -> StopReason {
+        match self.step_watched() {
+            (_, Some(access)) => StopReason::Watchpoint(access),
+            _ if self.gameboy.cpu().error().is_some() => StopReason::IllegalOpcode(self.pc()),
+            (_, None) => StopReason::Step,
+        }
+    }
+
+    /// Run until `done` returns true after an instruction, a breakpoint or watchpoint is hit,
+    /// or `max_cycles` T-cycles have passed
+    fn run_until(
+        &mut self,
+        max_cycles: u64,
+        mut done: impl FnMut(&Debugger) -> bool,
+    ) -> StopReason {
+        let mut elapsed = 0;
+        loop {
+            let (cycles, access) = self.step_watched();
+            elapsed += u64::from(cycles);
+
+            if let Some(access) = access {
+                return StopReason::Watchpoint(access);
+            }
+            if self.gameboy.cpu().error().is_some() {
+                return StopReason::IllegalOpcode(self.pc());
+            }
+            if done(self) {
+                return StopReason::Step;
+            }
+            if self.breakpoint_hit() {
+                return StopReason::Breakpoint(self.pc());
+            }
+            if elapsed >= max_cycles {
+                return StopReason::CycleLimit;
+            }
+        }
+    }
+
+    /// Run until a breakpoint or watchpoint is hit, or `max_cycles` T-cycles have passed
+    ///
+    /// The instruction at the current PC always runs, so continuing from a breakpoint makes progress
+    pub fn continue_execution(&mut self, max_cycles: u64) -> StopReason {
+        self.run_until(max_cycles, |_| false)
+    }
+
+    /// Execute one instruction, running a called function to completion as part of the step
+    pub fn step_over(&mut self, max_cycles: u64) -> StopReason {
+        let pc = self.pc();
+        let call = match Instruction::decode(self.gameboy.memory(), pc) {
+            Ok(
+                call @ (Instruction::CallImm16(_)
+                | Instruction::CallCondImm16(..)
+                | Instruction::RstTgt3(_)),
+            ) => call,
+            _ => return self.step(),
+        };
+
+        // the call may recurse, only the return to this level counts
+        let return_address = pc.wrapping_add(u16::from(call.len()));
+        let sp = self.sp();
+        self.run_until(max_cycles, |debugger| {
+            debugger.pc() == return_address && debugger.sp() >= sp
+        })
+    }
+
+    /// Run until the current function returns to its caller
+    pub fn run_to_return(&mut self, max_cycles: u64) -> StopReason {
+        let sp = self.sp();
+        let mut returning = RETURN_OPCODES.contains(&self.gameboy.memory().peek_byte(self.pc()));
+        let reason = self.run_until(max_cycles, |debugger| {
+            // checked after each instruction, `returning` is whether the next one is a return
+            let done = returning && debugger.sp() > sp;
+            let opcode = debugger.gameboy.memory().peek_byte(debugger.pc());
+            returning = RETURN_OPCODES.contains(&opcode);
+            done
+        });
+
+        match reason {
+            StopReason::Step => StopReason::Returned,
+            reason => reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{test_rom, IllegalOpcodePolicy};
+
+    /// A ROM only cartridge with each program copied to its address, execution starts at 0x0150
+    /// past the header
+    fn make_debugger(programs: &[(usize, &[u8])]) -> Debugger {
+        let mut rom = test_rom(&[]);
+        for (address, program) in programs {
+            rom[*address..*address + program.len()].copy_from_slice(program);
+        }
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+        gameboy.cpu_mut().registers.pc = 0x0150;
+        Debugger::new(gameboy)
+    }
+
+    // 0150: CALL $0200; NOP; JR -2
+    // 0200: INC A; RET
+    fn call_program() -> Debugger {
+        make_debugger(&[
+            (0x0150, &[0xCD, 0x00, 0x02, 0x00, 0x18, 0xFE]),
+            (0x0200, &[0x3C, 0xC9]),
+        ])
+    }
+
+    #[test]
+    fn test_step() {
+        let mut debugger = make_debugger(&[(0x0150, &[0x00, 0x00])]);
+
+        assert_eq!(debugger.step(), StopReason::Step);
+        assert_eq!(debugger.pc(), 0x0151);
+    }
+
+    #[test]
+    fn test_illegal_opcode_trap() {
+        // NOP; NOP; illegal
+        let mut debugger = make_debugger(&[(0x0150, &[0x00, 0x00, 0xDB])]);
+        debugger
+            .gameboy_mut()
+            .set_illegal_opcode_policy(IllegalOpcodePolicy::Trap);
+
+        assert_eq!(
+            debugger.continue_execution(1000),
+            StopReason::IllegalOpcode(0x0152)
+        );
+        assert!(debugger.gameboy_mut().take_error().is_some());
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        // NOP; NOP; NOP; JR -2
+        let mut debugger = make_debugger(&[(0x0150, &[0x00, 0x00, 0x00, 0x18, 0xFE])]);
+        debugger.add_breakpoint(0x0152);
+
+        assert_eq!(
+            debugger.continue_execution(1000),
+            StopReason::Breakpoint(0x0152)
+        );
+        assert_eq!(debugger.continue_execution(1000), StopReason::CycleLimit);
+        assert!(debugger.remove_breakpoint(0x0152));
+        assert!(!debugger.remove_breakpoint(0x0152));
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        // INC A; JR -3
+        let mut debugger = make_debugger(&[(0x0150, &[0x3C, 0x18, 0xFD])]);
+        debugger.add_conditional_breakpoint(0x0151, Expression::parse("A == 0x05").unwrap());
+
+        assert_eq!(
+            debugger.continue_execution(1000),
+            StopReason::Breakpoint(0x0151)
+        );
+        assert_eq!(
+            debugger.gameboy().cpu().registers.read_16(Register16::AF) >> 8,
+            0x05
+        );
+        assert_eq!(
+            debugger.breakpoint_condition(0x0151).unwrap().to_string(),
+            "A == 0x05"
+        );
+
+        // an unconditional breakpoint replaces the condition
+        assert!(!debugger.add_breakpoint(0x0151));
+        assert_eq!(debugger.breakpoint_condition(0x0151), None);
+        assert_eq!(
+            debugger.continue_execution(1000),
+            StopReason::Breakpoint(0x0151)
+        );
+    }
+
+    #[test]
+    fn test_watch_expressions() {
+        // LD A, $42; LD ($C123), A; JR -2
+        let mut debugger = make_debugger(&[(0x0150, &[0x3E, 0x42, 0xEA, 0x23, 0xC1, 0x18, 0xFE])]);
+        debugger.add_watch_expression(Expression::parse("[$C123]").unwrap());
+        debugger.add_watch_expression(Expression::parse("A + 1").unwrap());
+        debugger.step();
+        debugger.step();
+
+        let values: Vec<i64> = debugger
+            .watch_expressions()
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(values, [0x42, 0x43]);
+        assert!(debugger.remove_watch_expression(0).is_some());
+        assert!(debugger.remove_watch_expression(1).is_none());
+        assert_eq!(debugger.watch_expressions().count(), 1);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        // LD A, $42; LD ($C123), A; JR -2
+        let mut debugger = make_debugger(&[(0x0150, &[0x3E, 0x42, 0xEA, 0x23, 0xC1, 0x18, 0xFE])]);
+        debugger.add_watchpoint(0xC123, WatchKind::Read);
+        assert_eq!(debugger.continue_execution(1000), StopReason::CycleLimit);
+
+        let mut debugger = make_debugger(&[(0x0150, &[0x3E, 0x42, 0xEA, 0x23, 0xC1, 0x18, 0xFE])]);
+        debugger.add_watchpoint(0xC123, WatchKind::Write);
+        assert_eq!(
+            debugger.continue_execution(1000),
+            StopReason::Watchpoint(MemoryAccess::Write {
+                address: 0xC123,
+                value: 0x42
+            })
+        );
+        assert_eq!(debugger.pc(), 0x0155);
+    }
+
+    #[test]
+    fn test_step_over() {
+        let mut debugger = call_program();
+
+        assert_eq!(debugger.step_over(1000), StopReason::Step);
+        assert_eq!(debugger.pc(), 0x0153);
+        assert_eq!(
+            debugger.gameboy().cpu().registers.read_16(Register16::AF) >> 8,
+            0x02
+        );
+    }
+
+    #[test]
+    fn test_step_over_breakpoint_in_call() {
+        let mut debugger = call_program();
+        debugger.add_breakpoint(0x0201);
+
+        assert_eq!(debugger.step_over(1000), StopReason::Breakpoint(0x0201));
+    }
+
+    #[test]
+    fn test_run_to_return() {
+        let mut debugger = call_program();
+        debugger.step();
+        assert_eq!(debugger.pc(), 0x0200);
+
+        assert_eq!(debugger.run_to_return(1000), StopReason::Returned);
+        assert_eq!(debugger.pc(), 0x0153);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut debugger = call_program();
+        for _ in 0..3 {
+            debugger.step();
+        }
+        let history: Vec<u16> = debugger
+            .history(2)
+            .iter()
+            .map(|instruction| instruction.address)
+            .collect();
+        assert_eq!(history, [0x0200, 0x0201]);
+        assert_eq!(debugger.history(8)[0].text, "CALL $0200");
+    }
+}
@@ -0,0 +1,112 @@
+//! OAM DMA, started by writing the source page to 0xFF46.
+//!
+//! Copies 160 bytes from XX00-XX9F to OAM, one byte per machine cycle.
+//! While a transfer is running the CPU can only access HRAM and the IO registers.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub const DMA: u16 = 0xFF46;
+pub const DMA_LENGTH: u8 = 0xA0;
+
+pub struct Dma {
+    source: u16,
+    index: u8,
+    active: bool,
+}
+
+impl Dma {
+    pub fn new() -> Dma {
+        Dma {
+            source: 0,
+            index: 0,
+            active: false,
+        }
+    }
+
+    /// Start a transfer from page `page` (XX00-XX9F), restarting any running transfer
+    pub fn start(&mut self, page: u8) {
+        // pages above WRAM read from echo RAM
+        let page = if page >= 0xE0 { page - 0x20 } else { page };
+        self.source = u16::from(page) << 8;
+        self.index = 0;
+        self.active = true;
+        tracing::debug!(target: "dma", source = %format_args!("{:#06X}", self.source), "OAM DMA started");
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Advance one machine cycle, returns the source address and OAM offset of the byte to copy
+    pub fn next_transfer(&mut self) -> Option<(u16, u8)> {
+        if !self.active {
+            return None;
+        }
+
+        let transfer = (self.source + u16::from(self.index), self.index);
+        self.index += 1;
+        if self.index == DMA_LENGTH {
+            self.active = false;
+        }
+
+        Some(transfer)
+    }
+}
+
+impl SaveState for Dma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.source);
+        state.write_u8(self.index);
+        state.write_bool(self.active);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.source = state.read_u16()?;
+        self.index = state.read_u8()?;
+        self.active = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_sequence() {
+        let mut dma = Dma::new();
+        assert_eq!(dma.next_transfer(), None);
+
+        dma.start(0xC1);
+        assert!(dma.is_active());
+        assert_eq!(dma.next_transfer(), Some((0xC100, 0)));
+        assert_eq!(dma.next_transfer(), Some((0xC101, 1)));
+
+        for _ in 2..DMA_LENGTH {
+            dma.next_transfer();
+        }
+        assert!(!dma.is_active());
+        assert_eq!(dma.next_transfer(), None);
+    }
+
+    #[test]
+    fn test_echo_source() {
+        let mut dma = Dma::new();
+        dma.start(0xE0);
+
+        assert_eq!(dma.next_transfer(), Some((0xC000, 0)));
+    }
+
+    #[test]
+    fn test_restart() {
+        let mut dma = Dma::new();
+        dma.start(0xC0);
+        dma.next_transfer();
+        dma.start(0xD0);
+
+        assert_eq!(dma.next_transfer(), Some((0xD000, 0)));
+    }
+}
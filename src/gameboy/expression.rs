@@ -0,0 +1,400 @@
+//! Expressions over the registers and memory, for conditional breakpoints and watch expressions.
+//!
+//! `A == 0x3E && [0xFF44] > 0x90`: numbers are decimal, or hexadecimal after `0x` or `$`,
+//! registers are named in capitals or not (`A`, `hl`, `SP`, `PC`) and `[address]` reads a byte
+//! without side effects. From the loosest to the tightest binding the operators are `||`, `&&`,
+//! the comparisons `== != < <= > >=`, `|`, `^`, `&`, `+ -` and `!`, parentheses group.
+//! Comparisons and logic give 1 for true and 0 for false, anything but 0 is true.
+
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::utils::ExpressionError;
+
+use super::{GameBoy, MemoryBus, Register16};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    Pair(Register16),
+}
+
+impl Register {
+    fn parse(name: &str) -> Option<Register> {
+        let register = match name.to_ascii_uppercase().as_str() {
+            "A" => Register::A,
+            "F" => Register::F,
+            "B" => Register::B,
+            "C" => Register::C,
+            "D" => Register::D,
+            "E" => Register::E,
+            "H" => Register::H,
+            "L" => Register::L,
+            "AF" => Register::Pair(Register16::AF),
+            "BC" => Register::Pair(Register16::BC),
+            "DE" => Register::Pair(Register16::DE),
+            "HL" => Register::Pair(Register16::HL),
+            "SP" => Register::Pair(Register16::SP),
+            "PC" => Register::Pair(Register16::PC),
+            _ => return None,
+        };
+        Some(register)
+    }
+
+    fn read(self, gameboy: &GameBoy) -> u16 {
+        let registers = &gameboy.cpu().registers;
+        let (pair, high) = match self {
+            Register::A => (Register16::AF, true),
+            Register::F => (Register16::AF, false),
+            Register::B => (Register16::BC, true),
+            Register::C => (Register16::BC, false),
+            Register::D => (Register16::DE, true),
+            Register::E => (Register16::DE, false),
+            Register::H => (Register16::HL, true),
+            Register::L => (Register16::HL, false),
+            Register::Pair(Register16::PC) => return registers.pc,
+            Register::Pair(pair) => return registers.read_16(pair),
+        };
+        let [high_byte, low_byte] = registers.read_16(pair).to_be_bytes();
+        u16::from(if high { high_byte } else { low_byte })
+    }
+}
+
+/// Binary operators, loosest binding first
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Add,
+    Subtract,
+}
+
+impl Operator {
+    const ALL: [(&'static str, Operator); 13] = [
+        // two characters first, so `<=` is not read as `<`
+        ("||", Operator::Or),
+        ("&&", Operator::And),
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LessEqual),
+        (">=", Operator::GreaterEqual),
+        ("<", Operator::Less),
+        (">", Operator::Greater),
+        ("|", Operator::BitOr),
+        ("^", Operator::BitXor),
+        ("&", Operator::BitAnd),
+        ("+", Operator::Add),
+        ("-", Operator::Subtract),
+    ];
+
+    /// Operators on the same level associate to the left
+    fn level(self) -> u8 {
+        match self {
+            Operator::Or => 0,
+            Operator::And => 1,
+            Operator::Equal
+            | Operator::NotEqual
+            | Operator::Less
+            | Operator::LessEqual
+            | Operator::Greater
+            | Operator::GreaterEqual => 2,
+            Operator::BitOr => 3,
+            Operator::BitXor => 4,
+            Operator::BitAnd => 5,
+            Operator::Add | Operator::Subtract => 6,
+        }
+    }
+
+    fn apply(self, left: i64, right: i64) -> i64 {
+        match self {
+            Operator::Or => i64::from(left != 0 || right != 0),
+            Operator::And => i64::from(left != 0 && right != 0),
+            Operator::Equal => i64::from(left == right),
+            Operator::NotEqual => i64::from(left != right),
+            Operator::Less => i64::from(left < right),
+            Operator::LessEqual => i64::from(left <= right),
+            Operator::Greater => i64::from(left > right),
+            Operator::GreaterEqual => i64::from(left >= right),
+            Operator::BitOr => left | right,
+            Operator::BitXor => left ^ right,
+            Operator::BitAnd => left & right,
+            Operator::Add => left.wrapping_add(right),
+            Operator::Subtract => left.wrapping_sub(right),
+        }
+    }
+}
+
+const LEVELS: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Node>),
+    Not(Box<Node>),
+    Binary(Operator, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, gameboy: &GameBoy) -> i64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Register(register) => i64::from(register.read(gameboy)),
+            Node::Memory(address) => {
+                let address = address.evaluate(gameboy) as u16;
+                i64::from(gameboy.memory().peek_byte(address))
+            }
+            Node::Not(value) => i64::from(value.evaluate(gameboy) == 0),
+            Node::Binary(operator, left, right) => {
+                operator.apply(left.evaluate(gameboy), right.evaluate(gameboy))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(Operator),
+    Symbol(char), // ! [ ] ( )
+}
+
+/// The tokens of `text`, with where each starts
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(first) = rest.chars().next() {
+        let position = text.len() - rest.len();
+        let length = if first.is_ascii_alphanumeric() || first == '$' {
+            let length = rest
+                .find(|char: char| !char.is_ascii_alphanumeric() && char != '$')
+                .unwrap_or(rest.len());
+            let word = &rest[..length];
+            let hex = word
+                .strip_prefix("0x")
+                .or_else(|| word.strip_prefix("0X"))
+                .or_else(|| word.strip_prefix('$'));
+            let number = match hex {
+                Some(digits) => Some(i64::from_str_radix(digits, 16)),
+                None if first.is_ascii_digit() => Some(word.parse()),
+                None => None,
+            };
+            tokens.push(match number {
+                Some(Ok(number)) => (Token::Number(number), position),
+                Some(Err(_)) => return Err(ExpressionError::InvalidNumber(word.to_string())),
+                None => (Token::Name(word.to_string()), position),
+            });
+            length
+        } else if let Some((symbol, operator)) = Operator::ALL
+            .iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+        {
+            tokens.push((Token::Operator(*operator), position));
+            symbol.len()
+        } else if "![]()".contains(first) {
+            tokens.push((Token::Symbol(first), position));
+            1
+        } else {
+            return Err(ExpressionError::Unexpected {
+                found: first.to_string(),
+                position,
+            });
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn unexpected(&self) -> ExpressionError {
+        match self.tokens.get(self.next) {
+            Some((token, position)) => ExpressionError::Unexpected {
+                found: match token {
+                    Token::Number(number) => number.to_string(),
+                    Token::Name(name) => name.clone(),
+                    Token::Operator(operator) => Operator::ALL
+                        .iter()
+                        .find(|(_, other)| other == operator)
+                        .map_or_else(String::new, |(symbol, _)| symbol.to_string()),
+                    Token::Symbol(symbol) => symbol.to_string(),
+                },
+                position: *position,
+            },
+            None => ExpressionError::UnexpectedEnd,
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), ExpressionError> {
+        if self.peek() != Some(&Token::Symbol(symbol)) {
+            return Err(self.unexpected());
+        }
+        self.next += 1;
+        Ok(())
+    }
+
+    /// The operators of `level` and tighter
+    fn binary(&mut self, level: u8) -> Result<Node, ExpressionError> {
+        if level == LEVELS {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(&Token::Operator(operator)) = self.peek() {
+            if operator.level() != level {
+                break;
+            }
+            self.next += 1;
+            let right = self.binary(level + 1)?;
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        let node = match self.peek().cloned() {
+            Some(Token::Symbol('!')) => {
+                self.next += 1;
+                return Ok(Node::Not(Box::new(self.unary()?)));
+            }
+            Some(Token::Symbol(open @ ('[' | '('))) => {
+                self.next += 1;
+                let inner = self.binary(0)?;
+                if open == '[' {
+                    self.expect(']')?;
+                    return Ok(Node::Memory(Box::new(inner)));
+                }
+                self.expect(')')?;
+                return Ok(inner);
+            }
+            Some(Token::Number(number)) => Node::Number(number),
+            Some(Token::Name(name)) => match Register::parse(&name) {
+                Some(register) => Node::Register(register),
+                None => return Err(ExpressionError::UnknownRegister(name)),
+            },
+            _ => return Err(self.unexpected()),
+        };
+        self.next += 1;
+        Ok(node)
+    }
+}
+
+/// A parsed expression, printed back as it was written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let root = parser.binary(0)?;
+        if parser.next < parser.tokens.len() {
+            return Err(parser.unexpected());
+        }
+        Ok(Expression {
+            source: text.trim().to_string(),
+            root,
+        })
+    }
+
+    /// The value of the expression for the current state of `gameboy`
+    pub fn evaluate(&self, gameboy: &GameBoy) -> i64 {
+        self.root.evaluate(gameboy)
+    }
+
+    /// Whether the expression is true, not 0, for the current state of `gameboy`
+    pub fn is_true(&self, gameboy: &GameBoy) -> bool {
+        self.evaluate(gameboy) != 0
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(text: &str, gameboy: &GameBoy) -> i64 {
+        Expression::parse(text).unwrap().evaluate(gameboy)
+    }
+
+    #[test]
+    fn test_precedence() {
+        let gameboy = GameBoy::new();
+        assert_eq!(evaluate("1 + 2 == 3", &gameboy), 1);
+        assert_eq!(evaluate("$10 | 1 & 0", &gameboy), 0x10);
+        assert_eq!(evaluate("(0x10 | 1) & 1", &gameboy), 1);
+        assert_eq!(evaluate("5 - 3 - 1", &gameboy), 1);
+        assert_eq!(evaluate("0 || 2 > 1 && !0", &gameboy), 1);
+        assert_eq!(evaluate("3 != 3", &gameboy), 0);
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut gameboy = GameBoy::new();
+        gameboy.memory_mut().write_byte(0xC000, 0x3E);
+        gameboy.cpu_mut().registers.write_16(Register16::HL, 0xC000);
+        assert_eq!(evaluate("A", &gameboy), 0x01);
+        assert_eq!(evaluate("hl", &gameboy), 0xC000);
+        assert_eq!(evaluate("PC", &gameboy), 0x0100);
+        assert_eq!(evaluate("[HL] == 0x3E && [0xC000 + 1] == 0", &gameboy), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            Expression::parse("A =="),
+            Err(ExpressionError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Expression::parse("X > 1"),
+            Err(ExpressionError::UnknownRegister(name)) if name == "X"
+        ));
+        assert!(matches!(
+            Expression::parse("0xZZ"),
+            Err(ExpressionError::InvalidNumber(_))
+        ));
+        assert!(matches!(
+            Expression::parse("[1 2"),
+            Err(ExpressionError::Unexpected { position: 3, .. })
+        ));
+        assert_eq!(Expression::parse(" A == 1 ").unwrap().to_string(), "A == 1");
+    }
+}
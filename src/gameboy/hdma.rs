@@ -0,0 +1,200 @@
+//! CGB VRAM DMA, HDMA1-HDMA5 at 0xFF51-0xFF55.
+//!
+//! HDMA1/HDMA2 hold the source and HDMA3/HDMA4 the VRAM destination, both aligned to 16 bytes.
+//! Writing HDMA5 starts a transfer of (bits 0-6 + 1) blocks of 16 bytes. With bit 7 clear it is
+//! a general purpose transfer copying everything at once, with bit 7 set one block is copied
+//! at the start of every HBlank. Writing HDMA5 with bit 7 clear during an HBlank transfer
+//! cancels it. The CPU is paused for 8 machine cycles per block at normal speed, 16 at double
+//! speed, while the rest of the system keeps running.
+//!
+//! How long HBlank lasts depends on how long the line took to draw, so the PPU tells how many
+//! dots of HBlank are left and a block only starts when it can finish before HBlank ends. A
+//! transfer started during HBlank copies its first block right away if there is time.
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub const HDMA1: u16 = 0xFF51;
+pub const HDMA2: u16 = 0xFF52;
+pub const HDMA3: u16 = 0xFF53;
+pub const HDMA4: u16 = 0xFF54;
+pub const HDMA5: u16 = 0xFF55;
+
+pub const BLOCK_SIZE: u16 = 0x10;
+pub const BLOCK_CYCLES: u32 = 8;
+/// The dots a block takes, the same at both speeds
+pub const BLOCK_DOTS: u32 = 32;
+
+const HBLANK_MODE: u8 = 1 << 7;
+const LENGTH: u8 = 0x7F;
+
+pub struct Hdma {
+    source: u16,
+    destination: u16,    // offset into VRAM
+    remaining: u8,       // blocks left
+    hblank_active: bool, // an HBlank transfer is in progress
+    stall: u32,          // machine cycles the CPU still has to wait
+}
+
+impl Hdma {
+    pub fn new() -> Hdma {
+        Hdma {
+            source: 0,
+            destination: 0,
+            remaining: 0,
+            hblank_active: false,
+            stall: 0,
+        }
+    }
+
+    /// Write one of the HDMA registers
+    ///
+    /// Returns true if a general purpose transfer was started, which has to be run right away
+    pub fn write(&mut self, address: u16, value: u8) -> bool {
+        match address {
+            HDMA1 => self.source = (self.source & 0x00FF) | (u16::from(value) << 8),
+            HDMA2 => self.source = (self.source & 0xFF00) | u16::from(value & 0xF0),
+            HDMA3 => {
+                self.destination = (self.destination & 0x00FF) | (u16::from(value & 0x1F) << 8)
+            }
+            HDMA4 => self.destination = (self.destination & 0xFF00) | u16::from(value & 0xF0),
+            HDMA5 if self.hblank_active && value & HBLANK_MODE == 0 => self.hblank_active = false,
+            HDMA5 => {
+                self.remaining = (value & LENGTH) + 1;
+                self.hblank_active = value & HBLANK_MODE != 0;
+                tracing::debug!(
+                    target: "dma",
+                    source = %format_args!("{:#06X}", self.source),
+                    destination = %format_args!("{:#06X}", 0x8000 | self.destination),
+                    blocks = self.remaining,
+                    hblank = self.hblank_active,
+                    "HDMA started"
+                );
+                return !self.hblank_active;
+            }
+            _ => unreachable!("not an HDMA register"),
+        }
+        false
+    }
+
+    /// HDMA5 reads the blocks left minus one, bit 7 is set when no transfer is running
+    ///
+    /// After a finished transfer this reads 0xFF, after a cancelled one bit 7 is set above the count
+    pub fn read(&self) -> u8 {
+        let length = self.remaining.wrapping_sub(1) & LENGTH;
+        if self.hblank_active {
+            length
+        } else {
+            HBLANK_MODE | length
+        }
+    }
+
+    pub fn is_hblank_active(&self) -> bool {
+        self.hblank_active
+    }
+
+    /// Take the next block to copy, as (source address, VRAM destination address)
+    ///
+    /// The CPU is paused for `cycles` machine cycles for it
+    pub fn next_block(&mut self, cycles: u32) -> Option<(u16, u16)> {
+        if self.remaining == 0 {
+            self.hblank_active = false;
+            return None;
+        }
+
+        let block = (self.source, 0x8000 | self.destination);
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & 0x1FF0;
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            self.hblank_active = false;
+        }
+        self.stall += cycles;
+        Some(block)
+    }
+
+    /// The machine cycles the CPU has to wait for the blocks copied since the last call
+    pub fn take_stall(&mut self) -> u32 {
+        core::mem::take(&mut self.stall)
+    }
+}
+
+impl Default for Hdma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for Hdma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.source);
+        state.write_u16(self.destination);
+        state.write_u8(self.remaining);
+        state.write_bool(self.hblank_active);
+        state.write_u32(self.stall);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.source = state.read_u16()?;
+        self.destination = state.read_u16()? & 0x1FF0;
+        self.remaining = state.read_u8()?;
+        self.hblank_active = state.read_bool()?;
+        self.stall = state.read_u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(hdma: &mut Hdma) {
+        hdma.write(HDMA1, 0xC1);
+        hdma.write(HDMA2, 0x2F);
+        hdma.write(HDMA3, 0xF8);
+        hdma.write(HDMA4, 0x45);
+    }
+
+    #[test]
+    fn test_general_purpose() {
+        let mut hdma = Hdma::new();
+        setup(&mut hdma);
+
+        assert!(hdma.write(HDMA5, 0x01));
+        assert_eq!(hdma.next_block(8), Some((0xC120, 0x9840)));
+        assert_eq!(hdma.next_block(8), Some((0xC130, 0x9850)));
+        assert_eq!(hdma.next_block(8), None);
+        assert_eq!(hdma.take_stall(), 16);
+        assert_eq!(hdma.read(), 0xFF);
+    }
+
+    #[test]
+    fn test_hblank() {
+        let mut hdma = Hdma::new();
+        setup(&mut hdma);
+
+        assert!(!hdma.write(HDMA5, 0x82));
+        assert!(hdma.is_hblank_active());
+        assert_eq!(hdma.read(), 0x02);
+        hdma.next_block(8);
+        assert_eq!(hdma.read(), 0x01);
+        hdma.next_block(8);
+        hdma.next_block(8);
+        assert!(!hdma.is_hblank_active());
+        assert_eq!(hdma.read(), 0xFF);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut hdma = Hdma::new();
+        setup(&mut hdma);
+        hdma.write(HDMA5, 0x82);
+        hdma.next_block(8);
+
+        assert!(!hdma.write(HDMA5, 0x00));
+        assert!(!hdma.is_hblank_active());
+        assert_eq!(hdma.read(), 0x81);
+    }
+}
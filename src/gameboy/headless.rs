@@ -0,0 +1,90 @@
+//! Running a ROM without a frontend, for regression tests in CI.
+//!
+//! The final frame is reduced to a 64 bit FNV-1a hash of its RGBA bytes (with the grayscale
+//! palette), which stays the same across platforms and Rust versions, so expected hashes can be
+//! checked in next to the tests.
+
+use crate::utils::{fnv1a, CartridgeError};
+
+use super::{CaptureTransport, Frame, GameBoy};
+
+/// What a headless run left behind
+pub struct HeadlessRun {
+    /// The last frame rendered
+    pub frame: Frame,
+    pub frame_hash: u64,
+    /// Everything the ROM sent over the serial port
+    pub serial: Vec<u8>,
+}
+
+/// Run `rom` for `frames` frames and capture the final frame and the serial output
+pub fn run_headless(rom: Vec<u8>, frames: u32) -> Result<HeadlessRun, CartridgeError> {
+    let mut gameboy = GameBoy::new();
+    gameboy.load_rom(rom)?;
+    let capture = CaptureTransport::new();
+    gameboy.set_serial_transport(Box::new(capture.clone()));
+
+    for _ in 0..frames {
+        gameboy.run_until_vblank();
+    }
+
+    let frame = gameboy.frame();
+    Ok(HeadlessRun {
+        frame_hash: hash_frame(&frame),
+        frame,
+        serial: capture.sent(),
+    })
+}
+
+/// FNV-1a over the frame's RGBA bytes
+pub fn hash_frame(frame: &Frame) -> u64 {
+    fnv1a(frame.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::{test_rom, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    use super::*;
+
+    /// Sends "OK" over the serial port, then loops forever
+    fn make_rom() -> Vec<u8> {
+        #[rustfmt::skip]
+        let program = [
+            0x3E, b'O', 0xE0, 0x01, // LD A, 'O'; LDH (SB), A
+            0x3E, 0x81, 0xE0, 0x02, // LD A, 0x81; LDH (SC), A
+            0xF0, 0x02, 0x87, 0x38, 0xFB, // LDH A, (SC); ADD A; JR C, -5
+            0x3E, b'K', 0xE0, 0x01, // LD A, 'K'; LDH (SB), A
+            0x3E, 0x81, 0xE0, 0x02, // LD A, 0x81; LDH (SC), A
+            0x18, 0xFE, // JR -2
+        ];
+        test_rom(&program)
+    }
+
+    #[test]
+    fn test_run_headless() {
+        let run = run_headless(make_rom(), 2).unwrap();
+
+        assert_eq!(run.serial, b"OK");
+        assert_eq!(run.frame_hash, hash_frame(&run.frame));
+        assert_eq!(
+            run.frame_hash,
+            run_headless(make_rom(), 2).unwrap().frame_hash
+        );
+    }
+
+    #[test]
+    fn test_hash_frame() {
+        let blank = Frame::from_shades(&[0; SCREEN_WIDTH * SCREEN_HEIGHT], &Default::default());
+        let mut shades = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        shades[100] = 3;
+        let dot = Frame::from_shades(&shades, &Default::default());
+
+        assert_ne!(hash_frame(&blank), hash_frame(&dot));
+    }
+
+    #[test]
+    fn test_invalid_rom() {
+        assert!(run_headless(vec![], 1).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Counts of the CPU's reads and writes at every address, to see which regions a game touches.
+//!
+//! Reads include instruction fetches, so code shows up as read. The counts export as CSV, one
+//! row per address touched, or as a 256x256 image with a row per 256 byte page: reads in green,
+//! writes in red, brighter on a logarithmic scale.
+
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::Write as _;
+
+use super::Frame;
+
+const ADDRESSES: usize = 0x10000;
+const PAGE_SIZE: usize = 0x100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    reads: Vec<u32>,
+    writes: Vec<u32>,
+}
+
+impl Heatmap {
+    pub fn new() -> Heatmap {
+        Heatmap {
+            reads: vec![0; ADDRESSES],
+            writes: vec![0; ADDRESSES],
+        }
+    }
+
+    pub fn record_read(&mut self, address: u16) {
+        let count = &mut self.reads[usize::from(address)];
+        *count = count.saturating_add(1);
+    }
+
+    pub fn record_write(&mut self, address: u16) {
+        let count = &mut self.writes[usize::from(address)];
+        *count = count.saturating_add(1);
+    }
+
+    pub fn reads(&self, address: u16) -> u32 {
+        self.reads[usize::from(address)]
+    }
+
+    pub fn writes(&self, address: u16) -> u32 {
+        self.writes[usize::from(address)]
+    }
+
+    /// The reads and writes in the 256 byte page starting at `page << 8`
+    pub fn page(&self, page: u8) -> (u64, u64) {
+        let range = usize::from(page) * PAGE_SIZE..(usize::from(page) + 1) * PAGE_SIZE;
+        let sum = |counts: &[u32]| counts.iter().map(|&count| u64::from(count)).sum();
+        (sum(&self.reads[range.clone()]), sum(&self.writes[range]))
+    }
+
+    pub fn clear(&mut self) {
+        self.reads.fill(0);
+        self.writes.fill(0);
+    }
+
+    /// `address,reads,writes` with a row for every address touched
+    pub fn csv(&self) -> String {
+        let mut csv = String::from("address,reads,writes\n");
+        for (address, (reads, writes)) in self.reads.iter().zip(&self.writes).enumerate() {
+            if reads | writes != 0 {
+                let _ = writeln!(csv, "{address:04X},{reads},{writes}");
+            }
+        }
+        csv
+    }
+
+    /// The counts as an image, the pixel at `x`, `y` is the address `y << 8 | x`
+    pub fn image(&self) -> Frame {
+        let max_reads = self.reads.iter().copied().max().unwrap_or(0);
+        let max_writes = self.writes.iter().copied().max().unwrap_or(0);
+        let mut image = Frame::blank(PAGE_SIZE, ADDRESSES / PAGE_SIZE);
+        for address in 0..ADDRESSES {
+            let color = [
+                brightness(self.writes[address], max_writes),
+                brightness(self.reads[address], max_reads),
+                0x00,
+                0xFF,
+            ];
+            image.set_pixel(address % PAGE_SIZE, address / PAGE_SIZE, color);
+        }
+        image
+    }
+
+    /// A summary of the busiest pages, as `C0xx: 1234 reads, 56 writes`
+    pub fn busiest_pages(&self, count: usize) -> Vec<String> {
+        let mut pages: Vec<(u8, u64, u64)> = (0..=u8::MAX)
+            .map(|page| {
+                let (reads, writes) = self.page(page);
+                (page, reads, writes)
+            })
+            .filter(|&(_, reads, writes)| reads + writes > 0)
+            .collect();
+        pages.sort_by_key(|&(page, reads, writes)| (core::cmp::Reverse(reads + writes), page));
+        pages
+            .into_iter()
+            .take(count)
+            .map(|(page, reads, writes)| format!("{page:02X}xx: {reads} reads, {writes} writes"))
+            .collect()
+    }
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap::new()
+    }
+}
+
+/// 0 for no accesses, up to 255 for the most, by the bits needed to count them
+fn brightness(count: u32, max: u32) -> u8 {
+    if count == 0 {
+        return 0;
+    }
+    let bits = |count: u32| count.ilog2() + 1;
+    // the least touched addresses still show
+    (0x30 + (0xFF - 0x30) * bits(count) / bits(max)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap() {
+        let mut heatmap = Heatmap::new();
+        for _ in 0..3 {
+            heatmap.record_read(0x0150);
+        }
+        heatmap.record_write(0x2000);
+        heatmap.record_write(0xC001);
+        heatmap.record_read(0xC001);
+
+        assert_eq!(heatmap.reads(0x0150), 3);
+        assert_eq!(heatmap.page(0xC0), (1, 1));
+        assert_eq!(
+            heatmap.csv(),
+            "address,reads,writes\n0150,3,0\n2000,0,1\nC001,1,1\n"
+        );
+        assert_eq!(
+            heatmap.busiest_pages(2),
+            ["01xx: 3 reads, 0 writes", "C0xx: 1 reads, 1 writes"]
+        );
+
+        let image = heatmap.image();
+        assert_eq!(image.pixel(0x50, 0x01), [0x00, 0xFF, 0x00, 0xFF]);
+        assert_eq!(image.pixel(0x00, 0x20), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(image.pixel(0x00, 0x00), [0x00, 0x00, 0x00, 0xFF]);
+
+        heatmap.clear();
+        assert_eq!(heatmap.csv(), "address,reads,writes\n");
+    }
+}
@@ -0,0 +1,203 @@
+//! The CGB infrared port, RP at 0xFF56.
+//!
+//! Bit 0 turns the LED on. With both bits 6 and 7 set reading is enabled and bit 1 reads 0 while
+//! light reaches the receiver, 1 otherwise. What the port faces is an `IrTransport`: nothing by
+//! default, so no light ever arrives, `IrLoopback` for the port's own LED, as if in front of a
+//! mirror, or `LocalIr::pair` for two Game Boys in the same process facing each other.
+//! RP only exists in CGB mode, on a DMG it reads 0xFF.
+
+use alloc::boxed::Box;
+use core::ops::RangeInclusive;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+pub const RP: u16 = 0xFF56;
+
+const RP_LED: u8 = 1 << 0;
+const RP_NO_LIGHT: u8 = 1 << 1;
+const RP_READ_ENABLE: u8 = 0b1100_0000;
+
+/// What the infrared port points at
+pub trait IrTransport: Send {
+    /// The port's LED was turned on or off
+    fn set_led(&mut self, on: bool);
+
+    /// Whether light reaches the port's receiver
+    fn light(&self) -> bool;
+}
+
+/// Nothing in front of the port, no light is ever received
+pub struct NoLight;
+
+impl IrTransport for NoLight {
+    fn set_led(&mut self, _on: bool) {}
+
+    fn light(&self) -> bool {
+        false
+    }
+}
+
+/// The port receives its own LED
+#[derive(Default)]
+pub struct IrLoopback {
+    led: bool,
+}
+
+impl IrLoopback {
+    pub fn new() -> IrLoopback {
+        IrLoopback::default()
+    }
+}
+
+impl IrTransport for IrLoopback {
+    fn set_led(&mut self, on: bool) {
+        self.led = on;
+    }
+
+    fn light(&self) -> bool {
+        self.led
+    }
+}
+
+/// One of two infrared ports facing each other in the same process, see `LocalIr::pair`
+#[cfg(feature = "std")]
+pub struct LocalIr {
+    side: usize,
+    leds: Arc<Mutex<[bool; 2]>>,
+}
+
+#[cfg(feature = "std")]
+impl LocalIr {
+    /// Both ports, each receiving the other's LED
+    pub fn pair() -> (LocalIr, LocalIr) {
+        let leds = Arc::new(Mutex::new([false; 2]));
+        let side = |side| LocalIr {
+            side,
+            leds: leds.clone(),
+        };
+        (side(0), side(1))
+    }
+}
+
+#[cfg(feature = "std")]
+impl IrTransport for LocalIr {
+    fn set_led(&mut self, on: bool) {
+        self.leds.lock().unwrap()[self.side] = on;
+    }
+
+    fn light(&self) -> bool {
+        self.leds.lock().unwrap()[1 - self.side]
+    }
+}
+
+pub struct Infrared {
+    rp: u8,
+    transport: Box<dyn IrTransport>,
+}
+
+impl Infrared {
+    pub fn new() -> Infrared {
+        Infrared {
+            rp: 0,
+            transport: Box::new(NoLight),
+        }
+    }
+
+    /// Point the port at `transport`, which sees the LED as it is now
+    pub fn set_transport(&mut self, transport: Box<dyn IrTransport>) {
+        self.transport = transport;
+        self.transport.set_led(self.rp & RP_LED != 0);
+    }
+}
+
+impl Default for Infrared {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for Infrared {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[RP..=RP]
+    }
+
+    fn read(&self, _address: u16) -> u8 {
+        let receiving = self.rp & RP_READ_ENABLE == RP_READ_ENABLE && self.transport.light();
+        if receiving {
+            self.rp
+        } else {
+            self.rp | RP_NO_LIGHT
+        }
+    }
+
+    fn write(&mut self, _address: u16, value: u8) {
+        let led = value & RP_LED != 0;
+        if led != (self.rp & RP_LED != 0) {
+            self.transport.set_led(led);
+        }
+        self.rp = value & (RP_READ_ENABLE | RP_LED);
+    }
+}
+
+impl SaveState for Infrared {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.rp);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.rp = state.read_u8()?;
+        self.transport.set_led(self.rp & RP_LED != 0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_light() {
+        let mut infrared = Infrared::new();
+        infrared.write(RP, 0xC1);
+        assert_eq!(infrared.read(RP), 0xC3);
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut infrared = Infrared::new();
+        infrared.set_transport(Box::new(IrLoopback::new()));
+        infrared.write(RP, 0xC1);
+        assert_eq!(infrared.read(RP), 0xC1);
+        infrared.write(RP, 0xC0);
+        assert_eq!(infrared.read(RP), 0xC2);
+
+        // without the read enable bits nothing is received
+        infrared.write(RP, 0x01);
+        assert_eq!(infrared.read(RP), 0x03);
+    }
+
+    #[test]
+    fn test_local_pair() {
+        let (first, second) = LocalIr::pair();
+        let mut sender = Infrared::new();
+        sender.set_transport(Box::new(first));
+        let mut receiver = Infrared::new();
+        receiver.set_transport(Box::new(second));
+
+        receiver.write(RP, 0xC0);
+        assert_eq!(receiver.read(RP), 0xC2);
+        sender.write(RP, 0x01);
+        assert_eq!(receiver.read(RP), 0xC0);
+        // the sender does not see its own LED
+        sender.write(RP, 0xC1);
+        assert_eq!(sender.read(RP), 0xC3);
+    }
+}
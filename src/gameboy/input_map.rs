@@ -0,0 +1,268 @@
+//! Translates the frontend's inputs, keys and gamepad buttons, to joypad buttons.
+//!
+//! Inputs go by name, the frontend's name of the key (SDL's in the desktop frontend) or of the
+//! gamepad button (gilrs'), so the map does not depend on a frontend and is stored as is in a
+//! game config. Several inputs can press the same button, a button is held while any of them is.
+//!
+//! Turbo inputs fire their button at the frame level: while one is held the button is pressed for
+//! `TURBO_PERIOD / 2` frames and released for as many, counted by `InputMap::end_frame`.
+//!
+//! The frontend notes inputs going down and up as they come and hands the buttons to the joypad
+//! with `InputMap::apply` before each frame, or takes them with `InputMap::pressed_buttons` to
+//! send them elsewhere, like to a netplay session.
+
+use alloc::{string::String, vec::Vec};
+
+use super::{Button, GameBoy};
+
+/// Frames per press and release of a turbo input, 15 presses a second
+pub const TURBO_PERIOD: u32 = 4;
+
+const DEFAULT_KEYS: [(&str, Button); 8] = [
+    ("Right", Button::Right),
+    ("Left", Button::Left),
+    ("Up", Button::Up),
+    ("Down", Button::Down),
+    ("X", Button::A),
+    ("Z", Button::B),
+    ("Backspace", Button::Select),
+    ("Return", Button::Start),
+];
+
+/// A Nintendo layout, A on the right face button and B on the bottom one
+const DEFAULT_PAD: [(&str, Button); 8] = [
+    ("DPadRight", Button::Right),
+    ("DPadLeft", Button::Left),
+    ("DPadUp", Button::Up),
+    ("DPadDown", Button::Down),
+    ("East", Button::A),
+    ("South", Button::B),
+    ("Select", Button::Select),
+    ("Start", Button::Start),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Key(String),
+    Pad(String),
+}
+
+impl Input {
+    fn same_kind(&self, other: &Input) -> bool {
+        matches!(
+            (self, other),
+            (Input::Key(_), Input::Key(_)) | (Input::Pad(_), Input::Pad(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub input: Input,
+    pub button: Button,
+    pub turbo: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputMap {
+    bindings: Vec<Binding>,
+    held: Vec<Input>,
+    applied: u8, // the buttons `apply` pressed on the joypad
+    frame: u32,
+}
+
+impl InputMap {
+    /// A map without any bindings
+    pub fn new() -> InputMap {
+        InputMap {
+            bindings: Vec::new(),
+            held: Vec::new(),
+            applied: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// The inputs bound to `button`
+    pub fn inputs(&self, button: Button, turbo: bool) -> impl Iterator<Item = &Input> {
+        self.bindings
+            .iter()
+            .filter(move |binding| binding.button == button && binding.turbo == turbo)
+            .map(|binding| &binding.input)
+    }
+
+    /// Bind `input` to `button`, besides the button's other inputs
+    ///
+    /// An input only presses one button, a binding it had before is replaced.
+    pub fn bind(&mut self, input: Input, button: Button, turbo: bool) {
+        self.unbind(&input);
+        self.bindings.push(Binding {
+            input,
+            button,
+            turbo,
+        });
+    }
+
+    /// Bind `input` to `button` in place of the button's inputs of the same kind, so a key
+    /// replaces the button's keys and a gamepad button its gamepad buttons
+    pub fn rebind(&mut self, input: Input, button: Button, turbo: bool) {
+        self.bindings.retain(|binding| {
+            binding.button != button || binding.turbo != turbo || !binding.input.same_kind(&input)
+        });
+        self.bind(input, button, turbo);
+    }
+
+    pub fn unbind(&mut self, input: &Input) {
+        self.bindings.retain(|binding| binding.input != *input);
+    }
+
+    /// Note `input` went down
+    pub fn input_down(&mut self, input: Input) {
+        if !self.held.contains(&input) {
+            self.held.push(input);
+        }
+    }
+
+    /// Note `input` went up, its button stays down while another input holds it
+    pub fn input_up(&mut self, input: &Input) {
+        self.held.retain(|held| held != input);
+    }
+
+    /// Advance the turbo inputs a frame, call it once after every frame
+    pub fn end_frame(&mut self) {
+        self.frame = (self.frame + 1) % TURBO_PERIOD;
+    }
+
+    /// The buttons held down by the inputs, with the bit of each button in `Button::ALL` order
+    /// like `GameBoy::pressed_buttons`
+    pub fn pressed_buttons(&self) -> u8 {
+        let turbo_on = self.frame < TURBO_PERIOD / 2;
+        self.bindings
+            .iter()
+            .filter(|binding| self.held.contains(&binding.input) && (!binding.turbo || turbo_on))
+            .fold(0, |pressed, binding| pressed | Self::bit(binding.button))
+    }
+
+    /// Whether the inputs hold `button` down
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed_buttons() & Self::bit(button) != 0
+    }
+
+    fn bit(button: Button) -> u8 {
+        let index = Button::ALL.iter().position(|other| *other == button);
+        1 << index.unwrap_or_default()
+    }
+
+    /// Press and release the buttons whose inputs changed since the last call, leaving the
+    /// buttons pressed by others alone
+    pub fn apply(&mut self, gameboy: &mut GameBoy) {
+        let pressed = self.pressed_buttons();
+        for button in Button::ALL {
+            let bit = Self::bit(button);
+            if pressed & bit != 0 && self.applied & bit == 0 {
+                gameboy.press_button(button);
+            } else if pressed & bit == 0 && self.applied & bit != 0 {
+                gameboy.release_button(button);
+            }
+        }
+        self.applied = pressed;
+    }
+}
+
+impl Default for InputMap {
+    /// Arrow keys, X for A, Z for B, Enter for Start and Backspace for Select, and the same on a
+    /// gamepad's D-pad, face buttons, Start and Select
+    fn default() -> InputMap {
+        let mut map = InputMap::new();
+        for (key, button) in DEFAULT_KEYS {
+            map.bind(Input::Key(key.into()), button, false);
+        }
+        for (pad, button) in DEFAULT_PAD {
+            map.bind(Input::Pad(pad.into()), button, false);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> Input {
+        Input::Key(name.into())
+    }
+
+    #[test]
+    fn test_inputs_share_a_button() {
+        let mut map = InputMap::default();
+        map.input_down(key("X"));
+        map.input_down(Input::Pad("East".into()));
+        map.input_up(&key("X"));
+        assert!(map.is_pressed(Button::A));
+        map.input_up(&Input::Pad("East".into()));
+        assert!(!map.is_pressed(Button::A));
+
+        map.input_down(key("F1"));
+        assert_eq!(map.pressed_buttons(), 0);
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut map = InputMap::default();
+        map.rebind(key("Left Shift"), Button::A, false);
+        assert_eq!(map.inputs(Button::A, false).count(), 2);
+        assert!(map
+            .inputs(Button::A, false)
+            .any(|input| *input == key("Left Shift")));
+        assert!(!map.inputs(Button::A, false).any(|input| *input == key("X")));
+
+        // an input presses one button only
+        map.bind(key("Left Shift"), Button::B, false);
+        assert_eq!(map.inputs(Button::A, false).count(), 1);
+        assert_eq!(map.inputs(Button::B, false).count(), 3);
+    }
+
+    #[test]
+    fn test_turbo() {
+        let mut map = InputMap::default();
+        map.bind(key("S"), Button::A, true);
+        map.input_down(key("S"));
+
+        let mut presses = Vec::new();
+        for _ in 0..TURBO_PERIOD * 2 {
+            presses.push(map.is_pressed(Button::A));
+            map.end_frame();
+        }
+        assert_eq!(
+            presses,
+            [true, true, false, false, true, true, false, false]
+        );
+
+        // the plain binding holds the button through the turbo's pauses
+        map.input_down(key("X"));
+        map.end_frame();
+        map.end_frame();
+        assert!(map.is_pressed(Button::A));
+        map.input_up(&key("S"));
+        map.input_up(&key("X"));
+        assert!(!map.is_pressed(Button::A));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut gameboy = GameBoy::new();
+        let mut map = InputMap::default();
+        map.input_down(key("X"));
+        map.apply(&mut gameboy);
+        assert_eq!(gameboy.pressed_buttons(), 1 << 4);
+
+        // buttons pressed elsewhere are kept
+        gameboy.press_button(Button::Start);
+        map.input_up(&key("X"));
+        map.apply(&mut gameboy);
+        assert_eq!(gameboy.pressed_buttons(), 1 << 7);
+    }
+}
@@ -0,0 +1,170 @@
+//! The interrupt controller, interrupts are requested through the IF register (0xFF0F)
+//! and enabled through the IE register (0xFFFF).
+
+use core::ops::RangeInclusive;
+
+use crate::utils::SaveStateError;
+
+use super::{
+    memory::IoDevice,
+    save_state::{SaveState, StateReader, StateWriter},
+    MemoryBus,
+};
+
+pub const IF: u16 = 0xFF0F;
+pub const IE: u16 = 0xFFFF;
+
+/// Interrupt sources, in priority order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// bit of the interrupt in IE and IF
+    pub fn bit(self) -> u8 {
+        match self {
+            Interrupt::VBlank => 1 << 0,
+            Interrupt::Stat => 1 << 1,
+            Interrupt::Timer => 1 << 2,
+            Interrupt::Serial => 1 << 3,
+            Interrupt::Joypad => 1 << 4,
+        }
+    }
+
+    /// address the CPU jumps to when servicing the interrupt
+    pub fn vector(self) -> u16 {
+        match self {
+            Interrupt::VBlank => 0x40,
+            Interrupt::Stat => 0x48,
+            Interrupt::Timer => 0x50,
+            Interrupt::Serial => 0x58,
+            Interrupt::Joypad => 0x60,
+        }
+    }
+}
+
+/// Holds IF and IE
+pub struct InterruptController {
+    flags: u8,
+    enable: u8,
+}
+
+impl InterruptController {
+    pub fn new() -> InterruptController {
+        InterruptController {
+            flags: 0,
+            enable: 0,
+        }
+    }
+
+    /// Set the interrupt's bit in IF
+    pub fn request(&mut self, interrupt: Interrupt) {
+        tracing::trace!(target: "interrupts", ?interrupt, "requested");
+        self.flags |= interrupt.bit();
+    }
+}
+
+impl IoDevice for InterruptController {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[IF..=IF, IE..=IE]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            IF => self.flags,
+            _ => self.enable,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            IF => self.flags = value,
+            _ => self.enable = value,
+        }
+    }
+}
+
+impl SaveState for InterruptController {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.flags);
+        state.write_u8(self.enable);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.flags = state.read_u8()?;
+        self.enable = state.read_u8()?;
+        Ok(())
+    }
+}
+
+/// Request an interrupt by setting its bit in IF
+pub fn request_interrupt(memory: &mut impl MemoryBus, interrupt: Interrupt) {
+    tracing::trace!(target: "interrupts", ?interrupt, "requested");
+    let flags = memory.peek_byte(IF);
+    memory.poke_byte(IF, flags | interrupt.bit());
+}
+
+/// Acknowledge an interrupt by clearing its bit in IF
+pub fn clear_interrupt(memory: &mut impl MemoryBus, interrupt: Interrupt) {
+    let flags = memory.peek_byte(IF);
+    memory.poke_byte(IF, flags & !interrupt.bit());
+}
+
+/// The highest priority interrupt that is both requested and enabled
+pub fn pending_interrupt(memory: &impl MemoryBus) -> Option<Interrupt> {
+    let pending = memory.peek_byte(IF) & memory.peek_byte(IE);
+
+    Interrupt::ALL
+        .into_iter()
+        .find(|interrupt| pending & interrupt.bit() != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::Memory;
+
+    #[test]
+    fn test_request_clear() {
+        let mut memory = Memory::new();
+        request_interrupt(&mut memory, Interrupt::Timer);
+        request_interrupt(&mut memory, Interrupt::Joypad);
+        assert_eq!(memory.read_byte(IF), 0b1111_0100);
+
+        clear_interrupt(&mut memory, Interrupt::Timer);
+        assert_eq!(memory.read_byte(IF), 0b1111_0000);
+    }
+
+    #[test]
+    fn test_pending_requires_enable() {
+        let mut memory = Memory::new();
+        request_interrupt(&mut memory, Interrupt::Stat);
+        assert_eq!(pending_interrupt(&memory), None);
+
+        memory.write_byte(IE, Interrupt::Stat.bit());
+        assert_eq!(pending_interrupt(&memory), Some(Interrupt::Stat));
+    }
+
+    #[test]
+    fn test_pending_priority() {
+        let mut memory = Memory::new();
+        memory.write_byte(IE, 0x1F);
+        request_interrupt(&mut memory, Interrupt::Serial);
+        request_interrupt(&mut memory, Interrupt::VBlank);
+
+        assert_eq!(pending_interrupt(&memory), Some(Interrupt::VBlank));
+    }
+}
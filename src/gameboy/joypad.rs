@@ -0,0 +1,260 @@
+//! The joypad, read through P1 at 0xFF00.
+//!
+//! The eight buttons are wired as two groups of four lines, bits 4 and 5 of P1 select
+//! which group is visible in the lower nibble. Lines are active low, a pressed button reads 0.
+//! On a Super Game Boy the same lines carry command packets, see `SgbPort`.
+
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+        sgb::SgbPort,
+    },
+    utils::SaveStateError,
+};
+
+pub const P1: u16 = 0xFF00;
+
+const SELECT_DIRECTIONS: u8 = 1 << 4;
+const SELECT_ACTIONS: u8 = 1 << 5;
+const SELECT_MASK: u8 = SELECT_DIRECTIONS | SELECT_ACTIONS;
+const LINES_MASK: u8 = 0x0F;
+const UNUSED: u8 = 0b1100_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub const ALL: [Button; 8] = [
+        Button::Right,
+        Button::Left,
+        Button::Up,
+        Button::Down,
+        Button::A,
+        Button::B,
+        Button::Select,
+        Button::Start,
+    ];
+
+    /// The lowercase name used by scripts, the web bindings and game configs
+    pub fn name(self) -> &'static str {
+        match self {
+            Button::Right => "right",
+            Button::Left => "left",
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::A => "a",
+            Button::B => "b",
+            Button::Select => "select",
+            Button::Start => "start",
+        }
+    }
+
+    /// The button called `name`, ignoring case
+    pub fn from_name(name: &str) -> Option<Button> {
+        Button::ALL
+            .into_iter()
+            .find(|button| button.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The bit in the pressed state, directions in the low nibble and actions in the high nibble
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+pub struct Joypad {
+    pressed: u8,
+    select: u8,
+    sgb: Option<SgbPort>,
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad {
+            pressed: 0,
+            select: SELECT_MASK,
+            sgb: None,
+        }
+    }
+
+    /// Listen for Super Game Boy commands
+    pub fn set_sgb(&mut self, enabled: bool) {
+        self.sgb = enabled.then(SgbPort::new);
+    }
+
+    /// The Super Game Boy commands received since the last call
+    pub fn take_sgb_commands(&mut self) -> Vec<Vec<u8>> {
+        self.sgb
+            .as_mut()
+            .map(SgbPort::take_commands)
+            .unwrap_or_default()
+    }
+
+    /// Press a button
+    ///
+    /// Returns true if a selected line went from high to low and the joypad interrupt should be requested
+    pub fn press(&mut self, button: Button) -> bool {
+        let before = self.lines();
+        self.pressed |= button.bit();
+        before & !self.lines() != 0
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.pressed &= !button.bit();
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & button.bit() != 0
+    }
+
+    /// The active low state of the four lines for the selected groups
+    fn lines(&self) -> u8 {
+        let mut pressed = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.pressed & LINES_MASK;
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            pressed |= self.pressed >> 4;
+        }
+        !pressed & LINES_MASK
+    }
+}
+
+impl IoDevice for Joypad {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[P1..=P1]
+    }
+
+    fn read(&self, _address: u16) -> u8 {
+        let lines = match &self.sgb {
+            // with both groups deselected the lower nibble is the joypad read next
+            Some(port) if port.players() > 1 && self.select == SELECT_MASK => {
+                LINES_MASK - port.player()
+            }
+            // the other joypads are not connected
+            Some(port) if port.player() != 0 => LINES_MASK,
+            _ => self.lines(),
+        };
+        UNUSED | self.select | lines
+    }
+
+    fn write(&mut self, _address: u16, value: u8) {
+        self.select = value & SELECT_MASK;
+        if let Some(port) = &mut self.sgb {
+            port.write(self.select);
+        }
+    }
+}
+
+impl SaveState for Joypad {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.pressed);
+        state.write_u8(self.select);
+        state.write_bool(self.sgb.is_some());
+        if let Some(port) = &self.sgb {
+            port.save_state(state);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.pressed = state.read_u8()?;
+        self.select = state.read_u8()?;
+        self.set_sgb(state.read_bool()?);
+        match &mut self.sgb {
+            Some(port) => port.load_state(state),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nothing_selected() {
+        let mut joypad = Joypad::new();
+        joypad.press(Button::A);
+        joypad.press(Button::Down);
+
+        assert_eq!(joypad.read(P1), 0xFF);
+    }
+
+    #[test]
+    fn test_select_directions() {
+        let mut joypad = Joypad::new();
+        joypad.write(P1, SELECT_ACTIONS);
+        joypad.press(Button::Down);
+        joypad.press(Button::A);
+
+        assert_eq!(joypad.read(P1), 0xE7);
+    }
+
+    #[test]
+    fn test_select_actions() {
+        let mut joypad = Joypad::new();
+        joypad.write(P1, SELECT_DIRECTIONS);
+        joypad.press(Button::Start);
+        joypad.press(Button::Left);
+
+        assert_eq!(joypad.read(P1), 0xD7);
+    }
+
+    #[test]
+    fn test_release() {
+        let mut joypad = Joypad::new();
+        joypad.write(P1, 0x00);
+        joypad.press(Button::B);
+        assert!(joypad.is_pressed(Button::B));
+
+        joypad.release(Button::B);
+        assert!(!joypad.is_pressed(Button::B));
+        assert_eq!(joypad.read(P1), 0xCF);
+    }
+
+    #[test]
+    fn test_sgb_joypads() {
+        let mut joypad = Joypad::new();
+        joypad.set_sgb(true);
+        joypad.press(Button::A);
+        // MLT_REQ for two joypads
+        for select in crate::gameboy::sgb::packet_writes(&[0x89, 0x01]) {
+            joypad.write(P1, select);
+        }
+        assert_eq!(joypad.take_sgb_commands().len(), 1);
+        assert_eq!(joypad.read(P1) & LINES_MASK, 0x0F);
+
+        joypad.write(P1, SELECT_DIRECTIONS);
+        assert_eq!(joypad.read(P1) & LINES_MASK, 0x0E);
+        joypad.write(P1, SELECT_MASK);
+        assert_eq!(joypad.read(P1) & LINES_MASK, 0x0E);
+        joypad.write(P1, SELECT_DIRECTIONS);
+        assert_eq!(joypad.read(P1) & LINES_MASK, 0x0F);
+        joypad.write(P1, SELECT_MASK);
+        assert_eq!(joypad.read(P1) & LINES_MASK, 0x0F);
+    }
+
+    #[test]
+    fn test_press_interrupt() {
+        let mut joypad = Joypad::new();
+        assert!(!joypad.press(Button::A));
+
+        joypad.write(P1, SELECT_DIRECTIONS);
+        assert!(joypad.press(Button::Select));
+        assert!(!joypad.press(Button::Select));
+        assert!(!joypad.press(Button::Up));
+    }
+}
@@ -0,0 +1,467 @@
+//! A data directory holding what the frontend remembers about each game: its settings, its
+//! battery save and its save states, plus the list of recently played games.
+//!
+//! Games are told apart by a hash of their ROM, so renaming or moving a ROM keeps its data:
+//!
+//! ```text
+//! data/
+//!     recent.txt                  hash, title and path of the last games played, newest first
+//!     0123456789abcdef/
+//!         game.cfg                per-game settings, see `GameConfig`
+//!         game.sav                battery-backed RAM
+//!         state-0.state           save state slots
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::utils::{fnv1a, CartridgeError, CheatError, LibraryError};
+
+use super::{
+    read_rom_file, Binding, Button, Cartridge, DmgPalette, GameBoy, Header, Input, InputMap,
+};
+
+const RECENT_FILE: &str = "recent.txt";
+const CONFIG_FILE: &str = "game.cfg";
+const SAVE_FILE: &str = "game.sav";
+
+/// How many games `Library::recent` remembers
+pub const MAX_RECENT: usize = 10;
+
+/// The hash games are stored under
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    fnv1a(rom)
+}
+
+/// A game in the recently played list
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecentGame {
+    pub hash: u64,
+    pub title: String,
+    /// Where the ROM was last opened from
+    pub path: PathBuf,
+}
+
+pub struct Library {
+    dir: PathBuf,
+    recent: Vec<RecentGame>,
+}
+
+impl Library {
+    /// Open the library in `dir`, creating the directory if needed
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Library, LibraryError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let recent = match fs::read_to_string(dir.join(RECENT_FILE)) {
+            Ok(text) => text.lines().filter_map(parse_recent).collect(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Library { dir, recent })
+    }
+
+    /// The games played last, newest first
+    pub fn recent(&self) -> &[RecentGame] {
+        &self.recent
+    }
+
+    /// Read the ROM at `path` (which may be zipped or gzipped) with its settings and move it to
+    /// the front of the recently played list
+    pub fn open_game(&mut self, path: impl AsRef<Path>) -> Result<Game, LibraryError> {
+        let path = path.as_ref();
+        let rom = read_rom_file(path)?;
+        let hash = rom_hash(&rom);
+        let title = Header::parse(&rom)?.title;
+        let dir = self.dir.join(format!("{hash:016x}"));
+        fs::create_dir_all(&dir)?;
+        let config = match fs::read_to_string(dir.join(CONFIG_FILE)) {
+            Ok(text) => GameConfig::parse(&text)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => GameConfig::default(),
+            Err(error) => return Err(error.into()),
+        };
+
+        self.recent.retain(|game| game.hash != hash);
+        self.recent.insert(
+            0,
+            RecentGame {
+                hash,
+                title: title.clone(),
+                path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            },
+        );
+        self.recent.truncate(MAX_RECENT);
+        let recent: String = self
+            .recent
+            .iter()
+            .map(|game| {
+                format!(
+                    "{:016x}\t{}\t{}\n",
+                    game.hash,
+                    game.title,
+                    game.path.display()
+                )
+            })
+            .collect();
+        fs::write(self.dir.join(RECENT_FILE), recent)?;
+
+        Ok(Game {
+            hash,
+            title,
+            dir,
+            rom,
+            config,
+        })
+    }
+}
+
+/// `hash<TAB>title<TAB>path`, lines that don't parse are skipped
+fn parse_recent(line: &str) -> Option<RecentGame> {
+    let mut fields = line.splitn(3, '\t');
+    let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let title = fields.next()?.to_string();
+    let path = PathBuf::from(fields.next()?);
+    Some(RecentGame { hash, title, path })
+}
+
+/// A game opened through the library
+pub struct Game {
+    pub hash: u64,
+    pub title: String,
+    dir: PathBuf,
+    rom: Vec<u8>,
+    pub config: GameConfig,
+}
+
+impl Game {
+    /// The directory holding the game's data
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn save_path(&self) -> PathBuf {
+        self.dir.join(SAVE_FILE)
+    }
+
+    pub fn state_path(&self, slot: u8) -> PathBuf {
+        self.dir.join(format!("state-{slot}.state"))
+    }
+
+    /// The cartridge, with its battery-backed RAM kept in the library
+    pub fn cartridge(&self) -> Result<Cartridge, CartridgeError> {
+        Cartridge::load(self.rom.clone(), self.save_path())
+    }
+
+    /// Use the game's palette and cheats, the controls are up to the frontend
+    pub fn apply_config(&self, gameboy: &mut GameBoy) -> Result<(), CheatError> {
+        if let Some(palette) = self.config.palette {
+            gameboy.set_palette(palette);
+        }
+        for code in &self.config.cheats {
+            gameboy.add_cheat(code)?;
+        }
+        Ok(())
+    }
+
+    /// Write `config` back to the library
+    pub fn save_config(&self) -> Result<(), LibraryError> {
+        fs::write(self.dir.join(CONFIG_FILE), self.config.to_string())?;
+        Ok(())
+    }
+}
+
+/// Per-game overrides, stored as `key = value` lines:
+///
+/// ```text
+/// # comments and blank lines are ignored
+/// palette = pocket                  grayscale, classic-green, pocket or four #RRGGBB colors
+/// cheat = 00A-17B-C49               repeated for every code
+/// button.a = Q                      the frontend's name of the key for a button
+/// pad.a = South                     the gamepad button for a button
+/// turbo.a = S                       a key firing a button repeatedly while held
+/// pad_turbo.a = North               a gamepad button doing the same
+/// ```
+///
+/// Each takes the place of the button's default key or gamepad button, see `InputMap::rebind`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameConfig {
+    pub palette: Option<DmgPalette>,
+    pub cheats: Vec<String>,
+    pub controls: Vec<Binding>,
+}
+
+/// The config key prefixes of the controls, by kind of input and whether they are turbo
+const CONTROL_PREFIXES: [(&str, bool, bool); 4] = [
+    ("button.", false, false),
+    ("pad.", true, false),
+    ("turbo.", false, true),
+    ("pad_turbo.", true, true),
+];
+
+impl GameConfig {
+    pub fn parse(text: &str) -> Result<GameConfig, LibraryError> {
+        let mut config = GameConfig::default();
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |reason: String| LibraryError::InvalidConfig {
+                line: index + 1,
+                reason,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(invalid(format!("expected `key = value`, got {line:?}")));
+            };
+            let (key, value) = (key.trim(), value.trim());
+            match key {
+                "palette" => {
+                    config.palette = Some(
+                        parse_palette(value)
+                            .ok_or_else(|| invalid(format!("unknown palette {value:?}")))?,
+                    )
+                }
+                "cheat" => config.cheats.push(value.to_string()),
+                _ => {
+                    let (button, pad, turbo) = CONTROL_PREFIXES
+                        .iter()
+                        .find_map(|(prefix, pad, turbo)| {
+                            let button = Button::from_name(key.strip_prefix(prefix)?)?;
+                            Some((button, *pad, *turbo))
+                        })
+                        .ok_or_else(|| invalid(format!("unknown setting {key:?}")))?;
+                    let input = match pad {
+                        false => Input::Key(value.to_string()),
+                        true => Input::Pad(value.to_string()),
+                    };
+                    config.controls.retain(|binding| {
+                        binding.button != button
+                            || binding.turbo != turbo
+                            || matches!(binding.input, Input::Pad(_)) != pad
+                    });
+                    config.controls.push(Binding {
+                        input,
+                        button,
+                        turbo,
+                    });
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The key mapped to `button`, if it is overridden
+    pub fn control(&self, button: Button) -> Option<&str> {
+        self.controls
+            .iter()
+            .find_map(|binding| match &binding.input {
+                Input::Key(key) if binding.button == button && !binding.turbo => Some(key.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The default controls with the game's in their place
+    pub fn input_map(&self) -> InputMap {
+        let mut map = InputMap::default();
+        for binding in &self.controls {
+            map.rebind(binding.input.clone(), binding.button, binding.turbo);
+        }
+        map
+    }
+
+    /// Keep the controls of `map` that differ from the defaults, the first key and gamepad button
+    /// of each button and turbo
+    pub fn set_input_map(&mut self, map: &InputMap) {
+        let defaults = InputMap::default();
+        self.controls.clear();
+        for button in Button::ALL {
+            for (_, pad, turbo) in CONTROL_PREFIXES {
+                let kind = |map: &'_ InputMap| -> Vec<Input> {
+                    map.inputs(button, turbo)
+                        .filter(|input| matches!(input, Input::Pad(_)) == pad)
+                        .cloned()
+                        .collect()
+                };
+                let inputs = kind(map);
+                if inputs == kind(&defaults) {
+                    continue;
+                }
+                if let Some(input) = inputs.into_iter().next() {
+                    self.controls.push(Binding {
+                        input,
+                        button,
+                        turbo,
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for GameConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(palette) = &self.palette {
+            match palette.name() {
+                Some(name) => writeln!(f, "palette = {name}")?,
+                None => {
+                    let colors: Vec<String> = palette
+                        .0
+                        .iter()
+                        .map(|[r, g, b, _]| format!("#{r:02X}{g:02X}{b:02X}"))
+                        .collect();
+                    writeln!(f, "palette = {}", colors.join(" "))?
+                }
+            }
+        }
+        for code in &self.cheats {
+            writeln!(f, "cheat = {code}")?;
+        }
+        for binding in &self.controls {
+            let pad = matches!(binding.input, Input::Pad(_));
+            let (prefix, ..) = CONTROL_PREFIXES
+                .iter()
+                .find(|(_, kind, turbo)| *kind == pad && *turbo == binding.turbo)
+                .unwrap();
+            let (Input::Key(name) | Input::Pad(name)) = &binding.input;
+            writeln!(f, "{prefix}{} = {name}", binding.button.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// A palette by name or as four `#RRGGBB` colors from lightest to darkest
+fn parse_palette(value: &str) -> Option<DmgPalette> {
+    if let Some(palette) = DmgPalette::named(value) {
+        return Some(palette);
+    }
+    let mut colors = [[0xFF; 4]; 4];
+    let mut hex = value.split_whitespace();
+    for color in &mut colors {
+        let rgb = hex.next()?.strip_prefix('#')?;
+        let rgb = u32::from_str_radix(rgb, 16)
+            .ok()
+            .filter(|_| rgb.len() == 6)?;
+        color[..3].copy_from_slice(&rgb.to_be_bytes()[1..]);
+    }
+    hex.next().is_none().then_some(DmgPalette(colors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{fix_header_checksum, test_rom};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gameboy_emulator_{}_{name}", std::process::id()))
+    }
+
+    fn make_rom(title: &str) -> Vec<u8> {
+        let mut rom = test_rom(&[]);
+        rom[0x0134..0x0134 + title.len()].copy_from_slice(title.as_bytes());
+        rom[0x0147] = 0x03; // MBC1 with RAM and a battery
+        rom[0x0149] = 0x02;
+        fix_header_checksum(&mut rom);
+        rom
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let text = "# overrides\n\
+                    palette = #FFFFFF #C0C0C0 #606060 #000000\n\
+                    cheat = 00A-17B-C49\n\
+                    button.a = Left Shift\n\
+                    button.START = Return\n\
+                    pad.b = West\n\
+                    turbo.a = S\n";
+        let config = GameConfig::parse(text).unwrap();
+        assert_eq!(config.palette.unwrap().color(1), [0xC0, 0xC0, 0xC0, 0xFF]);
+        assert_eq!(config.cheats, ["00A-17B-C49"]);
+        assert_eq!(config.control(Button::A), Some("Left Shift"));
+        assert_eq!(config.control(Button::Start), Some("Return"));
+        assert_eq!(config.control(Button::B), None);
+        let map = config.input_map();
+        assert!(map
+            .inputs(Button::B, false)
+            .any(|input| *input == Input::Pad("West".into())));
+        assert!(map
+            .inputs(Button::A, true)
+            .any(|input| *input == Input::Key("S".into())));
+        let mut rebuilt = GameConfig::default();
+        rebuilt.set_input_map(&map);
+        let bindings = rebuilt.input_map().bindings().to_vec();
+        assert_eq!(bindings.len(), map.bindings().len());
+        assert!(bindings
+            .iter()
+            .all(|binding| map.bindings().contains(binding)));
+        assert_eq!(GameConfig::parse(&config.to_string()).unwrap(), config);
+
+        let named = GameConfig::parse("palette = pocket").unwrap();
+        assert_eq!(named.palette, Some(DmgPalette::POCKET));
+        assert_eq!(named.to_string(), "palette = pocket\n");
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        for text in [
+            "palette = sepia",
+            "palette = #FFFFFF #000000",
+            "\nbutton.turbo = T",
+            "\n\nvolume",
+        ] {
+            let line = text.lines().count();
+            assert!(
+                matches!(
+                    GameConfig::parse(text),
+                    Err(LibraryError::InvalidConfig { line: actual, .. }) if actual == line
+                ),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_library() {
+        let dir = temp_path("library");
+        let tetris = temp_path("library_tetris.gb");
+        let zelda = temp_path("library_zelda.gb");
+        fs::write(&tetris, make_rom("TETRIS")).unwrap();
+        fs::write(&zelda, make_rom("ZELDA")).unwrap();
+
+        let mut library = Library::open(&dir).unwrap();
+        let mut game = library.open_game(&tetris).unwrap();
+        assert_eq!(game.title, "TETRIS");
+        assert_eq!(game.config, GameConfig::default());
+        game.config.palette = Some(DmgPalette::CLASSIC_GREEN);
+        game.save_config().unwrap();
+        let mut cartridge = game.cartridge().unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA000, 0x42);
+        drop(cartridge);
+        library.open_game(&zelda).unwrap();
+        library.open_game(&zelda).unwrap();
+
+        let mut library = Library::open(&dir).unwrap();
+        let titles: Vec<&str> = library
+            .recent()
+            .iter()
+            .map(|game| game.title.as_str())
+            .collect();
+        assert_eq!(titles, ["ZELDA", "TETRIS"]);
+        let game = library.open_game(&tetris).unwrap();
+        let mut gameboy = GameBoy::new();
+        game.apply_config(&mut gameboy).unwrap();
+        let mut cartridge = game.cartridge().unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        let saved = cartridge.read_ram(0xA000);
+        let recent = library.recent()[0].clone();
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&tetris).unwrap();
+        fs::remove_file(&zelda).unwrap();
+        assert_eq!(*gameboy.palette(), DmgPalette::CLASSIC_GREEN);
+        assert_eq!(saved, 0x42);
+        assert_eq!(recent.hash, game.hash);
+        assert_eq!(game.save_path(), game.dir().join("game.sav"));
+    }
+}
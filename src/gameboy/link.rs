@@ -0,0 +1,163 @@
+//! A link cable between two emulators over TCP, one listening and the other connecting.
+//!
+//! After a handshake agreeing on the protocol version, every message is two bytes, a kind and
+//! a data byte:
+//!
+//! - `SEND byte` when a Game Boy clocks a transfer with its internal clock, it waits for the
+//!   answer (up to `REPLY_TIMEOUT`, then it reads 0xFF as without a cable)
+//! - `REPLY byte` from the other side once its serial port took the byte, carrying its SB
+//!
+//! The clocks are negotiated per transfer: if both start one with the internal clock at the same
+//! time, each side takes the other's `SEND` as its answer, as if one had clocked both bytes.
+//! Messages are read on a thread of their own, so an idle port is polled without blocking.
+
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::utils::LinkError;
+
+use super::SerialTransport;
+
+/// Sent by both sides after connecting, the last byte is the protocol version
+const HANDSHAKE: &[u8; 7] = b"GBLINK\x01";
+
+const SEND: u8 = 0x01;
+const REPLY: u8 = 0x02;
+
+/// How long a transfer clocked here waits for the other side before reading 0xFF, long enough
+/// for the other emulator to reach its next poll even when it falls a few frames behind
+pub const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct TcpLink {
+    stream: TcpStream,
+    messages: Receiver<[u8; 2]>,
+}
+
+impl TcpLink {
+    /// Wait for the other emulator to connect on `address`
+    pub fn listen(address: impl ToSocketAddrs) -> Result<TcpLink, LinkError> {
+        let (stream, peer) = TcpListener::bind(address)?.accept()?;
+        tracing::info!(target: "serial", "link cable connected to {peer}");
+        TcpLink::handshake(stream)
+    }
+
+    /// Connect to an emulator listening on `address`
+    pub fn connect(address: impl ToSocketAddrs) -> Result<TcpLink, LinkError> {
+        TcpLink::handshake(TcpStream::connect(address)?)
+    }
+
+    fn handshake(mut stream: TcpStream) -> Result<TcpLink, LinkError> {
+        stream.set_nodelay(true)?;
+        stream.write_all(HANDSHAKE)?;
+        let mut handshake = [0; HANDSHAKE.len()];
+        stream.read_exact(&mut handshake)?;
+        if handshake != *HANDSHAKE {
+            return Err(LinkError::Handshake(handshake.to_vec()));
+        }
+
+        let (sender, messages) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut message = [0; 2];
+            while reader.read_exact(&mut message).is_ok() && sender.send(message).is_ok() {}
+        });
+        Ok(TcpLink { stream, messages })
+    }
+
+    fn send(&mut self, kind: u8, byte: u8) {
+        if let Err(error) = self.stream.write_all(&[kind, byte]) {
+            tracing::warn!(target: "serial", "link cable disconnected: {error}");
+        }
+    }
+}
+
+impl SerialTransport for TcpLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        // the other side clocked a transfer first, answer it with this byte
+        while let Ok([kind, received]) = self.messages.try_recv() {
+            if kind == SEND {
+                self.send(REPLY, byte);
+                return received;
+            }
+        }
+
+        self.send(SEND, byte);
+        loop {
+            match self.messages.recv_timeout(REPLY_TIMEOUT) {
+                // a SEND crossing ours is the other side's byte, it takes ours the same way
+                Ok([REPLY | SEND, received]) => return received,
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => {
+                    tracing::debug!(target: "serial", "no answer over the link cable");
+                    return 0xFF;
+                }
+                Err(RecvTimeoutError::Disconnected) => return 0xFF,
+            }
+        }
+    }
+
+    fn receive(&mut self, byte: u8) -> Option<u8> {
+        // late answers to transfers that timed out are dropped
+        while let Ok([kind, received]) = self.messages.try_recv() {
+            if kind == SEND {
+                self.send(REPLY, byte);
+                return Some(received);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A listening and a connected link on a free local port
+    fn pair() -> (TcpLink, TcpLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || TcpLink::handshake(listener.accept().unwrap().0));
+        let client = TcpLink::connect(address).unwrap();
+        (server.join().unwrap().unwrap(), client)
+    }
+
+    #[test]
+    fn test_exchange() {
+        let (mut master, mut slave) = pair();
+        let answer = thread::spawn(move || loop {
+            if let Some(received) = slave.receive(0x22) {
+                return received;
+            }
+            thread::sleep(Duration::from_millis(1));
+        });
+        assert_eq!(master.exchange(0x11), 0x22);
+        assert_eq!(answer.join().unwrap(), 0x11);
+    }
+
+    #[test]
+    fn test_both_clock() {
+        let (mut server, mut client) = pair();
+        let client = thread::spawn(move || client.exchange(0x22));
+        assert_eq!(server.exchange(0x11), 0x22);
+        assert_eq!(client.join().unwrap(), 0x11);
+    }
+
+    #[test]
+    fn test_handshake_mismatch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            stream.write_all(b"HELLO!!").unwrap();
+        });
+        assert!(matches!(
+            TcpLink::connect(address),
+            Err(LinkError::Handshake(_))
+        ));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    gameboy::{interrupts::Interrupt, Observer},
+    utils::{combine, split},
+};
+
+use super::{Memory, OamAccess};
+
+/// The address space as seen by the CPU
+///
+/// Reads take `&mut self` as well, a timed bus advances the peripherals with every access
+pub trait MemoryBus {
+    fn read_byte(&mut self, address: u16) -> u8;
+
+    fn write_byte(&mut self, address: u16, value: u8);
+
+    /// Read without spending a bus cycle, for state the CPU sees internally like IF and IE
+    fn peek_byte(&self, address: u16) -> u8;
+
+    /// The bank mapped at `address`, for looking up debug symbols, see `Memory::bank`
+    fn bank(&self, address: u16) -> usize {
+        usize::from((0x4000..0x8000).contains(&address))
+    }
+
+    /// Write without spending a bus cycle, for state the CPU changes internally like IF and DIV
+    fn poke_byte(&mut self, address: u16, value: u8) {
+        self.write_byte(address, value)
+    }
+
+    /// Called by the CPU before it fetches the instruction at `address`
+    fn on_instruction(&mut self, address: u16) {}
+
+    /// Called by the CPU after it dispatched `interrupt`
+    fn on_interrupt(&mut self, interrupt: Interrupt) {}
+
+    /// Called by the CPU when it increments or decrements a 16-bit register holding `address`,
+    /// which the DMG puts on the bus, see `Memory::corrupt_oam`
+    fn on_increment(&mut self, address: u16) {}
+
+    /// Perform an armed CGB speed switch, called by STOP
+    ///
+    /// Returns true if the speed changed, buses without a speed switch never change
+    fn switch_speed(&mut self) -> bool {
+        false
+    }
+
+    fn read_word(&mut self, address: u16) -> u16 {
+        let lo = self.read_byte(address);
+        let hi = self.read_byte(address.wrapping_add(1));
+        combine(hi, lo)
+    }
+
+    fn write_word(&mut self, address: u16, value: u16) {
+        let (hi, lo) = split(value);
+        self.write_byte(address, lo);
+        self.write_byte(address.wrapping_add(1), hi);
+    }
+}
+
+/// A memory access made by the CPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryAccess {
+    Read { address: u16, value: u8 },
+    Write { address: u16, value: u8 },
+}
+
+impl MemoryAccess {
+    pub fn address(self) -> u16 {
+        match self {
+            MemoryAccess::Read { address, .. } | MemoryAccess::Write { address, .. } => address,
+        }
+    }
+}
+
+/// A view of `Memory` where every access takes one machine cycle
+///
+/// The peripherals are advanced before each access, so a read in the third cycle of an
+/// instruction sees the timer, DMA and serial port as they are in that cycle.
+/// The instruction's remaining internal cycles are left to the caller, see `cycles`
+pub struct TimedBus<'a> {
+    memory: &'a mut Memory,
+    cycles: u8,
+    observer: Option<&'a dyn Fn(MemoryAccess)>,
+    hooks: Option<&'a mut dyn Observer>,
+    clock: Option<&'a mut dyn FnMut(&mut Memory)>,
+}
+
+impl<'a> TimedBus<'a> {
+    pub fn new(memory: &'a mut Memory) -> TimedBus<'a> {
+        TimedBus {
+            memory,
+            cycles: 0,
+            observer: None,
+            hooks: None,
+            clock: None,
+        }
+    }
+
+    /// A timed bus that reports every (non-internal) access to `observer`
+    pub fn with_observer(
+        memory: &'a mut Memory,
+        observer: &'a dyn Fn(MemoryAccess),
+    ) -> TimedBus<'a> {
+        TimedBus {
+            observer: Some(observer),
+            ..TimedBus::new(memory)
+        }
+    }
+
+    /// A timed bus that calls `hooks` for every access, instruction and interrupt
+    pub fn with_hooks(memory: &'a mut Memory, hooks: &'a mut dyn Observer) -> TimedBus<'a> {
+        TimedBus {
+            hooks: Some(hooks),
+            ..TimedBus::new(memory)
+        }
+    }
+
+    /// Also call `clock` every machine cycle, after the peripherals advanced, to keep the PPU in step
+    pub fn clocked(self, clock: &'a mut dyn FnMut(&mut Memory)) -> TimedBus<'a> {
+        TimedBus {
+            clock: Some(clock),
+            ..self
+        }
+    }
+
+    pub fn memory(&self) -> &Memory {
+        self.memory
+    }
+
+    /// Machine cycles spent on accesses so far
+    pub fn cycles(&self) -> u8 {
+        self.cycles
+    }
+
+    fn advance(&mut self) {
+        self.memory.tick(1);
+        if let Some(clock) = &mut self.clock {
+            clock(self.memory);
+        }
+        self.cycles += 1;
+    }
+}
+
+impl MemoryBus for TimedBus<'_> {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.advance();
+        self.memory.corrupt_oam(address, OamAccess::Read);
+        self.memory.count_read(address);
+        let value = Memory::read_byte(self.memory, address);
+        if let Some(observer) = self.observer {
+            observer(MemoryAccess::Read { address, value });
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_memory_read(address, value);
+        }
+        value
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.advance();
+        self.memory.corrupt_oam(address, OamAccess::Write);
+        self.memory.count_write(address);
+        Memory::write_byte(self.memory, address, value);
+        if let Some(observer) = self.observer {
+            observer(MemoryAccess::Write { address, value });
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_memory_write(address, value);
+        }
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.memory.peek_byte(address)
+    }
+
+    fn bank(&self, address: u16) -> usize {
+        self.memory.bank(address)
+    }
+
+    fn poke_byte(&mut self, address: u16, value: u8) {
+        self.memory.write_byte(address, value)
+    }
+
+    fn switch_speed(&mut self) -> bool {
+        MemoryBus::switch_speed(self.memory)
+    }
+
+    fn on_instruction(&mut self, address: u16) {
+        if let Some(hooks) = &mut self.hooks {
+            let opcode = self.memory.peek_byte(address);
+            hooks.on_instruction(address, opcode);
+        }
+    }
+
+    fn on_interrupt(&mut self, interrupt: Interrupt) {
+        if let Some(hooks) = &mut self.hooks {
+            hooks.on_interrupt(interrupt);
+        }
+    }
+
+    fn on_increment(&mut self, address: u16) {
+        self.memory.corrupt_oam(address, OamAccess::Write);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::timer::DIV;
+
+    #[test]
+    fn test_timed_access() {
+        let mut memory = Memory::new();
+        let mut bus = TimedBus::new(&mut memory);
+
+        bus.write_byte(0xC000, 0x12);
+        assert_eq!(bus.read_word(0xC000), 0x0012);
+        assert_eq!(bus.cycles(), 3);
+    }
+
+    #[test]
+    fn test_peek_is_free() {
+        let mut memory = Memory::new();
+        let mut bus = TimedBus::new(&mut memory);
+
+        bus.poke_byte(0xC000, 0x12);
+        assert_eq!(bus.peek_byte(0xC000), 0x12);
+        assert_eq!(bus.cycles(), 0);
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut memory = Memory::new();
+        let accesses = core::cell::RefCell::new(Vec::new());
+        let observer = |access| accesses.borrow_mut().push(access);
+        let mut bus = TimedBus::with_observer(&mut memory, &observer);
+
+        bus.write_byte(0xC000, 0x12);
+        bus.read_byte(0xC000);
+        bus.peek_byte(0xC000);
+        assert_eq!(
+            *accesses.borrow(),
+            vec![
+                MemoryAccess::Write {
+                    address: 0xC000,
+                    value: 0x12
+                },
+                MemoryAccess::Read {
+                    address: 0xC000,
+                    value: 0x12
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_access_sees_advanced_timer() {
+        let mut memory = Memory::new();
+        let mut bus = TimedBus::new(&mut memory);
+
+        // DIV increments every 64 machine cycles, the 64th access reads it after the increment
+        for _ in 0..63 {
+            assert_eq!(bus.read_byte(DIV), 0);
+        }
+        assert_eq!(bus.read_byte(DIV), 1);
+    }
+}
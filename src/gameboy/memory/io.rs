@@ -0,0 +1,512 @@
+//! The IO registers at 0xFF00-0xFF7F and IE at 0xFFFF.
+//!
+//! Each peripheral implements `IoDevice` for the registers it owns and is registered here, its
+//! reads and writes are forwarded to it. The registers nobody owns are stored as written. Unused
+//! bits read as 1 and addresses without a register read 0xFF, going by the tables in Pan Docs.
+//!
+//! In CGB mode the undocumented registers at 0xFF72-0xFF75 hold what is written, they are used
+//! to detect the CGB.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        apu::{Apu, PCM12, PCM34},
+        infrared::{Infrared, IrTransport, RP},
+        interrupts::{Interrupt, InterruptController, IE},
+        joypad::{Button, Joypad},
+        ppu::{ColorPalettes, LcdRegisters, BCPD, BCPS, OCPD, OCPS, OPRI},
+        save_state::{SaveState, StateReader, StateWriter},
+        serial::{Serial, SerialTransport},
+        speed::{Speed, KEY1},
+        timer::Timer,
+    },
+    utils::SaveStateError,
+};
+
+use super::{regions::MemoryRegion, IO_SIZE, IO_START, SVBK, VBK};
+
+/// The bits of each register that read as 1 on the DMG, 0xFF where there is no register
+#[rustfmt::skip]
+const DMG_UNUSED_BITS: [u8; IO_SIZE] = [
+    // P1, SB, SC, -, DIV, TIMA, TMA, TAC, -, IF
+    0xC0, 0x00, 0x7E, 0xFF, 0x00, 0x00, 0x00, 0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xE0,
+    // NR10-NR14, -, NR21-NR24, NR30-NR34, -
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, 0xFF, 0x3F, 0x00, 0xFF, 0xBF, 0x7F, 0xFF, 0x9F, 0xFF, 0xBF, 0xFF,
+    // NR41-NR44, NR50-NR52, -
+    0xFF, 0x00, 0x00, 0xBF, 0x00, 0x00, 0x70, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    // wave RAM
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    // LCDC, STAT, SCY, SCX, LY, LYC, DMA, BGP, OBP0, OBP1, WY, WX, -
+    0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF,
+    // the boot ROM switch, which reads 0xFF, and the CGB registers
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+/// Undocumented CGB registers with no known function
+const FF72: u16 = 0xFF72;
+const FF73: u16 = 0xFF73;
+const FF74: u16 = 0xFF74;
+const FF75: u16 = 0xFF75;
+
+/// The registers that differ in CGB mode, HDMA1-HDMA5 are handled by `Memory`
+const CGB_UNUSED_BITS: [(u16, u8); 15] = [
+    (KEY1, 0x7E),
+    (RP, 0x3C),
+    (VBK, 0xFE),
+    (BCPS, 0x40),
+    (BCPD, 0x00),
+    (OCPS, 0x40),
+    (OCPD, 0x00),
+    (OPRI, 0xFE),
+    (SVBK, 0xF8),
+    (FF72, 0x00),
+    (FF73, 0x00),
+    (FF74, 0x00),
+    (FF75, 0x8F),
+    (PCM12, 0x00),
+    (PCM34, 0x00),
+];
+
+fn unused_bits(address: u16, cgb_mode: bool) -> u8 {
+    if address == IE {
+        return 0;
+    }
+    let cgb = cgb_mode
+        .then(|| {
+            CGB_UNUSED_BITS
+                .iter()
+                .find(|(register, _)| *register == address)
+        })
+        .flatten();
+    match cgb {
+        Some((_, bits)) => *bits,
+        None => DMG_UNUSED_BITS[usize::from(address) - IO_START],
+    }
+}
+
+/// A peripheral owning some of the IO registers
+///
+/// Register side effects, like DIV resetting on any write, belong in `write`
+pub(crate) trait IoDevice {
+    /// The addresses of the registers, no two devices share one
+    fn registers(&self) -> &'static [RangeInclusive<u16>];
+
+    /// Read one of the registers, the unused bits are set to 1 by `Io`
+    fn read(&self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+}
+
+/// The devices registered with `Io`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Device {
+    Joypad,
+    Serial,
+    Timer,
+    Interrupts,
+    Apu,
+    Lcd,
+    Speed,
+    Palettes,
+    Infrared,
+}
+
+impl Device {
+    const ALL: [Device; 9] = [
+        Device::Joypad,
+        Device::Serial,
+        Device::Timer,
+        Device::Interrupts,
+        Device::Apu,
+        Device::Lcd,
+        Device::Speed,
+        Device::Palettes,
+        Device::Infrared,
+    ];
+}
+
+/// The index of a register in `Io::owners`, IE comes after the IO region
+fn slot(address: u16) -> usize {
+    if address == IE {
+        IO_SIZE
+    } else {
+        usize::from(address) - IO_START
+    }
+}
+
+pub struct Io {
+    registers: [u8; IO_SIZE], // the registers no device owns
+    owners: [Option<Device>; IO_SIZE + 1],
+    interrupts: InterruptController,
+    timer: Timer,
+    joypad: Joypad,
+    serial: Serial,
+    apu: Apu,
+    speed: Speed,
+    lcd: LcdRegisters,
+    palettes: ColorPalettes,
+    infrared: Infrared,
+}
+
+impl Io {
+    pub fn new() -> Io {
+        let mut io = Io {
+            registers: [0; IO_SIZE],
+            owners: [None; IO_SIZE + 1],
+            interrupts: InterruptController::new(),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
+            apu: Apu::new(),
+            speed: Speed::new(),
+            lcd: LcdRegisters::new(),
+            palettes: ColorPalettes::new(),
+            infrared: Infrared::new(),
+        };
+        for device in Device::ALL {
+            io.register(device);
+        }
+        io
+    }
+
+    /// Forward the device's registers to it
+    fn register(&mut self, device: Device) {
+        for range in self.device(device).registers() {
+            for address in range.clone() {
+                let owner = &mut self.owners[slot(address)];
+                debug_assert!(owner.is_none(), "{address:#06X} has two owners");
+                *owner = Some(device);
+            }
+        }
+    }
+
+    fn device(&self, device: Device) -> &dyn IoDevice {
+        match device {
+            Device::Joypad => &self.joypad,
+            Device::Serial => &self.serial,
+            Device::Timer => &self.timer,
+            Device::Interrupts => &self.interrupts,
+            Device::Apu => &self.apu,
+            Device::Lcd => &self.lcd,
+            Device::Speed => &self.speed,
+            Device::Palettes => &self.palettes,
+            Device::Infrared => &self.infrared,
+        }
+    }
+
+    fn device_mut(&mut self, device: Device) -> &mut dyn IoDevice {
+        match device {
+            Device::Joypad => &mut self.joypad,
+            Device::Serial => &mut self.serial,
+            Device::Timer => &mut self.timer,
+            Device::Interrupts => &mut self.interrupts,
+            Device::Apu => &mut self.apu,
+            Device::Lcd => &mut self.lcd,
+            Device::Speed => &mut self.speed,
+            Device::Palettes => &mut self.palettes,
+            Device::Infrared => &mut self.infrared,
+        }
+    }
+
+    /// The device handling `address`, the CGB palettes and infrared port are only mapped in CGB
+    /// mode
+    fn owner(&self, address: u16) -> Option<Device> {
+        self.owners[slot(address)].filter(|device| {
+            !matches!(device, Device::Palettes | Device::Infrared) || self.cgb_mode()
+        })
+    }
+
+    /// Advance the peripherals by a number of CPU T-cycles, requesting their interrupts
+    ///
+    /// In double speed the APU only sees half of them
+    pub fn tick(&mut self, cycles: u32) {
+        if self.timer.tick(cycles) {
+            self.request_interrupt(Interrupt::Timer);
+        }
+        if self.serial.tick(cycles) {
+            self.request_interrupt(Interrupt::Serial);
+        }
+        let apu_cycles = if self.double_speed() {
+            cycles / 2
+        } else {
+            cycles
+        };
+        self.apu.tick(apu_cycles);
+    }
+
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.speed.set_cgb_mode(cgb_mode);
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.speed.cgb_mode()
+    }
+
+    /// The CGB color palettes, for the PPU
+    pub fn color_palettes(&self) -> &ColorPalettes {
+        &self.palettes
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.speed.double_speed()
+    }
+
+    /// Perform an armed CGB speed switch, returns true if the speed changed
+    pub fn switch_speed(&mut self) -> bool {
+        self.speed.switch()
+    }
+
+    /// Set the internal timer counter, DIV is its upper byte
+    pub fn set_timer_counter(&mut self, counter: u16) {
+        self.timer.set_counter(counter);
+    }
+
+    pub fn set_serial_transport(&mut self, transport: Box<dyn SerialTransport>) {
+        self.serial.set_transport(transport);
+    }
+
+    pub fn set_ir_transport(&mut self, transport: Box<dyn IrTransport>) {
+        self.infrared.set_transport(transport);
+    }
+
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        self.apu.set_sample_rate(rate);
+    }
+
+    pub fn drain_audio_samples(&mut self) -> Vec<i16> {
+        self.apu.drain_samples()
+    }
+
+    /// The APU, for channel muting and WAV dumps
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        &mut self.apu
+    }
+
+    pub fn press_button(&mut self, button: Button) {
+        if self.joypad.press(button) {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    pub fn release_button(&mut self, button: Button) {
+        self.joypad.release(button);
+    }
+
+    pub fn is_button_pressed(&self, button: Button) -> bool {
+        self.joypad.is_pressed(button)
+    }
+
+    pub fn set_sgb(&mut self, enabled: bool) {
+        self.joypad.set_sgb(enabled);
+    }
+
+    pub fn take_sgb_commands(&mut self) -> Vec<Vec<u8>> {
+        self.joypad.take_sgb_commands()
+    }
+
+    fn request_interrupt(&mut self, interrupt: Interrupt) {
+        self.interrupts.request(interrupt);
+    }
+
+    /// Set an LCD register from the PPU's side, including the bits the CPU can't write
+    pub fn set_lcd_register(&mut self, address: u16, value: u8) {
+        self.lcd.set(address, value);
+    }
+}
+
+impl MemoryRegion for Io {
+    fn read(&self, address: u16) -> u8 {
+        let value = match self.owner(address) {
+            Some(device) => self.device(device).read(address),
+            None => self.registers[slot(address)],
+        };
+        value | unused_bits(address, self.cgb_mode())
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match self.owner(address) {
+            Some(device) => self.device_mut(device).write(address, value),
+            None => self.registers[slot(address)] = value,
+        }
+    }
+}
+
+impl SaveState for Io {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.registers);
+        self.interrupts.save_state(state);
+        self.timer.save_state(state);
+        self.joypad.save_state(state);
+        self.serial.save_state(state);
+        self.apu.save_state(state);
+        self.speed.save_state(state);
+        self.lcd.save_state(state);
+        self.palettes.save_state(state);
+        self.infrared.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.registers)?;
+        self.interrupts.load_state(state)?;
+        self.timer.load_state(state)?;
+        self.joypad.load_state(state)?;
+        self.serial.load_state(state)?;
+        self.apu.load_state(state)?;
+        self.speed.load_state(state)?;
+        self.lcd.load_state(state)?;
+        self.palettes.load_state(state)?;
+        self.infrared.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{
+        interrupts::IF,
+        ppu::{LY, STAT},
+        serial::SC,
+        timer::{DIV, TAC},
+    };
+
+    #[test]
+    fn test_plain_registers() {
+        let mut io = Io::new();
+        io.write(0xFF40, 0x91);
+        io.write(IE, 0x1F);
+
+        assert_eq!(io.read(0xFF40), 0x91);
+        assert_eq!(io.read(IE), 0x1F);
+    }
+
+    #[test]
+    fn test_device_registration() {
+        let mut io = Io::new();
+        assert_eq!(io.owner(0xFF00), Some(Device::Joypad));
+        assert_eq!(io.owner(IF), Some(Device::Interrupts));
+        assert_eq!(io.owner(IE), Some(Device::Interrupts));
+        assert_eq!(io.owner(0xFF3F), Some(Device::Apu));
+        assert_eq!(io.owner(STAT), Some(Device::Lcd));
+        // DMA and the boot ROM switch are kept by `Memory`
+        assert_eq!(io.owner(0xFF46), None);
+        assert_eq!(io.owner(0xFF50), None);
+
+        assert_eq!(io.owner(BCPD), None);
+        io.set_cgb_mode(true);
+        assert_eq!(io.owner(BCPD), Some(Device::Palettes));
+    }
+
+    #[test]
+    fn test_forwarded_registers() {
+        let mut io = Io::new();
+        io.write(DIV, 0x12);
+        io.write(TAC, 0x05);
+
+        assert_eq!(io.read(DIV), 0x00);
+        assert_eq!(io.read(TAC), 0xFD);
+    }
+
+    #[test]
+    fn test_tick_requests_interrupt() {
+        let mut io = Io::new();
+        io.write(TAC, 0x05);
+        io.write(0xFF05, 0xFF);
+
+        io.tick(20);
+        assert_eq!(io.read(IF), 0xE0 | Interrupt::Timer.bit());
+    }
+
+    #[test]
+    fn test_unused_bits_read_as_one() {
+        let mut io = Io::new();
+        // what reads back after writing 0, from the register tables in Pan Docs
+        for (address, expected) in [
+            (SC, 0x7E),
+            (0xFF03, 0xFF),
+            (TAC, 0xF8),
+            (0xFF08, 0xFF),
+            (IF, 0xE0),
+            (0xFF10, 0x80), // NR10
+            (0xFF11, 0x3F), // NR11, the length is write only
+            (0xFF13, 0xFF), // NR13 is write only
+            (0xFF1A, 0x7F), // NR30
+            (0xFF1C, 0x9F), // NR32
+            (0xFF26, 0x70), // NR52
+            (0xFF27, 0xFF),
+            (STAT, 0x80),
+            (0xFF4C, 0xFF),
+            (0xFF50, 0xFF),
+            (0xFF7F, 0xFF),
+        ] {
+            io.write(address, 0x00);
+            assert_eq!(io.read(address), expected, "{address:#06X}");
+        }
+    }
+
+    #[test]
+    fn test_cgb_registers() {
+        let mut io = Io::new();
+        for address in [KEY1, BCPS, OCPD, RP] {
+            io.write(address, 0x00);
+            assert_eq!(io.read(address), 0xFF, "{address:#06X}");
+        }
+
+        io.set_cgb_mode(true);
+        for (address, expected) in [(KEY1, 0x7E), (BCPS, 0x40), (OCPS, 0x40), (RP, 0x3E)] {
+            io.write(address, 0x00);
+            assert_eq!(io.read(address), expected, "{address:#06X}");
+        }
+    }
+
+    #[test]
+    fn test_undocumented_registers() {
+        let mut io = Io::new();
+        io.write(FF72, 0x12);
+        assert_eq!(io.read(FF72), 0xFF);
+        assert_eq!(io.read(PCM12), 0xFF);
+
+        io.set_cgb_mode(true);
+        for (address, expected) in [(FF72, 0x12), (FF73, 0x34), (FF74, 0x56), (FF75, 0xFF)] {
+            io.write(address, expected);
+            assert_eq!(io.read(address), expected, "{address:#06X}");
+        }
+        io.write(FF75, 0x00);
+        assert_eq!(io.read(FF75), 0x8F);
+        // the PCM registers are read only
+        io.write(PCM34, 0x12);
+        assert_eq!(io.read(PCM34), 0x00);
+    }
+
+    #[test]
+    fn test_read_only_bits() {
+        let mut io = Io::new();
+        io.set_lcd_register(STAT, 0x06);
+        io.write(STAT, 0x41);
+        assert_eq!(io.read(STAT), 0xC6);
+
+        io.set_lcd_register(LY, 0x90);
+        io.write(LY, 0x00);
+        assert_eq!(io.read(LY), 0x90);
+    }
+
+    #[test]
+    fn test_key1() {
+        let mut io = Io::new();
+        io.write(KEY1, 0x01);
+        assert_eq!(io.read(KEY1), 0xFF);
+
+        io.set_cgb_mode(true);
+        io.write(KEY1, 0x01);
+        assert!(io.switch_speed());
+        assert!(io.double_speed());
+        assert_eq!(io.read(KEY1), 0xFE);
+    }
+}
@@ -0,0 +1,1062 @@
+//! The address space of the CPU.
+//!
+//! `Memory` decodes every address to the region handling it: the cartridge slot, VRAM,
+//! WRAM (mirrored by echo RAM), OAM, the IO registers or HRAM. Reads from the unusable
+//! region 0xFEA0-0xFEFF return 0xFF and writes there are ignored.
+//!
+//! With access blocking enabled the CPU is locked out of VRAM while the PPU draws (mode 3) and
+//! out of OAM while it scans or draws (modes 2 and 3), reads return 0xFF and writes are ignored.
+//! It is off by default, games that break the rule still work.
+//!
+//! With the OAM bug enabled, reads, writes and 16-bit increments and decrements of an address in
+//! 0xFE00-0xFEFF while the PPU scans OAM (mode 2) corrupt the row of OAM it is reading, like on
+//! the DMG, see `Memory::corrupt_oam`.
+//!
+//! In CGB mode VBK (0xFF4F) selects one of two VRAM banks and SVBK (0xFF70) one of seven
+//! WRAM banks for 0xD000-0xDFFF, on the DMG these are always VRAM bank 0 and WRAM bank 1.
+
+mod bus;
+mod io;
+mod regions;
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::utils::{combine, BootRomError, SaveStateError};
+
+use super::{
+    apu::Apu,
+    boot::{BOOT, BOOT_ROM_SIZE, POST_BOOT_IO, POST_BOOT_TIMER_COUNTER},
+    cartridge::{Cartridge, RomPatch},
+    dma::{Dma, DMA},
+    hdma::{Hdma, BLOCK_CYCLES, BLOCK_DOTS, BLOCK_SIZE, HDMA1, HDMA4, HDMA5},
+    heatmap::Heatmap,
+    infrared::IrTransport,
+    joypad::Button,
+    ppu::{stat, ColorPalettes, LYC, OPRI, STAT},
+    save_state::{SaveState, StateReader, StateWriter},
+    serial::SerialTransport,
+};
+
+pub use bus::{MemoryAccess, MemoryBus, TimedBus};
+use io::Io;
+pub(crate) use io::IoDevice;
+use regions::{BankedRam, CartridgeSlot, MemoryRegion, Ram};
+
+const ROM_00_START: usize = 0x0000;
+const ROM_00_END: usize = 0x3FFF;
+
+const ROM_NN_START: usize = 0x4000;
+const ROM_NN_END: usize = 0x7FFF;
+
+const VRAM_START: usize = 0x8000;
+const VRAM_END: usize = 0x9FFF;
+const VRAM_SIZE: usize = VRAM_END - VRAM_START + 1;
+
+const EXRAM_START: usize = 0xA000;
+const EXRAM_END: usize = 0xBFFF;
+
+const WRAM_START: usize = 0xC000;
+const WRAM_END: usize = 0xDFFF;
+const WRAM_BANK_SIZE: usize = 0x1000;
+const WRAM_BANKED_START: usize = WRAM_START + WRAM_BANK_SIZE;
+const WRAM_BANKS: usize = 8;
+
+const ECHO_RAM_START: usize = 0xE000;
+const ECHO_RAM_END: usize = 0xFDFF;
+const ECHO_RAM_OFFSET: u16 = (ECHO_RAM_START - WRAM_START) as u16;
+
+const OAM_START: usize = 0xFE00;
+const OAM_END: usize = 0xFE9F;
+const OAM_SIZE: usize = OAM_END - OAM_START + 1;
+
+const UNUSABLE_START: usize = 0xFEA0;
+const UNUSABLE_END: usize = 0xFEFF;
+
+const IO_START: usize = 0xFF00;
+const IO_END: usize = 0xFF7F;
+const IO_SIZE: usize = IO_END - IO_START + 1;
+
+const HRAM_START: usize = 0xFF80;
+const HRAM_END: usize = 0xFFFE;
+const HRAM_SIZE: usize = HRAM_END - HRAM_START + 1;
+
+const IE_ADDRESS: usize = 0xFFFF;
+
+const VRAM_BANKS: usize = 2;
+
+/// CGB VRAM bank select
+pub const VBK: u16 = 0xFF4F;
+/// CGB WRAM bank select
+pub const SVBK: u16 = 0xFF70;
+
+/// How the CPU put an address on the bus, for `Memory::corrupt_oam`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OamAccess {
+    Read,
+    /// A write or a 16-bit increment or decrement, they corrupt OAM the same way
+    Write,
+}
+
+pub struct Memory {
+    boot_rom: Option<Vec<u8>>,
+    cartridge: CartridgeSlot,
+    vram: BankedRam<VRAM_SIZE, VRAM_BANKS>,
+    wram: Ram<WRAM_BANK_SIZE>,
+    wram_banked: BankedRam<WRAM_BANK_SIZE, WRAM_BANKS>, // bank 0 is never mapped here
+    oam: Ram<OAM_SIZE>,
+    io: Io,
+    hram: Ram<HRAM_SIZE>,
+    dma: Dma,
+    hdma: Hdma,
+    hdma_started: bool, // an HBlank transfer started the PPU has not seen yet
+    access_blocking: bool,
+    stat_write_bug: bool,
+    stat_written: bool,         // a write to STAT the PPU has not seen yet
+    stat_sources_written: bool, // a write to LYC or STAT the PPU has not re-evaluated yet
+    oam_bug: bool,
+    oam_scan_row: Option<u8>, // the 8 byte row of OAM the PPU scans, set by the PPU in mode 2
+    heatmap: Option<Heatmap>, // counts the accesses the CPU makes
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            boot_rom: None,
+            cartridge: CartridgeSlot::new(),
+            vram: BankedRam::new(VRAM_START as u16, 0),
+            wram: Ram::new(WRAM_START as u16),
+            wram_banked: BankedRam::new(WRAM_BANKED_START as u16, 1),
+            oam: Ram::new(OAM_START as u16),
+            io: Io::new(),
+            hram: Ram::new(HRAM_START as u16),
+            dma: Dma::new(),
+            hdma: Hdma::new(),
+            hdma_started: false,
+            access_blocking: false,
+            stat_write_bug: true,
+            stat_written: false,
+            stat_sources_written: false,
+            oam_bug: false,
+            oam_scan_row: None,
+            heatmap: None,
+        }
+    }
+
+    /// Map a boot ROM over 0x0000-0x00FF, until it unmaps itself by writing to 0xFF50
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), BootRomError> {
+        if boot_rom.len() != BOOT_ROM_SIZE {
+            return Err(BootRomError::InvalidSize {
+                expected: BOOT_ROM_SIZE,
+                actual: boot_rom.len(),
+            });
+        }
+        self.boot_rom = Some(boot_rom);
+        Ok(())
+    }
+
+    /// Set the IO registers to the values the boot ROM leaves behind
+    pub fn skip_boot(&mut self) {
+        for (adress, value) in POST_BOOT_IO {
+            self.write_byte(adress, value);
+        }
+        self.io.set_timer_counter(POST_BOOT_TIMER_COUNTER);
+    }
+
+    /// Set an LCD register from the PPU's side, including the bits the CPU can't write, like
+    /// the STAT mode and LY
+    pub fn set_io_register(&mut self, adress: u16, value: u8) {
+        self.io.set_lcd_register(adress, value);
+    }
+
+    /// Whether the boot ROM is still mapped over 0x0000-0x00FF
+    pub fn is_boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    /// Insert a cartridge, from now on ROM and external RAM accesses go through its MBC
+    ///
+    /// CGB mode is enabled if the header declares CGB support
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        self.set_cgb_mode(cartridge.header().supports_cgb());
+        self.cartridge.insert(cartridge);
+    }
+
+    /// Patch the inserted cartridge's ROM, see `Cartridge::set_rom_patches`
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.cartridge.set_rom_patches(patches);
+    }
+
+    /// Remove the inserted cartridge, if any
+    pub fn eject_cartridge(&mut self) -> Option<Cartridge> {
+        self.set_cgb_mode(false);
+        self.cartridge.eject()
+    }
+
+    fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.io.set_cgb_mode(cgb_mode);
+        self.vram.set_bank(0);
+        self.wram_banked.set_bank(1);
+    }
+
+    /// Whether the CGB features are enabled
+    pub fn cgb_mode(&self) -> bool {
+        self.io.cgb_mode()
+    }
+
+    /// Whether overlapping sprites are ordered by OAM index, in CGB mode unless OPRI says otherwise
+    pub fn sprite_index_priority(&self) -> bool {
+        self.cgb_mode() && self.read_byte(OPRI) & 0x01 == 0
+    }
+
+    /// Whether the CPU runs at double speed, only possible in CGB mode
+    pub fn double_speed(&self) -> bool {
+        self.io.double_speed()
+    }
+
+    /// Connect a device to the serial port
+    pub fn set_serial_transport(&mut self, transport: Box<dyn SerialTransport>) {
+        self.io.set_serial_transport(transport);
+    }
+
+    pub fn set_ir_transport(&mut self, transport: Box<dyn IrTransport>) {
+        self.io.set_ir_transport(transport);
+    }
+
+    /// Produce `rate` audio samples per emulated second
+    pub fn set_audio_sample_rate(&mut self, rate: u32) {
+        self.io.set_audio_sample_rate(rate);
+    }
+
+    /// Take the audio samples produced since the last call, interleaved left and right
+    pub fn drain_audio_samples(&mut self) -> Vec<i16> {
+        self.io.drain_audio_samples()
+    }
+
+    /// The APU, for channel muting and WAV dumps
+    pub fn apu(&self) -> &Apu {
+        self.io.apu()
+    }
+
+    pub fn apu_mut(&mut self) -> &mut Apu {
+        self.io.apu_mut()
+    }
+
+    /// Press a joypad button, requesting the joypad interrupt if the button is selected
+    pub fn press_button(&mut self, button: Button) {
+        self.io.press_button(button);
+    }
+
+    pub fn release_button(&mut self, button: Button) {
+        self.io.release_button(button);
+    }
+
+    pub fn is_button_pressed(&self, button: Button) -> bool {
+        self.io.is_button_pressed(button)
+    }
+
+    /// Listen for Super Game Boy command packets on the joypad port
+    pub fn set_sgb(&mut self, enabled: bool) {
+        self.io.set_sgb(enabled);
+    }
+
+    /// The Super Game Boy commands received since the last call, each the bytes of its packets
+    pub fn take_sgb_commands(&mut self) -> Vec<Vec<u8>> {
+        self.io.take_sgb_commands()
+    }
+
+    /// Advance the timer, serial port, APU and running transfers by a number of machine cycles
+    ///
+    /// In double speed a machine cycle is 2 T-cycles for the APU, everything else runs at CPU speed
+    pub fn tick(&mut self, cycles: u8) {
+        self.io.tick(u32::from(cycles) * 4);
+
+        for _ in 0..cycles {
+            let Some((source, offset)) = self.dma.next_transfer() else {
+                return;
+            };
+            let value = self.read_mapped(source);
+            self.oam.write(OAM_START as u16 + u16::from(offset), value);
+        }
+    }
+
+    /// Copy the next block of a running HBlank HDMA transfer if it fits in the `dots` left of
+    /// HBlank, called by the PPU when HBlank starts and when a transfer starts during HBlank
+    pub fn hblank(&mut self, dots: u32) {
+        if self.hdma.is_hblank_active() && dots >= BLOCK_DOTS {
+            self.copy_hdma_blocks(1);
+        }
+    }
+
+    /// Whether an HBlank HDMA transfer was started since the last call
+    pub fn take_hdma_start(&mut self) -> bool {
+        core::mem::take(&mut self.hdma_started)
+    }
+
+    /// The machine cycles the CPU is paused for by HDMA transfers since the last call
+    pub fn take_hdma_stall(&mut self) -> u32 {
+        self.hdma.take_stall()
+    }
+
+    /// Copy up to `blocks` blocks of the running HDMA transfer into the current VRAM bank
+    fn copy_hdma_blocks(&mut self, blocks: usize) {
+        let cycles = if self.double_speed() {
+            BLOCK_CYCLES * 2
+        } else {
+            BLOCK_CYCLES
+        };
+        for _ in 0..blocks {
+            let Some((source, destination)) = self.hdma.next_block(cycles) else {
+                return;
+            };
+            for offset in 0..BLOCK_SIZE {
+                let value = self.read_mapped(source.wrapping_add(offset));
+                self.vram.write(destination + offset, value);
+            }
+        }
+    }
+
+    /// Read VRAM bank 0 for the PPU, bypassing the CPU access restrictions
+    pub fn read_vram(&self, adress: u16) -> u8 {
+        self.vram.read_bank(0, adress)
+    }
+
+    /// Read a VRAM bank for the PPU, bank 1 only exists in CGB mode
+    pub fn read_vram_bank(&self, bank: usize, adress: u16) -> u8 {
+        self.vram.read_bank(bank, adress)
+    }
+
+    /// The bank mapped at `address`, numbered as in symbol files: the ROM bank at
+    /// 0x0000-0x7FFF, the VRAM bank and the WRAM bank at 0xD000-0xDFFF, 0 everywhere else
+    pub fn bank(&self, address: u16) -> usize {
+        match usize::from(address) {
+            ROM_00_START..=ROM_NN_END => self.cartridge.rom_bank(address),
+            VRAM_START..=VRAM_END => self.vram.bank(),
+            WRAM_BANKED_START..=WRAM_END => self.wram_banked.bank(),
+            _ => 0,
+        }
+    }
+
+    /// Count the CPU's reads and writes at every address, `None` stops counting
+    pub fn set_heatmap(&mut self, heatmap: Option<Heatmap>) {
+        self.heatmap = heatmap;
+    }
+
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_ref()
+    }
+
+    pub fn heatmap_mut(&mut self) -> Option<&mut Heatmap> {
+        self.heatmap.as_mut()
+    }
+
+    /// Called for every read the CPU makes
+    pub(crate) fn count_read(&mut self, address: u16) {
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_read(address);
+        }
+    }
+
+    /// Called for every write the CPU makes
+    pub(crate) fn count_write(&mut self, address: u16) {
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_write(address);
+        }
+    }
+
+    /// The CGB color palettes, for the PPU
+    pub fn color_palettes(&self) -> &ColorPalettes {
+        self.io.color_palettes()
+    }
+
+    /// Read OAM for the PPU, bypassing the CPU access restrictions
+    pub fn read_oam(&self, adress: u16) -> u8 {
+        self.oam.read(adress)
+    }
+
+    /// Whether the CPU is locked out of the adress by a running OAM DMA transfer
+    fn is_dma_blocked(&self, adress: u16) -> bool {
+        usize::from(adress) < IO_START && self.dma.is_active()
+    }
+
+    /// Lock the CPU out of VRAM and OAM while the PPU uses them, like the hardware does
+    pub fn set_access_blocking(&mut self, enabled: bool) {
+        self.access_blocking = enabled;
+    }
+
+    pub fn access_blocking(&self) -> bool {
+        self.access_blocking
+    }
+
+    /// Emulate the DMG writing 0xFF to STAT for a cycle before every write, see `Ppu::tick`
+    pub fn set_stat_write_bug(&mut self, enabled: bool) {
+        self.stat_write_bug = enabled;
+    }
+
+    pub fn stat_write_bug(&self) -> bool {
+        self.stat_write_bug
+    }
+
+    /// Whether STAT was written with the STAT write bug since the last call
+    pub fn take_stat_write(&mut self) -> bool {
+        core::mem::take(&mut self.stat_written)
+    }
+
+    /// Whether LYC or the STAT interrupt sources were written since the last call, the PPU then
+    /// compares LY and LYC again and checks the STAT interrupt line
+    pub fn take_stat_sources_write(&mut self) -> bool {
+        core::mem::take(&mut self.stat_sources_written)
+    }
+
+    /// Emulate the DMG corrupting OAM on accesses to 0xFE00-0xFEFF during mode 2, see `corrupt_oam`
+    pub fn set_oam_bug(&mut self, enabled: bool) {
+        self.oam_bug = enabled;
+    }
+
+    pub fn oam_bug(&self) -> bool {
+        self.oam_bug
+    }
+
+    /// Note the row of OAM the PPU scans, `None` outside of mode 2
+    pub fn set_oam_scan_row(&mut self, row: Option<u8>) {
+        self.oam_scan_row = row;
+    }
+
+    /// Corrupt the OAM row the PPU is scanning, if `adress` in 0xFE00-0xFEFF is put on the bus
+    /// during mode 2
+    ///
+    /// OAM is 20 rows of four 16-bit words. A write or an increment or decrement mixes the first
+    /// word of the row with the first and third word of the row before, a read does the same
+    /// differently, and the rest of the row before is copied over. The first row is never
+    /// corrupted and the CGB does not have the bug.
+    pub fn corrupt_oam(&mut self, adress: u16, access: OamAccess) {
+        if !self.oam_bug || self.cgb_mode() || !(0xFE00..=0xFEFF).contains(&adress) {
+            return;
+        }
+        let Some(row) = self.oam_scan_row.filter(|row| *row > 0 && *row < 20) else {
+            return;
+        };
+        tracing::trace!(target: "ppu", "OAM bug {access:?} of {adress:#06X} corrupts row {row}");
+
+        let start = OAM_START as u16 + u16::from(row) * 8;
+        let previous = start - 8;
+        let word = |memory: &Memory, adress: u16| {
+            combine(memory.oam.read(adress + 1), memory.oam.read(adress))
+        };
+        let a = word(self, start);
+        let b = word(self, previous);
+        let c = word(self, previous + 4);
+        let first = match access {
+            OamAccess::Write => ((a ^ c) & (b ^ c)) ^ c,
+            OamAccess::Read => b | (a & c),
+        };
+        self.oam.write(start, first as u8);
+        self.oam.write(start + 1, (first >> 8) as u8);
+        for offset in 2..8 {
+            let value = self.oam.read(previous + offset);
+            self.oam.write(start + offset, value);
+        }
+    }
+
+    /// Whether the CPU is locked out of the adress by the PPU, going by the mode in STAT
+    fn is_ppu_blocked(&self, adress: u16) -> bool {
+        if !self.access_blocking {
+            return false;
+        }
+        let mode = self.io.read(STAT) & stat::MODE;
+        match usize::from(adress) {
+            VRAM_START..=VRAM_END => mode == 3,
+            OAM_START..=OAM_END => mode == 2 || mode == 3,
+            _ => false,
+        }
+    }
+
+    /// The handler for an adress, None for the unusable region
+    ///
+    /// Echo RAM adresses have to be resolved with `resolve_echo` first
+    fn region(&self, adress: u16) -> Option<&dyn MemoryRegion> {
+        match usize::from(adress) {
+            ROM_00_START..=ROM_00_END | ROM_NN_START..=ROM_NN_END => Some(&self.cartridge),
+            VRAM_START..=VRAM_END => Some(&self.vram),
+            EXRAM_START..=EXRAM_END => Some(&self.cartridge),
+            WRAM_START..WRAM_BANKED_START => Some(&self.wram),
+            WRAM_BANKED_START..=WRAM_END => Some(&self.wram_banked),
+            OAM_START..=OAM_END => Some(&self.oam),
+            UNUSABLE_START..=UNUSABLE_END => None,
+            IO_START..=IO_END | IE_ADDRESS => Some(&self.io),
+            HRAM_START..=HRAM_END => Some(&self.hram),
+            ECHO_RAM_START..=ECHO_RAM_END => unreachable!("echo RAM is resolved before decoding"),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Like `region`, for writes
+    fn region_mut(&mut self, adress: u16) -> Option<&mut dyn MemoryRegion> {
+        match usize::from(adress) {
+            ROM_00_START..=ROM_00_END | ROM_NN_START..=ROM_NN_END => Some(&mut self.cartridge),
+            VRAM_START..=VRAM_END => Some(&mut self.vram),
+            EXRAM_START..=EXRAM_END => Some(&mut self.cartridge),
+            WRAM_START..WRAM_BANKED_START => Some(&mut self.wram),
+            WRAM_BANKED_START..=WRAM_END => Some(&mut self.wram_banked),
+            OAM_START..=OAM_END => Some(&mut self.oam),
+            UNUSABLE_START..=UNUSABLE_END => None,
+            IO_START..=IO_END | IE_ADDRESS => Some(&mut self.io),
+            HRAM_START..=HRAM_END => Some(&mut self.hram),
+            ECHO_RAM_START..=ECHO_RAM_END => unreachable!("echo RAM is resolved before decoding"),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn read_byte(&self, adress: u16) -> u8 {
+        if self.is_dma_blocked(adress) || self.is_ppu_blocked(adress) {
+            return 0xFF;
+        }
+        self.read_mapped(adress)
+    }
+
+    fn read_mapped(&self, adress: u16) -> u8 {
+        if usize::from(adress) < BOOT_ROM_SIZE {
+            if let Some(boot_rom) = &self.boot_rom {
+                return boot_rom[usize::from(adress)];
+            }
+        }
+
+        if self.cgb_mode() {
+            match adress {
+                VBK => return 0xFE | self.vram.bank() as u8,
+                SVBK => return 0xF8 | (self.io.read(SVBK) & 0x07),
+                HDMA1..=HDMA4 => return 0xFF,
+                HDMA5 => return self.hdma.read(),
+                _ => {}
+            }
+        }
+
+        let adress = resolve_echo(adress);
+        self.region(adress)
+            .map_or(0xFF, |region| region.read(adress))
+    }
+
+    pub fn write_byte(&mut self, adress: u16, value: u8) {
+        if self.is_dma_blocked(adress) || self.is_ppu_blocked(adress) {
+            return;
+        }
+        if adress == DMA {
+            self.dma.start(value);
+        }
+        if adress == BOOT && value != 0 {
+            self.boot_rom = None;
+        }
+        if adress == STAT && self.stat_write_bug && !self.cgb_mode() {
+            self.stat_written = true;
+        }
+        if adress == STAT || adress == LYC {
+            self.stat_sources_written = true;
+        }
+        if self.cgb_mode() {
+            match adress {
+                VBK => self.vram.set_bank(usize::from(value & 0x01)),
+                // bank 0 selects bank 1
+                SVBK => self.wram_banked.set_bank(usize::from(value & 0x07).max(1)),
+                HDMA1..=HDMA5 => {
+                    if self.hdma.write(adress, value) {
+                        self.copy_hdma_blocks(usize::MAX);
+                    } else if adress == HDMA5 && self.hdma.is_hblank_active() {
+                        self.hdma_started = true;
+                    }
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let adress = resolve_echo(adress);
+        if let Some(region) = self.region_mut(adress) {
+            region.write(adress, value);
+        }
+    }
+
+    pub fn read_word(&self, adress: u16) -> u16 {
+        combine(
+            self.read_byte(adress.wrapping_add(1)),
+            self.read_byte(adress),
+        )
+    }
+
+    pub fn write_word(&mut self, adress: u16, value: u16) {
+        MemoryBus::write_word(self, adress, value)
+    }
+}
+
+/// Echo RAM at 0xE000-0xFDFF mirrors WRAM at 0xC000-0xDDFF
+fn resolve_echo(adress: u16) -> u16 {
+    match usize::from(adress) {
+        ECHO_RAM_START..=ECHO_RAM_END => adress - ECHO_RAM_OFFSET,
+        _ => adress,
+    }
+}
+
+impl MemoryBus for Memory {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        self.count_read(address);
+        Memory::read_byte(self, address)
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.count_write(address);
+        Memory::write_byte(self, address, value)
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        Memory::read_byte(self, address)
+    }
+
+    fn bank(&self, address: u16) -> usize {
+        Memory::bank(self, address)
+    }
+
+    fn switch_speed(&mut self) -> bool {
+        self.io.switch_speed()
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for Memory {
+    /// The boot ROM itself is not saved, only whether it is still mapped
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.boot_rom.is_some());
+        self.cartridge.save_state(state);
+        self.vram.save_state(state);
+        self.wram.save_state(state);
+        self.wram_banked.save_state(state);
+        self.oam.save_state(state);
+        self.io.save_state(state);
+        self.hram.save_state(state);
+        self.dma.save_state(state);
+        self.hdma.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let boot_rom_mapped = state.read_bool()?;
+        let boot_rom = &mut self.boot_rom;
+        if boot_rom_mapped && boot_rom.is_none() {
+            return Err(SaveStateError::InvalidData(
+                "boot ROM is mapped but not loaded",
+            ));
+        }
+        if !boot_rom_mapped {
+            *boot_rom = None;
+        }
+
+        self.cartridge.load_state(state)?;
+        self.vram.load_state(state)?;
+        self.wram.load_state(state)?;
+        self.wram_banked.load_state(state)?;
+        self.oam.load_state(state)?;
+        self.io.load_state(state)?;
+        self.hram.load_state(state)?;
+        self.dma.load_state(state)?;
+        self.hdma.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{
+        hdma::{HDMA2, HDMA3},
+        interrupts::Interrupt,
+        joypad::P1,
+        serial::{SB, SC},
+        speed::KEY1,
+        timer::TAC,
+    };
+
+    #[test]
+    fn test_read_write() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x0000, 0xAB);
+        assert_eq!(memory.read_byte(0x0000), 0xAB);
+    }
+
+    #[test]
+    fn test_read_write_word() {
+        let mut memory = Memory::new();
+        memory.write_word(0x0000, 0xABCD);
+        assert_eq!(memory.read_word(0x0000), 0xABCD);
+    }
+
+    #[test]
+    fn test_unusable_region() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xFEA0, 0x12);
+
+        assert_eq!(memory.read_byte(0xFEA0), 0xFF);
+        assert_eq!(memory.read_byte(0xFEFF), 0xFF);
+    }
+
+    #[test]
+    fn test_echo_ram() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC123, 0x12);
+        assert_eq!(memory.read_byte(0xE123), 0x12);
+
+        memory.write_byte(0xFDFF, 0x34);
+        assert_eq!(memory.read_byte(0xDDFF), 0x34);
+
+        // the last 512 bytes of WRAM are not mirrored
+        memory.write_byte(0xDE00, 0x56);
+        assert_eq!(memory.read_byte(0xFE00), 0x00);
+    }
+
+    #[test]
+    fn test_resolve_echo() {
+        assert_eq!(resolve_echo(0xE000), 0xC000);
+        assert_eq!(resolve_echo(0xFDFF), 0xDDFF);
+        assert_eq!(resolve_echo(0xDFFF), 0xDFFF);
+        assert_eq!(resolve_echo(0xFE00), 0xFE00);
+    }
+
+    #[test]
+    fn test_oam_dma_from_echo_ram() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC205, 0x12);
+        memory.write_byte(DMA, 0xE2);
+
+        memory.tick(160);
+        assert_eq!(memory.read_oam(0xFE05), 0x12);
+    }
+
+    #[test]
+    fn test_memory_bus() {
+        fn write_through_bus(bus: &mut impl MemoryBus) {
+            bus.write_word(0xC000, 0xABCD);
+        }
+
+        let mut memory = Memory::new();
+        write_through_bus(&mut memory);
+        assert_eq!(memory.read_byte(0xC000), 0xCD);
+        assert_eq!(memory.read_word(0xC000), 0xABCD);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut memory = Memory::new();
+        for offset in 0..0xA0 {
+            memory.write_byte(0xC100 + offset, offset as u8);
+        }
+        memory.write_byte(DMA, 0xC1);
+        assert_eq!(memory.read_byte(DMA), 0xC1);
+
+        memory.tick(10);
+        assert_eq!(memory.read_oam(0xFE09), 0x09);
+        assert_eq!(memory.read_oam(0xFE0A), 0x00);
+
+        memory.tick(150);
+        assert_eq!(memory.read_oam(0xFE9F), 0x9F);
+        assert_eq!(memory.read_byte(0xFE50), 0x50);
+    }
+
+    #[test]
+    fn test_ppu_access_blocking() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x8000, 0x12);
+        memory.write_byte(0xFE00, 0x34);
+        memory.set_access_blocking(true);
+
+        // OAM scan
+        memory.set_io_register(STAT, 2);
+        assert_eq!(memory.read_byte(0x8000), 0x12);
+        assert_eq!(memory.read_byte(0xFE00), 0xFF);
+        memory.write_byte(0xFE00, 0x56);
+        assert_eq!(memory.read_oam(0xFE00), 0x34);
+
+        // drawing
+        memory.set_io_register(STAT, 3);
+        assert_eq!(memory.read_byte(0x8000), 0xFF);
+        memory.write_byte(0x8000, 0x56);
+        assert_eq!(memory.read_vram(0x8000), 0x12);
+        assert_eq!(memory.read_byte(0xFE00), 0xFF);
+
+        // HBlank
+        memory.set_io_register(STAT, 0);
+        assert_eq!(memory.read_byte(0x8000), 0x12);
+        assert_eq!(memory.read_byte(0xFE00), 0x34);
+
+        memory.set_io_register(STAT, 3);
+        memory.set_access_blocking(false);
+        assert_eq!(memory.read_byte(0x8000), 0x12);
+    }
+
+    #[test]
+    fn test_oam_dma_blocks_cpu() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC000, 0x12);
+        memory.write_byte(0xFF80, 0x34);
+        memory.write_byte(DMA, 0xC0);
+
+        assert_eq!(memory.read_byte(0xC000), 0xFF);
+        memory.write_byte(0xC000, 0x56);
+        assert_eq!(memory.read_byte(0xFF80), 0x34);
+        memory.write_byte(0xFF81, 0x78);
+        assert_eq!(memory.read_byte(0xFF81), 0x78);
+
+        memory.tick(160);
+        assert_eq!(memory.read_byte(0xC000), 0x12);
+    }
+
+    #[test]
+    fn test_timer_interrupt() {
+        let mut memory = Memory::new();
+        memory.write_byte(TAC, 0b101);
+        memory.write_byte(0xFF05, 0xFF);
+
+        memory.tick(4);
+        assert_eq!(memory.read_byte(0xFF0F) & Interrupt::Timer.bit(), 0);
+        memory.tick(1);
+        assert_ne!(memory.read_byte(0xFF0F) & Interrupt::Timer.bit(), 0);
+    }
+
+    #[test]
+    fn test_joypad() {
+        let mut memory = Memory::new();
+        memory.write_byte(P1, 0x10);
+        memory.press_button(Button::A);
+
+        assert_eq!(memory.read_byte(P1), 0xDE);
+        assert_ne!(memory.read_byte(0xFF0F) & Interrupt::Joypad.bit(), 0);
+
+        memory.release_button(Button::A);
+        assert_eq!(memory.read_byte(P1), 0xDF);
+    }
+
+    #[test]
+    fn test_serial_interrupt() {
+        let mut memory = Memory::new();
+        memory.write_byte(SB, 0x42);
+        memory.write_byte(SC, 0x81);
+
+        // 8 bits at 128 machine cycles each
+        for _ in 0..1023 {
+            memory.tick(1);
+        }
+        assert_eq!(memory.read_byte(0xFF0F) & Interrupt::Serial.bit(), 0);
+        memory.tick(1);
+        assert_ne!(memory.read_byte(0xFF0F) & Interrupt::Serial.bit(), 0);
+        assert_eq!(memory.read_byte(SB), 0xFF);
+    }
+
+    #[test]
+    fn test_boot_rom() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x0000, 0x12);
+        memory.write_byte(0x0100, 0x34);
+        assert!(memory.load_boot_rom(vec![0xAA; 0x80]).is_err());
+        memory.load_boot_rom(vec![0xAA; BOOT_ROM_SIZE]).unwrap();
+
+        assert_eq!(memory.read_byte(0x0000), 0xAA);
+        assert_eq!(memory.read_byte(0x0100), 0x34);
+
+        memory.write_byte(BOOT, 0x00);
+        assert_eq!(memory.read_byte(0x0000), 0xAA);
+        memory.write_byte(BOOT, 0x01);
+        assert_eq!(memory.read_byte(0x0000), 0x12);
+    }
+
+    #[test]
+    fn test_skip_boot() {
+        let mut memory = Memory::new();
+        memory.skip_boot();
+
+        assert_eq!(memory.read_byte(0xFF04), 0xAB);
+        assert_eq!(memory.read_byte(0xFF26), 0xF0);
+        assert_eq!(memory.read_byte(0xFF40), 0x91);
+        assert_eq!(memory.read_byte(0xFF47), 0xFC);
+    }
+
+    #[test]
+    fn test_cartridge_mapping() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x13; // MBC3 + RAM + battery
+        rom[0x0148] = 0x01;
+        rom[0x0149] = 0x02;
+        rom[0xC000] = 0xAB; // bank 3
+        let mut memory = Memory::new();
+        memory.load_cartridge(Cartridge::new(rom).unwrap());
+
+        memory.write_byte(0x2000, 0x03);
+        assert_eq!(memory.read_byte(0x4000), 0xAB);
+
+        memory.write_byte(0x0000, 0x0A);
+        memory.write_byte(0xA000, 0x12);
+        assert_eq!(memory.read_byte(0xA000), 0x12);
+
+        assert!(memory.eject_cartridge().is_some());
+        assert_eq!(memory.read_byte(0x4000), 0x00);
+    }
+
+    fn cgb_memory() -> Memory {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        let mut memory = Memory::new();
+        memory.load_cartridge(Cartridge::new(rom).unwrap());
+        memory
+    }
+
+    #[test]
+    fn test_vram_banks() {
+        let mut memory = cgb_memory();
+        memory.write_byte(0x8000, 0x12);
+        memory.write_byte(VBK, 0x01);
+        memory.write_byte(0x8000, 0x34);
+
+        assert_eq!(memory.read_byte(VBK), 0xFF);
+        assert_eq!(memory.read_byte(0x8000), 0x34);
+        assert_eq!(memory.read_vram(0x8000), 0x12);
+        assert_eq!(memory.read_vram_bank(1, 0x8000), 0x34);
+    }
+
+    #[test]
+    fn test_wram_banks() {
+        let mut memory = cgb_memory();
+        memory.write_byte(0xD000, 0x12);
+        memory.write_byte(SVBK, 0x03);
+        memory.write_byte(0xD000, 0x34);
+        memory.write_byte(0xC000, 0x56);
+
+        assert_eq!(memory.read_byte(SVBK), 0xFB);
+        assert_eq!(memory.read_byte(0xF000), 0x34);
+        memory.write_byte(SVBK, 0x00);
+        assert_eq!(memory.read_byte(0xD000), 0x12);
+        assert_eq!(memory.read_byte(0xC000), 0x56);
+    }
+
+    #[test]
+    fn test_general_purpose_hdma() {
+        let mut memory = cgb_memory();
+        for offset in 0..0x20 {
+            memory.write_byte(0xC100 + offset, offset as u8);
+        }
+        memory.write_byte(VBK, 0x01);
+        memory.write_byte(HDMA1, 0xC1);
+        memory.write_byte(HDMA2, 0x00);
+        memory.write_byte(HDMA3, 0x10);
+        memory.write_byte(HDMA4, 0x00);
+        memory.write_byte(HDMA5, 0x01);
+
+        assert_eq!(memory.read_vram_bank(1, 0x9000), 0x00);
+        assert_eq!(memory.read_vram_bank(1, 0x901F), 0x1F);
+        assert_eq!(memory.read_vram(0x901F), 0x00);
+        assert_eq!(memory.read_byte(HDMA5), 0xFF);
+        assert_eq!(memory.take_hdma_stall(), 16);
+    }
+
+    #[test]
+    fn test_hblank_hdma() {
+        let mut memory = cgb_memory();
+        memory.write_byte(0xC010, 0x12);
+        memory.write_byte(HDMA1, 0xC0);
+        memory.write_byte(HDMA2, 0x00);
+        memory.write_byte(HDMA3, 0x00);
+        memory.write_byte(HDMA4, 0x00);
+        memory.write_byte(HDMA5, 0x81);
+        assert_eq!(memory.take_hdma_stall(), 0);
+        assert!(memory.take_hdma_start());
+
+        memory.hblank(BLOCK_DOTS);
+        assert_eq!(memory.read_byte(HDMA5), 0x00);
+        // too little of HBlank left for a block
+        memory.hblank(BLOCK_DOTS - 1);
+        assert_eq!(memory.read_byte(HDMA5), 0x00);
+        memory.hblank(BLOCK_DOTS);
+        assert_eq!(memory.read_byte(0x8010), 0x12);
+        assert_eq!(memory.read_byte(HDMA5), 0xFF);
+        assert_eq!(memory.take_hdma_stall(), 16);
+    }
+
+    #[test]
+    fn test_no_banking_on_dmg() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x8000, 0x12);
+        memory.write_byte(0xD000, 0x34);
+        memory.write_byte(VBK, 0x01);
+        memory.write_byte(SVBK, 0x02);
+
+        assert_eq!(memory.read_byte(0x8000), 0x12);
+        assert_eq!(memory.read_byte(0xD000), 0x34);
+    }
+
+    #[test]
+    fn test_cgb_mode_from_header() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        let mut memory = Memory::new();
+        assert!(!memory.cgb_mode());
+
+        memory.load_cartridge(Cartridge::new(rom).unwrap());
+        assert!(memory.cgb_mode());
+        memory.write_byte(KEY1, 0x01);
+        assert!(MemoryBus::switch_speed(&mut memory));
+        assert!(memory.double_speed());
+
+        memory.eject_cartridge();
+        assert!(!memory.cgb_mode());
+        assert!(!memory.double_speed());
+    }
+
+    #[test]
+    fn test_opri() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        let mut memory = Memory::new();
+        memory.write_byte(OPRI, 0x00);
+        assert_eq!(memory.read_byte(OPRI), 0xFF);
+        assert!(!memory.sprite_index_priority());
+
+        memory.load_cartridge(Cartridge::new(rom).unwrap());
+        assert_eq!(memory.read_byte(OPRI), 0xFE);
+        assert!(memory.sprite_index_priority());
+        memory.write_byte(OPRI, 0x01);
+        assert_eq!(memory.read_byte(OPRI), 0xFF);
+        assert!(!memory.sprite_index_priority());
+    }
+
+    #[test]
+    fn test_oam_bug() {
+        let mut memory = Memory::new();
+        for (index, adress) in (0xFE00..=0xFE9F).enumerate() {
+            memory.write_byte(adress, index as u8);
+        }
+        memory.set_oam_scan_row(Some(2));
+        memory.corrupt_oam(0xFE40, OamAccess::Write);
+        assert_eq!(memory.read_byte(0xFE10), 0x10, "off by default");
+
+        memory.set_oam_bug(true);
+        memory.corrupt_oam(0xFE40, OamAccess::Write);
+        // a = 0x1110, b = 0x0908, c = 0x0D0C: ((a ^ c) & (b ^ c)) ^ c
+        assert_eq!(memory.read_byte(0xFE10), 0x08);
+        assert_eq!(memory.read_byte(0xFE11), 0x09);
+        assert_eq!(memory.read_byte(0xFE12), 0x0A);
+        assert_eq!(memory.read_byte(0xFE17), 0x0F);
+        assert_eq!(memory.read_byte(0xFE18), 0x18);
+
+        memory.set_oam_scan_row(Some(3));
+        memory.corrupt_oam(0xFE00, OamAccess::Read);
+        // a = 0x1918, b = 0x0908, c = 0x0D0C: b | (a & c)
+        assert_eq!(memory.read_byte(0xFE18), 0x08);
+        assert_eq!(memory.read_byte(0xFE19), 0x09);
+        assert_eq!(memory.read_byte(0xFE1A), 0x0A);
+
+        let before: Vec<u8> = (0xFE00..=0xFE9F).map(|a| memory.read_byte(a)).collect();
+        memory.corrupt_oam(0xFF00, OamAccess::Write);
+        memory.set_oam_scan_row(Some(0));
+        memory.corrupt_oam(0xFE00, OamAccess::Write);
+        memory.set_oam_scan_row(None);
+        memory.corrupt_oam(0xFE00, OamAccess::Write);
+        let after: Vec<u8> = (0xFE00..=0xFE9F).map(|a| memory.read_byte(a)).collect();
+        assert_eq!(before, after);
+    }
+}
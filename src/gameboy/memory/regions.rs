@@ -0,0 +1,253 @@
+//! Handlers for the regions of the address space, each receives the full bus address.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    gameboy::{
+        cartridge::{Cartridge, RomPatch},
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+use super::{EXRAM_START, ROM_00_START, ROM_NN_START};
+
+pub trait MemoryRegion {
+    fn read(&self, address: u16) -> u8;
+
+    fn write(&mut self, address: u16, value: u8);
+}
+
+/// Plain RAM starting at `start`
+pub struct Ram<const SIZE: usize> {
+    start: u16,
+    bytes: Box<[u8; SIZE]>,
+}
+
+impl<const SIZE: usize> Ram<SIZE> {
+    pub fn new(start: u16) -> Ram<SIZE> {
+        Ram {
+            start,
+            // through a Vec, so the array is not built on the stack first
+            bytes: vec![0; SIZE].try_into().unwrap(),
+        }
+    }
+
+    fn index(&self, address: u16) -> usize {
+        usize::from(address - self.start)
+    }
+}
+
+impl<const SIZE: usize> MemoryRegion for Ram<SIZE> {
+    fn read(&self, address: u16) -> u8 {
+        self.bytes[self.index(address)]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let index = self.index(address);
+        self.bytes[index] = value;
+    }
+}
+
+/// RAM with `BANKS` switchable banks of `SIZE` bytes starting at `start`
+pub struct BankedRam<const SIZE: usize, const BANKS: usize> {
+    start: u16,
+    bytes: Vec<u8>,
+    bank: usize,
+}
+
+impl<const SIZE: usize, const BANKS: usize> BankedRam<SIZE, BANKS> {
+    pub fn new(start: u16, bank: usize) -> BankedRam<SIZE, BANKS> {
+        BankedRam {
+            start,
+            bytes: vec![0; SIZE * BANKS],
+            bank,
+        }
+    }
+
+    /// The bank mapped for the CPU
+    pub fn bank(&self) -> usize {
+        self.bank
+    }
+
+    pub fn set_bank(&mut self, bank: usize) {
+        self.bank = bank % BANKS;
+    }
+
+    /// Read from a bank other than the mapped one
+    pub fn read_bank(&self, bank: usize, address: u16) -> u8 {
+        self.bytes[bank * SIZE + usize::from(address - self.start)]
+    }
+
+    fn index(&self, address: u16) -> usize {
+        self.bank() * SIZE + usize::from(address - self.start)
+    }
+}
+
+impl<const SIZE: usize, const BANKS: usize> MemoryRegion for BankedRam<SIZE, BANKS> {
+    fn read(&self, address: u16) -> u8 {
+        self.bytes[self.index(address)]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let index = self.index(address);
+        self.bytes[index] = value;
+    }
+}
+
+/// The cartridge slot, ROM at 0x0000-0x7FFF and external RAM at 0xA000-0xBFFF
+///
+/// Without a cartridge both are backed by plain RAM
+pub struct CartridgeSlot {
+    cartridge: Option<Cartridge>,
+    rom: Ram<0x8000>,
+    ram: Ram<0x2000>,
+}
+
+impl CartridgeSlot {
+    pub fn new() -> CartridgeSlot {
+        CartridgeSlot {
+            cartridge: None,
+            rom: Ram::new(ROM_00_START as u16),
+            ram: Ram::new(EXRAM_START as u16),
+        }
+    }
+
+    pub fn insert(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
+    }
+
+    pub fn eject(&mut self) -> Option<Cartridge> {
+        self.cartridge.take()
+    }
+
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.set_rom_patches(patches);
+        }
+    }
+
+    /// The ROM bank mapped at `address` in 0x0000-0x7FFF, without a cartridge 0 and 1
+    pub fn rom_bank(&self, address: u16) -> usize {
+        match &self.cartridge {
+            Some(cartridge) => cartridge.rom_bank(address),
+            None => usize::from(usize::from(address) >= ROM_NN_START),
+        }
+    }
+}
+
+impl MemoryRegion for CartridgeSlot {
+    fn read(&self, address: u16) -> u8 {
+        let is_rom = usize::from(address) < EXRAM_START;
+        match (&self.cartridge, is_rom) {
+            (Some(cartridge), true) => cartridge.read_rom(address),
+            (Some(cartridge), false) => cartridge.read_ram(address),
+            (None, true) => self.rom.read(address),
+            (None, false) => self.ram.read(address),
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let is_rom = usize::from(address) < EXRAM_START;
+        match (&mut self.cartridge, is_rom) {
+            (Some(cartridge), true) => cartridge.write_rom(address, value),
+            (Some(cartridge), false) => cartridge.write_ram(address, value),
+            (None, true) => self.rom.write(address, value),
+            (None, false) => self.ram.write(address, value),
+        }
+    }
+}
+
+impl<const SIZE: usize> SaveState for Ram<SIZE> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&*self.bytes);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut *self.bytes)
+    }
+}
+
+impl<const SIZE: usize, const BANKS: usize> SaveState for BankedRam<SIZE, BANKS> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.bytes);
+        state.write_u8(self.bank() as u8);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.bytes)?;
+        let bank = usize::from(state.read_u8()?);
+        if bank >= BANKS {
+            return Err(SaveStateError::InvalidData("RAM bank"));
+        }
+        self.bank = bank;
+        Ok(())
+    }
+}
+
+impl SaveState for CartridgeSlot {
+    /// Without a cartridge the plain RAM standing in for it is saved instead
+    fn save_state(&self, state: &mut StateWriter) {
+        match &self.cartridge {
+            Some(cartridge) => {
+                state.write_bool(true);
+                cartridge.save_state(state);
+            }
+            None => {
+                state.write_bool(false);
+                self.rom.save_state(state);
+                self.ram.save_state(state);
+            }
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        let has_cartridge = state.read_bool()?;
+        match &mut self.cartridge {
+            Some(cartridge) if has_cartridge => cartridge.load_state(state),
+            None if !has_cartridge => {
+                self.rom.load_state(state)?;
+                self.ram.load_state(state)
+            }
+            _ => Err(SaveStateError::InvalidData("cartridge presence")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ram() {
+        let mut ram: Ram<0x7F> = Ram::new(0xFF80);
+        ram.write(0xFF80, 0x12);
+        ram.write(0xFFFE, 0x34);
+
+        assert_eq!(ram.read(0xFF80), 0x12);
+        assert_eq!(ram.read(0xFFFE), 0x34);
+    }
+
+    #[test]
+    fn test_banked_ram() {
+        let mut ram: BankedRam<0x2000, 2> = BankedRam::new(0x8000, 0);
+        ram.write(0x8000, 0x12);
+        ram.set_bank(1);
+        ram.write(0x8000, 0x34);
+
+        assert_eq!(ram.read(0x8000), 0x34);
+        assert_eq!(ram.read_bank(0, 0x8000), 0x12);
+        ram.set_bank(2);
+        assert_eq!(ram.bank(), 0);
+    }
+
+    #[test]
+    fn test_empty_cartridge_slot() {
+        let mut slot = CartridgeSlot::new();
+        slot.write(0x4000, 0x12);
+        slot.write(0xA000, 0x34);
+
+        assert_eq!(slot.read(0x4000), 0x12);
+        assert_eq!(slot.read(0xA000), 0x34);
+    }
+}
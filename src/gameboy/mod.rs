@@ -1,5 +1,1407 @@
+mod apu;
+mod boot;
+mod cartridge;
+mod cheats;
+#[cfg(feature = "std")]
+mod core_dump;
 mod cpu;
+mod debugger;
+mod dma;
+mod expression;
+mod hdma;
+#[cfg(feature = "std")]
+mod headless;
+mod heatmap;
+mod infrared;
+mod input_map;
+mod interrupts;
+mod joypad;
+#[cfg(feature = "std")]
+mod library;
+#[cfg(feature = "std")]
+mod link;
 mod memory;
+mod movie;
+#[cfg(feature = "std")]
+mod netplay;
+mod observer;
+mod post_process;
+mod ppu;
+mod profiler;
+#[cfg(feature = "std")]
+mod recording;
+mod rewind;
+mod save_state;
+mod scheduler;
+mod search;
+mod serial;
+mod sgb;
+mod speed;
+mod symbols;
+mod timer;
 
-pub use cpu::Cpu;
-pub use memory::Memory;
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{io, path::Path};
+
+use crate::utils::{
+    BootRomError, CartridgeError, CheatError, EmuError, MovieError, SaveStateError, Speed,
+};
+#[cfg(feature = "std")]
+use crate::utils::{RecordingError, ScreenshotError};
+
+use ppu::Mode;
+use save_state::{SaveState, StateReader, StateWriter};
+use sgb::Sgb;
+
+#[cfg(feature = "std")]
+pub use apu::WavSource;
+pub use apu::{Channel, SAMPLE_RATE};
+#[cfg(feature = "std")]
+pub use cartridge::read_rom_file;
+pub use cartridge::{
+    fix_header_checksum, header_checksum, test_rom, Cartridge, Header, RomPatch, NINTENDO_LOGO,
+};
+pub use cheats::{Cheat, CheatCode, CheatEngine};
+#[cfg(feature = "std")]
+pub use core_dump::CoreDump;
+pub use cpu::{
+    Cond, Cpu, DisassembledInstruction, Disassembler, IllegalOpcodePolicy, Instruction,
+    InstructionHistory, Instructions, Register16, StepResult, B3, HISTORY_LENGTH, R16, R16MEM,
+    R16STK, R8, TGT3,
+};
+pub use debugger::{Debugger, StopReason, WatchKind};
+pub use expression::Expression;
+#[cfg(feature = "std")]
+pub use headless::{hash_frame, run_headless, HeadlessRun};
+pub use heatmap::Heatmap;
+#[cfg(feature = "std")]
+pub use infrared::LocalIr;
+pub use infrared::{IrLoopback, IrTransport, NoLight};
+pub use input_map::{Binding, Input, InputMap, TURBO_PERIOD};
+pub use interrupts::Interrupt;
+pub use joypad::Button;
+#[cfg(feature = "std")]
+pub use library::{rom_hash, Game, GameConfig, Library, RecentGame, MAX_RECENT};
+#[cfg(feature = "std")]
+pub use link::{TcpLink, REPLY_TIMEOUT};
+pub use memory::{Memory, MemoryAccess, MemoryBus, OamAccess, TimedBus};
+pub use movie::{InputPlayer, InputRecorder, Movie};
+#[cfg(feature = "std")]
+pub use netplay::{Netplay, CHECKSUM_INTERVAL};
+pub use observer::Observer;
+pub use post_process::{PostProcessor, GRID_SCALE};
+pub use ppu::{
+    DebugImage, DmgPalette, Frame, HBlankTiming, OamEntry, Ppu, Renderer, TileMap, SCREEN_HEIGHT,
+    SCREEN_WIDTH,
+};
+pub use profiler::{ProfileEntry, Profiler};
+#[cfg(feature = "std")]
+pub use recording::{VideoFormat, VideoRecorder};
+pub use rewind::Rewind;
+pub use scheduler::{
+    AccuracyProfile, Scheduler, T_CYCLES_PER_FRAME, T_CYCLES_PER_M_CYCLE,
+    T_CYCLES_PER_M_CYCLE_DOUBLE_SPEED,
+};
+pub use search::{Comparison, MemorySearch, WatchChange, WatchList};
+#[cfg(feature = "std")]
+pub use serial::{CaptureTransport, LocalLink, StdoutTransport};
+pub use serial::{NullTransport, SerialTransport};
+pub use symbols::Symbols;
+
+/// A complete Game Boy, owning the CPU, memory (with the peripherals mapped into it) and the PPU
+pub struct GameBoy {
+    cpu: Cpu,
+    memory: Memory,
+    ppu: Ppu,
+    scheduler: Scheduler,
+    palette: DmgPalette,
+    sgb: Option<Sgb>,
+    rewind: Option<Rewind>,
+    cheats: CheatEngine,
+    observers: Vec<Box<dyn Observer + Send>>,
+    #[cfg(feature = "std")]
+    recorder: Option<VideoRecorder>,
+    movie: Option<MovieMode>,
+    ram_watches: WatchList,
+    watch_changes: Vec<WatchChange>, // during the last frame
+    profiler: Option<Profiler>,
+}
+
+/// What happens to the joypad at the start of each frame
+enum MovieMode {
+    Recording(InputRecorder),
+    Playing(InputPlayer),
+}
+
+impl GameBoy {
+    /// A Game Boy in the state the boot ROM leaves it in, ready to run a cartridge
+    pub fn new() -> GameBoy {
+        let mut memory = Memory::new();
+        memory.skip_boot();
+        GameBoy {
+            cpu: Cpu::new(),
+            memory,
+            ppu: Ppu::new(),
+            scheduler: Scheduler::new(),
+            palette: DmgPalette::default(),
+            sgb: None,
+            rewind: None,
+            cheats: CheatEngine::new(),
+            observers: Vec::new(),
+            #[cfg(feature = "std")]
+            recorder: None,
+            movie: None,
+            ram_watches: WatchList::new(),
+            watch_changes: Vec::new(),
+            profiler: None,
+        }
+    }
+
+    /// A Game Boy that starts by running the boot ROM
+    pub fn with_boot_rom(boot_rom: Vec<u8>) -> Result<GameBoy, BootRomError> {
+        let mut memory = Memory::new();
+        memory.load_boot_rom(boot_rom)?;
+        Ok(GameBoy {
+            cpu: Cpu::power_on(),
+            memory,
+            ppu: Ppu::new(),
+            scheduler: Scheduler::new(),
+            palette: DmgPalette::default(),
+            sgb: None,
+            rewind: None,
+            cheats: CheatEngine::new(),
+            observers: Vec::new(),
+            #[cfg(feature = "std")]
+            recorder: None,
+            movie: None,
+            ram_watches: WatchList::new(),
+            watch_changes: Vec::new(),
+            profiler: None,
+        })
+    }
+
+    /// Create a cartridge from the contents of a ROM and insert it, failing on ROMs the boot ROM
+    /// would reject (see `Header::validate`)
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), CartridgeError> {
+        Header::validate(&rom)?;
+        self.load_cartridge(Cartridge::new(rom)?);
+        Ok(())
+    }
+
+    /// Insert a cartridge, a CGB cartridge switches the Game Boy to CGB mode and any other
+    /// cartridge supporting the Super Game Boy to SGB mode
+    pub fn load_cartridge(&mut self, cartridge: Cartridge) {
+        let cgb = cartridge.header().supports_cgb();
+        let sgb = cartridge.header().supports_sgb() && !cgb;
+        self.sgb = sgb.then(Sgb::new);
+        self.memory.set_sgb(sgb);
+        self.memory.load_cartridge(cartridge);
+        self.memory.set_rom_patches(self.cheats.rom_patches());
+        self.cpu.flush_block_cache();
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        if cgb && !self.memory.is_boot_rom_mapped() {
+            self.cpu.skip_cgb_boot();
+        }
+    }
+
+    /// Whether a CGB cartridge is running with the CGB features enabled
+    pub fn cgb_mode(&self) -> bool {
+        self.memory.cgb_mode()
+    }
+
+    /// Whether a cartridge runs with the Super Game Boy's palettes and border
+    pub fn sgb_mode(&self) -> bool {
+        self.sgb.is_some()
+    }
+
+    /// Execute one instruction (or interrupt dispatch) and advance the rest of the system to match
+    ///
+    /// Returns the number of T-cycles taken
+    pub fn step(&mut self) -> u32 {
+        let start = self.profile_start();
+        if self.observers.is_empty() {
+            let cycles = self
+                .scheduler
+                .step(&mut self.cpu, &mut self.memory, &mut self.ppu);
+            self.profile_end(start, cycles);
+            return cycles;
+        }
+
+        let in_vblank = self.ppu.mode() == Mode::VBlank;
+        let cycles = self.scheduler.step_hooked(
+            &mut self.cpu,
+            &mut self.memory,
+            &mut self.ppu,
+            &mut self.observers,
+        );
+        self.profile_end(start, cycles);
+
+        if !in_vblank && self.ppu.mode() == Mode::VBlank {
+            let frame = self.frame();
+            self.observers.on_frame(&frame);
+        }
+        cycles
+    }
+
+    /// Like `step`, reporting each memory access the CPU makes to `observer`
+    pub fn step_observed(&mut self, observer: &dyn Fn(MemoryAccess)) -> u32 {
+        let start = self.profile_start();
+        let cycles =
+            self.scheduler
+                .step_observed(&mut self.cpu, &mut self.memory, &mut self.ppu, observer);
+        self.profile_end(start, cycles);
+        cycles
+    }
+
+    /// Whether every step has to go through `step`, for the observers or the profiler
+    fn is_hooked(&self) -> bool {
+        !self.observers.is_empty() || self.profiler.is_some()
+    }
+
+    /// The bank and address the next step runs at and whether the CPU is halted, for the
+    /// profiler, None if it is off
+    fn profile_start(&self) -> Option<(usize, u16, bool)> {
+        self.profiler.as_ref()?;
+        let pc = self.cpu.registers.pc;
+        Some((self.memory.bank(pc), pc, self.cpu.is_halted()))
+    }
+
+    fn profile_end(&mut self, start: Option<(usize, u16, bool)>, cycles: u32) {
+        let (Some(profiler), Some((bank, address, halted))) = (&mut self.profiler, start) else {
+            return;
+        };
+        if halted {
+            profiler.record_halted(cycles);
+        } else {
+            profiler.record(bank, address, cycles);
+        }
+    }
+
+    /// Run for at least `t_cycles`, returns the T-cycles actually run
+    pub fn run_cycles(&mut self, t_cycles: u32) -> u32 {
+        if !self.is_hooked() {
+            return self.scheduler.run_cycles(
+                &mut self.cpu,
+                &mut self.memory,
+                &mut self.ppu,
+                t_cycles,
+            );
+        }
+
+        let mut elapsed = 0;
+        while elapsed < t_cycles && self.cpu.error().is_none() {
+            elapsed += self.step();
+        }
+        elapsed
+    }
+
+    /// Run until the PPU completes a frame
+    ///
+    /// With the LCD turned off no frame is produced, then this returns after a frame's worth of cycles.
+    /// Super Game Boy commands are carried out after the frame
+    pub fn run_until_vblank(&mut self) -> u32 {
+        self.apply_movie();
+        let cycles = if !self.is_hooked() {
+            self.scheduler
+                .run_until_vblank(&mut self.cpu, &mut self.memory, &mut self.ppu)
+        } else {
+            let mut elapsed = 0;
+            while elapsed < T_CYCLES_PER_FRAME {
+                elapsed += self.step();
+                if self.ppu.take_frame_ready() || self.cpu.error().is_some() {
+                    break;
+                }
+            }
+            elapsed
+        };
+        if let Some(sgb) = &mut self.sgb {
+            sgb.end_frame(self.ppu.framebuffer());
+            for command in self.memory.take_sgb_commands() {
+                sgb.command(&command, self.ppu.framebuffer());
+            }
+        }
+        self.cheats.apply_ram_writes(&mut self.memory);
+        for cheat in self.cheats.cheats() {
+            if let (true, CheatCode::GameShark { address, .. }) = (cheat.enabled, cheat.cheat) {
+                self.cpu.invalidate_code(address);
+            }
+        }
+        self.watch_changes = self.ram_watches.update(&self.memory);
+        if self.rewind.as_mut().is_some_and(Rewind::frame) {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
+        }
+        #[cfg(feature = "std")]
+        self.record_frame();
+        cycles
+    }
+
+    #[cfg(feature = "std")]
+    fn record_frame(&mut self) {
+        if self.recorder.is_some() {
+            let frame = self.frame();
+            if let Err(error) = self.recorder.as_mut().unwrap().record(&frame) {
+                tracing::warn!("Video recording stopped: {}", error);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Add an observer called as emulation runs, returns its index
+    pub fn add_observer(&mut self, observer: Box<dyn Observer + Send>) -> usize {
+        self.observers.push(observer);
+        self.observers.len() - 1
+    }
+
+    pub fn remove_observer(&mut self, index: usize) -> Option<Box<dyn Observer + Send>> {
+        (index < self.observers.len()).then(|| self.observers.remove(index))
+    }
+
+    /// Add an enabled Game Genie or GameShark code, returns its index
+    ///
+    /// GameShark codes are applied at the end of every `run_until_vblank`
+    pub fn add_cheat(&mut self, code: &str) -> Result<usize, CheatError> {
+        let index = self.cheats.add(code)?;
+        self.memory.set_rom_patches(self.cheats.rom_patches());
+        self.cpu.flush_block_cache();
+        Ok(index)
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) -> Option<Cheat> {
+        let cheat = self.cheats.remove(index);
+        self.memory.set_rom_patches(self.cheats.rom_patches());
+        self.cpu.flush_block_cache();
+        cheat
+    }
+
+    /// Returns false if there is no cheat at `index`
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let found = self.cheats.set_enabled(index, enabled);
+        self.memory.set_rom_patches(self.cheats.rom_patches());
+        self.cpu.flush_block_cache();
+        found
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        self.cheats.cheats()
+    }
+
+    /// Report changes to the value at `address` after every frame, see `watch_changes`
+    pub fn add_ram_watch(&mut self, address: u16) {
+        self.ram_watches.add(&self.memory, address);
+    }
+
+    pub fn remove_ram_watch(&mut self, address: u16) {
+        self.ram_watches.remove(address);
+    }
+
+    pub fn ram_watches(&self) -> &WatchList {
+        &self.ram_watches
+    }
+
+    /// The watched values that changed during the last `run_until_vblank`
+    pub fn watch_changes(&self) -> &[WatchChange] {
+        &self.watch_changes
+    }
+
+    /// Charge the cycles of every step to the instruction it ran, `None` turns profiling off
+    ///
+    /// Emulation goes through `step` while profiling, which is slower
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    pub fn profiler_mut(&mut self) -> Option<&mut Profiler> {
+        self.profiler.as_mut()
+    }
+
+    /// The `count` hottest functions, or addresses without symbols, None when not profiling
+    pub fn profile_report(&self, count: usize) -> Option<String> {
+        Some(self.profiler.as_ref()?.report(self.symbols(), count))
+    }
+
+    /// Count the CPU's reads and writes at every address, `None` stops counting
+    pub fn set_heatmap(&mut self, heatmap: Option<Heatmap>) {
+        self.memory.set_heatmap(heatmap);
+    }
+
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.memory.heatmap()
+    }
+
+    pub fn heatmap_mut(&mut self) -> Option<&mut Heatmap> {
+        self.memory.heatmap_mut()
+    }
+
+    /// Record snapshots to rewind to while running with `run_until_vblank`, `None` turns it off
+    pub fn set_rewind(&mut self, rewind: Option<Rewind>) {
+        self.rewind = rewind;
+    }
+
+    /// Go back to the newest rewind snapshot, which is then dropped so the next call goes further back
+    ///
+    /// Returns false when there is nothing left to rewind to
+    pub fn rewind(&mut self) -> bool {
+        let Some(state) = self.rewind.as_mut().and_then(Rewind::pop) else {
+            return false;
+        };
+        self.load_state(&state).is_ok()
+    }
+
+    /// T-cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.scheduler.cycles()
+    }
+
+    /// The last rendered frame, one shade (0-3) per pixel
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        self.ppu.framebuffer()
+    }
+
+    /// The last rendered frame as RGB555 colors, `None` unless a CGB game is running
+    pub fn color_framebuffer(&self) -> Option<&[u16; SCREEN_WIDTH * SCREEN_HEIGHT]> {
+        self.cgb_mode().then(|| self.ppu.color_framebuffer())
+    }
+
+    /// The last rendered frame in RGBA, DMG shades are colored with the palette set by `set_palette`
+    /// unless a Super Game Boy game sent its own
+    pub fn frame(&self) -> Frame {
+        match (self.color_framebuffer(), &self.sgb) {
+            (Some(colors), _) => Frame::from_rgb555(colors),
+            (None, Some(sgb)) => sgb.screen(self.framebuffer(), &self.palette),
+            (None, None) => Frame::from_shades(self.framebuffer(), &self.palette),
+        }
+    }
+
+    /// `screen`, a frame from `frame`, in the middle of the 256x224 Super Game Boy border
+    ///
+    /// Returned as is until the game sent a border
+    pub fn with_sgb_border(&self, screen: Frame) -> Frame {
+        self.sgb
+            .as_ref()
+            .and_then(|sgb| sgb.border(&screen))
+            .unwrap_or(screen)
+    }
+
+    /// Save the last rendered frame as a PNG, colored the same way as `frame`
+    #[cfg(feature = "std")]
+    pub fn screenshot(&self, path: impl AsRef<Path>) -> Result<(), ScreenshotError> {
+        let file = std::fs::File::create(path)?;
+        self.frame().write_png(std::io::BufWriter::new(file))
+    }
+
+    /// Record every frame from now on, each `run_until_vblank` adds one
+    #[cfg(feature = "std")]
+    pub fn start_recording(
+        &mut self,
+        path: impl AsRef<Path>,
+        format: VideoFormat,
+    ) -> Result<(), RecordingError> {
+        self.stop_recording()?;
+        self.recorder = Some(VideoRecorder::create(path, format)?);
+        Ok(())
+    }
+
+    /// Finish the running recording, if any
+    #[cfg(feature = "std")]
+    pub fn stop_recording(&mut self) -> Result<(), RecordingError> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// The 384 tiles of VRAM `bank` (1 only in CGB mode) as a 128x192 image, see `tile_atlas`
+    pub fn tile_atlas(&self, bank: usize) -> DebugImage {
+        ppu::tile_atlas(&self.memory, bank, &self.palette)
+    }
+
+    /// The whole 256x256 background `map` with the visible area outlined
+    pub fn background_map(&self, map: TileMap) -> DebugImage {
+        ppu::background_map(&self.memory, map, self.cgb_mode(), &self.palette)
+    }
+
+    /// The sprites in OAM, decoded
+    pub fn oam_entries(&self) -> Vec<OamEntry> {
+        ppu::oam_entries(&self.memory)
+    }
+
+    /// The colors used for the four shades of DMG games
+    pub fn set_palette(&mut self, palette: DmgPalette) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> &DmgPalette {
+        &self.palette
+    }
+
+    /// Trade accuracy for speed, see `AccuracyProfile`
+    ///
+    /// This also picks the PPU renderer, VRAM/OAM access blocking and the STAT write bug, which
+    /// can still be changed on their own afterwards
+    pub fn set_accuracy(&mut self, profile: AccuracyProfile) {
+        let cycle_accurate = profile == AccuracyProfile::CycleAccurate;
+        self.scheduler.set_profile(profile);
+        self.ppu.set_renderer(if cycle_accurate {
+            Renderer::PixelFifo
+        } else {
+            Renderer::Scanline
+        });
+        self.memory.set_access_blocking(cycle_accurate);
+        self.memory.set_oam_bug(cycle_accurate);
+        self.memory
+            .set_stat_write_bug(profile != AccuracyProfile::Fast);
+    }
+
+    pub fn accuracy(&self) -> AccuracyProfile {
+        self.scheduler.profile()
+    }
+
+    /// Lock the CPU out of VRAM during mode 3 and OAM during modes 2 and 3, like the hardware does
+    ///
+    /// Off by default, turn it on to catch homebrew writing to VRAM at the wrong time
+    pub fn set_access_blocking(&mut self, enabled: bool) {
+        self.memory.set_access_blocking(enabled);
+    }
+
+    /// Fire the STAT interrupt on writes to STAT in HBlank, VBlank or on LY=LYC, like the DMG does
+    ///
+    /// On by default and in all but the Fast accuracy profile
+    pub fn set_stat_write_bug(&mut self, enabled: bool) {
+        self.memory.set_stat_write_bug(enabled);
+    }
+
+    /// Corrupt OAM on accesses to 0xFE00-0xFEFF while the PPU scans it, like the DMG does
+    ///
+    /// Off by default, only the Cycle-accurate profile turns it on
+    pub fn set_oam_bug(&mut self, enabled: bool) {
+        self.memory.set_oam_bug(enabled);
+    }
+
+    /// Run code from a cache of decoded basic blocks, experimental
+    ///
+    /// Faster, but memory accesses within an instruction are timed like with
+    /// `AccuracyProfile::Fast` and observers do not see instruction fetches from cached code
+    pub fn set_block_cache(&mut self, enabled: bool) {
+        self.cpu.set_block_cache(enabled);
+    }
+
+    /// Draw through the pixel FIFO for games that change registers in the middle of a line
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.ppu.set_renderer(renderer);
+    }
+
+    /// Press a button, ignored while a movie plays
+    pub fn press_button(&mut self, button: Button) {
+        if !self.is_playing_movie() {
+            self.memory.press_button(button);
+        }
+    }
+
+    pub fn release_button(&mut self, button: Button) {
+        if !self.is_playing_movie() {
+            self.memory.release_button(button);
+        }
+    }
+
+    /// The buttons held down, with the bit of each button in `Button::ALL` order
+    pub fn pressed_buttons(&self) -> u8 {
+        Button::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, &button)| self.memory.is_button_pressed(button))
+            .fold(0, |pressed, (bit, _)| pressed | 1 << bit)
+    }
+
+    fn set_pressed_buttons(&mut self, pressed: u8) {
+        for (bit, button) in Button::ALL.into_iter().enumerate() {
+            if pressed & 1 << bit != 0 {
+                self.memory.press_button(button);
+            } else {
+                self.memory.release_button(button);
+            }
+        }
+    }
+
+    /// Record a movie of the joypad from here on, anchored to the current state
+    ///
+    /// Each `run_until_vblank` is a frame, logging the buttons held when it starts
+    pub fn start_movie_recording(&mut self) {
+        let anchor = self.save_state();
+        self.movie = Some(MovieMode::Recording(InputRecorder::new(anchor)));
+    }
+
+    /// Restore the movie's anchor and feed it the recorded inputs, one frame per
+    /// `run_until_vblank`, until the movie ends and the joypad is handed back
+    pub fn play_movie(&mut self, movie: Movie) -> Result<(), MovieError> {
+        self.load_state(movie.anchor())?;
+        self.movie = Some(MovieMode::Playing(InputPlayer::new(movie)));
+        Ok(())
+    }
+
+    /// Play `movie` back up to `frame` and record from there, replacing the rest of it
+    pub fn rerecord_movie(&mut self, movie: Movie, frame: usize) -> Result<(), MovieError> {
+        let frame = frame.min(movie.len());
+        self.play_movie(movie)?;
+        while self.movie_frame() < Some(frame) {
+            self.run_until_vblank();
+        }
+        if let Some(MovieMode::Playing(player)) = self.movie.take() {
+            let recorder = InputRecorder::rerecord(player.finish(), frame);
+            self.movie = Some(MovieMode::Recording(recorder));
+        }
+        Ok(())
+    }
+
+    /// Stop recording or playing, returns the movie
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        match self.movie.take()? {
+            MovieMode::Recording(recorder) => Some(recorder.finish()),
+            MovieMode::Playing(player) => Some(player.finish()),
+        }
+    }
+
+    pub fn is_playing_movie(&self) -> bool {
+        matches!(self.movie, Some(MovieMode::Playing(_)))
+    }
+
+    /// The frames recorded or played so far, `None` without a movie
+    pub fn movie_frame(&self) -> Option<usize> {
+        match self.movie.as_ref()? {
+            MovieMode::Recording(recorder) => Some(recorder.frame()),
+            MovieMode::Playing(player) => Some(player.frame()),
+        }
+    }
+
+    /// Log or apply the joypad state for the frame about to run
+    fn apply_movie(&mut self) {
+        let pressed = self.pressed_buttons();
+        match &mut self.movie {
+            Some(MovieMode::Recording(recorder)) => recorder.record(pressed),
+            Some(MovieMode::Playing(player)) => match player.next_input() {
+                Some(input) => self.set_pressed_buttons(input),
+                None => {
+                    tracing::info!("Movie playback finished");
+                    self.movie = None;
+                }
+            },
+            None => {}
+        }
+    }
+
+    /// Adjust audio for running at `speed`, so the samples still play back at `SAMPLE_RATE` in real time
+    ///
+    /// Pacing the frames is up to the frontend, see `DeltaTime::wait_frame`
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.memory
+            .set_audio_sample_rate(speed.audio_sample_rate(SAMPLE_RATE));
+    }
+
+    /// Take the audio samples produced since the last call, interleaved left and right at `SAMPLE_RATE`
+    pub fn drain_audio_samples(&mut self) -> Vec<i16> {
+        self.memory.drain_audio_samples()
+    }
+
+    /// Mute or unmute a sound channel in the audio output
+    pub fn set_channel_muted(&mut self, channel: Channel, muted: bool) {
+        self.memory.apu_mut().set_channel_muted(channel, muted);
+    }
+
+    pub fn channel_muted(&self, channel: Channel) -> bool {
+        self.memory.apu().channel_muted(channel)
+    }
+
+    /// Only play `channel`, or every channel again with `None`
+    pub fn solo_channel(&mut self, channel: Option<Channel>) {
+        self.memory.apu_mut().solo_channel(channel);
+    }
+
+    /// Record the audio output, or a single channel, to a WAV file until `stop_wav_dump`
+    #[cfg(feature = "std")]
+    pub fn start_wav_dump(&mut self, path: impl AsRef<Path>, source: WavSource) -> io::Result<()> {
+        self.memory.apu_mut().start_wav_dump(path, source)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn stop_wav_dump(&mut self) -> io::Result<()> {
+        self.memory.apu_mut().stop_wav_dump()
+    }
+
+    /// Connect a device to the serial port
+    pub fn set_serial_transport(&mut self, transport: Box<dyn SerialTransport>) {
+        self.memory.set_serial_transport(transport);
+    }
+
+    /// Point the CGB infrared port at a device, by default it never receives light
+    pub fn set_ir_transport(&mut self, transport: Box<dyn IrTransport>) {
+        self.memory.set_ir_transport(transport);
+    }
+
+    /// Snapshot the whole emulator, the cartridge ROM and boot ROM are not included
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        self.cpu.save_state(&mut state);
+        self.memory.save_state(&mut state);
+        self.ppu.save_state(&mut state);
+        self.scheduler.save_state(&mut state);
+        state.write_bool(self.sgb.is_some());
+        if let Some(sgb) = &self.sgb {
+            sgb.save_state(&mut state);
+        }
+        state.into_bytes()
+    }
+
+    /// Restore a snapshot created by `save_state` with the same cartridge inserted
+    ///
+    /// If the state is invalid the emulator is left unchanged
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), SaveStateError> {
+        let backup = self.save_state();
+        let result = self.load_state_unchecked(state);
+        if result.is_err() {
+            self.load_state_unchecked(&backup)
+                .expect("restoring the previous state failed");
+        }
+        result
+    }
+
+    fn load_state_unchecked(&mut self, state: &[u8]) -> Result<(), SaveStateError> {
+        let mut state = StateReader::new(state)?;
+        self.cpu.load_state(&mut state)?;
+        self.memory.load_state(&mut state)?;
+        self.ppu.load_state(&mut state)?;
+        self.scheduler.load_state(&mut state)?;
+        self.sgb = state.read_bool()?.then(Sgb::new);
+        if let Some(sgb) = &mut self.sgb {
+            sgb.load_state(&mut state)?;
+        }
+        if !state.is_finished() {
+            return Err(SaveStateError::InvalidData("trailing bytes"));
+        }
+        Ok(())
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// Write a Gameboy Doctor trace line to `trace` before every instruction, `None` stops tracing
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: Option<Box<dyn std::io::Write + Send>>) {
+        self.cpu.set_trace(trace);
+    }
+
+    /// Keep the last `length` instructions started instead of `HISTORY_LENGTH`, 0 keeps none
+    pub fn set_instruction_history(&mut self, length: usize) {
+        self.cpu.set_instruction_history(length);
+    }
+
+    /// Name addresses by the labels in `symbols` in the trace, the debugger and core dumps
+    pub fn set_symbols(&mut self, symbols: Option<Symbols>) {
+        self.cpu.set_symbols(symbols);
+    }
+
+    pub fn symbols(&self) -> Option<&Symbols> {
+        self.cpu.symbols()
+    }
+
+    /// A disassembler over memory as the CPU sees it, naming addresses by the loaded symbols
+    pub fn disassembler(&self) -> Disassembler<'_, Memory> {
+        Disassembler::new(&self.memory).with_symbols(self.symbols())
+    }
+
+    /// Snapshot the emulator for a bug report, see `CoreDump`
+    #[cfg(feature = "std")]
+    pub fn core_dump(&self, reason: &str) -> CoreDump {
+        CoreDump::capture(self, reason)
+    }
+
+    /// Choose how illegal opcodes are handled, the default locks up like the hardware
+    pub fn set_illegal_opcode_policy(&mut self, policy: IllegalOpcodePolicy) {
+        self.cpu.set_illegal_opcode_policy(policy);
+    }
+
+    /// Take the error emulation stopped on, with `IllegalOpcodePolicy::Trap` running stops at an
+    /// illegal opcode until this is called
+    pub fn take_error(&mut self) -> Option<EmuError> {
+        self.cpu.take_error()
+    }
+
+    /// Mutable access to the CPU, for debuggers changing registers
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Mutable access to memory, for debuggers and scripts writing to it
+    ///
+    /// Drops the block cache, the code may be changed
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        self.cpu.flush_block_cache();
+        &mut self.memory
+    }
+}
+
+impl Default for GameBoy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_rom_validates() {
+        let mut gameboy = GameBoy::new();
+        let mut rom = test_rom(&[]);
+        rom[0x0104] = 0x00;
+
+        assert!(matches!(
+            gameboy.load_rom(rom),
+            Err(CartridgeError::InvalidLogo)
+        ));
+        assert!(gameboy.load_rom(test_rom(&[])).is_ok());
+    }
+
+    #[test]
+    fn test_step() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x00])).unwrap();
+        gameboy.cpu.registers.pc = 0x0100;
+
+        assert_eq!(gameboy.step(), 4);
+        assert_eq!(gameboy.cycles(), 4);
+        assert_eq!(gameboy.cpu().registers.pc, 0x0101);
+    }
+
+    #[test]
+    fn test_block_cache_sees_self_modifying_code() {
+        let mut gameboy = GameBoy::new();
+        gameboy
+            .load_rom(test_rom(&[
+                0x21, 0x00, 0xC0, // LD HL, 0xC000
+                0x36, 0x3C, // LD (HL), INC A
+                0x3E, 0xC9, // LD A, RET
+                0xEA, 0x01, 0xC0, // LD (0xC001), A
+                0xAF, // XOR A
+                0xCD, 0x00, 0xC0, // CALL 0xC000
+                0x36, 0x3D, // LD (HL), DEC A
+                0xCD, 0x00, 0xC0, // CALL 0xC000
+                0xCD, 0x00, 0xC0, // CALL 0xC000
+                0x18, 0xFE, // JR -2
+            ]))
+            .unwrap();
+        gameboy.set_block_cache(true);
+        while gameboy.cpu().registers.pc != 0x0166 {
+            gameboy.step();
+        }
+        assert_eq!(gameboy.cpu().registers.read_16(Register16::AF) >> 8, 0xFF);
+    }
+
+    #[derive(Default)]
+    struct Counts {
+        instructions: usize,
+        reads: usize,
+        writes: Vec<(u16, u8)>,
+        interrupts: Vec<Interrupt>,
+        frames: usize,
+    }
+
+    struct CountingObserver(std::sync::Arc<std::sync::Mutex<Counts>>);
+
+    impl Observer for CountingObserver {
+        fn on_instruction(&mut self, address: u16, opcode: u8) {
+            self.0.lock().unwrap().instructions += 1;
+        }
+
+        fn on_memory_read(&mut self, address: u16, value: u8) {
+            self.0.lock().unwrap().reads += 1;
+        }
+
+        fn on_memory_write(&mut self, address: u16, value: u8) {
+            self.0.lock().unwrap().writes.push((address, value));
+        }
+
+        fn on_interrupt(&mut self, interrupt: Interrupt) {
+            self.0.lock().unwrap().interrupts.push(interrupt);
+        }
+
+        fn on_frame(&mut self, frame: &Frame) {
+            self.0.lock().unwrap().frames += 1;
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        // EI; LD ($C000), A; JR -5, VBlank interrupts slide through NOPs back to the start
+        let rom = test_rom(&[0xFB, 0xEA, 0x00, 0xC0, 0x18, 0xFB]);
+        let mut plain = GameBoy::new();
+        plain.load_rom(rom.clone()).unwrap();
+        let mut observed = GameBoy::new();
+        observed.load_rom(rom).unwrap();
+        let counts = std::sync::Arc::new(std::sync::Mutex::new(Counts::default()));
+        let index = observed.add_observer(Box::new(CountingObserver(counts.clone())));
+        for gameboy in [&mut plain, &mut observed] {
+            gameboy.memory.write_byte(0xFF40, 0x91);
+            gameboy.memory.write_byte(0xFFFF, Interrupt::VBlank.bit());
+            gameboy.run_until_vblank();
+            gameboy.run_until_vblank();
+        }
+
+        // observing doesn't change emulation
+        assert_eq!(observed.cycles(), plain.cycles());
+        let counts = counts.lock().unwrap();
+        assert_eq!(counts.frames, 2);
+        // the boot ROM leaves VBlank pending in IF, then the first frame's is dispatched
+        assert_eq!(counts.interrupts, vec![Interrupt::VBlank; 2]);
+        assert!(counts.writes.contains(&(0xC000, 0x01)));
+        assert!(counts.instructions > 1000);
+        assert!(counts.reads > counts.instructions);
+
+        assert!(observed.remove_observer(index).is_some());
+        assert!(observed.remove_observer(index).is_none());
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+
+        assert_eq!(gameboy.run_cycles(100), 104);
+        assert_eq!(gameboy.cycles(), 104);
+    }
+
+    #[test]
+    fn test_run_until_vblank() {
+        let mut gameboy = GameBoy::new();
+        // JR -2, loop forever
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        gameboy.cpu.registers.pc = 0x0100;
+        gameboy.memory.write_byte(0xFF40, 0x91);
+
+        gameboy.run_until_vblank();
+        gameboy.run_until_vblank();
+        assert_eq!(gameboy.memory().read_byte(0xFF44), 144);
+    }
+
+    #[test]
+    fn test_run_until_vblank_lcd_off() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        gameboy.memory.write_byte(0xFF40, 0x00);
+
+        gameboy.run_until_vblank();
+        assert_eq!(gameboy.cpu().registers.pc, 0x0150);
+    }
+
+    #[test]
+    fn test_cgb_cartridge() {
+        let mut rom = test_rom(&[]);
+        rom[0x0143] = 0x80;
+        fix_header_checksum(&mut rom);
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+
+        assert!(gameboy.cgb_mode());
+        // games check A to detect the CGB
+        assert_eq!(gameboy.cpu().registers.read_16(Register16::AF) >> 8, 0x11);
+    }
+
+    #[test]
+    fn test_sgb_palette() {
+        let mut rom = test_rom(&[0x18, 0xFE]);
+        rom[0x0146] = 0x03;
+        fix_header_checksum(&mut rom);
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(rom).unwrap();
+        assert!(gameboy.sgb_mode());
+
+        // PAL01 with a red color 0
+        let mut packet = [0; 16];
+        packet[0] = 0x01;
+        packet[1] = 0x1F;
+        for select in sgb::packet_writes(&packet) {
+            gameboy.memory.write_byte(0xFF00, select);
+        }
+        gameboy.memory.write_byte(0xFF47, 0x00);
+        gameboy.run_until_vblank();
+        gameboy.run_until_vblank();
+        assert_eq!(gameboy.frame().pixel(0, 0), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(gameboy.with_sgb_border(gameboy.frame()).width(), 160);
+    }
+
+    #[test]
+    fn test_oam_bug() {
+        let run = |profile| {
+            let mut gameboy = GameBoy::new();
+            gameboy
+                .load_rom(test_rom(&[
+                    0x21, 0x40, 0xFE, // LD HL, 0xFE40
+                    0x23, // loop: INC HL
+                    0x2B, // DEC HL
+                    0x18, 0xFC, // JR loop
+                ]))
+                .unwrap();
+            for (index, adress) in (0xFE00..=0xFE9F).enumerate() {
+                gameboy.memory.write_byte(adress, index as u8);
+            }
+            gameboy.set_accuracy(profile);
+            gameboy.run_until_vblank();
+            (0xFE00..=0xFE9F)
+                .map(|adress| gameboy.memory.read_oam(adress))
+                .collect::<Vec<_>>()
+        };
+
+        let untouched: Vec<u8> = (0..0xA0).collect();
+        assert_eq!(run(AccuracyProfile::Balanced), untouched);
+        let corrupted = run(AccuracyProfile::CycleAccurate);
+        assert_ne!(corrupted, untouched);
+        // the first row is never corrupted
+        assert_eq!(corrupted[..8], untouched[..8]);
+    }
+
+    #[test]
+    fn test_boot_rom() {
+        // LD A, 1; LDH (0x50), A
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        let mut gameboy = GameBoy::with_boot_rom(boot_rom).unwrap();
+        gameboy.load_rom(test_rom(&[])).unwrap();
+
+        assert_eq!(gameboy.memory().read_byte(0x0000), 0x3E);
+        gameboy.step();
+        gameboy.step();
+        assert_eq!(gameboy.memory().read_byte(0x0000), 0x00);
+    }
+
+    #[test]
+    fn test_save_state_round_trip() {
+        let mut gameboy = GameBoy::new();
+        // INC A; JR -3
+        gameboy.load_rom(test_rom(&[0x3C, 0x18, 0xFD])).unwrap();
+        gameboy.memory.write_byte(0xC000, 0x12);
+        for _ in 0..100 {
+            gameboy.step();
+        }
+        let state = gameboy.save_state();
+
+        for _ in 0..5000 {
+            gameboy.step();
+        }
+        let expected = gameboy.save_state();
+
+        gameboy.memory.write_byte(0xC000, 0x34);
+        gameboy.load_state(&state).unwrap();
+        assert_eq!(gameboy.memory().read_byte(0xC000), 0x12);
+        for _ in 0..5000 {
+            gameboy.step();
+        }
+        assert_eq!(gameboy.save_state(), expected);
+    }
+
+    #[test]
+    fn test_load_state_other_cartridge() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[])).unwrap();
+        let state = gameboy.save_state();
+
+        let mut rom = test_rom(&[]);
+        rom[0x0134..0x0138].copy_from_slice(b"GAME");
+        fix_header_checksum(&mut rom);
+        let mut other = GameBoy::new();
+        other.load_rom(rom).unwrap();
+        other.step();
+        let before = other.save_state();
+
+        assert!(matches!(
+            other.load_state(&state),
+            Err(SaveStateError::CartridgeMismatch { .. })
+        ));
+        assert_eq!(other.save_state(), before);
+    }
+
+    #[test]
+    fn test_load_state_truncated() {
+        let mut gameboy = GameBoy::new();
+        let state = gameboy.save_state();
+
+        assert!(matches!(
+            gameboy.load_state(&state[..state.len() - 1]),
+            Err(SaveStateError::UnexpectedEnd)
+        ));
+    }
+
+    /// A writer whose contents can still be read after handing it to the emulator
+    #[derive(Clone, Default)]
+    struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut gameboy = GameBoy::new();
+        // the entry point, NOP; JP $0150
+        gameboy.load_rom(test_rom(&[])).unwrap();
+        let buffer = SharedBuffer::default();
+        gameboy.set_trace(Some(Box::new(buffer.clone())));
+
+        gameboy.step();
+        gameboy.step();
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            trace,
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01\n\
+             A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0101 PCMEM:C3,50,01,CE\n"
+        );
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut gameboy = GameBoy::new();
+        // LD A, (HL+); LD ($C000), A; JR -6
+        gameboy
+            .load_rom(test_rom(&[0x2A, 0xEA, 0x00, 0xC0, 0x18, 0xFA]))
+            .unwrap();
+        gameboy.set_heatmap(Some(Heatmap::new()));
+        for _ in 0..8 {
+            gameboy.step();
+        }
+
+        let heatmap = gameboy.heatmap().unwrap();
+        assert_eq!(heatmap.reads(0x0150), 2);
+        assert_eq!(heatmap.reads(0x0152), 2);
+        assert_eq!(heatmap.writes(0xC000), 2);
+        // HL starts at 0x014D
+        assert_eq!(heatmap.reads(0x014E), 1);
+    }
+
+    #[test]
+    fn test_profiler() {
+        let mut gameboy = GameBoy::new();
+        // NOP; JR -3
+        gameboy.load_rom(test_rom(&[0x00, 0x18, 0xFD])).unwrap();
+        gameboy.set_profiler(Some(Profiler::new()));
+        let cycles = gameboy.run_until_vblank();
+
+        let profiler = gameboy.profiler().unwrap();
+        assert_eq!(profiler.total_cycles(), u64::from(cycles));
+        assert_eq!(profiler.halted_cycles(), 0);
+        // 4 cycles for each NOP, 12 for each JR
+        let (nops, jumps) = (
+            profiler.cycles(0, 0x0150) / 4,
+            profiler.cycles(0, 0x0151) / 12,
+        );
+        assert!(nops > 0 && nops.abs_diff(jumps) <= 1);
+        assert!(gameboy.profile_report(1).unwrap().ends_with("%  00:0151\n"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_trace_labels() {
+        let mut gameboy = GameBoy::new();
+        // the entry point, NOP; JP $0150
+        gameboy.load_rom(test_rom(&[])).unwrap();
+        gameboy.set_symbols(Some(Symbols::parse("00:0150 Main").unwrap()));
+        let buffer = SharedBuffer::default();
+        gameboy.set_trace(Some(Box::new(buffer.clone())));
+
+        for _ in 0..3 {
+            gameboy.step();
+        }
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(trace.lines().nth(2), Some("Main:"));
+        assert_eq!(gameboy.disassembler().disassemble(0x0101).text, "JP Main");
+    }
+
+    #[test]
+    fn test_frame_palette() {
+        let mut gameboy = GameBoy::new();
+        assert_eq!(gameboy.frame().pixel(0, 0), [0xFF, 0xFF, 0xFF, 0xFF]);
+
+        gameboy.set_palette(DmgPalette::CLASSIC_GREEN);
+        assert_eq!(
+            gameboy.frame().pixel(0, 0),
+            DmgPalette::CLASSIC_GREEN.color(0)
+        );
+    }
+
+    #[test]
+    fn test_screenshot() {
+        let path = std::env::temp_dir().join(format!(
+            "gameboy_emulator_{}_screenshot.png",
+            std::process::id()
+        ));
+        let mut gameboy = GameBoy::new();
+        gameboy.set_palette(DmgPalette::CLASSIC_GREEN);
+        gameboy.screenshot(&path).unwrap();
+
+        let png = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded[..4], DmgPalette::CLASSIC_GREEN.color(0));
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        gameboy.set_rewind(Some(Rewind::new(2, 10)));
+        assert!(!gameboy.rewind());
+
+        gameboy.run_until_vblank();
+        gameboy.run_until_vblank();
+        let cycles = gameboy.cycles();
+        gameboy.memory.write_byte(0xC000, 0x12);
+        gameboy.run_until_vblank();
+        gameboy.run_until_vblank();
+        gameboy.memory.write_byte(0xC000, 0x34);
+
+        assert!(gameboy.rewind());
+        assert_eq!(gameboy.memory().read_byte(0xC000), 0x12);
+        assert!(gameboy.rewind());
+        assert_eq!(gameboy.cycles(), cycles);
+        assert_eq!(gameboy.memory().read_byte(0xC000), 0x00);
+        assert!(!gameboy.rewind());
+    }
+
+    #[test]
+    fn test_movie() {
+        let mut gameboy = GameBoy::new();
+        // LD A,($FF00); LD ($C000),A; JR -8, with the directions selected
+        let rom = test_rom(&[0xF0, 0x00, 0xEA, 0x00, 0xC0, 0x18, 0xF9]);
+        gameboy.load_rom(rom.clone()).unwrap();
+        gameboy.memory.write_byte(0xFF00, 0x20);
+        gameboy.start_movie_recording();
+        for frame in 0..10 {
+            if frame == 3 {
+                gameboy.press_button(Button::Down);
+            }
+            if frame == 6 {
+                gameboy.release_button(Button::Down);
+                gameboy.press_button(Button::Left);
+            }
+            gameboy.run_until_vblank();
+        }
+        let expected = gameboy.save_state();
+        let movie = gameboy.stop_movie().unwrap();
+        assert_eq!(movie.len(), 10);
+        assert_eq!(movie.inputs()[3], 0x08);
+        assert_eq!(movie.inputs()[9], 0x02);
+
+        let mut replay = GameBoy::new();
+        replay.load_rom(rom).unwrap();
+        replay
+            .play_movie(Movie::from_bytes(&movie.to_bytes()).unwrap())
+            .unwrap();
+        replay.press_button(Button::A);
+        for _ in 0..10 {
+            replay.run_until_vblank();
+        }
+        assert_eq!(replay.save_state(), expected);
+
+        // re-record from frame 5 without pressing Left
+        replay.rerecord_movie(movie, 5).unwrap();
+        assert_eq!(replay.movie_frame(), Some(5));
+        replay.release_button(Button::Down);
+        replay.run_until_vblank();
+        let movie = replay.stop_movie().unwrap();
+        assert_eq!(movie.inputs(), [0, 0, 0, 0x08, 0x08, 0x00]);
+        assert_eq!(movie.rerecords(), 1);
+    }
+
+    #[test]
+    fn test_ram_watch() {
+        let mut gameboy = GameBoy::new();
+        // INC A; LD ($C000),A; JR -6
+        gameboy
+            .load_rom(test_rom(&[0x3C, 0xEA, 0x00, 0xC0, 0x18, 0xFA]))
+            .unwrap();
+        gameboy.add_ram_watch(0xC000);
+        gameboy.add_ram_watch(0xC001);
+        gameboy.run_until_vblank();
+
+        let changes = gameboy.watch_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].address, 0xC000);
+        assert_eq!(changes[0].new, gameboy.memory().read_byte(0xC000));
+
+        let mut search = MemorySearch::new(gameboy.memory());
+        gameboy.run_until_vblank();
+        search.filter(gameboy.memory(), Comparison::NotEqual);
+        assert!(search
+            .candidates()
+            .iter()
+            .any(|&(address, _)| address == 0xC000));
+    }
+
+    #[test]
+    fn test_set_speed() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        gameboy.run_cycles(T_CYCLES_PER_FRAME);
+        let normal = gameboy.drain_audio_samples().len();
+
+        gameboy.set_speed(Speed::Double);
+        gameboy.run_cycles(T_CYCLES_PER_FRAME);
+        assert!(gameboy.drain_audio_samples().len().abs_diff(normal / 2) <= 2);
+
+        gameboy.set_speed(Speed::Unlimited);
+        gameboy.run_cycles(T_CYCLES_PER_FRAME);
+        assert!(gameboy.drain_audio_samples().is_empty());
+    }
+
+    #[test]
+    fn test_cheats() {
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        let genie = gameboy.add_cheat("001-51F").unwrap();
+        gameboy.add_cheat("01420AC1").unwrap();
+
+        assert_eq!(gameboy.memory().read_byte(0x0151), 0x00);
+        gameboy.run_until_vblank();
+        assert_eq!(gameboy.memory().read_byte(0xC10A), 0x42);
+
+        gameboy.set_cheat_enabled(genie, false);
+        assert_eq!(gameboy.memory().read_byte(0x0151), 0xFE);
+        assert!(gameboy.add_cheat("nope").is_err());
+        assert_eq!(gameboy.cheats().len(), 2);
+    }
+
+    #[test]
+    fn test_press_button() {
+        let mut gameboy = GameBoy::new();
+        gameboy.memory.write_byte(0xFF00, 0x20);
+        gameboy.press_button(Button::Down);
+
+        assert_eq!(gameboy.memory().read_byte(0xFF00) & 0x0F, 0x07);
+    }
+}
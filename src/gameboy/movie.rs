@@ -0,0 +1,226 @@
+//! TAS movies, the joypad state of every frame from a save state onwards.
+//!
+//! A movie starts from an anchor save state and stores one byte of pressed buttons per frame,
+//! applied before the frame runs. Emulation is deterministic given the same state and inputs at
+//! the same frame boundaries, so playing a movie back reproduces the run exactly. The MBC3
+//! real-time clock follows the wall clock and is the exception.
+//!
+//! Re-recording plays a movie back to an earlier frame and continues recording from there,
+//! dropping the inputs after it and counting the re-record.
+//!
+//! The file starts with `GBMV` and a version, followed by the re-record count, the anchor and the
+//! inputs, each prefixed by its length as a little endian u32.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::utils::MovieError;
+
+const MAGIC: &[u8; 4] = b"GBMV";
+const VERSION: u32 = 1;
+
+/// A recorded run, the pressed buttons use the bit of each `Button` in `Button::ALL` order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    anchor: Vec<u8>,
+    inputs: Vec<u8>,
+    rerecords: u32,
+}
+
+impl Movie {
+    /// An empty movie starting from the save state `anchor`
+    pub fn new(anchor: Vec<u8>) -> Movie {
+        Movie {
+            anchor,
+            inputs: Vec::new(),
+            rerecords: 0,
+        }
+    }
+
+    /// The save state the movie starts from
+    pub fn anchor(&self) -> &[u8] {
+        &self.anchor
+    }
+
+    /// The pressed buttons of each frame
+    pub fn inputs(&self) -> &[u8] {
+        &self.inputs
+    }
+
+    /// The number of frames
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// How often the movie was re-recorded from an earlier frame
+    pub fn rerecords(&self) -> u32 {
+        self.rerecords
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend(self.rerecords.to_le_bytes());
+        for section in [&self.anchor, &self.inputs] {
+            bytes.extend((section.len() as u32).to_le_bytes());
+            bytes.extend(section);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Movie, MovieError> {
+        let Some(bytes) = bytes.strip_prefix(MAGIC) else {
+            return Err(MovieError::InvalidMagic);
+        };
+        let mut reader = Reader(bytes);
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let rerecords = reader.read_u32()?;
+        let anchor = reader.read_section()?.to_vec();
+        let inputs = reader.read_section()?.to_vec();
+        Ok(Movie {
+            anchor,
+            inputs,
+            rerecords,
+        })
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MovieError> {
+        Ok(std::fs::write(path, self.to_bytes())?)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Movie, MovieError> {
+        Movie::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MovieError> {
+        if self.0.len() < length {
+            return Err(MovieError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_section(&mut self) -> Result<&'a [u8], MovieError> {
+        let length = self.read_u32()? as usize;
+        self.take(length)
+    }
+}
+
+/// Appends the pressed buttons of each frame to a movie
+pub struct InputRecorder {
+    movie: Movie,
+}
+
+impl InputRecorder {
+    /// Start recording from the save state `anchor`
+    pub fn new(anchor: Vec<u8>) -> InputRecorder {
+        InputRecorder {
+            movie: Movie::new(anchor),
+        }
+    }
+
+    /// Continue `movie` after its first `frame` frames, counting a re-record
+    pub fn rerecord(mut movie: Movie, frame: usize) -> InputRecorder {
+        movie.inputs.truncate(frame);
+        movie.rerecords += 1;
+        InputRecorder { movie }
+    }
+
+    /// Log the buttons pressed during the next frame
+    pub fn record(&mut self, pressed: u8) {
+        self.movie.inputs.push(pressed);
+    }
+
+    /// The number of frames recorded
+    pub fn frame(&self) -> usize {
+        self.movie.len()
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+/// Hands out the pressed buttons of each frame of a movie in turn
+pub struct InputPlayer {
+    movie: Movie,
+    frame: usize,
+}
+
+impl InputPlayer {
+    pub fn new(movie: Movie) -> InputPlayer {
+        InputPlayer { movie, frame: 0 }
+    }
+
+    /// The buttons to hold during the next frame, `None` once the movie is over
+    pub fn next_input(&mut self) -> Option<u8> {
+        let input = *self.movie.inputs.get(self.frame)?;
+        self.frame += 1;
+        Some(input)
+    }
+
+    /// The number of frames played
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn finish(self) -> Movie {
+        self.movie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_movie_bytes() {
+        let mut recorder = InputRecorder::new(vec![1, 2, 3]);
+        recorder.record(0x00);
+        recorder.record(0x81);
+        recorder.record(0x10);
+        let movie = InputRecorder::rerecord(recorder.finish(), 2).finish();
+        assert_eq!(movie.inputs(), [0x00, 0x81]);
+        assert_eq!(movie.rerecords(), 1);
+
+        let bytes = movie.to_bytes();
+        assert_eq!(Movie::from_bytes(&bytes).unwrap(), movie);
+        assert!(matches!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            Movie::from_bytes(b"GBSS"),
+            Err(MovieError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn test_player() {
+        let mut recorder = InputRecorder::new(Vec::new());
+        recorder.record(0x01);
+        let mut player = InputPlayer::new(recorder.finish());
+        assert_eq!(player.next_input(), Some(0x01));
+        assert_eq!(player.next_input(), None);
+        assert_eq!(player.frame(), 1);
+    }
+}
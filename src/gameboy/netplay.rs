@@ -0,0 +1,361 @@
+//! Lockstep netplay for two players on linked Game Boys, over TCP.
+//!
+//! Both sides emulate both Game Boys, linked by a `LocalLink` with their infrared ports facing
+//! each other through a `LocalIr`, and only the inputs travel: the buttons pressed for frame N
+//! are sent while frame N - `delay` runs, and a frame only runs once both players' buttons for it
+//! are in. The delay hides the round trip, with a delay of a few
+//! frames play is smooth as long as the network answers in that time.
+//!
+//! Emulation is deterministic, so both sides stay in sync by themselves. To catch it when they
+//! don't (a real-time clock, a bug) both send a checksum of their state every
+//! `CHECKSUM_INTERVAL` frames and stop with `NetplayError::Desync` if they differ.
+//!
+//! A session starts with a handshake where the host hands out the delay and both check they run
+//! the same ROM. Messages are 13 bytes, a kind, the frame as a `u32` and a `u64` value, all
+//! little endian.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+use crate::utils::{fnv1a, NetplayError};
+
+use super::{rom_hash, GameBoy, LocalIr, LocalLink};
+
+/// Sent by both sides after connecting, the last byte is the protocol version
+const MAGIC: &[u8; 7] = b"GBNETP\x01";
+/// The magic, the ROM hash and the delay
+const HANDSHAKE_SIZE: usize = MAGIC.len() + 8 + 4;
+
+const INPUT: u8 = 0x01;
+const CHECKSUM: u8 = 0x02;
+const MESSAGE_SIZE: usize = 13;
+
+/// Frames between state checksums, a second
+pub const CHECKSUM_INTERVAL: u32 = 60;
+
+/// How long to wait for the other side's buttons before giving up on it
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A message from the other side
+#[derive(Debug, Clone, Copy)]
+struct Message {
+    kind: u8,
+    frame: u32,
+    value: u64,
+}
+
+pub struct Netplay {
+    games: Vec<GameBoy>, // on the heap, they are too big to move around on the stack
+    player: usize,
+    delay: u32,
+    frame: u32,
+    stream: TcpStream,
+    messages: Receiver<Message>,
+    inputs: [BTreeMap<u32, u8>; 2], // each player's buttons by frame
+    checksums: [BTreeMap<u32, u64>; 2], // each side's checksums not compared yet
+}
+
+impl Netplay {
+    /// Wait for the second player to connect on `address`, running `rom` with `delay` frames of
+    /// input delay. The host is player 0
+    pub fn host(
+        address: impl ToSocketAddrs,
+        rom: Vec<u8>,
+        delay: u32,
+    ) -> Result<Netplay, NetplayError> {
+        let (stream, peer) = TcpListener::bind(address)?.accept()?;
+        tracing::info!(target: "serial", "netplay with {peer}");
+        Netplay::start(stream, 0, rom, delay)
+    }
+
+    /// Join the host at `address` as player 1, with the host's input delay
+    pub fn join(address: impl ToSocketAddrs, rom: Vec<u8>) -> Result<Netplay, NetplayError> {
+        Netplay::start(TcpStream::connect(address)?, 1, rom, 0)
+    }
+
+    fn start(
+        mut stream: TcpStream,
+        player: usize,
+        rom: Vec<u8>,
+        delay: u32,
+    ) -> Result<Netplay, NetplayError> {
+        stream.set_nodelay(true)?;
+        let mut handshake = MAGIC.to_vec();
+        handshake.extend_from_slice(&rom_hash(&rom).to_le_bytes());
+        handshake.extend_from_slice(&delay.to_le_bytes());
+        stream.write_all(&handshake)?;
+
+        let mut received = [0; HANDSHAKE_SIZE];
+        stream.read_exact(&mut received)?;
+        if received[..MAGIC.len()] != *MAGIC {
+            return Err(NetplayError::Handshake(received.to_vec()));
+        }
+        if received[MAGIC.len()..HANDSHAKE_SIZE - 4] != handshake[MAGIC.len()..HANDSHAKE_SIZE - 4] {
+            return Err(NetplayError::RomMismatch);
+        }
+        let delay = match player {
+            0 => delay,
+            _ => u32::from_le_bytes(received[HANDSHAKE_SIZE - 4..].try_into().unwrap()),
+        };
+
+        let (first, second) = LocalLink::pair();
+        let (first_ir, second_ir) = LocalIr::pair();
+        let mut games = Vec::new();
+        for (link, ir) in [(first, first_ir), (second, second_ir)] {
+            let mut game = GameBoy::new();
+            game.load_rom(rom.clone())?;
+            game.set_serial_transport(Box::new(link));
+            game.set_ir_transport(Box::new(ir));
+            games.push(game);
+        }
+
+        let (sender, messages) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::spawn(move || {
+            let mut bytes = [0; MESSAGE_SIZE];
+            while reader.read_exact(&mut bytes).is_ok() {
+                let message = Message {
+                    kind: bytes[0],
+                    frame: u32::from_le_bytes(bytes[1..5].try_into().unwrap()),
+                    value: u64::from_le_bytes(bytes[5..].try_into().unwrap()),
+                };
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // nobody pressed anything in the frames before the first inputs arrive
+        let inputs = core::array::from_fn(|_| (0..delay).map(|frame| (frame, 0)).collect());
+        Ok(Netplay {
+            games,
+            player,
+            delay,
+            frame: 0,
+            stream,
+            messages,
+            inputs,
+            checksums: Default::default(),
+        })
+    }
+
+    /// This side's player, 0 for the host and 1 for the one who joined
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    /// The frames run so far
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// The Game Boy of `player`
+    pub fn gameboy(&self, player: usize) -> &GameBoy {
+        &self.games[player]
+    }
+
+    /// This side's Game Boy, the one to show
+    pub fn local(&self) -> &GameBoy {
+        &self.games[self.player]
+    }
+
+    /// Take the audio samples of this side's Game Boy, see `GameBoy::drain_audio_samples`
+    pub fn drain_audio_samples(&mut self) -> Vec<i16> {
+        self.games[self.player].drain_audio_samples()
+    }
+
+    /// Send the buttons this side holds `delay` frames from now and run the next frame, waiting
+    /// for the other side's buttons for it if they are not in yet
+    ///
+    /// The buttons use the bit of each `Button` in `Button::ALL` order, see
+    /// `InputMap::pressed_buttons`.
+    pub fn run_frame(&mut self, buttons: u8) -> Result<(), NetplayError> {
+        let target = self.frame + self.delay;
+        self.inputs[self.player].insert(target, buttons);
+        self.send(INPUT, target, buttons.into())?;
+
+        let other = 1 - self.player;
+        while !self.inputs[other].contains_key(&self.frame) {
+            let message = self
+                .messages
+                .recv_timeout(TIMEOUT)
+                .map_err(|_| NetplayError::Disconnected)?;
+            self.receive(message);
+        }
+        while let Ok(message) = self.messages.try_recv() {
+            self.receive(message);
+        }
+
+        for (player, game) in self.games.iter_mut().enumerate() {
+            let buttons = self.inputs[player].remove(&self.frame).unwrap_or_default();
+            game.set_pressed_buttons(buttons);
+            game.run_until_vblank();
+        }
+        self.frame += 1;
+
+        if self.frame.is_multiple_of(CHECKSUM_INTERVAL) {
+            let checksum = self.checksum();
+            self.checksums[self.player].insert(self.frame, checksum);
+            self.send(CHECKSUM, self.frame, checksum)?;
+        }
+        self.compare_checksums()
+    }
+
+    fn receive(&mut self, message: Message) {
+        let other = 1 - self.player;
+        match message.kind {
+            INPUT => {
+                self.inputs[other].insert(message.frame, message.value as u8);
+            }
+            CHECKSUM => {
+                self.checksums[other].insert(message.frame, message.value);
+            }
+            kind => tracing::warn!(target: "serial", "unknown netplay message {kind:#04X}"),
+        }
+    }
+
+    fn send(&mut self, kind: u8, frame: u32, value: u64) -> Result<(), NetplayError> {
+        let mut bytes = [kind; MESSAGE_SIZE];
+        bytes[1..5].copy_from_slice(&frame.to_le_bytes());
+        bytes[5..].copy_from_slice(&value.to_le_bytes());
+        self.stream.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// A hash of both Game Boys' state
+    fn checksum(&self) -> u64 {
+        let mut state = self.games[0].save_state();
+        state.extend(self.games[1].save_state());
+        fnv1a(&state)
+    }
+
+    /// Check the checksums both sides have sent for a frame
+    fn compare_checksums(&mut self) -> Result<(), NetplayError> {
+        let [ours, theirs] = &mut self.checksums;
+        let frames: Vec<u32> = ours
+            .keys()
+            .filter(|frame| theirs.contains_key(frame))
+            .copied()
+            .collect();
+        for frame in frames {
+            if ours.remove(&frame) != theirs.remove(&frame) {
+                return Err(NetplayError::Desync(frame));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Netplay {
+    /// Hang up, the reader thread holds the connection open otherwise
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::test_rom;
+
+    /// Reads the joypad into WRAM forever, so the state depends on the buttons
+    fn make_rom() -> Vec<u8> {
+        #[rustfmt::skip]
+        let program = [
+            0x3E, 0x10, 0xE0, 0x00, // LD A, 0x10; LDH (P1), A
+            0xF0, 0x00, 0xEA, 0x00, 0xC0, // loop: LDH A, (P1); LD (0xC000), A
+            0x18, 0xF9, // JR loop
+        ];
+        test_rom(&program)
+    }
+
+    /// The host and the player who joined, on a free local port
+    fn session(
+        delay: u32,
+        rom: Vec<u8>,
+    ) -> (Result<Netplay, NetplayError>, Result<Netplay, NetplayError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host_rom = make_rom();
+        let host =
+            thread::spawn(move || Netplay::start(listener.accept().unwrap().0, 0, host_rom, delay));
+        let joined = Netplay::join(address, rom);
+        (host.join().unwrap(), joined)
+    }
+
+    /// Run `frames` frames on both sides, the host holding `buttons(frame)` and the other side
+    /// Start
+    fn play(
+        mut host: Netplay,
+        mut joined: Netplay,
+        frames: u32,
+        buttons: fn(u32) -> u8,
+    ) -> (Result<Netplay, NetplayError>, Result<Netplay, NetplayError>) {
+        let other = thread::spawn(move || {
+            for _ in 0..frames {
+                joined.run_frame(1 << 7)?;
+            }
+            Ok(joined)
+        });
+        let host = (0..frames)
+            .try_for_each(|frame| host.run_frame(buttons(frame)))
+            .map(|_| host);
+        (host, other.join().unwrap())
+    }
+
+    #[test]
+    fn test_lockstep() {
+        let (host, joined) = session(2, make_rom());
+        let (host, joined) = (host.unwrap(), joined.unwrap());
+        assert_eq!(joined.delay(), 2);
+
+        // player 1 holds A every other frame
+        let buttons = |frame: u32| u8::from(frame.is_multiple_of(2)) << 4;
+        let (host, joined) = play(host, joined, CHECKSUM_INTERVAL + 1, buttons);
+        let (host, joined) = (host.unwrap(), joined.unwrap());
+        assert_eq!(host.frame(), joined.frame());
+        assert_eq!(host.checksum(), joined.checksum());
+
+        // the last frame ran with the buttons sent two frames before
+        assert_eq!(host.gameboy(0).pressed_buttons(), buttons(host.frame() - 3));
+        assert_eq!(
+            joined.gameboy(0).pressed_buttons(),
+            buttons(host.frame() - 3)
+        );
+        assert_eq!(host.gameboy(1).pressed_buttons(), 1 << 7);
+        assert_eq!(joined.local().pressed_buttons(), 1 << 7);
+    }
+
+    #[test]
+    fn test_desync() {
+        let (host, joined) = session(0, make_rom());
+        let (host, mut joined) = (host.unwrap(), joined.unwrap());
+        joined.games[0].run_until_vblank();
+
+        let (host, joined) = play(host, joined, CHECKSUM_INTERVAL + 2, |_| 0);
+        assert!(matches!(host, Err(NetplayError::Desync(CHECKSUM_INTERVAL))));
+        assert!(matches!(
+            joined,
+            Err(NetplayError::Desync(CHECKSUM_INTERVAL))
+        ));
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let mut other_rom = make_rom();
+        other_rom[0x0200] = 0x01;
+        let (host, joined) = session(2, other_rom);
+        assert!(matches!(host, Err(NetplayError::RomMismatch)));
+        assert!(matches!(joined, Err(NetplayError::RomMismatch)));
+    }
+}
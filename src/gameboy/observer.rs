@@ -0,0 +1,60 @@
+//! Hooks into emulation for instrumentation.
+//!
+//! Observers added to a `GameBoy` are called at fixed points: before each instruction, on every
+//! memory access the CPU makes, when an interrupt is dispatched and when a frame completes.
+//! Profilers, tracers and test harnesses can watch execution without changing the CPU loop.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use super::{interrupts::Interrupt, Frame};
+
+/// Called by the core as emulation runs, every method does nothing by default
+pub trait Observer {
+    /// Before the instruction starting at `address` executes
+    fn on_instruction(&mut self, address: u16, opcode: u8) {}
+
+    /// The CPU read `value` from `address`
+    fn on_memory_read(&mut self, address: u16, value: u8) {}
+
+    /// The CPU wrote `value` to `address`
+    fn on_memory_write(&mut self, address: u16, value: u8) {}
+
+    /// The CPU jumped to the vector of `interrupt`
+    fn on_interrupt(&mut self, interrupt: Interrupt) {}
+
+    /// The PPU completed a frame and entered VBlank
+    fn on_frame(&mut self, frame: &Frame) {}
+}
+
+/// Every observer in turn, in the order they were added
+impl Observer for Vec<Box<dyn Observer + Send>> {
+    fn on_instruction(&mut self, address: u16, opcode: u8) {
+        for observer in self {
+            observer.on_instruction(address, opcode);
+        }
+    }
+
+    fn on_memory_read(&mut self, address: u16, value: u8) {
+        for observer in self {
+            observer.on_memory_read(address, value);
+        }
+    }
+
+    fn on_memory_write(&mut self, address: u16, value: u8) {
+        for observer in self {
+            observer.on_memory_write(address, value);
+        }
+    }
+
+    fn on_interrupt(&mut self, interrupt: Interrupt) {
+        for observer in self {
+            observer.on_interrupt(interrupt);
+        }
+    }
+
+    fn on_frame(&mut self, frame: &Frame) {
+        for observer in self {
+            observer.on_frame(frame);
+        }
+    }
+}
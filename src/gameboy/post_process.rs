@@ -0,0 +1,247 @@
+//! Post-processing of frames on their way to the screen, like the shaders of other emulators.
+//!
+//! The LCD grid scales every pixel up to 3x3 with darker gaps along its right and bottom edge, like
+//! the space between the pixels of the LCD. Frame blending shows every frame mixed half and half
+//! with the one before, so sprites a game flickers on and off every other frame look see-through
+//! like they did on the DMG's slow LCD. Ghosting goes further and fades every frame into all the
+//! ones shown before it, leaving trails behind moving sprites. Aspect correction shows pixels 8:7
+//! wide, the shape a Super Game Boy gives them on a TV, and integer scaling only scales by whole
+//! multiples. Both are up to the frontend drawing the frame, see `display_size`.
+
+use alloc::vec;
+
+use super::Frame;
+
+/// How many times larger the LCD grid makes a frame
+pub const GRID_SCALE: usize = 3;
+
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessor {
+    lcd_grid: bool,
+    frame_blending: bool,
+    ghosting: bool,
+    aspect_correction: bool,
+    integer_scaling: bool,
+    previous: Option<Frame>, // the last frame given, for frame blending
+    shown: Option<Frame>,    // the last frame with ghosting, faded into the next
+}
+
+impl PostProcessor {
+    pub fn new() -> PostProcessor {
+        PostProcessor::default()
+    }
+
+    pub fn set_lcd_grid(&mut self, enabled: bool) {
+        self.lcd_grid = enabled;
+    }
+
+    pub fn lcd_grid(&self) -> bool {
+        self.lcd_grid
+    }
+
+    pub fn set_frame_blending(&mut self, enabled: bool) {
+        self.frame_blending = enabled;
+        self.previous = None;
+    }
+
+    pub fn frame_blending(&self) -> bool {
+        self.frame_blending
+    }
+
+    pub fn set_ghosting(&mut self, enabled: bool) {
+        self.ghosting = enabled;
+        self.shown = None;
+    }
+
+    pub fn ghosting(&self) -> bool {
+        self.ghosting
+    }
+
+    /// Show pixels 8:7 wide
+    pub fn set_aspect_correction(&mut self, enabled: bool) {
+        self.aspect_correction = enabled;
+    }
+
+    pub fn aspect_correction(&self) -> bool {
+        self.aspect_correction
+    }
+
+    /// Only scale frames up by whole multiples, leaving a border around them
+    pub fn set_integer_scaling(&mut self, enabled: bool) {
+        self.integer_scaling = enabled;
+    }
+
+    pub fn integer_scaling(&self) -> bool {
+        self.integer_scaling
+    }
+
+    /// Apply frame blending, ghosting and the LCD grid, if enabled
+    pub fn process(&mut self, frame: Frame) -> Frame {
+        let frame = if self.frame_blending {
+            self.blend(frame)
+        } else {
+            frame
+        };
+        let frame = if self.ghosting {
+            self.ghost(frame)
+        } else {
+            frame
+        };
+        if self.lcd_grid {
+            grid(&frame)
+        } else {
+            frame
+        }
+    }
+
+    /// The size to show a processed `frame` at before scaling it to the window, wider with
+    /// aspect correction
+    pub fn display_size(&self, frame: &Frame) -> (usize, usize) {
+        let width = if self.aspect_correction {
+            (frame.width() * 8).div_ceil(7)
+        } else {
+            frame.width()
+        };
+        (width, frame.height())
+    }
+
+    /// Mix `frame` half and half with the frame before it
+    fn blend(&mut self, frame: Frame) -> Frame {
+        let blended = self
+            .previous
+            .as_ref()
+            .and_then(|previous| mix(&frame, previous));
+        let blended = blended.unwrap_or_else(|| frame.clone());
+        self.previous = Some(frame);
+        blended
+    }
+
+    /// Mix `frame` half and half with the last one shown, so every earlier frame fades out by
+    /// half each frame
+    fn ghost(&mut self, frame: Frame) -> Frame {
+        let shown = self.shown.take();
+        let shown = shown.and_then(|shown| mix(&frame, &shown)).unwrap_or(frame);
+        self.shown = Some(shown.clone());
+        shown
+    }
+}
+
+/// The average of two frames, `None` if their size differs as it does when a Super Game Boy
+/// border arrives
+fn mix(frame: &Frame, other: &Frame) -> Option<Frame> {
+    if (frame.width(), frame.height()) != (other.width(), other.height()) {
+        return None;
+    }
+    let rgba = frame
+        .as_bytes()
+        .iter()
+        .zip(other.as_bytes())
+        .map(|(&new, &old)| (u16::from(new) + u16::from(old)).div_ceil(2) as u8)
+        .collect();
+    Some(Frame::from_rgba(frame.width(), frame.height(), rgba))
+}
+
+/// `frame` scaled up by `GRID_SCALE`, the last row and column of every pixel darkened to 3/4
+fn grid(frame: &Frame) -> Frame {
+    let (width, height) = (frame.width() * GRID_SCALE, frame.height() * GRID_SCALE);
+    let mut rgba = vec![0; width * height * 4];
+    for (row, line) in rgba.chunks_exact_mut(width * 4).enumerate() {
+        let gap_row = row % GRID_SCALE == GRID_SCALE - 1;
+        for (column, pixel) in line.chunks_exact_mut(4).enumerate() {
+            let [r, g, b, a] = frame.pixel(column / GRID_SCALE, row / GRID_SCALE);
+            let color = if gap_row || column % GRID_SCALE == GRID_SCALE - 1 {
+                [darken(r), darken(g), darken(b), a]
+            } else {
+                [r, g, b, a]
+            };
+            pixel.copy_from_slice(&color);
+        }
+    }
+    Frame::from_rgba(width, height, rgba)
+}
+
+fn darken(channel: u8) -> u8 {
+    (u16::from(channel) * 3 / 4) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(color: [u8; 4]) -> Frame {
+        let mut frame = Frame::blank(2, 2);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            frame.set_pixel(x, y, color);
+        }
+        frame
+    }
+
+    #[test]
+    fn test_lcd_grid() {
+        let mut post = PostProcessor::new();
+        let frame = filled([0xFF, 0x80, 0x00, 0xFF]);
+        assert_eq!(post.process(frame.clone()), frame);
+
+        post.set_lcd_grid(true);
+        let gridded = post.process(frame);
+        assert_eq!((gridded.width(), gridded.height()), (6, 6));
+        assert_eq!(gridded.pixel(0, 0), [0xFF, 0x80, 0x00, 0xFF]);
+        assert_eq!(gridded.pixel(2, 0), [0xBF, 0x60, 0x00, 0xFF]);
+        assert_eq!(gridded.pixel(3, 3), [0xFF, 0x80, 0x00, 0xFF]);
+        assert_eq!(gridded.pixel(3, 5), [0xBF, 0x60, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_frame_blending() {
+        let mut post = PostProcessor::new();
+        post.set_frame_blending(true);
+        let black = filled([0x00, 0x00, 0x00, 0xFF]);
+        let white = filled([0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(post.process(black.clone()), black);
+        // a sprite flickering every other frame stays half visible
+        assert_eq!(
+            post.process(white.clone()).pixel(0, 0),
+            [0x80, 0x80, 0x80, 0xFF]
+        );
+        assert_eq!(
+            post.process(black.clone()).pixel(0, 0),
+            [0x80, 0x80, 0x80, 0xFF]
+        );
+        // only the frame before counts, unlike ghosting
+        assert_eq!(post.process(black.clone()), black);
+        assert_eq!(
+            post.process(white.clone()).pixel(0, 0),
+            [0x80, 0x80, 0x80, 0xFF]
+        );
+        assert_eq!(post.process(white.clone()), white);
+    }
+
+    #[test]
+    fn test_ghosting() {
+        let mut post = PostProcessor::new();
+        post.set_ghosting(true);
+        let black = filled([0x00, 0x00, 0x00, 0xFF]);
+        let white = filled([0xFF, 0xFF, 0xFF, 0xFF]);
+
+        assert_eq!(post.process(black.clone()), black);
+        assert_eq!(
+            post.process(white.clone()).pixel(0, 0),
+            [0x80, 0x80, 0x80, 0xFF]
+        );
+        assert_eq!(post.process(white).pixel(0, 0), [0xC0, 0xC0, 0xC0, 0xFF]);
+
+        // a new size starts over
+        let border = Frame::blank(4, 4);
+        assert_eq!(post.process(border.clone()), border);
+    }
+
+    #[test]
+    fn test_display_size() {
+        let mut post = PostProcessor::new();
+        let frame = Frame::blank(160, 144);
+        assert_eq!(post.display_size(&frame), (160, 144));
+        post.set_aspect_correction(true);
+        assert_eq!(post.display_size(&frame), (183, 144));
+    }
+}
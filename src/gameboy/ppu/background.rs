@@ -0,0 +1,190 @@
+//! Background layer rendering.
+//!
+//! In CGB mode every tile map entry has an attribute byte at the same address in VRAM bank 1,
+//! selecting the palette and tile data bank, flipping the tile and giving it priority over sprites.
+
+use super::{lcdc, LCDC, SCREEN_WIDTH, SCX, SCY};
+use crate::gameboy::Memory;
+
+pub const TILE_MAP_0: u16 = 0x9800;
+pub const TILE_MAP_1: u16 = 0x9C00;
+
+/// CGB tile map attribute bits
+pub mod map_attributes {
+    pub const PALETTE: u8 = 0b111;
+    pub const BANK: u8 = 1 << 3;
+    pub const X_FLIP: u8 = 1 << 5;
+    pub const Y_FLIP: u8 = 1 << 6;
+    pub const PRIORITY: u8 = 1 << 7;
+}
+
+/// A background or window pixel before palette mapping
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BackgroundPixel {
+    pub color_id: u8,
+    pub attributes: u8, // the CGB tile map attributes, 0 on the DMG
+}
+
+/// Render the background pixels of a scanline
+///
+/// On the DMG a disabled background is blank, in CGB mode LCDC bit 0 only takes away its priority
+pub fn render_line(memory: &Memory, line: u8, cgb_mode: bool) -> [BackgroundPixel; SCREEN_WIDTH] {
+    let mut pixels = [BackgroundPixel::default(); SCREEN_WIDTH];
+    let control = memory.read_byte(LCDC);
+
+    if control & lcdc::BG_ENABLE == 0 && !cgb_mode {
+        return pixels;
+    }
+
+    let tile_map = if control & lcdc::BG_TILE_MAP != 0 {
+        TILE_MAP_1
+    } else {
+        TILE_MAP_0
+    };
+    let y = line.wrapping_add(memory.read_byte(SCY));
+    let scroll_x = memory.read_byte(SCX);
+
+    for (screen_x, pixel) in pixels.iter_mut().enumerate() {
+        let x = (screen_x as u8).wrapping_add(scroll_x);
+        let map_address = tile_map + u16::from(y / 8) * 32 + u16::from(x / 8);
+        *pixel = map_pixel(memory, control, map_address, cgb_mode, x % 8, y % 8);
+    }
+
+    pixels
+}
+
+/// The pixel at `x`, `y` in the tile referenced by a tile map entry
+pub fn map_pixel(
+    memory: &Memory,
+    control: u8,
+    map_address: u16,
+    cgb_mode: bool,
+    x: u8,
+    y: u8,
+) -> BackgroundPixel {
+    let tile_index = memory.read_vram(map_address);
+    let attributes = if cgb_mode {
+        memory.read_vram_bank(1, map_address)
+    } else {
+        0
+    };
+
+    BackgroundPixel {
+        color_id: tile_pixel(memory, control, tile_index, attributes, x, y),
+        attributes,
+    }
+}
+
+/// Address of a tile in VRAM, honoring the LCDC tile data addressing mode
+pub fn tile_address(control: u8, tile_index: u8) -> u16 {
+    if control & lcdc::TILE_DATA != 0 {
+        0x8000 + u16::from(tile_index) * 16
+    } else {
+        0x9000u16.wrapping_add_signed(i16::from(tile_index as i8) * 16)
+    }
+}
+
+/// VRAM bank and address of the low byte of row `y` of a background/window tile
+pub fn tile_row_address(control: u8, tile_index: u8, attributes: u8, y: u8) -> (usize, u16) {
+    let y = if attributes & map_attributes::Y_FLIP != 0 {
+        7 - y
+    } else {
+        y
+    };
+    let bank = usize::from(attributes & map_attributes::BANK != 0);
+    (bank, tile_address(control, tile_index) + u16::from(y) * 2)
+}
+
+/// Color id of a pixel in a background/window tile, flipped and banked by its CGB attributes
+pub fn tile_pixel(
+    memory: &Memory,
+    control: u8,
+    tile_index: u8,
+    attributes: u8,
+    x: u8,
+    y: u8,
+) -> u8 {
+    let (bank, address) = tile_row_address(control, tile_index, attributes, y);
+    let lo = memory.read_vram_bank(bank, address);
+    let hi = memory.read_vram_bank(bank, address + 1);
+    let bit = if attributes & map_attributes::X_FLIP != 0 {
+        x
+    } else {
+        7 - x
+    };
+
+    (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_address() {
+        assert_eq!(tile_address(lcdc::TILE_DATA, 0), 0x8000);
+        assert_eq!(tile_address(lcdc::TILE_DATA, 0xFF), 0x8FF0);
+        assert_eq!(tile_address(0, 0), 0x9000);
+        assert_eq!(tile_address(0, 0x7F), 0x97F0);
+        assert_eq!(tile_address(0, 0x80), 0x8800);
+    }
+
+    #[test]
+    fn test_tile_pixel() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x8000, 0b1010_0000);
+        memory.write_byte(0x8001, 0b1100_0000);
+
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, 0, 0, 0), 3);
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, 0, 1, 0), 2);
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, 0, 2, 0), 1);
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, 0, 3, 0), 0);
+    }
+
+    #[test]
+    fn test_tile_pixel_attributes() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x8000, 0b1000_0000);
+
+        let flipped = map_attributes::X_FLIP | map_attributes::Y_FLIP;
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, flipped, 7, 7), 1);
+        assert_eq!(tile_pixel(&memory, lcdc::TILE_DATA, 0, flipped, 0, 0), 0);
+        // bank 1 is empty
+        assert_eq!(
+            tile_pixel(&memory, lcdc::TILE_DATA, 0, map_attributes::BANK, 0, 0),
+            0
+        );
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::BG_ENABLE | lcdc::TILE_DATA);
+        // tile 1 is solid color 1
+        for row in 0..8 {
+            memory.write_byte(0x8010 + row * 2, 0xFF);
+        }
+        // second row, second column of the map
+        memory.write_byte(0x9800 + 32 + 1, 0x01);
+        memory.write_byte(SCX, 4);
+        memory.write_byte(SCY, 8);
+
+        let color_ids = render_line(&memory, 0, false).map(|pixel| pixel.color_id);
+
+        assert_eq!(color_ids[3], 0);
+        assert_eq!(color_ids[4..12], [1; 8]);
+        assert_eq!(color_ids[12], 0);
+    }
+
+    #[test]
+    fn test_background_disabled() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::TILE_DATA);
+        memory.write_byte(0x8000, 0xFF);
+
+        assert_eq!(
+            render_line(&memory, 0, false),
+            [BackgroundPixel::default(); SCREEN_WIDTH]
+        );
+    }
+}
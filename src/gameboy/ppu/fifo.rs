@@ -0,0 +1,356 @@
+//! Pixel FIFO rendering, drawing a line one dot at a time like the hardware does.
+//!
+//! A fetcher reads a row of a background or window tile in 6 dots and pushes it into the
+//! background FIFO once that has run empty, every dot one pixel is shifted out and mixed with
+//! the sprite FIFO. Registers are read when the fetcher or shifter needs them, so SCX, LCDC and
+//! palette writes in the middle of a line show up in the middle of the line.
+//!
+//! The length of the drawing mode falls out of the fetcher: the first fetch of a line is thrown
+//! away, SCX % 8 pixels are discarded, the fetcher restarts when the window starts and the
+//! shifter stalls while a sprite is fetched.
+
+use alloc::collections::VecDeque;
+
+use super::{
+    background::{map_attributes, tile_row_address, BackgroundPixel, TILE_MAP_0, TILE_MAP_1},
+    lcdc,
+    sprites::{self, Sprite, SpritePixel},
+    window, LCDC, SCX, SCY,
+};
+use crate::gameboy::Memory;
+
+const FETCH_DOTS: u8 = 6; // the tile index, low and high byte take 2 dots each
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+/// The background and window tile fetcher
+#[derive(Default)]
+struct Fetcher {
+    dots: u8,   // dots into the current fetch, FETCH_DOTS once the row waits to be pushed
+    tile_x: u8, // tiles pushed on this line, restarts at the window
+    tile_index: u8,
+    attributes: u8,
+    row: u8,
+    lo: u8,
+    hi: u8,
+    discard: bool, // the first fetch of a line is thrown away
+}
+
+pub struct PixelFifo {
+    line: u8,
+    window_line: u8,
+    window_triggered: bool,
+    sprite_height: u8,
+    index_priority: bool, // overlapping sprites go by OAM index only
+    background: VecDeque<BackgroundPixel>,
+    sprites: VecDeque<Option<(SpritePixel, u8)>>, // lined up with the background, with the OAM index
+    pending: VecDeque<Sprite>,                    // the line's sprites still to fetch, by X
+    fetcher: Fetcher,
+    sprite_fetch: Option<(Sprite, u8)>, // the sprite the shifter stalls for and the dots spent on it
+    discard: u8,                        // pixels to throw away before drawing, SCX % 8
+    x: u8,                              // the next screen column
+    window: bool,                       // the fetcher switched to the window
+}
+
+impl PixelFifo {
+    /// Start drawing `line`, the window is drawn from `window_line` if it starts on this line
+    pub fn new(memory: &Memory, line: u8, window_line: u8, window_triggered: bool) -> PixelFifo {
+        let sprite_height = sprites::sprite_height(memory.read_byte(LCDC));
+        let mut pending = sprites::select_sprites(memory, line, sprite_height);
+        // stable sort keeps OAM order for equal X
+        pending.sort_by_key(|sprite| sprite.x);
+
+        PixelFifo {
+            line,
+            window_line,
+            window_triggered,
+            sprite_height,
+            index_priority: memory.sprite_index_priority(),
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            pending: pending.into(),
+            fetcher: Fetcher {
+                discard: true,
+                ..Fetcher::default()
+            },
+            sprite_fetch: None,
+            discard: memory.read_byte(SCX) % 8,
+            x: 0,
+            window: false,
+        }
+    }
+
+    /// Whether the window was drawn on this line
+    pub fn window_drawn(&self) -> bool {
+        self.window
+    }
+
+    /// Advance a dot, returning the screen column and pixels shifted out, if any
+    pub fn tick(
+        &mut self,
+        memory: &Memory,
+        cgb_mode: bool,
+    ) -> Option<(usize, BackgroundPixel, Option<SpritePixel>)> {
+        let control = memory.read_byte(LCDC);
+        self.step_fetcher(memory, control, cgb_mode);
+
+        // a sprite is fetched once the background fetcher has its row ready
+        if let Some((sprite, dots)) = self.sprite_fetch.as_mut() {
+            if self.fetcher.dots < FETCH_DOTS {
+                return None;
+            }
+            *dots += 1;
+            if *dots < SPRITE_FETCH_DOTS {
+                return None;
+            }
+            let sprite = *sprite;
+            self.sprite_fetch = None;
+            self.merge_sprite(memory, &sprite, cgb_mode);
+        }
+
+        if self.background.is_empty() || self.start_window(memory, control, cgb_mode) {
+            return None;
+        }
+        if let Some(sprite) = self.next_sprite(control) {
+            self.sprite_fetch = Some((sprite, 0));
+            return None;
+        }
+
+        let pixel = self.background.pop_front()?;
+        if self.discard > 0 {
+            self.discard -= 1;
+            return None;
+        }
+        let sprite = self
+            .sprites
+            .pop_front()
+            .flatten()
+            .filter(|_| control & lcdc::OBJ_ENABLE != 0)
+            .map(|(pixel, _)| pixel);
+        let x = usize::from(self.x);
+        self.x += 1;
+        Some((x, pixel, sprite))
+    }
+
+    fn step_fetcher(&mut self, memory: &Memory, control: u8, cgb_mode: bool) {
+        if self.fetcher.dots < FETCH_DOTS {
+            self.fetcher.dots += 1;
+            match self.fetcher.dots {
+                2 => self.fetch_tile(memory, control, cgb_mode),
+                4 | 6 => {
+                    let fetcher = &mut self.fetcher;
+                    let (bank, address) = tile_row_address(
+                        control,
+                        fetcher.tile_index,
+                        fetcher.attributes,
+                        fetcher.row,
+                    );
+                    if fetcher.dots == 4 {
+                        fetcher.lo = memory.read_vram_bank(bank, address);
+                    } else {
+                        fetcher.hi = memory.read_vram_bank(bank, address + 1);
+                    }
+                }
+                _ => {}
+            }
+            if self.fetcher.dots == FETCH_DOTS && self.fetcher.discard {
+                self.fetcher = Fetcher::default();
+            }
+            return;
+        }
+
+        if !self.background.is_empty() {
+            return;
+        }
+        let fetcher = &mut self.fetcher;
+        // on the DMG a disabled background is blank, in CGB mode LCDC bit 0 only takes away its priority
+        let blank = control & lcdc::BG_ENABLE == 0 && !cgb_mode;
+        for column in 0..8 {
+            let bit = if fetcher.attributes & map_attributes::X_FLIP != 0 {
+                column
+            } else {
+                7 - column
+            };
+            self.background.push_back(if blank {
+                BackgroundPixel::default()
+            } else {
+                BackgroundPixel {
+                    color_id: (((fetcher.hi >> bit) & 1) << 1) | ((fetcher.lo >> bit) & 1),
+                    attributes: fetcher.attributes,
+                }
+            });
+        }
+        fetcher.tile_x = fetcher.tile_x.wrapping_add(1);
+        fetcher.dots = 0;
+    }
+
+    /// Read the tile map entry of the next tile, the background scroll is read at this point
+    fn fetch_tile(&mut self, memory: &Memory, control: u8, cgb_mode: bool) {
+        let fetcher = &mut self.fetcher;
+        let map_address = if self.window {
+            let tile_map = if control & lcdc::WINDOW_TILE_MAP != 0 {
+                TILE_MAP_1
+            } else {
+                TILE_MAP_0
+            };
+            fetcher.row = self.window_line % 8;
+            tile_map + u16::from(self.window_line / 8) * 32 + u16::from(fetcher.tile_x % 32)
+        } else {
+            let tile_map = if control & lcdc::BG_TILE_MAP != 0 {
+                TILE_MAP_1
+            } else {
+                TILE_MAP_0
+            };
+            let y = self.line.wrapping_add(memory.read_byte(SCY));
+            let column = (memory.read_byte(SCX) / 8).wrapping_add(fetcher.tile_x) % 32;
+            fetcher.row = y % 8;
+            tile_map + u16::from(y / 8) * 32 + u16::from(column)
+        };
+
+        fetcher.tile_index = memory.read_vram(map_address);
+        fetcher.attributes = if cgb_mode {
+            memory.read_vram_bank(1, map_address)
+        } else {
+            0
+        };
+    }
+
+    /// Switch the fetcher to the window when the shifter reaches WX, returns whether it did
+    ///
+    /// On the DMG the window is hidden along with the background by LCDC bit 0, not so in CGB mode
+    fn start_window(&mut self, memory: &Memory, control: u8, cgb_mode: bool) -> bool {
+        if self.window
+            || !self.window_triggered
+            || self.discard > 0
+            || control & lcdc::WINDOW_ENABLE == 0
+            || (control & lcdc::BG_ENABLE == 0 && !cgb_mode)
+        {
+            return false;
+        }
+        // WX is offset by 7, values below 7 start the window off screen
+        let wx = memory.read_byte(window::WX);
+        if u16::from(self.x) + 7 != u16::from(wx) && !(self.x == 0 && wx < 7) {
+            return false;
+        }
+
+        self.window = true;
+        self.background.clear();
+        self.discard = 7u8.saturating_sub(wx);
+        self.fetcher = Fetcher::default();
+        self.step_fetcher(memory, control, cgb_mode);
+        true
+    }
+
+    /// The sprite to stall for at the current column, sprites at X 0 are fetched before the
+    /// SCX pixels are discarded
+    fn next_sprite(&mut self, control: u8) -> Option<Sprite> {
+        while let Some(&sprite) = self.pending.front() {
+            if sprite.x != 0 && (self.discard > 0 || sprite.x > self.x + 8) {
+                return None;
+            }
+            self.pending.pop_front();
+            // without sprites enabled nothing is fetched
+            if control & lcdc::OBJ_ENABLE != 0 {
+                return Some(sprite);
+            }
+        }
+        None
+    }
+
+    /// Mix a fetched sprite into the sprite FIFO
+    ///
+    /// On the DMG the pixel already in the FIFO wins, it belongs to a sprite with a lower X or
+    /// OAM index. In CGB mode only the OAM index counts, unless OPRI selects the DMG's order
+    fn merge_sprite(&mut self, memory: &Memory, sprite: &Sprite, cgb_mode: bool) {
+        let row = sprites::sprite_row(memory, sprite, self.line, self.sprite_height, cgb_mode);
+        // sprite X is offset by 8, columns left of the shifter are off screen
+        let skip = (i16::from(self.x) + 8 - i16::from(sprite.x)).max(0) as usize;
+
+        for (slot, pixel) in row.into_iter().skip(skip).enumerate() {
+            if self.sprites.len() <= slot {
+                self.sprites.push_back(None);
+            }
+            let Some(pixel) = pixel else {
+                continue;
+            };
+            let current = &mut self.sprites[slot];
+            let wins = match current {
+                None => true,
+                Some((_, index)) => self.index_priority && sprite.index < *index,
+            };
+            if wins {
+                *current = Some((pixel, sprite.index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dots until the last pixel of the line is shifted out
+    fn drawing_dots(memory: &Memory) -> u32 {
+        let mut fifo = PixelFifo::new(memory, 0, 0, memory.read_byte(window::WY) == 0);
+        let mut dots = 0;
+        loop {
+            dots += 1;
+            if let Some((159, _, _)) = fifo.tick(memory, false) {
+                return dots;
+            }
+        }
+    }
+
+    #[test]
+    fn test_drawing_dots() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::BG_ENABLE);
+        assert_eq!(drawing_dots(&memory), 172);
+
+        memory.write_byte(SCX, 5);
+        assert_eq!(drawing_dots(&memory), 177);
+        memory.write_byte(SCX, 0);
+
+        memory.write_byte(
+            LCDC,
+            lcdc::LCD_ENABLE | lcdc::BG_ENABLE | lcdc::WINDOW_ENABLE,
+        );
+        memory.write_byte(window::WX, 7);
+        assert_eq!(drawing_dots(&memory), 178);
+
+        // the same sprite penalties as the table, see sprites::fetch_penalty
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::OBJ_ENABLE);
+        memory.write_byte(sprites::OAM_START, 16);
+        for x in [0, 8, 13, 40] {
+            memory.write_byte(sprites::OAM_START + 1, x);
+            let sprite = Sprite::read(&memory, 0);
+            assert_eq!(
+                drawing_dots(&memory),
+                172 + sprites::fetch_penalty(&[sprite], 0)
+            );
+        }
+    }
+
+    #[test]
+    fn test_sprite_fifo_priority() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::OBJ_ENABLE);
+        // tile 0 is solid color 1, tile 1 solid color 2
+        memory.write_byte(0x8000, 0xFF);
+        memory.write_byte(0x8011, 0xFF);
+        // sprite 0 at column 4 over sprite 1 at column 0
+        for (address, value) in [(0xFE00, 16), (0xFE01, 12), (0xFE04, 16), (0xFE05, 8)] {
+            memory.write_byte(address, value);
+        }
+        memory.write_byte(0xFE06, 1);
+
+        let mut fifo = PixelFifo::new(&memory, 0, 0, false);
+        let mut color_ids = Vec::new();
+        while color_ids.len() < 16 {
+            if let Some((_, _, sprite)) = fifo.tick(&memory, false) {
+                color_ids.push(sprite.map_or(0, |sprite| sprite.color_id));
+            }
+        }
+        // on the DMG the lower X wins
+        assert_eq!(color_ids, [2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,324 @@
+//! RGBA8888 frames for frontends.
+//!
+//! DMG frames map the four shades through a `DmgPalette`, CGB frames expand the RGB555 colors
+//! the PPU rendered. Frames are the size of the screen, except with a Super Game Boy border.
+//!
+//! Palettes load from `.pal` files, either JASC-PAL as written by Paint Shop Pro and Aseprite or
+//! one hex color per line (`#RRGGBB`, `RRGGBB` or Paint.NET's `AARRGGBB`, `;` starts a comment),
+//! with the four colors from lightest to darkest.
+
+use alloc::{string::ToString, vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::{io::Write, path::Path};
+
+use crate::utils::PaletteError;
+#[cfg(feature = "std")]
+use crate::utils::ScreenshotError;
+
+use super::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const PIXELS: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+/// The RGBA colors shown for the four DMG shades, from lightest (0) to darkest (3)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DmgPalette(pub [[u8; 4]; 4]);
+
+impl DmgPalette {
+    pub const GRAYSCALE: DmgPalette = DmgPalette([
+        [0xFF, 0xFF, 0xFF, 0xFF],
+        [0xAA, 0xAA, 0xAA, 0xFF],
+        [0x55, 0x55, 0x55, 0xFF],
+        [0x00, 0x00, 0x00, 0xFF],
+    ]);
+
+    /// The green tint of the original DMG screen
+    pub const CLASSIC_GREEN: DmgPalette = DmgPalette([
+        [0x9B, 0xBC, 0x0F, 0xFF],
+        [0x8B, 0xAC, 0x0F, 0xFF],
+        [0x30, 0x62, 0x30, 0xFF],
+        [0x0F, 0x38, 0x0F, 0xFF],
+    ]);
+
+    /// The olive tones of the Game Boy Pocket screen
+    pub const POCKET: DmgPalette = DmgPalette([
+        [0xC4, 0xCF, 0xA1, 0xFF],
+        [0x8B, 0x95, 0x6D, 0xFF],
+        [0x4D, 0x53, 0x3C, 0xFF],
+        [0x1F, 0x1F, 0x1F, 0xFF],
+    ]);
+
+    /// The built-in palettes by the names settings and `--palette` use
+    pub const NAMED: [(&'static str, DmgPalette); 3] = [
+        ("grayscale", DmgPalette::GRAYSCALE),
+        ("classic-green", DmgPalette::CLASSIC_GREEN),
+        ("pocket", DmgPalette::POCKET),
+    ];
+
+    pub fn named(name: &str) -> Option<DmgPalette> {
+        DmgPalette::NAMED
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// The name of a built-in palette
+    pub fn name(&self) -> Option<&'static str> {
+        DmgPalette::NAMED
+            .iter()
+            .find(|(_, palette)| palette == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Parse the text of a `.pal` file
+    pub fn parse(text: &str) -> Result<DmgPalette, PaletteError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(number, line)| {
+                (
+                    number + 1,
+                    line.split(';').next().unwrap_or_default().trim(),
+                )
+            })
+            .filter(|(_, line)| !line.is_empty())
+            .peekable();
+        // JASC-PAL, its version and the number of colors, then a color per line in decimal
+        let jasc = lines.peek().is_some_and(|(_, line)| *line == "JASC-PAL");
+        if jasc {
+            lines.nth(2);
+        }
+
+        let mut colors = Vec::new();
+        for (number, line) in lines {
+            let color = if jasc {
+                parse_decimal(line)
+            } else {
+                parse_hex(line)
+            };
+            colors.push(color.ok_or_else(|| PaletteError::InvalidColor {
+                line: number,
+                text: line.to_string(),
+            })?);
+        }
+        let colors: [[u8; 4]; 4] = colors
+            .try_into()
+            .map_err(|colors: Vec<_>| PaletteError::ColorCount(colors.len()))?;
+        Ok(DmgPalette(colors))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<DmgPalette, PaletteError> {
+        DmgPalette::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn color(&self, shade: u8) -> [u8; 4] {
+        self.0[usize::from(shade & 0x3)]
+    }
+}
+
+/// `R G B` in decimal, JASC-PAL files may add an alpha that is ignored
+fn parse_decimal(line: &str) -> Option<[u8; 4]> {
+    let mut channels = line.split_whitespace().map(|channel| channel.parse().ok());
+    let color = [
+        channels.next()??,
+        channels.next()??,
+        channels.next()??,
+        0xFF,
+    ];
+    (channels.count() <= 1).then_some(color)
+}
+
+/// `#RRGGBB`, `RRGGBB` or `AARRGGBB` with the alpha ignored
+fn parse_hex(line: &str) -> Option<[u8; 4]> {
+    let hex = line.strip_prefix('#').unwrap_or(line);
+    let rgb = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| matches!(hex.len(), 6 | 8))?;
+    let [_, r, g, b] = rgb.to_be_bytes();
+    Some([r, g, b, 0xFF])
+}
+
+impl Default for DmgPalette {
+    fn default() -> Self {
+        DmgPalette::GRAYSCALE
+    }
+}
+
+/// A rendered screen, 4 bytes (red, green, blue, alpha) per pixel, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+}
+
+impl Frame {
+    /// A transparent frame of `width` by `height` pixels
+    pub fn blank(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            rgba: vec![0; width * height * 4],
+        }
+    }
+
+    /// Color a DMG frame of shades (0-3)
+    pub fn from_shades(shades: &[u8; PIXELS], palette: &DmgPalette) -> Frame {
+        Frame {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            rgba: shades
+                .iter()
+                .flat_map(|&shade| palette.color(shade))
+                .collect(),
+        }
+    }
+
+    /// Convert a CGB frame of RGB555 colors
+    pub fn from_rgb555(colors: &[u16; PIXELS]) -> Frame {
+        Frame {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            rgba: colors
+                .iter()
+                .flat_map(|&color| rgb555_to_rgba(color))
+                .collect(),
+        }
+    }
+
+    /// A frame of `width` by `height` pixels from 4 bytes per pixel, row by row
+    pub fn from_rgba(width: usize, height: usize, rgba: Vec<u8>) -> Frame {
+        assert_eq!(rgba.len(), width * height * 4, "wrong size for a frame");
+        Frame {
+            width,
+            height,
+            rgba,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The color of the pixel at `x`, `y`
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].try_into().unwrap()
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].copy_from_slice(&color);
+    }
+
+    /// All pixels, 4 bytes each
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.rgba
+    }
+
+    /// Encode the frame as an RGBA PNG
+    #[cfg(feature = "std")]
+    pub fn write_png(&self, writer: impl Write) -> Result<(), ScreenshotError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Expand a 5 bit per channel color to 8 bits per channel
+pub fn rgb555_to_rgba(color: u16) -> [u8; 4] {
+    let channel = |shift: u16| {
+        let value = ((color >> shift) & 0x1F) as u8;
+        (value << 3) | (value >> 2)
+    };
+    [channel(0), channel(5), channel(10), 0xFF]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_shades() {
+        let mut shades = [0; PIXELS];
+        shades[1] = 3;
+        shades[SCREEN_WIDTH] = 2;
+
+        let frame = Frame::from_shades(&shades, &DmgPalette::CLASSIC_GREEN);
+
+        assert_eq!(frame.as_bytes().len(), PIXELS * 4);
+        assert_eq!(frame.pixel(0, 0), DmgPalette::CLASSIC_GREEN.0[0]);
+        assert_eq!(frame.pixel(1, 0), DmgPalette::CLASSIC_GREEN.0[3]);
+        assert_eq!(frame.pixel(0, 1), DmgPalette::CLASSIC_GREEN.0[2]);
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        let jasc = "JASC-PAL\n0100\n4\n224 248 208\n136 192 112\n52 104 86\n8 24 32\n";
+        let palette = DmgPalette::parse(jasc).unwrap();
+        assert_eq!(palette.color(0), [224, 248, 208, 0xFF]);
+        assert_eq!(palette.color(3), [8, 24, 32, 0xFF]);
+
+        let hex = "; lightest first\n#E0F8D0\n88c070\nFF346856\n\n#081820\n";
+        assert_eq!(DmgPalette::parse(hex).unwrap(), palette);
+
+        assert!(matches!(
+            DmgPalette::parse("#E0F8D0\n#88C070\n#346856"),
+            Err(PaletteError::ColorCount(3))
+        ));
+        assert!(matches!(
+            DmgPalette::parse("#E0F8D0\nwhite"),
+            Err(PaletteError::InvalidColor { line: 2, .. })
+        ));
+        assert_eq!(DmgPalette::named("pocket"), Some(DmgPalette::POCKET));
+        assert_eq!(DmgPalette::GRAYSCALE.name(), Some("grayscale"));
+        assert_eq!(palette.name(), None);
+    }
+
+    #[test]
+    fn test_from_rgb555() {
+        let mut colors = [0; PIXELS];
+        colors[0] = 0x7FFF;
+        colors[1] = 0x001F;
+
+        let frame = Frame::from_rgb555(&colors);
+
+        assert_eq!(frame.pixel(0, 0), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(frame.pixel(1, 0), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(frame.pixel(2, 0), [0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_write_png() {
+        let mut shades = [0; PIXELS];
+        shades[SCREEN_WIDTH + 2] = 1;
+        let frame = Frame::from_shades(&shades, &DmgPalette::POCKET);
+        let mut png = Vec::new();
+        frame.write_png(&mut png).unwrap();
+
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (160, 144));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(decoded, frame.as_bytes());
+    }
+
+    #[test]
+    fn test_rgb555_to_rgba() {
+        assert_eq!(rgb555_to_rgba(0x03E0), [0x00, 0xFF, 0x00, 0xFF]);
+        assert_eq!(rgb555_to_rgba(0x4000), [0x00, 0x00, 0x84, 0xFF]);
+    }
+}
@@ -0,0 +1,1059 @@
+//! The picture processing unit, renders the background, window and sprites into a 160x144 framebuffer.
+//!
+//! The PPU registers are kept by `LcdRegisters` in the IO region of memory, the PPU reads them
+//! every scanline.
+//! Every scanline takes 456 dots: OAM scan (80 dots), drawing (172 to 289 dots) and HBlank,
+//! followed by 10 lines of VBlank after the 144 visible lines. Drawing is lengthened by the
+//! pixels discarded for SCX % 8, by starting the window and by fetching sprites, HBlank
+//! shrinks to match. The PPU hands the HBlank left to memory, for CGB HBlank HDMA, when HBlank
+//! starts and when a transfer starts during it, see `HBlankTiming`.
+//!
+//! LY is compared with LYC at the start of every line and again whenever LYC or STAT is written,
+//! so a game moving LYC mid-frame gets its coincidence flag and LYC interrupt right away when
+//! the new value matches the current line.
+//!
+//! Clearing LCDC bit 7 turns the LCD off: the screen goes blank, LY and the dot counter reset
+//! and STAT reports HBlank. Turning it back on starts a frame from line 0, whose OAM scan is
+//! skipped.
+//!
+//! Lines are drawn at once when the drawing mode ends, or pixel by pixel through a pixel FIFO
+//! with `Renderer::PixelFifo` for games that change registers in the middle of a line.
+//!
+//! In CGB mode the pixels are colored through the CGB palette RAM into an RGB555 framebuffer,
+//! the shade framebuffer then holds an approximation of their brightness.
+
+mod background;
+mod fifo;
+mod frame;
+mod palettes;
+mod registers;
+mod sprites;
+mod viewer;
+mod window;
+
+use alloc::vec::Vec;
+
+use crate::utils::SaveStateError;
+
+use background::{map_attributes, BackgroundPixel};
+use fifo::PixelFifo;
+use palettes::rgb555_to_shade;
+use sprites::SpritePixel;
+
+use super::{
+    interrupts::{request_interrupt, Interrupt},
+    save_state::{SaveState, StateReader, StateWriter},
+    Memory,
+};
+
+pub use frame::{rgb555_to_rgba, DmgPalette, Frame};
+pub use palettes::{ColorPalettes, BCPD, BCPS, OCPD, OCPS};
+pub use registers::LcdRegisters;
+pub use viewer::{background_map, oam_entries, tile_atlas, DebugImage, OamEntry, TileMap};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const LCDC: u16 = 0xFF40;
+pub const STAT: u16 = 0xFF41;
+pub const SCY: u16 = 0xFF42;
+pub const SCX: u16 = 0xFF43;
+pub const LY: u16 = 0xFF44;
+pub const LYC: u16 = 0xFF45;
+pub const BGP: u16 = 0xFF47;
+/// CGB object priority mode, bit 0 set orders overlapping sprites by X like the DMG
+pub const OPRI: u16 = 0xFF6C;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const DRAWING_DOTS: u32 = 172; // the shortest drawing mode, without penalties
+const WINDOW_PENALTY_DOTS: u32 = 6;
+const LINES_PER_FRAME: u8 = 154;
+const VBLANK_LINE: u8 = SCREEN_HEIGHT as u8;
+
+/// LCD control register bits
+pub mod lcdc {
+    pub const BG_ENABLE: u8 = 1 << 0;
+    pub const OBJ_ENABLE: u8 = 1 << 1;
+    pub const OBJ_SIZE: u8 = 1 << 2;
+    pub const BG_TILE_MAP: u8 = 1 << 3;
+    pub const TILE_DATA: u8 = 1 << 4;
+    pub const WINDOW_ENABLE: u8 = 1 << 5;
+    pub const WINDOW_TILE_MAP: u8 = 1 << 6;
+    pub const LCD_ENABLE: u8 = 1 << 7;
+}
+
+/// LCD status register bits
+pub mod stat {
+    pub const MODE: u8 = 0b11;
+    pub const COINCIDENCE: u8 = 1 << 2;
+    pub const HBLANK_INTERRUPT: u8 = 1 << 3;
+    pub const VBLANK_INTERRUPT: u8 = 1 << 4;
+    pub const OAM_INTERRUPT: u8 = 1 << 5;
+    pub const LYC_INTERRUPT: u8 = 1 << 6;
+    pub const INTERRUPT_SELECT: u8 = 0b0111_1000;
+    pub const UNUSED: u8 = 1 << 7;
+}
+
+/// The PPU mode, as reported in the lower two bits of STAT
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// When HBlank starts on a visible line and how many dots it lasts, what drawing leaves of the
+/// line's 456 dots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HBlankTiming {
+    pub line: u8,
+    pub start: u32,
+    pub dots: u32,
+}
+
+/// How the PPU draws the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Renderer {
+    /// Draw every line at once at the end of its drawing mode
+    #[default]
+    Scanline,
+    /// Draw dot by dot through the pixel FIFO, slower but shows mid-line register writes
+    PixelFifo,
+}
+
+pub struct Ppu {
+    framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    color_framebuffer: [u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+    dot: u32, // dot within the current line
+    line: u8,
+    mode: Mode,
+    stat_line: bool, // the STAT interrupt fires on the rising edge of this line
+    window_line: u8,
+    window_triggered: bool, // set once LY == WY during the frame
+    frame_ready: bool,
+    drawing_dots: u32, // length of the current line's drawing mode
+    renderer: Renderer,
+    fifo: Option<PixelFifo>, // the line being drawn by the pixel FIFO renderer
+    lcd_on: bool,            // LCDC bit 7 as last seen, to catch the LCD being switched
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu {
+            framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            color_framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            dot: 0,
+            line: 0,
+            mode: Mode::OamScan,
+            stat_line: false,
+            window_line: 0,
+            window_triggered: false,
+            frame_ready: false,
+            drawing_dots: DRAWING_DOTS,
+            renderer: Renderer::default(),
+            fifo: None,
+            lcd_on: true,
+        }
+    }
+
+    /// Advance the PPU by a number of dots (T-cycles)
+    pub fn tick(&mut self, memory: &mut Memory, dots: u32) {
+        if memory.take_stat_write() && self.lcd_on {
+            self.stat_write_bug(memory);
+        }
+        if memory.take_stat_sources_write() && self.lcd_on {
+            self.update_stat(memory);
+        }
+        if memory.take_hdma_start() && self.lcd_on {
+            if let Some(timing) = self.hblank_timing() {
+                memory.hblank(timing.start + timing.dots - self.dot);
+            }
+        }
+        let lcd_on = memory.read_byte(LCDC) & lcdc::LCD_ENABLE != 0;
+        if lcd_on != self.lcd_on {
+            self.lcd_on = lcd_on;
+            tracing::debug!(target: "ppu", lcd_on, "LCD switched");
+            if lcd_on {
+                self.turn_on(memory);
+            } else {
+                self.turn_off(memory);
+            }
+        }
+        if !lcd_on {
+            return;
+        }
+
+        for _ in 0..dots {
+            self.step(memory);
+        }
+    }
+
+    /// Blank the screen and reset LY, the dot counter and the window to the top of the frame
+    fn turn_off(&mut self, memory: &mut Memory) {
+        if self.mode != Mode::VBlank {
+            tracing::warn!(target: "ppu", "LCD turned off outside of VBlank on line {}", self.line);
+        }
+        self.framebuffer.fill(0);
+        self.color_framebuffer.fill(0x7FFF);
+        self.dot = 0;
+        self.line = 0;
+        self.window_line = 0;
+        self.window_triggered = false;
+        self.fifo = None;
+        self.stat_line = false;
+        self.mode = Mode::HBlank;
+        memory.set_oam_scan_row(None);
+        memory.set_io_register(LY, 0);
+        memory.set_io_register(STAT, memory.read_byte(STAT) & !stat::MODE);
+    }
+
+    /// Start a frame from line 0, which stays in HBlank until drawing starts
+    fn turn_on(&mut self, memory: &mut Memory) {
+        self.dot = 0;
+        self.line = 0;
+        memory.set_io_register(LY, 0);
+        self.set_mode(memory, Mode::HBlank);
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The HBlank of the current line, once it has started
+    pub fn hblank_timing(&self) -> Option<HBlankTiming> {
+        let start = OAM_SCAN_DOTS + self.drawing_dots;
+        let in_hblank = self.lcd_on
+            && self.mode == Mode::HBlank
+            && self.line < VBLANK_LINE
+            && self.dot >= start;
+        in_hblank.then_some(HBlankTiming {
+            line: self.line,
+            start,
+            dots: DOTS_PER_LINE - start,
+        })
+    }
+
+    /// Switch between the scanline and pixel FIFO renderer, takes effect on the next line
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    /// Advance a single dot, only doing work on mode and line transitions
+    fn step(&mut self, memory: &mut Memory) {
+        self.dot += 1;
+
+        if self.line < VBLANK_LINE {
+            if self.dot == OAM_SCAN_DOTS {
+                self.start_drawing(memory);
+            } else if self.mode == Mode::Drawing {
+                self.draw(memory);
+            } else if self.mode == Mode::OamScan {
+                memory.set_oam_scan_row(Some((self.dot / 4) as u8));
+            }
+        }
+
+        if self.dot < DOTS_PER_LINE {
+            return;
+        }
+
+        self.dot = 0;
+        self.line = (self.line + 1) % LINES_PER_FRAME;
+        memory.set_io_register(LY, self.line);
+
+        if self.line == VBLANK_LINE {
+            tracing::trace!(target: "ppu", "VBlank");
+            self.frame_ready = true;
+            self.window_line = 0;
+            self.window_triggered = false;
+            request_interrupt(memory, Interrupt::VBlank);
+            self.set_mode(memory, Mode::VBlank);
+        } else if self.line < VBLANK_LINE {
+            self.set_mode(memory, Mode::OamScan);
+            memory.set_oam_scan_row(Some(0));
+        } else {
+            self.update_stat(memory);
+        }
+    }
+
+    fn start_drawing(&mut self, memory: &mut Memory) {
+        memory.set_oam_scan_row(None);
+        match self.renderer {
+            Renderer::Scanline => {
+                self.fifo = None;
+                self.drawing_dots = self.drawing_dots(memory);
+            }
+            Renderer::PixelFifo => {
+                if memory.read_byte(window::WY) == self.line {
+                    self.window_triggered = true;
+                }
+                self.fifo = Some(PixelFifo::new(
+                    memory,
+                    self.line,
+                    self.window_line,
+                    self.window_triggered,
+                ));
+            }
+        }
+        self.set_mode(memory, Mode::Drawing);
+    }
+
+    /// Advance the drawing mode a dot, entering HBlank once the line is drawn
+    fn draw(&mut self, memory: &mut Memory) {
+        let Some(fifo) = self.fifo.as_mut() else {
+            if self.dot == OAM_SCAN_DOTS + self.drawing_dots {
+                self.render_line(memory);
+                self.end_drawing(memory);
+            }
+            return;
+        };
+
+        let cgb_mode = memory.cgb_mode();
+        let Some((x, pixel, sprite_pixel)) = fifo.tick(memory, cgb_mode) else {
+            return;
+        };
+        let window_drawn = fifo.window_drawn();
+        let control = memory.read_byte(LCDC);
+        self.draw_pixel(memory, control, cgb_mode, x, pixel, sprite_pixel);
+
+        if x == SCREEN_WIDTH - 1 {
+            if window_drawn {
+                self.window_line += 1;
+            }
+            self.fifo = None;
+            self.drawing_dots = self.dot - OAM_SCAN_DOTS;
+            self.end_drawing(memory);
+        }
+    }
+
+    fn end_drawing(&mut self, memory: &mut Memory) {
+        self.set_mode(memory, Mode::HBlank);
+        memory.hblank(DOTS_PER_LINE - self.dot);
+    }
+
+    /// The length of the drawing mode of the current line, in dots
+    ///
+    /// The fetcher discards SCX % 8 pixels at the start of the line, takes 6 dots to switch to the
+    /// window and stalls for every sprite fetch, see `sprites::fetch_penalty`
+    fn drawing_dots(&self, memory: &Memory) -> u32 {
+        let control = memory.read_byte(LCDC);
+        let scx = memory.read_byte(SCX);
+        let mut dots = DRAWING_DOTS + u32::from(scx % 8);
+
+        let window_visible = control & lcdc::WINDOW_ENABLE != 0
+            && (self.window_triggered || memory.read_byte(window::WY) == self.line)
+            && memory.read_byte(window::WX) <= window::MAX_WX;
+        if window_visible {
+            dots += WINDOW_PENALTY_DOTS;
+        }
+
+        if control & lcdc::OBJ_ENABLE != 0 {
+            let sprites =
+                sprites::select_sprites(memory, self.line, sprites::sprite_height(control));
+            dots += sprites::fetch_penalty(&sprites, scx);
+        }
+        dots
+    }
+
+    fn set_mode(&mut self, memory: &mut Memory, mode: Mode) {
+        self.mode = mode;
+        self.update_stat(memory);
+    }
+
+    /// A write to STAT on the DMG enables every STAT source for a cycle, so the interrupt fires
+    /// if the write lands in HBlank, VBlank or on LY=LYC. Some games depend on it, like Road Rash
+    /// and Legend of Zerd
+    fn stat_write_bug(&mut self, memory: &mut Memory) {
+        let coincidence = memory.read_byte(LYC) == self.line;
+        let stat_line = coincidence || matches!(self.mode, Mode::HBlank | Mode::VBlank);
+        if stat_line && !self.stat_line {
+            request_interrupt(memory, Interrupt::Stat);
+        }
+    }
+
+    /// Write the mode and coincidence flag to STAT and request a STAT interrupt on a rising edge
+    fn update_stat(&mut self, memory: &mut Memory) {
+        let status = memory.read_byte(STAT);
+        let coincidence = memory.read_byte(LYC) == self.line;
+
+        let stat_line = (coincidence && status & stat::LYC_INTERRUPT != 0)
+            || match self.mode {
+                Mode::HBlank => status & stat::HBLANK_INTERRUPT != 0,
+                Mode::VBlank => status & stat::VBLANK_INTERRUPT != 0,
+                Mode::OamScan => status & stat::OAM_INTERRUPT != 0,
+                Mode::Drawing => false,
+            };
+
+        let mut new_status = (status & stat::INTERRUPT_SELECT) | stat::UNUSED | self.mode as u8;
+        if coincidence {
+            new_status |= stat::COINCIDENCE;
+        }
+        memory.set_io_register(STAT, new_status);
+
+        if stat_line && !self.stat_line {
+            request_interrupt(memory, Interrupt::Stat);
+        }
+        self.stat_line = stat_line;
+    }
+
+    /// The rendered screen, one shade (0-3) per pixel, row by row
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.framebuffer
+    }
+
+    /// The rendered screen as RGB555 colors, row by row, only drawn in CGB mode
+    pub fn color_framebuffer(&self) -> &[u16; SCREEN_WIDTH * SCREEN_HEIGHT] {
+        &self.color_framebuffer
+    }
+
+    /// Returns true once per completed frame
+    pub fn take_frame_ready(&mut self) -> bool {
+        core::mem::take(&mut self.frame_ready)
+    }
+
+    fn render_line(&mut self, memory: &Memory) {
+        let control = memory.read_byte(LCDC);
+        let cgb_mode = memory.cgb_mode();
+        let mut pixels = background::render_line(memory, self.line, cgb_mode);
+
+        if memory.read_byte(window::WY) == self.line {
+            self.window_triggered = true;
+        }
+        if self.window_triggered
+            && window::render_line(memory, control, self.window_line, cgb_mode, &mut pixels)
+        {
+            self.window_line += 1;
+        }
+
+        let sprite_pixels = sprites::render_line(
+            memory,
+            control,
+            self.line,
+            cgb_mode,
+            memory.sprite_index_priority(),
+        );
+        for (x, (pixel, sprite_pixel)) in pixels.into_iter().zip(sprite_pixels).enumerate() {
+            self.draw_pixel(memory, control, cgb_mode, x, pixel, sprite_pixel);
+        }
+    }
+
+    /// Mix a background and sprite pixel through the palettes into column `x` of the current line
+    fn draw_pixel(
+        &mut self,
+        memory: &Memory,
+        control: u8,
+        cgb_mode: bool,
+        x: usize,
+        pixel: BackgroundPixel,
+        sprite_pixel: Option<SpritePixel>,
+    ) {
+        let index = usize::from(self.line) * SCREEN_WIDTH + x;
+        if cgb_mode {
+            let palettes = memory.color_palettes();
+            let color = match sprite_pixel {
+                Some(sprite) if cgb_sprite_wins(control, pixel, sprite) => {
+                    palettes.sprite_color(sprite.cgb_palette, sprite.color_id)
+                }
+                _ => palettes
+                    .background_color(pixel.attributes & map_attributes::PALETTE, pixel.color_id),
+            };
+            self.color_framebuffer[index] = color;
+            self.framebuffer[index] = rgb555_to_shade(color);
+            return;
+        }
+
+        self.framebuffer[index] = match sprite_pixel {
+            Some(sprite) if !(sprite.behind_background && pixel.color_id != 0) => {
+                apply_palette(memory.read_byte(sprite.palette), sprite.color_id)
+            }
+            _ => apply_palette(memory.read_byte(BGP), pixel.color_id),
+        };
+    }
+}
+
+/// Whether a sprite pixel is drawn over the background in CGB mode
+///
+/// With LCDC bit 0 clear sprites are always on top, otherwise either the sprite or the tile
+/// can ask for the background to be drawn over the sprite where its color id is not 0
+fn cgb_sprite_wins(control: u8, background: BackgroundPixel, sprite: SpritePixel) -> bool {
+    let background_priority =
+        sprite.behind_background || background.attributes & map_attributes::PRIORITY != 0;
+    control & lcdc::BG_ENABLE == 0 || background.color_id == 0 || !background_priority
+}
+
+/// Map a 2 bit color id through a DMG palette register to a shade
+pub fn apply_palette(palette: u8, color_id: u8) -> u8 {
+    (palette >> (color_id * 2)) & 0x3
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.framebuffer);
+        let colors: Vec<u8> = self
+            .color_framebuffer
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .collect();
+        state.write_bytes(&colors);
+        state.write_u32(self.dot);
+        state.write_u8(self.line);
+        state.write_u8(self.mode as u8);
+        state.write_bool(self.stat_line);
+        state.write_u8(self.window_line);
+        state.write_bool(self.window_triggered);
+        state.write_bool(self.frame_ready);
+        state.write_u32(self.drawing_dots);
+        state.write_bool(self.lcd_on);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.framebuffer)?;
+        let colors = state.read_bytes()?;
+        if colors.len() != self.color_framebuffer.len() * 2 {
+            return Err(SaveStateError::InvalidData("byte array length"));
+        }
+        for (color, bytes) in self.color_framebuffer.iter_mut().zip(colors.chunks(2)) {
+            *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        self.dot = state.read_u32()?;
+        self.line = state.read_u8()?;
+        self.mode = match state.read_u8()? {
+            0 => Mode::HBlank,
+            1 => Mode::VBlank,
+            2 => Mode::OamScan,
+            3 => Mode::Drawing,
+            _ => return Err(SaveStateError::InvalidData("PPU mode")),
+        };
+        self.stat_line = state.read_bool()?;
+        self.window_line = state.read_u8()?;
+        self.window_triggered = state.read_bool()?;
+        self.frame_ready = state.read_bool()?;
+        self.drawing_dots = state.read_u32()?;
+        self.lcd_on = state.read_bool()?;
+        // the pixel FIFO is not saved, a line loaded while drawing is finished by the scanline renderer
+        self.fifo = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::interrupts::IF;
+
+    #[test]
+    fn test_apply_palette() {
+        assert_eq!(apply_palette(0b11_10_01_00, 0), 0);
+        assert_eq!(apply_palette(0b11_10_01_00, 3), 3);
+        assert_eq!(apply_palette(0b00_01_10_11, 0), 3);
+        assert_eq!(apply_palette(0b00_01_10_11, 2), 1);
+    }
+
+    #[test]
+    fn test_ly_advances() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE * 3);
+        assert_eq!(memory.read_byte(LY), 3);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE * 151);
+        assert_eq!(memory.read_byte(LY), 0);
+        assert!(ppu.take_frame_ready());
+        assert!(!ppu.take_frame_ready());
+    }
+
+    #[test]
+    fn test_lcd_disabled() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+
+        ppu.tick(&mut memory, DOTS_PER_LINE * 3);
+        assert_eq!(memory.read_byte(LY), 0);
+    }
+
+    #[test]
+    fn test_lcd_off_and_on() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::BG_ENABLE);
+        memory.write_byte(BGP, 0b11_11_11_11);
+        ppu.tick(&mut memory, DOTS_PER_LINE * 5 + 100);
+        assert_eq!(memory.read_byte(LY), 5);
+        assert_eq!(ppu.framebuffer()[0], 3);
+
+        // turning the LCD off blanks the screen and resets LY and the mode
+        memory.write_byte(LCDC, lcdc::BG_ENABLE);
+        ppu.tick(&mut memory, DOTS_PER_LINE * 2);
+        assert_eq!(memory.read_byte(LY), 0);
+        assert_eq!(memory.read_byte(STAT) & stat::MODE, Mode::HBlank as u8);
+        assert_eq!(ppu.framebuffer()[0], 0);
+
+        // line 0 restarts in HBlank, skipping the OAM scan
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::BG_ENABLE);
+        ppu.tick(&mut memory, OAM_SCAN_DOTS - 1);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        assert_eq!(memory.read_byte(LY), 0);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        ppu.tick(&mut memory, DOTS_PER_LINE - OAM_SCAN_DOTS);
+        assert_eq!(memory.read_byte(LY), 1);
+    }
+
+    #[test]
+    fn test_mode_timing() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+
+        ppu.tick(&mut memory, OAM_SCAN_DOTS - 1);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        assert_eq!(memory.read_byte(STAT) & stat::MODE, Mode::Drawing as u8);
+        ppu.tick(&mut memory, DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        ppu.tick(&mut memory, DOTS_PER_LINE - OAM_SCAN_DOTS - DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+        assert_eq!(memory.read_byte(LY), 1);
+    }
+
+    /// Ticks from the start of a line until HBlank
+    fn drawing_end(memory: &mut Memory) -> u32 {
+        let mut ppu = Ppu::new();
+        memory.set_io_register(LY, 0);
+        let mut dots = 0;
+        while ppu.mode() != Mode::HBlank {
+            ppu.tick(memory, 1);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn test_mode_3_penalties() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        assert_eq!(drawing_end(&mut memory), OAM_SCAN_DOTS + DRAWING_DOTS);
+
+        memory.write_byte(SCX, 3);
+        assert_eq!(drawing_end(&mut memory), OAM_SCAN_DOTS + DRAWING_DOTS + 3);
+        memory.write_byte(SCX, 0);
+
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::WINDOW_ENABLE);
+        memory.write_byte(window::WX, 7);
+        assert_eq!(drawing_end(&mut memory), OAM_SCAN_DOTS + DRAWING_DOTS + 6);
+
+        // a sprite on line 0 at the left edge of a tile
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::OBJ_ENABLE);
+        memory.write_byte(sprites::OAM_START, 16);
+        memory.write_byte(sprites::OAM_START + 1, 8);
+        assert_eq!(drawing_end(&mut memory), OAM_SCAN_DOTS + DRAWING_DOTS + 11);
+    }
+
+    #[test]
+    fn test_vblank_interrupt() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE * u32::from(VBLANK_LINE) - 1);
+        assert_eq!(memory.read_byte(IF) & Interrupt::VBlank.bit(), 0);
+
+        ppu.tick(&mut memory, 1);
+        assert_eq!(ppu.mode(), Mode::VBlank);
+        assert_ne!(memory.read_byte(IF) & Interrupt::VBlank.bit(), 0);
+    }
+
+    #[test]
+    fn test_stat_lyc_interrupt() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        memory.write_byte(STAT, stat::LYC_INTERRUPT);
+        memory.write_byte(LYC, 2);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+        assert_eq!(memory.read_byte(STAT) & stat::COINCIDENCE, 0);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+        assert_ne!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+        assert_ne!(memory.read_byte(STAT) & stat::COINCIDENCE, 0);
+    }
+
+    #[test]
+    fn test_lyc_written_mid_frame() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        memory.write_byte(LYC, 0x90);
+        memory.write_byte(STAT, stat::LYC_INTERRUPT | stat::HBLANK_INTERRUPT);
+        ppu.tick(&mut memory, DOTS_PER_LINE * 5 + OAM_SCAN_DOTS + 10);
+        memory.write_byte(IF, 0);
+
+        // moved onto the line being drawn, the interrupt fires at once
+        memory.write_byte(LYC, 5);
+        ppu.tick(&mut memory, 1);
+        assert_ne!(memory.read_byte(STAT) & stat::COINCIDENCE, 0);
+        assert_ne!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+
+        // moved away, the flag clears
+        memory.write_byte(IF, 0);
+        memory.write_byte(LYC, 6);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(memory.read_byte(STAT) & stat::COINCIDENCE, 0);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+
+        // in HBlank the line is already high, so moving back does not fire again
+        ppu.tick(&mut memory, DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        memory.write_byte(IF, 0);
+        memory.write_byte(LYC, 5);
+        ppu.tick(&mut memory, 1);
+        assert_ne!(memory.read_byte(STAT) & stat::COINCIDENCE, 0);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+    }
+
+    #[test]
+    fn test_stat_hblank_interrupt() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        // away from LY so the STAT write bug does not fire
+        memory.write_byte(LYC, 0x90);
+        memory.write_byte(STAT, stat::HBLANK_INTERRUPT);
+
+        ppu.tick(&mut memory, OAM_SCAN_DOTS + DRAWING_DOTS - 1);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+        ppu.tick(&mut memory, 1);
+        assert_ne!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+    }
+
+    #[test]
+    fn test_stat_write_bug() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        memory.write_byte(LYC, 0x90);
+        ppu.tick(&mut memory, OAM_SCAN_DOTS + 10);
+
+        // while drawing the write does nothing
+        memory.write_byte(STAT, 0x00);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+
+        // in HBlank it fires the interrupt, with every source off
+        ppu.tick(&mut memory, DRAWING_DOTS);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+        memory.write_byte(STAT, 0x00);
+        ppu.tick(&mut memory, 1);
+        assert_ne!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+
+        memory.write_byte(IF, 0);
+        memory.set_stat_write_bug(false);
+        memory.write_byte(STAT, 0x00);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(memory.read_byte(IF) & Interrupt::Stat.bit(), 0);
+    }
+
+    #[test]
+    fn test_render_background() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE);
+        memory.write_byte(BGP, 0b11_10_01_00);
+
+        // tile 1, first row: color ids 3,3,3,3,0,0,0,0
+        memory.write_byte(0x8010, 0xF0);
+        memory.write_byte(0x8011, 0xF0);
+        // top left of the tile map uses tile 1
+        memory.write_byte(0x9800, 0x01);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+
+        assert_eq!(ppu.framebuffer()[..8], [3, 3, 3, 3, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_window_line_counter() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        let control = lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE | lcdc::WINDOW_ENABLE;
+        memory.write_byte(LCDC, control);
+        memory.write_byte(BGP, 0b11_10_01_00);
+        memory.write_byte(window::WY, 1);
+        memory.write_byte(window::WX, 7);
+        // tile 1: first row color 1, second row color 2
+        memory.write_byte(0x8010, 0xFF);
+        memory.write_byte(0x8013, 0xFF);
+        memory.write_byte(0x9800, 0x01);
+
+        // line 0 is above the window, line 1 draws window row 0
+        ppu.tick(&mut memory, DOTS_PER_LINE * 2);
+        assert_eq!(ppu.framebuffer()[SCREEN_WIDTH], 1);
+
+        // hiding the window on line 2 pauses the window line counter
+        memory.write_byte(LCDC, control & !lcdc::WINDOW_ENABLE);
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+        memory.write_byte(LCDC, control);
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+        assert_eq!(ppu.framebuffer()[SCREEN_WIDTH * 3], 2);
+    }
+
+    #[test]
+    fn test_render_sprite_priority() {
+        let mut memory = Memory::new();
+        let mut ppu = Ppu::new();
+        memory.write_byte(
+            LCDC,
+            lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE | lcdc::OBJ_ENABLE,
+        );
+        memory.write_byte(BGP, 0b11_10_01_00);
+        memory.write_byte(sprites::OBP0, 0b10_10_10_10);
+
+        // background tile 1: color ids 3,3,3,3,0,0,0,0
+        memory.write_byte(0x8010, 0xF0);
+        memory.write_byte(0x8011, 0xF0);
+        memory.write_byte(0x9800, 0x01);
+        // sprite tile 2: solid color id 1
+        memory.write_byte(0x8020, 0xFF);
+        // sprite 0 over the first tile, behind the background
+        memory.write_byte(0xFE00, 16);
+        memory.write_byte(0xFE01, 8);
+        memory.write_byte(0xFE02, 2);
+        memory.write_byte(0xFE03, sprites::attributes::BEHIND_BACKGROUND);
+        // sprite 1 in front of the background
+        memory.write_byte(0xFE04, 16);
+        memory.write_byte(0xFE05, 16);
+        memory.write_byte(0xFE06, 2);
+        memory.write_byte(0xFE07, 0);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+
+        // background wins where its color id is not 0
+        assert_eq!(ppu.framebuffer()[..8], [3, 3, 3, 3, 2, 2, 2, 2]);
+        assert_eq!(ppu.framebuffer()[8..16], [2; 8]);
+    }
+
+    /// Background, window and overlapping sprites on the first 16 lines
+    fn scene(memory: &mut Memory) {
+        memory.write_byte(
+            LCDC,
+            lcdc::LCD_ENABLE
+                | lcdc::TILE_DATA
+                | lcdc::BG_ENABLE
+                | lcdc::OBJ_ENABLE
+                | lcdc::WINDOW_ENABLE
+                | lcdc::WINDOW_TILE_MAP,
+        );
+        memory.write_byte(BGP, 0b11_10_01_00);
+        memory.write_byte(sprites::OBP0, 0b00_01_10_11);
+        memory.write_byte(SCX, 3);
+        memory.write_byte(window::WY, 8);
+        memory.write_byte(window::WX, 87);
+        for address in 0x8010..0x8040 {
+            memory.write_byte(address, (address as u8).wrapping_mul(37));
+        }
+        for column in 0..32 {
+            memory.write_byte(0x9800 + column, column as u8 % 3 + 1);
+            memory.write_byte(0x9C00 + column, 2);
+        }
+        for (index, x) in [5u16, 12, 14, 60, 100].into_iter().enumerate() {
+            let address = sprites::OAM_START + index as u16 * 4;
+            memory.write_byte(address, 16 + index as u8);
+            memory.write_byte(address + 1, x as u8);
+            memory.write_byte(address + 2, 3);
+            memory.write_byte(address + 3, (index as u8 % 2) << 7);
+        }
+    }
+
+    #[test]
+    fn test_pixel_fifo_matches_scanline() {
+        let mut memory = Memory::new();
+        scene(&mut memory);
+        let mut scanline = Ppu::new();
+        scanline.tick(&mut memory, DOTS_PER_LINE * 16);
+
+        let mut memory = Memory::new();
+        scene(&mut memory);
+        let mut fifo = Ppu::new();
+        fifo.set_renderer(Renderer::PixelFifo);
+        fifo.tick(&mut memory, DOTS_PER_LINE * 16);
+
+        assert_eq!(
+            fifo.framebuffer()[..SCREEN_WIDTH * 16],
+            scanline.framebuffer()[..SCREEN_WIDTH * 16]
+        );
+    }
+
+    #[test]
+    fn test_pixel_fifo_mode_3() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        memory.write_byte(SCX, 2);
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(Renderer::PixelFifo);
+
+        ppu.tick(&mut memory, OAM_SCAN_DOTS + DRAWING_DOTS + 1);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+    }
+
+    #[test]
+    fn test_pixel_fifo_mid_line_scroll() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE);
+        memory.write_byte(BGP, 0b11_10_01_00);
+        // tile 1 is solid color 3, in the map from column 16 on
+        memory.write_byte(0x8010, 0xFF);
+        memory.write_byte(0x8011, 0xFF);
+        for column in 16..32 {
+            memory.write_byte(0x9800 + column, 0x01);
+        }
+        let mut ppu = Ppu::new();
+        ppu.set_renderer(Renderer::PixelFifo);
+
+        // scroll by 8 tiles halfway through the line, only the tiles fetched after it move
+        ppu.tick(&mut memory, OAM_SCAN_DOTS + 12 + 80);
+        memory.write_byte(SCX, 64);
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+
+        let line = &ppu.framebuffer()[..SCREEN_WIDTH];
+        assert_eq!(line[..80], [0; 80]);
+        assert_eq!(line[96..], [3; 64]);
+    }
+
+    fn cgb_memory() -> Memory {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0x80;
+        let mut memory = Memory::new();
+        memory.load_cartridge(crate::gameboy::Cartridge::new(rom).unwrap());
+        memory
+    }
+
+    #[test]
+    fn test_hblank_hdma_timing() {
+        use crate::gameboy::hdma::{HDMA1, HDMA2, HDMA3, HDMA4, HDMA5};
+
+        let mut memory = cgb_memory();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+        for offset in 0..0x40 {
+            memory.write_byte(0xC000 + offset, 0x11 + offset as u8);
+        }
+        memory.write_byte(HDMA1, 0xC0);
+        memory.write_byte(HDMA2, 0x00);
+        memory.write_byte(HDMA3, 0x10);
+        memory.write_byte(HDMA4, 0x00);
+        memory.write_byte(HDMA5, 0x83);
+
+        // line 0 draws in the shortest time, the first block is copied as HBlank starts
+        ppu.tick(&mut memory, OAM_SCAN_DOTS + DRAWING_DOTS - 1);
+        assert_eq!(ppu.hblank_timing(), None);
+        assert_eq!(memory.read_byte(0x9000), 0x00);
+        ppu.tick(&mut memory, 1);
+        let timing = HBlankTiming {
+            line: 0,
+            start: 252,
+            dots: 204,
+        };
+        assert_eq!(ppu.hblank_timing(), Some(timing));
+        assert_eq!(memory.read_byte(0x9000), 0x11);
+        assert_eq!(memory.read_byte(HDMA5), 0x02);
+
+        // SCX % 8 = 5 draws line 1 for 5 dots longer, the block waits for its HBlank
+        memory.write_byte(SCX, 5);
+        ppu.tick(&mut memory, DOTS_PER_LINE + 4);
+        assert_eq!(ppu.hblank_timing(), None);
+        assert_eq!(memory.read_byte(HDMA5), 0x02);
+        ppu.tick(&mut memory, 1);
+        let timing = HBlankTiming {
+            line: 1,
+            start: 257,
+            dots: 199,
+        };
+        assert_eq!(ppu.hblank_timing(), Some(timing));
+        assert_eq!(memory.read_byte(0x9010), 0x21);
+        assert_eq!(memory.read_byte(HDMA5), 0x01);
+
+        // restarted with 30 dots of HBlank left a block doesn't fit, it waits for the next line
+        ppu.tick(&mut memory, 168);
+        memory.write_byte(HDMA5, 0x00);
+        memory.write_byte(HDMA5, 0x81);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(memory.read_byte(HDMA5), 0x01);
+        ppu.tick(&mut memory, 30 + 257);
+        assert_eq!(ppu.hblank_timing().map(|timing| timing.line), Some(2));
+        assert_eq!(memory.read_byte(0x9020), 0x31);
+        assert_eq!(memory.read_byte(HDMA5), 0x00);
+
+        // restarted early in HBlank, the block is copied right away
+        ppu.tick(&mut memory, 10);
+        memory.write_byte(HDMA5, 0x00);
+        memory.write_byte(HDMA5, 0x80);
+        ppu.tick(&mut memory, 1);
+        assert_eq!(memory.read_byte(0x9030), 0x41);
+        assert_eq!(memory.read_byte(HDMA5), 0xFF);
+    }
+
+    #[test]
+    fn test_render_cgb_colors() {
+        let mut memory = cgb_memory();
+        let mut ppu = Ppu::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE);
+        // background palette 2, color 1 is pure red
+        memory.write_byte(BCPS, 0x80 | (2 * 8 + 2));
+        memory.write_byte(palettes::BCPD, 0x1F);
+        memory.write_byte(palettes::BCPD, 0x00);
+        // tile 1 in bank 1 is solid color 1, the first map entry uses it with palette 2
+        memory.write_byte(crate::gameboy::memory::VBK, 1);
+        for row in 0..8 {
+            memory.write_byte(0x8010 + row * 2, 0xFF);
+        }
+        memory.write_byte(0x9800, map_attributes::BANK | 2);
+        memory.write_byte(crate::gameboy::memory::VBK, 0);
+        memory.write_byte(0x9800, 0x01);
+
+        ppu.tick(&mut memory, DOTS_PER_LINE);
+
+        assert_eq!(ppu.color_framebuffer()[..8], [0x001F; 8]);
+        assert_eq!(ppu.color_framebuffer()[8], 0);
+        assert_eq!(ppu.framebuffer()[0], 2);
+    }
+
+    #[test]
+    fn test_cgb_sprite_priority() {
+        let sprite = SpritePixel {
+            color_id: 1,
+            palette: sprites::OBP0,
+            cgb_palette: 0,
+            behind_background: false,
+        };
+        let background = BackgroundPixel {
+            color_id: 1,
+            attributes: map_attributes::PRIORITY,
+        };
+
+        assert!(!cgb_sprite_wins(lcdc::BG_ENABLE, background, sprite));
+        assert!(cgb_sprite_wins(0, background, sprite));
+        assert!(cgb_sprite_wins(
+            lcdc::BG_ENABLE,
+            BackgroundPixel::default(),
+            sprite
+        ));
+    }
+}
@@ -0,0 +1,180 @@
+//! CGB color palette RAM, BCPS/BCPD for the background and OCPS/OCPD for sprites.
+//!
+//! Each of the 8 background and 8 sprite palettes holds 4 colors of 2 bytes, little endian
+//! RGB555. The specification register (BCPS/OCPS) selects a byte of the 64 byte palette RAM
+//! in bits 0-5, with bit 7 set the index increments after every write to the data register.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+pub const BCPS: u16 = 0xFF68;
+pub const BCPD: u16 = 0xFF69;
+pub const OCPS: u16 = 0xFF6A;
+pub const OCPD: u16 = 0xFF6B;
+
+const PALETTE_RAM_SIZE: usize = 64;
+const INDEX: u8 = 0b0011_1111;
+const AUTO_INCREMENT: u8 = 1 << 7;
+
+/// One palette RAM with its specification register
+struct PaletteRam {
+    bytes: [u8; PALETTE_RAM_SIZE],
+    specification: u8,
+}
+
+impl PaletteRam {
+    fn new() -> PaletteRam {
+        PaletteRam {
+            bytes: [0; PALETTE_RAM_SIZE],
+            specification: 0,
+        }
+    }
+
+    fn index(&self) -> usize {
+        usize::from(self.specification & INDEX)
+    }
+
+    fn write_data(&mut self, value: u8) {
+        self.bytes[self.index()] = value;
+        if self.specification & AUTO_INCREMENT != 0 {
+            let index = (self.specification + 1) & INDEX;
+            self.specification = AUTO_INCREMENT | index;
+        }
+    }
+
+    fn color(&self, palette: u8, color_id: u8) -> u16 {
+        let index = usize::from(palette & 0x7) * 8 + usize::from(color_id) * 2;
+        u16::from_le_bytes([self.bytes[index], self.bytes[index + 1]]) & 0x7FFF
+    }
+}
+
+pub struct ColorPalettes {
+    background: PaletteRam,
+    sprites: PaletteRam,
+}
+
+impl ColorPalettes {
+    pub fn new() -> ColorPalettes {
+        ColorPalettes {
+            background: PaletteRam::new(),
+            sprites: PaletteRam::new(),
+        }
+    }
+
+    /// RGB555 color `color_id` of background palette `palette` (0-7)
+    pub fn background_color(&self, palette: u8, color_id: u8) -> u16 {
+        self.background.color(palette, color_id)
+    }
+
+    /// RGB555 color `color_id` of sprite palette `palette` (0-7)
+    pub fn sprite_color(&self, palette: u8, color_id: u8) -> u16 {
+        self.sprites.color(palette, color_id)
+    }
+}
+
+impl Default for ColorPalettes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for ColorPalettes {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[BCPS..=OCPD]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            BCPS => self.background.specification | 0x40,
+            BCPD => self.background.bytes[self.background.index()],
+            OCPS => self.sprites.specification | 0x40,
+            OCPD => self.sprites.bytes[self.sprites.index()],
+            _ => unreachable!("not a palette register"),
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            BCPS => self.background.specification = value & (AUTO_INCREMENT | INDEX),
+            BCPD => self.background.write_data(value),
+            OCPS => self.sprites.specification = value & (AUTO_INCREMENT | INDEX),
+            OCPD => self.sprites.write_data(value),
+            _ => unreachable!("not a palette register"),
+        }
+    }
+}
+
+impl SaveState for ColorPalettes {
+    fn save_state(&self, state: &mut StateWriter) {
+        for ram in [&self.background, &self.sprites] {
+            state.write_bytes(&ram.bytes);
+            state.write_u8(ram.specification);
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        for ram in [&mut self.background, &mut self.sprites] {
+            state.read_into(&mut ram.bytes)?;
+            ram.specification = state.read_u8()?;
+        }
+        Ok(())
+    }
+}
+
+/// A DMG shade (0-3) approximating the brightness of an RGB555 color
+pub fn rgb555_to_shade(color: u16) -> u8 {
+    let sum = (color & 0x1F) + ((color >> 5) & 0x1F) + ((color >> 10) & 0x1F);
+    3 - (sum * 4 / (31 * 3 + 1)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auto_increment() {
+        let mut palettes = ColorPalettes::new();
+        palettes.write(BCPS, AUTO_INCREMENT | 0x02);
+        palettes.write(BCPD, 0x1F);
+        palettes.write(BCPD, 0x7C);
+
+        assert_eq!(palettes.read(BCPS), 0xC4);
+        assert_eq!(palettes.background_color(0, 1), 0x7C1F);
+        assert_eq!(palettes.sprite_color(0, 1), 0);
+    }
+
+    #[test]
+    fn test_no_increment() {
+        let mut palettes = ColorPalettes::new();
+        palettes.write(OCPS, 0x3F);
+        palettes.write(OCPD, 0x12);
+        palettes.write(OCPD, 0xFF);
+
+        assert_eq!(palettes.read(OCPS), 0x7F);
+        assert_eq!(palettes.read(OCPD), 0xFF);
+        // the high bit of a color is unused
+        assert_eq!(palettes.sprite_color(7, 3), 0x7F00);
+    }
+
+    #[test]
+    fn test_increment_wraps() {
+        let mut palettes = ColorPalettes::new();
+        palettes.write(BCPS, AUTO_INCREMENT | 0x3F);
+        palettes.write(BCPD, 0x12);
+
+        assert_eq!(palettes.read(BCPS) & INDEX, 0);
+    }
+
+    #[test]
+    fn test_rgb555_to_shade() {
+        assert_eq!(rgb555_to_shade(0x7FFF), 0);
+        assert_eq!(rgb555_to_shade(0x0000), 3);
+    }
+}
@@ -0,0 +1,78 @@
+//! The LCD registers LCDC to LYC and BGP to WX, the PPU reads them through memory like the CPU.
+//!
+//! Bits only the hardware sets (the STAT mode and coincidence flag, LY) ignore CPU writes, the
+//! PPU sets them through `Memory::set_io_register`. DMA (0xFF46) in between belongs to the OAM
+//! DMA transfer.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+use super::{window::WX, BGP, LCDC, LY, LYC, STAT};
+
+/// Bits the CPU can't write, they belong to the hardware
+const READ_ONLY_BITS: [(u16, u8); 2] = [(STAT, 0x07), (LY, 0xFF)];
+
+fn read_only_bits(address: u16) -> u8 {
+    READ_ONLY_BITS
+        .iter()
+        .find(|(register, _)| *register == address)
+        .map_or(0, |(_, bits)| *bits)
+}
+
+pub struct LcdRegisters {
+    registers: [u8; 12], // indexed from LCDC, the DMA slot is unused
+}
+
+impl LcdRegisters {
+    pub fn new() -> LcdRegisters {
+        LcdRegisters { registers: [0; 12] }
+    }
+
+    fn register(&mut self, address: u16) -> &mut u8 {
+        &mut self.registers[usize::from(address - LCDC)]
+    }
+
+    /// Set a register including the bits the CPU can't write
+    pub fn set(&mut self, address: u16, value: u8) {
+        *self.register(address) = value;
+    }
+}
+
+impl Default for LcdRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for LcdRegisters {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[LCDC..=LYC, BGP..=WX]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        self.registers[usize::from(address - LCDC)]
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        let read_only = read_only_bits(address);
+        let register = self.register(address);
+        *register = (*register & read_only) | (value & !read_only);
+    }
+}
+
+impl SaveState for LcdRegisters {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.registers);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        state.read_into(&mut self.registers)
+    }
+}
@@ -0,0 +1,393 @@
+//! Sprite (OBJ) layer rendering.
+//!
+//! Up to 10 sprites are selected per line in OAM order. On the DMG the sprite with the
+//! lowest X coordinate wins when sprites overlap, ties are won by the lower OAM index.
+//! In CGB mode only the OAM index counts.
+
+use alloc::vec::Vec;
+
+use super::{lcdc, SCREEN_WIDTH};
+use crate::gameboy::Memory;
+
+pub const OAM_START: u16 = 0xFE00;
+pub const OAM_ENTRIES: u8 = 40;
+pub const SPRITES_PER_LINE: usize = 10;
+
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+
+/// OAM attribute bits
+pub mod attributes {
+    pub const CGB_PALETTE: u8 = 0b111;
+    pub const CGB_BANK: u8 = 1 << 3;
+    pub const PALETTE: u8 = 1 << 4;
+    pub const X_FLIP: u8 = 1 << 5;
+    pub const Y_FLIP: u8 = 1 << 6;
+    pub const BEHIND_BACKGROUND: u8 = 1 << 7;
+}
+
+/// A single OAM entry
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    pub index: u8,
+}
+
+impl Sprite {
+    /// Read OAM entry `index` (0-39)
+    pub fn read(memory: &Memory, index: u8) -> Sprite {
+        let address = OAM_START + u16::from(index) * 4;
+        Sprite {
+            y: memory.read_oam(address),
+            x: memory.read_oam(address + 1),
+            tile: memory.read_oam(address + 2),
+            attributes: memory.read_oam(address + 3),
+            index,
+        }
+    }
+
+    /// Whether the sprite covers the line, sprite coordinates are offset by 16 vertically
+    fn on_line(&self, line: u8, height: u8) -> bool {
+        let top = i16::from(self.y) - 16;
+        let line = i16::from(line);
+        line >= top && line < top + i16::from(height)
+    }
+}
+
+/// A sprite pixel that should be mixed with the background
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpritePixel {
+    pub color_id: u8,
+    pub palette: u16,    // OBP0 or OBP1
+    pub cgb_palette: u8, // the color palette in CGB mode
+    pub behind_background: bool,
+}
+
+/// Sprite height in pixels, 8 or 16 depending on LCDC
+pub fn sprite_height(control: u8) -> u8 {
+    if control & lcdc::OBJ_SIZE != 0 {
+        16
+    } else {
+        8
+    }
+}
+
+/// Select the sprites on a line, at most 10 in OAM order
+pub fn select_sprites(memory: &Memory, line: u8, height: u8) -> Vec<Sprite> {
+    (0..OAM_ENTRIES)
+        .map(|index| Sprite::read(memory, index))
+        .filter(|sprite| sprite.on_line(line, height))
+        .take(SPRITES_PER_LINE)
+        .collect()
+}
+
+/// The dots the drawing mode is lengthened by to fetch `sprites`, with the background scrolled
+/// by `scx`
+///
+/// Each fetch costs 6 dots. The first sprite starting in a background tile also waits for that
+/// tile's fetch to finish, 0 to 5 dots depending on where in the tile it starts, and a sprite at
+/// X 0 always waits the longest. Sprites off the right edge are never fetched.
+pub fn fetch_penalty(sprites: &[Sprite], scx: u8) -> u32 {
+    let mut tiles_waited = Vec::new();
+    let mut dots = 0;
+    for sprite in sprites.iter().filter(|sprite| sprite.x < 168) {
+        dots += 6;
+        if sprite.x == 0 {
+            dots += 5;
+            continue;
+        }
+        // the background position under the sprite's leftmost pixel, sprite X is offset by 8
+        let position = u16::from(sprite.x) + u16::from(scx);
+        let tile = position / 8;
+        if !tiles_waited.contains(&tile) {
+            tiles_waited.push(tile);
+            dots += 5u32.saturating_sub(u32::from(position % 8));
+        }
+    }
+    dots
+}
+
+/// The 8 pixels of `sprite` on `line` from left to right, `None` where it is transparent
+pub fn sprite_row(
+    memory: &Memory,
+    sprite: &Sprite,
+    line: u8,
+    height: u8,
+    cgb_mode: bool,
+) -> [Option<SpritePixel>; 8] {
+    let mut row = line + 16 - sprite.y;
+    if sprite.attributes & attributes::Y_FLIP != 0 {
+        row = height - 1 - row;
+    }
+    let tile = if height == 16 {
+        (sprite.tile & 0xFE) + row / 8
+    } else {
+        sprite.tile
+    };
+    let bank = usize::from(cgb_mode && sprite.attributes & attributes::CGB_BANK != 0);
+    let address = 0x8000 + u16::from(tile) * 16 + u16::from(row % 8) * 2;
+    let lo = memory.read_vram_bank(bank, address);
+    let hi = memory.read_vram_bank(bank, address + 1);
+
+    core::array::from_fn(|column| {
+        let bit = if sprite.attributes & attributes::X_FLIP != 0 {
+            column as u8
+        } else {
+            7 - column as u8
+        };
+        let color_id = (((hi >> bit) & 1) << 1) | ((lo >> bit) & 1);
+        (color_id != 0).then_some(SpritePixel {
+            color_id,
+            palette: if sprite.attributes & attributes::PALETTE != 0 {
+                OBP1
+            } else {
+                OBP0
+            },
+            cgb_palette: sprite.attributes & attributes::CGB_PALETTE,
+            behind_background: sprite.attributes & attributes::BEHIND_BACKGROUND != 0,
+        })
+    })
+}
+
+/// Render the sprite pixels of a scanline, the winning sprite pixel per screen column
+///
+/// Overlapping sprites are ordered by OAM index with `index_priority`, otherwise by X and then
+/// OAM index
+pub fn render_line(
+    memory: &Memory,
+    control: u8,
+    line: u8,
+    cgb_mode: bool,
+    index_priority: bool,
+) -> [Option<SpritePixel>; SCREEN_WIDTH] {
+    let mut pixels = [None; SCREEN_WIDTH];
+
+    if control & lcdc::OBJ_ENABLE == 0 {
+        return pixels;
+    }
+
+    let height = sprite_height(control);
+    let mut sprites = select_sprites(memory, line, height);
+    if !index_priority {
+        // stable sort keeps OAM order for equal X
+        sprites.sort_by_key(|sprite| sprite.x);
+    }
+
+    // draw lowest priority first so higher priority sprites overwrite them
+    for sprite in sprites.iter().rev() {
+        for (column, pixel) in sprite_row(memory, sprite, line, height, cgb_mode)
+            .into_iter()
+            .enumerate()
+        {
+            let screen_x = i16::from(sprite.x) - 8 + column as i16;
+            if pixel.is_some() && (0..SCREEN_WIDTH as i16).contains(&screen_x) {
+                pixels[screen_x as usize] = pixel;
+            }
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sprite_at(x: u8) -> Sprite {
+        Sprite {
+            y: 16,
+            x,
+            tile: 0,
+            attributes: 0,
+            index: 0,
+        }
+    }
+
+    #[test]
+    fn test_fetch_penalty() {
+        assert_eq!(fetch_penalty(&[], 0), 0);
+        assert_eq!(fetch_penalty(&[sprite_at(0)], 0), 11);
+        assert_eq!(fetch_penalty(&[sprite_at(8)], 0), 11);
+        assert_eq!(fetch_penalty(&[sprite_at(13)], 0), 6);
+        // the second sprite in the same tile doesn't wait for it again
+        assert_eq!(fetch_penalty(&[sprite_at(8), sprite_at(9)], 0), 17);
+        // scrolling moves the sprite within its background tile
+        assert_eq!(fetch_penalty(&[sprite_at(8)], 3), 8);
+        assert_eq!(fetch_penalty(&[sprite_at(168)], 0), 0);
+    }
+
+    fn write_sprite(memory: &mut Memory, index: u8, y: u8, x: u8, tile: u8, attributes: u8) {
+        let address = OAM_START + u16::from(index) * 4;
+        memory.write_byte(address, y);
+        memory.write_byte(address + 1, x);
+        memory.write_byte(address + 2, tile);
+        memory.write_byte(address + 3, attributes);
+    }
+
+    /// tile filled with a single color id
+    fn write_solid_tile(memory: &mut Memory, tile: u8, color_id: u8) {
+        let address = 0x8000 + u16::from(tile) * 16;
+        for row in 0..8 {
+            memory.write_byte(address + row * 2, if color_id & 1 != 0 { 0xFF } else { 0 });
+            memory.write_byte(
+                address + row * 2 + 1,
+                if color_id & 2 != 0 { 0xFF } else { 0 },
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_sprites_limit() {
+        let mut memory = Memory::new();
+        for index in 0..12 {
+            write_sprite(&mut memory, index, 16, index * 8, 0, 0);
+        }
+
+        let sprites = select_sprites(&memory, 0, 8);
+
+        assert_eq!(sprites.len(), SPRITES_PER_LINE);
+        assert_eq!(sprites[9].index, 9);
+    }
+
+    #[test]
+    fn test_select_sprites_height() {
+        let mut memory = Memory::new();
+        write_sprite(&mut memory, 0, 16, 8, 0, 0);
+
+        assert_eq!(select_sprites(&memory, 7, 8).len(), 1);
+        assert_eq!(select_sprites(&memory, 8, 8).len(), 0);
+        assert_eq!(select_sprites(&memory, 15, 16).len(), 1);
+    }
+
+    #[test]
+    fn test_render_position_and_palette() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 2);
+        write_sprite(&mut memory, 0, 16, 8, 1, attributes::PALETTE);
+
+        let pixels = render_line(&memory, lcdc::OBJ_ENABLE, 0, false, false);
+
+        assert_eq!(pixels[0].unwrap().color_id, 2);
+        assert_eq!(pixels[0].unwrap().palette, OBP1);
+        assert!(pixels[7].is_some());
+        assert!(pixels[8].is_none());
+    }
+
+    #[test]
+    fn test_render_disabled() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 2);
+        write_sprite(&mut memory, 0, 16, 8, 1, 0);
+
+        assert!(render_line(&memory, 0, 0, false, false)
+            .iter()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn test_x_priority() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 1);
+        write_solid_tile(&mut memory, 2, 2);
+        // later in OAM but further left, wins the overlap
+        write_sprite(&mut memory, 0, 16, 12, 1, 0);
+        write_sprite(&mut memory, 1, 16, 10, 2, 0);
+
+        let pixels = render_line(&memory, lcdc::OBJ_ENABLE, 0, false, false);
+
+        assert_eq!(pixels[4].unwrap().color_id, 2);
+        assert_eq!(pixels[10].unwrap().color_id, 1);
+    }
+
+    #[test]
+    fn test_cgb_oam_priority() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 1);
+        write_solid_tile(&mut memory, 2, 2);
+        // in CGB mode the lower OAM index wins even further right
+        write_sprite(&mut memory, 0, 16, 12, 1, 0x05);
+        write_sprite(&mut memory, 1, 16, 10, 2, 0);
+
+        let pixels = render_line(&memory, lcdc::OBJ_ENABLE, 0, true, true);
+
+        assert_eq!(pixels[4].unwrap().color_id, 1);
+        assert_eq!(pixels[4].unwrap().cgb_palette, 5);
+        assert_eq!(pixels[2].unwrap().color_id, 2);
+    }
+
+    #[test]
+    fn test_cgb_x_priority() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 1);
+        write_solid_tile(&mut memory, 2, 2);
+        // with OPRI bit 0 set CGB games order sprites like the DMG
+        write_sprite(&mut memory, 0, 16, 12, 1, 0x05);
+        write_sprite(&mut memory, 1, 16, 10, 2, 0);
+
+        let pixels = render_line(&memory, lcdc::OBJ_ENABLE, 0, true, false);
+
+        assert_eq!(pixels[4].unwrap().color_id, 2);
+        assert_eq!(pixels[10].unwrap().cgb_palette, 5);
+    }
+
+    #[test]
+    fn test_oam_index_priority() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 1, 1);
+        write_solid_tile(&mut memory, 2, 2);
+        write_sprite(&mut memory, 0, 16, 8, 1, 0);
+        write_sprite(&mut memory, 1, 16, 8, 2, 0);
+
+        let pixels = render_line(&memory, lcdc::OBJ_ENABLE, 0, false, false);
+
+        assert_eq!(pixels[0].unwrap().color_id, 1);
+    }
+
+    #[test]
+    fn test_flips() {
+        let mut memory = Memory::new();
+        // tile 1: only the top left pixel is set
+        memory.write_byte(0x8010, 0x80);
+        write_sprite(
+            &mut memory,
+            0,
+            16,
+            8,
+            1,
+            attributes::X_FLIP | attributes::Y_FLIP,
+        );
+
+        let top = render_line(&memory, lcdc::OBJ_ENABLE, 0, false, false);
+        let bottom = render_line(&memory, lcdc::OBJ_ENABLE, 7, false, false);
+
+        assert!(top.iter().all(Option::is_none));
+        assert!(bottom[7].is_some());
+        assert!(bottom[0].is_none());
+    }
+
+    #[test]
+    fn test_tall_sprites() {
+        let mut memory = Memory::new();
+        write_solid_tile(&mut memory, 2, 1);
+        write_solid_tile(&mut memory, 3, 2);
+        // odd tile index is ignored in 8x16 mode
+        write_sprite(&mut memory, 0, 16, 8, 3, 0);
+        let control = lcdc::OBJ_ENABLE | lcdc::OBJ_SIZE;
+
+        assert_eq!(
+            render_line(&memory, control, 0, false, false)[0]
+                .unwrap()
+                .color_id,
+            1
+        );
+        assert_eq!(
+            render_line(&memory, control, 8, false, false)[0]
+                .unwrap()
+                .color_id,
+            2
+        );
+    }
+}
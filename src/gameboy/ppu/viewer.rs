@@ -0,0 +1,294 @@
+//! Debug views of VRAM and OAM for PPU panels in frontends.
+//!
+//! The tile atlas shows raw color ids (0-3) through a `DmgPalette`, the background map is
+//! colored like the screen would be: through BGP on the DMG and the background palettes in CGB
+//! mode. Reads bypass the CPU access restrictions, so the views work in any PPU mode.
+
+use alloc::{vec, vec::Vec};
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use crate::gameboy::Memory;
+#[cfg(feature = "std")]
+use crate::utils::ScreenshotError;
+
+use super::{
+    apply_palette,
+    background::{map_attributes, tile_pixel, TILE_MAP_0, TILE_MAP_1},
+    frame::rgb555_to_rgba,
+    lcdc,
+    sprites::{attributes, Sprite, OAM_ENTRIES},
+    DmgPalette, BGP, LCDC, SCREEN_HEIGHT, SCREEN_WIDTH, SCX, SCY,
+};
+
+/// Tiles per row of the atlas
+const ATLAS_COLUMNS: usize = 16;
+/// The 384 tiles of a VRAM bank
+const ATLAS_TILES: usize = 384;
+const MAP_SIZE: usize = 256;
+
+/// The color of the visible area outlined on the background map
+const VIEWPORT_COLOR: [u8; 4] = [0xFF, 0x00, 0x00, 0xFF];
+
+/// An RGBA image of any size, 4 bytes per pixel, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct DebugImage {
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+}
+
+impl DebugImage {
+    fn new(width: usize, height: usize) -> DebugImage {
+        DebugImage {
+            width,
+            height,
+            rgba: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].try_into().unwrap()
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let index = (y * self.width + x) * 4;
+        self.rgba[index..index + 4].copy_from_slice(&color);
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Encode the image as an RGBA PNG
+    #[cfg(feature = "std")]
+    pub fn write_png(&self, writer: impl Write) -> Result<(), ScreenshotError> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// One of the two background tile maps
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TileMap {
+    /// At 0x9800
+    Low,
+    /// At 0x9C00
+    High,
+}
+
+impl TileMap {
+    fn address(self) -> u16 {
+        match self {
+            TileMap::Low => TILE_MAP_0,
+            TileMap::High => TILE_MAP_1,
+        }
+    }
+}
+
+/// An OAM entry decoded for display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OamEntry {
+    pub index: u8,
+    /// The position of the top left corner on screen, OAM stores it offset by 8 and 16
+    pub x: i16,
+    pub y: i16,
+    pub tile: u8,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    pub behind_background: bool,
+    /// OBP0 or OBP1 on the DMG
+    pub dmg_palette: u8,
+    /// The palette (0-7) and tile data bank in CGB mode
+    pub cgb_palette: u8,
+    pub cgb_bank: u8,
+    /// Whether the sprite is on screen at all
+    pub visible: bool,
+}
+
+/// The 384 tiles of a VRAM bank, 16 per row in a 128x192 image, from 0x8000 onwards
+pub fn tile_atlas(memory: &Memory, bank: usize, palette: &DmgPalette) -> DebugImage {
+    let mut image = DebugImage::new(ATLAS_COLUMNS * 8, ATLAS_TILES / ATLAS_COLUMNS * 8);
+    let bank_attribute = if bank == 1 { map_attributes::BANK } else { 0 };
+    for tile in 0..ATLAS_TILES {
+        // tiles 0x100 and up are only reachable through signed indices from 0x9000
+        let (control, index) = if tile < 0x100 {
+            (lcdc::TILE_DATA, tile as u8)
+        } else {
+            (0, (tile - 0x100) as u8)
+        };
+        let left = tile % ATLAS_COLUMNS * 8;
+        let top = tile / ATLAS_COLUMNS * 8;
+        for y in 0..8 {
+            for x in 0..8 {
+                let color_id = tile_pixel(memory, control, index, bank_attribute, x, y);
+                image.set_pixel(
+                    left + usize::from(x),
+                    top + usize::from(y),
+                    palette.color(color_id),
+                );
+            }
+        }
+    }
+    image
+}
+
+/// The whole 256x256 background `map`, with the visible area at SCX/SCY outlined in red
+pub fn background_map(
+    memory: &Memory,
+    map: TileMap,
+    cgb_mode: bool,
+    palette: &DmgPalette,
+) -> DebugImage {
+    let mut image = DebugImage::new(MAP_SIZE, MAP_SIZE);
+    let control = memory.read_byte(LCDC);
+    let bgp = memory.read_byte(BGP);
+    let palettes = memory.color_palettes();
+    for row in 0..32 {
+        for column in 0..32 {
+            let map_address = map.address() + row * 32 + column;
+            let index = memory.read_vram(map_address);
+            let attributes = if cgb_mode {
+                memory.read_vram_bank(1, map_address)
+            } else {
+                0
+            };
+            for y in 0..8 {
+                for x in 0..8 {
+                    let color_id = tile_pixel(memory, control, index, attributes, x, y);
+                    let color = if cgb_mode {
+                        let number = attributes & map_attributes::PALETTE;
+                        rgb555_to_rgba(palettes.background_color(number, color_id))
+                    } else {
+                        palette.color(apply_palette(bgp, color_id))
+                    };
+                    image.set_pixel(
+                        usize::from(column) * 8 + usize::from(x),
+                        usize::from(row) * 8 + usize::from(y),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    // the viewport wraps around the edges of the map
+    let scroll_x = usize::from(memory.read_byte(SCX));
+    let scroll_y = usize::from(memory.read_byte(SCY));
+    for x in 0..SCREEN_WIDTH {
+        let x = (scroll_x + x) % MAP_SIZE;
+        image.set_pixel(x, scroll_y, VIEWPORT_COLOR);
+        image.set_pixel(x, (scroll_y + SCREEN_HEIGHT - 1) % MAP_SIZE, VIEWPORT_COLOR);
+    }
+    for y in 0..SCREEN_HEIGHT {
+        let y = (scroll_y + y) % MAP_SIZE;
+        image.set_pixel(scroll_x, y, VIEWPORT_COLOR);
+        image.set_pixel((scroll_x + SCREEN_WIDTH - 1) % MAP_SIZE, y, VIEWPORT_COLOR);
+    }
+    image
+}
+
+/// All 40 OAM entries in OAM order
+pub fn oam_entries(memory: &Memory) -> Vec<OamEntry> {
+    let height = if memory.read_byte(LCDC) & lcdc::OBJ_SIZE != 0 {
+        16
+    } else {
+        8
+    };
+    (0..OAM_ENTRIES)
+        .map(|index| {
+            let sprite = Sprite::read(memory, index);
+            let x = i16::from(sprite.x) - 8;
+            let y = i16::from(sprite.y) - 16;
+            OamEntry {
+                index,
+                x,
+                y,
+                tile: sprite.tile,
+                x_flip: sprite.attributes & attributes::X_FLIP != 0,
+                y_flip: sprite.attributes & attributes::Y_FLIP != 0,
+                behind_background: sprite.attributes & attributes::BEHIND_BACKGROUND != 0,
+                dmg_palette: u8::from(sprite.attributes & attributes::PALETTE != 0),
+                cgb_palette: sprite.attributes & attributes::CGB_PALETTE,
+                cgb_bank: u8::from(sprite.attributes & attributes::CGB_BANK != 0),
+                visible: x > -8
+                    && x < SCREEN_WIDTH as i16
+                    && y > -height
+                    && y < SCREEN_HEIGHT as i16,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tile_atlas() {
+        let mut memory = Memory::new();
+        // the top row of tile 1 and of tile 0x100 at 0x9000
+        memory.write_byte(0x8010, 0xFF);
+        memory.write_byte(0x9001, 0x80);
+
+        let atlas = tile_atlas(&memory, 0, &DmgPalette::GRAYSCALE);
+        assert_eq!((atlas.width(), atlas.height()), (128, 192));
+        assert_eq!(atlas.pixel(8, 0), DmgPalette::GRAYSCALE.color(1));
+        assert_eq!(atlas.pixel(0, 0), DmgPalette::GRAYSCALE.color(0));
+        assert_eq!(atlas.pixel(0, 128), DmgPalette::GRAYSCALE.color(2));
+    }
+
+    #[test]
+    fn test_background_map() {
+        let mut memory = Memory::new();
+        memory.write_byte(LCDC, lcdc::LCD_ENABLE | lcdc::TILE_DATA | lcdc::BG_ENABLE);
+        memory.write_byte(BGP, 0b1110_0100);
+        memory.write_byte(SCX, 200);
+        memory.write_byte(SCY, 0);
+        // tile 1 is solid color 3, placed at the second column
+        for address in 0x8010..0x8020 {
+            memory.write_byte(address, 0xFF);
+        }
+        memory.write_byte(TILE_MAP_0 + 1, 1);
+
+        let image = background_map(&memory, TileMap::Low, false, &DmgPalette::GRAYSCALE);
+        assert_eq!(image.pixel(9, 5), DmgPalette::GRAYSCALE.color(3));
+        assert_eq!(image.pixel(1, 5), DmgPalette::GRAYSCALE.color(0));
+        // the viewport starts at x 200 and wraps to end at x 103
+        assert_eq!(image.pixel(200, 5), VIEWPORT_COLOR);
+        assert_eq!(image.pixel(103, 5), VIEWPORT_COLOR);
+        assert_eq!(image.pixel(50, 0), VIEWPORT_COLOR);
+        assert_eq!(image.pixel(150, 0), DmgPalette::GRAYSCALE.color(0));
+    }
+
+    #[test]
+    fn test_oam_entries() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xFE04, 16);
+        memory.write_byte(0xFE05, 8);
+        memory.write_byte(0xFE06, 0x42);
+        memory.write_byte(0xFE07, attributes::X_FLIP | attributes::PALETTE | 0b101);
+
+        let entries = oam_entries(&memory);
+        assert_eq!(entries.len(), 40);
+        let entry = entries[1];
+        assert_eq!((entry.x, entry.y, entry.tile), (0, 0, 0x42));
+        assert!(entry.x_flip && !entry.y_flip && entry.visible);
+        assert_eq!((entry.dmg_palette, entry.cgb_palette), (1, 0b101));
+        assert!(!entries[0].visible);
+    }
+}
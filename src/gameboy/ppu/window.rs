@@ -0,0 +1,133 @@
+//! Window layer rendering.
+//!
+//! The window has its own line counter which only advances on lines where the window
+//! was actually drawn, so hiding the window mid-frame continues where it left off.
+
+use super::{
+    background::{map_pixel, BackgroundPixel},
+    lcdc, SCREEN_WIDTH,
+};
+use crate::gameboy::Memory;
+
+pub const WY: u16 = 0xFF4A;
+pub const WX: u16 = 0xFF4B;
+
+/// The largest WX that still shows the window, WX is offset by 7
+pub const MAX_WX: u8 = SCREEN_WIDTH as u8 + 6;
+
+const TILE_MAP_0: u16 = 0x9800;
+const TILE_MAP_1: u16 = 0x9C00;
+
+/// Draw the window over the background pixels of a scanline
+///
+/// On the DMG the window is hidden along with the background by LCDC bit 0, not so in CGB mode.
+/// Returns whether the window was visible on this line
+pub fn render_line(
+    memory: &Memory,
+    control: u8,
+    window_line: u8,
+    cgb_mode: bool,
+    pixels: &mut [BackgroundPixel],
+) -> bool {
+    if control & lcdc::WINDOW_ENABLE == 0 || (control & lcdc::BG_ENABLE == 0 && !cgb_mode) {
+        return false;
+    }
+
+    // WX is offset by 7, values below 7 start the window off screen
+    let left = i16::from(memory.read_byte(WX)) - 7;
+    if left >= SCREEN_WIDTH as i16 {
+        return false;
+    }
+
+    let tile_map = if control & lcdc::WINDOW_TILE_MAP != 0 {
+        TILE_MAP_1
+    } else {
+        TILE_MAP_0
+    };
+
+    for (screen_x, pixel) in pixels.iter_mut().enumerate() {
+        let x = screen_x as i16 - left;
+        if x < 0 {
+            continue;
+        }
+        let x = x as u8;
+        let map_address = tile_map + u16::from(window_line / 8) * 32 + u16::from(x / 8);
+        *pixel = map_pixel(
+            memory,
+            control,
+            map_address,
+            cgb_mode,
+            x % 8,
+            window_line % 8,
+        );
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTROL: u8 = lcdc::BG_ENABLE | lcdc::WINDOW_ENABLE | lcdc::TILE_DATA;
+
+    fn color_ids(pixels: &[BackgroundPixel]) -> Vec<u8> {
+        pixels.iter().map(|pixel| pixel.color_id).collect()
+    }
+
+    fn setup() -> Memory {
+        let mut memory = Memory::new();
+        // tile 1 is solid color 2
+        for row in 0..8 {
+            memory.write_byte(0x8011 + row * 2, 0xFF);
+        }
+        for column in 0..32 {
+            memory.write_byte(TILE_MAP_1 + column, 0x01);
+        }
+        memory
+    }
+
+    #[test]
+    fn test_window_position() {
+        let mut memory = setup();
+        memory.write_byte(WX, 7 + 100);
+        let mut pixels = [BackgroundPixel::default(); SCREEN_WIDTH];
+
+        assert!(render_line(
+            &memory,
+            CONTROL | lcdc::WINDOW_TILE_MAP,
+            0,
+            false,
+            &mut pixels
+        ));
+        let color_ids = color_ids(&pixels);
+        assert_eq!(color_ids[99], 0);
+        assert_eq!(color_ids[100..], [2; 60]);
+    }
+
+    #[test]
+    fn test_window_tile_map_select() {
+        let mut memory = setup();
+        memory.write_byte(WX, 7);
+        let mut pixels = [BackgroundPixel::default(); SCREEN_WIDTH];
+
+        assert!(render_line(&memory, CONTROL, 0, false, &mut pixels));
+        assert_eq!(color_ids(&pixels), [0; SCREEN_WIDTH]);
+    }
+
+    #[test]
+    fn test_window_disabled() {
+        let mut memory = setup();
+        let mut pixels = [BackgroundPixel::default(); SCREEN_WIDTH];
+
+        assert!(!render_line(
+            &memory,
+            CONTROL & !lcdc::WINDOW_ENABLE,
+            0,
+            false,
+            &mut pixels
+        ));
+        memory.write_byte(WX, 167);
+        assert!(!render_line(&memory, CONTROL, 0, false, &mut pixels));
+    }
+}
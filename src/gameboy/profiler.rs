@@ -0,0 +1,190 @@
+//! An exact cycle profiler, for homebrew developers looking for their slowest code.
+//!
+//! Every step is charged to the bank and address of the instruction it ran, interrupt dispatch
+//! to the instruction it interrupted and time spent in HALT to a bucket of its own. With
+//! `Symbols` the addresses are summed per function, the closest global label before them, so
+//! `Main.loop` counts towards `Main`. With the block cache on a whole cached block is charged to
+//! its first instruction.
+
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+
+use super::Symbols;
+
+/// The cycles charged to one address or function
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileEntry {
+    /// The function, or `BB:AAAA` for an address without a label before it
+    pub name: String,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    cycles: BTreeMap<(usize, u16), u64>, // per bank and address
+    halted: u64,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Charge `cycles` to the instruction at `address` in `bank`
+    pub fn record(&mut self, bank: usize, address: u16, cycles: u32) {
+        *self.cycles.entry((bank, address)).or_default() += u64::from(cycles);
+        self.total += u64::from(cycles);
+    }
+
+    /// Charge `cycles` spent in HALT
+    pub fn record_halted(&mut self, cycles: u32) {
+        self.halted += u64::from(cycles);
+        self.total += u64::from(cycles);
+    }
+
+    /// All T-cycles profiled, halted ones included
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    pub fn halted_cycles(&self) -> u64 {
+        self.halted
+    }
+
+    /// The cycles charged to the instruction at `address` in `bank`
+    pub fn cycles(&self, bank: usize, address: u16) -> u64 {
+        self.cycles.get(&(bank, address)).copied().unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        *self = Profiler::new();
+    }
+
+    /// The instructions taking the most cycles, hottest first
+    pub fn hottest_addresses(&self) -> Vec<ProfileEntry> {
+        let mut entries: Vec<ProfileEntry> = self
+            .cycles
+            .iter()
+            .map(|(&(bank, address), &cycles)| ProfileEntry {
+                name: format!("{bank:02X}:{address:04X}"),
+                cycles,
+            })
+            .collect();
+        sort(&mut entries);
+        entries
+    }
+
+    /// The functions taking the most cycles, hottest first, an address without a label before
+    /// it in its bank is its own entry
+    pub fn hottest_functions(&self, symbols: &Symbols) -> Vec<ProfileEntry> {
+        let mut functions: BTreeMap<String, u64> = BTreeMap::new();
+        for (&(bank, address), &cycles) in &self.cycles {
+            let name = match symbols.preceding(bank, address) {
+                Some((label, _)) => label.split('.').next().unwrap_or(label).to_string(),
+                None => format!("{bank:02X}:{address:04X}"),
+            };
+            *functions.entry(name).or_default() += cycles;
+        }
+
+        let mut entries: Vec<ProfileEntry> = functions
+            .into_iter()
+            .map(|(name, cycles)| ProfileEntry { name, cycles })
+            .collect();
+        sort(&mut entries);
+        entries
+    }
+
+    /// The `count` hottest functions with `symbols`, or addresses without, as a table
+    pub fn report(&self, symbols: Option<&Symbols>, count: usize) -> String {
+        let percent = |cycles: u64| cycles as f64 * 100.0 / self.total.max(1) as f64;
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "{} cycles profiled, {:.1}% halted",
+            self.total,
+            percent(self.halted)
+        );
+
+        let entries = match symbols {
+            Some(symbols) => self.hottest_functions(symbols),
+            None => self.hottest_addresses(),
+        };
+        let _ = writeln!(
+            report,
+            "{:>12} {:>6}  {}",
+            "cycles",
+            "%",
+            if symbols.is_some() {
+                "function"
+            } else {
+                "address"
+            }
+        );
+        for entry in entries.iter().take(count) {
+            let _ = writeln!(
+                report,
+                "{:>12} {:>5.1}%  {}",
+                entry.cycles,
+                percent(entry.cycles),
+                entry.name
+            );
+        }
+        report
+    }
+}
+
+/// Hottest first, ties by name
+fn sort(entries: &mut [ProfileEntry]) {
+    entries.sort_by(|a, b| b.cycles.cmp(&a.cycles).then_with(|| a.name.cmp(&b.name)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hottest_functions() {
+        let mut profiler = Profiler::new();
+        profiler.record(0, 0x0150, 4);
+        profiler.record(0, 0x0156, 12);
+        profiler.record(0, 0x0156, 12);
+        profiler.record(1, 0x4000, 16);
+        profiler.record(0, 0x0100, 4);
+        profiler.record_halted(52);
+
+        assert_eq!(profiler.total_cycles(), 100);
+        assert_eq!(profiler.cycles(0, 0x0156), 24);
+        assert_eq!(profiler.hottest_addresses()[0].name, "00:0156");
+
+        let symbols = Symbols::parse("00:0150 Main\n00:0156 Main.loop\n01:4000 Banked").unwrap();
+        let functions = profiler.hottest_functions(&symbols);
+        assert_eq!(
+            functions,
+            [
+                ProfileEntry {
+                    name: "Main".to_string(),
+                    cycles: 28
+                },
+                ProfileEntry {
+                    name: "Banked".to_string(),
+                    cycles: 16
+                },
+                ProfileEntry {
+                    name: "00:0100".to_string(),
+                    cycles: 4
+                },
+            ]
+        );
+
+        let report = profiler.report(Some(&symbols), 2);
+        assert!(report.starts_with("100 cycles profiled, 52.0% halted\n"));
+        assert!(report.contains("          28  28.0%  Main\n"));
+        assert!(!report.contains("00:0100"));
+    }
+}
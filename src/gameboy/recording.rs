@@ -0,0 +1,233 @@
+//! Recording the screen to an animated GIF or a numbered PNG sequence.
+//!
+//! Every completed frame is passed to `record`. PNG sequences keep every frame, to be played
+//! back at the Game Boy's ~59.73 fps. GIF delays are in hundredths of a second and most viewers
+//! slow down delays below 2, so each GIF frame is timed against the emulated clock: frames that
+//! repeat the previous one extend its delay and frames too close to it are dropped, keeping the
+//! animation in sync over any length of recording.
+
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::utils::RecordingError;
+
+use super::{scheduler::T_CYCLES_PER_FRAME, Frame, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const CLOCK_RATE: u64 = 4_194_304;
+
+/// The shortest GIF delay viewers honor, in hundredths of a second
+const MIN_GIF_DELAY: u64 = 2;
+
+/// How hard the GIF encoder works to pick a palette for frames with more than 256 colors
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoFormat {
+    /// An animated GIF, looping forever
+    Gif,
+    /// `frame-000000.png`, `frame-000001.png`, ... in a directory
+    PngSequence,
+}
+
+impl VideoFormat {
+    /// GIF for paths ending in `.gif`, a PNG sequence in a directory otherwise
+    pub fn from_path(path: impl AsRef<Path>) -> VideoFormat {
+        let is_gif = path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+        if is_gif {
+            VideoFormat::Gif
+        } else {
+            VideoFormat::PngSequence
+        }
+    }
+}
+
+enum Output {
+    Gif {
+        encoder: Option<gif::Encoder<BufWriter<File>>>, // taken when the recording is finished
+        pending: Option<(Frame, u64)>, // the frame waiting for its delay and when it was shown
+    },
+    PngSequence(PathBuf),
+}
+
+/// Writes the frames passed to `record`, the file is complete once `finish` is called or the
+/// recorder is dropped
+pub struct VideoRecorder {
+    output: Output,
+    frames: u64,
+}
+
+impl VideoRecorder {
+    /// Start a recording at `path`, a PNG sequence creates the directory if needed
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: VideoFormat,
+    ) -> Result<VideoRecorder, RecordingError> {
+        let output = match format {
+            VideoFormat::Gif => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder =
+                    gif::Encoder::new(file, SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, &[])?;
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+                Output::Gif {
+                    encoder: Some(encoder),
+                    pending: None,
+                }
+            }
+            VideoFormat::PngSequence => {
+                fs::create_dir_all(&path)?;
+                Output::PngSequence(path.as_ref().to_path_buf())
+            }
+        };
+        Ok(VideoRecorder { output, frames: 0 })
+    }
+
+    /// Add the next frame, one is expected for every emulated frame
+    pub fn record(&mut self, frame: &Frame) -> Result<(), RecordingError> {
+        let index = self.frames;
+        self.frames += 1;
+        match &mut self.output {
+            Output::Gif { encoder, pending } => {
+                let Some(encoder) = encoder else {
+                    return Ok(());
+                };
+                let now = centiseconds(index);
+                if let Some((previous, shown)) = pending {
+                    if previous == frame || now - *shown < MIN_GIF_DELAY {
+                        return Ok(());
+                    }
+                    write_gif_frame(encoder, previous, now - *shown)?;
+                }
+                *pending = Some((frame.clone(), now));
+            }
+            Output::PngSequence(directory) => {
+                let path = directory.join(format!("frame-{:06}.png", index));
+                frame.write_png(BufWriter::new(File::create(path)?))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of frames recorded, including those merged or dropped from a GIF
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Write the last frame and complete the file
+    pub fn finish(mut self) -> Result<(), RecordingError> {
+        self.end()
+    }
+
+    fn end(&mut self) -> Result<(), RecordingError> {
+        if let Output::Gif { encoder, pending } = &mut self.output {
+            let Some(mut encoder) = encoder.take() else {
+                return Ok(());
+            };
+            if let Some((frame, shown)) = pending.take() {
+                let delay = centiseconds(self.frames) - shown;
+                write_gif_frame(&mut encoder, &frame, delay.max(MIN_GIF_DELAY))?;
+            }
+            encoder.into_inner()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VideoRecorder {
+    fn drop(&mut self) {
+        if let Err(error) = self.end() {
+            tracing::warn!("Failed to finish the video recording: {}", error);
+        }
+    }
+}
+
+/// When emulated frame `index` starts, in hundredths of a second
+fn centiseconds(index: u64) -> u64 {
+    index * u64::from(T_CYCLES_PER_FRAME) * 100 / CLOCK_RATE
+}
+
+fn write_gif_frame(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    frame: &Frame,
+    delay: u64,
+) -> Result<(), RecordingError> {
+    let mut rgba = frame.as_bytes().to_vec();
+    let mut gif_frame = gif::Frame::from_rgba_speed(
+        SCREEN_WIDTH as u16,
+        SCREEN_HEIGHT as u16,
+        &mut rgba,
+        GIF_QUANTIZE_SPEED,
+    );
+    gif_frame.delay = delay.min(u64::from(u16::MAX)) as u16;
+    encoder.write_frame(&gif_frame)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gameboy::DmgPalette;
+
+    use super::*;
+
+    fn frame(shade: u8) -> Frame {
+        Frame::from_shades(
+            &[shade; SCREEN_WIDTH * SCREEN_HEIGHT],
+            &DmgPalette::GRAYSCALE,
+        )
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(VideoFormat::from_path("run.GIF"), VideoFormat::Gif);
+        assert_eq!(VideoFormat::from_path("frames"), VideoFormat::PngSequence);
+    }
+
+    #[test]
+    fn test_gif_timing() {
+        let path = std::env::temp_dir().join(format!(
+            "gameboy_emulator_{}_recording.gif",
+            std::process::id()
+        ));
+        let mut recorder = VideoRecorder::create(&path, VideoFormat::Gif).unwrap();
+        // a second of frames alternating between two shades, then a second of the same one
+        for index in 0..120 {
+            let shade = if index < 60 { index % 2 } else { 3 };
+            recorder.record(&frame(shade as u8)).unwrap();
+        }
+        recorder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        let mut delays = Vec::new();
+        while let Some(gif_frame) = decoder.read_next_frame().unwrap() {
+            delays.push(gif_frame.delay);
+        }
+        fs::remove_file(&path).unwrap();
+
+        assert!(delays.iter().all(|&delay| delay >= 2));
+        // 120 frames at 59.73 fps
+        assert_eq!(delays.iter().sum::<u16>(), 200);
+        assert_eq!(delays.last(), Some(&100));
+    }
+
+    #[test]
+    fn test_png_sequence() {
+        let directory =
+            std::env::temp_dir().join(format!("gameboy_emulator_{}_recording", std::process::id()));
+        let mut recorder = VideoRecorder::create(&directory, VideoFormat::PngSequence).unwrap();
+        recorder.record(&frame(0)).unwrap();
+        recorder.record(&frame(0)).unwrap();
+        assert_eq!(recorder.frames(), 2);
+        recorder.finish().unwrap();
+
+        assert!(directory.join("frame-000000.png").exists());
+        assert!(directory.join("frame-000001.png").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+//! Rewinding, a bounded history of save states taken every few frames.
+//!
+//! Only the newest state is kept whole. Each older one is stored as its XOR with the next newer
+//! state, which is mostly zeros between nearby frames, with the runs of zeros left out. Popping
+//! the newest state rebuilds the one before it from its delta, and the oldest delta can be dropped
+//! without touching the others.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Zeros shorter than this stay inside a literal run, a new run costs 8 bytes of header
+const MIN_ZERO_RUN: usize = 8;
+
+pub struct Rewind {
+    interval: u32,             // frames between snapshots
+    capacity: usize,           // snapshots kept
+    frames: u32,               // frames since the last snapshot
+    latest: Option<Vec<u8>>,   // the newest snapshot
+    deltas: VecDeque<Vec<u8>>, // older snapshots, oldest first, each against the next newer one
+}
+
+impl Rewind {
+    /// Take a snapshot every `interval` frames and keep the last `capacity` of them
+    pub fn new(interval: u32, capacity: usize) -> Rewind {
+        Rewind {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames: 0,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Count a frame, returns true when a snapshot is due
+    pub fn frame(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames < self.interval {
+            return false;
+        }
+        self.frames = 0;
+        true
+    }
+
+    /// Add a snapshot, dropping the oldest one if the history is full
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.latest.take() {
+            if previous.len() == state.len() {
+                self.deltas.push_back(encode_delta(&previous, &state));
+            } else {
+                // the layout changed, older snapshots can't be rebuilt from this one
+                self.deltas.clear();
+            }
+        }
+        self.latest = Some(state);
+
+        while self.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+    }
+
+    /// Take the newest snapshot
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let state = self.latest.take()?;
+        self.latest = self
+            .deltas
+            .pop_back()
+            .map(|delta| decode_delta(&delta, &state));
+        self.frames = 0;
+        Some(state)
+    }
+
+    /// The number of snapshots kept
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(self.latest.is_some())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.frames = 0;
+    }
+}
+
+impl Default for Rewind {
+    /// A snapshot every 4 frames, about 20 seconds of history
+    fn default() -> Self {
+        Self::new(4, 300)
+    }
+}
+
+/// XOR `old` with `new` and store it as runs of (zeros skipped, literal length, literal bytes)
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = old.iter().zip(new).map(|(old, new)| old ^ new).collect();
+    let mut delta = Vec::new();
+    let mut position = 0;
+    while position < xor.len() {
+        let zeros = xor[position..]
+            .iter()
+            .take_while(|&&byte| byte == 0)
+            .count();
+        position += zeros;
+        if position == xor.len() {
+            break;
+        }
+
+        // the literal ends at the next run of zeros long enough to be worth skipping
+        let start = position;
+        let mut zero_run = 0;
+        while position < xor.len() && zero_run < MIN_ZERO_RUN {
+            zero_run = if xor[position] == 0 { zero_run + 1 } else { 0 };
+            position += 1;
+        }
+        position -= zero_run;
+
+        delta.extend_from_slice(&(zeros as u32).to_le_bytes());
+        delta.extend_from_slice(&((position - start) as u32).to_le_bytes());
+        delta.extend_from_slice(&xor[start..position]);
+    }
+    delta
+}
+
+/// Rebuild the older state from a delta created by `encode_delta` and the newer state
+fn decode_delta(delta: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut old = new.to_vec();
+    let mut position = 0;
+    let mut read = 0;
+    let read_u32 = |read: &mut usize| {
+        let value = u32::from_le_bytes(delta[*read..*read + 4].try_into().unwrap());
+        *read += 4;
+        value as usize
+    };
+    while read < delta.len() {
+        position += read_u32(&mut read);
+        let length = read_u32(&mut read);
+        for (byte, xor) in old[position..position + length]
+            .iter_mut()
+            .zip(&delta[read..read + length])
+        {
+            *byte ^= xor;
+        }
+        position += length;
+        read += length;
+    }
+    old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta_round_trip() {
+        let old: Vec<u8> = (0..200).map(|value| value as u8).collect();
+        let mut new = old.clone();
+        new[3] = 0xFF;
+        new[5] = 0xAA;
+        new[150] = 0x00;
+        new[199] = 0x12;
+
+        let delta = encode_delta(&old, &new);
+        assert!(delta.len() < 40);
+        assert_eq!(decode_delta(&delta, &new), old);
+        assert!(encode_delta(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut rewind = Rewind::new(1, 10);
+        rewind.push(vec![1, 2, 3]);
+        rewind.push(vec![1, 2, 4]);
+        rewind.push(vec![5, 2, 4]);
+
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.pop(), Some(vec![5, 2, 4]));
+        assert_eq!(rewind.pop(), Some(vec![1, 2, 4]));
+        assert_eq!(rewind.pop(), Some(vec![1, 2, 3]));
+        assert_eq!(rewind.pop(), None);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut rewind = Rewind::new(1, 2);
+        for value in 0..5 {
+            rewind.push(vec![value; 4]);
+        }
+
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.pop(), Some(vec![4; 4]));
+        assert_eq!(rewind.pop(), Some(vec![3; 4]));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn test_length_change() {
+        let mut rewind = Rewind::new(1, 10);
+        rewind.push(vec![1, 2]);
+        rewind.push(vec![1, 2, 3]);
+
+        assert_eq!(rewind.len(), 1);
+        assert_eq!(rewind.pop(), Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn test_interval() {
+        let mut rewind = Rewind::new(3, 10);
+
+        assert!(!rewind.frame());
+        assert!(!rewind.frame());
+        assert!(rewind.frame());
+        assert!(!rewind.frame());
+    }
+}
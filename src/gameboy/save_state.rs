@@ -0,0 +1,196 @@
+//! Save states, a snapshot of the whole emulator in a versioned binary format.
+//!
+//! A state starts with the magic bytes and the format version, followed by every component
+//! writing its fields in a fixed order. Numbers are little endian, byte arrays are length prefixed.
+
+use alloc::vec::Vec;
+
+use crate::utils::SaveStateError;
+
+pub const MAGIC: &[u8; 4] = b"GBSS";
+pub const VERSION: u32 = 13;
+
+/// A component whose state can be written to and restored from a save state
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError>;
+}
+
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    /// A writer with the header already written
+    pub fn new() -> StateWriter {
+        let mut writer = StateWriter { bytes: Vec::new() };
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.write_u32(VERSION);
+        writer
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(u8::from(value));
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// A reader positioned after the header, fails if the header is missing or from another version
+    pub fn new(bytes: &'a [u8]) -> Result<StateReader<'a>, SaveStateError> {
+        let mut reader = StateReader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SaveStateError::InvalidMagic);
+        }
+        let version = reader.read_u32()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        Ok(reader)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveStateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], SaveStateError> {
+        let length = self.read_u32()? as usize;
+        self.take(length)
+    }
+
+    /// Read a byte array into `target`, which must have the saved length
+    pub fn read_into(&mut self, target: &mut [u8]) -> Result<(), SaveStateError> {
+        let bytes = self.read_bytes()?;
+        if bytes.len() != target.len() {
+            return Err(SaveStateError::InvalidData("byte array length"));
+        }
+        target.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Whether all bytes have been read
+    pub fn is_finished(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SaveStateError> {
+        let end = self.position + length;
+        if end > self.bytes.len() {
+            return Err(SaveStateError::UnexpectedEnd);
+        }
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_u32(0x789ABCDE);
+        writer.write_u64(0x0123456789ABCDEF);
+        writer.write_bytes(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes).unwrap();
+        assert_eq!(reader.read_u8().unwrap(), 0x12);
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_u16().unwrap(), 0x3456);
+        assert_eq!(reader.read_u32().unwrap(), 0x789ABCDE);
+        assert_eq!(reader.read_u64().unwrap(), 0x0123456789ABCDEF);
+        let mut array = [0; 3];
+        reader.read_into(&mut array).unwrap();
+        assert_eq!(array, [1, 2, 3]);
+        assert!(reader.is_finished());
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert!(matches!(
+            StateReader::new(b"NOPE\x01\x00\x00\x00"),
+            Err(SaveStateError::InvalidMagic)
+        ));
+        assert!(matches!(
+            StateReader::new(b"GBSS\xFF\x00\x00\x00"),
+            Err(SaveStateError::UnsupportedVersion(255))
+        ));
+        assert!(matches!(
+            StateReader::new(b"GB"),
+            Err(SaveStateError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn test_unexpected_end() {
+        let bytes = StateWriter::new().into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+
+        assert!(matches!(
+            reader.read_u16(),
+            Err(SaveStateError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn test_read_into_length_mismatch() {
+        let mut writer = StateWriter::new();
+        writer.write_bytes(&[1, 2]);
+        let bytes = writer.into_bytes();
+        let mut reader = StateReader::new(&bytes).unwrap();
+
+        assert!(reader.read_into(&mut [0; 3]).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Drives the whole system from the CPU's cycle counts.
+//!
+//! The CPU executes one instruction at a time and reports how many machine cycles it took,
+//! the scheduler converts that to T-cycles and advances the timer, serial port, APU, DMA
+//! and PPU by exactly the same amount before the next instruction runs.
+//!
+//! How closely everything else follows the CPU is set by the `AccuracyProfile`.
+//!
+//! In CGB double speed a machine cycle only takes 2 T-cycles of the PPU and APU, which keep
+//! their pace, so all cycle counts here are in normal speed T-cycles (dots).
+
+use crate::{
+    gameboy::{
+        save_state::{SaveState, StateReader, StateWriter},
+        Cpu, Memory, MemoryAccess, Observer, Ppu, TimedBus,
+    },
+    utils::SaveStateError,
+};
+
+/// T-cycles in one machine cycle
+pub const T_CYCLES_PER_M_CYCLE: u32 = 4;
+
+/// T-cycles in one machine cycle in CGB double speed
+pub const T_CYCLES_PER_M_CYCLE_DOUBLE_SPEED: u32 = 2;
+
+/// T-cycles (dots) in one frame of 154 lines
+pub const T_CYCLES_PER_FRAME: u32 = 70224;
+
+/// How much accuracy to trade for speed
+///
+/// The ROM test suites in `tests/` run with the profile in `GB_ACCURACY`, Balanced by default. No
+/// profile has been checked against the suites yet
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AccuracyProfile {
+    /// The peripherals and PPU catch up after every instruction and VRAM and OAM are always
+    /// accessible, and the DMG STAT write bug is left out. Expected to fail the tests timing memory
+    /// accesses within an instruction, blargg's mem_timing and mooneye's `*_timing` and OAM DMA
+    /// tests
+    Fast,
+    /// The peripherals advance with every memory access, the PPU catches up after every
+    /// instruction
+    #[default]
+    Balanced,
+    /// The PPU advances with every memory access too and draws through the pixel FIFO, and the
+    /// CPU is locked out of VRAM and OAM while the PPU uses them. On the DMG accesses to OAM
+    /// during mode 2 corrupt it, see `Memory::corrupt_oam`
+    CycleAccurate,
+}
+
+/// Keeps the CPU, memory mapped peripherals and PPU in lockstep
+pub struct Scheduler {
+    cycles: u64, // T-cycles since power on
+    profile: AccuracyProfile,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler {
+            cycles: 0,
+            profile: AccuracyProfile::default(),
+        }
+    }
+
+    /// Set how closely the peripherals and PPU follow the CPU's memory accesses
+    ///
+    /// The PPU renderer and VRAM/OAM blocking are set by `GameBoy::set_accuracy`
+    pub fn set_profile(&mut self, profile: AccuracyProfile) {
+        self.profile = profile;
+    }
+
+    pub fn profile(&self) -> AccuracyProfile {
+        self.profile
+    }
+
+    /// T-cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Execute one instruction (or interrupt dispatch) and advance everything else to match
+    ///
+    /// The peripherals advance with each memory access, the PPU catches up after the instruction,
+    /// unless the accuracy profile says otherwise. Observed steps never use the Fast profile's
+    /// untimed accesses.
+    /// Returns the number of T-cycles taken
+    pub fn step(&mut self, cpu: &mut Cpu, memory: &mut Memory, ppu: &mut Ppu) -> u32 {
+        if self.profile == AccuracyProfile::Fast {
+            let m_cycles = cpu.step_instruction(memory).cycles;
+            memory.tick(m_cycles);
+            return self.catch_up(memory, ppu, m_cycles, 0);
+        }
+        self.step_bus(cpu, memory, ppu, None, None)
+    }
+
+    /// Like `step`, reporting each memory access the CPU makes to `observer`
+    pub fn step_observed(
+        &mut self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        ppu: &mut Ppu,
+        observer: &dyn Fn(MemoryAccess),
+    ) -> u32 {
+        self.step_bus(cpu, memory, ppu, Some(observer), None)
+    }
+
+    /// Like `step`, calling `hooks` for each instruction, memory access and interrupt
+    pub fn step_hooked(
+        &mut self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        ppu: &mut Ppu,
+        hooks: &mut dyn Observer,
+    ) -> u32 {
+        self.step_bus(cpu, memory, ppu, None, Some(hooks))
+    }
+
+    fn step_bus(
+        &mut self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        ppu: &mut Ppu,
+        observer: Option<&dyn Fn(MemoryAccess)>,
+        hooks: Option<&mut dyn Observer>,
+    ) -> u32 {
+        let cycle_accurate = self.profile == AccuracyProfile::CycleAccurate;
+        let mut clock = |memory: &mut Memory| ppu.tick(memory, t_cycles_per_m_cycle(memory));
+        let (m_cycles, bus_cycles) = {
+            let mut bus = match (observer, hooks) {
+                (Some(observer), _) => TimedBus::with_observer(memory, observer),
+                (None, Some(hooks)) => TimedBus::with_hooks(memory, hooks),
+                (None, None) => TimedBus::new(memory),
+            };
+            if cycle_accurate {
+                bus = bus.clocked(&mut clock);
+            }
+            let m_cycles = cpu.step_instruction(&mut bus).cycles;
+            (m_cycles, bus.cycles())
+        };
+        memory.tick(m_cycles.saturating_sub(bus_cycles));
+        let ppu_m_cycles = if cycle_accurate { bus_cycles } else { 0 };
+        self.catch_up(memory, ppu, m_cycles, ppu_m_cycles)
+    }
+
+    /// Advance the PPU over the `m_cycles` of an instruction it has not seen yet and run HDMA
+    ///
+    /// The first `ppu_m_cycles` already ticked the PPU. Returns the T-cycles taken
+    fn catch_up(
+        &mut self,
+        memory: &mut Memory,
+        ppu: &mut Ppu,
+        m_cycles: u8,
+        ppu_m_cycles: u8,
+    ) -> u32 {
+        let t_cycles_per_m_cycle = t_cycles_per_m_cycle(memory);
+        let mut t_cycles = u32::from(m_cycles) * t_cycles_per_m_cycle;
+        ppu.tick(
+            memory,
+            u32::from(m_cycles.saturating_sub(ppu_m_cycles)) * t_cycles_per_m_cycle,
+        );
+
+        // the CPU waits for HDMA blocks while everything else keeps running, including the PPU
+        // which may start the next HBlank block
+        loop {
+            let stall = memory.take_hdma_stall();
+            if stall == 0 {
+                break;
+            }
+            for _ in 0..stall {
+                memory.tick(1);
+            }
+            ppu.tick(memory, stall * t_cycles_per_m_cycle);
+            t_cycles += stall * t_cycles_per_m_cycle;
+        }
+
+        self.cycles += u64::from(t_cycles);
+        t_cycles
+    }
+
+    /// Step until at least `t_cycles` have elapsed
+    ///
+    /// Instructions are not split, so this returns the T-cycles actually run, which may be a few more.
+    /// Returns early if the CPU traps on an illegal opcode
+    pub fn run_cycles(
+        &mut self,
+        cpu: &mut Cpu,
+        memory: &mut Memory,
+        ppu: &mut Ppu,
+        t_cycles: u32,
+    ) -> u32 {
+        let mut elapsed = 0;
+        while elapsed < t_cycles && cpu.error().is_none() {
+            elapsed += self.step(cpu, memory, ppu);
+        }
+        elapsed
+    }
+
+    /// Step until the PPU enters VBlank with a completed frame
+    ///
+    /// With the LCD turned off no frame is produced, then this returns after a frame's worth of cycles.
+    /// Returns the T-cycles run, early if the CPU traps on an illegal opcode
+    pub fn run_until_vblank(&mut self, cpu: &mut Cpu, memory: &mut Memory, ppu: &mut Ppu) -> u32 {
+        let _frame =
+            tracing::debug_span!(target: "gameboy", "frame", cycle = self.cycles).entered();
+        let mut elapsed = 0;
+        while elapsed < T_CYCLES_PER_FRAME {
+            elapsed += self.step(cpu, memory, ppu);
+            if ppu.take_frame_ready() || cpu.error().is_some() {
+                break;
+            }
+        }
+        elapsed
+    }
+}
+
+/// The PPU's dots in one machine cycle of the CPU
+fn t_cycles_per_m_cycle(memory: &Memory) -> u32 {
+    if memory.double_speed() {
+        T_CYCLES_PER_M_CYCLE_DOUBLE_SPEED
+    } else {
+        T_CYCLES_PER_M_CYCLE
+    }
+}
+
+impl SaveState for Scheduler {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u64(self.cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cycles = state.read_u64()?;
+        Ok(())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(program: &[u8]) -> (Cpu, Memory, Ppu) {
+        let mut memory = Memory::new();
+        for (offset, byte) in program.iter().enumerate() {
+            memory.write_byte(0xC000 + offset as u16, *byte);
+        }
+        let mut cpu = Cpu::power_on();
+        cpu.registers.pc = 0xC000;
+        (cpu, memory, Ppu::new())
+    }
+
+    #[test]
+    fn test_step_converts_to_t_cycles() {
+        // LD BC, d16
+        let (mut cpu, mut memory, mut ppu) = setup(&[0x01, 0x34, 0x12]);
+        let mut scheduler = Scheduler::new();
+
+        assert_eq!(scheduler.step(&mut cpu, &mut memory, &mut ppu), 12);
+        assert_eq!(scheduler.cycles(), 12);
+    }
+
+    #[test]
+    fn test_accuracy_profiles() {
+        use crate::gameboy::ppu::{lcdc, LCDC, LY};
+
+        for (profile, ly) in [
+            (AccuracyProfile::Fast, 0),
+            (AccuracyProfile::Balanced, 0),
+            (AccuracyProfile::CycleAccurate, 1),
+        ] {
+            // LDH A, ($44) reads LY in its third machine cycle; LD ($C100), A
+            let (mut cpu, mut memory, mut ppu) = setup(&[0xF0, 0x44, 0xEA, 0x00, 0xC1]);
+            memory.write_byte(LCDC, lcdc::LCD_ENABLE);
+            ppu.tick(&mut memory, 450);
+            let mut scheduler = Scheduler::new();
+            scheduler.set_profile(profile);
+
+            assert_eq!(scheduler.step(&mut cpu, &mut memory, &mut ppu), 12);
+            assert_eq!(memory.read_byte(LY), 1);
+            scheduler.step(&mut cpu, &mut memory, &mut ppu);
+            assert_eq!(memory.read_byte(0xC100), ly, "{profile:?}");
+        }
+    }
+
+    #[test]
+    fn test_double_speed() {
+        // LD A, $01; LDH ($4D), A; STOP; JR -2
+        let (mut cpu, mut memory, mut ppu) =
+            setup(&[0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00, 0x18, 0xFE]);
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0xC0;
+        memory.load_cartridge(crate::gameboy::Cartridge::new(rom).unwrap());
+        let mut scheduler = Scheduler::new();
+        for _ in 0..3 {
+            scheduler.step(&mut cpu, &mut memory, &mut ppu);
+        }
+        assert!(memory.double_speed());
+
+        // JR takes 3 machine cycles, 6 dots at double speed
+        assert_eq!(scheduler.step(&mut cpu, &mut memory, &mut ppu), 6);
+        // the timer runs at CPU speed, DIV counts twice as many dots
+        let elapsed = scheduler.run_cycles(&mut cpu, &mut memory, &mut ppu, 128);
+        assert_eq!(elapsed, 132);
+        assert_eq!(memory.read_byte(0xFF04), 1);
+    }
+
+    #[test]
+    fn test_hdma_pauses_cpu() {
+        // LD A, $00; LDH ($55), A
+        let (mut cpu, mut memory, mut ppu) = setup(&[0x3E, 0x00, 0xE0, 0x55]);
+        let mut rom = vec![0; 0x8000];
+        rom[0x0143] = 0xC0;
+        memory.load_cartridge(crate::gameboy::Cartridge::new(rom).unwrap());
+        let mut scheduler = Scheduler::new();
+        scheduler.step(&mut cpu, &mut memory, &mut ppu);
+
+        // a single block takes 8 machine cycles on top of the 3 of the LDH
+        assert_eq!(scheduler.step(&mut cpu, &mut memory, &mut ppu), 44);
+    }
+
+    #[test]
+    fn test_run_cycles_advances_timer() {
+        // JR -2
+        let (mut cpu, mut memory, mut ppu) = setup(&[0x18, 0xFE]);
+        let mut scheduler = Scheduler::new();
+
+        let elapsed = scheduler.run_cycles(&mut cpu, &mut memory, &mut ppu, 256);
+        assert_eq!(elapsed, 264);
+        assert_eq!(memory.read_byte(0xFF04), 1);
+    }
+
+    #[test]
+    fn test_run_until_vblank() {
+        let (mut cpu, mut memory, mut ppu) = setup(&[0x18, 0xFE]);
+        let mut scheduler = Scheduler::new();
+        memory.write_byte(0xFF40, 0x91);
+
+        scheduler.run_until_vblank(&mut cpu, &mut memory, &mut ppu);
+        assert_eq!(memory.read_byte(0xFF44), 144);
+    }
+
+    #[test]
+    fn test_run_until_vblank_lcd_off() {
+        let (mut cpu, mut memory, mut ppu) = setup(&[0x18, 0xFE]);
+        let mut scheduler = Scheduler::new();
+
+        let elapsed = scheduler.run_until_vblank(&mut cpu, &mut memory, &mut ppu);
+        assert_eq!(elapsed, T_CYCLES_PER_FRAME);
+    }
+}
@@ -0,0 +1,186 @@
+//! Finding and watching RAM values, the backend of a cheat finder.
+//!
+//! A `MemorySearch` starts with every RAM address as a candidate and narrows them down by
+//! comparing each candidate's value now to its value at the previous search, for example
+//! "decreased" after losing a life. A `WatchList` then reports when the addresses found change.
+//!
+//! Memory is read the way the CPU sees it, so only the mapped WRAM and cartridge RAM banks are
+//! searched and disabled cartridge RAM reads as 0xFF.
+
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use super::Memory;
+
+/// The RAM searched: cartridge RAM, WRAM and HRAM
+const SEARCHED: [RangeInclusive<u16>; 3] = [0xA000..=0xBFFF, 0xC000..=0xDFFF, 0xFF80..=0xFFFE];
+
+/// How a value has to compare to its previous value to stay a candidate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Increased,
+    Decreased,
+    /// The value is now exactly this, whatever it was before
+    Exact(u8),
+}
+
+impl Comparison {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            Comparison::Equal => current == previous,
+            Comparison::NotEqual => current != previous,
+            Comparison::Increased => current > previous,
+            Comparison::Decreased => current < previous,
+            Comparison::Exact(value) => current == value,
+        }
+    }
+}
+
+/// The addresses still matching every comparison so far, with their values at the last search
+pub struct MemorySearch {
+    candidates: Vec<(u16, u8)>,
+}
+
+impl MemorySearch {
+    /// Snapshot all of RAM, every address is a candidate
+    pub fn new(memory: &Memory) -> MemorySearch {
+        let candidates = SEARCHED
+            .iter()
+            .flat_map(|range| range.clone())
+            .map(|address| (address, memory.read_byte(address)))
+            .collect();
+        MemorySearch { candidates }
+    }
+
+    /// Keep the candidates whose value compares to the last snapshot as `comparison` says and
+    /// take a new snapshot of them, returns how many are left
+    pub fn filter(&mut self, memory: &Memory, comparison: Comparison) -> usize {
+        self.candidates.retain_mut(|(address, value)| {
+            let current = memory.read_byte(*address);
+            let keep = comparison.matches(*value, current);
+            *value = current;
+            keep
+        });
+        self.candidates.len()
+    }
+
+    /// The remaining addresses and their values at the last snapshot, in address order
+    pub fn candidates(&self) -> &[(u16, u8)] {
+        &self.candidates
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+/// A value in a `WatchList` that changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchChange {
+    pub address: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// Addresses to check for changes, usually once per frame
+#[derive(Default)]
+pub struct WatchList {
+    watches: Vec<(u16, u8)>, // address and the value seen last
+}
+
+impl WatchList {
+    pub fn new() -> WatchList {
+        WatchList::default()
+    }
+
+    /// Watch `address`, changes are reported from its current value on
+    pub fn add(&mut self, memory: &Memory, address: u16) {
+        if !self.contains(address) {
+            self.watches.push((address, memory.read_byte(address)));
+        }
+    }
+
+    pub fn remove(&mut self, address: u16) {
+        self.watches.retain(|(watched, _)| *watched != address);
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.watches.iter().any(|(watched, _)| *watched == address)
+    }
+
+    /// The watched addresses and their last seen values
+    pub fn watches(&self) -> &[(u16, u8)] {
+        &self.watches
+    }
+
+    /// Read every watched address, returns those that changed since the last update
+    pub fn update(&mut self, memory: &Memory) -> Vec<WatchChange> {
+        let mut changes = Vec::new();
+        for (address, value) in &mut self.watches {
+            let current = memory.read_byte(*address);
+            if current != *value {
+                changes.push(WatchChange {
+                    address: *address,
+                    old: *value,
+                    new: current,
+                });
+                *value = current;
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xC100, 3);
+        memory.write_byte(0xC200, 3);
+        let mut search = MemorySearch::new(&memory);
+        // cartridge RAM is not enabled, so it is searched but reads 0xFF
+        assert_eq!(search.len(), 0x2000 + 0x2000 + 0x7F);
+
+        memory.write_byte(0xC100, 2);
+        memory.write_byte(0xC200, 4);
+        assert_eq!(search.filter(&memory, Comparison::NotEqual), 2);
+        assert_eq!(search.filter(&memory, Comparison::Equal), 2);
+
+        memory.write_byte(0xC100, 1);
+        assert_eq!(search.filter(&memory, Comparison::Decreased), 1);
+        assert_eq!(search.candidates(), [(0xC100, 1)]);
+        assert_eq!(search.filter(&memory, Comparison::Exact(2)), 0);
+    }
+
+    #[test]
+    fn test_watch_list() {
+        let mut memory = Memory::new();
+        let mut watches = WatchList::new();
+        watches.add(&memory, 0xC000);
+        watches.add(&memory, 0xC000);
+        assert_eq!(watches.watches().len(), 1);
+        assert!(watches.update(&memory).is_empty());
+
+        memory.write_byte(0xC000, 9);
+        assert_eq!(
+            watches.update(&memory),
+            [WatchChange {
+                address: 0xC000,
+                old: 0,
+                new: 9
+            }]
+        );
+        assert!(watches.update(&memory).is_empty());
+        watches.remove(0xC000);
+        assert!(!watches.contains(0xC000));
+    }
+}
@@ -0,0 +1,382 @@
+//! The serial port, SB (0xFF01) and SC (0xFF02).
+//!
+//! Writing SC with bit 7 and the internal clock bit set starts a transfer, the byte in SB
+//! is shifted out one bit every 512 T-cycles (8192 Hz) while the received byte is shifted in.
+//! The other side of the link cable is a `SerialTransport`. When it clocks a transfer itself the
+//! port notices within `POLL_CYCLES`, answering with SB and finishing a transfer started with
+//! the external clock.
+
+use alloc::boxed::Box;
+use core::ops::RangeInclusive;
+#[cfg(feature = "std")]
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+const TRANSFER_START: u8 = 1 << 7;
+const INTERNAL_CLOCK: u8 = 1 << 0;
+const SC_UNUSED: u8 = 0b0111_1110;
+const CYCLES_PER_BIT: u32 = 512;
+/// How often an idle port asks the transport for a transfer clocked by the other side, a byte's time
+const POLL_CYCLES: u32 = CYCLES_PER_BIT * 8;
+
+/// The device on the other end of the link cable
+pub trait SerialTransport: Send {
+    /// Send a byte and return the byte received in exchange
+    fn exchange(&mut self, byte: u8) -> u8;
+
+    /// If the other side clocked a transfer, answer it with `byte` and return the byte it sent
+    ///
+    /// Called regularly while no transfer with the internal clock runs, devices that never
+    /// drive the clock have nothing to answer.
+    fn receive(&mut self, byte: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// No cable connected, every transfer receives 0xFF
+pub struct NullTransport;
+
+impl SerialTransport for NullTransport {
+    fn exchange(&mut self, _byte: u8) -> u8 {
+        0xFF
+    }
+}
+
+/// Prints every sent byte as a character, test ROMs report their results this way
+#[cfg(feature = "std")]
+pub struct StdoutTransport;
+
+#[cfg(feature = "std")]
+impl SerialTransport for StdoutTransport {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+        0xFF
+    }
+}
+
+/// Records every sent byte, clones share the same recording
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct CaptureTransport {
+    sent: Arc<Mutex<Vec<u8>>>,
+}
+
+#[cfg(feature = "std")]
+impl CaptureTransport {
+    pub fn new() -> CaptureTransport {
+        CaptureTransport::default()
+    }
+
+    /// The bytes sent so far
+    pub fn sent(&self) -> Vec<u8> {
+        self.sent.lock().unwrap().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl SerialTransport for CaptureTransport {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.sent.lock().unwrap().push(byte);
+        0xFF
+    }
+}
+
+/// One end of a link cable between two Game Boys in the same process, see `LocalLink::pair`
+///
+/// A transfer clocked by one side takes the other's SB as of its last poll, so the result only
+/// depends on the order the two are run in, which keeps it deterministic for netplay.
+#[cfg(feature = "std")]
+pub struct LocalLink {
+    side: usize,
+    cable: Arc<Mutex<LocalCable>>,
+}
+
+#[cfg(feature = "std")]
+struct LocalCable {
+    sb: [u8; 2],              // each side's SB when it was last polled
+    clocked: [Option<u8>; 2], // the byte the other side clocked to each side
+}
+
+#[cfg(feature = "std")]
+impl LocalLink {
+    /// Both ends of a new cable
+    pub fn pair() -> (LocalLink, LocalLink) {
+        let cable = Arc::new(Mutex::new(LocalCable {
+            sb: [0xFF; 2],
+            clocked: [None; 2],
+        }));
+        let end = |side| LocalLink {
+            side,
+            cable: cable.clone(),
+        };
+        (end(0), end(1))
+    }
+}
+
+#[cfg(feature = "std")]
+impl SerialTransport for LocalLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut cable = self.cable.lock().unwrap();
+        let other = 1 - self.side;
+        cable.clocked[other] = Some(byte);
+        cable.sb[other]
+    }
+
+    fn receive(&mut self, byte: u8) -> Option<u8> {
+        let mut cable = self.cable.lock().unwrap();
+        cable.sb[self.side] = byte;
+        cable.clocked[self.side].take()
+    }
+}
+
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+    incoming: u8,
+    bits_left: u8,
+    cycles: u32,
+    transport: Box<dyn SerialTransport>,
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            sb: 0,
+            sc: 0,
+            incoming: 0,
+            bits_left: 0,
+            cycles: 0,
+            transport: Box::new(NullTransport),
+        }
+    }
+
+    pub fn set_transport(&mut self, transport: Box<dyn SerialTransport>) {
+        self.transport = transport;
+    }
+
+    /// Advance a running transfer by a number of T-cycles
+    ///
+    /// Returns true if the transfer finished and the serial interrupt should be requested
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        if self.bits_left == 0 {
+            return self.poll(cycles);
+        }
+
+        self.cycles += cycles;
+        while self.cycles >= CYCLES_PER_BIT && self.bits_left > 0 {
+            self.cycles -= CYCLES_PER_BIT;
+            self.sb = (self.sb << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+        }
+
+        if self.bits_left == 0 {
+            self.sc &= !TRANSFER_START;
+            return true;
+        }
+        false
+    }
+
+    /// Answer a transfer clocked by the other side, true if it finished one waiting for the
+    /// external clock
+    fn poll(&mut self, cycles: u32) -> bool {
+        self.cycles += cycles;
+        if self.cycles < POLL_CYCLES {
+            return false;
+        }
+        self.cycles = 0;
+        let Some(byte) = self.transport.receive(self.sb) else {
+            return false;
+        };
+        if self.sc & (TRANSFER_START | INTERNAL_CLOCK) != TRANSFER_START {
+            return false;
+        }
+        self.sb = byte;
+        self.sc &= !TRANSFER_START;
+        true
+    }
+
+    fn start_transfer(&mut self) {
+        self.incoming = self.transport.exchange(self.sb);
+        self.bits_left = 8;
+        self.cycles = 0;
+    }
+}
+
+impl IoDevice for Serial {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[SB..=SC]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            SB => self.sb,
+            SC => self.sc | SC_UNUSED,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            SB => self.sb = value,
+            SC => {
+                self.sc = value & !SC_UNUSED;
+                // with an external clock the transfer waits for the other side, see `poll`
+                if value & (TRANSFER_START | INTERNAL_CLOCK) == TRANSFER_START | INTERNAL_CLOCK {
+                    self.start_transfer();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for Serial {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.sb);
+        state.write_u8(self.sc);
+        state.write_u8(self.incoming);
+        state.write_u8(self.bits_left);
+        state.write_u32(self.cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.sb = state.read_u8()?;
+        self.sc = state.read_u8()?;
+        self.incoming = state.read_u8()?;
+        self.bits_left = state.read_u8()?;
+        self.cycles = state.read_u32()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Arc<Mutex<Vec<u8>>>);
+
+    impl SerialTransport for Recorder {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            self.0.lock().unwrap().push(byte);
+            0x5A
+        }
+    }
+
+    #[test]
+    fn test_transfer() {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut serial = Serial::new();
+        serial.set_transport(Box::new(Recorder(sent.clone())));
+
+        serial.write(SB, 0x42);
+        serial.write(SC, 0x81);
+        assert_eq!(*sent.lock().unwrap(), vec![0x42]);
+
+        assert!(!serial.tick(CYCLES_PER_BIT * 8 - 1));
+        assert_eq!(serial.read(SC), 0xFF);
+        assert!(serial.tick(1));
+        assert_eq!(serial.read(SB), 0x5A);
+        assert_eq!(serial.read(SC), 0x7F);
+    }
+
+    #[test]
+    fn test_capture() {
+        let capture = CaptureTransport::new();
+        let mut serial = Serial::new();
+        serial.set_transport(Box::new(capture.clone()));
+
+        serial.write(SB, b'O');
+        serial.write(SC, 0x81);
+        serial.tick(CYCLES_PER_BIT * 8);
+        serial.write(SB, b'K');
+        serial.write(SC, 0x81);
+
+        assert_eq!(capture.sent(), b"OK");
+        assert_eq!(serial.read(SB), b'K');
+    }
+
+    #[test]
+    fn test_bits_shift() {
+        let mut serial = Serial::new();
+        serial.write(SB, 0x00);
+        serial.write(SC, 0x81);
+
+        serial.tick(CYCLES_PER_BIT * 4);
+        assert_eq!(serial.read(SB), 0x0F);
+    }
+
+    #[test]
+    fn test_external_clock() {
+        let mut serial = Serial::new();
+        serial.write(SB, 0x42);
+        serial.write(SC, 0x80);
+
+        assert!(!serial.tick(CYCLES_PER_BIT * 16));
+        assert_eq!(serial.read(SB), 0x42);
+        assert_eq!(serial.read(SC), 0xFE);
+    }
+
+    /// Clocks a transfer with 0x99 the first time it is asked
+    struct Master(Option<u8>);
+
+    impl SerialTransport for Master {
+        fn exchange(&mut self, _byte: u8) -> u8 {
+            0xFF
+        }
+
+        fn receive(&mut self, byte: u8) -> Option<u8> {
+            let sent = self.0.take()?;
+            assert_eq!(byte, 0x42);
+            Some(sent)
+        }
+    }
+
+    #[test]
+    fn test_clocked_by_other_side() {
+        let mut serial = Serial::new();
+        serial.set_transport(Box::new(Master(Some(0x99))));
+        serial.write(SB, 0x42);
+        serial.write(SC, 0x80);
+
+        assert!(!serial.tick(POLL_CYCLES - 1));
+        assert!(serial.tick(1));
+        assert_eq!(serial.read(SB), 0x99);
+        assert_eq!(serial.read(SC), 0x7E);
+        assert!(!serial.tick(POLL_CYCLES * 2));
+    }
+
+    #[test]
+    fn test_local_link() {
+        let (master, slave) = LocalLink::pair();
+        let mut master_port = Serial::new();
+        master_port.set_transport(Box::new(master));
+        let mut slave_port = Serial::new();
+        slave_port.set_transport(Box::new(slave));
+
+        slave_port.write(SB, 0x22);
+        slave_port.write(SC, 0x80);
+        slave_port.tick(POLL_CYCLES);
+        master_port.write(SB, 0x11);
+        master_port.write(SC, 0x81);
+        assert!(master_port.tick(CYCLES_PER_BIT * 8));
+        assert_eq!(master_port.read(SB), 0x22);
+        assert!(slave_port.tick(POLL_CYCLES));
+        assert_eq!(slave_port.read(SB), 0x11);
+    }
+}
@@ -0,0 +1,530 @@
+//! The Super Game Boy, which colors the screen and draws a border around it on command.
+//!
+//! Commands come in over the joypad port (see `SgbPort`) and are carried out at the end of each
+//! frame. Data too big for packets is shown on screen instead, the frame after a `*_TRN` command
+//! is read back as 4KB of tile data: the first 256 tiles, 20 per row.
+//!
+//! PAL01, PAL23, PAL03, PAL12, ATTR_BLK, PAL_SET, PAL_TRN, CHR_TRN, PCT_TRN, MASK_EN and MLT_REQ
+//! are understood, other commands are ignored.
+
+mod port;
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    gameboy::{
+        ppu::{rgb555_to_rgba, DmgPalette, Frame, SCREEN_HEIGHT, SCREEN_WIDTH},
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+#[cfg(test)]
+pub use port::packet_writes;
+pub use port::SgbPort;
+
+pub const BORDER_WIDTH: usize = 256;
+pub const BORDER_HEIGHT: usize = 224;
+/// Where the screen is within the border
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+const PIXELS: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+const TILES_X: usize = SCREEN_WIDTH / 8;
+const TILES_Y: usize = SCREEN_HEIGHT / 8;
+
+const TRANSFER_SIZE: usize = 0x1000;
+/// 512 palettes of 4 colors
+const SYSTEM_PALETTES: usize = 512;
+/// 256 tiles of 4 bits per pixel
+const BORDER_TILE_SIZE: usize = 32;
+const BORDER_TILES_SIZE: usize = 256 * BORDER_TILE_SIZE;
+/// 32x32 map entries followed by the border palettes 4-7 of 16 colors
+const BORDER_MAP_SIZE: usize = 0x800;
+const BORDER_PALETTES: usize = BORDER_MAP_SIZE;
+const BORDER_DATA_SIZE: usize = BORDER_MAP_SIZE + 4 * 16 * 2;
+
+const PAL01: u8 = 0x00;
+const PAL23: u8 = 0x01;
+const PAL03: u8 = 0x02;
+const PAL12: u8 = 0x03;
+const ATTR_BLK: u8 = 0x04;
+const PAL_SET: u8 = 0x0A;
+const PAL_TRN: u8 = 0x0B;
+const MLT_REQ: u8 = 0x11;
+const CHR_TRN: u8 = 0x13;
+const PCT_TRN: u8 = 0x14;
+const MASK_EN: u8 = 0x17;
+
+/// Data to read from the next frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transfer {
+    SystemPalettes,
+    BorderTiles { first: usize },
+    BorderMap,
+}
+
+/// What MASK_EN shows instead of the game
+enum Mask {
+    Off,
+    Freeze(Box<[u8; PIXELS]>),
+    Black,
+    Backdrop,
+}
+
+pub struct Sgb {
+    palettes: [[u16; 4]; 4], // color 0 of palette 0 is shared by all of them
+    attributes: [u8; TILES_X * TILES_Y], // the palette of each tile on screen
+    system_palettes: Vec<u8>,
+    border_tiles: Vec<u8>,
+    border_data: Vec<u8>,
+    colored: bool, // a palette has been sent, until then the DMG palette is used
+    has_border: bool,
+    mask: Mask,
+    transfer: Option<Transfer>,
+}
+
+impl Sgb {
+    pub fn new() -> Sgb {
+        Sgb {
+            palettes: [[0; 4]; 4],
+            attributes: [0; TILES_X * TILES_Y],
+            system_palettes: vec![0; SYSTEM_PALETTES * 8],
+            border_tiles: vec![0; BORDER_TILES_SIZE],
+            border_data: vec![0; BORDER_DATA_SIZE],
+            colored: false,
+            has_border: false,
+            mask: Mask::Off,
+            transfer: None,
+        }
+    }
+
+    /// Carry out a command, `screen` is the frame just rendered
+    pub fn command(&mut self, command: &[u8], screen: &[u8; PIXELS]) {
+        let data = &command[1..];
+        match command[0] >> 3 {
+            PAL01 => self.set_palettes(0, 1, data),
+            PAL23 => self.set_palettes(2, 3, data),
+            PAL03 => self.set_palettes(0, 3, data),
+            PAL12 => self.set_palettes(1, 2, data),
+            ATTR_BLK => self.set_attribute_blocks(data),
+            PAL_SET => self.set_system_palettes(data),
+            PAL_TRN => self.transfer = Some(Transfer::SystemPalettes),
+            CHR_TRN => {
+                let first = usize::from(data[0] & 0x01) * 128;
+                self.transfer = Some(Transfer::BorderTiles { first });
+            }
+            PCT_TRN => self.transfer = Some(Transfer::BorderMap),
+            MASK_EN => {
+                self.mask = match data[0] & 0x03 {
+                    1 => Mask::Freeze(Box::new(*screen)),
+                    2 => Mask::Black,
+                    3 => Mask::Backdrop,
+                    _ => Mask::Off,
+                }
+            }
+            MLT_REQ => {} // taken care of by the joypad port
+            command => tracing::debug!(target: "ppu", command, "unsupported SGB command"),
+        }
+    }
+
+    /// Read the data of a transfer requested during the frame before `screen`
+    pub fn end_frame(&mut self, screen: &[u8; PIXELS]) {
+        let Some(transfer) = self.transfer.take() else {
+            return;
+        };
+        let data = transfer_data(screen);
+        match transfer {
+            Transfer::SystemPalettes => self.system_palettes.copy_from_slice(&data),
+            Transfer::BorderTiles { first } => {
+                let start = first * BORDER_TILE_SIZE;
+                self.border_tiles[start..start + TRANSFER_SIZE].copy_from_slice(&data);
+            }
+            Transfer::BorderMap => {
+                self.border_data.copy_from_slice(&data[..BORDER_DATA_SIZE]);
+                self.has_border = true;
+            }
+        }
+    }
+
+    /// Color 0 of every palette, then colors 1-3 of palettes `first` and `second`
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+        let color = |index: usize| read_color(data, index);
+        self.palettes[0][0] = color(0);
+        for shade in 1..4 {
+            self.palettes[first][shade] = color(shade);
+            self.palettes[second][shade] = color(shade + 3);
+        }
+        self.colored = true;
+    }
+
+    /// Copy four of the palettes sent with PAL_TRN
+    fn set_system_palettes(&mut self, data: &[u8]) {
+        for (palette, number) in self.palettes.iter_mut().zip(data.chunks_exact(2)) {
+            let number = usize::from(u16::from_le_bytes([number[0], number[1]])) % SYSTEM_PALETTES;
+            for (shade, color) in palette.iter_mut().enumerate() {
+                *color = read_color(&self.system_palettes, number * 4 + shade);
+            }
+        }
+        if data[8] & 0x40 != 0 {
+            self.mask = Mask::Off;
+        }
+        self.colored = true;
+    }
+
+    /// Give rectangles of tiles a palette, inside, on and outside their edges
+    fn set_attribute_blocks(&mut self, data: &[u8]) {
+        let count = usize::from(data[0] & 0x1F);
+        for block in data[1..].chunks_exact(6).take(count) {
+            let control = block[0] & 0x07;
+            let inside = block[1] & 0x03;
+            let outside = (block[1] >> 4) & 0x03;
+            // changing only one side also changes the edge
+            let edge = match control {
+                0b001 => Some(inside),
+                0b100 => Some(outside),
+                _ => (control & 0b010 != 0).then_some((block[1] >> 2) & 0x03),
+            };
+            let (left, top) = (usize::from(block[2] & 0x1F), usize::from(block[3] & 0x1F));
+            let (right, bottom) = (usize::from(block[4] & 0x1F), usize::from(block[5] & 0x1F));
+
+            for y in 0..TILES_Y {
+                for x in 0..TILES_X {
+                    let within = (left..=right).contains(&x) && (top..=bottom).contains(&y);
+                    let on_edge = within && (x == left || x == right || y == top || y == bottom);
+                    let palette = if !within {
+                        (control & 0b100 != 0).then_some(outside)
+                    } else if on_edge {
+                        edge
+                    } else {
+                        (control & 0b001 != 0).then_some(inside)
+                    };
+                    if let Some(palette) = palette {
+                        self.attributes[y * TILES_X + x] = palette;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The screen in the SGB palettes, or in `palette` until the game sent one
+    pub fn screen(&self, shades: &[u8; PIXELS], palette: &DmgPalette) -> Frame {
+        let shades = match &self.mask {
+            Mask::Freeze(frozen) => frozen,
+            _ => shades,
+        };
+        if !self.colored {
+            return match self.mask {
+                Mask::Black => Frame::from_shades(&[3; PIXELS], palette),
+                Mask::Backdrop => Frame::from_shades(&[0; PIXELS], palette),
+                _ => Frame::from_shades(shades, palette),
+            };
+        }
+
+        let mut colors = [0; PIXELS];
+        for (index, color) in colors.iter_mut().enumerate() {
+            *color = match self.mask {
+                Mask::Black => 0,
+                Mask::Backdrop => self.palettes[0][0],
+                _ => self.color(index % SCREEN_WIDTH, index / SCREEN_WIDTH, shades[index]),
+            };
+        }
+        Frame::from_rgb555(&colors)
+    }
+
+    fn color(&self, x: usize, y: usize, shade: u8) -> u16 {
+        if shade == 0 {
+            return self.palettes[0][0];
+        }
+        let palette = self.attributes[y / 8 * TILES_X + x / 8];
+        self.palettes[usize::from(palette)][usize::from(shade & 0x03)]
+    }
+
+    /// `screen` within the border, `None` until the game sent one
+    pub fn border(&self, screen: &Frame) -> Option<Frame> {
+        if !self.has_border {
+            return None;
+        }
+
+        let mut frame = Frame::blank(BORDER_WIDTH, BORDER_HEIGHT);
+        for y in 0..BORDER_HEIGHT {
+            for x in 0..BORDER_WIDTH {
+                let (screen_x, screen_y) = (x.wrapping_sub(SCREEN_X), y.wrapping_sub(SCREEN_Y));
+                let color = match self.border_color(x, y) {
+                    Some(color) => rgb555_to_rgba(color),
+                    None if screen_x < screen.width() && screen_y < screen.height() => {
+                        screen.pixel(screen_x, screen_y)
+                    }
+                    None => rgb555_to_rgba(self.palettes[0][0]),
+                };
+                frame.set_pixel(x, y, color);
+            }
+        }
+        Some(frame)
+    }
+
+    /// The border's color at `x`, `y`, `None` where it is transparent
+    fn border_color(&self, x: usize, y: usize) -> Option<u16> {
+        let entry = (y / 8 * 32 + x / 8) * 2;
+        let entry = u16::from_le_bytes([self.border_data[entry], self.border_data[entry + 1]]);
+        let tile = usize::from(entry & 0xFF) * BORDER_TILE_SIZE;
+        let column = if entry & 0x4000 != 0 {
+            7 - x % 8
+        } else {
+            x % 8
+        };
+        let row = if entry & 0x8000 != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+
+        // SNES tiles keep bit planes 0 and 1 in the first half, 2 and 3 in the second
+        let planes = [
+            self.border_tiles[tile + row * 2],
+            self.border_tiles[tile + row * 2 + 1],
+            self.border_tiles[tile + 16 + row * 2],
+            self.border_tiles[tile + 16 + row * 2 + 1],
+        ];
+        let index = planes.iter().enumerate().fold(0, |index, (plane, bits)| {
+            index | ((bits >> (7 - column)) & 1) << plane
+        });
+        if index == 0 {
+            return None;
+        }
+        let palette = usize::from((entry >> 10) & 0x03);
+        Some(read_color(
+            &self.border_data[BORDER_PALETTES..],
+            palette * 16 + usize::from(index),
+        ))
+    }
+}
+
+impl Default for Sgb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The little endian RGB555 color number `index` of `data`
+fn read_color(data: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([data[index * 2], data[index * 2 + 1]]) & 0x7FFF
+}
+
+/// The 2 bit per pixel tile data showing on screen, as the SGB reads it for transfers
+fn transfer_data(screen: &[u8; PIXELS]) -> Vec<u8> {
+    let mut data = vec![0; TRANSFER_SIZE];
+    for (tile, bytes) in data.chunks_exact_mut(16).enumerate() {
+        let (left, top) = (tile % TILES_X * 8, tile / TILES_X * 8);
+        for row in 0..8 {
+            for column in 0..8 {
+                let shade = screen[(top + row) * SCREEN_WIDTH + left + column];
+                bytes[row * 2] |= (shade & 1) << (7 - column);
+                bytes[row * 2 + 1] |= ((shade >> 1) & 1) << (7 - column);
+            }
+        }
+    }
+    data
+}
+
+impl SaveState for Sgb {
+    fn save_state(&self, state: &mut StateWriter) {
+        for palette in &self.palettes {
+            for color in palette {
+                state.write_u16(*color);
+            }
+        }
+        state.write_bytes(&self.attributes);
+        state.write_bytes(&self.system_palettes);
+        state.write_bytes(&self.border_tiles);
+        state.write_bytes(&self.border_data);
+        state.write_bool(self.colored);
+        state.write_bool(self.has_border);
+        match &self.mask {
+            Mask::Off => state.write_u8(0),
+            Mask::Freeze(frozen) => {
+                state.write_u8(1);
+                state.write_bytes(&frozen[..]);
+            }
+            Mask::Black => state.write_u8(2),
+            Mask::Backdrop => state.write_u8(3),
+        }
+        let transfer = match self.transfer {
+            None => 0,
+            Some(Transfer::SystemPalettes) => 1,
+            Some(Transfer::BorderTiles { first }) => 2 + (first / 128) as u8,
+            Some(Transfer::BorderMap) => 4,
+        };
+        state.write_u8(transfer);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        for palette in &mut self.palettes {
+            for color in palette {
+                *color = state.read_u16()?;
+            }
+        }
+        state.read_into(&mut self.attributes)?;
+        state.read_into(&mut self.system_palettes)?;
+        state.read_into(&mut self.border_tiles)?;
+        state.read_into(&mut self.border_data)?;
+        self.colored = state.read_bool()?;
+        self.has_border = state.read_bool()?;
+        self.mask = match state.read_u8()? {
+            0 => Mask::Off,
+            1 => {
+                let mut frozen = Box::new([0; PIXELS]);
+                state.read_into(&mut frozen[..])?;
+                Mask::Freeze(frozen)
+            }
+            2 => Mask::Black,
+            3 => Mask::Backdrop,
+            _ => return Err(SaveStateError::InvalidData("SGB mask")),
+        };
+        self.transfer = match state.read_u8()? {
+            0 => None,
+            1 => Some(Transfer::SystemPalettes),
+            2 => Some(Transfer::BorderTiles { first: 0 }),
+            3 => Some(Transfer::BorderTiles { first: 128 }),
+            4 => Some(Transfer::BorderMap),
+            _ => return Err(SaveStateError::InvalidData("SGB transfer")),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static BLANK: [u8; PIXELS] = [0; PIXELS];
+
+    /// A command of one packet
+    fn packet(command: u8, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0; port::PACKET_SIZE];
+        packet[0] = command << 3 | 1;
+        packet[1..=data.len()].copy_from_slice(data);
+        packet
+    }
+
+    /// A screen showing `data` as tiles, to be read back by a transfer
+    fn show(data: &[u8]) -> [u8; PIXELS] {
+        let mut screen = [0; PIXELS];
+        for (tile, bytes) in data.chunks(16).enumerate() {
+            let (left, top) = (tile % TILES_X * 8, tile / TILES_X * 8);
+            for (row, planes) in bytes.chunks(2).enumerate() {
+                for column in 0..8 {
+                    let bit = |byte: u8| (byte >> (7 - column)) & 1;
+                    screen[(top + row) * SCREEN_WIDTH + left + column] =
+                        bit(planes[0]) | bit(planes[1]) << 1;
+                }
+            }
+        }
+        screen
+    }
+
+    #[test]
+    fn test_pal01() {
+        let mut sgb = Sgb::new();
+        let mut shades = [1; PIXELS];
+        shades[0] = 0;
+        assert_eq!(
+            sgb.screen(&shades, &DmgPalette::GRAYSCALE).pixel(1, 0),
+            DmgPalette::GRAYSCALE.color(1)
+        );
+
+        // color 0 blue, palette 0 color 1 red, palette 1 color 1 green
+        let colors = [0x7C00u16, 0x001F, 0, 0, 0x03E0, 0, 0];
+        let data: Vec<u8> = colors
+            .iter()
+            .flat_map(|color| color.to_le_bytes())
+            .collect();
+        sgb.command(&packet(PAL01, &data), &BLANK);
+
+        let frame = sgb.screen(&shades, &DmgPalette::GRAYSCALE);
+        assert_eq!(frame.pixel(0, 0), [0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(frame.pixel(1, 0), [0xFF, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_attr_blk() {
+        let mut sgb = Sgb::new();
+        // inside palette 1, edge palette 2, outside palette 3, tiles 2,2 to 5,4
+        sgb.command(
+            &packet(ATTR_BLK, &[1, 0b111, 0b11_10_01, 2, 2, 5, 4]),
+            &BLANK,
+        );
+        assert_eq!(sgb.attributes[0], 3);
+        assert_eq!(sgb.attributes[2 * TILES_X + 2], 2);
+        assert_eq!(sgb.attributes[3 * TILES_X + 3], 1);
+        assert_eq!(sgb.attributes[4 * TILES_X + 5], 2);
+        assert_eq!(sgb.attributes[4 * TILES_X + 6], 3);
+
+        // only inside also changes the edge
+        sgb.command(&packet(ATTR_BLK, &[1, 0b001, 0b00, 0, 0, 1, 1]), &BLANK);
+        assert_eq!(sgb.attributes[0], 0);
+        assert_eq!(sgb.attributes[TILES_X + 1], 0);
+        assert_eq!(sgb.attributes[2 * TILES_X + 2], 2);
+    }
+
+    #[test]
+    fn test_pal_trn_and_pal_set() {
+        let mut sgb = Sgb::new();
+        sgb.command(&packet(PAL_TRN, &[]), &BLANK);
+        let mut palettes = vec![0; TRANSFER_SIZE];
+        palettes[5 * 8 + 2..5 * 8 + 4].copy_from_slice(&0x1234u16.to_le_bytes());
+        sgb.end_frame(&show(&palettes));
+        assert!(!sgb.colored);
+
+        sgb.command(&packet(PAL_SET, &[0, 0, 5, 0, 0, 0, 0, 0, 0]), &BLANK);
+        assert_eq!(sgb.palettes[1][1], 0x1234);
+        assert!(sgb.colored);
+    }
+
+    #[test]
+    fn test_border() {
+        let mut sgb = Sgb::new();
+        let screen = Frame::from_shades(&[3; PIXELS], &DmgPalette::GRAYSCALE);
+        assert!(sgb.border(&screen).is_none());
+
+        // tile 1 is color 1 at its top left pixel, mapped to the top left corner with palette 4
+        sgb.command(&packet(CHR_TRN, &[0]), &BLANK);
+        let mut tiles = vec![0; TRANSFER_SIZE];
+        tiles[BORDER_TILE_SIZE] = 0x80;
+        sgb.end_frame(&show(&tiles));
+        sgb.command(&packet(PCT_TRN, &[]), &BLANK);
+        let mut map = vec![0; TRANSFER_SIZE];
+        map[0..2].copy_from_slice(&(1u16 | 4 << 10).to_le_bytes());
+        map[BORDER_PALETTES + 2..BORDER_PALETTES + 4].copy_from_slice(&0x001Fu16.to_le_bytes());
+        sgb.end_frame(&show(&map));
+
+        let frame = sgb.border(&screen).unwrap();
+        assert_eq!(
+            (frame.width(), frame.height()),
+            (BORDER_WIDTH, BORDER_HEIGHT)
+        );
+        assert_eq!(frame.pixel(0, 0), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(frame.pixel(1, 0), rgb555_to_rgba(0));
+        assert_eq!(
+            frame.pixel(SCREEN_X, SCREEN_Y),
+            DmgPalette::GRAYSCALE.color(3)
+        );
+    }
+
+    #[test]
+    fn test_mask() {
+        let mut sgb = Sgb::new();
+        let shown = [1; PIXELS];
+        sgb.command(&packet(MASK_EN, &[1]), &shown);
+        let frame = sgb.screen(&BLANK, &DmgPalette::GRAYSCALE);
+        assert_eq!(frame.pixel(0, 0), DmgPalette::GRAYSCALE.color(1));
+
+        sgb.command(&packet(MASK_EN, &[2]), &BLANK);
+        let frame = sgb.screen(&BLANK, &DmgPalette::GRAYSCALE);
+        assert_eq!(frame.pixel(0, 0), DmgPalette::GRAYSCALE.color(3));
+
+        sgb.command(&packet(MASK_EN, &[0]), &BLANK);
+        let frame = sgb.screen(&BLANK, &DmgPalette::GRAYSCALE);
+        assert_eq!(frame.pixel(0, 0), DmgPalette::GRAYSCALE.color(0));
+    }
+}
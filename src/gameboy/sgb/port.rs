@@ -0,0 +1,237 @@
+//! The Super Game Boy's end of the joypad port.
+//!
+//! Games send command packets over P14 and P15: both low resets, then each of the 128 bits is a
+//! pulse from both high, P14 low for a 0 and P15 low for a 1, with a 0 as stop bit. The low 3
+//! bits of a command's first byte give its number of packets.
+//!
+//! After MLT_REQ the SGB reads up to four joypads, with both lines high P1 shows which one is
+//! next, 0xF for the first, and raising P15 moves on to the next.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::{
+    gameboy::save_state::{SaveState, StateReader, StateWriter},
+    utils::SaveStateError,
+};
+
+pub const PACKET_SIZE: usize = 16;
+const PACKET_BITS: usize = PACKET_SIZE * 8;
+
+const MLT_REQ: u8 = 0x11;
+
+const P14: u8 = 1 << 4;
+const P15: u8 = 1 << 5;
+/// Both lines high, between pulses
+const IDLE: u8 = P14 | P15;
+/// A pulse on P14 sends a 0
+const ZERO: u8 = P15;
+/// A pulse on P15 sends a 1
+const ONE: u8 = P14;
+
+pub struct SgbPort {
+    select: u8,         // the P14 and P15 bits last written
+    bit: Option<usize>, // the next bit of the packet being received, None between packets
+    packet: [u8; PACKET_SIZE],
+    command: Vec<u8>,       // the packets received of the current command
+    commands: Vec<Vec<u8>>, // complete commands waiting for `take_commands`
+    players: u8,
+    player: u8,
+}
+
+impl SgbPort {
+    pub fn new() -> SgbPort {
+        SgbPort {
+            select: IDLE,
+            bit: None,
+            packet: [0; PACKET_SIZE],
+            command: Vec::new(),
+            commands: Vec::new(),
+            players: 1,
+            player: 0,
+        }
+    }
+
+    /// Follow a write of the P14 and P15 bits of P1
+    pub fn write(&mut self, select: u8) {
+        let previous = mem::replace(&mut self.select, select);
+        match select {
+            0x00 => {
+                self.bit = Some(0);
+                self.packet = [0; PACKET_SIZE];
+            }
+            IDLE if previous & P15 == 0 => self.player = (self.player + 1) % self.players,
+            IDLE => {}
+            _ if previous == IDLE => {
+                if let Some(bit) = self.bit {
+                    self.receive(bit, select == ONE);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn receive(&mut self, bit: usize, one: bool) {
+        if bit == PACKET_BITS {
+            // a packet not ended by a 0 is dropped
+            self.bit = None;
+            if !one {
+                self.end_packet();
+            }
+            return;
+        }
+        if one {
+            self.packet[bit / 8] |= 1 << (bit % 8);
+        }
+        self.bit = Some(bit + 1);
+    }
+
+    fn end_packet(&mut self) {
+        self.command.extend_from_slice(&self.packet);
+        let packets = usize::from(self.command[0] & 0x07).max(1);
+        if self.command.len() < packets * PACKET_SIZE {
+            return;
+        }
+
+        let command = mem::take(&mut self.command);
+        if command[0] >> 3 == MLT_REQ {
+            self.players = match command[1] & 0x03 {
+                1 => 2,
+                3 => 4,
+                _ => 1,
+            };
+            self.player = 0;
+        }
+        self.commands.push(command);
+    }
+
+    /// The commands received since the last call, each the bytes of all its packets
+    pub fn take_commands(&mut self) -> Vec<Vec<u8>> {
+        mem::take(&mut self.commands)
+    }
+
+    /// The number of joypads read, set by MLT_REQ
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    /// The joypad read next, 0 is the Game Boy's own
+    pub fn player(&self) -> u8 {
+        self.player
+    }
+}
+
+impl Default for SgbPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SaveState for SgbPort {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.select);
+        state.write_u8(self.bit.map_or(0xFF, |bit| bit as u8));
+        state.write_bytes(&self.packet);
+        state.write_bytes(&self.command);
+        state.write_u8(self.players);
+        state.write_u8(self.player);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.select = state.read_u8()?;
+        self.bit = match state.read_u8()? {
+            0xFF => None,
+            bit if usize::from(bit) <= PACKET_BITS => Some(usize::from(bit)),
+            _ => return Err(SaveStateError::InvalidData("SGB packet bit")),
+        };
+        state.read_into(&mut self.packet)?;
+        self.command = state.read_bytes()?.to_vec();
+        self.players = state.read_u8()?.max(1);
+        self.player = state.read_u8()? % self.players;
+        self.commands.clear();
+        Ok(())
+    }
+}
+
+/// The P1 writes sending `bytes` as packets, for tests
+#[cfg(test)]
+pub fn packet_writes(bytes: &[u8]) -> Vec<u8> {
+    let mut writes = Vec::new();
+    for packet in bytes.chunks(PACKET_SIZE) {
+        writes.extend([0x00, IDLE]);
+        for bit in 0..PACKET_BITS {
+            let one = packet
+                .get(bit / 8)
+                .is_some_and(|byte| byte & 1 << (bit % 8) != 0);
+            writes.extend([if one { ONE } else { ZERO }, IDLE]);
+        }
+        writes.extend([ZERO, IDLE]);
+    }
+    writes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(port: &mut SgbPort, bytes: &[u8]) {
+        for select in packet_writes(bytes) {
+            port.write(select);
+        }
+    }
+
+    #[test]
+    fn test_packet() {
+        let mut port = SgbPort::new();
+        let mut packet = [0; PACKET_SIZE];
+        packet[0] = 0x01; // PAL01, one packet
+        packet[1] = 0xA5;
+        packet[15] = 0x80;
+        send(&mut port, &packet);
+
+        assert_eq!(port.take_commands(), vec![packet.to_vec()]);
+        assert!(port.take_commands().is_empty());
+    }
+
+    #[test]
+    fn test_multiple_packets() {
+        let mut port = SgbPort::new();
+        let mut command = [0; PACKET_SIZE * 2];
+        command[0] = 0x22; // ATTR_BLK, two packets
+        command[PACKET_SIZE] = 0x42;
+
+        send(&mut port, &command[..PACKET_SIZE]);
+        assert!(port.take_commands().is_empty());
+        send(&mut port, &command[PACKET_SIZE..]);
+        assert_eq!(port.take_commands(), vec![command.to_vec()]);
+    }
+
+    #[test]
+    fn test_missing_stop_bit() {
+        let mut port = SgbPort::new();
+        let mut writes = packet_writes(&[0x01]);
+        let stop = writes.len() - 2;
+        writes[stop] = ONE;
+        for select in writes {
+            port.write(select);
+        }
+        assert!(port.take_commands().is_empty());
+    }
+
+    #[test]
+    fn test_mlt_req() {
+        let mut port = SgbPort::new();
+        send(&mut port, &[MLT_REQ << 3 | 1, 0x01]);
+        assert_eq!((port.players(), port.player()), (2, 0));
+
+        port.write(ZERO);
+        port.write(IDLE);
+        assert_eq!(port.player(), 0);
+        port.write(ONE);
+        port.write(IDLE);
+        assert_eq!(port.player(), 1);
+        port.write(ONE);
+        port.write(IDLE);
+        assert_eq!(port.player(), 0);
+    }
+}
@@ -0,0 +1,159 @@
+//! The CGB speed switch, KEY1 at 0xFF4D.
+//!
+//! Writing bit 0 of KEY1 arms a switch which the next STOP performs, toggling the CPU
+//! between normal and double speed. Bit 7 reads the current speed. In double speed the CPU,
+//! timer, serial port and OAM DMA run twice as fast while the PPU and APU keep their pace.
+//! KEY1 only exists in CGB mode, on a DMG it reads 0xFF.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+pub const KEY1: u16 = 0xFF4D;
+
+const KEY1_DOUBLE_SPEED: u8 = 1 << 7;
+const KEY1_ARMED: u8 = 1 << 0;
+const KEY1_UNUSED: u8 = 0b0111_1110;
+
+pub struct Speed {
+    cgb_mode: bool,
+    double_speed: bool,
+    armed: bool,
+}
+
+impl Speed {
+    pub fn new() -> Speed {
+        Speed {
+            cgb_mode: false,
+            double_speed: false,
+            armed: false,
+        }
+    }
+
+    /// Enable or disable the CGB features, leaving CGB mode returns to normal speed
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        if !cgb_mode {
+            self.double_speed = false;
+            self.armed = false;
+        }
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Perform an armed speed switch, called by STOP
+    ///
+    /// Returns true if the speed changed
+    pub fn switch(&mut self) -> bool {
+        if !self.armed {
+            return false;
+        }
+        self.armed = false;
+        self.double_speed = !self.double_speed;
+        true
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IoDevice for Speed {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[KEY1..=KEY1]
+    }
+
+    fn read(&self, _address: u16) -> u8 {
+        if !self.cgb_mode {
+            return 0xFF;
+        }
+
+        let mut value = KEY1_UNUSED;
+        if self.double_speed {
+            value |= KEY1_DOUBLE_SPEED;
+        }
+        if self.armed {
+            value |= KEY1_ARMED;
+        }
+        value
+    }
+
+    fn write(&mut self, _address: u16, value: u8) {
+        if self.cgb_mode {
+            self.armed = value & KEY1_ARMED != 0;
+        }
+    }
+}
+
+impl SaveState for Speed {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.cgb_mode);
+        state.write_bool(self.double_speed);
+        state.write_bool(self.armed);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.cgb_mode = state.read_bool()?;
+        self.double_speed = state.read_bool()?;
+        self.armed = state.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dmg_mode() {
+        let mut speed = Speed::new();
+        speed.write(KEY1, KEY1_ARMED);
+
+        assert_eq!(speed.read(KEY1), 0xFF);
+        assert!(!speed.switch());
+        assert!(!speed.double_speed());
+    }
+
+    #[test]
+    fn test_switch() {
+        let mut speed = Speed::new();
+        speed.set_cgb_mode(true);
+        assert_eq!(speed.read(KEY1), 0x7E);
+        assert!(!speed.switch());
+
+        speed.write(KEY1, 0x01);
+        assert_eq!(speed.read(KEY1), 0x7F);
+        assert!(speed.switch());
+        assert!(speed.double_speed());
+        assert_eq!(speed.read(KEY1), 0xFE);
+
+        speed.write(KEY1, 0x01);
+        assert!(speed.switch());
+        assert!(!speed.double_speed());
+    }
+
+    #[test]
+    fn test_leave_cgb_mode() {
+        let mut speed = Speed::new();
+        speed.set_cgb_mode(true);
+        speed.write(KEY1, 0x01);
+        speed.switch();
+
+        speed.set_cgb_mode(false);
+        assert!(!speed.double_speed());
+    }
+}
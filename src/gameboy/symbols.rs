@@ -0,0 +1,154 @@
+//! Debug symbols from RGBDS `.sym` files, naming addresses for the disassembler, the trace log
+//! and the debugger.
+//!
+//! Every line is `BANK:ADDRESS Label` in hexadecimal, `01:4000 Main.loop`, anything after a `;`
+//! is a comment and `[section]` headers, as written by other linkers, are skipped. The bank is
+//! the ROM bank at 0x0000-0x7FFF, the VRAM bank or the WRAM bank at 0xD000-0xDFFF and 0
+//! everywhere else, as `MemoryBus::bank` tells them.
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::utils::SymbolError;
+
+use super::MemoryBus;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Symbols {
+    labels: BTreeMap<(usize, u16), String>, // the first label at each bank and address
+    addresses: BTreeMap<String, (usize, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Symbols, SymbolError> {
+        let mut symbols = Symbols::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+
+            let invalid = || SymbolError::InvalidLine {
+                line: number + 1,
+                text: line.to_string(),
+            };
+            let (location, label) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let (bank, address) = location.split_once(':').ok_or_else(invalid)?;
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| invalid())?;
+            let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+            symbols.insert(bank, address, label.trim());
+        }
+        Ok(symbols)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Symbols, SymbolError> {
+        Symbols::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Name `address` in `bank`, an address keeps the first label it was given
+    pub fn insert(&mut self, bank: usize, address: u16, label: &str) {
+        self.labels
+            .entry((bank, address))
+            .or_insert_with(|| label.to_string());
+        self.addresses.insert(label.to_string(), (bank, address));
+    }
+
+    /// The label at `address` in `bank`
+    pub fn label(&self, bank: usize, address: u16) -> Option<&str> {
+        self.labels.get(&(bank, address)).map(String::as_str)
+    }
+
+    /// The label at `address` in the bank `memory` maps there
+    pub fn lookup(&self, memory: &impl MemoryBus, address: u16) -> Option<&str> {
+        self.label(memory.bank(address), address)
+    }
+
+    /// The closest label at or before `address` in the bank `memory` maps there, with the
+    /// distance from it, `Main+3` is `("Main", 3)`
+    pub fn nearest(&self, memory: &impl MemoryBus, address: u16) -> Option<(&str, u16)> {
+        self.preceding(memory.bank(address), address)
+    }
+
+    /// The closest label at or before `address` in `bank`, with the distance from it
+    pub fn preceding(&self, bank: usize, address: u16) -> Option<(&str, u16)> {
+        self.labels
+            .range((bank, 0)..=(bank, address))
+            .next_back()
+            .map(|((_, start), label)| (label.as_str(), address - start))
+    }
+
+    /// The bank and address of `label`
+    pub fn address(&self, label: &str) -> Option<(usize, u16)> {
+        self.addresses.get(label).copied()
+    }
+
+    /// How many addresses are named
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::{Cartridge, Memory};
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0150 EntryPoint
+00:0156 Main.loop
+01:4000 BankedRoutine
+02:4000 OtherBank
+00:c000 wBuffer
+[labels]
+";
+
+    #[test]
+    fn test_parse() {
+        let symbols = Symbols::parse(SYM).unwrap();
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(symbols.label(0, 0x0150), Some("Main"));
+        assert_eq!(symbols.label(0, 0x0151), None);
+        assert_eq!(symbols.address("EntryPoint"), Some((0, 0x0150)));
+        assert_eq!(symbols.address("Main.loop"), Some((0, 0x0156)));
+        assert_eq!(symbols.label(2, 0x4000), Some("OtherBank"));
+
+        assert!(matches!(
+            Symbols::parse("00:0150 Main\n0150 Broken"),
+            Err(SymbolError::InvalidLine { line: 2, .. })
+        ));
+        assert!(Symbols::parse("zz:0150 Main").is_err());
+    }
+
+    #[test]
+    fn test_lookup_by_bank() {
+        let symbols = Symbols::parse(SYM).unwrap();
+        // MBC1 with 4 banks
+        let mut rom = vec![0; 0x10000];
+        rom[0x0147] = 0x01;
+        rom[0x0148] = 0x01;
+        let mut memory = Memory::new();
+        memory.load_cartridge(Cartridge::new(rom).unwrap());
+
+        assert_eq!(symbols.lookup(&memory, 0x4000), Some("BankedRoutine"));
+        memory.write_byte(0x2000, 0x02);
+        assert_eq!(symbols.lookup(&memory, 0x4000), Some("OtherBank"));
+        assert_eq!(symbols.nearest(&memory, 0x0158), Some(("Main.loop", 2)));
+        assert_eq!(symbols.nearest(&memory, 0xC010), Some(("wBuffer", 0x10)));
+        assert_eq!(symbols.nearest(&memory, 0x0100), None);
+    }
+}
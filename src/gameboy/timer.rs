@@ -0,0 +1,300 @@
+//! The timer, DIV, TIMA, TMA and TAC at 0xFF04-0xFF07.
+//!
+//! DIV is the upper byte of an internal 16 bit counter incremented every T-cycle.
+//! TIMA increments on the falling edge of the counter bit selected by TAC, on overflow
+//! it reads 0 for 4 cycles before being reloaded from TMA and requesting the timer interrupt.
+//!
+//! The edge is taken after the enable bit too, so resetting the counter by writing DIV or
+//! changing TAC while the selected bit is high increments TIMA early. Writing TIMA while it
+//! reads 0 cancels the reload and the interrupt, during the cycle of the reload TIMA ignores
+//! writes and takes whatever is written to TMA.
+
+use core::ops::RangeInclusive;
+
+use crate::{
+    gameboy::{
+        memory::IoDevice,
+        save_state::{SaveState, StateReader, StateWriter},
+    },
+    utils::SaveStateError,
+};
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+const TAC_ENABLE: u8 = 1 << 2;
+const TAC_CLOCK_SELECT: u8 = 0b11;
+const TAC_UNUSED: u8 = 0b1111_1000;
+const RELOAD_DELAY: u8 = 4;
+
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    reload_delay: u8, // T-cycles left until TIMA is reloaded, 0 when no reload is pending
+    reloading: u8,    // T-cycles left in the cycle TIMA was reloaded in
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            reload_delay: 0,
+            reloading: 0,
+        }
+    }
+
+    /// Set the internal counter, DIV is its upper byte
+    pub fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
+    /// Advance the timer by a number of T-cycles
+    ///
+    /// Returns true if the timer interrupt should be requested
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        let mut interrupt = false;
+
+        for _ in 0..cycles {
+            self.reloading = self.reloading.saturating_sub(1);
+            if self.reload_delay > 0 {
+                self.reload_delay -= 1;
+                if self.reload_delay == 0 {
+                    self.tima = self.tma;
+                    self.reloading = RELOAD_DELAY;
+                    interrupt = true;
+                }
+            }
+
+            let before = self.timer_bit();
+            self.counter = self.counter.wrapping_add(1);
+            if before && !self.timer_bit() {
+                self.increment_tima();
+            }
+        }
+
+        interrupt
+    }
+
+    /// State of the counter bit selected by TAC, gated by the enable bit
+    fn timer_bit(&self) -> bool {
+        if self.tac & TAC_ENABLE == 0 {
+            return false;
+        }
+
+        let bit = match self.tac & TAC_CLOCK_SELECT {
+            0b00 => 9, // 4096 Hz
+            0b01 => 3, // 262144 Hz
+            0b10 => 5, // 65536 Hz
+            _ => 7,    // 16384 Hz
+        };
+        self.counter & (1 << bit) != 0
+    }
+
+    /// Apply a register write that may move the selected counter bit, a falling edge
+    /// increments TIMA like the counter running does
+    fn write_edge(&mut self, write: impl FnOnce(&mut Timer)) {
+        let before = self.timer_bit();
+        write(self);
+        if before && !self.timer_bit() {
+            self.increment_tima();
+        }
+    }
+
+    fn increment_tima(&mut self) {
+        let (result, overflow) = self.tima.overflowing_add(1);
+        self.tima = result;
+        if overflow {
+            tracing::trace!(target: "timer", tma = self.tma, "TIMA overflowed");
+            self.reload_delay = RELOAD_DELAY;
+        }
+    }
+}
+
+impl IoDevice for Timer {
+    fn registers(&self) -> &'static [RangeInclusive<u16>] {
+        &[DIV..=TAC]
+    }
+
+    fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV => (self.counter >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | TAC_UNUSED,
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, address: u16, value: u8) {
+        match address {
+            DIV => self.write_edge(|timer| timer.counter = 0),
+            // the reload wins over a write in the same cycle
+            TIMA if self.reloading > 0 => {}
+            TIMA => {
+                self.tima = value;
+                self.reload_delay = 0;
+            }
+            TMA => {
+                self.tma = value;
+                if self.reloading > 0 {
+                    self.tima = value;
+                }
+            }
+            TAC => {
+                self.write_edge(|timer| timer.tac = value & !TAC_UNUSED);
+                tracing::debug!(target: "timer", tac = self.tac, "timer control written");
+            }
+            _ => {}
+        }
+    }
+}
+
+impl SaveState for Timer {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.counter);
+        state.write_u8(self.tima);
+        state.write_u8(self.tma);
+        state.write_u8(self.tac);
+        state.write_u8(self.reload_delay);
+        state.write_u8(self.reloading);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), SaveStateError> {
+        self.counter = state.read_u16()?;
+        self.tima = state.read_u8()?;
+        self.tma = state.read_u8()?;
+        self.tac = state.read_u8()?;
+        self.reload_delay = state.read_u8()?;
+        self.reloading = state.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_div() {
+        let mut timer = Timer::new();
+        timer.tick(255);
+        assert_eq!(timer.read(DIV), 0);
+        timer.tick(1);
+        assert_eq!(timer.read(DIV), 1);
+
+        timer.write(DIV, 0x12);
+        assert_eq!(timer.read(DIV), 0);
+    }
+
+    #[test]
+    fn test_tima_rates() {
+        for (tac, period) in [(0b100, 1024), (0b101, 16), (0b110, 64), (0b111, 256)] {
+            let mut timer = Timer::new();
+            timer.write(TAC, tac);
+
+            timer.tick(period - 1);
+            assert_eq!(timer.read(TIMA), 0, "tac {:#b}", tac);
+            timer.tick(1);
+            assert_eq!(timer.read(TIMA), 1, "tac {:#b}", tac);
+        }
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b001);
+        timer.tick(1024);
+
+        assert_eq!(timer.read(TIMA), 0);
+        assert_eq!(timer.read(TAC), 0xF9);
+    }
+
+    #[test]
+    fn test_overflow_reload_delay() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b101);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+
+        assert!(!timer.tick(16));
+        assert_eq!(timer.read(TIMA), 0x00);
+
+        assert!(!timer.tick(3));
+        assert_eq!(timer.read(TIMA), 0x00);
+
+        assert!(timer.tick(1));
+        assert_eq!(timer.read(TIMA), 0xAB);
+    }
+
+    #[test]
+    fn test_div_write_falling_edge() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b101);
+        // bit 3 of the counter is high
+        timer.tick(8);
+        assert_eq!(timer.read(TIMA), 0);
+        timer.write(DIV, 0);
+        assert_eq!(timer.read(TIMA), 1);
+
+        // with the bit low nothing happens
+        timer.tick(4);
+        timer.write(DIV, 0);
+        assert_eq!(timer.read(TIMA), 1);
+    }
+
+    #[test]
+    fn test_tac_write_falling_edge() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b101);
+        timer.tick(8);
+        // disabling the timer drops the bit
+        timer.write(TAC, 0b001);
+        assert_eq!(timer.read(TIMA), 1);
+
+        // so does selecting a bit that is low, bit 9 here
+        timer.write(TAC, 0b101);
+        timer.write(TAC, 0b100);
+        assert_eq!(timer.read(TIMA), 2);
+    }
+
+    #[test]
+    fn test_tima_write_cancels_reload() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b101);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+        timer.tick(16);
+
+        timer.write(TIMA, 0x12);
+        assert!(!timer.tick(4));
+        assert_eq!(timer.read(TIMA), 0x12);
+    }
+
+    #[test]
+    fn test_writes_during_reload() {
+        let mut timer = Timer::new();
+        timer.write(TAC, 0b101);
+        timer.write(TMA, 0xAB);
+        timer.write(TIMA, 0xFF);
+        assert!(timer.tick(20));
+
+        // TIMA ignores the write, TMA goes through to it
+        timer.write(TIMA, 0x12);
+        assert_eq!(timer.read(TIMA), 0xAB);
+        timer.write(TMA, 0xCD);
+        assert_eq!(timer.read(TIMA), 0xCD);
+
+        // a cycle later they are back to normal
+        timer.tick(4);
+        timer.write(TMA, 0x00);
+        timer.write(TIMA, 0x12);
+        assert_eq!(timer.read(TIMA), 0x12);
+    }
+}
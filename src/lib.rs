@@ -0,0 +1,37 @@
+#![allow(dead_code)]
+#![allow(unused_variables)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! A Game Boy emulator.
+//!
+//! `GameBoy` ties the CPU, memory and PPU together and is the entry point for frontends.
+//!
+//! Events are reported through `tracing`, with a target per subsystem so they can be filtered
+//! separately: `cpu` (instructions, HALT, STOP), `interrupts` (requests and dispatch), `ppu`,
+//! `timer`, `dma` (OAM DMA and HDMA), `mbc` (bank switches) and `serial` (the link cable).
+//! Every frame runs in a `frame` span.
+//!
+//! The `std` feature is on by default. Without it the core builds for `no_std` targets that have
+//! an allocator, leaving out file access, screenshots, recordings and the frontends.
+
+extern crate alloc;
+
+pub mod gameboy;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod utils;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use gameboy::{Button, Cartridge, DmgPalette, Frame, GameBoy};
+pub use utils::{
+    BootRomError, CartridgeError, CheatError, EmuError, ExpressionError, MovieError, PaletteError,
+    SaveStateError, SymbolError,
+};
+#[cfg(feature = "std")]
+pub use utils::{
+    CoreDumpError, LibraryError, LinkError, NetplayError, RecordingError, ScreenshotError,
+    ScriptError,
+};
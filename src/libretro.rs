@@ -0,0 +1,514 @@
+//! A libretro core, behind the `libretro` feature, so the emulator runs inside RetroArch.
+//!
+//! Build with `cargo build --release --features libretro` and load the resulting
+//! `libgameboy_emulator.so` (`.dll`, `.dylib`) as a core. The ROM is loaded from its path so that
+//! battery saves end up next to it, like with the desktop frontend. Frames are sent as XRGB8888.
+
+use std::{
+    ffi::{c_char, c_uint, c_void, CStr},
+    sync::Mutex,
+};
+
+use crate::{
+    gameboy::{Frame, SAMPLE_RATE, SCREEN_HEIGHT, SCREEN_WIDTH, T_CYCLES_PER_FRAME},
+    Button, Cartridge, GameBoy,
+};
+
+const API_VERSION: c_uint = 1;
+
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const DEVICE_JOYPAD: c_uint = 1;
+const REGION_NTSC: c_uint = 0;
+
+const CLOCK_RATE: f64 = 4_194_304.0;
+
+/// The joypad IDs libretro uses for each button
+const BUTTONS: [(c_uint, Button); 8] = [
+    (0, Button::B),
+    (2, Button::Select),
+    (3, Button::Start),
+    (4, Button::Up),
+    (5, Button::Down),
+    (6, Button::Left),
+    (7, Button::Right),
+    (8, Button::A),
+];
+
+type EnvironmentFn = unsafe extern "C" fn(command: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+/// The callbacks the frontend registered
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The running game, `None` until `retro_load_game`
+static GAMEBOY: Mutex<Option<GameBoy>> = Mutex::new(None);
+
+/// The path of the loaded ROM, to reload it on reset
+static GAME_PATH: Mutex<Option<String>> = Mutex::new(None);
+
+/// The cheats RetroArch set, in the order of their indices
+static CHEATS: Mutex<Vec<(bool, String)>> = Mutex::new(Vec::new());
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    CALLBACKS.lock().unwrap().environment = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap().video_refresh = Some(callback);
+}
+
+/// Samples are always sent in batches
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    CALLBACKS.lock().unwrap().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    CALLBACKS.lock().unwrap().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *GAMEBOY.lock().unwrap() = None;
+}
+
+/// # Safety
+///
+/// `info` must point to writable memory for a `SystemInfo`
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    info.write(SystemInfo {
+        library_name: c"gameboy_emulator".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"gb|gbc".as_ptr(),
+        need_fullpath: true,
+        block_extract: false,
+    });
+}
+
+/// # Safety
+///
+/// `info` must point to writable memory for a `SystemAvInfo`
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    info.write(SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: SCREEN_WIDTH as c_uint,
+            max_height: SCREEN_HEIGHT as c_uint,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: SystemTiming {
+            fps: CLOCK_RATE / f64::from(T_CYCLES_PER_FRAME),
+            sample_rate: f64::from(SAMPLE_RATE),
+        },
+    });
+}
+
+/// Only the joypad is supported
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+/// Power cycle, reloading the cartridge from its file
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    let Some(path) = GAME_PATH.lock().unwrap().clone() else {
+        return;
+    };
+    // drop the running game first so its battery save is written before it is loaded again
+    *GAMEBOY.lock().unwrap() = None;
+    *GAMEBOY.lock().unwrap() = load(&path);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = CALLBACKS.lock().unwrap();
+    let mut gameboy = GAMEBOY.lock().unwrap();
+    let Some(gameboy) = gameboy.as_mut() else {
+        return;
+    };
+
+    if let (Some(input_poll), Some(input_state)) = (callbacks.input_poll, callbacks.input_state) {
+        // SAFETY: callbacks registered by the frontend, called as the libretro API specifies
+        unsafe { input_poll() };
+        for (id, button) in BUTTONS {
+            if unsafe { input_state(0, DEVICE_JOYPAD, 0, id) } != 0 {
+                gameboy.press_button(button);
+            } else {
+                gameboy.release_button(button);
+            }
+        }
+    }
+
+    gameboy.run_until_vblank();
+
+    if let Some(video_refresh) = callbacks.video_refresh {
+        let pixels = xrgb8888(&gameboy.frame());
+        // SAFETY: the buffer holds SCREEN_HEIGHT rows of `pitch` bytes and outlives the call
+        unsafe {
+            video_refresh(
+                pixels.as_ptr().cast(),
+                SCREEN_WIDTH as c_uint,
+                SCREEN_HEIGHT as c_uint,
+                SCREEN_WIDTH * 4,
+            )
+        };
+    }
+
+    let samples = gameboy.drain_audio_samples();
+    if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+        // SAFETY: `samples` holds `len / 2` interleaved stereo frames
+        unsafe { audio_sample_batch(samples.as_ptr(), samples.len() / 2) };
+    }
+}
+
+/// # Safety
+///
+/// `game` must point to a valid `GameInfo` whose `path` is a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.path.is_null() {
+        return false;
+    }
+    let Ok(path) = CStr::from_ptr(game.path).to_str() else {
+        return false;
+    };
+
+    if let Some(environment) = CALLBACKS.lock().unwrap().environment {
+        let mut format = PIXEL_FORMAT_XRGB8888;
+        if !environment(
+            ENVIRONMENT_SET_PIXEL_FORMAT,
+            (&mut format as *mut c_uint).cast(),
+        ) {
+            return false;
+        }
+    }
+
+    let Some(gameboy) = load(path) else {
+        return false;
+    };
+    *GAMEBOY.lock().unwrap() = Some(gameboy);
+    *GAME_PATH.lock().unwrap() = Some(path.to_string());
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+/// Dropping the Game Boy writes the battery save
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *GAMEBOY.lock().unwrap() = None;
+    *GAME_PATH.lock().unwrap() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    GAMEBOY
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(0, |gameboy| gameboy.save_state().len())
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let Some(state) = GAMEBOY.lock().unwrap().as_ref().map(GameBoy::save_state) else {
+        return false;
+    };
+    if state.len() > size {
+        return false;
+    }
+    std::ptr::copy_nonoverlapping(state.as_ptr(), data.cast(), state.len());
+    true
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let state = std::slice::from_raw_parts(data.cast::<u8>(), size);
+    GAMEBOY
+        .lock()
+        .unwrap()
+        .as_mut()
+        .is_some_and(|gameboy| gameboy.load_state(state).is_ok())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    CHEATS.lock().unwrap().clear();
+    if let Some(gameboy) = GAMEBOY.lock().unwrap().as_mut() {
+        apply_cheats(gameboy);
+    }
+}
+
+/// # Safety
+///
+/// `code` must be a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let code = CStr::from_ptr(code).to_string_lossy().into_owned();
+    {
+        let mut cheats = CHEATS.lock().unwrap();
+        let index = index as usize;
+        if cheats.len() <= index {
+            cheats.resize(index + 1, (false, String::new()));
+        }
+        cheats[index] = (enabled, code);
+    }
+    if let Some(gameboy) = GAMEBOY.lock().unwrap().as_mut() {
+        apply_cheats(gameboy);
+    }
+}
+
+/// Battery saves go to the `.sav` file next to the ROM instead
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+/// A Game Boy running the ROM at `path`, with the cheats applied
+fn load(path: &str) -> Option<GameBoy> {
+    let cartridge = match Cartridge::from_file(path) {
+        Ok(cartridge) => cartridge,
+        Err(error) => {
+            tracing::error!("Failed to load {path}: {error}");
+            return None;
+        }
+    };
+    let mut gameboy = GameBoy::new();
+    gameboy.load_cartridge(cartridge);
+    apply_cheats(&mut gameboy);
+    Some(gameboy)
+}
+
+/// Replace the Game Boy's cheats with the enabled ones RetroArch set
+///
+/// A RetroArch cheat can hold several codes separated by `+`
+fn apply_cheats(gameboy: &mut GameBoy) {
+    while gameboy.remove_cheat(0).is_some() {}
+    for (_, code) in CHEATS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+    {
+        for code in code.split('+') {
+            if let Err(error) = gameboy.add_cheat(code) {
+                tracing::warn!("{error}");
+            }
+        }
+    }
+}
+
+/// Pack the frame as 0x00RRGGBB in native byte order
+fn xrgb8888(frame: &Frame) -> Vec<u32> {
+    frame
+        .as_bytes()
+        .chunks_exact(4)
+        .map(|pixel| u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::gameboy::test_rom;
+
+    static VIDEO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+    static AUDIO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn video_refresh(
+        data: *const c_void,
+        width: c_uint,
+        height: c_uint,
+        pitch: usize,
+    ) {
+        assert!(!data.is_null());
+        assert_eq!((width, height, pitch), (160, 144, 640));
+        VIDEO_FRAMES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+        AUDIO_FRAMES.fetch_add(frames, Ordering::SeqCst);
+        frames
+    }
+
+    unsafe extern "C" fn input_poll() {}
+
+    unsafe extern "C" fn input_state(
+        _port: c_uint,
+        _device: c_uint,
+        _index: c_uint,
+        id: c_uint,
+    ) -> i16 {
+        i16::from(id == 3)
+    }
+
+    #[test]
+    fn test_core() {
+        let path = std::env::temp_dir().join(format!(
+            "gameboy_emulator_{}_libretro.gb",
+            std::process::id()
+        ));
+        // JR -2
+        std::fs::write(&path, test_rom(&[0x18, 0xFE])).unwrap();
+        let path_string = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+
+        retro_set_video_refresh(video_refresh);
+        retro_set_audio_sample_batch(audio_sample_batch);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+        let game = GameInfo {
+            path: path_string.as_ptr(),
+            data: std::ptr::null(),
+            size: 0,
+            meta: std::ptr::null(),
+        };
+        assert!(unsafe { retro_load_game(&game) });
+        std::fs::remove_file(&path).unwrap();
+
+        retro_run();
+        assert_eq!(VIDEO_FRAMES.load(Ordering::SeqCst), 1);
+        assert!(AUDIO_FRAMES.load(Ordering::SeqCst) > 0);
+
+        let mut state = vec![0u8; retro_serialize_size()];
+        assert!(unsafe { retro_serialize(state.as_mut_ptr().cast(), state.len()) });
+        retro_run();
+        assert!(unsafe { retro_unserialize(state.as_ptr().cast(), state.len()) });
+
+        unsafe { retro_cheat_set(0, true, c"0163A0C0".as_ptr()) };
+        retro_run();
+        let value = GAMEBOY
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .memory()
+            .read_byte(0xC0A0);
+        assert_eq!(value, 0x63);
+
+        retro_unload_game();
+        retro_deinit();
+    }
+
+    #[test]
+    fn test_xrgb8888() {
+        let mut colors = [0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        colors[0] = 0x001F;
+        let pixels = xrgb8888(&Frame::from_rgb555(&colors));
+
+        assert_eq!(pixels[0], 0x00FF_0000);
+        assert_eq!(pixels[1], 0);
+    }
+}
@@ -1,10 +1,534 @@
-#![allow(dead_code)]
-#![allow(unused_variables)]
+mod frontend;
 
-mod gameboy;
-mod utils;
+use gameboy_emulator::{gameboy, utils::Speed, BootRomError, Cartridge, GameBoy};
 
 fn main() {
-    let memory = gameboy::Memory::new();
-    let cpu = gameboy::Cpu::new();
+    let (flags, args): (Vec<String>, Vec<String>) = std::env::args()
+        .skip(1)
+        .partition(|arg| arg.starts_with("--"));
+
+    // --log=FILTER (or RUST_LOG) prints the emulator's events to stderr, e.g. --log=warn,mbc=debug
+    let log_filter = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--log="))
+        .map(str::to_string)
+        .or_else(|| std::env::var("RUST_LOG").ok());
+    if let Some(filter) = log_filter {
+        match tracing_subscriber::EnvFilter::try_new(&filter) {
+            Ok(env_filter) => tracing_subscriber::fmt()
+                .with_env_filter(env_filter)
+                .with_writer(std::io::stderr)
+                .init(),
+            Err(error) => {
+                eprintln!("Invalid log filter {}: {}", filter, error);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let debug = flags.iter().any(|flag| flag == "--debug");
+    // --gdb listens on the default port, --gdb=PORT on another one
+    let gdb_port = flags.iter().find_map(|flag| match flag.as_str() {
+        "--gdb" => Some(frontend::gdb::DEFAULT_PORT),
+        flag => flag.strip_prefix("--gdb=")?.parse().ok(),
+    });
+    if args.first().map(String::as_str) == Some("headless") {
+        run_headless(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("rom-info") {
+        rom_info(&args[1..]);
+        return;
+    }
+    if args.first().map(String::as_str) == Some("recent") {
+        recent(&args[1..]);
+        return;
+    }
+
+    // --speed=2 runs twice as fast, --speed=unlimited as fast as possible
+    let speed = match flags.iter().find_map(|flag| flag.strip_prefix("--speed=")) {
+        None => Speed::Normal,
+        Some("unlimited") => Speed::Unlimited,
+        Some(multiplier) => match multiplier.parse() {
+            Ok(multiplier) => Speed::Multiplier(multiplier),
+            Err(_) => {
+                eprintln!("Invalid speed {}", multiplier);
+                std::process::exit(2);
+            }
+        },
+    };
+    let mut args = args.into_iter();
+    let Some(path) = args.next() else {
+        return;
+    };
+
+    // --netplay-host=ADDRESS waits for a second player to connect, --netplay=ADDRESS joins one,
+    // both emulate the two Game Boys linked and only exchange inputs
+    let netplay_host = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--netplay-host="));
+    let netplay_join = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--netplay="));
+    if netplay_host.is_some() || netplay_join.is_some() {
+        // --input-delay=FRAMES for the host, more hides more latency, 2 by default
+        let delay = match flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix("--input-delay="))
+        {
+            None => 2,
+            Some(delay) => delay.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid input delay {}", delay);
+                std::process::exit(2);
+            }),
+        };
+        run_netplay(&path, netplay_host, netplay_join, delay);
+        return;
+    }
+
+    // an optional second argument is a boot ROM to run before the cartridge
+    let mut gameboy = match args.next() {
+        Some(boot_rom_path) => {
+            match std::fs::read(&boot_rom_path)
+                .map_err(BootRomError::from)
+                .and_then(GameBoy::with_boot_rom)
+            {
+                Ok(gameboy) => gameboy,
+                Err(error) => {
+                    eprintln!("Failed to load boot ROM {}: {}", boot_rom_path, error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => GameBoy::new(),
+    };
+
+    // --trace=PATH writes a Gameboy Doctor line for every instruction
+    if let Some(trace_path) = flags.iter().find_map(|flag| flag.strip_prefix("--trace=")) {
+        match std::fs::File::create(trace_path) {
+            Ok(file) => gameboy.set_trace(Some(Box::new(std::io::BufWriter::new(file)))),
+            Err(error) => {
+                eprintln!("Failed to create {}: {}", trace_path, error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // --sym=PATH names addresses in the trace, the debugger and core dumps, by default the
+    // ROM's .sym file is read when there is one
+    let sym_flag = flags.iter().find_map(|flag| flag.strip_prefix("--sym="));
+    let sym_path = sym_flag
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::Path::new(&path).with_extension("sym"));
+    if sym_flag.is_some() || sym_path.exists() {
+        match gameboy::Symbols::from_file(&sym_path) {
+            Ok(symbols) => gameboy.set_symbols(Some(symbols)),
+            Err(error) => {
+                eprintln!("Failed to load symbols {}: {}", sym_path.display(), error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // --profile charges the cycles to the code running them, see the debugger's `profile` and F9
+    if flags.iter().any(|flag| flag == "--profile") {
+        gameboy.set_profiler(Some(gameboy::Profiler::new()));
+    }
+
+    // --heatmap counts the reads and writes at every address, see the debugger's `heatmap` and F8
+    if flags.iter().any(|flag| flag == "--heatmap") {
+        gameboy.set_heatmap(Some(gameboy::Heatmap::new()));
+    }
+
+    // --accuracy=fast|balanced|cycle-accurate trades accuracy for speed
+    if let Some(accuracy) = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--accuracy="))
+    {
+        gameboy.set_accuracy(match accuracy {
+            "fast" => gameboy::AccuracyProfile::Fast,
+            "balanced" => gameboy::AccuracyProfile::Balanced,
+            "cycle-accurate" => gameboy::AccuracyProfile::CycleAccurate,
+            _ => {
+                eprintln!("Invalid accuracy {}", accuracy);
+                std::process::exit(2);
+            }
+        });
+    }
+
+    // --fifo draws through the pixel FIFO, for raster effects in the middle of a line
+    if flags.iter().any(|flag| flag == "--fifo") {
+        gameboy.set_renderer(gameboy::Renderer::PixelFifo);
+    }
+
+    // --block-cache runs code from a cache of decoded blocks, faster but experimental
+    if flags.iter().any(|flag| flag == "--block-cache") {
+        gameboy.set_block_cache(true);
+    }
+
+    // --library=DIR keeps the save and per-game settings in DIR instead of next to the ROM
+    let (input_map, save_controls) = match flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--library="))
+    {
+        Some(dir) => match load_from_library(dir, &path, &mut gameboy) {
+            Ok(controls) => controls,
+            Err(error) => {
+                eprintln!("Failed to load {}: {}", path, error);
+                std::process::exit(1);
+            }
+        },
+        None => match Cartridge::from_file(&path) {
+            Ok(cartridge) => {
+                gameboy.load_cartridge(cartridge);
+                (gameboy::InputMap::default(), None)
+            }
+            Err(error) => {
+                eprintln!("Failed to load {}: {}", path, error);
+                std::process::exit(1);
+            }
+        },
+    };
+
+    // --palette=NAME|PATH colors DMG games with a built-in palette or one from a .pal file, over
+    // the game's settings
+    if let Some(palette) = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--palette="))
+    {
+        match gameboy::DmgPalette::named(palette)
+            .map_or_else(|| gameboy::DmgPalette::from_file(palette), Ok)
+        {
+            Ok(palette) => gameboy.set_palette(palette),
+            Err(error) => {
+                eprintln!("Failed to load palette {}: {}", palette, error);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // --link-listen=ADDRESS waits for a second emulator to connect a link cable, --link=ADDRESS
+    // connects to one
+    let link = if let Some(address) = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--link-listen="))
+    {
+        println!("Waiting for the other emulator on {}", address);
+        Some((address, gameboy::TcpLink::listen(address)))
+    } else {
+        flags
+            .iter()
+            .find_map(|flag| flag.strip_prefix("--link="))
+            .map(|address| (address, gameboy::TcpLink::connect(address)))
+    };
+    match link {
+        Some((_, Ok(link))) => gameboy.set_serial_transport(Box::new(link)),
+        Some((address, Err(error))) => {
+            eprintln!("Failed to link with {}: {}", address, error);
+            std::process::exit(1);
+        }
+        None => {}
+    }
+
+    // --ir=loopback points the CGB infrared port at a mirror, it receives its own LED
+    match flags.iter().find_map(|flag| flag.strip_prefix("--ir=")) {
+        None | Some("none") => {}
+        Some("loopback") => gameboy.set_ir_transport(Box::new(gameboy::IrLoopback::new())),
+        Some(mode) => {
+            eprintln!("Invalid infrared mode {}", mode);
+            std::process::exit(2);
+        }
+    }
+
+    // --cheat=CODE adds a Game Genie or GameShark code, it can be given several times
+    for code in flags
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("--cheat="))
+    {
+        if let Err(error) = gameboy.add_cheat(code) {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    }
+
+    // --movie=PATH plays back a movie recorded with `GameBoy::start_movie_recording`
+    if let Some(movie_path) = flags.iter().find_map(|flag| flag.strip_prefix("--movie=")) {
+        if let Err(error) =
+            gameboy::Movie::load(movie_path).and_then(|movie| gameboy.play_movie(movie))
+        {
+            eprintln!("Failed to play {}: {}", movie_path, error);
+            std::process::exit(1);
+        }
+    }
+
+    // --record=PATH.gif records an animated GIF, --record=DIRECTORY a numbered PNG per frame
+    if let Some(record_path) = flags.iter().find_map(|flag| flag.strip_prefix("--record=")) {
+        let format = gameboy::VideoFormat::from_path(record_path);
+        if let Err(error) = gameboy.start_recording(record_path, format) {
+            eprintln!("Failed to record to {}: {}", record_path, error);
+            std::process::exit(1);
+        }
+    }
+
+    // --script=PATH runs a Rhai script after every frame
+    let frame_hook = flags
+        .iter()
+        .find_map(|flag| flag.strip_prefix("--script="))
+        .map(script_hook);
+
+    if let Some(port) = gdb_port {
+        if let Err(error) = frontend::gdb::run(gameboy, port) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if debug {
+        if let Err(error) = frontend::debugger::run(gameboy) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    gameboy.set_speed(speed);
+
+    #[cfg(feature = "tui")]
+    if flags.iter().any(|flag| flag == "--tui") {
+        if let Err(error) = frontend::tui::run(gameboy, frame_hook) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // --lcd-grid, --frame-blending, --ghosting, --integer-scale and --aspect-correction
+    // post-process the screen
+    #[cfg(feature = "sdl")]
+    {
+        let mut post = gameboy::PostProcessor::new();
+        let enabled = |name: &str| flags.iter().any(|flag| flag == name);
+        post.set_lcd_grid(enabled("--lcd-grid"));
+        post.set_frame_blending(enabled("--frame-blending"));
+        post.set_ghosting(enabled("--ghosting"));
+        post.set_integer_scaling(enabled("--integer-scale"));
+        post.set_aspect_correction(enabled("--aspect-correction"));
+        let result = frontend::sdl::run(gameboy, speed, frame_hook, input_map, save_controls, post);
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+    }
+
+    #[cfg(not(feature = "sdl"))]
+    {
+        let _ = (input_map, save_controls);
+        let mut frame_hook = frame_hook;
+        let mut delta_time = gameboy_emulator::utils::DeltaTime::new();
+        loop {
+            delta_time.update();
+            gameboy.run_until_vblank();
+            if let Some(hook) = &mut frame_hook {
+                let mut frame = gameboy.frame();
+                hook(&mut gameboy, &mut frame);
+            }
+            delta_time.wait_frame(frontend::FRAME_DURATION, speed);
+        }
+    }
+}
+
+/// Load the game at `path` through the library in `dir` and apply its settings, returning its
+/// controls for the frontend and how to store them in the game's config
+fn load_from_library(
+    dir: &str,
+    path: &str,
+    gameboy: &mut GameBoy,
+) -> Result<(gameboy::InputMap, Option<frontend::SaveControls>), String> {
+    let mut library = gameboy::Library::open(dir).map_err(|error| error.to_string())?;
+    let mut game = library.open_game(path).map_err(|error| error.to_string())?;
+    gameboy.load_cartridge(game.cartridge().map_err(|error| error.to_string())?);
+    game.apply_config(gameboy)
+        .map_err(|error| error.to_string())?;
+    let input_map = game.config.input_map();
+    let save_controls: frontend::SaveControls = Box::new(move |input_map| {
+        game.config.set_input_map(input_map);
+        game.save_config().map_err(|error| error.to_string())
+    });
+    Ok((input_map, Some(save_controls)))
+}
+
+/// Play the ROM at `path` over netplay, hosting on `host` or joining `join`
+fn run_netplay(path: &str, host: Option<&str>, join: Option<&str>, delay: u32) {
+    let rom = match std::fs::read(path) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("Failed to load {}: {}", path, error);
+            std::process::exit(1);
+        }
+    };
+    let (address, netplay) = match (host, join) {
+        (Some(address), _) => {
+            println!("Waiting for the second player on {}", address);
+            (address, gameboy::Netplay::host(address, rom, delay))
+        }
+        (None, Some(address)) => (address, gameboy::Netplay::join(address, rom)),
+        (None, None) => return,
+    };
+    let netplay = match netplay {
+        Ok(netplay) => netplay,
+        Err(error) => {
+            eprintln!("Failed to start netplay with {}: {}", address, error);
+            std::process::exit(1);
+        }
+    };
+
+    #[cfg(feature = "sdl")]
+    if let Err(error) = frontend::sdl::run_netplay(netplay, gameboy::InputMap::default()) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+
+    #[cfg(not(feature = "sdl"))]
+    {
+        let _ = netplay;
+        eprintln!("Netplay needs the sdl feature");
+        std::process::exit(2);
+    }
+}
+
+/// `recent DIR`, list the games played last through the library in DIR
+fn recent(args: &[String]) {
+    let [dir] = args else {
+        eprintln!("Usage: recent path/to/library");
+        std::process::exit(2);
+    };
+    match gameboy::Library::open(dir) {
+        Ok(library) => {
+            for game in library.recent() {
+                println!("{:<16} {}", game.title, game.path.display());
+            }
+        }
+        Err(error) => {
+            eprintln!("Failed to open {}: {}", dir, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Run the script at `path` after every frame and draw its overlay, errors stop the script
+#[cfg(feature = "scripting")]
+fn script_hook(path: &str) -> frontend::FrameHook {
+    let mut script = match gameboy_emulator::scripting::Script::from_file(path) {
+        Ok(script) => Some(script),
+        Err(error) => {
+            eprintln!("Failed to load {}: {}", path, error);
+            std::process::exit(1);
+        }
+    };
+    Box::new(move |gameboy, frame| {
+        let Some(running) = &mut script else {
+            return;
+        };
+        match running.frame(gameboy) {
+            Ok(()) => running.draw_overlay(frame),
+            Err(error) => {
+                eprintln!("{}", error);
+                script = None;
+            }
+        }
+    })
+}
+
+#[cfg(not(feature = "scripting"))]
+fn script_hook(_path: &str) -> frontend::FrameHook {
+    eprintln!("Scripts need the scripting feature");
+    std::process::exit(2);
+}
+
+/// `headless ROM FRAMES`, run without a window and print the final frame hash and serial output
+fn run_headless(args: &[String]) {
+    let [path, frames] = args else {
+        eprintln!("Usage: headless path/to/rom.gb FRAMES");
+        std::process::exit(2);
+    };
+    let Ok(frames) = frames.parse() else {
+        eprintln!("Invalid frame count {}", frames);
+        std::process::exit(2);
+    };
+
+    let run = std::fs::read(path)
+        .map_err(|error| error.to_string())
+        .and_then(|rom| gameboy::run_headless(rom, frames).map_err(|error| error.to_string()));
+    match run {
+        Ok(run) => {
+            println!("frame hash: {:016x}", run.frame_hash);
+            println!("serial: {}", String::from_utf8_lossy(&run.serial));
+        }
+        Err(error) => {
+            eprintln!("Failed to run {}: {}", path, error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// `rom-info ROM`, print the cartridge header and whether the ROM passes validation
+fn rom_info(args: &[String]) {
+    let [path] = args else {
+        eprintln!("Usage: rom-info path/to/rom.gb");
+        std::process::exit(2);
+    };
+    let header = gameboy::read_rom_file(std::path::Path::new(path))
+        .map_err(|error| error.to_string())
+        .and_then(|rom| {
+            let header = gameboy::Header::parse(&rom).map_err(|error| error.to_string())?;
+            Ok((rom, header))
+        });
+    let (rom, header) = match header {
+        Ok(header) => header,
+        Err(error) => {
+            eprintln!("Failed to read {}: {}", path, error);
+            std::process::exit(1);
+        }
+    };
+
+    let cgb = match header.cgb_flag {
+        0x80 => "supported",
+        0xC0 => "required",
+        _ => "no",
+    };
+    println!("Title:           {}", header.title);
+    println!(
+        "Cartridge type:  {:#04X} {}",
+        header.cartridge_type,
+        header.cartridge_type_name()
+    );
+    println!("ROM size:        {} KiB", header.rom_size / 1024);
+    println!("RAM size:        {} KiB", header.ram_size / 1024);
+    println!("CGB:             {}", cgb);
+    println!(
+        "SGB:             {}",
+        if header.supports_sgb() { "yes" } else { "no" }
+    );
+    println!(
+        "Licensee:        {} {}",
+        header.licensee,
+        header.licensee_name().unwrap_or("")
+    );
+    println!("Version:         {}", header.version);
+    println!(
+        "Global checksum: {:#06X} {}",
+        header.global_checksum,
+        if header.global_checksum_valid(&rom) {
+            "ok"
+        } else {
+            "mismatch"
+        }
+    );
+
+    if let Err(error) = gameboy::Header::validate(&rom) {
+        println!("Invalid:         {}", error);
+        std::process::exit(1);
+    }
+    println!("Valid:           yes");
 }
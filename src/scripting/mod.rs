@@ -0,0 +1,302 @@
+//! Rhai scripting, behind the `scripting` feature, for bots, auto-splitters and test automation.
+//!
+//! A script runs its top level once when it starts and registers callbacks to run after every
+//! frame. It has these functions:
+//!
+//! - `read(address)`, `read16(address)` and `write(address, value)` access memory like the CPU
+//! - `reg(name)` reads a register, `a` to `l`, `af` to `hl`, `sp` or `pc`
+//! - `press(button)` and `release(button)`, buttons are `a`, `b`, `start`, `select`, `up`,
+//!   `down`, `left` and `right`
+//! - `on_frame(callback)` calls `callback` after every frame, when the PPU enters VBlank
+//! - `frame()` counts the frames since the script started
+//! - `draw_text(x, y, text)` draws text over the next frame shown, see `Script::draw_overlay`
+//!
+//! ```text
+//! on_frame(|| {
+//!     if read(0xC0A0) == 0 { press("start") } else { release("start") }
+//!     draw_text(1, 1, `LIVES ${read(0xDA15)}`);
+//! });
+//! ```
+//!
+//! The emulator is lent to the script while it runs, so the functions can only be called from
+//! the top level and the callbacks.
+
+mod font;
+
+use std::{cell::RefCell, mem, path::Path, rc::Rc};
+
+use rhai::{Engine, EvalAltResult, FnPtr, Position, AST};
+
+use crate::{
+    gameboy::{Frame, Register16, SCREEN_HEIGHT, SCREEN_WIDTH},
+    utils::ScriptError,
+    Button, GameBoy,
+};
+
+const TEXT_COLOR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const SHADOW_COLOR: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+/// Text drawn by a script at `x`, `y` in screen pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlayText {
+    pub x: i32,
+    pub y: i32,
+    pub text: String,
+}
+
+/// What the script's functions reach, the emulator is swapped in while the script runs
+struct Host {
+    gameboy: GameBoy,
+    callbacks: Vec<FnPtr>,
+    overlay: Vec<OverlayText>,
+    frame: u64,
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    host: Rc<RefCell<Host>>,
+    started: bool,
+}
+
+impl Script {
+    /// Compile a script, it starts running with the first call to `frame`
+    pub fn new(source: &str) -> Result<Script, ScriptError> {
+        let host = Rc::new(RefCell::new(Host {
+            gameboy: GameBoy::new(),
+            callbacks: Vec::new(),
+            overlay: Vec::new(),
+            frame: 0,
+        }));
+        let engine = make_engine(&host);
+        let ast = engine
+            .compile(source)
+            .map_err(|error| ScriptError::Compile(error.to_string()))?;
+        Ok(Script {
+            engine,
+            ast,
+            host,
+            started: false,
+        })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Script, ScriptError> {
+        Script::new(&std::fs::read_to_string(path)?)
+    }
+
+    /// Call after each frame: the first call runs the top level of the script, every call
+    /// runs the frame callbacks
+    ///
+    /// The overlay is cleared first, so it only shows what the script drew for this frame
+    pub fn frame(&mut self, gameboy: &mut GameBoy) -> Result<(), ScriptError> {
+        mem::swap(gameboy, &mut self.host.borrow_mut().gameboy);
+        let result = self.run();
+        mem::swap(gameboy, &mut self.host.borrow_mut().gameboy);
+        result.map_err(|error| ScriptError::Runtime(error.to_string()))
+    }
+
+    fn run(&mut self) -> Result<(), Box<EvalAltResult>> {
+        self.host.borrow_mut().overlay.clear();
+        if !self.started {
+            self.started = true;
+            self.engine.run_ast(&self.ast)?;
+        }
+        let callbacks = self.host.borrow().callbacks.clone();
+        for callback in callbacks {
+            let _ = callback.call::<rhai::Dynamic>(&self.engine, &self.ast, ())?;
+        }
+        self.host.borrow_mut().frame += 1;
+        Ok(())
+    }
+
+    /// The text the script drew during the last `frame`
+    pub fn overlay(&self) -> Vec<OverlayText> {
+        self.host.borrow().overlay.clone()
+    }
+
+    /// Draw the overlay text onto `frame`, in white with a dark shadow
+    pub fn draw_overlay(&self, frame: &mut Frame) {
+        for text in &self.host.borrow().overlay {
+            for (color, offset) in [(SHADOW_COLOR, 1), (TEXT_COLOR, 0)] {
+                draw_text(frame, text.x + offset, text.y + offset, &text.text, color);
+            }
+        }
+    }
+}
+
+fn make_engine(host: &Rc<RefCell<Host>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| tracing::info!("Script: {}", text));
+
+    let shared = host.clone();
+    engine.register_fn("read", move |address: i64| {
+        i64::from(shared.borrow().gameboy.memory().read_byte(address as u16))
+    });
+    let shared = host.clone();
+    engine.register_fn("read16", move |address: i64| {
+        let host = shared.borrow();
+        let memory = host.gameboy.memory();
+        let address = address as u16;
+        i64::from(u16::from_le_bytes([
+            memory.read_byte(address),
+            memory.read_byte(address.wrapping_add(1)),
+        ]))
+    });
+    let shared = host.clone();
+    engine.register_fn("write", move |address: i64, value: i64| {
+        shared
+            .borrow_mut()
+            .gameboy
+            .memory_mut()
+            .write_byte(address as u16, value as u8);
+    });
+    let shared = host.clone();
+    engine.register_fn(
+        "reg",
+        move |name: &str| -> Result<i64, Box<EvalAltResult>> {
+            let host = shared.borrow();
+            let registers = &host.gameboy.cpu().registers;
+            let (register, shift) = match name.to_ascii_lowercase().as_str() {
+                "a" => (Register16::AF, Some(8)),
+                "f" => (Register16::AF, Some(0)),
+                "b" => (Register16::BC, Some(8)),
+                "c" => (Register16::BC, Some(0)),
+                "d" => (Register16::DE, Some(8)),
+                "e" => (Register16::DE, Some(0)),
+                "h" => (Register16::HL, Some(8)),
+                "l" => (Register16::HL, Some(0)),
+                "af" => (Register16::AF, None),
+                "bc" => (Register16::BC, None),
+                "de" => (Register16::DE, None),
+                "hl" => (Register16::HL, None),
+                "sp" => (Register16::SP, None),
+                "pc" => (Register16::PC, None),
+                _ => return Err(error(format!("Unknown register {}", name))),
+            };
+            let value = registers.read_16(register);
+            Ok(i64::from(match shift {
+                Some(shift) => (value >> shift) & 0xFF,
+                None => value,
+            }))
+        },
+    );
+    let shared = host.clone();
+    engine.register_fn(
+        "press",
+        move |name: &str| -> Result<(), Box<EvalAltResult>> {
+            shared.borrow_mut().gameboy.press_button(button(name)?);
+            Ok(())
+        },
+    );
+    let shared = host.clone();
+    engine.register_fn(
+        "release",
+        move |name: &str| -> Result<(), Box<EvalAltResult>> {
+            shared.borrow_mut().gameboy.release_button(button(name)?);
+            Ok(())
+        },
+    );
+    let shared = host.clone();
+    engine.register_fn("on_frame", move |callback: FnPtr| {
+        shared.borrow_mut().callbacks.push(callback);
+    });
+    let shared = host.clone();
+    engine.register_fn("frame", move || shared.borrow().frame as i64);
+    let shared = host.clone();
+    engine.register_fn("draw_text", move |x: i64, y: i64, text: &str| {
+        shared.borrow_mut().overlay.push(OverlayText {
+            x: x as i32,
+            y: y as i32,
+            text: text.to_string(),
+        });
+    });
+    engine
+}
+
+fn button(name: &str) -> Result<Button, Box<EvalAltResult>> {
+    Button::from_name(name).ok_or_else(|| error(format!("Unknown button {}", name)))
+}
+
+fn error(message: String) -> Box<EvalAltResult> {
+    Box::new(EvalAltResult::ErrorRuntime(message.into(), Position::NONE))
+}
+
+/// Draw `text` with its top left corner at `x`, `y`, clipped to the screen
+fn draw_text(frame: &mut Frame, x: i32, y: i32, text: &str, color: [u8; 4]) {
+    for (index, character) in text.chars().enumerate() {
+        let left = x + index as i32 * font::ADVANCE;
+        for (row, bits) in font::glyph(character).iter().enumerate() {
+            for column in 0..font::WIDTH {
+                if bits & (1 << (font::WIDTH - 1 - column)) == 0 {
+                    continue;
+                }
+                let (x, y) = (left + column, y + row as i32);
+                if (0..SCREEN_WIDTH as i32).contains(&x) && (0..SCREEN_HEIGHT as i32).contains(&y) {
+                    frame.set_pixel(x as usize, y as usize, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::test_rom;
+
+    fn make_gameboy() -> GameBoy {
+        let mut gameboy = GameBoy::new();
+        // JR -2
+        gameboy.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        gameboy
+    }
+
+    #[test]
+    fn test_script_callbacks() {
+        let mut gameboy = make_gameboy();
+        let mut script = Script::new(
+            r#"
+            write(0xC000, reg("pc") & 0xFF);
+            on_frame(|| {
+                write(0xC001, frame());
+                if frame() == 1 { press("start") }
+                draw_text(0, 0, "HI");
+            });
+            "#,
+        )
+        .unwrap();
+
+        gameboy.run_until_vblank();
+        script.frame(&mut gameboy).unwrap();
+        gameboy.run_until_vblank();
+        script.frame(&mut gameboy).unwrap();
+
+        assert_eq!(gameboy.memory().read_byte(0xC000), 0x50);
+        assert_eq!(gameboy.memory().read_byte(0xC001), 1);
+        assert_eq!(gameboy.pressed_buttons(), 1 << 7);
+        assert_eq!(script.overlay().len(), 1);
+
+        let mut frame = gameboy.frame();
+        script.draw_overlay(&mut frame);
+        // the top left of the H and its shadow
+        assert_eq!(frame.pixel(0, 0), TEXT_COLOR);
+        assert_eq!(frame.pixel(1, 1), SHADOW_COLOR);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        assert!(matches!(
+            Script::new("on_frame(|| {"),
+            Err(ScriptError::Compile(_))
+        ));
+
+        let mut gameboy = make_gameboy();
+        let mut script = Script::new(r#"press("turbo")"#).unwrap();
+        assert!(matches!(
+            script.frame(&mut gameboy),
+            Err(ScriptError::Runtime(_))
+        ));
+        // the emulator is handed back after an error
+        assert_eq!(gameboy.cpu().registers.read_16(Register16::PC), 0x0100);
+    }
+}
@@ -0,0 +1,149 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Converts interleaved stereo samples from one sample rate to another by linear interpolation
+///
+/// The APU already takes its samples from the ~1 MiHz channel output at `SAMPLE_RATE`, this
+/// brings them to whatever the audio device runs at, usually 48 kHz
+pub struct Resampler {
+    from_rate: u32,
+    to_rate: u32,
+    position: u64, // how far the next output frame is past `previous`, `to_rate` is a whole input frame
+    previous: [i16; 2], // the last input frame, left and right
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        Self {
+            from_rate,
+            to_rate,
+            position: 0,
+            previous: [0; 2],
+        }
+    }
+
+    /// Resample `input`, interleaved left and right, appending the output frames to `output`
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let from = u64::from(self.from_rate);
+        let to = u64::from(self.to_rate);
+        for frame in input.chunks_exact(2) {
+            // emit every output frame that falls between the previous input frame and this one
+            while self.position < to {
+                for (&start, &end) in self.previous.iter().zip(frame) {
+                    let (start, end) = (i64::from(start), i64::from(end));
+                    let sample = start + (end - start) * self.position as i64 / to as i64;
+                    output.push(sample as i16);
+                }
+                self.position += from;
+            }
+            self.position -= to;
+            self.previous = [frame[0], frame[1]];
+        }
+    }
+}
+
+/// A bounded queue of interleaved stereo samples between the emulator and the audio device
+///
+/// Pushing more than `capacity` samples drops the oldest ones so the latency never grows past
+/// it. When the device asks for more than is queued the last frame is held, which avoids the
+/// click of dropping to silence, and the underrun is counted
+pub struct AudioRing {
+    samples: VecDeque<i16>,
+    capacity: usize,
+    last: [i16; 2],
+    underruns: u64,
+}
+
+impl AudioRing {
+    /// A queue of at most `capacity` samples, left and right samples counted separately
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(2) & !1,
+            last: [0; 2],
+            underruns: 0,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[i16]) {
+        self.samples.extend(samples);
+        let excess = self.samples.len().saturating_sub(self.capacity);
+        self.samples.drain(..excess);
+    }
+
+    /// Fill `output` with interleaved stereo samples, returns false on an underrun
+    pub fn fill(&mut self, output: &mut [i16]) -> bool {
+        let complete = self.samples.len() >= output.len();
+        for (index, sample) in output.iter_mut().enumerate() {
+            *sample = match self.samples.pop_front() {
+                Some(sample) => {
+                    self.last[index % 2] = sample;
+                    sample
+                }
+                None => self.last[index % 2],
+            };
+        }
+        if !complete {
+            self.underruns += 1;
+        }
+        complete
+    }
+
+    /// The number of samples queued
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// How often the device asked for more samples than were queued
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resampler_rate() {
+        let mut resampler = Resampler::new(44100, 48000);
+        let mut output = Vec::new();
+        resampler.process(&[1000; 44100 * 2], &mut output);
+        assert_eq!(output.len(), 48000 * 2);
+    }
+
+    #[test]
+    fn test_resampler_interpolates() {
+        let mut resampler = Resampler::new(1, 2);
+        let mut output = Vec::new();
+        resampler.process(&[100, -100, 200, -200], &mut output);
+        // starts from silence, halfway points fall between the input frames
+        assert_eq!(output, [0, 0, 50, -50, 100, -100, 150, -150]);
+    }
+
+    #[test]
+    fn test_ring_bounds_latency() {
+        let mut ring = AudioRing::new(4);
+        ring.push(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(ring.len(), 4);
+
+        let mut output = [0; 4];
+        assert!(ring.fill(&mut output));
+        assert_eq!(output, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_ring_underrun_holds_last_frame() {
+        let mut ring = AudioRing::new(8);
+        ring.push(&[1, 2]);
+
+        let mut output = [0; 6];
+        assert!(!ring.fill(&mut output));
+        assert_eq!(output, [1, 2, 1, 2, 1, 2]);
+        assert_eq!(ring.underruns(), 1);
+    }
+}
@@ -92,6 +92,15 @@ pub fn half_carry_u16_add(left: u16, right: u16) -> u16 {
     }
 }
 
+/// 64 bit FNV-1a, stable across platforms and Rust versions
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
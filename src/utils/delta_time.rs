@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use super::errors::DeltaTimeError;
+use super::{errors::DeltaTimeError, Speed};
 
 pub struct DeltaTime {
     last_time: Option<std::time::Instant>,
@@ -42,6 +42,19 @@ impl DeltaTime {
             }
         }
     }
+
+    /// wait until a frame of `frame_duration` has passed at `speed`, unlimited doesn't wait
+    pub fn wait_frame(&self, frame_duration: Duration, speed: Speed) {
+        if let Some(duration) = speed.frame_duration(frame_duration) {
+            self.wait(duration);
+        }
+    }
+}
+
+impl Default for DeltaTime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -81,4 +94,17 @@ mod tests {
         let diff = dt.diff().unwrap();
         assert!(diff >= duration);
     }
+
+    #[test]
+    fn test_wait_frame() {
+        let mut dt = DeltaTime::new();
+        dt.update();
+        dt.wait_frame(Duration::from_millis(100), Speed::Double);
+        let diff = dt.diff().unwrap();
+        assert!(diff >= Duration::from_millis(50));
+
+        dt.update();
+        dt.wait_frame(Duration::from_secs(10), Speed::Unlimited);
+        assert!(dt.diff().unwrap() < Duration::from_secs(10));
+    }
 }
@@ -1,5 +1,197 @@
+use alloc::string::String;
+
 #[derive(Debug, thiserror::Error)]
 pub enum DeltaTimeError {
     #[error("DeltaTime has no time to compare against")]
     NoStartTime,
 }
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EmuError {
+    #[error("Illegal opcode {opcode:#04X} at {address:#06X}")]
+    IllegalOpcode { opcode: u8, address: u16 },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CartridgeError {
+    #[error("ROM is too small to contain a cartridge header ({0} bytes)")]
+    RomTooSmall(usize),
+    #[error("The Nintendo logo in the header is wrong, the boot ROM would refuse this ROM")]
+    InvalidLogo,
+    #[error(
+        "Header checksum is {actual:#04X}, expected {expected:#04X}, the ROM is likely corrupt"
+    )]
+    HeaderChecksumMismatch { expected: u8, actual: u8 },
+    #[error(
+        "ROM is {actual} bytes but the header declares {expected}, the ROM is likely truncated"
+    )]
+    RomSizeMismatch { expected: usize, actual: usize },
+    #[error("Unsupported cartridge type: {0:#04X}")]
+    UnsupportedCartridgeType(u8),
+    #[error("Invalid RTC state: expected {expected} bytes, got {actual}")]
+    InvalidRtcState { expected: usize, actual: usize },
+    #[error("Invalid save file: expected {expected} bytes of RAM, got {actual}")]
+    InvalidSaveSize { expected: usize, actual: usize },
+    #[cfg(feature = "std")]
+    #[error("There is no .gb or .gbc file in the archive")]
+    NoRomInArchive,
+    #[cfg(feature = "std")]
+    #[error("Invalid zip archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BootRomError {
+    #[error("Boot ROM must be {expected} bytes, got {actual}")]
+    InvalidSize { expected: usize, actual: usize },
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SaveStateError {
+    #[error("Not a save state")]
+    InvalidMagic,
+    #[error("Unsupported save state version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Save state ended unexpectedly")]
+    UnexpectedEnd,
+    #[error("Save state is for {expected:?}, but {actual:?} is loaded")]
+    CartridgeMismatch { expected: String, actual: String },
+    #[error("Invalid save state: {0}")]
+    InvalidData(&'static str),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum ScreenshotError {
+    #[error("PNG encoding failed: {0}")]
+    Encoding(#[from] png::EncodingError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MovieError {
+    #[error("Not a movie")]
+    InvalidMagic,
+    #[error("Unsupported movie version: {0}")]
+    UnsupportedVersion(u32),
+    #[error("Movie ended unexpectedly")]
+    UnexpectedEnd,
+    #[error("Invalid movie anchor: {0}")]
+    Anchor(#[from] SaveStateError),
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    #[error("Script does not compile: {0}")]
+    Compile(String),
+    #[error("Script failed: {0}")]
+    Runtime(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum CoreDumpError {
+    #[error("{0}")]
+    Png(#[from] ScreenshotError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum RecordingError {
+    #[error("GIF encoding failed: {0}")]
+    Gif(#[from] gif::EncodingError),
+    #[error("{0}")]
+    Png(#[from] ScreenshotError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheatError {
+    #[error("Not a Game Genie or GameShark code: {0}")]
+    InvalidCode(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpressionError {
+    #[error("Unexpected {found:?} at {position}")]
+    Unexpected { found: String, position: usize },
+    #[error("The expression ends too early")]
+    UnexpectedEnd,
+    #[error("Invalid number {0:?}")]
+    InvalidNumber(String),
+    #[error("Unknown register {0:?}")]
+    UnknownRegister(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SymbolError {
+    #[error("Invalid symbol file, line {line}: {text:?}")]
+    InvalidLine { line: usize, text: String },
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaletteError {
+    #[error("Invalid palette file, line {line}: {text:?}")]
+    InvalidColor { line: usize, text: String },
+    #[error("A palette needs 4 colors, the file has {0}")]
+    ColorCount(usize),
+    #[cfg(feature = "std")]
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum LibraryError {
+    #[error("Invalid game config, line {line}: {reason}")]
+    InvalidConfig { line: usize, reason: String },
+    #[error("{0}")]
+    Cartridge(#[from] CartridgeError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum LinkError {
+    #[error("The other side is not a compatible emulator, it sent {0:02X?}")]
+    Handshake(Vec<u8>),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum NetplayError {
+    #[error("The other side is not a compatible emulator, it sent {0:02X?}")]
+    Handshake(Vec<u8>),
+    #[error("The other side runs a different ROM")]
+    RomMismatch,
+    #[error("{0}")]
+    Cartridge(#[from] CartridgeError),
+    #[error("The other side stopped answering")]
+    Disconnected,
+    #[error("The emulators went out of sync at frame {0}")]
+    Desync(u32),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
@@ -1,5 +1,21 @@
+mod audio;
 mod bytes;
+#[cfg(feature = "std")]
 mod delta_time;
 mod errors;
+mod speed;
 
-pub use bytes::{combine, get_bit_u16, get_hi, get_lo, set_bit_u16, set_hi, set_lo, split};
+pub use audio::{AudioRing, Resampler};
+pub use bytes::{combine, fnv1a, get_bit_u16, get_hi, get_lo, set_bit_u16, set_hi, set_lo, split};
+#[cfg(feature = "std")]
+pub use delta_time::DeltaTime;
+pub use errors::{
+    BootRomError, CartridgeError, CheatError, EmuError, ExpressionError, MovieError, PaletteError,
+    SaveStateError, SymbolError,
+};
+#[cfg(feature = "std")]
+pub use errors::{
+    CoreDumpError, LibraryError, LinkError, NetplayError, RecordingError, ScreenshotError,
+    ScriptError,
+};
+pub use speed::Speed;
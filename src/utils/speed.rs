@@ -0,0 +1,68 @@
+use core::time::Duration;
+
+/// How fast the emulator runs compared to a real Game Boy
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Speed {
+    /// As fast as the host allows, without audio
+    Unlimited,
+    #[default]
+    Normal,
+    Double,
+    Multiplier(f64),
+}
+
+impl Speed {
+    /// The factor to real time, `None` when unlimited
+    pub fn multiplier(&self) -> Option<f64> {
+        match self {
+            Speed::Unlimited => None,
+            Speed::Normal => Some(1.0),
+            Speed::Double => Some(2.0),
+            Speed::Multiplier(multiplier) if *multiplier > 0.0 => Some(*multiplier),
+            Speed::Multiplier(_) => None,
+        }
+    }
+
+    /// How long a frame of `base` duration should take at this speed, `None` when unlimited
+    pub fn frame_duration(&self, base: Duration) -> Option<Duration> {
+        self.multiplier().map(|multiplier| base.div_f64(multiplier))
+    }
+
+    /// The rate to sample audio at in emulated time so that it plays back at `rate` in real time
+    ///
+    /// Unlimited produces no audio, it would pile up faster than it can be played
+    pub fn audio_sample_rate(&self, rate: u32) -> u32 {
+        self.multiplier()
+            .map_or(0, |multiplier| (rate as f64 / multiplier + 0.5) as u32) // rounded, `round` needs std
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_duration() {
+        let base = Duration::from_millis(16);
+
+        assert_eq!(Speed::Normal.frame_duration(base), Some(base));
+        assert_eq!(
+            Speed::Double.frame_duration(base),
+            Some(Duration::from_millis(8))
+        );
+        assert_eq!(
+            Speed::Multiplier(0.5).frame_duration(base),
+            Some(Duration::from_millis(32))
+        );
+        assert_eq!(Speed::Unlimited.frame_duration(base), None);
+        assert_eq!(Speed::Multiplier(0.0).frame_duration(base), None);
+    }
+
+    #[test]
+    fn test_audio_sample_rate() {
+        assert_eq!(Speed::Normal.audio_sample_rate(44100), 44100);
+        assert_eq!(Speed::Double.audio_sample_rate(44100), 22050);
+        assert_eq!(Speed::Multiplier(4.0).audio_sample_rate(44100), 11025);
+        assert_eq!(Speed::Unlimited.audio_sample_rate(44100), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! WebAssembly bindings for running the emulator in a browser, behind the `wasm` feature.
+//!
+//! Build with `wasm-pack build --target web -- --features wasm`, `examples/web` shows how to
+//! drive it from JavaScript: load a ROM, call `run_frame` once per animation frame and draw
+//! `framebuffer` to a canvas.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    gameboy::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    Button, GameBoy,
+};
+
+#[wasm_bindgen]
+pub struct Emulator {
+    gameboy: GameBoy,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Emulator {
+        Emulator {
+            gameboy: GameBoy::new(),
+        }
+    }
+
+    /// Insert a cartridge from the contents of a ROM file, ROMs the boot ROM would reject are
+    /// refused
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), JsError> {
+        self.gameboy
+            .load_rom(rom)
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Run until the next frame is complete
+    pub fn run_frame(&mut self) {
+        self.gameboy.run_until_vblank();
+    }
+
+    /// The last frame as RGBA bytes, ready for an `ImageData`
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.gameboy.frame().into_bytes()
+    }
+
+    /// Audio produced since the last call, interleaved stereo at `sample_rate`
+    pub fn audio_samples(&mut self) -> Vec<i16> {
+        self.gameboy.drain_audio_samples()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        crate::gameboy::SAMPLE_RATE
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        SCREEN_WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        SCREEN_HEIGHT
+    }
+
+    /// Press a button by name: right, left, up, down, a, b, select or start
+    ///
+    /// Returns false for an unknown name
+    pub fn key_down(&mut self, button: &str) -> bool {
+        let Some(button) = Button::from_name(button) else {
+            return false;
+        };
+        self.gameboy.press_button(button);
+        true
+    }
+
+    /// Release a button by name, see `key_down`
+    pub fn key_up(&mut self, button: &str) -> bool {
+        let Some(button) = Button::from_name(button) else {
+            return false;
+        };
+        self.gameboy.release_button(button);
+        true
+    }
+}
+
+impl Default for Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::test_rom;
+
+    #[test]
+    fn test_emulator() {
+        let mut emulator = Emulator::new();
+        // JR -2
+        emulator.load_rom(test_rom(&[0x18, 0xFE])).unwrap();
+        emulator.run_frame();
+
+        assert_eq!(
+            emulator.framebuffer().len(),
+            emulator.width() * emulator.height() * 4
+        );
+        assert!(emulator.key_down("start"));
+        assert!(emulator.key_up("start"));
+        assert!(!emulator.key_down("turbo"));
+    }
+}
@@ -0,0 +1,88 @@
+//! The dmg-acid2 and cgb-acid2 PPU conformance tests, the rendered screen is compared against
+//! the reference image of each test.
+//!
+//! Both draw a face from the background, window and sprites, every PPU feature they test going
+//! wrong shows up as a broken feature. Point `ACID2_ROMS` at a directory with the ROMs and
+//! reference images from https://github.com/mattcurrie/dmg-acid2/releases and
+//! https://github.com/mattcurrie/cgb-acid2/releases (`dmg-acid2.gb`, `dmg-acid2.png`,
+//! `cgb-acid2.gbc` and `cgb-acid2.png`) to run these:
+//!
+//! `ACID2_ROMS=path/to/acid2 cargo test --release --test acid2`
+//!
+//! The reference images use the same colors as the emulator, the grayscale DMG palette and
+//! CGB colors scaled from 5 to 8 bits, so their frame hashes have to match exactly. On a
+//! mismatch the screen is written next to the reference as `<name>-actual.png`.
+//!
+//! Neither ROM has been run against the emulator yet, whether the faces come out right is
+//! still unknown.
+
+mod common;
+
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use gameboy_emulator::{
+    gameboy::{hash_frame, Frame},
+    GameBoy,
+};
+
+/// The face is drawn once after a few frames and stays up
+const FRAMES: u32 = 60;
+
+/// The reference image next to `rom`, as a frame
+fn read_reference(rom: &str) -> Option<Frame> {
+    let directory = PathBuf::from(std::env::var_os("ACID2_ROMS")?);
+    let path = directory.join(rom).with_extension("png");
+    let file = File::open(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().unwrap();
+    let mut bytes = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut bytes).unwrap();
+
+    let mut frame = Frame::blank(info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    for (index, pixel) in bytes[..info.buffer_size()].chunks(channels).enumerate() {
+        let color = match *pixel {
+            [gray] | [gray, _] => [gray, gray, gray, 0xFF],
+            [red, green, blue] | [red, green, blue, _] => [red, green, blue, 0xFF],
+            _ => unreachable!("{channels} channels"),
+        };
+        let width = info.width as usize;
+        frame.set_pixel(index % width, index / width, color);
+    }
+    Some(frame)
+}
+
+fn run_rom(name: &str) {
+    let Some(rom) = common::read_rom("ACID2_ROMS", name) else {
+        return;
+    };
+    let reference = read_reference(name).unwrap();
+
+    let mut gameboy = GameBoy::new();
+    gameboy.set_accuracy(common::accuracy());
+    gameboy.load_rom(rom).unwrap();
+    for _ in 0..FRAMES {
+        gameboy.run_until_vblank();
+    }
+
+    let frame = gameboy.frame();
+    if hash_frame(&frame) != hash_frame(&reference) {
+        let directory = PathBuf::from(std::env::var_os("ACID2_ROMS").unwrap());
+        let path = directory.join(format!("{}-actual.png", name.split('.').next().unwrap()));
+        frame
+            .write_png(BufWriter::new(File::create(&path).unwrap()))
+            .unwrap();
+        panic!(
+            "{name} does not match its reference image, see {}",
+            path.display()
+        );
+    }
+}
+
+rom_tests! {
+    run_rom:
+    dmg_acid2 => "dmg-acid2.gb",
+    cgb_acid2 => "cgb-acid2.gbc",
+}
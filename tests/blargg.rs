@@ -0,0 +1,99 @@
+//! Blargg's test ROMs, run headlessly with their serial output checked for "Passed".
+//!
+//! The ROMs are not distributed with the emulator, point `BLARGG_ROMS` at a checkout of
+//! https://github.com/retrio/gb-test-roms to run these, without it they are skipped:
+//!
+//! `BLARGG_ROMS=path/to/gb-test-roms cargo test --release --test blargg`
+//!
+//! No results have been recorded against the ROMs yet, a run may well turn up failures.
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use gameboy_emulator::{
+    gameboy::{AccuracyProfile, SerialTransport},
+    GameBoy,
+};
+
+/// Two minutes of emulated time, cpu_instrs as a whole needs about one
+const MAX_FRAMES: u32 = 60 * 120;
+
+/// Collects everything the test ROM prints over the serial port
+struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl SerialTransport for Capture {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.0.lock().unwrap().push(byte);
+        0xFF
+    }
+}
+
+fn run_rom(name: &str) {
+    run_rom_with(name, common::accuracy());
+}
+
+/// The OAM bug is only emulated by the Cycle-accurate profile, whatever `GB_ACCURACY` says
+fn run_oam_bug_rom(name: &str) {
+    run_rom_with(name, AccuracyProfile::CycleAccurate);
+}
+
+fn run_rom_with(name: &str, accuracy: AccuracyProfile) {
+    let Some(rom) = common::read_rom("BLARGG_ROMS", name) else {
+        return;
+    };
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let mut gameboy = GameBoy::new();
+    gameboy.set_accuracy(accuracy);
+    gameboy.load_rom(rom).unwrap();
+    gameboy.set_serial_transport(Box::new(Capture(output.clone())));
+
+    for frame in 0..MAX_FRAMES {
+        gameboy.run_until_vblank();
+        if frame % 60 != 0 {
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+        if text.contains("Passed") {
+            return;
+        }
+        if text.contains("Failed") {
+            panic!("{name} failed:\n{text}");
+        }
+    }
+
+    let text = String::from_utf8_lossy(&output.lock().unwrap()).into_owned();
+    panic!("{name} did not finish:\n{text}");
+}
+
+rom_tests! {
+    run_rom:
+    cpu_instrs_01_special => "cpu_instrs/individual/01-special.gb",
+    cpu_instrs_02_interrupts => "cpu_instrs/individual/02-interrupts.gb",
+    cpu_instrs_03_op_sp_hl => "cpu_instrs/individual/03-op sp,hl.gb",
+    cpu_instrs_04_op_r_imm => "cpu_instrs/individual/04-op r,imm.gb",
+    cpu_instrs_05_op_rp => "cpu_instrs/individual/05-op rp.gb",
+    cpu_instrs_06_ld_r_r => "cpu_instrs/individual/06-ld r,r.gb",
+    cpu_instrs_07_jr_jp_call_ret_rst => "cpu_instrs/individual/07-jr,jp,call,ret,rst.gb",
+    cpu_instrs_08_misc_instrs => "cpu_instrs/individual/08-misc instrs.gb",
+    cpu_instrs_09_op_r_r => "cpu_instrs/individual/09-op r,r.gb",
+    cpu_instrs_10_bit_ops => "cpu_instrs/individual/10-bit ops.gb",
+    cpu_instrs_11_op_a_hl => "cpu_instrs/individual/11-op a,(hl).gb",
+    instr_timing => "instr_timing/instr_timing.gb",
+    mem_timing_01_read_timing => "mem_timing/individual/01-read_timing.gb",
+    mem_timing_02_write_timing => "mem_timing/individual/02-write_timing.gb",
+    mem_timing_03_modify_timing => "mem_timing/individual/03-modify_timing.gb",
+}
+
+rom_tests! {
+    run_oam_bug_rom:
+    oam_bug_1_lcd_sync => "oam_bug/rom_singles/1-lcd_sync.gb",
+    oam_bug_2_causes => "oam_bug/rom_singles/2-causes.gb",
+    oam_bug_3_non_causes => "oam_bug/rom_singles/3-non_causes.gb",
+    oam_bug_4_scanline_timing => "oam_bug/rom_singles/4-scanline_timing.gb",
+    oam_bug_5_timing_bug => "oam_bug/rom_singles/5-timing_bug.gb",
+    oam_bug_6_timing_no_bug => "oam_bug/rom_singles/6-timing_no_bug.gb",
+    oam_bug_8_instr_effect => "oam_bug/rom_singles/8-instr_effect.gb",
+}
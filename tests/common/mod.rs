@@ -0,0 +1,43 @@
+//! Shared helpers for the test ROM harnesses.
+//!
+//! Test ROMs are not distributed with the emulator, each suite is found through an
+//! environment variable and skipped when it is not set.
+
+use std::path::PathBuf;
+
+use gameboy_emulator::gameboy::AccuracyProfile;
+
+/// Read `name` from the directory in `variable`, `None` (and a note) if the variable is not set
+pub fn read_rom(variable: &str, name: &str) -> Option<Vec<u8>> {
+    let Some(directory) = std::env::var_os(variable) else {
+        eprintln!("{variable} is not set, skipping {name}");
+        return None;
+    };
+
+    let path = PathBuf::from(directory).join(name);
+    let rom = std::fs::read(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+    Some(rom)
+}
+
+/// The accuracy profile in `GB_ACCURACY` (fast, balanced or cycle-accurate), Balanced if unset
+pub fn accuracy() -> AccuracyProfile {
+    match std::env::var("GB_ACCURACY").as_deref() {
+        Ok("fast") => AccuracyProfile::Fast,
+        Ok("cycle-accurate") => AccuracyProfile::CycleAccurate,
+        Ok("balanced") | Err(_) => AccuracyProfile::Balanced,
+        Ok(other) => panic!("unknown GB_ACCURACY {other}"),
+    }
+}
+
+/// Generate one `#[test]` per ROM, each calling `$run` with the ROM's path
+#[macro_export]
+macro_rules! rom_tests {
+    ($run:ident: $($test:ident => $rom:expr,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                $run($rom);
+            }
+        )*
+    };
+}
@@ -0,0 +1,118 @@
+//! Golden frame hashes, per-game regression tests: each ROM listed in `tests/golden.txt` is run
+//! headlessly for a number of frames and the hash of its last frame compared to the one stored.
+//!
+//! The ROMs are not distributed with the emulator, their paths are relative to `GOLDEN_ROMS`
+//! and without it the goldens are skipped:
+//!
+//! `GOLDEN_ROMS=path/to/roms cargo test --release --test golden`
+//!
+//! A change that is meant to alter the picture updates the stored hashes with `--bless`, a new
+//! ROM is added with `-` as its hash and blessed the same way. Arguments not starting with `--`
+//! only check the ROMs whose path contains them:
+//!
+//! `GOLDEN_ROMS=path/to/roms cargo test --release --test golden -- --bless tetris`
+
+use std::{env, fs, path::PathBuf, process::ExitCode};
+
+use gameboy_emulator::gameboy::run_headless;
+
+const GOLDENS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden.txt");
+
+/// A line of `tests/golden.txt`: `FRAMES HASH PATH`, the path last as it may contain spaces
+struct Golden {
+    frames: u32,
+    hash: Option<u64>, // `-` until blessed
+    path: String,
+}
+
+impl Golden {
+    /// `None` for comments and blank lines
+    fn parse(line: &str) -> Result<Option<Golden>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let mut fields = line.splitn(3, char::is_whitespace);
+        let (Some(frames), Some(hash), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(format!("expected FRAMES HASH PATH, got {line:?}"));
+        };
+        let frames = frames
+            .parse()
+            .map_err(|_| format!("invalid frame count {frames:?}"))?;
+        let hash = match hash {
+            "-" => None,
+            hash => {
+                Some(u64::from_str_radix(hash, 16).map_err(|_| format!("invalid hash {hash:?}"))?)
+            }
+        };
+        Ok(Some(Golden {
+            frames,
+            hash,
+            path: path.trim().to_string(),
+        }))
+    }
+
+    fn line(&self) -> String {
+        let hash = self
+            .hash
+            .map_or_else(|| "-".to_string(), |hash| format!("{hash:016x}"));
+        format!("{} {} {}", self.frames, hash, self.path)
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let bless = args.iter().any(|arg| arg == "--bless");
+    let filters: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+    let Some(directory) = env::var_os("GOLDEN_ROMS").map(PathBuf::from) else {
+        eprintln!("GOLDEN_ROMS is not set, skipping the golden frame hashes");
+        return ExitCode::SUCCESS;
+    };
+
+    let text = fs::read_to_string(GOLDENS).unwrap();
+    let mut lines = Vec::new();
+    let mut failures = 0;
+    for (number, line) in text.lines().enumerate() {
+        let golden = Golden::parse(line)
+            .unwrap_or_else(|error| panic!("tests/golden.txt:{}: {error}", number + 1));
+        let Some(mut golden) = golden.filter(|golden| {
+            filters.is_empty() || filters.iter().any(|filter| golden.path.contains(*filter))
+        }) else {
+            lines.push(line.to_string());
+            continue;
+        };
+
+        let path = directory.join(&golden.path);
+        let rom = fs::read(&path).unwrap_or_else(|error| panic!("{}: {error}", path.display()));
+        let hash = run_headless(rom, golden.frames).unwrap().frame_hash;
+        match golden.hash {
+            Some(expected) if expected == hash => println!("golden {} ... ok", golden.path),
+            _ if bless => {
+                println!("golden {} ... blessed {hash:016x}", golden.path);
+                golden.hash = Some(hash);
+            }
+            Some(expected) => {
+                println!(
+                    "golden {} ... FAILED, expected {expected:016x}, got {hash:016x}",
+                    golden.path
+                );
+                failures += 1;
+            }
+            None => {
+                println!("golden {} ... FAILED, not blessed yet", golden.path);
+                failures += 1;
+            }
+        }
+        lines.push(golden.line());
+    }
+
+    if bless {
+        fs::write(GOLDENS, lines.join("\n") + "\n").unwrap();
+    }
+    if failures > 0 {
+        println!("{failures} golden frame hashes differ, run with --bless if that is intended");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
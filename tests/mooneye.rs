@@ -0,0 +1,116 @@
+//! The mooneye-gb test suite, run headlessly until the ROM reports its result.
+//!
+//! Mooneye tests signal the end by executing `LD B, B`, having loaded the Fibonacci numbers
+//! 3, 5, 8, 13, 21, 34 into B, C, D, E, H, L on success and 0x42 into every register on failure.
+//! Point `MOONEYE_ROMS` at a build of https://github.com/Gekkio/mooneye-test-suite to run these:
+//!
+//! `MOONEYE_ROMS=path/to/mts cargo test --release --test mooneye`
+//!
+//! No results have been recorded against the ROMs yet, a run may well turn up failures.
+
+mod common;
+
+use gameboy_emulator::{
+    gameboy::{MemoryBus, Register16, T_CYCLES_PER_FRAME},
+    GameBoy,
+};
+
+/// The `LD B, B` opcode used as a breakpoint
+const LD_B_B: u8 = 0x40;
+
+/// Every test finishes well within 10 seconds of emulated time
+const MAX_CYCLES: u64 = T_CYCLES_PER_FRAME as u64 * 60 * 10;
+
+const PASS_SIGNATURE: [u16; 3] = [0x0305, 0x080D, 0x1522];
+const FAIL_SIGNATURE: [u16; 3] = [0x4242, 0x4242, 0x4242];
+
+fn run_rom(name: &str) {
+    let Some(rom) = common::read_rom("MOONEYE_ROMS", name) else {
+        return;
+    };
+
+    let mut gameboy = GameBoy::new();
+    gameboy.set_accuracy(common::accuracy());
+    gameboy.load_rom(rom).unwrap();
+
+    while gameboy.cycles() < MAX_CYCLES {
+        let pc = gameboy.cpu().registers.pc;
+        if gameboy.memory().peek_byte(pc) == LD_B_B {
+            let registers = &gameboy.cpu().registers;
+            let signature = [Register16::BC, Register16::DE, Register16::HL]
+                .map(|register| registers.read_16(register));
+            match signature {
+                PASS_SIGNATURE => return,
+                FAIL_SIGNATURE => panic!("{name} failed"),
+                // a LD B, B that is not the end of the test
+                _ => {}
+            }
+        }
+        gameboy.step();
+    }
+
+    panic!("{name} did not finish");
+}
+
+rom_tests! {
+    run_rom:
+    add_sp_e_timing => "acceptance/add_sp_e_timing.gb",
+    boot_regs_dmg_abc => "acceptance/boot_regs-dmgABC.gb",
+    call_timing => "acceptance/call_timing.gb",
+    call_cc_timing => "acceptance/call_cc_timing.gb",
+    di_timing => "acceptance/di_timing-GS.gb",
+    div_timing => "acceptance/div_timing.gb",
+    ei_sequence => "acceptance/ei_sequence.gb",
+    ei_timing => "acceptance/ei_timing.gb",
+    halt_ime0_ei => "acceptance/halt_ime0_ei.gb",
+    halt_ime1_timing => "acceptance/halt_ime1_timing.gb",
+    if_ie_registers => "acceptance/if_ie_registers.gb",
+    intr_timing => "acceptance/intr_timing.gb",
+    jp_timing => "acceptance/jp_timing.gb",
+    ld_hl_sp_e_timing => "acceptance/ld_hl_sp_e_timing.gb",
+    oam_dma_restart => "acceptance/oam_dma_restart.gb",
+    oam_dma_start => "acceptance/oam_dma_start.gb",
+    oam_dma_timing => "acceptance/oam_dma_timing.gb",
+    pop_timing => "acceptance/pop_timing.gb",
+    push_timing => "acceptance/push_timing.gb",
+    rapid_di_ei => "acceptance/rapid_di_ei.gb",
+    ret_timing => "acceptance/ret_timing.gb",
+    reti_intr_timing => "acceptance/reti_intr_timing.gb",
+    rst_timing => "acceptance/rst_timing.gb",
+    bits_mem_oam => "acceptance/bits/mem_oam.gb",
+    bits_reg_f => "acceptance/bits/reg_f.gb",
+    instr_daa => "acceptance/instr/daa.gb",
+    interrupts_ie_push => "acceptance/interrupts/ie_push.gb",
+    oam_dma_basic => "acceptance/oam_dma/basic.gb",
+    oam_dma_reg_read => "acceptance/oam_dma/reg_read.gb",
+    timer_div_write => "acceptance/timer/div_write.gb",
+    timer_rapid_toggle => "acceptance/timer/rapid_toggle.gb",
+    timer_tim00 => "acceptance/timer/tim00.gb",
+    timer_tim00_div_trigger => "acceptance/timer/tim00_div_trigger.gb",
+    timer_tim01 => "acceptance/timer/tim01.gb",
+    timer_tim01_div_trigger => "acceptance/timer/tim01_div_trigger.gb",
+    timer_tim10 => "acceptance/timer/tim10.gb",
+    timer_tim10_div_trigger => "acceptance/timer/tim10_div_trigger.gb",
+    timer_tim11 => "acceptance/timer/tim11.gb",
+    timer_tim11_div_trigger => "acceptance/timer/tim11_div_trigger.gb",
+    timer_tima_reload => "acceptance/timer/tima_reload.gb",
+    timer_tima_write_reloading => "acceptance/timer/tima_write_reloading.gb",
+    timer_tma_write_reloading => "acceptance/timer/tma_write_reloading.gb",
+    mbc1_bits_bank1 => "emulator-only/mbc1/bits_bank1.gb",
+    mbc1_bits_bank2 => "emulator-only/mbc1/bits_bank2.gb",
+    mbc1_bits_mode => "emulator-only/mbc1/bits_mode.gb",
+    mbc1_bits_ramg => "emulator-only/mbc1/bits_ramg.gb",
+    mbc1_ram_64kb => "emulator-only/mbc1/ram_64kb.gb",
+    mbc1_ram_256kb => "emulator-only/mbc1/ram_256kb.gb",
+    mbc1_rom_512kb => "emulator-only/mbc1/rom_512kb.gb",
+    mbc1_rom_1mb => "emulator-only/mbc1/rom_1Mb.gb",
+    mbc1_rom_2mb => "emulator-only/mbc1/rom_2Mb.gb",
+    mbc1_multicart_rom_8mb => "emulator-only/mbc1/multicart_rom_8Mb.gb",
+    mbc2_bits_ramg => "emulator-only/mbc2/bits_ramg.gb",
+    mbc2_bits_romb => "emulator-only/mbc2/bits_romb.gb",
+    mbc2_bits_unused => "emulator-only/mbc2/bits_unused.gb",
+    mbc2_ram => "emulator-only/mbc2/ram.gb",
+    mbc2_rom_512kb => "emulator-only/mbc2/rom_512kb.gb",
+    mbc2_rom_1mb => "emulator-only/mbc2/rom_1Mb.gb",
+    mbc2_rom_2mb => "emulator-only/mbc2/rom_2Mb.gb",
+}
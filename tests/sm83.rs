@@ -0,0 +1,221 @@
+//! The SM83 single step tests, thousands of randomized cases per opcode checking registers,
+//! memory, bus activity and cycle counts after executing one instruction.
+//!
+//! The tests run against a flat 64KB bus instead of the Game Boy memory map. Point `SM83_TESTS`
+//! at the `v1` directory of https://github.com/SingleStepTests/sm83 to run them:
+//!
+//! `SM83_TESTS=path/to/sm83/v1 cargo test --release --test sm83`
+//!
+//! No results have been recorded against the test data yet.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use gameboy_emulator::gameboy::{Cpu, MemoryBus, Register16};
+use serde_json::Value;
+
+/// Failures printed per opcode, the rest are only counted
+const REPORTED_FAILURES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Read(u16, u8),
+    Write(u16, u8),
+}
+
+/// 64KB of RAM that records every bus access
+struct TestBus {
+    bytes: Vec<u8>,
+    accesses: Vec<Access>,
+}
+
+impl MemoryBus for TestBus {
+    fn read_byte(&mut self, address: u16) -> u8 {
+        let value = self.peek_byte(address);
+        self.accesses.push(Access::Read(address, value));
+        value
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        self.poke_byte(address, value);
+        self.accesses.push(Access::Write(address, value));
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+        self.bytes[usize::from(address)]
+    }
+
+    fn poke_byte(&mut self, address: u16, value: u8) {
+        self.bytes[usize::from(address)] = value;
+    }
+}
+
+fn field(state: &Value, name: &str) -> u16 {
+    state[name]
+        .as_u64()
+        .unwrap_or_else(|| panic!("missing {name}")) as u16
+}
+
+fn pair(state: &Value, hi: &str, lo: &str) -> u16 {
+    (field(state, hi) << 8) | field(state, lo)
+}
+
+/// `[[address, value], ...]`
+fn ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|entry| {
+            (
+                entry[0].as_u64().unwrap() as u16,
+                entry[1].as_u64().unwrap() as u8,
+            )
+        })
+        .collect()
+}
+
+/// The bus accesses in `cycles`, idle cycles are skipped
+fn expected_accesses(test: &Value) -> Vec<Access> {
+    test["cycles"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|cycle| {
+            let address = cycle.get(0)?.as_u64()? as u16;
+            let value = cycle.get(1)?.as_u64()? as u8;
+            match cycle.get(2)?.as_str()? {
+                pins if pins.starts_with('r') => Some(Access::Read(address, value)),
+                pins if pins.contains('w') => Some(Access::Write(address, value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+const REGISTERS: [(&str, Register16, &str, &str); 4] = [
+    ("AF", Register16::AF, "a", "f"),
+    ("BC", Register16::BC, "b", "c"),
+    ("DE", Register16::DE, "d", "e"),
+    ("HL", Register16::HL, "h", "l"),
+];
+
+/// Run one test case, describing the first difference on failure
+fn run_test(test: &Value) -> Result<(), String> {
+    let initial = &test["initial"];
+    let expected = &test["final"];
+
+    let mut bus = TestBus {
+        bytes: vec![0; 0x10000],
+        accesses: Vec::new(),
+    };
+    for (address, value) in ram(initial) {
+        bus.poke_byte(address, value);
+    }
+
+    let mut cpu = Cpu::power_on();
+    for (_, register, hi, lo) in REGISTERS {
+        cpu.registers.write_16(register, pair(initial, hi, lo));
+    }
+    cpu.registers.write_16(Register16::SP, field(initial, "sp"));
+    cpu.registers.pc = field(initial, "pc");
+    cpu.set_ime(field(initial, "ime") != 0);
+
+    let cycles = panic::catch_unwind(AssertUnwindSafe(|| cpu.step_instruction(&mut bus).cycles))
+        .map_err(|_| "panicked".to_string())?;
+
+    for (name, register, hi, lo) in REGISTERS {
+        let (actual, wanted) = (cpu.registers.read_16(register), pair(expected, hi, lo));
+        if actual != wanted {
+            return Err(format!("{name} is {actual:04X}, expected {wanted:04X}"));
+        }
+    }
+    // EI sets IME after the next instruction, tests with an `ei` field count that as not set yet
+    let ime = match expected.get("ei") {
+        Some(_) => cpu.ime(),
+        None => cpu.ime() || cpu.ime_pending(),
+    };
+    let checks = [
+        (
+            "SP",
+            cpu.registers.read_16(Register16::SP),
+            field(expected, "sp"),
+        ),
+        ("PC", cpu.registers.pc, field(expected, "pc")),
+        ("IME", u16::from(ime), field(expected, "ime")),
+    ];
+    for (name, actual, wanted) in checks {
+        if actual != wanted {
+            return Err(format!("{name} is {actual:04X}, expected {wanted:04X}"));
+        }
+    }
+
+    for (address, wanted) in ram(expected) {
+        let actual = bus.peek_byte(address);
+        if actual != wanted {
+            return Err(format!(
+                "[{address:04X}] is {actual:02X}, expected {wanted:02X}"
+            ));
+        }
+    }
+
+    let accesses = bus.accesses;
+    let wanted = expected_accesses(test);
+    if accesses != wanted {
+        return Err(format!("bus activity {accesses:?}, expected {wanted:?}"));
+    }
+
+    let wanted_cycles = test["cycles"].as_array().map_or(0, Vec::len);
+    if usize::from(cycles) != wanted_cycles {
+        return Err(format!("took {cycles} cycles, expected {wanted_cycles}"));
+    }
+    Ok(())
+}
+
+/// Run every case in one opcode's file, returns the number of failures
+fn run_file(path: &Path) -> usize {
+    let text = std::fs::read_to_string(path).unwrap();
+    let tests: Vec<Value> = serde_json::from_str(&text).unwrap();
+
+    let mut failures = 0;
+    for test in &tests {
+        if let Err(error) = run_test(test) {
+            if failures < REPORTED_FAILURES {
+                println!("{}: {error}", test["name"].as_str().unwrap_or("?"));
+            }
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        println!("{}: {failures} of {} failed", path.display(), tests.len());
+    }
+    failures
+}
+
+#[test]
+fn sm83_single_step() {
+    let Some(directory) = std::env::var_os("SM83_TESTS") else {
+        eprintln!("SM83_TESTS is not set, skipping the SM83 single step tests");
+        return;
+    };
+
+    let mut paths: Vec<_> = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        })
+        .collect();
+    paths.sort();
+
+    // a panicking instruction counts as a failure, keep the output to the failure summary
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let failed_files = paths.iter().filter(|path| run_file(path) > 0).count();
+    panic::set_hook(hook);
+
+    assert_eq!(failed_files, 0, "{failed_files} opcodes have failures");
+}